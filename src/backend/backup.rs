@@ -0,0 +1,228 @@
+//! Remote backups: `backup push` uploads the JSON export to a WebDAV or
+//! S3-compatible target named in config.toml, and `backup pull` fetches it
+//! back. Transfers run `curl`, which also signs the S3 requests.
+
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The copy `backup pull` fetches unless told otherwise
+pub const LATEST_NAME: &str = "cc-tracker-latest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupKind {
+    Webdav,
+    S3,
+}
+
+/// A place backups are kept, e.g. `[backup_targets.nas]`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackupTarget {
+    pub kind: BackupKind,
+    /// Folder the backups go in, e.g. `https://nas.local/dav/cc-tracker/` or
+    /// `https://s3.eu-west-1.amazonaws.com/my-bucket/cc-tracker/`
+    pub url: String,
+    /// WebDAV user name, or S3 access key ID
+    pub username: Option<String>,
+    /// Environment variable holding the WebDAV password or S3 secret key,
+    /// so the secret stays out of the config file
+    pub password_env: Option<String>,
+    /// S3 region (defaults to "us-east-1")
+    pub region: Option<String>,
+}
+
+impl BackupTarget {
+    /// The URL of a backup file in the target's folder
+    pub fn file_url(&self, name: &str) -> String {
+        format!("{}/{}", self.url.trim_end_matches('/'), name)
+    }
+
+    /// The curl request to PUT (with the body on stdin) or GET one file.
+    fn curl_request(&self, name: &str, upload: bool) -> Result<CurlRequest, String> {
+        let mut args: Vec<String> = vec!["--silent".into(), "--show-error".into(), "--fail".into()];
+        let mut credentials = None;
+        if let Some(username) = &self.username {
+            let password = match &self.password_env {
+                Some(var) => std::env::var(var).map_err(|_| format!("{} is not set", var))?,
+                None => String::new(),
+            };
+            credentials = Some(format!("{}:{}", username, password));
+        } else if self.kind == BackupKind::S3 {
+            return Err("An S3 backup target needs a username (the access key ID)".to_string());
+        }
+        if self.kind == BackupKind::S3 {
+            let region = self.region.as_deref().unwrap_or("us-east-1");
+            args.push("--aws-sigv4".into());
+            args.push(format!("aws:amz:{}:s3", region));
+        }
+        if upload {
+            // Read the whole body first so it is sent with a length: S3 does
+            // not take chunked uploads
+            args.extend(["--request".into(), "PUT".into(), "--data-binary".into(), "@-".into()]);
+        }
+        args.push(self.file_url(name));
+        Ok(CurlRequest { args, credentials })
+    }
+}
+
+/// A curl run: its arguments, and the `user:password` kept out of them,
+/// since any local user can read a process's arguments
+#[derive(Debug, PartialEq)]
+struct CurlRequest {
+    args: Vec<String>,
+    credentials: Option<String>,
+}
+
+/// A curl config file only we can read, holding the credentials; removed
+/// when dropped.
+struct CredentialsFile(std::path::PathBuf);
+
+impl CredentialsFile {
+    fn create(credentials: &str) -> Result<Self, String> {
+        let mut random = [0u8; 8];
+        getrandom::fill(&mut random).map_err(|e| format!("No randomness for a temporary file: {}", e))?;
+        let suffix: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+        let path = std::env::temp_dir().join(format!("cc-tracker-curl-{}", suffix));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let created = CredentialsFile(path);
+        file.write_all(config_line("user", credentials).as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", created.0.display(), e))?;
+        Ok(created)
+    }
+}
+
+impl Drop for CredentialsFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// A curl config file line setting `option` to `value`, quoted.
+fn config_line(option: &str, value: &str) -> String {
+    let mut quoted = String::new();
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    format!("{} = \"{}\"\n", option, quoted)
+}
+
+/// The name of the dated copy `backup push` keeps for `date`
+pub fn dated_name(date: &str) -> String {
+    format!("cc-tracker-{}.json", date)
+}
+
+fn curl(request: &CurlRequest, body: Option<&str>) -> Result<Vec<u8>, String> {
+    let credentials = request.credentials.as_deref().map(CredentialsFile::create).transpose()?;
+    let mut command = Command::new("curl");
+    if let Some(file) = &credentials {
+        command.arg("--config").arg(&file.0);
+    }
+    let mut child = command
+        .args(&request.args)
+        .stdin(if body.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let Some(body) = body {
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(body.as_bytes())
+            .map_err(|e| format!("Failed to send the backup to curl: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Transfer of {} failed: {}",
+            request.args.last().expect("curl_request ends with the URL"),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Uploads an export as the dated copy for `date` and as the latest one,
+/// returning the URLs written.
+pub fn push(target: &BackupTarget, json: &str, date: &str) -> Result<Vec<String>, String> {
+    let mut urls = Vec::new();
+    for name in [dated_name(date), LATEST_NAME.to_string()] {
+        curl(&target.curl_request(&name, true)?, Some(json))?;
+        urls.push(target.file_url(&name));
+    }
+    Ok(urls)
+}
+
+/// Downloads a backup, the latest one unless `name` picks a dated copy.
+pub fn pull(target: &BackupTarget, name: Option<&str>) -> Result<String, String> {
+    let body = curl(&target.curl_request(name.unwrap_or(LATEST_NAME), false)?, None)?;
+    String::from_utf8(body).map_err(|_| "The downloaded backup is not text".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curl_request_per_target_kind() {
+        let webdav: BackupTarget = toml::from_str(
+            "kind = \"webdav\"\nurl = \"https://nas.local/dav/cc/\"\nusername = \"me\"",
+        )
+        .unwrap();
+        assert_eq!(
+            webdav.curl_request(LATEST_NAME, false).unwrap(),
+            CurlRequest {
+                args: ["--silent", "--show-error", "--fail", "https://nas.local/dav/cc/cc-tracker-latest.json"]
+                    .map(String::from)
+                    .to_vec(),
+                credentials: Some("me:".to_string()),
+            }
+        );
+
+        let s3: BackupTarget = toml::from_str(
+            "kind = \"s3\"\nurl = \"https://s3.example.com/bucket\"\nusername = \"AKID\"\nregion = \"eu-west-1\"",
+        )
+        .unwrap();
+        let args = s3.curl_request(&dated_name("2026-10-15"), true).unwrap().args;
+        assert_eq!(args[3..5], ["--aws-sigv4", "aws:amz:eu-west-1:s3"]);
+        assert_eq!(args[5..9], ["--request", "PUT", "--data-binary", "@-"]);
+        assert_eq!(args[9], "https://s3.example.com/bucket/cc-tracker-2026-10-15.json");
+
+        let unset = BackupTarget {
+            password_env: Some("CC_TRACKER_TEST_UNSET_SECRET".into()),
+            ..s3.clone()
+        };
+        assert_eq!(unset.curl_request(LATEST_NAME, false).unwrap_err(), "CC_TRACKER_TEST_UNSET_SECRET is not set");
+        let anonymous = BackupTarget { username: None, ..s3 };
+        assert!(anonymous.curl_request(LATEST_NAME, false).is_err());
+    }
+
+    #[test]
+    fn test_credentials_stay_in_a_private_file() {
+        let file = CredentialsFile::create("me:pa\"ss\\word").unwrap();
+        assert_eq!(std::fs::read_to_string(&file.0).unwrap(), "user = \"me:pa\\\"ss\\\\word\"\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&file.0).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        let path = file.0.clone();
+        drop(file);
+        assert!(!path.exists());
+    }
+}
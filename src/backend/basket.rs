@@ -0,0 +1,399 @@
+//! Basket allocation: a list of planned purchases split across the cards so
+//! that the basket as a whole earns most. Unlike `best-card` one purchase at
+//! a time, a card's cap is shared out between the purchases that earn most
+//! on it, and purchases can be pooled on a card to meet its minimum spend.
+//!
+//! A basket file is JSON or CSV, e.g.
+//!
+//! ```json
+//! [
+//!   {"item": "Groceries", "category": "groceries", "amount": 180},
+//!   {"item": "Laptop", "category": "shopping", "amount": 1400, "payment_category": "online"}
+//! ]
+//! ```
+//!
+//! ```csv
+//! item,category,amount,payment_category
+//! Groceries,groceries,180,
+//! Laptop,shopping,1400,online
+//! ```
+
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error;
+use crate::models::{BasketAllocation, CapUnit, Card, PlannedSpend, RecommendOptions};
+use crate::rollover;
+use crate::store::Store;
+
+/// Assignments tried before settling for the best found so far
+const SEARCH_LIMIT: usize = 200_000;
+
+/// One planned purchase
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BasketItem {
+    /// What it is, for the output; defaults to the category
+    #[serde(default)]
+    pub item: Option<String>,
+    pub category: String,
+    pub amount: f64,
+    /// How it is paid; without it, whichever way earns most on each card
+    #[serde(default)]
+    pub payment_category: Option<String>,
+}
+
+impl FromStr for BasketItem {
+    type Err = String;
+
+    /// Reads `CATEGORY:AMOUNT` or `CATEGORY:AMOUNT:PAYMENT_CATEGORY`.
+    fn from_str(value: &str) -> Result<BasketItem, String> {
+        let mut parts = value.splitn(3, ':');
+        let (Some(category), Some(amount)) = (parts.next(), parts.next()) else {
+            return Err(format!("'{}' is not CATEGORY:AMOUNT[:PAYMENT_CATEGORY]", value));
+        };
+        let amount: f64 = amount.trim().parse().map_err(|_| format!("'{}' is not an amount", amount))?;
+        if amount <= 0.0 {
+            return Err(format!("the amount in '{}' must be positive", value));
+        }
+        Ok(BasketItem {
+            item: None,
+            category: category.trim().to_string(),
+            amount,
+            payment_category: parts.next().map(|p| p.trim().to_string()),
+        })
+    }
+}
+
+/// Reads a basket file, picking JSON or CSV by its extension.
+pub fn read(path: &Path) -> Result<Vec<BasketItem>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let items: Vec<BasketItem> = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("json") => serde_json::from_str(&text).map_err(|e| format!("Invalid basket file: {}", e))?,
+        Some("csv") => csv::Reader::from_reader(text.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Invalid basket file: {}", e))?,
+        _ => return Err(format!("Basket files must be .json or .csv: {}", path.display())),
+    };
+    let problems: Vec<String> = items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.amount <= 0.0)
+        .map(|(i, _)| format!("item {}: amount must be positive", i + 1))
+        .collect();
+    if problems.is_empty() {
+        Ok(items)
+    } else {
+        Err(format!("Invalid basket file:\n  {}", problems.join("\n  ")))
+    }
+}
+
+/// The basket split across the cards
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    /// One row per item, in the order given
+    pub rows: Vec<BasketAllocation>,
+    pub miles: f64,
+    /// What picking the best card for each item in turn would earn
+    pub one_at_a_time: f64,
+    /// Whether every split was weighed; false for baskets too big to,
+    /// leaving the best split found
+    pub exhaustive: bool,
+}
+
+/// Where a card's cycle stands before the basket
+struct CardRoom {
+    card: Card,
+    /// Spend (or miles, for a cap in miles) left under the cap
+    cap_left: Option<f64>,
+    /// Qualifying spend still needed for the minimum spend
+    min_left: f64,
+    min_spend_exclusions: Vec<String>,
+}
+
+/// What an item would earn on a card, the cycle aside
+#[derive(Clone)]
+struct Choice {
+    card: usize,
+    payment_category: String,
+    miles: f64,
+}
+
+/// The miles a split earns and each item's share: a card's cap in dollars
+/// must hold all of its items, one in miles cuts its items' miles (in the
+/// order given) once used up, and a card whose items fall short of its
+/// minimum spend earns nothing on them. None if a cap in dollars is exceeded.
+fn evaluate(rooms: &[CardRoom], items: &[BasketItem], choices: &[Option<&Choice>]) -> Option<(f64, Vec<f64>)> {
+    let mut per_item = vec![0.0; items.len()];
+    for (index, room) in rooms.iter().enumerate() {
+        let assigned: Vec<usize> = (0..items.len()).filter(|&i| choices[i].is_some_and(|c| c.card == index)).collect();
+        if assigned.is_empty() {
+            continue;
+        }
+        let spent: f64 = assigned.iter().map(|&i| items[i].amount).sum();
+        let qualifying: f64 = assigned
+            .iter()
+            .filter(|&&i| !room.min_spend_exclusions.contains(&items[i].category.to_lowercase()))
+            .map(|&i| items[i].amount)
+            .sum();
+        if room.card.cap_unit == CapUnit::Dollars && room.cap_left.is_some_and(|left| spent > left + 0.005) {
+            return None;
+        }
+        if qualifying + 0.005 < room.min_left {
+            continue;
+        }
+        let mut miles_left = room.cap_left.filter(|_| room.card.cap_unit == CapUnit::Miles).unwrap_or(f64::INFINITY);
+        for &i in &assigned {
+            let miles = choices[i].map_or(0.0, |c| c.miles).min(miles_left);
+            miles_left -= miles;
+            per_item[i] = miles;
+        }
+    }
+    Some((per_item.iter().sum(), per_item))
+}
+
+/// Splits `items` across the open cards to earn most as of `date`, with the
+/// spending already in each card's cycle counting towards its cap and
+/// minimum spend. Every split is weighed, pruning those that cannot beat
+/// the best so far, up to `SEARCH_LIMIT` of them.
+pub fn allocate<S: Store + ?Sized>(store: &S, items: &[BasketItem], date: &str) -> error::Result<Allocation> {
+    let cards: Vec<Card> = store.list_cards()?.into_iter().filter(|c| !c.is_closed_on(date)).collect();
+    let mut rooms = Vec::new();
+    for card in cards {
+        let summary = rollover::summary(store, &card, date)?;
+        rooms.push(CardRoom {
+            cap_left: summary.cap_left,
+            min_left: card.min_spend.map_or(0.0, |minimum| (minimum - summary.qualifying).max(0.0)),
+            min_spend_exclusions: card.excluded_from_min_spend(),
+            card,
+        });
+    }
+    let payment_categories_of =
+        |room: &CardRoom| serde_json::from_str::<Vec<String>>(&room.card.payment_categories).unwrap_or_default();
+
+    // What each item could earn on each card, paid whichever way earns most
+    let mut choices: Vec<Vec<Choice>> = Vec::new();
+    for item in items {
+        let mut item_choices: Vec<Choice> = Vec::new();
+        let mut ways: Vec<String> = match &item.payment_category {
+            Some(payment_category) => vec![payment_category.clone()],
+            None => rooms.iter().flat_map(payment_categories_of).collect(),
+        };
+        ways.sort_by_key(|w| w.to_lowercase());
+        ways.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        for way in &ways {
+            for r in store.best_card_for_category(&item.category, item.amount, way, date, &RecommendOptions::default())? {
+                let Some(card) = rooms.iter().position(|room| room.card.id == r.card_id) else { continue };
+                if r.miles_earned <= 0.0 {
+                    continue;
+                }
+                match item_choices.iter_mut().find(|c| c.card == card) {
+                    Some(choice) if choice.miles >= r.miles_earned => {}
+                    Some(choice) => *choice = Choice { card, payment_category: way.clone(), miles: r.miles_earned },
+                    None => item_choices.push(Choice { card, payment_category: way.clone(), miles: r.miles_earned }),
+                }
+            }
+        }
+        item_choices.sort_by(|a, b| b.miles.total_cmp(&a.miles));
+        choices.push(item_choices);
+    }
+
+    let one_at_a_time = one_at_a_time(store, items, &rooms, &choices, date)?;
+    let mut search = Search {
+        rooms: &rooms,
+        items,
+        choices: &choices,
+        best: (0.0, vec![None; items.len()]),
+        tried: 0,
+    };
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| items[b].amount.total_cmp(&items[a].amount));
+    // The most each item left after the i-th in `order` could still add
+    let mut still_possible = vec![0.0; items.len() + 1];
+    for position in (0..items.len()).rev() {
+        let best = choices[order[position]].first().map_or(0.0, |c| c.miles);
+        still_possible[position] = still_possible[position + 1] + best;
+    }
+    let mut picked = vec![None; items.len()];
+    search.visit(&order, &still_possible, 0, 0.0, &mut picked);
+    let exhaustive = search.tried < SEARCH_LIMIT;
+
+    let picked: Vec<Option<&Choice>> =
+        search.best.1.iter().enumerate().map(|(i, pick)| pick.map(|c| &choices[i][c])).collect();
+    let (miles, per_item) = evaluate(&rooms, items, &picked).unwrap_or((0.0, vec![0.0; items.len()]));
+    let rows = items
+        .iter()
+        .zip(&picked)
+        .zip(per_item)
+        .map(|((item, choice), miles)| BasketAllocation {
+            item: item.item.clone().unwrap_or_else(|| item.category.clone()),
+            category: item.category.clone(),
+            amount: item.amount,
+            card_name: choice.map(|c| rooms[c.card].card.name.clone()),
+            payment_category: choice.map(|c| c.payment_category.clone()),
+            miles,
+        })
+        .collect();
+    Ok(Allocation { rows, miles, one_at_a_time, exhaustive })
+}
+
+/// The miles from taking `best-card`'s pick for each item in turn, earlier
+/// picks counting towards later ones' caps and minimum spends.
+fn one_at_a_time<S: Store + ?Sized>(
+    store: &S,
+    items: &[BasketItem],
+    rooms: &[CardRoom],
+    choices: &[Vec<Choice>],
+    date: &str,
+) -> error::Result<f64> {
+    let mut planned: Vec<PlannedSpend> = Vec::new();
+    let mut total = 0.0;
+    for (item, item_choices) in items.iter().zip(choices) {
+        let options = RecommendOptions { planned: planned.clone(), ..RecommendOptions::default() };
+        let mut best: Option<(i64, f64)> = None;
+        for choice in item_choices {
+            let found = store
+                .best_card_for_category(&item.category, item.amount, &choice.payment_category, date, &options)?
+                .into_iter()
+                .find(|r| r.eligible && r.card_id == rooms[choice.card].card.id);
+            if let Some(found) = found
+                && best.is_none_or(|(_, miles)| found.miles_earned > miles)
+            {
+                best = Some((found.card_id, found.miles_earned));
+            }
+        }
+        if let Some((card_id, miles)) = best {
+            planned.push(PlannedSpend {
+                card_id,
+                date: date.to_string(),
+                amount: item.amount,
+                category: item.category.clone(),
+                miles,
+            });
+            total += miles;
+        }
+    }
+    Ok(total)
+}
+
+/// A depth-first walk over the splits, one item at a time
+struct Search<'a> {
+    rooms: &'a [CardRoom],
+    items: &'a [BasketItem],
+    choices: &'a [Vec<Choice>],
+    /// The most miles found and the choice (an index into the item's
+    /// choices, or None for no card) behind them
+    best: (f64, Vec<Option<usize>>),
+    tried: usize,
+}
+
+impl Search<'_> {
+    fn visit(
+        &mut self,
+        order: &[usize],
+        still_possible: &[f64],
+        position: usize,
+        so_far: f64,
+        picked: &mut Vec<Option<usize>>,
+    ) {
+        if self.tried >= SEARCH_LIMIT {
+            return;
+        }
+        self.tried += 1;
+        // Caps and minimum spends only ever take miles away, so this is as
+        // many as the rest of the split could earn
+        if so_far + still_possible[position] <= self.best.0 + 0.005 {
+            return;
+        }
+        let Some(&item) = order.get(position) else {
+            let split: Vec<Option<&Choice>> =
+                picked.iter().enumerate().map(|(i, pick)| pick.map(|c| &self.choices[i][c])).collect();
+            if let Some((miles, _)) = evaluate(self.rooms, self.items, &split)
+                && miles > self.best.0 + 0.005
+            {
+                self.best = (miles, picked.clone());
+            }
+            return;
+        };
+        for choice in (0..self.choices[item].len()).map(Some).chain([None]) {
+            picked[item] = choice;
+            let miles = choice.map_or(0.0, |c| self.choices[item][c].miles);
+            self.visit(order, still_possible, position + 1, so_far + miles, picked);
+        }
+        picked[item] = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    fn card(conn: &Connection, name: &str, categories: &[&str], miles_per_dollar: f64, cap: Option<f64>, min_spend: Option<f64>) -> i64 {
+        db::add_card(
+            conn,
+            &NewCard {
+                name: name.to_string(),
+                categories: categories.iter().map(|c| c.to_string()).collect(),
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit: cap,
+                min_spend,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn item(category: &str, amount: f64) -> BasketItem {
+        BasketItem { item: None, category: category.to_string(), amount, payment_category: None }
+    }
+
+    #[test]
+    fn test_items_parse_from_flags() {
+        assert_eq!("dining:45.5".parse(), Ok(item("dining", 45.5)));
+        let paid: BasketItem = "travel:300:online".parse().unwrap();
+        assert_eq!(paid.payment_category.as_deref(), Some("online"));
+        assert!("dining".parse::<BasketItem>().is_err());
+        assert!("dining:-5".parse::<BasketItem>().is_err());
+    }
+
+    #[test]
+    fn test_allocation_shares_a_cap_better_than_one_at_a_time() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        card(&conn, "Capped", &["dining", "shopping"], 4.0, Some(500.0), None);
+        card(&conn, "Dining", &["dining"], 3.0, None, None);
+        card(&conn, "Everyday", &["dining", "shopping"], 1.0, None, None);
+        // One at a time, dining takes the capped card first and the
+        // shopping no longer fits in what is left of its cap
+        let items = [item("dining", 200.0), item("shopping", 400.0)];
+
+        let allocation = allocate(&conn, &items, "2026-04-10").unwrap();
+        let cards: Vec<_> = allocation.rows.iter().map(|r| r.card_name.as_deref()).collect();
+        assert_eq!(cards, vec![Some("Dining"), Some("Capped")]);
+        assert_eq!((allocation.miles, allocation.one_at_a_time), (2200.0, 1200.0));
+        assert!(allocation.exhaustive);
+    }
+
+    #[test]
+    fn test_allocation_pools_purchases_to_meet_a_minimum_spend() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        card(&conn, "Bonus", &["dining", "groceries"], 4.0, None, Some(300.0));
+        card(&conn, "Everyday", &["dining", "groceries"], 1.5, None, None);
+        let items = [item("dining", 120.0), item("groceries", 200.0)];
+
+        let allocation = allocate(&conn, &items, "2026-04-10").unwrap();
+        assert!(allocation.rows.iter().all(|r| r.card_name.as_deref() == Some("Bonus")));
+        assert_eq!(allocation.miles, 1280.0);
+        // The minimum spend is not met before either purchase on its own
+        assert_eq!(allocation.one_at_a_time, 480.0);
+    }
+}
@@ -0,0 +1,270 @@
+//! Synthetic data and query timings: `generate` fills an empty database with
+//! made-up but realistic cards and spending, and `bench` times the queries
+//! behind best-card, summary and the reports against it, so a slowdown in
+//! the storage layer shows up as a number.
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::db::STATUS_POSTED;
+use crate::digest;
+use crate::engine::{add_days, calculate_miles, parse_date, round_cents};
+use crate::error::Result;
+use crate::models::{
+    BenchTiming, DEFAULT_CATEGORIES, DEFAULT_PAYMENT_CATEGORIES, NewCard, RecommendOptions, Spending,
+};
+use crate::overview;
+use crate::period::Period;
+use crate::pivot::{self, PivotBy};
+use crate::store::Store;
+
+/// Days of history the spending is spread over, ending today
+const HISTORY_DAYS: i32 = 730;
+/// Transactions written per database transaction
+const BATCH_SIZE: usize = 10_000;
+
+const PROGRAMS: &[&str] = &["KrisFlyer", "Asia Miles", "Avios"];
+
+/// SplitMix64: small and deterministic, so a seed always gives the same
+/// data and timings taken on different builds compare like with like.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.unit() * n as f64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        self.unit() < probability
+    }
+}
+
+/// Merchants descriptions are drawn from, per built-in category
+fn merchants(category: &str) -> &'static [&'static str] {
+    match category {
+        "dining" => &["STARBUCKS", "MCDONALD'S", "DIN TAI FUNG", "TOAST BOX", "SUSHI TEI"],
+        "travel" => &["SINGAPORE AIRLINES", "AGODA", "BOOKING.COM", "KLOOK", "EXPEDIA"],
+        "groceries" => &["FAIRPRICE", "COLD STORAGE", "SHENG SIONG", "GIANT", "DON DON DONKI"],
+        "transport" => &["GRAB", "GOJEK", "SMRT", "COMFORTDELGRO", "SHELL"],
+        "shopping" => &["AMAZON", "SHOPEE", "LAZADA", "UNIQLO", "IKEA"],
+        _ => &["NETFLIX", "SPOTIFY", "GOLDEN VILLAGE", "STEAM", "KLOOK"],
+    }
+}
+
+/// What `generate` wrote
+pub struct Generated {
+    pub cards: usize,
+    pub transactions: usize,
+    pub payments: usize,
+}
+
+/// Adds `cards` cards with assorted rates, caps and renewal days, then
+/// `transactions` posted transactions over the two years up to `today`,
+/// with miles at each card's rates, and a payment of each card's spend the
+/// month after it. Favourite cards get more of the spending, mostly in
+/// their bonus categories, as in a real wallet.
+pub fn generate<S: Store + ?Sized>(
+    store: &S,
+    cards: usize,
+    transactions: usize,
+    seed: u64,
+    today: &str,
+) -> Result<Generated> {
+    let mut rng = Rng(seed);
+    let mut wallet = Vec::new();
+    for number in 1..=cards {
+        let categories: Vec<String> = DEFAULT_CATEGORIES
+            .iter()
+            .filter(|_| rng.chance(0.5))
+            .map(|c| c.to_string())
+            .collect();
+        let card = NewCard {
+            name: format!("Synthetic Card {}", number),
+            categories: if categories.is_empty() { vec!["dining".to_string()] } else { categories },
+            payment_categories: DEFAULT_PAYMENT_CATEGORIES.iter().map(|c| c.to_string()).collect(),
+            miles_per_dollar: *rng.pick(&[0.4, 1.0, 1.2, 1.4, 2.0, 3.0, 4.0]),
+            block_size: *rng.pick(&[1.0, 1.0, 5.0]),
+            statement_renewal_date: 1 + rng.below(28) as i32,
+            max_reward_limit: rng.chance(0.4).then(|| *rng.pick(&[500.0, 1000.0, 2000.0])),
+            min_spend: rng.chance(0.3).then(|| *rng.pick(&[300.0, 500.0, 800.0])),
+            posting_delay: rng.below(2) as i32,
+            annual_fee: rng.chance(0.5).then(|| *rng.pick(&[0.0, 192.6, 261.6, 535.0])),
+            program: rng.chance(0.7).then(|| rng.pick(PROGRAMS).to_string()),
+            ..Default::default()
+        };
+        let id = store.add_card(&card)?;
+        wallet.push((id, card));
+    }
+
+    let mut monthly_spend: BTreeMap<(i64, String), f64> = BTreeMap::new();
+    let mut batch = Vec::with_capacity(BATCH_SIZE.min(transactions));
+    for _ in 0..transactions {
+        // Squaring skews the choice towards the first cards
+        let (card_id, card) = &wallet[(rng.unit().powi(2) * wallet.len() as f64) as usize];
+        let category = if rng.chance(0.8) {
+            rng.pick(&card.categories).clone()
+        } else {
+            rng.pick(DEFAULT_CATEGORIES).to_string()
+        };
+        // Mostly small purchases with the occasional large one
+        let amount = round_cents((3f64.ln() + rng.unit() * (800f64 / 3.0).ln()).exp());
+        let date = add_days(today, -(rng.below(HISTORY_DAYS as usize) as i32));
+        *monthly_spend.entry((*card_id, date[..7].to_string())).or_default() += amount;
+        batch.push(Spending {
+            id: 0,
+            card_id: *card_id,
+            amount,
+            miles_earned: calculate_miles(amount, card.block_size, card.miles_per_dollar),
+            description: Some(format!("{} #{}", rng.pick(merchants(&category)), 1 + rng.below(300))),
+            category,
+            date,
+            status: STATUS_POSTED.to_string(),
+            payment_category: Some(rng.pick(DEFAULT_PAYMENT_CATEGORIES).to_string()),
+            tag: None,
+            foreign: false,
+            original_amount: None,
+            original_currency: None,
+            recorded_on: None,
+            cashback: None,
+        });
+        if batch.len() == BATCH_SIZE {
+            store.restore_spending_batch(&batch)?;
+            batch.clear();
+        }
+    }
+    store.restore_spending_batch(&batch)?;
+
+    let mut payments = 0;
+    for ((card_id, month), spend) in monthly_spend {
+        let paid_on = add_days(&format!("{}-28", month), 7);
+        if paid_on.as_str() <= today {
+            store.add_payment(card_id, round_cents(spend), &paid_on)?;
+            payments += 1;
+        }
+    }
+    Ok(Generated {
+        cards,
+        transactions,
+        payments,
+    })
+}
+
+/// The queries `bench` times, in the order it runs them
+pub const QUERIES: &[&str] = &[
+    "best-card",
+    "best-card split",
+    "summary",
+    "balances",
+    "cash-flow",
+    "list-spending page",
+    "list-spending this-cycle",
+    "export-pivot",
+    "today",
+    "digest",
+];
+
+/// Runs `query` once to warm the caches, then `runs` more times, timing each.
+fn time(name: &str, runs: usize, query: &mut dyn FnMut() -> Result<()>) -> Result<BenchTiming> {
+    query()?;
+    let mut times = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let started = Instant::now();
+        query()?;
+        times.push(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    let round = |ms: f64| (ms * 1000.0).round() / 1000.0;
+    Ok(BenchTiming {
+        query: name.to_string(),
+        runs,
+        mean_ms: round(times.iter().sum::<f64>() / runs as f64),
+        min_ms: round(times.iter().copied().fold(f64::INFINITY, f64::min)),
+        max_ms: round(times.iter().copied().fold(0.0, f64::max)),
+    })
+}
+
+/// Times the queries behind best-card, summary, balances and the reports on
+/// `today`, each `runs` times; only those named in `only` unless it is empty.
+/// The digest ranks every card for each of the month's purchases, so it is
+/// by far the slowest on a large database.
+pub fn bench<S: Store + ?Sized>(store: &S, runs: usize, today: &str, only: &[String]) -> Result<Vec<BenchTiming>> {
+    let options = RecommendOptions::default();
+    let cards = store.list_cards()?;
+    let this_cycle = Period::ThisCycle.card_ranges(&cards, today);
+    let (year, month, _) = parse_date(today);
+    let (year, month) = if month == 1 { (year - 1, 12) } else { (year, month - 1) };
+    let mut queries: Vec<Box<dyn FnMut() -> Result<()> + '_>> = vec![
+        Box::new(|| store.best_card_for_category("dining", 85.0, "contactless", today, &options).map(drop)),
+        Box::new(|| store.suggest_split("shopping", 2500.0, "online", today, &options).map(drop)),
+        Box::new(|| store.outstanding_balances(today).map(drop)),
+        Box::new(|| store.card_balances(today).map(drop)),
+        Box::new(|| store.cash_flow(None, None).map(drop)),
+        Box::new(|| store.list_spending_page(None, None, None, 50).map(drop)),
+        Box::new(|| {
+            let mut count = 0;
+            store.for_each_spending(None, None, &mut |s| {
+                count += usize::from(this_cycle.contains(s.card_id, &s.date));
+                Ok(())
+            })
+        }),
+        Box::new(|| pivot::build(store, PivotBy::Category, None).map(drop)),
+        Box::new(|| overview::build(store, today).map(drop)),
+        Box::new(|| digest::build(store, year, month).map(drop)),
+    ];
+    let mut timings = Vec::new();
+    for (name, query) in QUERIES.iter().zip(&mut queries) {
+        if only.is_empty() || only.iter().any(|o| o == name) {
+            timings.push(time(name, runs, query.as_mut())?);
+        }
+    }
+    Ok(timings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_generated_data_is_reproducible_and_benchmarks() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let generated = generate(&conn, 4, 500, 7, "2026-10-15").unwrap();
+        assert_eq!((generated.cards, generated.transactions), (4, 500));
+        assert!(generated.payments > 0);
+
+        let spending = db::list_spending(&conn, None, None).unwrap();
+        assert_eq!(spending.len(), 500);
+        assert!(spending.iter().all(|s| ("2024-10-16".."2026-10-16").contains(&s.date.as_str())));
+
+        let again = Connection::open_in_memory().unwrap();
+        db::init_tables(&again).unwrap();
+        generate(&again, 4, 500, 7, "2026-10-15").unwrap();
+        let amounts = |spending: Vec<Spending>| spending.into_iter().map(|s| (s.date, s.amount)).collect::<Vec<_>>();
+        assert_eq!(amounts(db::list_spending(&again, None, None).unwrap()), amounts(spending));
+
+        let timings = bench(&conn, 1, "2026-10-15", &[]).unwrap();
+        let names: Vec<&str> = timings.iter().map(|t| t.query.as_str()).collect();
+        assert_eq!(names, QUERIES);
+        assert!(timings.iter().all(|t| t.runs == 1 && t.min_ms <= t.max_ms));
+        let only = bench(&conn, 1, "2026-10-15", &["summary".to_string()]).unwrap();
+        assert_eq!(only.len(), 1);
+    }
+}
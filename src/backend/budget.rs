@@ -0,0 +1,141 @@
+//! `budget`: each budgeted category's posted spend in a calendar month
+//! against its monthly budget, drawn as utilization bars, and the warning
+//! `add-spending` prints when a transaction takes a category over.
+
+use std::collections::BTreeMap;
+
+use crate::chart;
+use crate::db::STATUS_POSTED;
+use crate::digest::month_bounds;
+use crate::engine::{parse_date, round_cents};
+use crate::error::Result;
+use crate::models::CategoryBudget;
+use crate::store::Store;
+
+/// Cells a full (100%) utilization bar takes
+const BAR_WIDTH: usize = 20;
+
+/// Posted spend per category (keyed in lower case) from `first` to `last`.
+fn spent_by_category<S: Store + ?Sized>(store: &S, first: &str, last: &str) -> Result<BTreeMap<String, f64>> {
+    let mut spent: BTreeMap<String, f64> = BTreeMap::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status == STATUS_POSTED && s.date.as_str() >= first && s.date.as_str() <= last {
+            *spent.entry(s.category.to_lowercase()).or_default() += s.amount;
+        }
+        Ok(())
+    })?;
+    Ok(spent)
+}
+
+/// Each budgeted category's spend in the month against its budget.
+pub fn report<S: Store + ?Sized>(store: &S, year: i32, month: i32) -> Result<Vec<CategoryBudget>> {
+    let (first, last) = month_bounds(year, month);
+    let spent = spent_by_category(store, &first, &last)?;
+    Ok(store
+        .list_categories()?
+        .into_iter()
+        .filter_map(|category| {
+            let budget = category.monthly_budget?;
+            let spent = round_cents(spent.get(&category.name.to_lowercase()).copied().unwrap_or_default());
+            Some(CategoryBudget {
+                category: category.name,
+                budget,
+                spent,
+                remaining: round_cents(budget - spent),
+                used_percent: if budget > 0.0 { (spent / budget * 1000.0).round() / 10.0 } else { 0.0 },
+            })
+        })
+        .collect())
+}
+
+/// The budgets as labelled bars, full at 100% used, each followed by the
+/// spend against the budget and what is left or how far over it went.
+pub fn render(rows: &[CategoryBudget]) -> String {
+    let bars: Vec<(String, f64, String)> = rows
+        .iter()
+        .map(|row| {
+            let left = if row.remaining < 0.0 {
+                format!("${:.2} over", -row.remaining)
+            } else {
+                format!("${:.2} left", row.remaining)
+            };
+            let shown = format!("${:.2} of ${:.2} ({:.0}%), {}", row.spent, row.budget, row.used_percent, left);
+            (row.category.clone(), row.used_percent, shown)
+        })
+        .collect();
+    chart::bars(&bars, 100.0, BAR_WIDTH)
+}
+
+/// A warning if the `amount` just recorded on `date` took `category` over
+/// its monthly budget, counting what else was posted there that month.
+pub fn overspend_warning<S: Store + ?Sized>(store: &S, category: &str, date: &str, amount: f64) -> Result<Option<String>> {
+    let Some(budget) = store
+        .list_categories()?
+        .into_iter()
+        .find(|c| c.name.eq_ignore_ascii_case(category))
+        .and_then(|c| c.monthly_budget)
+    else {
+        return Ok(None);
+    };
+    let (year, month, _) = parse_date(date);
+    let (first, last) = month_bounds(year, month);
+    let spent = spent_by_category(store, &first, &last)?.get(&category.to_lowercase()).copied().unwrap_or_default();
+    Ok((spent > budget && spent - amount <= budget).then(|| {
+        format!(
+            "Warning: '{}' is ${:.2} over its ${:.2} monthly budget, with ${:.2} spent in {:04}-{:02}",
+            category,
+            spent - budget,
+            budget,
+            spent,
+            year,
+            month
+        )
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_budget_report_and_warning_when_crossed() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Card A".to_string(),
+                categories: vec!["dining".to_string()],
+                payment_categories: vec!["contactless".to_string()],
+                miles_per_dollar: 1.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::set_category_budget(&conn, "dining", Some(200.0)).unwrap();
+        db::add_spending(&conn, card, 150.0, "Dining", "2026-04-03", None, false, None).unwrap();
+        // Last month's spend does not count
+        db::add_spending(&conn, card, 500.0, "dining", "2026-03-30", None, false, None).unwrap();
+
+        let rows = report(&conn, 2026, 4).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].spent, rows[0].remaining, rows[0].used_percent), (150.0, 50.0, 75.0));
+        assert_eq!(render(&rows), "dining  ███████████████       $150.00 of $200.00 (75%), $50.00 left\n");
+        assert_eq!(overspend_warning(&conn, "dining", "2026-04-03", 150.0).unwrap(), None);
+
+        db::add_spending(&conn, card, 80.0, "dining", "2026-04-20", None, false, None).unwrap();
+        let warning = overspend_warning(&conn, "dining", "2026-04-20", 80.0).unwrap().unwrap();
+        assert!(warning.contains("$30.00 over its $200.00 monthly budget"), "{}", warning);
+        // Only the transaction that crosses the budget warns
+        db::add_spending(&conn, card, 10.0, "dining", "2026-04-21", None, false, None).unwrap();
+        assert_eq!(overspend_warning(&conn, "dining", "2026-04-21", 10.0).unwrap(), None);
+
+        db::set_category_budget(&conn, "dining", None).unwrap();
+        assert!(report(&conn, 2026, 4).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,570 @@
+//! Card files: every card written out declaratively in JSON or YAML, rules,
+//! caps and fees included, so a new machine is set up with one `add-cards`.
+//!
+//! ```yaml
+//! cards:
+//!   - name: DBS Altitude
+//!     categories: [dining, travel]
+//!     miles_per_dollar: 3
+//!     block_size: 1
+//!     statement_renewal_date: 15
+//!     max_reward_limit: 1000
+//!     annual_fee: 192.6
+//!     partnerships:
+//!       - merchant: starbucks
+//!         bonus_miles_per_dollar: 2
+//! ```
+//!
+//! A single card can also be shared with others as a versioned JSON file
+//! (`export-card` / `import-card`), without spending or balances.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error;
+use crate::export::{self, CARD_FORMAT, CARD_VERSION};
+use crate::models::{CapUnit, Card, CardRates, NewCard, RewardType, DEFAULT_CATEGORIES, DEFAULT_PAYMENT_CATEGORIES};
+use crate::rules;
+use crate::store::Store;
+
+/// One card as written in a card file. Settings left out take add-card's
+/// defaults: every category and payment category, no cap, no fee.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CardDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub payment_categories: Vec<String>,
+    pub miles_per_dollar: f64,
+    #[serde(default)]
+    pub miles_per_dollar_foreign: Option<f64>,
+    pub block_size: f64,
+    pub statement_renewal_date: i32,
+    #[serde(default)]
+    pub max_reward_limit: Option<f64>,
+    #[serde(default)]
+    pub min_spend: Option<f64>,
+    #[serde(default)]
+    pub posting_delay: i32,
+    #[serde(default)]
+    pub bank_time_offset_minutes: i32,
+    #[serde(default)]
+    pub cutoff_hour: i32,
+    #[serde(default)]
+    pub annual_fee: Option<f64>,
+    /// Month (1-12) the annual fee is charged in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_posting_month: Option<i32>,
+    /// Personal, so left out of shared cards
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub starting_balance: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub balance_alert: Option<f64>,
+    /// What the card earns: miles (the default), points or cashback
+    #[serde(default)]
+    pub reward_type: RewardType,
+    /// Percent of each purchase paid back as cash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cashback_percent: Option<f64>,
+    /// Blocks are counted in a foreign purchase's own currency ("per US$1")
+    #[serde(default)]
+    pub foreign_blocks: bool,
+    /// Whether max_reward_limit caps spend (the default) or miles
+    #[serde(default)]
+    pub cap_unit: CapUnit,
+    /// Categories that do not count towards min_spend
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub min_spend_exclusions: Vec<String>,
+    /// Loyalty program the miles go to, shared by cards that pool miles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// Months the card's miles stay valid, instead of its program's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub miles_expiry_months: Option<i32>,
+    /// Foreign transaction fee in percent (e.g. 3.25)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fx_fee_percent: Option<f64>,
+    /// Categories the card earns nothing on
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_categories: Vec<String>,
+    /// Days after a statement closes that its payment is due
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment_due_days: Option<i32>,
+    #[serde(default)]
+    pub partnerships: Vec<PartnershipDefinition>,
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// A merchant partnership of a card in a card file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PartnershipDefinition {
+    /// Case-insensitive regex matched against merchant names
+    pub merchant: String,
+    pub bonus_miles_per_dollar: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CardFile {
+    cards: Vec<CardDefinition>,
+}
+
+impl CardDefinition {
+    /// The card to add or update to, with add-card's defaults filled in.
+    pub fn new_card(&self) -> NewCard {
+        let or_all = |values: &[String], all: &[&str]| {
+            if values.is_empty() {
+                all.iter().map(|s| s.to_string()).collect()
+            } else {
+                values.to_vec()
+            }
+        };
+        NewCard {
+            name: self.name.clone(),
+            categories: or_all(&self.categories, DEFAULT_CATEGORIES),
+            payment_categories: or_all(&self.payment_categories, DEFAULT_PAYMENT_CATEGORIES),
+            miles_per_dollar: self.miles_per_dollar,
+            miles_per_dollar_foreign: self.miles_per_dollar_foreign,
+            block_size: self.block_size,
+            statement_renewal_date: self.statement_renewal_date,
+            max_reward_limit: self.max_reward_limit,
+            min_spend: self.min_spend,
+            posting_delay: self.posting_delay,
+            bank_time_offset_minutes: self.bank_time_offset_minutes,
+            cutoff_hour: self.cutoff_hour,
+            annual_fee: self.annual_fee,
+            fee_posting_month: self.fee_posting_month,
+            starting_balance: self.starting_balance,
+            balance_alert: self.balance_alert,
+            reward_type: self.reward_type,
+            cashback_percent: self.cashback_percent,
+            foreign_blocks: self.foreign_blocks,
+            cap_unit: self.cap_unit,
+            min_spend_exclusions: self.min_spend_exclusions.clone(),
+            program: self.program.clone(),
+            miles_expiry_months: self.miles_expiry_months,
+            fx_fee_percent: self.fx_fee_percent,
+            excluded_categories: self.excluded_categories.clone(),
+            payment_due_days: self.payment_due_days,
+        }
+    }
+
+    fn rates(&self) -> CardRates {
+        CardRates {
+            miles_per_dollar: self.miles_per_dollar,
+            block_size: self.block_size,
+            max_reward_limit: self.max_reward_limit,
+            min_spend: self.min_spend,
+        }
+    }
+}
+
+/// Reads a card file, JSON or YAML by its extension.
+pub fn read(path: &Path) -> Result<Vec<CardDefinition>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("json") => parse(&text, false),
+        Some("yaml" | "yml") => parse(&text, true),
+        _ => Err(format!("Card files must be .json, .yaml or .yml: {}", path.display())),
+    }
+}
+
+/// Parses and validates a card file. Errors name the offending card, e.g.
+/// `cards[2].block_size: must be positive`, and list every problem found
+/// rather than only the first.
+pub fn parse(text: &str, yaml: bool) -> Result<Vec<CardDefinition>, String> {
+    let file: CardFile = if yaml {
+        serde_yaml::from_str(text).map_err(|e| format!("Invalid card file: {}", e))?
+    } else {
+        serde_json::from_str(text).map_err(|e| format!("Invalid card file: {}", e))?
+    };
+    let mut problems = Vec::new();
+    for (i, card) in file.cards.iter().enumerate() {
+        if file.cards[..i].iter().any(|other| other.name == card.name) {
+            problems.push(format!("cards[{}].name: \"{}\" is defined twice", i, card.name));
+        }
+        problems.extend(validate(card).into_iter().map(|problem| format!("cards[{}].{}", i, problem)));
+    }
+    if problems.is_empty() {
+        Ok(file.cards)
+    } else {
+        Err(format!("Invalid card file:\n  {}", problems.join("\n  ")))
+    }
+}
+
+/// Checks one card's values, returning problems as `field: reason`.
+fn validate(card: &CardDefinition) -> Vec<String> {
+    let mut problems = card.new_card().problems();
+    for (j, partnership) in card.partnerships.iter().enumerate() {
+        if let Err(e) = rules::merchant_pattern(&partnership.merchant) {
+            problems.push(format!("partnerships[{}].merchant: {}", j, e));
+        }
+    }
+    problems
+}
+
+/// What `apply` did with one card of the file
+#[derive(Debug, PartialEq)]
+pub enum CardSync {
+    Added(i64),
+    /// With the settings that changed
+    Updated(i64, Vec<&'static str>),
+    Unchanged(i64),
+    /// Several cards already have the name, so none was touched
+    Ambiguous,
+}
+
+/// Adds each card of a file, or brings the card of the same name up to
+/// date with it, so applying a file twice changes nothing the second time.
+/// Rate changes take effect on `today` (or the card's last rate change, if
+/// later) so earlier recommendations keep their rates. Cards not in the
+/// file are left alone.
+pub fn apply(
+    store: &(impl Store + ?Sized),
+    cards: &[CardDefinition],
+    today: &str,
+) -> error::Result<Vec<(String, CardSync)>> {
+    let existing = store.list_cards()?;
+    let mut results = Vec::new();
+    for definition in cards {
+        let named: Vec<&Card> = existing.iter().filter(|card| card.name == definition.name).collect();
+        let sync = match named.as_slice() {
+            [] => {
+                let id = store.add_card(&definition.new_card())?;
+                for partnership in &definition.partnerships {
+                    store.add_partnership(id, &partnership.merchant, partnership.bonus_miles_per_dollar)?;
+                }
+                CardSync::Added(id)
+            }
+            [card] => {
+                let changed = update(store, card, definition, today)?;
+                if changed.is_empty() {
+                    CardSync::Unchanged(card.id)
+                } else {
+                    CardSync::Updated(card.id, changed)
+                }
+            }
+            _ => CardSync::Ambiguous,
+        };
+        results.push((definition.name.clone(), sync));
+    }
+    Ok(results)
+}
+
+/// Brings one card up to date with its definition, returning the names of
+/// the settings that changed.
+fn update(
+    store: &(impl Store + ?Sized),
+    card: &Card,
+    definition: &CardDefinition,
+    today: &str,
+) -> error::Result<Vec<&'static str>> {
+    let wanted = definition.new_card();
+    let current = definition_of(card, Vec::new()).new_card();
+    let mut changed = Vec::new();
+
+    let rates = definition.rates();
+    if (rates.miles_per_dollar, rates.block_size, rates.max_reward_limit, rates.min_spend)
+        != (card.miles_per_dollar, card.block_size, card.max_reward_limit, card.min_spend)
+    {
+        let effective_from = match store.last_rate_change(card.id)? {
+            Some(last) if last.as_str() > today => last,
+            _ => today.to_string(),
+        };
+        store.change_card_rates(card.id, &effective_from, &rates)?;
+        changed.push("rates");
+    }
+
+    let details = [
+        ("categories", wanted.categories != current.categories),
+        ("payment_categories", wanted.payment_categories != current.payment_categories),
+        ("miles_per_dollar_foreign", wanted.miles_per_dollar_foreign != current.miles_per_dollar_foreign),
+        ("statement_renewal_date", wanted.statement_renewal_date != current.statement_renewal_date),
+        ("posting_delay", wanted.posting_delay != current.posting_delay),
+        ("bank_time_offset_minutes", wanted.bank_time_offset_minutes != current.bank_time_offset_minutes),
+        ("cutoff_hour", wanted.cutoff_hour != current.cutoff_hour),
+        ("annual_fee", wanted.annual_fee != current.annual_fee),
+        ("fee_posting_month", wanted.fee_posting_month != current.fee_posting_month),
+        ("starting_balance", wanted.starting_balance != current.starting_balance),
+        ("balance_alert", wanted.balance_alert != current.balance_alert),
+        ("reward_type", wanted.reward_type != current.reward_type),
+        ("cashback_percent", wanted.cashback_percent != current.cashback_percent),
+        ("foreign_blocks", wanted.foreign_blocks != current.foreign_blocks),
+        ("cap_unit", wanted.cap_unit != current.cap_unit),
+        ("min_spend_exclusions", wanted.min_spend_exclusions != current.min_spend_exclusions),
+        ("program", wanted.program != current.program),
+        ("miles_expiry_months", wanted.miles_expiry_months != current.miles_expiry_months),
+        ("fx_fee_percent", wanted.fx_fee_percent != current.fx_fee_percent),
+        ("excluded_categories", wanted.excluded_categories != current.excluded_categories),
+        ("payment_due_days", wanted.payment_due_days != current.payment_due_days),
+    ];
+    let detail_changes: Vec<&'static str> =
+        details.iter().filter(|(_, differs)| *differs).map(|(name, _)| *name).collect();
+    if !detail_changes.is_empty() {
+        store.update_card_details(card.id, &wanted)?;
+        changed.extend(detail_changes);
+    }
+
+    // The file's partnerships replace the card's
+    let partnerships = store.list_partnerships(Some(card.id))?;
+    let mut partnerships_changed = false;
+    for partnership in &partnerships {
+        let kept = definition.partnerships.iter().any(|p| {
+            p.merchant == partnership.merchant_pattern && p.bonus_miles_per_dollar == partnership.bonus_miles_per_dollar
+        });
+        if !kept {
+            store.remove_partnership(partnership.id)?;
+            partnerships_changed = true;
+        }
+    }
+    for wanted in &definition.partnerships {
+        let exists = partnerships.iter().any(|p| {
+            p.merchant_pattern == wanted.merchant && p.bonus_miles_per_dollar == wanted.bonus_miles_per_dollar
+        });
+        if !exists {
+            store.add_partnership(card.id, &wanted.merchant, wanted.bonus_miles_per_dollar)?;
+            partnerships_changed = true;
+        }
+    }
+    if partnerships_changed {
+        changed.push("partnerships");
+    }
+    Ok(changed)
+}
+
+/// A stored card written as a definition.
+pub fn definition_of(card: &Card, partnerships: Vec<PartnershipDefinition>) -> CardDefinition {
+    CardDefinition {
+        name: card.name.clone(),
+        categories: serde_json::from_str(&card.categories).unwrap_or_default(),
+        payment_categories: serde_json::from_str(&card.payment_categories).unwrap_or_default(),
+        miles_per_dollar: card.miles_per_dollar,
+        miles_per_dollar_foreign: card.miles_per_dollar_foreign,
+        block_size: card.block_size,
+        statement_renewal_date: card.statement_renewal_date,
+        max_reward_limit: card.max_reward_limit,
+        min_spend: card.min_spend,
+        posting_delay: card.posting_delay,
+        bank_time_offset_minutes: card.bank_time_offset_minutes,
+        cutoff_hour: card.cutoff_hour,
+        annual_fee: card.annual_fee,
+        fee_posting_month: card.fee_posting_month,
+        starting_balance: card.starting_balance,
+        balance_alert: card.balance_alert,
+        reward_type: card.reward_type,
+        cashback_percent: card.cashback_percent,
+        foreign_blocks: card.foreign_blocks,
+        cap_unit: card.cap_unit,
+        min_spend_exclusions: serde_json::from_str(&card.min_spend_exclusions).unwrap_or_default(),
+        program: card.program.clone(),
+        miles_expiry_months: card.miles_expiry_months,
+        fx_fee_percent: card.fx_fee_percent,
+        excluded_categories: serde_json::from_str(&card.excluded_categories).unwrap_or_default(),
+        payment_due_days: card.payment_due_days,
+        partnerships,
+    }
+}
+
+/// A card shared with others, as written by `export-card`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SharedCard {
+    pub format: String,
+    pub format_version: u32,
+    /// YYYY-MM-DD
+    pub exported_on: String,
+    pub card: CardDefinition,
+}
+
+/// A card's portable definition: its rules, caps, fees and partnerships.
+/// Spending and the owner's balances stay behind.
+pub fn export_card(store: &(impl Store + ?Sized), card_id: i64, today: &str) -> error::Result<Option<SharedCard>> {
+    let Some(card) = store.get_card(card_id)? else {
+        return Ok(None);
+    };
+    let partnerships = store
+        .list_partnerships(Some(card_id))?
+        .into_iter()
+        .map(|p| PartnershipDefinition {
+            merchant: p.merchant_pattern,
+            bonus_miles_per_dollar: p.bonus_miles_per_dollar,
+        })
+        .collect();
+    let mut definition = definition_of(&card, partnerships);
+    definition.starting_balance = 0.0;
+    definition.balance_alert = None;
+    Ok(Some(SharedCard {
+        format: CARD_FORMAT.to_string(),
+        format_version: CARD_VERSION,
+        exported_on: today.to_string(),
+        card: definition,
+    }))
+}
+
+/// Parses and validates a file written by `export-card`.
+pub fn parse_shared(text: &str) -> Result<CardDefinition, String> {
+    export::check_stamp(text, CARD_FORMAT, CARD_VERSION, "card file")?;
+    let shared: SharedCard = serde_json::from_str(text).map_err(|e| format!("Invalid card file: {}", e))?;
+    let card = shared.card;
+    let problems: Vec<String> = validate(&card).into_iter().map(|problem| format!("card.{}", problem)).collect();
+    if problems.is_empty() {
+        Ok(card)
+    } else {
+        Err(format!("Invalid card file:\n  {}", problems.join("\n  ")))
+    }
+}
+
+/// Adds a shared card, or updates the card of the same name to it while
+/// keeping that card's own balance settings.
+pub fn import_shared(
+    store: &(impl Store + ?Sized),
+    mut definition: CardDefinition,
+    today: &str,
+) -> error::Result<CardSync> {
+    if let Some(card) = store.list_cards()?.into_iter().find(|card| card.name == definition.name) {
+        definition.starting_balance = card.starting_balance;
+        definition.balance_alert = card.balance_alert;
+    }
+    let mut results = apply(store, &[definition], today)?;
+    Ok(results.remove(0).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn test_apply_twice_changes_nothing() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let cards = parse(
+            "cards:
+  - name: Altitude
+    categories: [dining]
+    miles_per_dollar: 3
+    block_size: 1
+    statement_renewal_date: 15
+    annual_fee: 192.6
+    partnerships:
+      - merchant: starbucks
+        bonus_miles_per_dollar: 2
+  - name: Everyday
+    miles_per_dollar: 1.2
+    block_size: 1
+    statement_renewal_date: 1
+",
+            true,
+        )
+        .unwrap();
+
+        let first = apply(&conn, &cards, "2026-02-19").unwrap();
+        assert_eq!(first[0], ("Altitude".to_string(), CardSync::Added(1)));
+        assert_eq!(first[1], ("Everyday".to_string(), CardSync::Added(2)));
+        let second = apply(&conn, &cards, "2026-02-19").unwrap();
+        assert_eq!(second[0].1, CardSync::Unchanged(1));
+        assert_eq!(second[1].1, CardSync::Unchanged(2));
+
+        let mut edited = cards.clone();
+        edited[0].miles_per_dollar = 4.0;
+        edited[0].annual_fee = None;
+        edited[0].partnerships.clear();
+        let third = apply(&conn, &edited, "2026-02-19").unwrap();
+        assert_eq!(third[0].1, CardSync::Updated(1, vec!["rates", "annual_fee", "partnerships"]));
+        let card = db::get_card(&conn, 1).unwrap().unwrap();
+        assert_eq!((card.miles_per_dollar, card.annual_fee), (4.0, None));
+        assert_eq!(db::last_rate_change(&conn, 1).unwrap().as_deref(), Some("2026-02-19"));
+        assert!(db::list_partnerships(&conn, Some(1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_reports_every_problem() {
+        let err = parse(
+            r#"{"cards": [
+                {"name": "A", "miles_per_dollar": 1, "block_size": 0, "statement_renewal_date": 32},
+                {"name": "A", "miles_per_dollar": 1, "block_size": 1, "statement_renewal_date": 1,
+                 "partnerships": [{"merchant": "(", "bonus_miles_per_dollar": 1}]}
+            ]}"#,
+            false,
+        )
+        .unwrap_err();
+        assert!(err.contains("cards[0].block_size: must be positive"), "{}", err);
+        assert!(err.contains("cards[0].statement_renewal_date: must be 1-31"), "{}", err);
+        assert!(err.contains("cards[1].name: \"A\" is defined twice"), "{}", err);
+        assert!(err.contains("cards[1].partnerships[0].merchant"), "{}", err);
+
+        let err = parse("cards:\n  - name: A\n    mile_per_dollar: 1\n", true).unwrap_err();
+        assert!(err.contains("unknown field `mile_per_dollar`"), "{}", err);
+    }
+
+    #[test]
+    fn test_shared_card_imports_without_spending_or_balances() {
+        let source = rusqlite::Connection::open_in_memory().unwrap();
+        db::init_tables(&source).unwrap();
+        let mut card = parse(
+            "cards:\n  - name: Altitude\n    miles_per_dollar: 3\n    block_size: 1\n    statement_renewal_date: 15\n",
+            true,
+        )
+        .unwrap()
+        .remove(0);
+        card.starting_balance = 250.0;
+        card.partnerships.push(PartnershipDefinition {
+            merchant: "starbucks".to_string(),
+            bonus_miles_per_dollar: 2.0,
+        });
+        apply(&source, &[card], "2026-02-19").unwrap();
+        db::add_spending(&source, 1, 42.0, "dining", "2026-02-19", None, false, None).unwrap();
+        let json = serde_json::to_string(&export_card(&source, 1, "2026-02-19").unwrap().unwrap()).unwrap();
+        assert!(!json.contains("starting_balance"), "{}", json);
+
+        let target = rusqlite::Connection::open_in_memory().unwrap();
+        db::init_tables(&target).unwrap();
+        let shared = parse_shared(&json).unwrap();
+        assert_eq!(import_shared(&target, shared.clone(), "2026-03-01").unwrap(), CardSync::Added(1));
+        assert_eq!(import_shared(&target, shared, "2026-03-01").unwrap(), CardSync::Unchanged(1));
+        assert_eq!(db::get_card(&target, 1).unwrap().unwrap().starting_balance, 0.0);
+        assert_eq!(db::list_partnerships(&target, Some(1)).unwrap()[0].merchant_pattern, "starbucks");
+        assert!(db::list_spending(&target, None, None).unwrap().is_empty());
+
+        let err = parse_shared(&json.replace("\"format_version\":2", "\"format_version\":3")).unwrap_err();
+        assert!(err.contains("newer than this cc-tracker supports"), "{}", err);
+    }
+
+    /// The published schema must describe exactly the fields `export-card` writes.
+    #[test]
+    fn test_schema_matches_shared_card_layout() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../schemas/cc-tracker-card.v2.schema.json")).unwrap();
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = parse(
+            r#"{"cards": [{"name": "A", "miles_per_dollar": 1, "block_size": 1, "statement_renewal_date": 1,
+                          "cashback_percent": 1, "min_spend_exclusions": ["bills"],
+                          "program": "KrisFlyer", "fee_posting_month": 3,
+                          "miles_expiry_months": 36, "fx_fee_percent": 3.25,
+                          "excluded_categories": ["insurance"], "payment_due_days": 25}]}"#,
+            false,
+        )
+        .unwrap();
+        apply(&conn, &card, "2026-02-19").unwrap();
+        let shared = serde_json::to_value(export_card(&conn, 1, "2026-02-19").unwrap().unwrap()).unwrap();
+
+        let keys = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&schema["properties"]), keys(&shared));
+        assert_eq!(schema["properties"]["format_version"]["const"], CARD_VERSION);
+        let mut written = keys(&shared["card"]);
+        written.extend(["balance_alert".to_string(), "starting_balance".to_string()]);
+        written.sort();
+        assert_eq!(keys(&schema["$defs"]["card"]["properties"]), written);
+    }
+}
@@ -0,0 +1,75 @@
+//! Terminal charts for `report --chart`: horizontal bars and sparklines
+//! drawn with Unicode block characters.
+
+/// Partial cells of a bar, from one eighth to seven eighths wide
+const EIGHTHS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+const FULL: char = '█';
+/// Sparkline levels, lowest first
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A bar `width` cells long at `max`, and in proportion below it, drawn to
+/// an eighth of a cell. Any value above 0 shows at least a sliver.
+pub fn bar(value: f64, max: f64, width: usize) -> String {
+    if value <= 0.0 || max <= 0.0 {
+        return String::new();
+    }
+    let eighths = ((value / max).min(1.0) * (width * 8) as f64).round().max(1.0) as usize;
+    let mut out = FULL.to_string().repeat(eighths / 8);
+    if let Some(partial) = (eighths % 8).checked_sub(1) {
+        out.push(EIGHTHS[partial]);
+    }
+    out
+}
+
+/// Labelled bars, one per line: each label padded to line the bars up,
+/// then the bar against `max` and the value as `shown`.
+pub fn bars(rows: &[(String, f64, String)], max: f64, width: usize) -> String {
+    let label_width = rows.iter().map(|(label, _, _)| label.chars().count()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(label, value, shown)| {
+            let bar = bar(*value, max, width);
+            format!("{:<lw$}  {:<bw$}  {}\n", label, bar, shown, lw = label_width, bw = width)
+        })
+        .collect()
+}
+
+/// One character per value, from ▁ for the least to █ for the most. All
+/// equal values draw at the lowest level.
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min { ((value - min) / (max - min) * 7.0).round() as usize } else { 0 };
+            LEVELS[level]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bar_draws_eighths() {
+        assert_eq!(bar(50.0, 100.0, 10), "█████");
+        assert_eq!(bar(55.0, 100.0, 10), "█████▌");
+        assert_eq!(bar(250.0, 100.0, 4), "████");
+        assert_eq!(bar(0.1, 100.0, 10), "▏");
+        assert_eq!(bar(0.0, 100.0, 10), "");
+    }
+
+    #[test]
+    fn test_bars_line_up() {
+        let rows = [("travel".to_string(), 300.0, "300".to_string()), ("dining".to_string(), 150.0, "150".to_string())];
+        assert_eq!(bars(&rows, 300.0, 4), "travel  ████  300\ndining  ██    150\n");
+    }
+
+    #[test]
+    fn test_sparkline_scales_between_least_and_most() {
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0]), "▁▅█");
+        assert_eq!(sparkline(&[20.0, 20.0]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
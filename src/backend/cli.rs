@@ -0,0 +1,4094 @@
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::io::{BufWriter, IsTerminal, Stdout, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use tabled::{Table, Tabled};
+
+use crate::basket::BasketItem;
+use crate::card_file::{self, CardSync};
+use crate::error::{self, CcTrackerError};
+use crate::merge::{MergeOptions, MergeStrategy};
+use crate::period::{CardRanges, Period};
+use crate::pivot::PivotBy;
+use crate::pool::BalancesBy;
+use crate::report::ReportFormat;
+use crate::rules::Rules;
+use crate::store::Store;
+use crate::template::Template;
+use crate::velocity::VelocityBy;
+use crate::{backup, basket, bench, budget, compare, config, dates, db, digest, due, encrypt, engine, export, fees, forecast, fx, import, ledger, overview, period, pivot, pool, report, rollover, rules, statement, suggest, trip, velocity, whatif};
+use crate::models::{
+    CapUnit, Card, CardComparison, CardRates, CycleOverride, ImportPreview, NewCard, PatternSyntax, Program, ProgramKind, RecommendOptions, RewardType, RuleMatch, SpendingRow, SpendingEdit, SplitSuggestion, StatsBy,
+    CHARGE_KINDS,
+};
+
+/// Command-line interface. Running without a subcommand starts the API server.
+#[derive(Parser)]
+#[command(name = "cc-tracker", about = "Credit card miles tracker")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Log cycle windows and rule evaluations (-v), plus every SQL
+    /// statement (-vv). RUST_LOG overrides this.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Write logs to this file (appending) instead of stderr
+    #[arg(long, global = true)]
+    pub log_file: Option<PathBuf>,
+    /// Open the database read-only: any change is refused and the file is
+    /// never touched, so it is safe on a backup. Listings and other queries
+    /// always open it this way.
+    #[arg(long, global = true)]
+    pub read_only: bool,
+    /// SQLite database file to use, in place of `CC_TRACKER_DB`, the
+    /// `database_path` in config.toml and the data directory default
+    #[arg(long, global = true, value_name = "PATH")]
+    pub db: Option<PathBuf>,
+    /// Print listings as a table, or their rows as a JSON array for scripts
+    /// and jq; defaults to `output` in config.toml, or else a table
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+}
+
+/// How a listing is printed
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Print each row on one line from a template instead of a table,
+    /// e.g. '{card_name}: {miles_earned} mi ({reason})'
+    #[arg(long, conflicts_with_all = ["template_file", "output"])]
+    template: Option<String>,
+    /// Read the --template from a file
+    #[arg(long, conflicts_with = "output")]
+    template_file: Option<PathBuf>,
+}
+
+impl OutputArgs {
+    /// Whether rows are printed as a table in `format`, with notes and
+    /// totals around it; templates and JSON print the rows alone so they
+    /// can be parsed.
+    fn is_table(&self, format: OutputFormat) -> bool {
+        self.template.is_none() && self.template_file.is_none() && format == OutputFormat::Table
+    }
+}
+
+/// A new card's settings, for `add-card`
+#[derive(Args)]
+pub struct AddCardArgs {
+    #[arg(long)]
+    name: String,
+    /// Spending categories (defaults to all, or `categories` in config.toml)
+    #[arg(long, value_delimiter = ',')]
+    categories: Vec<String>,
+    /// Payment categories (defaults to all, or `payment_categories` in config.toml)
+    #[arg(long, value_delimiter = ',')]
+    payment_categories: Vec<String>,
+    #[arg(long)]
+    miles_per_dollar: f64,
+    #[arg(long)]
+    miles_per_dollar_foreign: Option<f64>,
+    #[arg(long)]
+    block_size: f64,
+    /// Day of month the statement cycle renews
+    #[arg(long)]
+    renewal_date: i32,
+    #[arg(long)]
+    max_reward_limit: Option<f64>,
+    /// Whether --max-reward-limit caps the cycle's spend or the miles it earns
+    #[arg(long, value_enum, default_value_t = CapUnit::Dollars)]
+    cap_unit: CapUnit,
+    #[arg(long)]
+    min_spend: Option<f64>,
+    /// Spending categories that do not count towards --min-spend (e.g. bills)
+    #[arg(long, value_delimiter = ',')]
+    min_spend_exclusions: Vec<String>,
+    /// Statements to wait after the spend's statement before miles are credited
+    #[arg(long, default_value_t = 0)]
+    posting_delay: i32,
+    /// Minutes the bank's clock is ahead of yours (negative if behind)
+    #[arg(long, default_value_t = 0, allow_hyphen_values = true)]
+    bank_time_offset_minutes: i32,
+    /// Hour of the bank's day from which transactions count towards the next day (0 = midnight)
+    #[arg(long, default_value_t = 0)]
+    cutoff_hour: i32,
+    #[arg(long)]
+    annual_fee: Option<f64>,
+    /// Month (1-12) the annual fee is charged in
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=12))]
+    fee_posting_month: Option<i32>,
+    /// Balance owed before tracking started
+    #[arg(long, default_value_t = 0.0)]
+    starting_balance: f64,
+    /// Warn when the outstanding balance exceeds this amount
+    #[arg(long)]
+    balance_alert: Option<f64>,
+    /// What the card earns: miles, bank points (at --miles-per-dollar
+    /// points a block) or cashback (at --cashback-percent)
+    #[arg(long, value_enum, default_value_t = RewardType::Miles)]
+    reward_type: RewardType,
+    /// Percent of each purchase paid back as cash, for cashback cards
+    #[arg(long, required_if_eq("reward_type", "cashback"))]
+    cashback_percent: Option<f64>,
+    /// Count blocks in a foreign purchase's own currency ("per US$1")
+    /// instead of the home currency
+    #[arg(long)]
+    foreign_blocks: bool,
+    /// Loyalty program the miles go to (e.g. KrisFlyer); cards in the
+    /// same program pool their miles
+    #[arg(long)]
+    program: Option<String>,
+    /// Months the card's miles stay valid, instead of its program's
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    miles_expiry_months: Option<i32>,
+    /// Foreign transaction fee, in percent of a foreign purchase (e.g. 3.25)
+    #[arg(long)]
+    fx_fee: Option<f64>,
+    /// Spending categories the card earns nothing on (e.g. insurance,utilities)
+    #[arg(long, value_delimiter = ',')]
+    excluded_categories: Vec<String>,
+    /// Days after a statement closes that its payment is due, for `due`
+    #[arg(long, value_parser = clap::value_parser!(i32).range(0..=60))]
+    payment_due_days: Option<i32>,
+}
+
+impl AddCardArgs {
+    /// The card to add, with categories and payment categories defaulted
+    /// from config.toml or the database when none are given.
+    fn new_card<S: Store + ?Sized>(self, store: &S) -> error::Result<NewCard> {
+        let AddCardArgs {
+            name,
+            categories,
+            payment_categories,
+            miles_per_dollar,
+            miles_per_dollar_foreign,
+            block_size,
+            renewal_date,
+            max_reward_limit,
+            cap_unit,
+            min_spend,
+            min_spend_exclusions,
+            posting_delay,
+            bank_time_offset_minutes,
+            cutoff_hour,
+            annual_fee,
+            fee_posting_month,
+            starting_balance,
+            balance_alert,
+            reward_type,
+            cashback_percent,
+            foreign_blocks,
+            program,
+            miles_expiry_months,
+            fx_fee,
+            excluded_categories,
+            payment_due_days,
+        } = self;
+        let config = config::load().map_err(CcTrackerError::Invalid)?;
+        let categories = if categories.is_empty() {
+            config.default_categories(store)?
+        } else {
+            categories
+        };
+        let payment_categories = if payment_categories.is_empty() {
+            config.default_payment_categories()
+        } else {
+            payment_categories
+        };
+        Ok(NewCard {
+            name,
+            categories,
+            payment_categories,
+            miles_per_dollar,
+            miles_per_dollar_foreign,
+            block_size,
+            statement_renewal_date: renewal_date,
+            max_reward_limit,
+            min_spend,
+            posting_delay,
+            bank_time_offset_minutes,
+            cutoff_hour,
+            annual_fee,
+            fee_posting_month,
+            starting_balance,
+            balance_alert,
+            reward_type,
+            cashback_percent,
+            foreign_blocks,
+            cap_unit,
+            min_spend_exclusions,
+            program,
+            miles_expiry_months,
+            fx_fee_percent: fx_fee,
+            excluded_categories,
+            payment_due_days,
+        })
+    }
+}
+
+/// The settings `edit-card` changes; those not given are kept
+#[derive(Args)]
+pub struct EditCardArgs {
+    #[arg(long, visible_alias = "id")]
+    card: CardRef,
+    #[arg(long)]
+    name: Option<String>,
+    #[arg(long, value_delimiter = ',')]
+    categories: Option<Vec<String>>,
+    #[arg(long, value_delimiter = ',')]
+    payment_categories: Option<Vec<String>>,
+    /// Corrects the rate for all dates; use set-rates for a rate that
+    /// changes from a given date
+    #[arg(long)]
+    miles_per_dollar: Option<f64>,
+    #[arg(long)]
+    miles_per_dollar_foreign: Option<f64>,
+    #[arg(long)]
+    block_size: Option<f64>,
+    /// Day of month the statement cycle renews
+    #[arg(long)]
+    renewal_date: Option<i32>,
+    #[arg(long)]
+    max_reward_limit: Option<f64>,
+    #[arg(long, value_enum)]
+    cap_unit: Option<CapUnit>,
+    #[arg(long)]
+    min_spend: Option<f64>,
+    #[arg(long, value_delimiter = ',')]
+    min_spend_exclusions: Option<Vec<String>>,
+    #[arg(long)]
+    posting_delay: Option<i32>,
+    #[arg(long, allow_hyphen_values = true)]
+    bank_time_offset_minutes: Option<i32>,
+    #[arg(long)]
+    cutoff_hour: Option<i32>,
+    #[arg(long)]
+    annual_fee: Option<f64>,
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=12))]
+    fee_posting_month: Option<i32>,
+    #[arg(long)]
+    starting_balance: Option<f64>,
+    #[arg(long)]
+    balance_alert: Option<f64>,
+    #[arg(long, value_enum)]
+    reward_type: Option<RewardType>,
+    #[arg(long)]
+    cashback_percent: Option<f64>,
+    /// true or false
+    #[arg(long)]
+    foreign_blocks: Option<bool>,
+    #[arg(long)]
+    program: Option<String>,
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+    miles_expiry_months: Option<i32>,
+    #[arg(long)]
+    fx_fee: Option<f64>,
+    #[arg(long, value_delimiter = ',')]
+    excluded_categories: Option<Vec<String>>,
+    #[arg(long, value_parser = clap::value_parser!(i32).range(0..=60))]
+    payment_due_days: Option<i32>,
+}
+
+/// What `--output` prints a listing as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    Table,
+    /// Indented JSON
+    Json,
+    /// JSON on one line
+    JsonCompact,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the REST API server (default)
+    Serve,
+    /// Add a new card
+    AddCard(AddCardArgs),
+    /// Change a card's settings in place, keeping its spending; settings not
+    /// given are left as they are
+    EditCard(EditCardArgs),
+    /// Add the cards of a JSON or YAML card file, or update the cards of
+    /// the same names to match it; applying a file again changes nothing
+    AddCards {
+        /// Card file (.json, .yaml or .yml) with a `cards` list
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// List all cards
+    ListCards {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a card and its spending
+    RemoveCard {
+        #[arg(long, visible_alias = "id")]
+        card: CardRef,
+    },
+    /// Mark a card closed, keeping its history: it is no longer recommended
+    /// from the closing date, and its fee and minimum spend reminders stop
+    CloseCard {
+        #[arg(long, visible_alias = "id")]
+        card: CardRef,
+        /// Closing date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        on: Option<String>,
+    },
+    /// Find the best card for a purchase
+    BestCard {
+        #[arg(long, required_unless_present = "merchant_for")]
+        category: Option<String>,
+        #[arg(long, value_parser = parse_amount)]
+        amount: f64,
+        /// How the purchase is paid; defaults to what the rules file infers
+        /// from the merchant or category
+        #[arg(long)]
+        payment_category: Option<String>,
+        /// Merchant to look up: its category comes from the rules file or
+        /// past spending there, and cards' partnership bonuses there apply
+        #[arg(long = "for", value_name = "MERCHANT", conflicts_with = "merchant")]
+        merchant_for: Option<String>,
+        /// Reference date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        /// Answer as the tracker would have on this date (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input, conflicts_with = "date")]
+        as_of: Option<String>,
+        /// Count scheduled spending towards caps and min spend (projection)
+        #[arg(long)]
+        include_scheduled: bool,
+        /// Rank by the net rate after amortizing each card's annual fee
+        #[arg(long)]
+        fee_adjusted: bool,
+        /// Show every step of each card's decision: rates, block math, cycle
+        /// window and total, cap and minimum spend
+        #[arg(long, conflicts_with_all = ["template", "template_file"])]
+        explain: bool,
+        /// Rank by dollar value, miles at this many dollars each against
+        /// cashback cards' cash back (e.g. 0.02 for 2 cents a mile)
+        #[arg(long, value_name = "DOLLARS")]
+        mile_value: Option<f64>,
+        /// Dollars a point of a points card is worth when ranking by value,
+        /// defaults to point_value in config.toml, then to the mile value
+        #[arg(long, value_name = "DOLLARS")]
+        point_value: Option<f64>,
+        /// Charged in a foreign currency, so cards earn their foreign rate
+        #[arg(long)]
+        foreign: bool,
+        /// The purchase's amount in the foreign currency it is charged in
+        /// (--amount stays in the home currency); cards with foreign blocks
+        /// count their blocks in it. Implies --foreign.
+        #[arg(long, value_parser = parse_amount)]
+        foreign_amount: Option<f64>,
+        /// Merchant the purchase is at, applying cards' partnership bonuses there
+        #[arg(long)]
+        merchant: Option<String>,
+        /// Print only the name of the best card, for scripts; the exit code
+        /// says whether it is eligible (0), only ineligible cards match (2)
+        /// or none do (3)
+        #[arg(long, conflicts_with_all = ["explain", "template", "template_file"])]
+        quiet: bool,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show the best eligible card for every category at a glance, with
+    /// caps and minimum spends as they stand: a cheat sheet for the wallet
+    BestCardMatrix {
+        /// Purchase amount the cards are compared on
+        #[arg(long, value_parser = parse_amount, default_value_t = overview::TYPICAL_PURCHASE)]
+        amount: f64,
+        /// A column per payment category instead of the best way to pay
+        #[arg(long)]
+        by_payment: bool,
+        /// Reference date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Compare two or more cards side by side: rates, block sizes, caps,
+    /// minimum spends and fees, and with --category the miles each would
+    /// earn on the purchase
+    CompareCards {
+        /// Cards to compare, by ID or name (e.g. 1,3 or altitude,rewards)
+        #[arg(long, value_delimiter = ',', num_args = 1.., required = true)]
+        cards: Vec<CardRef>,
+        /// Category of a purchase to project each card's miles for
+        #[arg(long)]
+        category: Option<String>,
+        /// Amount of the purchase
+        #[arg(long, value_parser = parse_amount, requires = "category", default_value_t = overview::TYPICAL_PURCHASE)]
+        amount: f64,
+        /// How the purchase is paid; defaults to whichever way earns most
+        /// on each card
+        #[arg(long, requires = "category")]
+        payment_category: Option<String>,
+        /// Reference date (YYYY-MM-DD) for caps and minimum spends, defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Replay past spending against a card you don't have, taking the same
+    /// settings as add-card, to see the miles it would have earned. Nothing
+    /// is written.
+    WhatIf {
+        #[command(flatten)]
+        card: AddCardArgs,
+        /// Only replay spending in this period (see list-spending); the
+        /// cycles are the hypothetical card's
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Split a basket of planned purchases across the cards so the basket
+    /// earns most, weighing caps and minimum spends across all of it rather
+    /// than one purchase at a time
+    OptimizeBasket {
+        /// A purchase as CATEGORY:AMOUNT or CATEGORY:AMOUNT:PAYMENT_CATEGORY;
+        /// repeat for each
+        #[arg(long = "item", value_name = "CATEGORY:AMOUNT[:PAYMENT]", required_unless_present = "file")]
+        items: Vec<BasketItem>,
+        /// Basket file (.json or .csv) listing the purchases, after any --item
+        #[arg(long)]
+        file: Option<PathBuf>,
+        /// Date of the purchases (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Record a spending transaction
+    AddSpending {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Amount charged, in --currency if given
+        #[arg(long, value_parser = parse_amount)]
+        amount: f64,
+        /// Spending category; without it a rule matching --merchant picks it
+        #[arg(long, required_unless_present = "merchant")]
+        category: Option<String>,
+        /// Transaction date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        /// Local time of the transaction (HH:MM); shifts the date to the bank's calendar
+        #[arg(long, value_parser = dates::parse_time)]
+        time: Option<i32>,
+        /// Record planned future spending that has not been charged yet
+        #[arg(long)]
+        scheduled: bool,
+        /// Merchant, kept as the description; earns the card's partnership bonus there
+        #[arg(long)]
+        merchant: Option<String>,
+        /// Charged in a foreign currency; earns at the card's foreign rate
+        #[arg(long)]
+        foreign: bool,
+        /// Currency --amount is in (e.g. JPY), converted to the home currency
+        /// at --fx-rate or the rate stored with `fx-rates set`; implies --foreign
+        #[arg(long)]
+        currency: Option<String>,
+        /// Home currency per unit of --currency, for this transaction only
+        #[arg(long, requires = "currency")]
+        fx_rate: Option<f64>,
+    },
+    /// Mark a scheduled transaction as charged
+    PostScheduled {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Subscriptions and other charges a card takes every month: add,
+    /// list and remove them
+    Recurring {
+        #[command(subcommand)]
+        action: RecurringAction,
+    },
+    /// Record every recurring charge due by a date as spending, earning
+    /// miles as if added with add-spending
+    ApplyRecurring {
+        /// Record charges due up to this date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Move transactions recorded on the wrong card to another card,
+    /// recalculating their miles at its rates
+    MoveSpending {
+        /// IDs of the transactions to move, e.g. 12,13
+        #[arg(long, value_delimiter = ',', required_unless_present = "from_card")]
+        ids: Vec<i64>,
+        /// Move this card's transactions instead, narrowed by --category and --period
+        #[arg(long, conflicts_with = "ids")]
+        from_card: Option<CardRef>,
+        /// Only move transactions in this category
+        #[arg(long, requires = "from_card")]
+        category: Option<String>,
+        /// Only move transactions dated in this period (see list-spending)
+        #[arg(long, requires = "from_card")]
+        period: Option<Period>,
+        /// Card to move them to
+        #[arg(long)]
+        to_card: CardRef,
+    },
+    /// Correct a recorded transaction, recalculating its miles at the
+    /// rates of its (possibly new) card
+    EditSpending {
+        #[arg(long)]
+        id: i64,
+        /// Card the transaction belongs on
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[arg(long, value_parser = parse_amount)]
+        amount: Option<f64>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Transaction date (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        /// Whether it was charged in a foreign currency (true or false)
+        #[arg(long)]
+        foreign: Option<bool>,
+    },
+    /// List spending transactions
+    ListSpending {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Only show transactions known by this date (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        /// Only show transactions dated in this period: this-cycle,
+        /// last-cycle, this-month, last-month, ytd, a quarter (2026-Q1) or
+        /// FROM..TO dates. Cycles follow each card's own statement cycle.
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Import spending from a bank statement CSV using a named profile from the config file
+    ImportStatement {
+        /// Name of the import profile, e.g. "dbs" for [import_profiles.dbs],
+        /// or of a built-in preset: amex, chase, citi, dbs or uob
+        #[arg(long, required_unless_present = "mapping")]
+        profile: Option<String>,
+        /// TOML file holding the profile instead: its columns, date_format,
+        /// amount_sign and so on at the top level
+        #[arg(long, conflicts_with = "profile")]
+        mapping: Option<PathBuf>,
+        /// Statement CSV file
+        file: PathBuf,
+        /// Card to record against, overriding the profile's card_id
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Import without asking for confirmation after the preview
+        #[arg(long)]
+        yes: bool,
+        /// Also write the preview as JSON to this file
+        #[arg(long)]
+        preview_file: Option<PathBuf>,
+    },
+    /// Show which categorization rule matches each line of a sample file
+    TestRules {
+        /// File with one transaction description per line
+        samples: PathBuf,
+        /// Rules file to test, defaults to rules.txt in the config directory
+        #[arg(long)]
+        rules: Option<PathBuf>,
+    },
+    /// Merge cards and transactions from another database file (e.g. a backup) into this one
+    MergeDb {
+        /// Database file to merge from; it is opened read-only
+        file: PathBuf,
+        /// How to settle cards that differ between the databases
+        #[arg(long, value_enum, default_value_t = MergeStrategy::KeepLocal)]
+        cards: MergeStrategy,
+        /// How to settle transactions that differ between the databases
+        #[arg(long, value_enum, default_value_t = MergeStrategy::KeepLocal)]
+        spending: MergeStrategy,
+        /// Label for records kept by duplicate-and-tag
+        #[arg(long, default_value = "merged")]
+        tag: String,
+    },
+    /// Write the whole database as a versioned JSON export
+    Export {
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Keep every card but only the spending, payments and charges
+        /// dated in this period (see list-spending)
+        #[arg(long)]
+        period: Option<Period>,
+        /// Scrub merchant names and card numbers and scale every amount by
+        /// a random factor, for sharing in a bug report
+        #[arg(long)]
+        anonymize: bool,
+        /// Encrypt the export with a passphrase (from CC_TRACKER_PASSPHRASE,
+        /// or asked for); `restore` asks for it back
+        #[arg(long)]
+        encrypt: bool,
+    },
+    /// Write posted spend and miles per month as a wide CSV, with a pair
+    /// of columns per category or card, for spreadsheet pivots and charts
+    ExportPivot {
+        /// One pair of columns per category or per card
+        #[arg(long, value_enum, default_value = "category")]
+        by: PivotBy,
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Only count spending dated in this period (see list-spending)
+        #[arg(long)]
+        period: Option<Period>,
+    },
+    /// Write spending as CSV with a header row, for spreadsheets
+    ExportSpending {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Only export spending dated in this period, e.g. 2026-01-01..2026-03-31
+        /// (see list-spending)
+        #[arg(long)]
+        period: Option<Period>,
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Load a JSON export into this database, which must have no cards yet
+    Restore {
+        /// Export file written by `export`, encrypted or not
+        file: PathBuf,
+    },
+    /// Copy the export to a WebDAV or S3 backup target from the config
+    /// file, or restore from one
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Write one card's definition (rules, caps, fees, partnerships; no
+    /// spending or balances) as JSON to share with others
+    ExportCard {
+        #[arg(long, visible_alias = "id")]
+        card: CardRef,
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Add a card shared with `export-card`, or update the card of the same name to it
+    ImportCard {
+        /// Card file written by `export-card`
+        file: PathBuf,
+    },
+    /// List imported transactions waiting for a category
+    ReviewQueue {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Record a queued transaction as spending
+    ResolveReview {
+        #[arg(long)]
+        id: i64,
+        /// Category to record it under, defaults to the suggested category
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// Change a card's earn rates from a given date, keeping the old rates for earlier dates
+    SetRates {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// First date the new rates apply (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        effective_from: Option<String>,
+        #[arg(long)]
+        miles_per_dollar: Option<f64>,
+        #[arg(long)]
+        block_size: Option<f64>,
+        #[arg(long)]
+        max_reward_limit: Option<f64>,
+        #[arg(long)]
+        min_spend: Option<f64>,
+    },
+    /// Compare tracked miles for a statement cycle with what the bank reported
+    Reconcile {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Any date inside the cycle to reconcile (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        /// Miles the bank says were earned in the cycle
+        #[arg(long, value_parser = parse_amount)]
+        bank_miles: f64,
+    },
+    /// Close a statement cycle, freezing its transactions and totals
+    CloseCycle {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Last day of the cycle to close (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input)]
+        through: String,
+        /// Reopen the closed cycle containing --through instead
+        #[arg(long)]
+        reopen: bool,
+    },
+    /// List closed statement cycles
+    ClosedCycles {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Record a repayment towards a card
+    AddPayment {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        #[arg(long, value_parser = parse_amount)]
+        amount: f64,
+        /// Payment date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+    },
+    /// List card repayments
+    ListPayments {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Only show repayments made in this period (see list-spending)
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Record interest or a fee charged to a card (earns no miles)
+    AddCharge {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// interest, late_fee, fx_fee, annual_fee or other
+        #[arg(long)]
+        kind: String,
+        #[arg(long, value_parser = parse_amount)]
+        amount: f64,
+        /// Charge date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+    },
+    /// List interest and fee charges
+    ListCharges {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Only show charges made in this period (see list-spending)
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Give a card extra miles at matching merchants (e.g. a supermarket partner)
+    AddPartnership {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Merchant name or regular expression, matched case-insensitively
+        #[arg(long)]
+        merchant: String,
+        /// Extra miles per block on top of the card's rate
+        #[arg(long)]
+        bonus_miles_per_dollar: f64,
+    },
+    /// List merchant partnerships
+    ListPartnerships {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a merchant partnership
+    RemovePartnership {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Give a card a promotional rate on a category between two dates, e.g.
+    /// 5 miles per block on groceries for a quarter
+    AddPromotion {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        #[arg(long)]
+        category: String,
+        /// Miles per block while the promotion runs, in place of the card's rate
+        #[arg(long)]
+        miles_per_dollar: f64,
+        /// First day of the promotion (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input)]
+        start: String,
+        /// Last day of the promotion (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input)]
+        end: String,
+    },
+    /// List promotions, past and upcoming ones included
+    ListPromotions {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a promotion
+    RemovePromotion {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Categorize merchants matching a pattern when importing statements or
+    /// adding spending without --category, after the rules file's rules
+    AddRule {
+        /// Glob over the whole description (`*` any text, `?` one character),
+        /// matched case-insensitively, e.g. 'GRAB*'
+        pattern: String,
+        category: String,
+        /// Treat the pattern as a regular expression matching anywhere instead
+        #[arg(long)]
+        regex: bool,
+    },
+    /// List the rules added with add-rule, in the order they are tried
+    ListRules {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a rule added with add-rule
+    RemoveRule {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Record the dates of a statement cycle the bank moved, which then take
+    /// precedence over the card's renewal day
+    OverrideCycle {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Month the cycle is for (YYYY-MM)
+        #[arg(long)]
+        month: String,
+        /// First day of the cycle (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input, required_unless_present = "clear")]
+        start: Option<String>,
+        /// Last day of the cycle (YYYY-MM-DD)
+        #[arg(long, value_parser = dates::parse_input, required_unless_present = "clear")]
+        end: Option<String>,
+        /// Remove the month's override instead
+        #[arg(long, conflicts_with_all = ["start", "end"])]
+        clear: bool,
+    },
+    /// Set a card's starting balance and outstanding-balance warning threshold;
+    /// the one left out keeps its current value
+    SetBalance {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        #[arg(long, required_unless_present = "balance_alert")]
+        starting_balance: Option<f64>,
+        #[arg(long)]
+        balance_alert: Option<f64>,
+    },
+    /// Show the outstanding balance on every card
+    Summary {
+        /// Summary date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        /// Show what each card owed when this period began, what changed in
+        /// it and what it owed at its end (see list-spending)
+        #[arg(long, conflicts_with = "as_of")]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show what matters today: the best card per category, caps nearly used,
+    /// minimum spends to go and what falls due this week
+    Today,
+    /// Show spending vs repayments per month
+    CashFlow {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Only count what happened in this period (see list-spending);
+        /// this-cycle and last-cycle need --card
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show posted spend and miles totalled and averaged per card, category
+    /// or month
+    Stats {
+        /// One row per card, category or month
+        #[arg(long, value_enum, default_value = "card")]
+        by: StatsBy,
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Only count spending in this period (see list-spending);
+        /// this-cycle and last-cycle need --card
+        #[arg(long)]
+        period: Option<Period>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Recommend a card for each expense of an upcoming trip and project the
+    /// miles, after FX fees and with caps filling up as the trip goes
+    PlanTrip {
+        /// Trip file (.json, .yaml or .yml) listing the expenses
+        file: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show when a card's statement cycle ends, what resets then and how the
+    /// next cycle opens
+    PreviewCycle {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Preview the cycle containing this date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+    },
+    /// Rebuild a statement cycle: its transactions, spend and the miles
+    /// they earn after the reward cap and minimum spend
+    Statement {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        /// Month of the cycle (YYYY-MM), the one the renewal in that month
+        /// starts; defaults to the current cycle
+        #[arg(long)]
+        cycle: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show where a card's statement cycle stands: its dates after any
+    /// move, spend and miles so far, cap headroom and minimum spend
+    CycleSummary {
+        /// Card to summarize; every open card without it
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Summarize the cycle containing this date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show rolling 7- and 30-day spend per card or category, with trends
+    /// and whether each card's cycle is on pace for its minimum spend and cap
+    SpendVelocity {
+        /// One row per card or per category
+        #[arg(long, value_enum, default_value = "card")]
+        by: VelocityBy,
+        /// Report date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// List when each card's statements must be paid and what is still owed
+    /// on them, for cards with --payment-due-days
+    Due {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Date to list from (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Forecast whether each card will meet its minimum spend or run through
+    /// its cap by the end of its cycle, from its recent daily spend per category
+    Forecast {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        /// Days of history to average each category's daily spend over
+        #[arg(long, default_value_t = forecast::DEFAULT_LOOKBACK_DAYS, value_parser = clap::value_parser!(i32).range(1..))]
+        days: i32,
+        /// Forecast date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show credited and pending miles per card, or pooled per loyalty program
+    Balances {
+        /// Balance date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        /// One row per card, or per program with its cards' miles added up
+        #[arg(long, value_enum, default_value = "card")]
+        by: BalancesBy,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show each card's miles ledger balance (earned, redeemed, adjusted),
+    /// or one card's or program's entries with the running balance
+    Balance {
+        #[arg(long, visible_alias = "card-id", conflicts_with = "program")]
+        card: Option<CardRef>,
+        /// A loyalty program, pooling the miles of its cards
+        #[arg(long)]
+        program: Option<String>,
+        /// Balance date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        /// Without --card or --program: one row per card, or per program
+        #[arg(long, value_enum, default_value = "card")]
+        by: BalancesBy,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Correct a card's or program's miles balance to what the bank shows
+    AdjustMiles {
+        #[arg(long, visible_alias = "card-id", required_unless_present = "program", conflicts_with = "program")]
+        card: Option<CardRef>,
+        #[arg(long)]
+        program: Option<String>,
+        /// Miles to add, or take off when negative
+        #[arg(long, allow_hyphen_values = true, required_unless_present = "to", conflicts_with = "to")]
+        miles: Option<f64>,
+        /// The balance the bank shows; the difference is recorded
+        #[arg(long)]
+        to: Option<f64>,
+        /// Date of the correction (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// List miles that expire soon, batch by batch, under each card's or
+    /// program's expiry policy
+    ExpiringMiles {
+        /// Days ahead to look, defaults to expiry_horizon_days in
+        /// config.toml or 90
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        within: Option<i32>,
+        /// Start from this date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        as_of: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Record miles redeemed from a card or program and the cash they saved;
+    /// the miles come off the ledger
+    AddRedemption {
+        #[arg(long, visible_alias = "card-id", required_unless_present = "program", conflicts_with = "program")]
+        card: Option<CardRef>,
+        #[arg(long)]
+        program: Option<String>,
+        /// Miles used
+        #[arg(long)]
+        miles: f64,
+        /// What the flight, upgrade or voucher would have cost in cash
+        #[arg(long)]
+        cash_value: f64,
+        /// Date of the redemption (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        date: Option<String>,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// List redemptions with the cents each mile was worth, and the average
+    Redemptions {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Loyalty programs, the cards earning into them, and the ratios bank
+    /// points transfer to airline miles at
+    Programs {
+        #[command(subcommand)]
+        action: ProgramsAction,
+    },
+    /// Rates that convert spending recorded in a foreign currency to the
+    /// home currency
+    FxRates {
+        #[command(subcommand)]
+        action: FxRatesAction,
+    },
+    /// Spending categories: add new ones, budget them, and rename or merge
+    /// them everywhere cards and spending use them
+    Category {
+        #[command(subcommand)]
+        action: CategoryAction,
+    },
+    /// Show each budgeted category's spend in a month as a bar against its
+    /// monthly budget
+    Budget {
+        /// Month to show (YYYY-MM), defaults to this month
+        #[arg(long)]
+        month: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Whether each card earned more over a year than its fees and charges cost,
+    /// valuing miles at --mile-value dollars each
+    CardValue {
+        /// Calendar year, defaults to this year
+        #[arg(long)]
+        year: Option<i32>,
+        /// Dollars a mile is worth (e.g. 0.02), defaults to mile_value in
+        /// config.toml
+        #[arg(long, value_name = "DOLLARS")]
+        mile_value: Option<f64>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Write a month's digest as markdown: highlights, missed miles and
+    /// the next month's fees and deadlines
+    Digest {
+        /// Month to summarize (YYYY-MM), defaults to last month
+        #[arg(long)]
+        month: Option<String>,
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Also send it with the digest_command from the config file
+        #[arg(long)]
+        send: bool,
+    },
+    /// Break a month's spend and miles down per card and per category, with
+    /// cap use and minimum spend status, as tables or markdown
+    Report {
+        /// Month to report (YYYY-MM), defaults to last month
+        #[arg(long)]
+        month: Option<String>,
+        #[arg(long, value_enum, default_value = "text")]
+        format: ReportFormat,
+        /// Add charts: the spend trend over the last year, miles by
+        /// category and each card's cap use
+        #[arg(long)]
+        chart: bool,
+        /// File to write; prints to stdout if omitted
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Fill an empty database with realistic synthetic cards and spending,
+    /// for trying things out and for `bench`
+    Generate {
+        #[arg(long, default_value_t = 30)]
+        cards: usize,
+        #[arg(long, default_value_t = 200_000)]
+        transactions: usize,
+        /// The same seed always generates the same data
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Time the queries behind best-card, summary, balances and the reports
+    /// against this database
+    Bench {
+        /// Timed runs of each query, after one warm-up run
+        #[arg(long, default_value_t = 10)]
+        runs: usize,
+        /// Only time these queries, e.g. best-card,summary (see the README)
+        #[arg(long, value_delimiter = ',')]
+        queries: Vec<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// Upload the whole database as today's dated backup and as the latest one
+    Push {
+        /// Name of the backup target, e.g. "nas" for [backup_targets.nas]
+        #[arg(long)]
+        target: String,
+        /// Encrypt the backup with a passphrase, as `export --encrypt` does
+        #[arg(long)]
+        encrypt: bool,
+    },
+    /// Download a backup and restore it into this database, which must have
+    /// no cards yet
+    Pull {
+        /// Name of the backup target
+        #[arg(long)]
+        target: String,
+        /// Backup to fetch, e.g. cc-tracker-2026-03-01.json; defaults to the latest
+        #[arg(long)]
+        name: Option<String>,
+        /// Save the backup to this file instead of restoring it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProgramsAction {
+    /// Add a loyalty program; cards join it with `add-card --program NAME`
+    Add {
+        #[arg(long)]
+        name: String,
+        /// An airline's miles, or a bank's points
+        #[arg(long, value_enum, default_value_t = ProgramKind::Airline)]
+        kind: ProgramKind,
+        /// Months miles stay valid after they are credited
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..))]
+        expiry_months: Option<i32>,
+    },
+    /// Set how many months a program's miles stay valid
+    SetExpiry {
+        /// Program, by name
+        #[arg(long)]
+        name: String,
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..), required_unless_present = "never")]
+        months: Option<i32>,
+        /// The program's miles never expire
+        #[arg(long, conflicts_with = "months")]
+        never: bool,
+    },
+    /// List the programs
+    List {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a program and its transfers; its cards keep the program name
+    Remove {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Show which program each card's earnings land in
+    Cards {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Let one program's units transfer to another's at a ratio
+    AddTransfer {
+        /// Program transferred from, by name
+        #[arg(long)]
+        from: String,
+        /// Program transferred to, by name
+        #[arg(long)]
+        to: String,
+        /// Units received for each unit transferred (e.g. 0.4 miles a point)
+        #[arg(long)]
+        ratio: f64,
+    },
+    /// List the transfers between programs
+    Transfers {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a transfer
+    RemoveTransfer {
+        #[arg(long)]
+        id: i64,
+    },
+    /// Convert a program's units to another's at the transfer ratio
+    Convert {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+        /// Units to convert, defaults to the credited balance of the cards
+        /// in --from
+        #[arg(long, value_parser = parse_amount)]
+        amount: Option<f64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecurringAction {
+    /// Charge a card the same amount on the same day every month
+    Add {
+        #[arg(long, visible_alias = "card-id")]
+        card: CardRef,
+        #[arg(long, value_parser = parse_amount)]
+        amount: f64,
+        #[arg(long)]
+        category: String,
+        /// Day of the month it is charged; the last day in shorter months
+        #[arg(long, value_parser = clap::value_parser!(i32).range(1..=31))]
+        day: i32,
+        /// Merchant to record the spending with, for partnership bonuses
+        #[arg(long)]
+        merchant: Option<String>,
+        /// First charge on or after this date (YYYY-MM-DD), defaults to today
+        #[arg(long, value_parser = dates::parse_input)]
+        start: Option<String>,
+    },
+    /// List the recurring charges with when each is next due
+    List {
+        #[arg(long, visible_alias = "card-id")]
+        card: Option<CardRef>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Stop a recurring charge; spending already recorded for it stays
+    Remove {
+        #[arg(long)]
+        id: i64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum FxRatesAction {
+    /// Store a currency's rate, replacing any earlier one
+    Set {
+        /// ISO currency code, e.g. JPY
+        #[arg(long)]
+        currency: String,
+        /// Home currency per unit of the currency (e.g. 0.009 for JPY)
+        #[arg(long)]
+        rate: f64,
+    },
+    /// List the stored rates
+    List {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Remove a currency's rate
+    Remove {
+        #[arg(long)]
+        currency: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CategoryAction {
+    /// Add a category cards can earn in and spending can be recorded under
+    Add {
+        #[arg(long)]
+        name: String,
+    },
+    /// List the categories with how many cards and transactions use each
+    List {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Rename a category on every card, transaction, promotion and rule
+    Rename {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        to: String,
+    },
+    /// Fold a category into another, moving everything recorded under it
+    Merge {
+        #[arg(long)]
+        from: String,
+        #[arg(long)]
+        into: String,
+    },
+    /// Set the dollars a month meant to be spent in a category, warned
+    /// about by `add-spending` and shown by `budget`
+    Budget {
+        #[arg(long)]
+        name: String,
+        #[arg(long, value_parser = parse_amount, required_unless_present = "clear")]
+        amount: Option<f64>,
+        /// Remove the category's budget
+        #[arg(long, conflicts_with = "amount")]
+        clear: bool,
+    },
+}
+
+impl Command {
+    /// Whether the command only reads the database
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            Command::ListCards { .. }
+                | Command::BestCard { .. }
+                | Command::BestCardMatrix { .. }
+                | Command::CompareCards { .. }
+                | Command::WhatIf { .. }
+                | Command::OptimizeBasket { .. }
+                | Command::ListSpending { .. }
+                | Command::TestRules { .. }
+                | Command::Export { .. }
+                | Command::ExportPivot { .. }
+                | Command::ExportSpending { .. }
+                | Command::Backup {
+                    action: BackupAction::Push { .. } | BackupAction::Pull { out: Some(_), .. }
+                }
+                | Command::ExportCard { .. }
+                | Command::ReviewQueue { .. }
+                | Command::Reconcile { .. }
+                | Command::ClosedCycles { .. }
+                | Command::ListPayments { .. }
+                | Command::ListCharges { .. }
+                | Command::ListPartnerships { .. }
+                | Command::ListRules { .. }
+                | Command::ListPromotions { .. }
+                | Command::Recurring {
+                    action: RecurringAction::List { .. }
+                }
+                | Command::Summary { .. }
+                | Command::Today
+                | Command::CashFlow { .. }
+                | Command::Stats { .. }
+                | Command::PreviewCycle { .. }
+                | Command::CycleSummary { .. }
+                | Command::Statement { .. }
+                | Command::SpendVelocity { .. }
+                | Command::Forecast { .. }
+                | Command::Due { .. }
+                | Command::PlanTrip { .. }
+                | Command::Balances { .. }
+                | Command::Balance { .. }
+                | Command::Redemptions { .. }
+                | Command::ExpiringMiles { .. }
+                | Command::Programs {
+                    action: ProgramsAction::List { .. }
+                        | ProgramsAction::Cards { .. }
+                        | ProgramsAction::Transfers { .. }
+                        | ProgramsAction::Convert { .. }
+                }
+                | Command::FxRates {
+                    action: FxRatesAction::List { .. }
+                }
+                | Command::Category {
+                    action: CategoryAction::List { .. }
+                }
+                | Command::Budget { .. }
+                | Command::CardValue { .. }
+                | Command::Digest { .. }
+                | Command::Report { .. }
+                | Command::Bench { .. }
+        )
+    }
+}
+
+/// A card as given on the command line: its ID, or its name or the start
+/// of its name, in any case
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardRef {
+    Id(i64),
+    Name(String),
+}
+
+impl FromStr for CardRef {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> std::result::Result<CardRef, Infallible> {
+        Ok(match value.parse() {
+            Ok(id) => CardRef::Id(id),
+            Err(_) => CardRef::Name(value.to_string()),
+        })
+    }
+}
+
+impl CardRef {
+    /// Looks the card up by ID, or by name: a card of exactly that name, or
+    /// else the only card whose name starts with it.
+    pub fn resolve<S: Store + ?Sized>(&self, store: &S) -> error::Result<Card> {
+        let name = match self {
+            CardRef::Id(id) => return store.get_card(*id)?.ok_or(CcTrackerError::card_not_found(*id)),
+            CardRef::Name(name) => name,
+        };
+        let cards = store.list_cards()?;
+        match cards_named(&cards, name).as_slice() {
+            [card] => Ok((*card).clone()),
+            [] => {
+                let suggestion = suggest::closest(name, cards.iter().map(|c| c.name.as_str()));
+                if let Some(suggested) = suggestion
+                    && accept_suggestion("card", name, suggested)
+                {
+                    return CardRef::Name(suggested.to_string()).resolve(store);
+                }
+                Err(CcTrackerError::UnknownName { what: "card", name: name.clone(), suggestion: suggestion.map(str::to_string) })
+            }
+            several => {
+                let matches: Vec<_> = several.iter().map(|c| format!("{} (ID {})", c.name, c.id)).collect();
+                Err(CcTrackerError::Invalid(format!(
+                    "'{}' could be any of: {}; use more of the name or the ID",
+                    name,
+                    matches.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+/// The cards named `name` in any case, or else those whose names start
+/// with it.
+fn cards_named<'a>(cards: &'a [Card], name: &str) -> Vec<&'a Card> {
+    let name = name.to_lowercase();
+    let exact: Vec<&Card> = cards.iter().filter(|c| c.name.to_lowercase() == name).collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+    cards.iter().filter(|c| c.name.to_lowercase().starts_with(&name)).collect()
+}
+
+/// The known category named `name` in any case (see `category list`).
+/// Fails for an unknown one, suggesting the closest known category, which
+/// `accept_suggestion` may take instead.
+fn category_named<S: Store + ?Sized>(store: &S, name: &str) -> error::Result<String> {
+    let categories: Vec<String> = store.list_categories()?.into_iter().map(|c| c.name).collect();
+    if let Some(known) = categories.iter().find(|c| c.eq_ignore_ascii_case(name)) {
+        return Ok(known.clone());
+    }
+    let suggestion = suggest::closest(name, categories.iter().map(String::as_str));
+    match suggestion {
+        Some(suggested) if accept_suggestion("category", name, suggested) => Ok(suggested.to_string()),
+        _ => Err(CcTrackerError::UnknownName { what: "category", name: name.to_string(), suggestion: suggestion.map(str::to_string) }),
+    }
+}
+
+/// A category spending is recorded or looked up under: as typed if it is
+/// known or unlike any known one (a new category), but refused if it looks
+/// like a typo of a known one, e.g. "dinning" for "dining".
+fn checked_category<S: Store + ?Sized>(store: &S, category: String) -> error::Result<String> {
+    match category_named(store, &category) {
+        Ok(known) if known.eq_ignore_ascii_case(&category) => Ok(category),
+        Ok(suggested) => Ok(suggested),
+        Err(CcTrackerError::UnknownName { suggestion: None, .. }) => Ok(category),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether to use the known `suggestion` for the mistyped `name` of a
+/// `what`: asked on the terminal when `confirm_suggestions` is set in
+/// config.toml, and otherwise no.
+fn accept_suggestion(what: &str, name: &str, suggestion: &str) -> bool {
+    config::loaded().is_some_and(|config| config.confirm_suggestions)
+        && std::io::stdin().is_terminal()
+        && confirm(&format!("No {} named '{}'. Did you mean '{}'?", what, name, suggestion))
+}
+
+/// Refuses card settings with any of `NewCard::problems`.
+fn check_card(card: &NewCard) -> error::Result<()> {
+    let problems = card.problems();
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(CcTrackerError::Invalid(format!("Invalid card settings:\n  {}", problems.join("\n  "))))
+    }
+}
+
+/// An amount given on the command line; NaN and infinity are refused.
+fn parse_amount(value: &str) -> error::Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(amount) if amount.is_finite() => Ok(amount),
+        _ => Err(CcTrackerError::Invalid(format!("'{}' is not an amount", value))),
+    }
+}
+
+/// The program named `name`, in any case.
+fn program_named<S: Store + ?Sized>(store: &S, name: &str) -> error::Result<Program> {
+    store
+        .list_programs()?
+        .into_iter()
+        .find(|program| program.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| CcTrackerError::UnknownName { what: "program", name: name.to_string(), suggestion: None })
+}
+
+/// The miles account a command names: the card given, or else the
+/// program, matched in any case against the programs and the cards' own.
+fn miles_account<S: Store + ?Sized>(store: &S, card: Option<&CardRef>, program: Option<&str>) -> error::Result<ledger::Account> {
+    if let Some(card) = card {
+        let card = card.resolve(store)?;
+        if card.reward_type == RewardType::Cashback {
+            return Err(CcTrackerError::Invalid(format!("'{}' is a cashback card; it has no miles balance", card.name)));
+        }
+        return Ok(ledger::Account::Card(card.id));
+    }
+    let name = program.unwrap_or_default();
+    if let Ok(program) = program_named(store, name) {
+        return Ok(ledger::Account::Program(program.name));
+    }
+    store
+        .list_cards()?
+        .into_iter()
+        .filter_map(|card| card.program)
+        .find(|program| program.eq_ignore_ascii_case(name))
+        .map(ledger::Account::Program)
+        .ok_or_else(|| CcTrackerError::UnknownName { what: "program", name: name.to_string(), suggestion: None })
+}
+
+/// The ID of the card given, if one is.
+fn card_id_of<S: Store + ?Sized>(store: &S, card: Option<&CardRef>) -> error::Result<Option<i64>> {
+    Ok(match card {
+        Some(card) => Some(card.resolve(store)?.id),
+        None => None,
+    })
+}
+
+/// How a command ended, reported as the process exit code so scripts need
+/// not parse its output. Commands that fail exit with their error's
+/// [`CcTrackerError::exit_code`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Done; for best-card, a card is eligible for the purchase
+    Success = 0,
+    /// best-card matched only cards that are not eligible, e.g. capped out
+    OnlyIneligible = 2,
+    /// best-card matched no card
+    NoMatch = 3,
+}
+
+/// Runs a single CLI command against the given store, printing listings as
+/// `output` (the global `--output`). A refused command fails with the
+/// reason, having changed nothing.
+pub fn run<S: Store + ?Sized>(store: &S, command: Command, output: Option<OutputFormat>) -> error::Result<Outcome> {
+    let format = output.or_else(|| config::loaded().and_then(|c| c.output)).unwrap_or(OutputFormat::Table);
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::AddCard(args) => {
+            let card = args.new_card(store)?;
+            check_card(&card)?;
+            let id = store.add_card(&card)?;
+            println!("Added card '{}' with ID {}", card.name, id);
+        }
+        Command::EditCard(EditCardArgs {
+            card,
+            name,
+            categories,
+            payment_categories,
+            miles_per_dollar,
+            miles_per_dollar_foreign,
+            block_size,
+            renewal_date,
+            max_reward_limit,
+            cap_unit,
+            min_spend,
+            min_spend_exclusions,
+            posting_delay,
+            bank_time_offset_minutes,
+            cutoff_hour,
+            annual_fee,
+            fee_posting_month,
+            starting_balance,
+            balance_alert,
+            reward_type,
+            cashback_percent,
+            foreign_blocks,
+            program,
+            miles_expiry_months,
+            fx_fee,
+            excluded_categories,
+            payment_due_days,
+        }) => {
+            let card = card.resolve(store)?;
+            let id = card.id;
+            let current = card_file::definition_of(&card, Vec::new()).new_card();
+            let before = current.clone();
+            let edited = NewCard {
+                name: name.unwrap_or(current.name),
+                categories: categories.unwrap_or(current.categories),
+                payment_categories: payment_categories.unwrap_or(current.payment_categories),
+                miles_per_dollar: miles_per_dollar.unwrap_or(current.miles_per_dollar),
+                miles_per_dollar_foreign: miles_per_dollar_foreign.or(current.miles_per_dollar_foreign),
+                block_size: block_size.unwrap_or(current.block_size),
+                statement_renewal_date: renewal_date.unwrap_or(current.statement_renewal_date),
+                max_reward_limit: max_reward_limit.or(current.max_reward_limit),
+                min_spend: min_spend.or(current.min_spend),
+                posting_delay: posting_delay.unwrap_or(current.posting_delay),
+                bank_time_offset_minutes: bank_time_offset_minutes.unwrap_or(current.bank_time_offset_minutes),
+                cutoff_hour: cutoff_hour.unwrap_or(current.cutoff_hour),
+                annual_fee: annual_fee.or(current.annual_fee),
+                fee_posting_month: fee_posting_month.or(current.fee_posting_month),
+                starting_balance: starting_balance.unwrap_or(current.starting_balance),
+                balance_alert: balance_alert.or(current.balance_alert),
+                reward_type: reward_type.unwrap_or(current.reward_type),
+                cashback_percent: cashback_percent.or(current.cashback_percent),
+                foreign_blocks: foreign_blocks.unwrap_or(current.foreign_blocks),
+                cap_unit: cap_unit.unwrap_or(current.cap_unit),
+                min_spend_exclusions: min_spend_exclusions.unwrap_or(current.min_spend_exclusions),
+                program: program.or(current.program),
+                miles_expiry_months: miles_expiry_months.or(current.miles_expiry_months),
+                fx_fee_percent: fx_fee.or(current.fx_fee_percent),
+                excluded_categories: excluded_categories.unwrap_or(current.excluded_categories),
+                payment_due_days: payment_due_days.or(current.payment_due_days),
+            };
+            check_card(&edited)?;
+            if edited == before {
+                println!("Card '{}' (ID {}) is up to date", card.name, id);
+            } else {
+                store.update_card(id, &edited)?;
+                println!("Updated card '{}' (ID {})", edited.name, id);
+            }
+        }
+        Command::AddCards { file } => {
+            let (mut cards, config) = card_file::read(&file)
+                .and_then(|cards| Ok((cards, config::load()?)))
+                .map_err(CcTrackerError::Invalid)?;
+            for card in &mut cards {
+                if card.categories.is_empty() {
+                    card.categories = config.default_categories(store)?;
+                }
+                if card.payment_categories.is_empty() {
+                    card.payment_categories = config.default_payment_categories();
+                }
+            }
+            for (name, sync) in card_file::apply(store, &cards, &db::today())? {
+                print_card_sync(&name, sync);
+            }
+        }
+        Command::ListCards { output } => {
+            let cards = store.list_cards()?;
+            print_rows(&cards, &output, format)?;
+        }
+        Command::RemoveCard { card } => {
+            let id = card.resolve(store)?.id;
+            if store.remove_card(id)? {
+                println!("Removed card with ID {}", id);
+            } else {
+                return Err(CcTrackerError::card_not_found(id));
+            }
+        }
+        Command::CloseCard { card, on } => {
+            let card = card.resolve(store)?;
+            let id = card.id;
+            let on = on.unwrap_or_else(db::today);
+            store.close_card(id, &on)?;
+            println!("Closed card '{}' as of {}; its history is kept", card.name, on);
+            // Miles still with the bank are usually lost with the card
+            if let Some(balance) = store.card_balances(&db::today())?.into_iter().find(|b| b.card_id == id)
+                && balance.total_miles > 0.0
+            {
+                println!(
+                    "Warning: '{}' still has {} miles ({} pending); convert or transfer them before {} or they may be forfeited",
+                    card.name, balance.total_miles, balance.pending_miles, on
+                );
+            }
+        }
+        Command::BestCard {
+            category,
+            amount,
+            payment_category,
+            date,
+            as_of,
+            include_scheduled,
+            fee_adjusted,
+            explain,
+            merchant_for,
+            mile_value,
+            point_value,
+            foreign,
+            foreign_amount,
+            merchant,
+            quiet,
+            output,
+        } => {
+            let date = as_of.clone().or(date).unwrap_or_else(db::today);
+            let templated = quiet || !output.is_table(format);
+            let merchant = merchant.or(merchant_for.clone());
+            let rules = if category.is_none() || payment_category.is_none() {
+                rules::load(&config::rules_path())
+                    .unwrap_or_else(|e| {
+                        println!("Ignoring rules: {}", e);
+                        Rules::default()
+                    })
+                    .with_stored(&store.list_merchant_rules()?)
+            } else {
+                Rules::default()
+            };
+            let category = match (category, &merchant_for) {
+                (Some(category), _) => checked_category(store, category)?,
+                (None, Some(merchant)) => {
+                    let mut seen = Vec::new();
+                    store.for_each_spending(None, None, &mut |s| {
+                        if let Some(description) = s.description {
+                            seen.push((description, s.category));
+                        }
+                        Ok(())
+                    })?;
+                    let found = rules::categorize_merchant(&rules, &import::merchant_history(seen), merchant);
+                    if !templated && let Some(found) = &found {
+                        println!("'{}' is {} ({})", merchant, found.category(), found);
+                    }
+                    let Some(found) = found else {
+                        return Err(CcTrackerError::Invalid(format!(
+                            "No rule matches '{}' and nothing was recorded there before; pass --category",
+                            merchant
+                        )));
+                    };
+                    found.category().to_string()
+                }
+                (None, None) => unreachable!("clap requires --category without --for"),
+            };
+            let payment_category = match payment_category {
+                Some(payment_category) => payment_category,
+                None => match rules::infer_payment_category(&rules, merchant.as_deref(), Some(&category)) {
+                    Some(inferred) => {
+                        if !templated {
+                            println!("Paid {} (rule on line {})", inferred.payment_category, inferred.line);
+                        }
+                        inferred.payment_category.to_string()
+                    }
+                    None => match config::loaded().and_then(|c| c.payment_category.clone()) {
+                        Some(configured) => {
+                            if !templated {
+                                println!("Paid {} (payment_category in config.toml)", configured);
+                            }
+                            configured
+                        }
+                        None => {
+                            return Err(CcTrackerError::Invalid(match &merchant {
+                                Some(merchant) => format!(
+                                    "No rule says how '{}' or {} purchases are paid; pass --payment-category",
+                                    merchant, category
+                                ),
+                                None => format!(
+                                    "No rule says how {} purchases are paid; pass --payment-category",
+                                    category
+                                ),
+                            }));
+                        }
+                    },
+                },
+            };
+            let mile_value = mile_value.or_else(|| config::loaded().and_then(|c| c.mile_value));
+            let point_value = point_value.or_else(|| config::loaded().and_then(|c| c.point_value));
+            let options = RecommendOptions {
+                known_by: as_of,
+                include_scheduled,
+                fee_adjusted,
+                explain,
+                merchant,
+                mile_value,
+                point_value,
+                foreign,
+                foreign_amount,
+                ..RecommendOptions::default()
+            };
+            let results = store.best_card_for_category(
+                &category,
+                amount,
+                &payment_category,
+                &date,
+                &options,
+            )?;
+            // Eligible cards rank first
+            let outcome = match results.first() {
+                Some(best) if best.eligible => Outcome::Success,
+                Some(_) => Outcome::OnlyIneligible,
+                None => Outcome::NoMatch,
+            };
+            if quiet {
+                if let Some(best) = results.first() {
+                    println!("{}", best.card_name);
+                }
+            } else if results.is_empty() && output.is_table(format) {
+                println!("No cards match '{}' with '{}'", category, payment_category);
+            } else {
+                print_rows(&results, &output, format)?;
+                if explain && !templated {
+                    for (rank, result) in results.iter().enumerate() {
+                        println!("\n#{} {}", rank + 1, result.card_name);
+                        println!("{}", Table::new(&result.explanation));
+                    }
+                }
+                if !templated
+                    && options.mile_value.is_none()
+                    && results.iter().any(|r| r.fx_fee.is_some_and(|fee| fee > 0.0))
+                {
+                    println!(
+                        "Ranked by earn rate; pass --mile-value or set mile_value in config.toml to rank net of FX fees"
+                    );
+                }
+                if !templated
+                    && let Some(split) =
+                        store.suggest_split(&category, amount, &payment_category, &date, &options)?
+                {
+                    print_split(&split);
+                }
+            }
+            return Ok(outcome);
+        }
+        Command::BestCardMatrix { amount, by_payment, date, output } => {
+            let date = date.unwrap_or_else(db::today);
+            let categories: Vec<String> = store.list_categories()?.into_iter().map(|c| c.name).collect();
+            // Every way any card can be paid, each once whatever its case
+            let mut payment_categories: Vec<String> = Vec::new();
+            for card in store.list_cards()? {
+                for payment_category in serde_json::from_str::<Vec<String>>(&card.payment_categories).unwrap_or_default() {
+                    if !payment_categories.iter().any(|p| p.eq_ignore_ascii_case(&payment_category)) {
+                        payment_categories.push(payment_category);
+                    }
+                }
+            }
+            let cells = overview::best_cards(store, &categories, &payment_categories, amount, &date, by_payment)?;
+            if by_payment && output.is_table(format) {
+                let mut grid = tabled::builder::Builder::default();
+                grid.push_record(std::iter::once("category".to_string()).chain(payment_categories.iter().cloned()));
+                for (category, row) in categories.iter().zip(cells.chunks(payment_categories.len().max(1))) {
+                    grid.push_record(std::iter::once(category.clone()).chain(row.iter().map(|cell| match &cell.card_name {
+                        Some(card_name) => format!("{} ({:.2} mi/$)", card_name, cell.effective_rate),
+                        None => "-".to_string(),
+                    })));
+                }
+                println!("{}", grid.build());
+            } else {
+                print_rows(&cells, &output, format)?;
+            }
+        }
+        Command::CompareCards { cards, category, amount, payment_category, date, output } => {
+            let cards = cards.iter().map(|card| card.resolve(store)).collect::<error::Result<Vec<_>>>()?;
+            if cards.len() < 2 {
+                return Err(CcTrackerError::Invalid("Give at least two cards to compare".to_string()));
+            }
+            let category = category.map(|category| checked_category(store, category)).transpose()?;
+            let purchase = category.as_deref().map(|category| compare::Purchase {
+                category,
+                amount,
+                payment_category: payment_category.as_deref(),
+            });
+            let rows = compare::compare(store, &cards, purchase.as_ref(), &date.unwrap_or_else(db::today))?;
+            if output.is_table(format) {
+                // A column per card, so their terms line up row by row
+                let mut grid = tabled::builder::Builder::default();
+                let fields: Vec<Vec<String>> =
+                    rows.iter().map(|row| row.fields().into_iter().map(|f| f.into_owned()).collect()).collect();
+                // Card names head the columns; the projection rows only
+                // show with a purchase
+                let projection = ["payment_category", "projected_miles", "effective_rate", "note"];
+                for (i, header) in CardComparison::headers().into_iter().enumerate().skip(1) {
+                    if purchase.is_none() && projection.contains(&header.as_ref()) {
+                        continue;
+                    }
+                    grid.push_record(std::iter::once(header.into_owned()).chain(fields.iter().map(|f| f[i].clone())));
+                }
+                println!("{}", grid.build());
+            } else {
+                print_rows(&rows, &output, format)?;
+            }
+        }
+        Command::WhatIf { card, period, output } => {
+            let card = card.new_card(store)?;
+            let range = period.and_then(|period| {
+                period.range(Some((card.statement_renewal_date, &BTreeMap::new())), &db::today())
+            });
+            let whatif = whatif::replay(store, &card, range.as_ref())?;
+            print_rows(&whatif.categories, &output, format)?;
+            if output.is_table(format) {
+                let cashback: f64 = whatif.categories.iter().map(|c| c.cashback).sum();
+                let earned = match card.reward_type {
+                    RewardType::Cashback => format!("${:.2} cash back", cashback),
+                    RewardType::Points => format!("{:.0} points", whatif.miles()),
+                    RewardType::Miles => format!("{:.0} miles", whatif.miles()),
+                };
+                println!(
+                    "'{}' would have earned {} on ${:.2} of spending, where the cards used earned {:.0} miles",
+                    card.name,
+                    earned,
+                    whatif.spent(),
+                    whatif.actual_miles()
+                );
+                if whatif.uncovered > 0.0 {
+                    println!("${:.2} went on categories or payment methods it does not cover", whatif.uncovered);
+                }
+                if let Some(min_spend) = card.min_spend
+                    && whatif.missed_min_spend > 0
+                {
+                    println!(
+                        "{} statement cycle(s) fell short of its ${:.2} minimum spend and earned nothing",
+                        whatif.missed_min_spend, min_spend
+                    );
+                }
+            }
+        }
+        Command::OptimizeBasket { mut items, file, date, output } => {
+            if let Some(file) = file {
+                items.extend(basket::read(&file).map_err(CcTrackerError::Invalid)?);
+            }
+            for item in &mut items {
+                item.category = checked_category(store, std::mem::take(&mut item.category))?;
+            }
+            let allocation = basket::allocate(store, &items, &date.unwrap_or_else(db::today))?;
+            print_rows(&allocation.rows, &output, format)?;
+            if output.is_table(format) {
+                println!(
+                    "Projected miles: {:.0} (one purchase at a time: {:.0})",
+                    allocation.miles, allocation.one_at_a_time
+                );
+                if !allocation.exhaustive {
+                    println!("The basket is too big to weigh every split; this is the best one found");
+                }
+            }
+        }
+        Command::AddSpending {
+            card,
+            amount,
+            category,
+            date,
+            time,
+            scheduled,
+            merchant,
+            foreign,
+            currency,
+            fx_rate,
+        } => {
+            let card = card.resolve(store)?;
+            let card_id = card.id;
+            let category = match (category, &merchant) {
+                (Some(category), _) => checked_category(store, category)?,
+                (None, Some(merchant)) => {
+                    let rules = rules::load(&config::rules_path())
+                        .map_err(CcTrackerError::Invalid)?
+                        .with_stored(&store.list_merchant_rules()?);
+                    let Some(found) = rules.categorize(merchant) else {
+                        return Err(CcTrackerError::Invalid(format!(
+                            "No rule matches '{}'; pass --category",
+                            merchant
+                        )));
+                    };
+                    println!("'{}' is {} ({})", merchant, found.category(), found);
+                    found.category().to_string()
+                }
+                (None, None) => unreachable!("clap requires --category without --merchant"),
+            };
+            let mut date = date.unwrap_or_else(db::today);
+            if let Some(time) = time {
+                date = db::bank_transaction_date(&card, &date, time);
+            }
+            let (amount, original) = match currency {
+                Some(currency) => {
+                    let (home, original) = fx::to_home(store, amount, &currency, fx_rate)?;
+                    (home, Some(original))
+                }
+                None => (amount, None),
+            };
+            let charged = original
+                .as_ref()
+                .map_or_else(String::new, |o| format!(" ({:.2} {})", o.amount, o.currency));
+            if scheduled {
+                if date <= db::today() {
+                    return Err(CcTrackerError::Invalid(
+                        "Scheduled spending must be dated in the future".to_string(),
+                    ));
+                }
+                let (id, miles) =
+                    store.schedule_spending(card_id, amount, &category, &date, merchant.as_deref(), foreign, original.as_ref())?;
+                println!(
+                    "Scheduled ${:.2}{} on card {} for '{}' on {} — will earn {} (ID {})",
+                    amount,
+                    charged,
+                    card_id,
+                    category,
+                    date,
+                    card.reward_type.describe(miles, card.cashback_on(amount, &category)),
+                    id
+                );
+            } else {
+                let (id, miles) =
+                    store.add_spending(card_id, amount, &category, &date, merchant.as_deref(), foreign, original.as_ref())?;
+                println!(
+                    "Recorded ${:.2}{} on card {} for '{}' on {} — earned {} (ID {})",
+                    amount,
+                    charged,
+                    card_id,
+                    category,
+                    date,
+                    card.reward_type.describe(miles, card.cashback_on(amount, &category)),
+                    id
+                );
+                if let Some(warning) = budget::overspend_warning(store, &category, &date, amount)? {
+                    println!("{}", warning);
+                }
+            }
+            let promotions = store.list_promotions(Some(card_id))?;
+            if card.excludes(&category) {
+                println!("Nothing earned: '{}' excludes {} spending from earning", card.name, category);
+            } else if let Some(promotion) = engine::promotion_for(&promotions, card_id, &category, &date) {
+                println!(
+                    "Promotion {} applied: {} miles per block on {} until {}",
+                    promotion.id,
+                    promotion.miles_per_dollar,
+                    promotion.category,
+                    dates::display(&promotion.end_date)
+                );
+            }
+        }
+        Command::PostScheduled { id } => {
+            if store.post_scheduled_spending(id)? {
+                println!("Posted scheduled transaction {}", id);
+            } else {
+                return Err(CcTrackerError::NotFound { what: "scheduled transaction", id });
+            }
+        }
+        Command::Recurring { action } => run_recurring(store, action, format)?,
+        Command::ApplyRecurring { as_of, output } => {
+            let as_of = as_of.unwrap_or_else(db::today);
+            if as_of > db::today() {
+                return Err(CcTrackerError::Invalid(
+                    "Recurring charges can only be applied up to today".to_string(),
+                ));
+            }
+            let applied = store.apply_recurring(&as_of)?;
+            if applied.is_empty() && output.is_table(format) {
+                println!("No recurring charges are due");
+            } else {
+                print_rows(&applied, &output, format)?;
+            }
+        }
+        Command::MoveSpending {
+            ids,
+            from_card,
+            category,
+            period,
+            to_card,
+        } => {
+            let card = to_card.resolve(store)?;
+            let to_card = card.id;
+            let category = category.map(|category| checked_category(store, category)).transpose()?;
+            let ids = match card_id_of(store, from_card.as_ref())? {
+                Some(from_card) if from_card == to_card => {
+                    return Err(CcTrackerError::Invalid(format!("The transactions are already on card {}", to_card)));
+                }
+                Some(from_card) => {
+                    let ranges = card_ranges(store, period.as_ref())?;
+                    store
+                        .list_spending(Some(from_card), None)?
+                        .into_iter()
+                        .filter(|s| category.as_ref().is_none_or(|c| &s.category == c))
+                        .filter(|s| ranges.as_ref().is_none_or(|r| r.contains(s.card_id, &s.date)))
+                        .map(|s| s.id)
+                        .collect()
+                }
+                None => ids,
+            };
+            if ids.is_empty() {
+                return Err(CcTrackerError::Invalid("No transactions to move".to_string()));
+            }
+            let moved = store.move_spending(&ids, to_card)?;
+            for id in ids.iter().filter(|id| !moved.iter().any(|(moved, _)| moved == *id)) {
+                println!("No spending transaction with ID {}", id);
+            }
+            for (id, miles) in &moved {
+                println!("Moved transaction {} to card '{}' — now earns {:.0} miles", id, card.name, miles);
+            }
+        }
+        Command::EditSpending {
+            id,
+            card,
+            amount,
+            category,
+            date,
+            foreign,
+        } => {
+            let edit = SpendingEdit {
+                card_id: card_id_of(store, card.as_ref())?,
+                amount,
+                category: category.map(|category| checked_category(store, category)).transpose()?,
+                date,
+                foreign,
+            };
+            match store.edit_spending(id, &edit)? {
+                Some(miles) => println!("Updated transaction {} — now earns {:.0} miles", id, miles),
+                None => {
+                    return Err(CcTrackerError::NotFound { what: "spending transaction", id });
+                }
+            }
+        }
+        Command::ListSpending {
+            card,
+            as_of,
+            period,
+            output,
+        } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let mut rows = RowStream::new(&output, format).map_err(CcTrackerError::Invalid)?;
+            let ranges = card_ranges(store, period.as_ref())?;
+            let reward_types: HashMap<i64, RewardType> =
+                store.list_cards()?.into_iter().map(|card| (card.id, card.reward_type)).collect();
+            let printed = store
+                .for_each_spending(card_id, as_of.as_deref(), &mut |spending| {
+                    if ranges.as_ref().is_some_and(|r| !r.contains(spending.card_id, &spending.date)) {
+                        return Ok(());
+                    }
+                    // As stored when recorded, not at the card's current rates
+                    let reward_type = reward_types.get(&spending.card_id).copied().unwrap_or_default();
+                    let reward = match reward_type {
+                        RewardType::Cashback => spending.cashback.unwrap_or(0.0),
+                        RewardType::Miles | RewardType::Points => spending.miles_earned,
+                    };
+                    rows.push(SpendingRow { spending, reward, reward_unit: reward_type.unit() })
+                })
+                .and_then(|()| rows.finish());
+            // Stop quietly when the reader has gone away
+            if !rows.closed {
+                printed?;
+            }
+        }
+        Command::ImportStatement {
+            profile,
+            mapping,
+            file,
+            card,
+            yes,
+            preview_file,
+        } => {
+            let config = config::load().map_err(CcTrackerError::Invalid)?;
+            let (mut import_profile, source) = match (profile, mapping) {
+                (_, Some(path)) => {
+                    let source = path.display().to_string();
+                    let mapping = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("Failed to read {}: {}", source, e))
+                        .and_then(|text| import::parse_mapping(&text, &source));
+                    match mapping {
+                        Ok(mapping) => (mapping, format!("Mapping {}", source)),
+                        Err(e) => {
+                            return Err(CcTrackerError::Invalid(e.to_string()));
+                        }
+                    }
+                }
+                (Some(profile), None) => {
+                    let Some(import_profile) =
+                        config.import_profiles.get(&profile).cloned().or_else(|| import::preset(&profile))
+                    else {
+                        return Err(CcTrackerError::Invalid(format!(
+                            "No import profile named '{}' in {}, and no built-in preset of that name ({})",
+                            profile,
+                            config::config_path().display(),
+                            import::preset_names().join(", ")
+                        )));
+                    };
+                    (import_profile, format!("Profile '{}'", profile))
+                }
+                (None, None) => unreachable!("clap requires --profile without --mapping"),
+            };
+            if import_profile.date_format.is_none() {
+                import_profile.date_format = config.date_format.clone();
+            }
+            let Some(card) = card.or(import_profile.card_id.map(CardRef::Id)) else {
+                return Err(CcTrackerError::Invalid(format!("{} has no card_id; pass --card", source)));
+            };
+            let card_id = card.resolve(store)?.id;
+            let stored_rules = store.list_merchant_rules()?;
+            let rows = match std::fs::File::open(&file)
+                .map_err(|e| format!("Failed to open {}: {}", file.display(), e))
+                .and_then(|f| import::parse_statement(f, &import_profile))
+                .and_then(|mut rows| {
+                    rules::apply(&rules::load(&config::rules_path())?.with_stored(&stored_rules), &mut rows);
+                    Ok(rows)
+                }) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    return Err(CcTrackerError::Invalid(e.to_string()));
+                }
+            };
+
+            let preview = store.preview_import(card_id, &rows, &import_profile)?;
+            print_import_preview(&preview);
+            if let Some(path) = &preview_file {
+                let json = export::to_versioned_json(export::PREVIEW_FORMAT, export::PREVIEW_VERSION, &preview);
+                match std::fs::write(path, json) {
+                    Ok(()) => println!("Preview written to {}", path.display()),
+                    Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                }
+            }
+            if !yes && !confirm("Import these transactions?") {
+                println!("Import cancelled; nothing was recorded");
+                return Ok(Outcome::Success);
+            }
+
+            let summary = store.import_spending(card_id, &rows, &import_profile)?;
+            println!(
+                "Imported {} transactions from {} into card {} ({} duplicates skipped)",
+                summary.imported,
+                file.display(),
+                card_id,
+                summary.duplicates
+            );
+            if summary.queued_for_review > 0 {
+                println!(
+                    "{} transactions need a category; see review-queue",
+                    summary.queued_for_review
+                );
+            }
+        }
+        Command::TestRules { samples, rules: rules_file } => {
+            let rules_file = rules_file.unwrap_or_else(config::rules_path);
+            let loaded = rules::load(&rules_file).and_then(|rules| {
+                std::fs::read_to_string(&samples)
+                    .map(|text| (rules, text))
+                    .map_err(|e| format!("Failed to read {}: {}", samples.display(), e))
+            });
+            let (rules, text) = loaded.map_err(CcTrackerError::Invalid)?;
+            let matches: Vec<RuleMatch> = text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    let rule = rules::first_match(&rules.categories, line);
+                    let category = rule.map(|r| r.category.clone());
+                    RuleMatch {
+                        description: line.to_string(),
+                        rule_line: rule.map(|r| r.line),
+                        pattern: rule.map(|r| r.pattern.as_str().to_string()),
+                        payment_category: rules::infer_payment_category(&rules, Some(line), category.as_deref())
+                            .map(|p| p.payment_category.to_string()),
+                        category,
+                    }
+                })
+                .collect();
+            println!("{}", Table::new(matches));
+        }
+        Command::MergeDb {
+            file,
+            cards,
+            spending,
+            tag,
+        } => {
+            if !file.exists() {
+                return Err(CcTrackerError::Invalid(format!("No database file at {}", file.display())));
+            }
+            let options = MergeOptions { cards, spending, tag };
+            let reports = store.merge_database(&file, &options)?;
+            println!("{}", Table::new(reports));
+        }
+        Command::Export {
+            out,
+            period,
+            anonymize,
+            encrypt,
+        } => {
+            let mut data = export::export(store)?;
+            if let Some(ranges) = card_ranges(store, period.as_ref())? {
+                data.spending.retain(|s| ranges.contains(s.card_id, &s.date));
+                data.payments.retain(|p| ranges.contains(p.card_id, &p.date));
+                data.charges.retain(|c| ranges.contains(c.card_id, &c.date));
+                data.review_queue.retain(|r| ranges.contains(r.card_id, &r.date));
+                data.closed_cycles.retain(|c| ranges.contains(c.card_id, &c.cycle_start));
+            }
+            if anonymize {
+                export::anonymize(&mut data, export::random_scale_factor());
+            }
+            let mut json = serde_json::to_string_pretty(&data).expect("export serializes to JSON");
+            if encrypt {
+                json = match encrypt_export(&json) {
+                    Ok(sealed) => sealed,
+                    Err(e) => {
+                        return Err(CcTrackerError::Invalid(e.to_string()));
+                    }
+                };
+            }
+            match out {
+                None => println!("{}", json),
+                Some(path) => match std::fs::write(&path, json) {
+                    Ok(()) => println!(
+                        "Exported {} cards and {} transactions to {}",
+                        data.cards.len(),
+                        data.spending.len(),
+                        path.display()
+                    ),
+                    Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                },
+            }
+        }
+        Command::ExportPivot { by, out, period } => {
+            let ranges = card_ranges(store, period.as_ref())?;
+            let pivot = pivot::build(store, by, ranges.as_ref())?;
+            let csv = pivot.to_csv();
+            match out {
+                None => print!("{}", csv),
+                Some(path) => match std::fs::write(&path, csv) {
+                    Ok(()) => println!(
+                        "Exported {} months by {} to {}",
+                        pivot.rows.len(),
+                        if by == PivotBy::Card { "card" } else { "category" },
+                        path.display()
+                    ),
+                    Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                },
+            }
+        }
+        Command::ExportSpending {
+            card,
+            category,
+            period,
+            out,
+        } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let ranges = card_ranges(store, period.as_ref())?;
+            // Oldest first, as on a statement
+            let spending: Vec<_> = store
+                .list_spending(card_id, None)?
+                .into_iter()
+                .rev()
+                .filter(|s| category.as_ref().is_none_or(|c| &s.category == c))
+                .filter(|s| ranges.as_ref().is_none_or(|r| r.contains(s.card_id, &s.date)))
+                .collect();
+            let csv = export::spending_csv(&spending, &store.list_cards()?);
+            match out {
+                None => print!("{}", csv),
+                Some(path) => match std::fs::write(&path, csv) {
+                    Ok(()) => println!("Exported {} transactions to {}", spending.len(), path.display()),
+                    Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                },
+            }
+        }
+        Command::Restore { file } => {
+            let text = std::fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e));
+            return restore_from(store, text, &file.display().to_string());
+        }
+        Command::Backup { action } => {
+            let config = config::load().map_err(CcTrackerError::Invalid)?;
+            let name = match &action {
+                BackupAction::Push { target, .. } | BackupAction::Pull { target, .. } => target,
+            };
+            let Some(target) = config.backup_targets.get(name) else {
+                return Err(CcTrackerError::Invalid(format!(
+                    "No backup target named '{}' in {}",
+                    name, config::config_path().display()
+                )));
+            };
+            match action {
+                BackupAction::Push { encrypt, .. } => {
+                    let data = export::export(store)?;
+                    let mut json = serde_json::to_string_pretty(&data).expect("export serializes to JSON");
+                    if encrypt {
+                        json = match encrypt_export(&json) {
+                            Ok(sealed) => sealed,
+                            Err(e) => {
+                                return Err(CcTrackerError::Invalid(e.to_string()));
+                            }
+                        };
+                    }
+                    match backup::push(target, &json, &db::today()) {
+                        Ok(urls) => println!(
+                            "Backed up {} cards and {} transactions to {}",
+                            data.cards.len(),
+                            data.spending.len(),
+                            urls.join(" and ")
+                        ),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+                BackupAction::Pull { name, out, .. } => {
+                    let text = backup::pull(target, name.as_deref());
+                    let source = target.file_url(name.as_deref().unwrap_or(backup::LATEST_NAME));
+                    match (out, text) {
+                        (Some(path), Ok(text)) => match std::fs::write(&path, text) {
+                            Ok(()) => println!("Saved {} to {}", source, path.display()),
+                            Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                        },
+                        (_, text) => return restore_from(store, text, &source),
+                    }
+                }
+            }
+        }
+        Command::ExportCard { card, out } => {
+            let id = card.resolve(store)?.id;
+            let Some(shared) = card_file::export_card(store, id, &db::today())? else {
+                return Err(CcTrackerError::card_not_found(id));
+            };
+            let json = serde_json::to_string_pretty(&shared).expect("card serializes to JSON");
+            match out {
+                None => println!("{}", json),
+                Some(path) => match std::fs::write(&path, json) {
+                    Ok(()) => println!("Exported card '{}' to {}", shared.card.name, path.display()),
+                    Err(e) => println!("Failed to write {}: {}", path.display(), e),
+                },
+            }
+        }
+        Command::ImportCard { file } => {
+            let card = match std::fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))
+                .and_then(|text| card_file::parse_shared(&text))
+            {
+                Ok(card) => card,
+                Err(e) => {
+                    return Err(CcTrackerError::Invalid(e.to_string()));
+                }
+            };
+            let name = card.name.clone();
+            print_card_sync(&name, card_file::import_shared(store, card, &db::today())?);
+        }
+        Command::ReviewQueue { card, output } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let queue = store.list_review_queue(card_id)?;
+            print_rows(&queue, &output, format)?;
+        }
+        Command::ResolveReview { id, category } => {
+            match store.resolve_review(id, category.as_deref())? {
+                Some((spending_id, miles)) => println!(
+                    "Recorded queued transaction {} as spending {} — earned {:.0} miles",
+                    id, spending_id, miles
+                ),
+                None => println!("No queued transaction {} with a category to record; pass --category", id),
+            }
+        }
+        Command::SetRates {
+            card,
+            effective_from,
+            miles_per_dollar,
+            block_size,
+            max_reward_limit,
+            min_spend,
+        } => {
+            let card = card.resolve(store)?;
+            let card_id = card.id;
+            let effective_from = effective_from.unwrap_or_else(db::today);
+            if let Some(last) = store.last_rate_change(card_id)?
+                && effective_from < last
+            {
+                return Err(CcTrackerError::Invalid(format!(
+                    "Rates already changed on {}; new changes must be effective on or after that date",
+                    last
+                )));
+            }
+            let rates = CardRates {
+                miles_per_dollar: miles_per_dollar.unwrap_or(card.miles_per_dollar),
+                block_size: block_size.unwrap_or(card.block_size),
+                max_reward_limit: max_reward_limit.or(card.max_reward_limit),
+                min_spend: min_spend.or(card.min_spend),
+            };
+            store.change_card_rates(card_id, &effective_from, &rates)?;
+            println!("Updated rates for '{}' from {}", card.name, effective_from);
+        }
+        Command::Reconcile {
+            card,
+            date,
+            bank_miles,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            let date = date.unwrap_or_else(db::today);
+            let Some(rec) = store.reconcile_cycle(card_id, &date, bank_miles)? else {
+                return Err(CcTrackerError::card_not_found(card_id));
+            };
+            println!(
+                "{} cycle {} to {}: tracked {:.0} miles, bank reported {:.0} (difference {:+.0})",
+                rec.card_name,
+                rec.cycle_start,
+                rec.cycle_end,
+                rec.tracked_miles,
+                rec.bank_miles,
+                rec.difference
+            );
+            if rec.difference == 0.0 {
+                println!("Miles match the bank.");
+                return Ok(Outcome::Success);
+            }
+            if rec.statement_level_miles == rec.bank_miles {
+                println!("The bank's figure matches block rounding on the cycle total rather than per transaction.");
+            }
+            println!("Transactions most likely responsible:");
+            println!("{}", Table::new(rec.suspects));
+        }
+        Command::CloseCycle {
+            card,
+            through,
+            reopen,
+        } => {
+            let card = card.resolve(store)?;
+            let card_id = card.id;
+            if reopen {
+                if !store.reopen_cycle(card_id, &through)? {
+                    return Err(CcTrackerError::Invalid(format!(
+                        "No closed cycle contains {} on card {}",
+                        through, card_id
+                    )));
+                }
+                println!("Reopened cycle containing {} on card {}", through, card_id);
+                return Ok(Outcome::Success);
+            }
+            if let Some(existing) = store.closed_cycle_containing(card_id, &through)? {
+                return Err(CcTrackerError::Invalid(format!(
+                    "Cycle {} to {} is already closed; use --reopen to unlock it",
+                    existing.cycle_start, existing.cycle_end
+                )));
+            }
+            // Closing mid-cycle would freeze the rest of the cycle too
+            let (cycle_start, cycle_end) = engine::card_cycle(&card, &through);
+            if through != cycle_end {
+                return Err(CcTrackerError::Invalid(format!(
+                    "{} is not the last day of its statement cycle ({} to {}); use --through {}",
+                    through, cycle_start, cycle_end, cycle_end
+                )));
+            }
+            match store.close_cycle(card_id, &through)? {
+                Some(closed) => println!(
+                    "Closed cycle {} to {} on card {}: {} transactions, ${:.2} spent, {:.0} miles",
+                    closed.cycle_start,
+                    closed.cycle_end,
+                    card_id,
+                    closed.transaction_count,
+                    closed.total_spend,
+                    closed.total_miles
+                ),
+                None => {
+                    return Err(CcTrackerError::card_not_found(card_id));
+                }
+            }
+        }
+        Command::ClosedCycles { card, output } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let cycles = store.list_closed_cycles(card_id)?;
+            print_rows(&cycles, &output, format)?;
+        }
+        Command::AddPayment {
+            card,
+            amount,
+            date,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            let date = date.unwrap_or_else(db::today);
+            let id = store.add_payment(card_id, amount, &date)?;
+            println!("Recorded ${:.2} payment to card {} on {} (ID {})", amount, card_id, date, id);
+        }
+        Command::ListPayments {
+            card,
+            period,
+            output,
+        } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let mut payments = store.list_payments(card_id)?;
+            if let Some(ranges) = card_ranges(store, period.as_ref())? {
+                payments.retain(|p| ranges.contains(p.card_id, &p.date));
+            }
+            print_rows(&payments, &output, format)?;
+        }
+        Command::AddCharge {
+            card,
+            kind,
+            amount,
+            date,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            if !CHARGE_KINDS.contains(&kind.as_str()) {
+                return Err(CcTrackerError::Invalid(format!(
+                    "Unknown charge kind '{}' (expected one of: {})",
+                    kind, CHARGE_KINDS.join(", ")
+                )));
+            }
+            let date = date.unwrap_or_else(db::today);
+            let id = store.add_charge(card_id, &kind, amount, &date)?;
+            println!("Recorded ${:.2} {} on card {} on {} (ID {})", amount, kind, card_id, date, id);
+        }
+        Command::ListCharges {
+            card,
+            period,
+            output,
+        } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let mut charges = store.list_charges(card_id)?;
+            if let Some(ranges) = card_ranges(store, period.as_ref())? {
+                charges.retain(|c| ranges.contains(c.card_id, &c.date));
+            }
+            print_rows(&charges, &output, format)?;
+        }
+        Command::AddPartnership {
+            card,
+            merchant,
+            bonus_miles_per_dollar,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            if let Err(e) = rules::merchant_pattern(&merchant) {
+                return Err(CcTrackerError::Invalid(e.to_string()));
+            }
+            let id = store.add_partnership(card_id, &merchant, bonus_miles_per_dollar)?;
+            println!(
+                "Card {} now earns {} extra miles per block at merchants matching '{}' (ID {})",
+                card_id, bonus_miles_per_dollar, merchant, id
+            );
+        }
+        Command::ListPartnerships { card, output } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            let partnerships = store.list_partnerships(card_id)?;
+            print_rows(&partnerships, &output, format)?;
+        }
+        Command::RemovePartnership { id } => {
+            if store.remove_partnership(id)? {
+                println!("Removed partnership {}", id);
+            } else {
+                return Err(CcTrackerError::NotFound { what: "partnership", id });
+            }
+        }
+        Command::AddPromotion {
+            card,
+            category,
+            miles_per_dollar,
+            start,
+            end,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            if start > end {
+                return Err(CcTrackerError::Invalid("The promotion ends before it starts".to_string()));
+            }
+            if miles_per_dollar < 0.0 {
+                return Err(CcTrackerError::Invalid("--miles-per-dollar cannot be negative".to_string()));
+            }
+            let id = store.add_promotion(card_id, &category, miles_per_dollar, &start, &end)?;
+            println!(
+                "Card {} earns {} miles per block on {} from {} to {} (promotion {})",
+                card_id,
+                miles_per_dollar,
+                category,
+                dates::display(&start),
+                dates::display(&end),
+                id
+            );
+        }
+        Command::ListPromotions { card, output } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            print_rows(&store.list_promotions(card_id)?, &output, format)?;
+        }
+        Command::RemovePromotion { id } => {
+            if store.remove_promotion(id)? {
+                println!("Removed promotion {}", id);
+            } else {
+                return Err(CcTrackerError::NotFound { what: "promotion", id });
+            }
+        }
+        Command::AddRule { pattern, category, regex } => {
+            let syntax = if regex { PatternSyntax::Regex } else { PatternSyntax::Glob };
+            rules::stored_pattern(&pattern, syntax).map_err(CcTrackerError::Invalid)?;
+            let category = category.to_lowercase();
+            let id = store.add_merchant_rule(&pattern, syntax, &category)?;
+            println!("Merchants matching '{}' are now {} (rule {})", pattern, category, id);
+        }
+        Command::ListRules { output } => {
+            print_rows(&store.list_merchant_rules()?, &output, format)?;
+        }
+        Command::RemoveRule { id } => {
+            if store.remove_merchant_rule(id)? {
+                println!("Removed rule {}", id);
+            } else {
+                return Err(CcTrackerError::NotFound { what: "rule", id });
+            }
+        }
+        Command::OverrideCycle {
+            card,
+            month,
+            start,
+            end,
+            clear,
+        } => {
+            let card = card.resolve(store)?;
+            let card_id = card.id;
+            if !period::valid_date(&format!("{}-01", month)) {
+                return Err(CcTrackerError::InvalidDate(format!("Invalid month '{}'; use YYYY-MM", month)));
+            }
+            let mut overrides = card.cycle_overrides();
+            if clear {
+                if overrides.remove(&month).is_none() {
+                    return Err(CcTrackerError::Invalid(format!(
+                        "Card {} has no cycle override for {}",
+                        card_id, month
+                    )));
+                }
+                store.set_cycle_overrides(card_id, &overrides)?;
+                println!("Removed the {} cycle override from card {}", month, card_id);
+                return Ok(Outcome::Success);
+            }
+            let (start, end) = (start.unwrap_or_default(), end.unwrap_or_default());
+            if start > end {
+                return Err(CcTrackerError::Invalid("The cycle ends before it starts".to_string()));
+            }
+            if let Some((other, _)) =
+                overrides.iter().find(|(m, o)| **m != month && o.start <= end && start <= o.end)
+            {
+                return Err(CcTrackerError::Invalid(format!(
+                    "The cycle overlaps the {} override; change or clear that one first",
+                    other
+                )));
+            }
+            overrides.insert(month.clone(), CycleOverride { start, end });
+            store.set_cycle_overrides(card_id, &overrides)?;
+            let cycle = &overrides[&month];
+            println!(
+                "Card {}'s {} statement cycle now runs {} to {}",
+                card_id, month, cycle.start, cycle.end
+            );
+        }
+        Command::SetBalance {
+            card,
+            starting_balance,
+            balance_alert,
+        } => {
+            let card_id = card.resolve(store)?.id;
+            if store.set_balance_settings(card_id, starting_balance, balance_alert)? {
+                println!("Updated balance settings for card {}", card_id);
+            } else {
+                return Err(CcTrackerError::card_not_found(card_id));
+            }
+        }
+        Command::Summary { as_of, period, output } => {
+            let balances = match card_ranges(store, period.as_ref())? {
+                Some(ranges) => period::outstanding_balances(store, &ranges)?,
+                None => store.outstanding_balances(&as_of.unwrap_or_else(db::today))?,
+            };
+            for balance in balances.iter().filter(|b| b.over_alert && output.is_table(format)) {
+                println!(
+                    "Warning: '{}' owes ${:.2}, above its ${:.2} alert",
+                    balance.card_name,
+                    balance.outstanding,
+                    balance.balance_alert.unwrap_or_default()
+                );
+            }
+            print_rows(&balances, &output, format)?;
+        }
+        Command::Today => {
+            print!("{}", overview::build(store, &db::today())?);
+        }
+        Command::CashFlow {
+            card,
+            period,
+            output,
+        } => {
+            let card = card.map(|card| card.resolve(store)).transpose()?;
+            let range = match (&period, &card) {
+                (None, _) => None,
+                (Some(period), None) if period.is_cycle() => {
+                    return Err(CcTrackerError::Invalid(
+                        "Cash flow adds up all cards by month; pass --card to use a card's cycle".to_string(),
+                    ));
+                }
+                (Some(period), None) => period.range(None, &db::today()),
+                (Some(period), Some(card)) => period.card_range(card, &db::today()),
+            };
+            let flow = store.cash_flow(card.map(|card| card.id), range.as_ref())?;
+            print_rows(&flow, &output, format)?;
+        }
+        Command::Stats { by, card, period, output } => {
+            let card = card.map(|card| card.resolve(store)).transpose()?;
+            let range = match (&period, &card) {
+                (None, _) => None,
+                (Some(period), None) if period.is_cycle() => {
+                    return Err(CcTrackerError::Invalid(
+                        "Stats add up all cards' spending; pass --card to use a card's cycle".to_string(),
+                    ));
+                }
+                (Some(period), None) => period.range(None, &db::today()),
+                (Some(period), Some(card)) => period.card_range(card, &db::today()),
+            };
+            print_rows(&store.spending_stats(by, card.map(|card| card.id), range.as_ref())?, &output, format)?;
+        }
+        Command::PlanTrip { file, output } => {
+            let trip = trip::read(&file).map_err(CcTrackerError::Invalid)?;
+            let cards = store.list_cards()?;
+            if let Some(name) = trip.fx_fees.keys().find(|name| !cards.iter().any(|c| &c.name == *name)) {
+                return Err(CcTrackerError::Invalid(format!("fx_fees names no card called '{}'", name)));
+            }
+            let legs = trip::plan(store, &trip)?;
+            print_rows(&legs, &output, format)?;
+            if output.is_table(format) {
+                let miles: f64 = legs.iter().map(|l| l.miles).sum();
+                let fees: f64 = legs.iter().map(|l| l.fx_fee).sum();
+                println!(
+                    "Total: {:.0} miles for ${:.2} in FX fees, worth ${:.2} net at ${} a mile",
+                    miles,
+                    fees,
+                    miles * trip.mile_value - fees,
+                    trip.mile_value
+                );
+                let unplanned = legs.iter().filter(|l| l.card_name.is_none()).count();
+                if unplanned > 0 {
+                    println!("{} expense(s) earn on no card; see the note column", unplanned);
+                }
+            }
+        }
+        Command::PreviewCycle { card, as_of } => {
+            let card = card.resolve(store)?;
+            print!("{}", rollover::preview(store, &card, &as_of.unwrap_or_else(db::today))?);
+        }
+        Command::Statement { card, cycle, output } => {
+            let card = card.resolve(store)?;
+            let (start, end) = match cycle {
+                Some(cycle) => {
+                    let (year, month) = digest::parse_month(&cycle).map_err(CcTrackerError::InvalidDate)?;
+                    engine::statement_cycle(&card, year, month)
+                }
+                None => engine::card_cycle(&card, &db::today()),
+            };
+            let statement = statement::reconstruct(store, &card, &start, &end)?;
+            print_rows(&statement.lines, &output, format)?;
+            if output.is_table(format) {
+                print!("{}", statement::render_totals(&statement));
+            }
+        }
+        Command::CycleSummary { card, date, output } => {
+            let date = date.unwrap_or_else(db::today);
+            let cards = match card {
+                Some(card) => vec![card.resolve(store)?],
+                None => store.list_cards()?.into_iter().filter(|c| !c.is_closed_on(&date)).collect(),
+            };
+            let rows = cards
+                .iter()
+                .map(|card| rollover::summary(store, card, &date))
+                .collect::<error::Result<Vec<_>>>()?;
+            print_rows(&rows, &output, format)?;
+        }
+        Command::SpendVelocity { by, as_of, output } => {
+            let as_of = as_of.unwrap_or_else(db::today);
+            let rows = velocity::report(store, &as_of, by)?;
+            print_rows(&rows, &output, format)?;
+        }
+        Command::Due { card, as_of, output } => run_due(store, card, as_of, output, format)?,
+        Command::Forecast { card, days, as_of, output } => {
+            let card = card.map(|card| card.resolve(store)).transpose()?;
+            let as_of = as_of.unwrap_or_else(db::today);
+            print_rows(&forecast::forecast(store, card.as_ref(), &as_of, days)?, &output, format)?;
+        }
+        Command::Balances { as_of, by, output } => {
+            let as_of = as_of.unwrap_or_else(db::today);
+            match by {
+                BalancesBy::Card => print_rows(&store.card_balances(&as_of)?, &output, format)?,
+                BalancesBy::Program => print_rows(&pool::program_balances(store, &as_of)?, &output, format)?,
+            }
+        }
+        Command::Balance { card, program, as_of, by, output } => {
+            let as_of = as_of.unwrap_or_else(db::today);
+            if card.is_none() && program.is_none() {
+                print_rows(&ledger::accounts(store, &as_of, by)?, &output, format)?;
+            } else {
+                let account = miles_account(store, card.as_ref(), program.as_deref())?;
+                print_rows(&ledger::statement(store, &account, &as_of)?, &output, format)?;
+            }
+        }
+        Command::AdjustMiles { card, program, miles, to, date, note } => {
+            let account = miles_account(store, card.as_ref(), program.as_deref())?;
+            let date = date.unwrap_or_else(db::today);
+            let before = ledger::balance(store, &account, &date)?;
+            let miles = match (miles, to) {
+                (Some(miles), _) => miles,
+                (None, to) => engine::round_cents(to.unwrap_or_default() - before),
+            };
+            if miles == 0.0 {
+                return Err(CcTrackerError::Invalid(format!("The balance is already {:.0} miles", before)));
+            }
+            let (card_id, program) = account.parts();
+            let note = note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+            store.add_miles_entry(card_id, program, "adjustment", miles, &date, note)?;
+            println!("Adjusted by {:+.0} miles; the balance on {} is {:.0}", miles, date, before + miles);
+        }
+        Command::ExpiringMiles { within, as_of, output } => {
+            let as_of = as_of.unwrap_or_else(db::today);
+            let within = within.or_else(|| config::loaded().and_then(|c| c.expiry_horizon_days)).unwrap_or(90);
+            let rows = ledger::expiring(store, &as_of, &engine::add_days(&as_of, within))?;
+            print_rows(&rows, &output, format)?;
+            if output.is_table(format) {
+                let miles: f64 = rows.iter().map(|row| row.miles).sum();
+                println!("{:.0} miles expire in the next {} days", miles, within);
+            }
+        }
+        Command::AddRedemption { card, program, miles, cash_value, date, description } => {
+            if miles <= 0.0 {
+                return Err(CcTrackerError::Invalid("--miles must be above 0".to_string()));
+            }
+            if cash_value < 0.0 {
+                return Err(CcTrackerError::Invalid("--cash-value cannot be negative".to_string()));
+            }
+            let account = miles_account(store, card.as_ref(), program.as_deref())?;
+            let date = date.unwrap_or_else(db::today);
+            let available = ledger::balance(store, &account, &date)?;
+            let (card_id, program) = account.parts();
+            let description = description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+            let id = store.add_redemption(card_id, program, miles, cash_value, &date, description)?;
+            println!(
+                "Recorded redemption {}: {:.0} miles for ${:.2}, {:.2} cents a mile",
+                id,
+                miles,
+                cash_value,
+                engine::cents_per_mile(miles, cash_value)
+            );
+            if miles > available {
+                println!("Warning: the ledger held only {:.0} miles on {}; it now shows {:.0}", available, date, available - miles);
+            }
+        }
+        Command::Redemptions { output } => {
+            let redemptions = store.list_redemptions()?;
+            print_rows(&redemptions, &output, format)?;
+            if output.is_table(format) && !redemptions.is_empty() {
+                let miles: f64 = redemptions.iter().map(|r| r.miles).sum();
+                let cash: f64 = redemptions.iter().map(|r| r.cash_value).sum();
+                println!(
+                    "Average: {:.2} cents a mile over {} redemption(s), {:.0} miles for ${:.2}",
+                    engine::cents_per_mile(miles, cash),
+                    redemptions.len(),
+                    miles,
+                    cash
+                );
+            }
+        }
+        Command::Programs { action } => run_programs(store, action, format)?,
+        Command::FxRates { action } => run_fx_rates(store, action, format)?,
+        Command::Category { action } => run_category(store, action, format)?,
+        Command::Budget { month, output } => {
+            let month = month.unwrap_or_else(|| db::today()[..7].to_string());
+            let (year, number) = digest::parse_month(&month).map_err(CcTrackerError::InvalidDate)?;
+            let rows = budget::report(store, year, number)?;
+            if !output.is_table(format) {
+                print_rows(&rows, &output, format)?;
+            } else if rows.is_empty() {
+                println!("No category has a budget; set one with `category budget`");
+            } else {
+                print!("{}", budget::render(&rows));
+            }
+        }
+        Command::CardValue { year, mile_value, output } => {
+            let year = year.unwrap_or_else(|| engine::parse_date(&db::today()).0);
+            let Some(mile_value) = mile_value.or_else(|| config::loaded().and_then(|c| c.mile_value)) else {
+                return Err(CcTrackerError::Invalid("Pass --mile-value or set mile_value in config.toml".to_string()));
+            };
+            if mile_value <= 0.0 {
+                return Err(CcTrackerError::Invalid("--mile-value must be above 0".to_string()));
+            }
+            print_rows(&fees::card_values(store, year, mile_value)?, &output, format)?;
+        }
+        Command::Digest { month, out, send } => {
+            let month = month.unwrap_or_else(|| digest::previous_month(&db::today()));
+            let (year, number) = digest::parse_month(&month).map_err(CcTrackerError::InvalidDate)?;
+            let command = if send {
+                match config::load() {
+                    Ok(config::Config {
+                        digest_command: Some(command),
+                        ..
+                    }) => Some(command),
+                    Ok(_) => {
+                        return Err(CcTrackerError::Invalid(format!(
+                            "Set digest_command in {} to send digests",
+                            config::config_path().display()
+                        )));
+                    }
+                    Err(e) => {
+                        return Err(CcTrackerError::Invalid(e.to_string()));
+                    }
+                }
+            } else {
+                None
+            };
+
+            let markdown = digest::build(store, year, number)?;
+            match &out {
+                None => print!("{}", markdown),
+                Some(path) => match std::fs::write(path, &markdown) {
+                    Ok(()) => println!("Wrote the {} digest to {}", month, path.display()),
+                    Err(e) => {
+                        return Err(CcTrackerError::Invalid(format!("Failed to write {}: {}", path.display(), e)));
+                    }
+                },
+            }
+            if let Some(command) = command {
+                match digest::send(&command, &digest::subject(year, number), &markdown) {
+                    Ok(()) => println!("Sent the {} digest", month),
+                    Err(e) => println!("{}", e),
+                }
+            }
+        }
+        Command::Report { month, format, chart, out } => {
+            let month = month.unwrap_or_else(|| digest::previous_month(&db::today()));
+            let (year, number) = digest::parse_month(&month).map_err(CcTrackerError::InvalidDate)?;
+            let report = report::build(store, year, number, format, chart)?;
+            match &out {
+                None => print!("{}", report),
+                Some(path) => match std::fs::write(path, &report) {
+                    Ok(()) => println!("Wrote the {} report to {}", month, path.display()),
+                    Err(e) => {
+                        return Err(CcTrackerError::Invalid(format!("Failed to write {}: {}", path.display(), e)));
+                    }
+                },
+            }
+        }
+        Command::Generate {
+            cards,
+            transactions,
+            seed,
+        } => {
+            if cards == 0 {
+                return Err(CcTrackerError::Invalid("Generate at least one card".to_string()));
+            }
+            if !store.list_cards()?.is_empty() {
+                return Err(CcTrackerError::Invalid(
+                    "This database already has cards; generate into an empty one".to_string(),
+                ));
+            }
+            let started = std::time::Instant::now();
+            let generated = bench::generate(store, cards, transactions, seed, &db::today())?;
+            println!(
+                "Generated {} cards, {} transactions and {} payments in {:.1}s",
+                generated.cards,
+                generated.transactions,
+                generated.payments,
+                started.elapsed().as_secs_f64()
+            );
+        }
+        Command::Bench { runs, queries, output } => {
+            if runs == 0 {
+                return Err(CcTrackerError::Invalid("Time at least one run".to_string()));
+            }
+            if let Some(unknown) = queries.iter().find(|q| !bench::QUERIES.contains(&q.as_str())) {
+                return Err(CcTrackerError::Invalid(format!(
+                    "Unknown query '{}'; choose from {}",
+                    unknown, bench::QUERIES.join(", ")
+                )));
+            }
+            let timings = bench::bench(store, runs, &db::today(), &queries)?;
+            print_rows(&timings, &output, format)?;
+        }
+    }
+    Ok(Outcome::Success)
+}
+
+/// Runs `due`: payments owed by due date, then how many cards have none set.
+fn run_due<S: Store + ?Sized>(
+    store: &S,
+    card: Option<CardRef>,
+    as_of: Option<String>,
+    output: OutputArgs,
+    format: OutputFormat,
+) -> error::Result<()> {
+    let card = card.map(|card| card.resolve(store)).transpose()?;
+    let as_of = as_of.unwrap_or_else(db::today);
+    let rows = due::upcoming(store, card.as_ref(), &as_of)?;
+    print_rows(&rows, &output, format)?;
+    let unknown = match &card {
+        Some(card) => usize::from(card.payment_due_days.is_none()),
+        None => store.list_cards()?.iter().filter(|c| c.payment_due_days.is_none() && !c.is_closed_on(&as_of)).count(),
+    };
+    if unknown > 0 && output.is_table(format) {
+        println!("{} card(s) have no payment due date; set one with edit-card --payment-due-days", unknown);
+    }
+    Ok(())
+}
+
+/// Runs a `programs` subcommand.
+fn run_programs<S: Store + ?Sized>(store: &S, action: ProgramsAction, format: OutputFormat) -> error::Result<()> {
+    match action {
+        ProgramsAction::Add { name, kind, expiry_months } => {
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                return Err(CcTrackerError::Invalid("The program needs a name".to_string()));
+            }
+            if program_named(store, &name).is_ok() {
+                return Err(CcTrackerError::Invalid(format!("Program '{}' already exists", name)));
+            }
+            let id = store.add_program(&name, kind, expiry_months)?;
+            println!("Added {} program '{}' with ID {}", kind, name, id);
+        }
+        ProgramsAction::List { output } => print_rows(&store.list_programs()?, &output, format)?,
+        ProgramsAction::SetExpiry { name, months, never: _ } => {
+            let program = program_named(store, &name)?;
+            store.set_program_expiry(program.id, months)?;
+            match months {
+                Some(months) => println!("{} miles now expire {} months after they are credited", program.name, months),
+                None => println!("{} miles no longer expire", program.name),
+            }
+        }
+        ProgramsAction::Remove { id } => {
+            if !store.remove_program(id)? {
+                return Err(CcTrackerError::NotFound { what: "program", id });
+            }
+            println!("Removed program {}", id);
+        }
+        ProgramsAction::Cards { output } => print_rows(&pool::memberships(store)?, &output, format)?,
+        ProgramsAction::AddTransfer { from, to, ratio } => {
+            let (from, to) = (program_named(store, &from)?, program_named(store, &to)?);
+            if from.id == to.id {
+                return Err(CcTrackerError::Invalid("A program cannot transfer to itself".to_string()));
+            }
+            if ratio <= 0.0 {
+                return Err(CcTrackerError::Invalid("--ratio must be above 0".to_string()));
+            }
+            let id = store.add_program_transfer(from.id, to.id, ratio)?;
+            println!("Added transfer {}: {} to {} at {} {} each", id, from.name, to.name, ratio, to.kind.unit());
+        }
+        ProgramsAction::Transfers { output } => print_rows(&store.list_program_transfers()?, &output, format)?,
+        ProgramsAction::RemoveTransfer { id } => {
+            if !store.remove_program_transfer(id)? {
+                return Err(CcTrackerError::NotFound { what: "transfer", id });
+            }
+            println!("Removed transfer {}", id);
+        }
+        ProgramsAction::Convert { from, to, amount } => {
+            let (from, to) = (program_named(store, &from)?, program_named(store, &to)?);
+            let Some(transfer) = store
+                .list_program_transfers()?
+                .into_iter()
+                .find(|t| t.from_program_id == from.id && t.to_program_id == to.id)
+            else {
+                return Err(CcTrackerError::Invalid(format!(
+                    "{} has no transfer to {}; add one with `programs add-transfer`",
+                    from.name, to.name
+                )));
+            };
+            let amount = match amount {
+                Some(amount) => amount,
+                None => pool::program_balances(store, &db::today())?
+                    .into_iter()
+                    .find(|balance| balance.program.eq_ignore_ascii_case(&from.name))
+                    .map_or(0.0, |balance| balance.credited_miles),
+            };
+            println!(
+                "{:.0} {} in {} transfer to {:.0} {} in {} at {} each",
+                amount,
+                from.kind.unit(),
+                from.name,
+                (amount * transfer.ratio).floor(),
+                to.kind.unit(),
+                to.name,
+                transfer.ratio
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Runs an `fx-rates` subcommand.
+fn run_fx_rates<S: Store + ?Sized>(store: &S, action: FxRatesAction, format: OutputFormat) -> error::Result<()> {
+    match action {
+        FxRatesAction::Set { currency, rate } => {
+            let currency = fx::currency_code(&currency)?;
+            if rate <= 0.0 {
+                return Err(CcTrackerError::Invalid("--rate must be above 0".to_string()));
+            }
+            store.set_fx_rate(&currency, rate)?;
+            println!("1 {} is now {} in the home currency", currency, rate);
+        }
+        FxRatesAction::List { output } => print_rows(&store.list_fx_rates()?, &output, format)?,
+        FxRatesAction::Remove { currency } => {
+            let currency = fx::currency_code(&currency)?;
+            if !store.remove_fx_rate(&currency)? {
+                return Err(CcTrackerError::UnknownName { what: "FX rate", name: currency, suggestion: None });
+            }
+            println!("Removed the {} rate", currency);
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `recurring` subcommand.
+fn run_recurring<S: Store + ?Sized>(store: &S, action: RecurringAction, format: OutputFormat) -> error::Result<()> {
+    match action {
+        RecurringAction::Add { card, amount, category, day, merchant, start } => {
+            let card_id = card.resolve(store)?.id;
+            if amount <= 0.0 {
+                return Err(CcTrackerError::Invalid("--amount must be above 0".to_string()));
+            }
+            let category = checked_category(store, category)?;
+            let start = start.unwrap_or_else(db::today);
+            let id = store.add_recurring(card_id, amount, &category, day, merchant.as_deref(), &start)?;
+            println!(
+                "Card {} is charged ${:.2} for '{}' on day {} of every month, next on {} (recurring {})",
+                card_id,
+                amount,
+                category,
+                day,
+                dates::display(&engine::next_monthly_date(day, &start)),
+                id
+            );
+        }
+        RecurringAction::List { card, output } => {
+            let card_id = card_id_of(store, card.as_ref())?;
+            print_rows(&store.list_recurring(card_id)?, &output, format)?;
+        }
+        RecurringAction::Remove { id } => {
+            if store.remove_recurring(id)? {
+                println!("Removed recurring charge {}", id);
+            } else {
+                return Err(CcTrackerError::NotFound { what: "recurring charge", id });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `category` subcommand.
+fn run_category<S: Store + ?Sized>(store: &S, action: CategoryAction, format: OutputFormat) -> error::Result<()> {
+    let known = |name: &str| -> error::Result<Option<String>> {
+        let categories = store.list_categories()?;
+        Ok(categories.into_iter().map(|c| c.name).find(|c| c.eq_ignore_ascii_case(name)))
+    };
+    match action {
+        CategoryAction::Add { name } => {
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                return Err(CcTrackerError::Invalid("--name must not be empty".to_string()));
+            }
+            if let Some(existing) = known(&name)? {
+                return Err(CcTrackerError::Invalid(format!("Category '{}' already exists", existing)));
+            }
+            store.add_category(&name)?;
+            println!("Added category '{}'", name);
+        }
+        CategoryAction::List { output } => print_rows(&store.list_categories()?, &output, format)?,
+        CategoryAction::Rename { from, to } => {
+            let from = category_named(store, &from)?;
+            let to = to.trim().to_string();
+            if to.is_empty() {
+                return Err(CcTrackerError::Invalid("--to must not be empty".to_string()));
+            }
+            if let Some(existing) = known(&to)?
+                && !existing.eq_ignore_ascii_case(&from)
+            {
+                return Err(CcTrackerError::Invalid(format!(
+                    "Category '{}' already exists; use `category merge` to fold '{}' into it",
+                    existing, from
+                )));
+            }
+            let (cards, transactions, frozen) = store.rename_category(&from, &to)?;
+            println!("Renamed '{}' to '{}' on {} cards and {} transactions", from, to, cards, transactions);
+            print_frozen_category(&from, frozen);
+        }
+        CategoryAction::Merge { from, into } => {
+            let from = category_named(store, &from)?;
+            let into = category_named(store, &into)?;
+            if from.eq_ignore_ascii_case(&into) {
+                return Err(CcTrackerError::Invalid("--from and --into are the same category".to_string()));
+            }
+            let (cards, transactions, frozen) = store.rename_category(&from, &into)?;
+            println!("Merged '{}' into '{}' on {} cards and {} transactions", from, into, cards, transactions);
+            print_frozen_category(&from, frozen);
+        }
+        CategoryAction::Budget { name, amount, clear } => {
+            let name = category_named(store, &name)?;
+            if clear {
+                store.set_category_budget(&name, None)?;
+                println!("Removed the budget for '{}'", name);
+            } else if let Some(amount) = amount.filter(|amount| *amount > 0.0) {
+                store.set_category_budget(&name, Some(amount))?;
+                println!("Budgeted ${:.2} a month for '{}'", amount, name);
+            } else {
+                return Err(CcTrackerError::Invalid("--amount must be above 0".to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Notes the transactions a rename left alone because their cycle is closed.
+fn print_frozen_category(from: &str, frozen: usize) {
+    if frozen > 0 {
+        println!("{} transactions in closed cycles keep '{}'; reopen their cycles to rename them too", frozen, from);
+    }
+}
+
+/// Prints the summary shown before an import is confirmed.
+fn print_import_preview(preview: &ImportPreview) {
+    println!(
+        "{} rows: {} to import (${:.2}), {} duplicates, {} for review",
+        preview.rows,
+        preview.to_import,
+        preview.total_amount,
+        preview.duplicates.len(),
+        preview.to_review
+    );
+    if !preview.categories.is_empty() {
+        println!("{}", Table::new(&preview.categories));
+    }
+    if !preview.duplicates.is_empty() {
+        println!("Duplicates (will be skipped):");
+        println!("{}", Table::new(&preview.duplicates));
+    }
+    if !preview.unmapped_merchants.is_empty() {
+        println!("Unmapped merchants (will go to the review queue):");
+        for merchant in &preview.unmapped_merchants {
+            println!("  {}", merchant);
+        }
+    }
+}
+
+/// Encrypts export JSON with a passphrase from the environment or typed twice.
+fn encrypt_export(json: &str) -> std::result::Result<String, String> {
+    encrypt::encrypt(json, &encrypt::passphrase(true)?)
+}
+
+/// Restores an export read from `source` (a file or URL) into an empty store,
+/// asking for the passphrase of an encrypted one, and refuses a failed read
+/// or an invalid export.
+fn restore_from<S: Store + ?Sized>(
+    store: &S,
+    text: std::result::Result<String, String>,
+    source: &str,
+) -> error::Result<Outcome> {
+    let text = text.and_then(|text| {
+        if encrypt::is_encrypted(&text) {
+            encrypt::decrypt(&text, &encrypt::passphrase(false)?)
+        } else {
+            Ok(text)
+        }
+    });
+    let data = text.and_then(|text| export::parse(&text)).map_err(CcTrackerError::Invalid)?;
+    if !store.list_cards()?.is_empty() {
+        return Err(CcTrackerError::Invalid(
+            "This database already has cards; restore into an empty one, or use merge-db".to_string(),
+        ));
+    }
+    let summary = export::restore(store, &data)?;
+    println!(
+        "Restored {} cards, {} transactions, {} payments, {} charges and {} other records from {}",
+        summary.cards, summary.spending, summary.payments, summary.charges, summary.other, source
+    );
+    Ok(Outcome::Success)
+}
+
+/// Resolves a --period for every card as of today, or None without one.
+fn card_ranges<S: Store + ?Sized>(store: &S, period: Option<&Period>) -> error::Result<Option<CardRanges>> {
+    match period {
+        Some(period) => Ok(Some(period.card_ranges(&store.list_cards()?, &db::today()))),
+        None => Ok(None),
+    }
+}
+
+/// Prints rows as a table, as JSON, or one line each from the --template.
+/// A template that cannot be read or rendered prints nothing.
+fn print_rows<T: Tabled + Serialize>(rows: &[T], output: &OutputArgs, format: OutputFormat) -> error::Result<()> {
+    let Some(template) = output_template(output).map_err(CcTrackerError::Invalid)? else {
+        match format {
+            OutputFormat::Table => println!("{}", Table::new(rows)),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows).expect("rows serialize to JSON")),
+            OutputFormat::JsonCompact => println!("{}", serde_json::to_string(rows).expect("rows serialize to JSON")),
+        }
+        return Ok(());
+    };
+    let lines = rows.iter().map(|row| template.render(row)).collect::<std::result::Result<Vec<_>, _>>();
+    lines.map_err(CcTrackerError::Invalid)?.iter().for_each(|line| println!("{}", line));
+    Ok(())
+}
+
+/// The template given by `--template` or `--template-file`, if any.
+fn output_template(output: &OutputArgs) -> std::result::Result<Option<Template>, String> {
+    let text = match (&output.template, &output.template_file) {
+        (Some(text), _) => text.clone(),
+        (None, Some(path)) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            // A template file holds one line, usually newline-terminated
+            text.strip_suffix('\n').unwrap_or(&text).to_string()
+        }
+        (None, None) => return Ok(None),
+    };
+    Template::parse(&text).map(Some)
+}
+
+/// Rows per table when streaming a listing; each block is printed as its own
+/// table once full.
+const TABLE_BLOCK_ROWS: usize = 1000;
+
+/// Prints a listing as its rows are read instead of collecting them first:
+/// template lines and JSON array elements one at a time, tables in blocks
+/// of `TABLE_BLOCK_ROWS`.
+struct RowStream<T> {
+    template: Option<Template>,
+    format: OutputFormat,
+    block: Vec<T>,
+    /// Whether any row has been pushed
+    started: bool,
+    out: BufWriter<Stdout>,
+    /// Set once stdout is closed (e.g. piped into `head`)
+    closed: bool,
+}
+
+impl<T: Tabled + Serialize> RowStream<T> {
+    fn new(output: &OutputArgs, format: OutputFormat) -> std::result::Result<RowStream<T>, String> {
+        Ok(RowStream {
+            template: output_template(output)?,
+            format,
+            block: Vec::new(),
+            started: false,
+            out: BufWriter::new(std::io::stdout()),
+            closed: false,
+        })
+    }
+
+    fn push(&mut self, row: T) -> error::Result<()> {
+        let first = !self.started;
+        self.started = true;
+        if let Some(template) = &self.template {
+            let line = template.render(&row).map_err(CcTrackerError::Invalid)?;
+            return self.write(&line);
+        }
+        match self.format {
+            OutputFormat::Table => {
+                self.block.push(row);
+                if self.block.len() < TABLE_BLOCK_ROWS {
+                    return Ok(());
+                }
+                self.write_block()
+            }
+            // Elements are written without their trailing newline, which
+            // comes after the comma before the next one
+            OutputFormat::Json => {
+                let element = serde_json::to_string_pretty(&row).expect("rows serialize to JSON").replace('\n', "\n  ");
+                self.write_raw(&format!("{}  {}", if first { "[\n" } else { ",\n" }, element))
+            }
+            OutputFormat::JsonCompact => {
+                let element = serde_json::to_string(&row).expect("rows serialize to JSON");
+                self.write_raw(&format!("{}{}", if first { "[" } else { "," }, element))
+            }
+        }
+    }
+
+    fn finish(&mut self) -> error::Result<()> {
+        if self.template.is_none() {
+            match self.format {
+                // An empty listing still prints the table's header
+                OutputFormat::Table if !self.block.is_empty() || !self.started => self.write_block()?,
+                OutputFormat::Table => {}
+                _ if !self.started => self.write("[]")?,
+                OutputFormat::Json => self.write("\n]")?,
+                OutputFormat::JsonCompact => self.write("]")?,
+            }
+        }
+        self.out.flush().map_err(|e| self.io_error(e))
+    }
+
+    fn write_block(&mut self) -> error::Result<()> {
+        let table = Table::new(std::mem::take(&mut self.block)).to_string();
+        self.write(&table)
+    }
+
+    fn write(&mut self, text: &str) -> error::Result<()> {
+        writeln!(self.out, "{}", text).map_err(|e| self.io_error(e))
+    }
+
+    fn write_raw(&mut self, text: &str) -> error::Result<()> {
+        write!(self.out, "{}", text).map_err(|e| self.io_error(e))
+    }
+
+    fn io_error(&mut self, error: std::io::Error) -> CcTrackerError {
+        self.closed |= error.kind() == std::io::ErrorKind::BrokenPipe;
+        CcTrackerError::Invalid(format!("Failed to write the listing: {}", error))
+    }
+}
+
+fn print_split(split: &SplitSuggestion) {
+    let single = match &split.single_card {
+        Some(card) => format!("{} mi on {} alone", split.single_card_miles, card),
+        None => "no single card within its limits".to_string(),
+    };
+    println!("\nSplitting earns {} mi in total, vs {}:", split.total_miles, single);
+    println!("{}", Table::new(&split.parts));
+}
+
+/// Reports what applying a card file or shared card did with one card.
+fn print_card_sync(name: &str, sync: CardSync) {
+    match sync {
+        CardSync::Added(id) => println!("Added card '{}' with ID {}", name, id),
+        CardSync::Updated(id, changed) => println!("Updated card '{}' (ID {}): {}", name, id, changed.join(", ")),
+        CardSync::Unchanged(id) => println!("Card '{}' (ID {}) is up to date", name, id),
+        CardSync::Ambiguous => println!("Skipped '{}': several cards have that name", name),
+    }
+}
+
+/// Asks a yes/no question on stdin; anything but "y" or "yes" is a no.
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn run(store: &impl Store, args: &[&str]) -> Outcome {
+        try_run(store, args).unwrap()
+    }
+
+    fn try_run(store: &impl Store, args: &[&str]) -> error::Result<Outcome> {
+        let cli = Cli::try_parse_from(std::iter::once("backend").chain(args.iter().copied())).unwrap();
+        super::run(store, cli.command.unwrap(), cli.output)
+    }
+
+    #[test]
+    fn test_cli_runs_against_a_store() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        let card = &store.list_cards().unwrap()[0];
+        run(&store, &["add-spending", "--card-id", &card.id.to_string(), "--amount", "42", "--category", "dining"]);
+
+        let spending = store.list_spending(Some(card.id), None).unwrap();
+        assert_eq!(spending.len(), 1);
+        assert_eq!(spending[0].miles_earned, 32.0);
+    }
+
+    #[test]
+    fn test_cli_outcomes_for_scripts() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--categories", "dining", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1", "--max-reward-limit", "50"]);
+
+        let best = |amount: &str, category: &str| {
+            run(&store, &["best-card", "--quiet", "--category", category, "--amount", amount, "--payment-category", "online"])
+        };
+        assert_eq!(best("10", "dining"), Outcome::Success);
+        assert_eq!(best("100", "dining"), Outcome::OnlyIneligible);
+        assert_eq!(best("10", "travel"), Outcome::NoMatch);
+        let unknown = try_run(&store, &["close-card", "--id", "7"]).unwrap_err();
+        assert!(matches!(unknown, CcTrackerError::NotFound { what: "card", id: 7 }));
+        assert_eq!(unknown.exit_code(), 5);
+        assert_eq!(try_run(&store, &["remove-card", "--id", "7"]).unwrap_err().exit_code(), 5);
+        let refused = try_run(&store, &["add-charge", "--card-id", "1", "--kind", "tip", "--amount", "5"]).unwrap_err();
+        assert_eq!(refused.exit_code(), 4);
+    }
+
+    #[test]
+    fn test_edit_card_keeps_spending() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--categories", "dining", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        run(&store, &["add-spending", "--card-id", "1", "--amount", "42", "--category", "dining", "--date", "2026-03-05"]);
+
+        let edit = &["edit-card", "--id", "1", "--name", "Card B", "--categories", "dining,travel", "--miles-per-dollar", "2", "--renewal-date", "15", "--max-reward-limit", "800"];
+        assert_eq!(run(&store, edit), Outcome::Success);
+        let card = store.get_card(1).unwrap().unwrap();
+        assert_eq!((card.name.as_str(), card.categories.as_str()), ("Card B", r#"["dining","travel"]"#));
+        assert_eq!((card.miles_per_dollar, card.block_size), (2.0, 5.0));
+        assert_eq!((card.statement_renewal_date, card.max_reward_limit), (15, Some(800.0)));
+        // The rate is corrected rather than versioned, and past miles stay as recorded
+        assert_eq!(store.last_rate_change(1).unwrap(), None);
+        assert_eq!(store.list_spending(Some(1), None).unwrap()[0].miles_earned, 32.0);
+
+        assert_eq!(run(&store, edit), Outcome::Success);
+        assert!(try_run(&store, &["edit-card", "--id", "7", "--name", "X"]).is_err());
+    }
+
+    #[test]
+    fn test_cashback_cards_need_a_percent() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        let card = ["backend", "add-card", "--name", "Cash", "--miles-per-dollar", "0", "--block-size", "1", "--renewal-date", "1", "--reward-type", "cashback"];
+        assert!(Cli::try_parse_from(card).is_err());
+        run(&store, &[&card[1..], &["--cashback-percent", "1.5"]].concat());
+        run(&store, &["add-card", "--name", "Miles", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        assert_eq!(store.get_card(1).unwrap().unwrap().reward_type, RewardType::Cashback);
+
+        let refused = try_run(&store, &["edit-card", "--id", "2", "--reward-type", "cashback"]).unwrap_err();
+        assert_eq!(refused.exit_code(), 4);
+        run(&store, &["edit-card", "--id", "2", "--reward-type", "points"]);
+        assert_eq!(store.get_card(2).unwrap().unwrap().reward_type, RewardType::Points);
+    }
+
+    #[test]
+    fn test_cashback_is_kept_as_recorded() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Cash", "--miles-per-dollar", "0", "--block-size", "1", "--renewal-date", "1", "--reward-type", "cashback", "--cashback-percent", "1.5"]);
+        run(&store, &["add-card", "--name", "Miles", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        run(&store, &["add-spending", "--card", "cash", "--amount", "100", "--category", "dining", "--date", "2026-02-05"]);
+        run(&store, &["edit-card", "--id", "1", "--cashback-percent", "3"]);
+        run(&store, &["add-spending", "--card", "cash", "--amount", "100", "--category", "dining", "--date", "2026-02-06"]);
+        let cashback: Vec<Option<f64>> = store.list_spending(Some(1), None).unwrap().iter().map(|s| s.cashback).collect();
+        assert_eq!(cashback, [Some(3.0), Some(1.5)]);
+
+        // Only the miles card has a miles balance
+        let accounts = ledger::accounts(&store, "2026-03-31", pool::BalancesBy::Card).unwrap();
+        assert_eq!(accounts.iter().map(|a| a.account.as_str()).collect::<Vec<_>>(), ["Miles"]);
+        let refused = try_run(&store, &["balance", "--card", "cash"]).unwrap_err();
+        assert_eq!(refused.exit_code(), 4);
+    }
+
+    #[test]
+    fn test_invalid_card_settings_are_refused() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        let add = ["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"];
+        let refused = try_run(&store, &[&add[..], &["--cutoff-hour", "24", "--fx-fee", "120"]].concat()).unwrap_err();
+        assert_eq!(refused.exit_code(), 4);
+        assert!(refused.to_string().contains("fx_fee_percent: must be 0-100"), "{}", refused);
+        assert!(store.list_cards().unwrap().is_empty());
+        run(&store, &add);
+
+        let refused = try_run(&store, &["edit-card", "--id", "1", "--block-size", "0", "--renewal-date", "45", "--cutoff-hour", "99"]).unwrap_err();
+        assert_eq!(refused.exit_code(), 4);
+        let message = refused.to_string();
+        for problem in ["block_size: must be positive", "statement_renewal_date: must be 1-31", "cutoff_hour: must be 0-23"] {
+            assert!(message.contains(problem), "{}", message);
+        }
+        let card = store.get_card(1).unwrap().unwrap();
+        assert_eq!((card.block_size, card.statement_renewal_date, card.cutoff_hour), (5.0, 1, 0));
+
+        for (flag, value, problem) in [
+            ("--miles-per-dollar", "inf", "miles_per_dollar: must be a number, 0 or more"),
+            ("--miles-per-dollar", "-2", "miles_per_dollar: must be a number, 0 or more"),
+            ("--block-size", "NaN", "block_size: must be positive"),
+            ("--miles-per-dollar-foreign", "NaN", "miles_per_dollar_foreign: must be a number, 0 or more"),
+            ("--max-reward-limit", "-500", "max_reward_limit: must be a number, 0 or more"),
+            ("--min-spend", "inf", "min_spend: must be a number, 0 or more"),
+            ("--annual-fee", "-1", "annual_fee: must be a number, 0 or more"),
+            ("--starting-balance", "NaN", "starting_balance: must be a number, 0 or more"),
+            ("--posting-delay", "-1", "posting_delay: must be 0 or more"),
+        ] {
+            let refused = try_run(&store, &["edit-card", "--id", "1", &format!("{}={}", flag, value)]).unwrap_err();
+            assert_eq!(refused.exit_code(), 4, "{} {}", flag, value);
+            assert!(refused.to_string().contains(problem), "{}", refused);
+        }
+        let card = store.get_card(1).unwrap().unwrap();
+        assert_eq!((card.miles_per_dollar, card.max_reward_limit, card.posting_delay), (4.0, None, 0));
+    }
+
+    #[test]
+    fn test_amounts_must_be_finite() {
+        for args in [
+            &["add-spending", "--card", "1", "--category", "dining", "--amount", "NaN"][..],
+            &["add-spending", "--card", "1", "--category", "dining", "--amount", "inf"],
+            &["reconcile", "--card", "1", "--bank-miles=-infinity"],
+        ] {
+            let Err(refused) = Cli::try_parse_from(std::iter::once("backend").chain(args.iter().copied())) else {
+                panic!("{:?} was accepted", args);
+            };
+            assert!(refused.to_string().contains("is not an amount"), "{}", refused);
+        }
+    }
+
+    #[test]
+    fn test_cards_by_name() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        for name in ["DBS Altitude", "DBS Woman's World", "Citi Rewards"] {
+            run(&store, &["add-card", "--name", name, "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        }
+
+        run(&store, &["add-spending", "--card", "citi", "--amount", "42", "--category", "dining"]);
+        run(&store, &["add-spending", "--card", "dbs altitude", "--amount", "42", "--category", "dining"]);
+        run(&store, &["add-spending", "--card-id", "2", "--amount", "42", "--category", "dining"]);
+        let on = |card_id| store.list_spending(Some(card_id), None).unwrap().len();
+        assert_eq!((on(1), on(2), on(3)), (1, 1, 1));
+
+        let ambiguous = try_run(&store, &["close-card", "--card", "DBS"]).unwrap_err();
+        assert_eq!(ambiguous.exit_code(), 4);
+        assert!(ambiguous.to_string().contains("DBS Altitude (ID 1), DBS Woman's World (ID 2)"));
+        let unknown = try_run(&store, &["list-spending", "--card", "amex"]).unwrap_err();
+        assert!(matches!(unknown, CcTrackerError::UnknownName { what: "card", .. }));
+        assert_eq!(unknown.exit_code(), 5);
+        let typo = try_run(&store, &["list-spending", "--card", "citi rewrads"]).unwrap_err();
+        assert_eq!(typo.to_string(), "No card named 'citi rewrads'; did you mean 'Citi Rewards'?");
+        run(&store, &["remove-card", "--id", "citi rewards"]);
+        assert_eq!(store.list_cards().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_stored_rules_categorize_spending() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        run(&store, &["add-rule", "GRAB*", "Transport"]);
+        run(&store, &["add-rule", "--regex", "fair ?price", "groceries"]);
+        let bad = try_run(&store, &["add-rule", "--regex", "(unclosed", "dining"]).unwrap_err();
+        assert_eq!(bad.exit_code(), 4);
+        assert_eq!(store.list_merchant_rules().unwrap()[0].category, "transport");
+
+        run(&store, &["add-spending", "--card", "1", "--amount", "12", "--merchant", "GRAB*RIDE 1234", "--date", "2026-03-05"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "30", "--merchant", "NTUC FairPrice", "--date", "2026-03-05"]);
+        // An explicit category wins over the rules
+        run(&store, &["add-spending", "--card", "1", "--amount", "8", "--category", "dining", "--merchant", "GRAB FOOD", "--date", "2026-03-06"]);
+        let categories: Vec<String> = store.list_spending(None, None).unwrap().into_iter().map(|s| s.category).collect();
+        assert_eq!(categories, ["dining", "groceries", "transport"]);
+
+        let unmatched = ["add-spending", "--card", "1", "--amount", "5", "--merchant", "SHELL", "--date", "2026-03-06"];
+        assert!(try_run(&store, &unmatched).unwrap_err().to_string().contains("pass --category"));
+        assert!(Cli::try_parse_from(["backend", "add-spending", "--card", "1", "--amount", "5"]).is_err());
+
+        run(&store, &["remove-rule", "--id", "1"]);
+        assert_eq!(store.list_merchant_rules().unwrap().len(), 1);
+        assert_eq!(try_run(&store, &["remove-rule", "--id", "1"]).unwrap_err().exit_code(), 5);
+    }
+
+    #[test]
+    fn test_promotions_from_the_cli() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "1", "--block-size", "1", "--renewal-date", "1", "--categories", "groceries"]);
+        let promotion = ["add-promotion", "--card", "card a", "--category", "groceries", "--miles-per-dollar", "5"];
+        let backwards = [&promotion[..], &["--start", "2026-06-30", "--end", "2026-04-01"]].concat();
+        assert_eq!(try_run(&store, &backwards).unwrap_err().exit_code(), 4);
+        run(&store, &[&promotion[..], &["--start", "2026-04-01", "--end", "2026-06-30"]].concat());
+        assert_eq!(store.list_promotions(Some(1)).unwrap().len(), 1);
+
+        run(&store, &["add-spending", "--card", "1", "--amount", "10", "--category", "groceries", "--date", "2026-05-05"]);
+        assert_eq!(store.list_spending(None, None).unwrap()[0].miles_earned, 50.0);
+
+        run(&store, &["remove-promotion", "--id", "1"]);
+        assert_eq!(try_run(&store, &["remove-promotion", "--id", "1"]).unwrap_err().exit_code(), 5);
+    }
+
+    #[test]
+    fn test_programs_from_the_cli() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Altitude", "--miles-per-dollar", "2", "--block-size", "1", "--renewal-date", "1", "--program", "dbs points", "--reward-type", "points"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "500", "--category", "dining", "--date", "2025-01-05"]);
+        run(&store, &["programs", "add", "--name", "DBS Points", "--kind", "bank"]);
+        run(&store, &["programs", "add", "--name", "KrisFlyer", "--expiry-months", "36"]);
+        assert_eq!(try_run(&store, &["programs", "add", "--name", "krisflyer"]).unwrap_err().exit_code(), 4);
+        run(&store, &["programs", "set-expiry", "--name", "dbs points", "--months", "12"]);
+        run(&store, &["programs", "set-expiry", "--name", "krisflyer", "--never"]);
+        let expiry: Vec<Option<i32>> = store.list_programs().unwrap().iter().map(|p| p.expiry_months).collect();
+        assert_eq!(expiry, [Some(12), None]);
+
+        let transfer = ["programs", "add-transfer", "--from", "DBS Points", "--to", "krisflyer", "--ratio", "0.4"];
+        run(&store, &transfer);
+        let transfers = store.list_program_transfers().unwrap();
+        assert_eq!((transfers[0].from_program.as_str(), transfers[0].to_program.as_str()), ("DBS Points", "KrisFlyer"));
+        let unknown = try_run(&store, &["programs", "add-transfer", "--from", "Asia Miles", "--to", "KrisFlyer", "--ratio", "1"]);
+        assert!(matches!(unknown.unwrap_err(), CcTrackerError::UnknownName { what: "program", .. }));
+        run(&store, &["programs", "convert", "--from", "dbs points", "--to", "KrisFlyer"]);
+        let backwards = try_run(&store, &["programs", "convert", "--from", "KrisFlyer", "--to", "DBS Points", "--amount", "10"]);
+        assert_eq!(backwards.unwrap_err().exit_code(), 4);
+
+        let memberships = pool::memberships(&store).unwrap();
+        assert_eq!((memberships[0].program.as_str(), memberships[0].kind.as_str()), ("dbs points", "bank"));
+
+        run(&store, &["programs", "remove", "--id", "1"]);
+        assert!(store.list_program_transfers().unwrap().is_empty());
+        assert_eq!(try_run(&store, &["programs", "remove-transfer", "--id", "1"]).unwrap_err().exit_code(), 5);
+    }
+
+    #[test]
+    fn test_adjust_miles_from_the_cli() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Altitude", "--miles-per-dollar", "2", "--block-size", "1", "--renewal-date", "1", "--program", "KrisFlyer"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "500", "--category", "dining", "--date", "2025-01-05"]);
+
+        // The bank shows 980 of the 1000 miles January earned
+        run(&store, &["adjust-miles", "--card", "altitude", "--to", "980", "--date", "2025-03-01", "--note", "rounding"]);
+        run(&store, &["adjust-miles", "--program", "krisflyer", "--miles", "-80", "--date", "2025-03-02"]);
+        let entries = store.list_miles_entries().unwrap();
+        assert_eq!((entries[0].miles, entries[0].description.as_deref()), (-20.0, Some("rounding")));
+        assert_eq!(entries[1].program.as_deref(), Some("KrisFlyer"));
+        let account = ledger::Account::Program("KrisFlyer".to_string());
+        assert_eq!(ledger::balance(&store, &account, "2025-03-02").unwrap(), 900.0);
+
+        let unchanged = try_run(&store, &["adjust-miles", "--card", "1", "--to", "980", "--date", "2025-03-02"]);
+        assert_eq!(unchanged.unwrap_err().exit_code(), 4);
+        let unknown = try_run(&store, &["adjust-miles", "--program", "Asia Miles", "--miles", "5"]);
+        assert!(matches!(unknown.unwrap_err(), CcTrackerError::UnknownName { what: "program", .. }));
+        assert!(Cli::try_parse_from(["backend", "adjust-miles", "--card", "1", "--miles", "5", "--to", "10"]).is_err());
+    }
+
+    #[test]
+    fn test_redemptions_from_the_cli() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Altitude", "--miles-per-dollar", "2", "--block-size", "1", "--renewal-date", "1", "--program", "KrisFlyer"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "20000", "--category", "travel", "--date", "2025-01-05"]);
+
+        run(&store, &["add-redemption", "--card", "altitude", "--miles", "30000", "--cash-value", "540", "--date", "2025-03-01", "--description", "SIN-NRT"]);
+        run(&store, &["add-redemption", "--program", "krisflyer", "--miles", "10000", "--cash-value", "120", "--date", "2025-03-05"]);
+        let redemptions = store.list_redemptions().unwrap();
+        assert_eq!((redemptions[0].account.as_str(), redemptions[0].cents_per_mile), ("Altitude", 1.8));
+        assert_eq!((redemptions[1].account.as_str(), redemptions[1].cents_per_mile), ("KrisFlyer", 1.2));
+
+        // Both come off the ledger, which the 40,000 miles earned cover
+        let account = ledger::Account::Program("KrisFlyer".to_string());
+        assert_eq!(ledger::balance(&store, &account, "2025-03-05").unwrap(), 0.0);
+        assert_eq!(ledger::accounts(&store, "2025-03-05", pool::BalancesBy::Card).unwrap()[0].redeemed, 30000.0);
+        run(&store, &["redemptions"]);
+
+        let nothing = try_run(&store, &["add-redemption", "--card", "1", "--miles", "0", "--cash-value", "10"]);
+        assert_eq!(nothing.unwrap_err().exit_code(), 4);
+    }
+
+    #[test]
+    fn test_spending_in_a_foreign_currency_from_the_cli() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Travel", "--miles-per-dollar", "1", "--miles-per-dollar-foreign", "3", "--block-size", "1", "--renewal-date", "1", "--max-reward-limit", "1000"]);
+
+        let unrated = try_run(&store, &["add-spending", "--card", "1", "--amount", "10000", "--currency", "JPY", "--category", "dining"]);
+        assert_eq!(unrated.unwrap_err().exit_code(), 4);
+        run(&store, &["fx-rates", "set", "--currency", "jpy", "--rate", "0.009"]);
+        assert_eq!(store.list_fx_rates().unwrap()[0].currency, "JPY");
+
+        run(&store, &["add-spending", "--card", "1", "--amount", "10000", "--currency", "JPY", "--category", "dining", "--date", "2026-03-05"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "200", "--currency", "EUR", "--fx-rate", "1.45", "--category", "dining", "--date", "2026-03-06"]);
+        let spending = store.list_spending(Some(1), None).unwrap();
+        // Amounts are converted to the home currency, earning the foreign rate
+        assert_eq!((spending[0].amount, spending[0].miles_earned), (290.0, 870.0));
+        assert_eq!((spending[1].amount, spending[1].miles_earned), (90.0, 270.0));
+        assert_eq!((spending[1].original_amount, spending[1].original_currency.as_deref()), (Some(10000.0), Some("JPY")));
+        // and count towards the cap in it
+        let best = store.best_card_for_category("dining", 10.0, "online", "2026-03-07", &RecommendOptions::default()).unwrap();
+        assert_eq!(best[0].remaining_limit, Some(620.0));
+
+        // Correcting the amount drops the original it no longer matches
+        run(&store, &["edit-spending", "--id", &spending[1].id.to_string(), "--amount", "95"]);
+        assert_eq!(store.list_spending(Some(1), None).unwrap()[1].original_amount, None);
+
+        let bad = try_run(&store, &["fx-rates", "set", "--currency", "YEN1", "--rate", "0.009"]);
+        assert_eq!(bad.unwrap_err().exit_code(), 4);
+        run(&store, &["fx-rates", "remove", "--currency", "JPY"]);
+        assert!(store.list_fx_rates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_excluded_categories_earn_nothing() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Everyday", "--miles-per-dollar", "2", "--block-size", "1", "--renewal-date", "1", "--categories", "dining,insurance,utilities", "--excluded-categories", "Insurance,utilities"]);
+
+        run(&store, &["add-spending", "--card", "1", "--amount", "120", "--category", "insurance", "--date", "2026-03-05"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "50", "--category", "dining", "--date", "2026-03-05"]);
+        let miles = |category: &str| {
+            store.list_spending(Some(1), None).unwrap().into_iter().find(|s| s.category == category).unwrap().miles_earned
+        };
+        assert_eq!((miles("insurance"), miles("dining")), (0.0, 100.0));
+
+        // best-card still lists the card, as earning nothing
+        let best = ["best-card", "--category", "insurance", "--amount", "120", "--payment-category", "online", "--date", "2026-03-06"];
+        assert_eq!(run(&store, &best), Outcome::OnlyIneligible);
+        let results = store.best_card_for_category("insurance", 120.0, "online", "2026-03-06", &RecommendOptions::default()).unwrap();
+        assert_eq!((results[0].miles_earned, results[0].reason.as_str()), (0.0, "Excludes insurance: earns nothing"));
+
+        run(&store, &["edit-card", "--card", "1", "--excluded-categories", "utilities"]);
+        assert_eq!(run(&store, &best), Outcome::Success);
+    }
+
+    #[test]
+    fn test_category_commands_cascade_to_cards_and_spending() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["category", "add", "--name", "Fuel"]);
+        assert_eq!(try_run(&store, &["category", "add", "--name", "fuel"]).unwrap_err().exit_code(), 4);
+        // A card added without categories earns in every known one
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        assert!(store.get_card(1).unwrap().unwrap().categories.contains(r#""Fuel""#));
+        run(&store, &["add-spending", "--card", "1", "--amount", "40", "--category", "fuel", "--date", "2026-03-05"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "20", "--category", "transport", "--date", "2026-03-05"]);
+
+        run(&store, &["category", "rename", "--from", "FUEL", "--to", "Petrol"]);
+        let card = store.get_card(1).unwrap().unwrap();
+        assert!(card.categories.contains(r#""Petrol""#) && !card.categories.contains(r#""Fuel""#));
+        let taken = try_run(&store, &["category", "rename", "--from", "petrol", "--to", "transport"]);
+        assert_eq!(taken.unwrap_err().exit_code(), 4);
+
+        run(&store, &["category", "merge", "--from", "petrol", "--into", "transport"]);
+        let card = store.get_card(1).unwrap().unwrap();
+        assert_eq!(card.categories.matches(r#""transport""#).count(), 1);
+        assert!(store.list_spending(Some(1), None).unwrap().iter().all(|s| s.category == "transport"));
+        let categories = store.list_categories().unwrap();
+        assert!(categories.iter().all(|c| c.name != "Petrol"));
+        let transport = categories.iter().find(|c| c.name == "transport").unwrap();
+        assert_eq!((transport.cards, transport.transactions), (1, 2));
+
+        let unknown = try_run(&store, &["category", "merge", "--from", "petrol", "--into", "transport"]);
+        assert_eq!(unknown.unwrap_err().exit_code(), 5);
+    }
+
+    #[test]
+    fn test_mistyped_categories_are_refused_with_a_suggestion() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+
+        let typo = try_run(&store, &["add-spending", "--card", "1", "--amount", "42", "--category", "dinning"]).unwrap_err();
+        assert_eq!(typo.to_string(), "No category named 'dinning'; did you mean 'dining'?");
+        assert_eq!(typo.exit_code(), 5);
+        let best = try_run(&store, &["best-card", "--category", "grocceries", "--amount", "10", "--payment-category", "online"]);
+        assert!(best.unwrap_err().to_string().ends_with("did you mean 'groceries'?"));
+        let merge = try_run(&store, &["category", "merge", "--from", "dinning", "--into", "travel"]);
+        assert!(merge.unwrap_err().to_string().ends_with("did you mean 'dining'?"));
+
+        // A category unlike any known one is new, and taken as typed
+        run(&store, &["add-spending", "--card", "1", "--amount", "42", "--category", "Dining"]);
+        run(&store, &["add-spending", "--card", "1", "--amount", "42", "--category", "insurance"]);
+        assert_eq!(store.list_spending(Some(1), None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_json_output_for_listings() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        run(&store, &["add-spending", "--card-id", "1", "--amount", "42", "--category", "dining", "--date", "2026-03-05"]);
+
+        assert_eq!(run(&store, &["list-spending", "--output", "json"]), Outcome::Success);
+        assert_eq!(run(&store, &["list-cards", "--output", "json-compact"]), Outcome::Success);
+        let best = ["best-card", "--category", "bills", "--amount", "10", "--payment-category", "online", "--output", "json"];
+        assert_eq!(run(&store, &best), Outcome::NoMatch);
+        // The flag is global, so it can come before the command too
+        assert_eq!(run(&store, &["--output", "json", "list-cards"]), Outcome::Success);
+        let both = ["backend", "list-cards", "--output", "json", "--template", "{name}"];
+        assert!(Cli::try_parse_from(both).is_err());
+    }
+
+    #[test]
+    fn test_close_cycle_needs_the_last_day_of_the_cycle() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "2"]);
+        run(&store, &["add-spending", "--card-id", "1", "--amount", "42", "--category", "dining", "--date", "2026-02-10"]);
+
+        let Err(CcTrackerError::Invalid(message)) = try_run(&store, &["close-cycle", "--card", "1", "--through", "2026-02-10"]) else {
+            panic!("closed a cycle mid-way");
+        };
+        assert_eq!(message, "2026-02-10 is not the last day of its statement cycle (2026-02-02 to 2026-03-01); use --through 2026-03-01");
+        assert!(store.list_closed_cycles(None).unwrap().is_empty());
+        run(&store, &["close-cycle", "--card", "1", "--through", "2026-03-01"]);
+        assert_eq!(store.list_closed_cycles(None).unwrap().len(), 1);
+
+        // Writes into the closed cycle are refused, not database failures
+        let writes: &[&[&str]] = &[
+            &["add-spending", "--card-id", "1", "--amount", "10", "--category", "dining", "--date", "2026-02-15"],
+            &["edit-spending", "--id", "1", "--amount", "20"],
+        ];
+        for args in writes {
+            let Err(err) = try_run(&store, args) else {
+                panic!("{} wrote into a closed cycle", args[0]);
+            };
+            assert_eq!(err.exit_code(), 4, "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_listings_refuse_a_bad_template_alike() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1"]);
+        run(&store, &["add-spending", "--card-id", "1", "--amount", "42", "--category", "dining", "--date", "2026-03-05"]);
+
+        for listing in ["list-cards", "list-spending"] {
+            let result = try_run(&store, &[listing, "--template", "{no_such_field}"]);
+            let Err(CcTrackerError::Invalid(message)) = result else {
+                panic!("{} accepted the template", listing);
+            };
+            assert!(message.starts_with("Unknown template field 'no_such_field'"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_query_commands_never_write() {
+        let store = Connection::open_in_memory().unwrap();
+        db::init_tables(&store).unwrap();
+        run(&store, &["add-card", "--name", "Card A", "--miles-per-dollar", "4", "--block-size", "5", "--renewal-date", "1", "--max-reward-limit", "500"]);
+        run(&store, &["add-spending", "--card-id", "1", "--amount", "42", "--category", "dining", "--date", "2025-03-05"]);
+        run(&store, &["add-payment", "--card-id", "1", "--amount", "42", "--date", "2025-03-20"]);
+        store.pragma_update(None, "query_only", true).unwrap();
+
+        let queries: &[&[&str]] = &[
+            &["list-cards"],
+            &["best-card", "--category", "dining", "--amount", "10", "--payment-category", "online"],
+            &["list-spending"],
+            &["review-queue"],
+            &["reconcile", "--card-id", "1", "--date", "2025-03-05", "--bank-miles", "32"],
+            &["closed-cycles"],
+            &["list-payments"],
+            &["list-charges"],
+            &["list-partnerships"],
+            &["list-rules"],
+            &["list-promotions"],
+            &["summary"],
+            &["cash-flow"],
+            &["balances"],
+            &["card-value", "--mile-value", "0.02"],
+            &["programs", "list"],
+            &["programs", "cards"],
+            &["programs", "transfers"],
+            &["balance"],
+            &["balance", "--card", "1"],
+            &["redemptions"],
+            &["expiring-miles", "--within", "30"],
+            &["fx-rates", "list"],
+            &["category", "list"],
+            &["cycle-summary"],
+            &["stats"],
+            &["best-card-matrix"],
+            &["best-card-matrix", "--by-payment", "--amount", "120"],
+            &["compare-cards", "--cards", "1,Card A"],
+            &["forecast", "--as-of", "2025-03-10"],
+            &["forecast", "--card", "1", "--days", "30", "--output", "json"],
+            &["optimize-basket", "--item", "dining:40", "--item", "dining:400:online", "--date", "2025-03-10"],
+            &["what-if", "--name", "Card B", "--miles-per-dollar", "2", "--block-size", "1", "--renewal-date", "15", "--period", "this-cycle"],
+            &["compare-cards", "--cards", "1,1", "--category", "dining", "--amount", "80", "--output", "json"],
+            &["report", "--month", "2025-03", "--format", "markdown"],
+            &["report", "--month", "2025-03", "--chart"],
+            &["stats", "--by", "category", "--period", "ytd"],
+            &["stats", "--by", "month", "--card", "1", "--period", "this-cycle"],
+            &["cycle-summary", "--card", "1", "--output", "json"],
+        ];
+        for args in queries {
+            let cli = Cli::try_parse_from(std::iter::once("backend").chain(args.iter().copied())).unwrap();
+            let command = cli.command.unwrap();
+            assert!(command.is_query(), "{:?} is not a query", args);
+            super::run(&store, command, cli.output).unwrap();
+        }
+    }
+}
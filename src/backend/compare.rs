@@ -0,0 +1,147 @@
+//! `compare-cards`: cards' rates, blocks, caps, minimum spends and fees
+//! side by side, with what each would earn on a given purchase, to decide
+//! which to use or which to keep.
+
+
+use crate::error::Result;
+use crate::models::{Card, CardComparison, CardRecommendation, RecommendOptions};
+use crate::store::Store;
+
+/// A purchase the compared cards' miles are projected for
+pub struct Purchase<'a> {
+    pub category: &'a str,
+    pub amount: f64,
+    /// How it is paid; None to take whichever way earns most on each card
+    pub payment_category: Option<&'a str>,
+}
+
+/// Compares `cards`, in the order given. With a `purchase`, each card's
+/// miles on it are projected as of `date`, caps and minimum spends as they
+/// stand then: none where the purchase is ineligible, the note saying why.
+pub fn compare<S: Store + ?Sized>(
+    store: &S,
+    cards: &[Card],
+    purchase: Option<&Purchase>,
+    date: &str,
+) -> Result<Vec<CardComparison>> {
+    let mut rows = Vec::new();
+    for card in cards {
+        let mut row = CardComparison {
+            card_id: card.id,
+            card_name: card.name.clone(),
+            miles_per_dollar: card.miles_per_dollar,
+            miles_per_dollar_foreign: card.miles_per_dollar_foreign,
+            block_size: card.block_size,
+            max_reward_limit: card.max_reward_limit,
+            cap_unit: card.cap_unit,
+            min_spend: card.min_spend,
+            fx_fee_percent: card.fx_fee_percent,
+            annual_fee: card.annual_fee,
+            payment_category: None,
+            projected_miles: None,
+            effective_rate: None,
+            note: None,
+        };
+        if let Some(purchase) = purchase {
+            let payment_categories = match purchase.payment_category {
+                Some(payment_category) => vec![payment_category.to_string()],
+                None => serde_json::from_str::<Vec<String>>(&card.payment_categories).unwrap_or_default(),
+            };
+            let mut best: Option<(String, CardRecommendation)> = None;
+            for payment_category in payment_categories {
+                let found = store
+                    .best_card_for_category(
+                        purchase.category,
+                        purchase.amount,
+                        &payment_category,
+                        date,
+                        &RecommendOptions::default(),
+                    )?
+                    .into_iter()
+                    .find(|r| r.card_id == card.id);
+                if let Some(found) = found
+                    && best.as_ref().is_none_or(|(_, b)| {
+                        (found.eligible, found.miles_earned) > (b.eligible, b.miles_earned)
+                    })
+                {
+                    best = Some((payment_category, found));
+                }
+            }
+            match best {
+                Some((payment_category, found)) => {
+                    row.payment_category = Some(payment_category);
+                    // A card the purchase is ineligible on earns nothing
+                    row.projected_miles = Some(if found.eligible { found.miles_earned } else { 0.0 });
+                    row.effective_rate = Some(if found.eligible { found.effective_rate } else { 0.0 });
+                    row.note = Some(found.reason);
+                }
+                None => {
+                    row.projected_miles = Some(0.0);
+                    row.note = Some(match purchase.payment_category {
+                        Some(payment_category) => {
+                            format!("Does not earn on {} paid {}", purchase.category, payment_category)
+                        }
+                        None => format!("Does not earn on {}", purchase.category),
+                    });
+                }
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    fn add_card(conn: &Connection, name: &str, payment_categories: &[&str], miles_per_dollar: f64, cap: Option<f64>) -> Card {
+        let id = db::add_card(
+            conn,
+            &NewCard {
+                name: name.to_string(),
+                categories: vec!["dining".to_string()],
+                payment_categories: payment_categories.iter().map(|p| p.to_string()).collect(),
+                miles_per_dollar,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit: cap,
+                fx_fee_percent: Some(3.25),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::get_card(conn, id).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_compare_projects_each_cards_miles() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let capped = add_card(&conn, "Capped", &["online"], 4.0, Some(500.0));
+        let everyday = add_card(&conn, "Everyday", &["online", "contactless"], 1.5, None);
+        db::add_spending(&conn, capped.id, 480.0, "dining", "2026-04-02", None, false, None).unwrap();
+        let cards = [capped, everyday];
+
+        let terms = compare(&conn, &cards, None, "2026-04-24").unwrap();
+        assert_eq!((terms[0].card_name.as_str(), terms[0].max_reward_limit), ("Capped", Some(500.0)));
+        assert_eq!((terms[1].miles_per_dollar, terms[1].fx_fee_percent), (1.5, Some(3.25)));
+        assert!(terms.iter().all(|t| t.projected_miles.is_none()));
+
+        let purchase = Purchase { category: "dining", amount: 50.0, payment_category: None };
+        let projected = compare(&conn, &cards, Some(&purchase), "2026-04-24").unwrap();
+        // Only $20 of the cap is left
+        assert_eq!(projected[0].projected_miles, Some(0.0));
+        assert!(projected[0].note.is_some());
+        assert_eq!((projected[1].projected_miles, projected[1].payment_category.as_deref()), (Some(75.0), Some("online")));
+
+        let purchase = Purchase { category: "dining", amount: 50.0, payment_category: Some("contactless") };
+        let projected = compare(&conn, &cards, Some(&purchase), "2026-04-24").unwrap();
+        assert_eq!(projected[0].projected_miles, Some(0.0));
+        assert_eq!(projected[0].note.as_deref(), Some("Does not earn on dining paid contactless"));
+        assert_eq!(projected[1].projected_miles, Some(75.0));
+    }
+}
@@ -0,0 +1,191 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::backup::BackupTarget;
+use crate::cli::OutputFormat;
+use crate::dates;
+use crate::error;
+use crate::import::{self, ImportProfile};
+use crate::models::DEFAULT_PAYMENT_CATEGORIES;
+use crate::store::Store;
+
+/// User configuration, read from `config.toml` in the cc-tracker config directory.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Postgres connection string for a shared database; the local SQLite
+    /// file is used when unset. `CC_TRACKER_DATABASE_URL` overrides it.
+    pub database_url: Option<String>,
+    /// SQLite database file, in place of `cc_tracker.db` in the data
+    /// directory. `--db` and `CC_TRACKER_DB` override it.
+    pub database_path: Option<PathBuf>,
+    /// Named statement import profiles, e.g. `[import_profiles.dbs]`
+    #[serde(deserialize_with = "import::deserialize_profiles")]
+    pub import_profiles: HashMap<String, ImportProfile>,
+    /// Shell command monthly digests are piped to, e.g.
+    /// `mail -s "$CC_TRACKER_DIGEST_SUBJECT" me@example.com`
+    pub digest_command: Option<String>,
+    /// Categories a card added without any gets, in place of the known ones
+    pub categories: Option<Vec<String>>,
+    /// Payment categories a card added without any gets, in place of the
+    /// built-in set
+    pub payment_categories: Option<Vec<String>>,
+    /// Named remote backup destinations, e.g. `[backup_targets.nas]`
+    pub backup_targets: HashMap<String, BackupTarget>,
+    /// Layout dates are typed and shown in, e.g. "DD/MM/YYYY", and the
+    /// default for import profiles without their own; YYYY-MM-DD is always
+    /// accepted and is what is stored and exported
+    pub date_format: Option<String>,
+    /// Payment category `best-card` assumes when neither `--payment-category`
+    /// nor the rules file says how a purchase is paid
+    pub payment_category: Option<String>,
+    /// Dollars a mile is worth, for `best-card` without `--mile-value`
+    pub mile_value: Option<f64>,
+    /// Dollars a bank point is worth, for points cards in `best-card`
+    /// without `--point-value`; defaults to mile_value
+    pub point_value: Option<f64>,
+    /// Days ahead `expiring-miles` looks without `--within`; defaults to 90
+    pub expiry_horizon_days: Option<i32>,
+    /// How listings print without `--output`: table, json or json-compact
+    pub output: Option<OutputFormat>,
+    /// Offer the suggestion for a mistyped category or card name on the
+    /// terminal, using it if accepted, instead of only naming it in the error
+    pub confirm_suggestions: bool,
+    /// Record recurring charges that have come due before each command
+    /// that writes, and when the server starts, as `apply-recurring` does
+    pub apply_recurring: bool,
+}
+
+impl Config {
+    /// The categories `add-card` gives a card when none are passed: those
+    /// set here, or else every known category (see `category list`).
+    pub fn default_categories<S: Store + ?Sized>(&self, store: &S) -> error::Result<Vec<String>> {
+        match &self.categories {
+            Some(categories) => Ok(categories.clone()),
+            None => Ok(store.list_categories()?.into_iter().map(|c| c.name).collect()),
+        }
+    }
+
+    /// The payment categories `add-card` gives a card when none are passed.
+    pub fn default_payment_categories(&self) -> Vec<String> {
+        configured_or(&self.payment_categories, DEFAULT_PAYMENT_CATEGORIES)
+    }
+}
+
+fn configured_or(configured: &Option<Vec<String>>, built_in: &[&str]) -> Vec<String> {
+    match configured {
+        Some(values) => values.clone(),
+        None => built_in.iter().map(|s| s.to_string()).collect(),
+    }
+}
+
+/// Returns the config directory: `$XDG_CONFIG_HOME/cc-tracker`, falling back
+/// to `~/.config/cc-tracker`.
+pub fn config_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("cc-tracker")
+}
+
+/// Returns the data directory the database lives in by default:
+/// `$XDG_DATA_HOME/cc-tracker`, falling back to `~/.local/share/cc-tracker`.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join("cc-tracker")
+}
+
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
+
+/// Categorization rules file, one `pattern -> category [+ payment_category]` per line
+pub fn rules_path() -> PathBuf {
+    config_dir().join("rules.txt")
+}
+
+/// Holds the month of the last digest the server sent
+pub fn digest_sent_path() -> PathBuf {
+    config_dir().join("digest_sent")
+}
+
+/// The config file, loaded once; None when it cannot be read, which the
+/// commands that need all of it report through `load`.
+pub fn loaded() -> Option<&'static Config> {
+    static CONFIG: OnceLock<Option<Config>> = OnceLock::new();
+    CONFIG.get_or_init(|| load().ok()).as_ref()
+}
+
+/// Loads the config file, returning the defaults if it does not exist.
+pub fn load() -> Result<Config, String> {
+    let path = config_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let config: Config =
+        toml::from_str(&text).map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+    for (key, values) in [("categories", &config.categories), ("payment_categories", &config.payment_categories)] {
+        if let Some(values) = values
+            && (values.is_empty() || values.iter().any(|v| v.trim().is_empty()))
+        {
+            return Err(format!(
+                "Invalid config {}: {} must list at least one name and no blank ones",
+                path.display(),
+                key
+            ));
+        }
+    }
+    if let Some(layout) = &config.date_format
+        && !dates::valid_layout(layout)
+    {
+        return Err(format!(
+            "Invalid config {}: date_format must lay out YYYY, MM and DD, e.g. \"DD/MM/YYYY\"",
+            path.display()
+        ));
+    }
+    if config.mile_value.is_some_and(|value| value <= 0.0) {
+        return Err(format!("Invalid config {}: mile_value must be above 0", path.display()));
+    }
+    if config.point_value.is_some_and(|value| value <= 0.0) {
+        return Err(format!("Invalid config {}: point_value must be above 0", path.display()));
+    }
+    if config.expiry_horizon_days.is_some_and(|days| days < 1) {
+        return Err(format!("Invalid config {}: expiry_horizon_days must be at least 1", path.display()));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_categories_replace_the_built_in_ones() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::init_tables(&conn).unwrap();
+        assert_eq!(Config::default().default_categories(&conn).unwrap().len(), crate::models::DEFAULT_CATEGORIES.len());
+        let config: Config = toml::from_str("categories = [\"dining\", \"fuel\"]").unwrap();
+        assert_eq!(config.default_categories(&conn).unwrap(), vec!["dining", "fuel"]);
+        assert_eq!(config.default_payment_categories().len(), DEFAULT_PAYMENT_CATEGORIES.len());
+    }
+
+    #[test]
+    fn test_flag_defaults() {
+        let config: Config = toml::from_str(
+            "database_path = \"data/cards.db\"\noutput = \"json-compact\"\nmile_value = 0.015",
+        )
+        .unwrap();
+        assert_eq!(config.database_path, Some(PathBuf::from("data/cards.db")));
+        assert_eq!(config.output, Some(OutputFormat::JsonCompact));
+        assert_eq!(config.mile_value, Some(0.015));
+        assert!(toml::from_str::<Config>("output = \"yaml\"").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+//! Dates as they are typed on the command line and shown in tables. They
+//! are always stored as YYYY-MM-DD; `date_format` in config.toml (e.g.
+//! "DD/MM/YYYY") lets people who write dates day-first type and read them
+//! that way instead.
+
+use crate::config;
+use crate::error::CcTrackerError;
+use crate::import::parse_date_with_format;
+use crate::period::valid_date;
+
+/// The layout dates are stored, exported and accepted in regardless
+pub const ISO_FORMAT: &str = "YYYY-MM-DD";
+
+/// Whether a layout has a four-digit year, a month and a day, once each,
+/// between separators, e.g. "DD/MM/YYYY" or "DD.MM.YYYY".
+pub fn valid_layout(layout: &str) -> bool {
+    let mut tokens: Vec<String> = layout
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_ascii_uppercase())
+        .collect();
+    tokens.sort();
+    tokens == ["DD", "MM", "YYYY"] && !layout.contains(|c: char| c.is_ascii_digit())
+}
+
+/// `date_format` from the config file; None when it is unset or the file
+/// cannot be read.
+fn configured() -> Option<&'static str> {
+    config::loaded()?.date_format.as_deref()
+}
+
+/// Reads a date typed as YYYY-MM-DD or in `layout`, as YYYY-MM-DD.
+pub fn parse_with(value: &str, layout: Option<&str>) -> Result<String, String> {
+    let parsed = if valid_date(value) {
+        Some(value.to_string())
+    } else {
+        layout.and_then(|layout| parse_date_with_format(value, layout))
+    };
+    match (parsed, layout) {
+        (Some(date), _) if valid_date(&date) => Ok(date),
+        (_, Some(layout)) => Err(format!("'{}' is not a date; use {} or {}", value, layout, ISO_FORMAT)),
+        (_, None) => Err(format!("'{}' is not a date; use {}", value, ISO_FORMAT)),
+    }
+}
+
+/// Parses a date argument in the configured layout or as YYYY-MM-DD.
+pub fn parse_input(value: &str) -> Result<String, CcTrackerError> {
+    parse_with(value, configured()).map_err(CcTrackerError::InvalidDate)
+}
+
+/// Parses a time of day typed as HH:MM (or a bare hour) into minutes after
+/// midnight, refusing hours past 23 and minutes past 59.
+pub fn parse_time(value: &str) -> Result<i32, CcTrackerError> {
+    let (hours, minutes) = value.split_once(':').unwrap_or((value, "00"));
+    let field = |part: &str| {
+        let digits = !part.is_empty() && part.len() <= 2 && part.bytes().all(|b| b.is_ascii_digit());
+        digits.then(|| part.parse::<i32>().ok()).flatten()
+    };
+    match (field(hours), field(minutes)) {
+        (Some(h), Some(m)) if h < 24 && m < 60 => Ok(h * 60 + m),
+        _ => Err(CcTrackerError::Invalid(format!("'{}' is not a time; use HH:MM, from 00:00 to 23:59", value))),
+    }
+}
+
+/// A YYYY-MM-DD date laid out per `layout`; anything else is left as it is.
+pub fn format_with(date: &str, layout: &str) -> String {
+    if !valid_date(date) {
+        return date.to_string();
+    }
+    // The digits put in cannot be mistaken for the letters still to replace
+    layout
+        .to_ascii_uppercase()
+        .replace("YYYY", &date[..4])
+        .replace("MM", &date[5..7])
+        .replace("DD", &date[8..10])
+}
+
+/// A stored date as tables show it, in the configured layout if any.
+pub fn display(date: &str) -> String {
+    match configured() {
+        Some(layout) => format_with(date, layout),
+        None => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_first_dates_in_and_out() {
+        let layout = Some("DD/MM/YYYY");
+        assert_eq!(parse_with("05/03/2026", layout).unwrap(), "2026-03-05");
+        assert_eq!(parse_with("2026-03-05", layout).unwrap(), "2026-03-05");
+        assert_eq!(parse_with("30/02/2026", layout).unwrap_err(), "'30/02/2026' is not a date; use DD/MM/YYYY or YYYY-MM-DD");
+        assert!(parse_with("05/03/2026", None).is_err());
+        assert!(parse_with("05/03/26", layout).is_err());
+
+        assert_eq!(format_with("2026-03-05", "DD/MM/YYYY"), "05/03/2026");
+        assert_eq!(format_with("2026-03-05", "dd-mm-yyyy"), "05-03-2026");
+        assert_eq!(format_with("2026-03", "DD/MM/YYYY"), "2026-03");
+
+        assert!(valid_layout("DD.MM.YYYY"));
+        assert!(!valid_layout("DD/MM/YY"));
+        assert!(!valid_layout("MM/YYYY"));
+    }
+
+    #[test]
+    fn test_times_are_checked() {
+        assert_eq!(parse_time("09:30").unwrap(), 570);
+        assert_eq!(parse_time("23:59").unwrap(), 1439);
+        assert_eq!(parse_time("7").unwrap(), 420);
+        for bad in ["9pm", "25:99", "24:00", "12:60", "-1:00", "12:", ""] {
+            let err = parse_time(bad).unwrap_err();
+            assert_eq!(err.exit_code(), 4, "{}", bad);
+        }
+    }
+}
@@ -1,9 +1,32 @@
-use rusqlite::{Connection, Result, params};
-
-use crate::models::{Card, CardRecommendation, Spending};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{Connection, OpenFlags, Result, params};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::engine::{
+    self, calculate_miles, card_cycle, cycle_bounds, cycle_end_date, cycle_start_date, days_to_ymd, format_date,
+    miles_posting_date, parse_date, rank_recommendations, recommend, reconcile_suspects,
+    ymd_to_days, explain, CandidateCard, CycleState, CycleWindow, MilesCap, Purchase,
+};
+use crate::import::{self, CategoryDecision, ImportProfile, ImportedRow, MerchantHistory};
+use crate::period::DateRange;
+use crate::rules;
+use crate::statement;
+use crate::models::{
+    CapUnit, Card, CardBalance, CardRates, CardRecommendation, CashFlowMonth, Category, Charge,
+    AppliedRecurring, ClosedCycle, CycleOverride, FxRate, ImportPreview, ImportSummary, MerchantPartnership, MerchantRule, MilesEntry, NewCard, OriginalAmount, Promotion, Recurring, Redemption, OutstandingBalance, Payment,
+    PatternSyntax, Program, ProgramKind, ProgramTransfer, RateChange, RecommendOptions, RewardType, ReconcileSuspect, Reconciliation, ReviewItem, Spending, SpendingCursor, SpendingStats, StatsBy,
+    SpendingEdit, SpendingPage, SplitSuggestion, DEFAULT_CATEGORIES,
+};
+
+/// Status of a spending row that has actually been charged.
+pub const STATUS_POSTED: &str = "posted";
+/// Status of planned future spending, excluded from current totals.
+pub const STATUS_SCHEDULED: &str = "scheduled";
 
 /// Creates tables on the given connection.
 pub fn init_tables(conn: &Connection) -> Result<()> {
+    let new_categories = table_columns(conn, "categories")?.is_empty();
     conn.execute_batch(
         "CREATE TABLE IF NOT EXISTS cards (
             id                      INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -15,7 +38,26 @@ pub fn init_tables(conn: &Connection) -> Result<()> {
             block_size              REAL NOT NULL,
             statement_renewal_date  INTEGER NOT NULL,
             max_reward_limit        REAL,
-            min_spend               REAL
+            min_spend               REAL,
+            posting_delay           INTEGER NOT NULL DEFAULT 0,
+            bank_time_offset_minutes INTEGER NOT NULL DEFAULT 0,
+            cutoff_hour             INTEGER NOT NULL DEFAULT 0,
+            annual_fee              REAL,
+            starting_balance        REAL NOT NULL DEFAULT 0,
+            balance_alert           REAL,
+            cashback_percent        REAL,
+            foreign_blocks          INTEGER NOT NULL DEFAULT 0,
+            cap_unit                TEXT NOT NULL DEFAULT 'dollars',
+            min_spend_exclusions    TEXT NOT NULL DEFAULT '[]',
+            program                 TEXT,
+            fee_posting_month       INTEGER,
+            reward_type             TEXT NOT NULL DEFAULT 'miles',
+            miles_expiry_months     INTEGER,
+            fx_fee_percent          REAL,
+            excluded_categories     TEXT NOT NULL DEFAULT '[]',
+            closed_on               TEXT,
+            cycle_overrides         TEXT NOT NULL DEFAULT '{}',
+            payment_due_days        INTEGER
         );
         CREATE TABLE IF NOT EXISTS spending (
             id           INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -23,64 +65,583 @@ pub fn init_tables(conn: &Connection) -> Result<()> {
             amount       REAL NOT NULL,
             category     TEXT NOT NULL,
             date         TEXT NOT NULL,
-            miles_earned REAL NOT NULL
-        );",
+            miles_earned REAL NOT NULL,
+            recorded_on  TEXT,
+            status       TEXT NOT NULL DEFAULT 'posted',
+            description  TEXT,
+            payment_category TEXT,
+            is_foreign   INTEGER NOT NULL DEFAULT 0,
+            original_amount   REAL,
+            original_currency TEXT
+        );
+        -- Rates a card had before each change; rows are valid until (excluding) valid_until
+        CREATE TABLE IF NOT EXISTS card_rate_history (
+            id               INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id          INTEGER NOT NULL REFERENCES cards(id),
+            valid_until      TEXT NOT NULL,
+            miles_per_dollar REAL NOT NULL,
+            block_size       REAL NOT NULL,
+            max_reward_limit REAL,
+            min_spend        REAL
+        );
+        CREATE TABLE IF NOT EXISTS payments (
+            id      INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id INTEGER NOT NULL REFERENCES cards(id),
+            amount  REAL NOT NULL,
+            date    TEXT NOT NULL
+        );
+        -- Interest and fees: owed like spending but never earn miles
+        CREATE TABLE IF NOT EXISTS charges (
+            id      INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id INTEGER NOT NULL REFERENCES cards(id),
+            kind    TEXT NOT NULL,
+            amount  REAL NOT NULL,
+            date    TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS closed_cycles (
+            id                INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id           INTEGER NOT NULL REFERENCES cards(id),
+            cycle_start       TEXT NOT NULL,
+            cycle_end         TEXT NOT NULL,
+            total_spend       REAL NOT NULL,
+            total_miles       REAL NOT NULL,
+            transaction_count INTEGER NOT NULL,
+            closed_on         TEXT NOT NULL,
+            UNIQUE (card_id, cycle_start)
+        );
+        -- Imported statement rows waiting for a category before they become spending
+        CREATE TABLE IF NOT EXISTS import_review (
+            id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id            INTEGER NOT NULL REFERENCES cards(id),
+            amount             REAL NOT NULL,
+            date               TEXT NOT NULL,
+            description        TEXT,
+            suggested_category TEXT,
+            confidence         REAL
+        );
+        -- Extra miles per block a card earns at merchants matching a pattern
+        CREATE TABLE IF NOT EXISTS merchant_partnerships (
+            id                     INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id                INTEGER NOT NULL REFERENCES cards(id),
+            merchant_pattern       TEXT NOT NULL,
+            bonus_miles_per_dollar REAL NOT NULL
+        );
+        -- Promotional miles per block for a category while a promotion runs
+        CREATE TABLE IF NOT EXISTS promotions (
+            id               INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id          INTEGER NOT NULL REFERENCES cards(id),
+            category         TEXT NOT NULL,
+            miles_per_dollar REAL NOT NULL,
+            start_date       TEXT NOT NULL,
+            end_date         TEXT NOT NULL
+        );
+        -- Monthly charges added with `recurring add`, recorded as spending
+        -- by apply-recurring once due
+        CREATE TABLE IF NOT EXISTS recurring (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id      INTEGER NOT NULL REFERENCES cards(id),
+            amount       REAL NOT NULL,
+            category     TEXT NOT NULL,
+            day_of_month INTEGER NOT NULL,
+            merchant     TEXT,
+            next_date    TEXT NOT NULL
+        );
+        -- Merchant categorization rules added with add-rule, tried after the rules file
+        CREATE TABLE IF NOT EXISTS merchant_rules (
+            id       INTEGER PRIMARY KEY AUTOINCREMENT,
+            pattern  TEXT NOT NULL,
+            syntax   TEXT NOT NULL DEFAULT 'glob',
+            category TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS programs (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            name          TEXT NOT NULL,
+            kind          TEXT NOT NULL DEFAULT 'airline',
+            expiry_months INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS program_transfers (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            from_program_id INTEGER NOT NULL REFERENCES programs(id),
+            to_program_id   INTEGER NOT NULL REFERENCES programs(id),
+            ratio           REAL NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS miles_ledger (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id     INTEGER REFERENCES cards(id),
+            program     TEXT,
+            kind        TEXT NOT NULL,
+            miles       REAL NOT NULL,
+            date        TEXT NOT NULL,
+            description TEXT
+        );
+        -- Home currency per unit of each foreign currency
+        CREATE TABLE IF NOT EXISTS fx_rates (
+            currency   TEXT PRIMARY KEY,
+            rate       REAL NOT NULL,
+            updated_on TEXT NOT NULL
+        );
+        -- Spending categories added with `category add`, besides those cards
+        -- and spending already use
+        CREATE TABLE IF NOT EXISTS categories (
+            name TEXT PRIMARY KEY COLLATE NOCASE
+        );
+        CREATE TABLE IF NOT EXISTS redemptions (
+            id             INTEGER PRIMARY KEY AUTOINCREMENT,
+            card_id        INTEGER REFERENCES cards(id),
+            program        TEXT,
+            miles          REAL NOT NULL,
+            cash_value     REAL NOT NULL,
+            cents_per_mile REAL NOT NULL,
+            date           TEXT NOT NULL,
+            description    TEXT,
+            miles_entry_id INTEGER REFERENCES miles_ledger(id)
+        );
+        -- Spending inside a closed cycle is frozen until the cycle is reopened
+        CREATE TRIGGER IF NOT EXISTS spending_closed_insert BEFORE INSERT ON spending
+        WHEN EXISTS (SELECT 1 FROM closed_cycles
+                     WHERE card_id = NEW.card_id AND NEW.date BETWEEN cycle_start AND cycle_end)
+        BEGIN
+            SELECT RAISE(ABORT, 'statement cycle is closed; reopen it first');
+        END;
+        -- Settling a transaction's cycle (see init_cycle_totals) changes
+        -- nothing about it, so is allowed in a closed cycle too
+        DROP TRIGGER IF EXISTS spending_closed_update;
+        CREATE TRIGGER spending_closed_update BEFORE UPDATE ON spending
+        WHEN NEW.cycle_card_id IS OLD.cycle_card_id AND NEW.cycle_start IS OLD.cycle_start
+         AND NEW.cycle_end IS OLD.cycle_end
+         AND EXISTS (SELECT 1 FROM closed_cycles
+                     WHERE (card_id = OLD.card_id AND OLD.date BETWEEN cycle_start AND cycle_end)
+                        OR (card_id = NEW.card_id AND NEW.date BETWEEN cycle_start AND cycle_end))
+        BEGIN
+            SELECT RAISE(ABORT, 'statement cycle is closed; reopen it first');
+        END;
+        CREATE TRIGGER IF NOT EXISTS spending_closed_delete BEFORE DELETE ON spending
+        WHEN EXISTS (SELECT 1 FROM closed_cycles
+                     WHERE card_id = OLD.card_id AND OLD.date BETWEEN cycle_start AND cycle_end)
+        BEGIN
+            SELECT RAISE(ABORT, 'statement cycle is closed; reopen it first');
+        END;",
+    )?;
+    ensure_column(conn, "cards", "posting_delay", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "cards", "bank_time_offset_minutes", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "cards", "cutoff_hour", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "cards", "annual_fee", "REAL")?;
+    ensure_column(conn, "cards", "starting_balance", "REAL NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "cards", "balance_alert", "REAL")?;
+    ensure_column(conn, "cards", "cashback_percent", "REAL")?;
+    ensure_column(conn, "cards", "foreign_blocks", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "cards", "cap_unit", "TEXT NOT NULL DEFAULT 'dollars'")?;
+    ensure_column(conn, "cards", "min_spend_exclusions", "TEXT NOT NULL DEFAULT '[]'")?;
+    ensure_column(conn, "cards", "program", "TEXT")?;
+    ensure_column(conn, "cards", "fee_posting_month", "INTEGER")?;
+    ensure_column(conn, "cards", "reward_type", "TEXT NOT NULL DEFAULT 'miles'")?;
+    ensure_column(conn, "cards", "miles_expiry_months", "INTEGER")?;
+    ensure_column(conn, "cards", "fx_fee_percent", "REAL")?;
+    ensure_column(conn, "cards", "excluded_categories", "TEXT NOT NULL DEFAULT '[]'")?;
+    ensure_column(conn, "cards", "closed_on", "TEXT")?;
+    ensure_column(conn, "cards", "cycle_overrides", "TEXT NOT NULL DEFAULT '{}'")?;
+    ensure_column(conn, "cards", "payment_due_days", "INTEGER")?;
+    // Rows recorded before this column existed count as known from their own date
+    ensure_column(conn, "spending", "recorded_on", "TEXT")?;
+    ensure_column(conn, "spending", "status", "TEXT NOT NULL DEFAULT 'posted'")?;
+    ensure_column(conn, "spending", "description", "TEXT")?;
+    ensure_column(conn, "spending", "payment_category", "TEXT")?;
+    ensure_column(conn, "spending", "is_foreign", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(conn, "spending", "original_amount", "REAL")?;
+    ensure_column(conn, "spending", "original_currency", "TEXT")?;
+    // Set on rows a database merge duplicated instead of overwriting
+    ensure_column(conn, "spending", "tag", "TEXT")?;
+    ensure_column(conn, "cards", "updated_at", "TEXT")?;
+    ensure_column(conn, "programs", "expiry_months", "INTEGER")?;
+    ensure_column(conn, "spending", "updated_at", "TEXT")?;
+    // Cash back as earned when recorded, so a later change of percent leaves it be
+    ensure_column(conn, "spending", "cashback", "REAL")?;
+    // Dollars a month the user means to spend in the category, if budgeted
+    ensure_column(conn, "categories", "monthly_budget", "REAL")?;
+    if new_categories {
+        for category in DEFAULT_CATEGORIES {
+            conn.execute("INSERT OR IGNORE INTO categories (name) VALUES (?1)", params![category])?;
+        }
+    }
+    init_cycle_totals(conn)?;
+    init_views(conn)?;
+    // Newest-first listings and their pages (see list_spending_page)
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_spending_date ON spending (date, id);
+         CREATE INDEX IF NOT EXISTS idx_spending_card_date ON spending (card_id, date, id);",
+    )?;
+    // Last-change timestamps, used by keep-newest merges. Writers that set
+    // updated_at themselves (merges copying a row) are left alone, and
+    // settling a transaction's cycle is no change to it.
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS cards_touch_insert AFTER INSERT ON cards
+        WHEN NEW.updated_at IS NULL
+        BEGIN
+            UPDATE cards SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS cards_touch_update AFTER UPDATE ON cards
+        WHEN NEW.updated_at IS OLD.updated_at
+        BEGIN
+            UPDATE cards SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE id = NEW.id;
+        END;
+        CREATE TRIGGER IF NOT EXISTS spending_touch_insert AFTER INSERT ON spending
+        WHEN NEW.updated_at IS NULL
+        BEGIN
+            UPDATE spending SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE id = NEW.id;
+        END;
+        DROP TRIGGER IF EXISTS spending_touch_update;
+        CREATE TRIGGER spending_touch_update AFTER UPDATE ON spending
+        WHEN NEW.updated_at IS OLD.updated_at AND NEW.cycle_card_id IS OLD.cycle_card_id
+         AND NEW.cycle_start IS OLD.cycle_start AND NEW.cycle_end IS OLD.cycle_end
+        BEGIN
+            UPDATE spending SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE id = NEW.id;
+        END;",
+    )?;
+    Ok(())
+}
+
+/// Registers `statement_cycle_start(renewal_day, date)` and
+/// `statement_cycle_end(renewal_day, date)`, `engine::cycle_start_date` and
+/// `engine::cycle_end_date` in SQL, on a connection. Given a card's
+/// `cycle_overrides` as a third argument they are `engine::cycle_bounds`.
+fn register_functions(conn: &Connection) -> Result<()> {
+    type CycleBound = fn(i32, &str) -> String;
+    let functions: [(&str, CycleBound, bool); 2] = [
+        ("statement_cycle_start", cycle_start_date, false),
+        ("statement_cycle_end", cycle_end_date, true),
+    ];
+    for (name, function, end) in functions {
+        conn.create_scalar_function(
+            name,
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| {
+                let renewal_day: Option<i32> = ctx.get(0)?;
+                let date: String = ctx.get(1)?;
+                Ok(renewal_day.map(|day| function(day, &date)))
+            },
+        )?;
+        conn.create_scalar_function(
+            name,
+            3,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| {
+                let renewal_day: Option<i32> = ctx.get(0)?;
+                let date: String = ctx.get(1)?;
+                let overrides: BTreeMap<String, CycleOverride> =
+                    serde_json::from_str(&ctx.get::<String>(2)?).unwrap_or_default();
+                Ok(renewal_day.map(|day| {
+                    let (start, finish) = cycle_bounds(day, &overrides, &date);
+                    if end { finish } else { start }
+                }))
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Creates `cycle_totals`, the running spend of each card per statement cycle
+/// and status, kept current by triggers on `spending` so recommendations
+/// need not re-sum a cycle's transactions. The triggers are plain SQL, so
+/// any SQLite client can still change spending: each transaction carries
+/// the card and bounds of the cycle it was counted in (`cycle_card_id`,
+/// `cycle_start` and `cycle_end`, set by `settle_cycles`) and counts only
+/// while they still fit it. Transactions not yet settled are summed
+/// directly when totals are read.
+fn init_cycle_totals(conn: &Connection) -> Result<()> {
+    register_functions(conn)?;
+
+    // Totals from before transactions carried their cycle were kept by
+    // triggers calling a function only this app registers; count afresh
+    let recount = !table_columns(conn, "spending")?.iter().any(|c| c == "cycle_card_id");
+    ensure_column(conn, "spending", "cycle_card_id", "INTEGER")?;
+    ensure_column(conn, "spending", "cycle_start", "TEXT")?;
+    ensure_column(conn, "spending", "cycle_end", "TEXT")?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS cycle_totals (
+            card_id     INTEGER NOT NULL,
+            cycle_start TEXT NOT NULL,
+            status      TEXT NOT NULL,
+            total       REAL NOT NULL,
+            PRIMARY KEY (card_id, cycle_start, status)
+        );
+        -- Transactions whose cycle is not worked out yet, or no longer fits
+        CREATE INDEX IF NOT EXISTS idx_spending_unsettled ON spending (card_id, date)
+        WHERE cycle_card_id IS NOT card_id OR date NOT BETWEEN cycle_start AND cycle_end;
+        DROP TRIGGER IF EXISTS cycle_totals_insert;
+        CREATE TRIGGER cycle_totals_insert AFTER INSERT ON spending
+        WHEN NEW.cycle_card_id IS NEW.card_id AND NEW.date BETWEEN NEW.cycle_start AND NEW.cycle_end
+        BEGIN
+            INSERT INTO cycle_totals (card_id, cycle_start, status, total)
+            VALUES (NEW.card_id, NEW.cycle_start, NEW.status, NEW.amount)
+            ON CONFLICT (card_id, cycle_start, status) DO UPDATE SET total = total + excluded.total;
+        END;
+        DROP TRIGGER IF EXISTS cycle_totals_delete;
+        CREATE TRIGGER cycle_totals_delete AFTER DELETE ON spending
+        WHEN OLD.cycle_card_id IS OLD.card_id AND OLD.date BETWEEN OLD.cycle_start AND OLD.cycle_end
+        BEGIN
+            UPDATE cycle_totals SET total = total - OLD.amount
+            WHERE card_id = OLD.card_id AND cycle_start = OLD.cycle_start AND status = OLD.status;
+        END;
+        DROP TRIGGER IF EXISTS cycle_totals_update;
+        CREATE TRIGGER cycle_totals_update
+        AFTER UPDATE OF card_id, amount, date, status, cycle_card_id, cycle_start, cycle_end ON spending
+        BEGIN
+            UPDATE cycle_totals SET total = total - OLD.amount
+            WHERE card_id = OLD.card_id AND cycle_start = OLD.cycle_start AND status = OLD.status
+              AND OLD.cycle_card_id IS OLD.card_id AND OLD.date BETWEEN OLD.cycle_start AND OLD.cycle_end;
+            INSERT INTO cycle_totals (card_id, cycle_start, status, total)
+            SELECT NEW.card_id, NEW.cycle_start, NEW.status, NEW.amount
+            WHERE NEW.cycle_card_id IS NEW.card_id AND NEW.date BETWEEN NEW.cycle_start AND NEW.cycle_end
+            ON CONFLICT (card_id, cycle_start, status) DO UPDATE SET total = total + excluded.total;
+        END;
+        -- A new renewal day or cycle override moves the card's cycle
+        -- boundaries, so all its spending is settled again
+        DROP TRIGGER IF EXISTS cycle_totals_renewal;
+        CREATE TRIGGER cycle_totals_renewal AFTER UPDATE OF statement_renewal_date, cycle_overrides ON cards
+        BEGIN
+            UPDATE spending SET cycle_card_id = NULL, cycle_start = NULL, cycle_end = NULL
+            WHERE card_id = NEW.id AND cycle_card_id IS NOT NULL;
+        END;
+        CREATE TRIGGER IF NOT EXISTS cycle_totals_card_delete AFTER DELETE ON cards
+        BEGIN
+            DELETE FROM cycle_totals WHERE card_id = OLD.id;
+        END;",
     )?;
+    if recount {
+        conn.execute("DELETE FROM cycle_totals", [])?;
+    }
+    settle_cycles(conn)
+}
+
+/// Works out the statement cycle of every transaction not yet counted in
+/// `cycle_totals`: new ones, those moved out of their cycle or to another
+/// card, and those on a card whose cycles changed. Storing the cycle
+/// counts the transaction. Spending changed by another SQLite client is
+/// settled the next time the tracker opens the database.
+pub fn settle_cycles(conn: &Connection) -> Result<()> {
+    let unsettled = {
+        let mut stmt = conn.prepare_cached(
+            "SELECT s.id, s.date, c.statement_renewal_date, c.id, c.cycle_overrides
+             FROM spending s JOIN cards c ON c.id = s.card_id
+             WHERE s.cycle_card_id IS NOT s.card_id OR s.date NOT BETWEEN s.cycle_start AND s.cycle_end",
+        )?;
+        stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i32>(2)?, row.get::<_, i64>(3)?, row.get::<_, String>(4)?))
+        })?
+        .collect::<Result<Vec<_>>>()?
+    };
+    let mut overrides_of: HashMap<i64, BTreeMap<String, CycleOverride>> = HashMap::new();
+    let mut update =
+        conn.prepare_cached("UPDATE spending SET cycle_card_id = ?1, cycle_start = ?2, cycle_end = ?3 WHERE id = ?4")?;
+    for (id, date, renewal_day, card_id, overrides) in unsettled {
+        let overrides = overrides_of
+            .entry(card_id)
+            .or_insert_with(|| serde_json::from_str(&overrides).unwrap_or_default());
+        let (start, end) = cycle_bounds(renewal_day, overrides, &date);
+        update.execute(params![card_id, start, end, id])?;
+    }
+    Ok(())
+}
+
+/// Creates the `v_*` views offered to reporting tools (Metabase, Datasette,
+/// Excel). Their columns are documented in the README and only ever gain
+/// new ones, so queries written against them keep working.
+fn init_views(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "-- One row per transaction, with its card's name and statement cycle
+        CREATE VIEW IF NOT EXISTS v_spending_enriched AS
+        SELECT s.id, s.card_id, c.name AS card_name, s.date, s.amount, s.category, s.payment_category,
+               s.description, s.miles_earned, s.status, s.tag,
+               (SELECT MAX(t.cycle_start) FROM cycle_totals t
+                WHERE t.card_id = s.card_id AND t.cycle_start <= s.date) AS cycle_start
+        FROM spending s JOIN cards c ON c.id = s.card_id;
+
+        -- One row per card and statement cycle that has spending; recreated
+        -- since reward_limit_remaining learned caps in miles
+        DROP VIEW IF EXISTS v_cycle_summary;
+        CREATE VIEW v_cycle_summary AS
+        SELECT totals.card_id, totals.card_name, totals.cycle_start,
+               closed.cycle_end,
+               totals.posted_spend, totals.scheduled_spend, totals.miles_earned, totals.transaction_count,
+               c.max_reward_limit,
+               CASE WHEN c.cap_unit = 'miles' THEN MAX(c.max_reward_limit - totals.miles_earned, 0)
+                    ELSE MAX(c.max_reward_limit - totals.posted_spend, 0) END AS reward_limit_remaining,
+               c.min_spend,
+               c.min_spend IS NULL OR totals.posted_spend >= c.min_spend AS min_spend_met,
+               closed.id IS NOT NULL AS closed
+        FROM (SELECT card_id, card_name, cycle_start,
+                     TOTAL(CASE WHEN status = 'posted' THEN amount END) AS posted_spend,
+                     TOTAL(CASE WHEN status = 'scheduled' THEN amount END) AS scheduled_spend,
+                     TOTAL(CASE WHEN status = 'posted' THEN miles_earned END) AS miles_earned,
+                     COUNT(CASE WHEN status = 'posted' THEN 1 END) AS transaction_count
+              FROM v_spending_enriched
+              GROUP BY card_id, cycle_start) totals
+        JOIN cards c ON c.id = totals.card_id
+        LEFT JOIN closed_cycles closed
+               ON closed.card_id = totals.card_id AND closed.cycle_start = totals.cycle_start;
+
+        -- One row per card, spending category and payment category it earns on
+        CREATE VIEW IF NOT EXISTS v_card_rules AS
+        SELECT c.id AS card_id, c.name AS card_name, category.value AS category,
+               payment.value AS payment_category,
+               c.miles_per_dollar, c.block_size, c.miles_per_dollar / c.block_size AS effective_rate,
+               c.miles_per_dollar_foreign, c.max_reward_limit, c.min_spend, c.annual_fee,
+               c.statement_renewal_date, c.posting_delay
+        FROM cards c, json_each(c.categories) category, json_each(c.payment_categories) payment;",
+    )
+}
+
+/// Adds a column to an existing table if it is missing, so databases created
+/// before the column existed keep working.
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !table_columns(conn, table)?.iter().any(|c| c == column) {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
     Ok(())
 }
 
-/// Opens (or creates) the SQLite database file and ensures tables exist.
-pub fn init_db() -> Result<Connection> {
-    let conn = Connection::open("cc_tracker.db")?;
+/// Names of a table's columns (empty if the table does not exist).
+pub fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(columns)
+}
+
+/// How the database is opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpenMode {
+    /// Tables are created or upgraded as needed
+    ReadWrite,
+    /// Nothing is ever written, not even a table upgrade, so an existing
+    /// file such as a backup is left exactly as it was
+    ReadOnly,
+}
+
+/// Opens (or creates) the SQLite database file and ensures tables exist,
+/// creating its directory if needed. In `ReadOnly` mode the file must
+/// already exist and is opened as it is, without creating or upgrading
+/// anything.
+pub fn init_db(path: &Path, mode: OpenMode) -> Result<Connection> {
+    if mode == OpenMode::ReadOnly {
+        if !path.is_file() {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+                Some(format!("no database file at {}", path.display())),
+            ));
+        }
+        let mut conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        conn.trace(Some(trace_sql));
+        register_functions(&conn)?;
+        return Ok(conn);
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+                Some(format!("cannot create {}: {}", dir.display(), e)),
+            )
+        })?;
+    }
+    let mut conn = Connection::open(path)?;
+    conn.trace(Some(trace_sql));
     init_tables(&conn)?;
     Ok(conn)
 }
 
+/// Logs each statement as executed, with its parameters bound (`-vv`).
+fn trace_sql(sql: &str) {
+    tracing::trace!(target: "cc_tracker::sql", "{}", sql);
+}
+
 // ── Card operations ──────────────────────────────────────────────
 
-pub fn add_card(
-    conn: &Connection,
-    name: &str,
-    categories: &[String],
-    payment_categories: &[String],
-    miles_per_dollar: f64,
-    miles_per_dollar_foreign: Option<f64>,
-    block_size: f64,
-    statement_renewal_date: i32,
-    max_reward_limit: Option<f64>,
-    min_spend: Option<f64>,
-) -> Result<i64> {
-    let categories_json = serde_json::to_string(categories).unwrap();
-    let payment_categories_json = serde_json::to_string(payment_categories).unwrap();
+pub fn add_card(conn: &Connection, card: &NewCard) -> Result<i64> {
+    let categories_json = serde_json::to_string(&card.categories).expect("strings serialize to JSON");
+    let payment_categories_json = serde_json::to_string(&card.payment_categories).expect("strings serialize to JSON");
     conn.execute(
-        "INSERT INTO cards (name, categories, payment_categories, miles_per_dollar, miles_per_dollar_foreign, block_size, statement_renewal_date, max_reward_limit, min_spend)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![name, categories_json, payment_categories_json, miles_per_dollar, miles_per_dollar_foreign, block_size, statement_renewal_date, max_reward_limit, min_spend],
+        "INSERT INTO cards (name, categories, payment_categories, miles_per_dollar, miles_per_dollar_foreign, block_size, statement_renewal_date, max_reward_limit, min_spend, posting_delay, bank_time_offset_minutes, cutoff_hour, annual_fee, starting_balance, balance_alert, cashback_percent, foreign_blocks, cap_unit, min_spend_exclusions, program, fee_posting_month, reward_type, miles_expiry_months, fx_fee_percent, excluded_categories, payment_due_days)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26)",
+        params![
+            card.name,
+            categories_json,
+            payment_categories_json,
+            card.miles_per_dollar,
+            card.miles_per_dollar_foreign,
+            card.block_size,
+            card.statement_renewal_date,
+            card.max_reward_limit,
+            card.min_spend,
+            card.posting_delay,
+            card.bank_time_offset_minutes,
+            card.cutoff_hour,
+            card.annual_fee,
+            card.starting_balance,
+            card.balance_alert,
+            card.cashback_percent,
+            card.foreign_blocks,
+            card.cap_unit.as_str(),
+            serde_json::to_string(&card.min_spend_exclusions).expect("strings serialize to JSON"),
+            card.program,
+            card.fee_posting_month,
+            card.reward_type.as_str(),
+            card.miles_expiry_months,
+            card.fx_fee_percent,
+            serde_json::to_string(&card.excluded_categories).expect("strings serialize to JSON"),
+            card.payment_due_days
+        ],
     )?;
     Ok(conn.last_insert_rowid())
 }
 
-pub fn list_cards(conn: &Connection) -> Result<Vec<Card>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, name, categories, payment_categories, miles_per_dollar,
+/// Columns selected for a `Card`, in the order `card_from_row` reads them.
+const CARD_COLUMNS: &str = "id, name, categories, payment_categories, miles_per_dollar,
                 miles_per_dollar_foreign, block_size,
-                statement_renewal_date, max_reward_limit, min_spend
-         FROM cards",
-    )?;
-    let rows = stmt.query_map([], |row| {
-        Ok(Card {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            categories: row.get(2)?,
-            payment_categories: row.get(3)?,
-            miles_per_dollar: row.get(4)?,
-            miles_per_dollar_foreign: row.get(5)?,
-            block_size: row.get(6)?,
-            statement_renewal_date: row.get(7)?,
-            max_reward_limit: row.get(8)?,
-            min_spend: row.get(9)?,
-        })
-    })?;
+                statement_renewal_date, max_reward_limit, min_spend, posting_delay,
+                bank_time_offset_minutes, cutoff_hour, annual_fee,
+                starting_balance, balance_alert, cashback_percent, foreign_blocks, cap_unit,
+                min_spend_exclusions, program, fee_posting_month, reward_type, miles_expiry_months, fx_fee_percent,
+                excluded_categories, closed_on, cycle_overrides, payment_due_days";
+
+fn card_from_row(row: &rusqlite::Row) -> Result<Card> {
+    Ok(Card {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        categories: row.get(2)?,
+        payment_categories: row.get(3)?,
+        miles_per_dollar: row.get(4)?,
+        miles_per_dollar_foreign: row.get(5)?,
+        block_size: row.get(6)?,
+        statement_renewal_date: row.get(7)?,
+        max_reward_limit: row.get(8)?,
+        min_spend: row.get(9)?,
+        posting_delay: row.get(10)?,
+        bank_time_offset_minutes: row.get(11)?,
+        cutoff_hour: row.get(12)?,
+        annual_fee: row.get(13)?,
+        starting_balance: row.get(14)?,
+        balance_alert: row.get(15)?,
+        cashback_percent: row.get(16)?,
+        foreign_blocks: row.get(17)?,
+        cap_unit: CapUnit::from_db(&row.get::<_, String>(18)?),
+        min_spend_exclusions: row.get(19)?,
+        program: row.get(20)?,
+        fee_posting_month: row.get(21)?,
+        reward_type: RewardType::from_db(&row.get::<_, String>(22)?),
+        miles_expiry_months: row.get(23)?,
+        fx_fee_percent: row.get(24)?,
+        excluded_categories: row.get(25)?,
+        payment_due_days: row.get(28)?,
+        closed_on: row.get(26)?,
+        cycle_overrides: row.get(27)?,
+    })
+}
+
+pub fn list_cards(conn: &Connection) -> Result<Vec<Card>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM cards", CARD_COLUMNS))?;
+    let rows = stmt.query_map([], card_from_row)?;
 
     let mut cards = Vec::new();
     for card in rows {
@@ -89,717 +650,3999 @@ pub fn list_cards(conn: &Connection) -> Result<Vec<Card>> {
     Ok(cards)
 }
 
+pub fn get_card(conn: &Connection, id: i64) -> Result<Option<Card>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM cards WHERE id = ?1", CARD_COLUMNS))?;
+    let mut rows = stmt.query_map(params![id], card_from_row)?;
+    rows.next().transpose()
+}
+
 pub fn remove_card(conn: &Connection, id: i64) -> Result<bool> {
+    // Removing the card discards its closed cycles too, which unlocks their spending
+    conn.execute("DELETE FROM closed_cycles WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM card_rate_history WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM payments WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM charges WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM import_review WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM merchant_partnerships WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM promotions WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM recurring WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM redemptions WHERE card_id = ?1", params![id])?;
+    conn.execute("DELETE FROM miles_ledger WHERE card_id = ?1", params![id])?;
     conn.execute("DELETE FROM spending WHERE card_id = ?1", params![id])?;
     let changed = conn.execute("DELETE FROM cards WHERE id = ?1", params![id])?;
     Ok(changed > 0)
 }
 
-/// Converts a (year, month, day) to days since Unix epoch using the
-/// algorithm from http://howardhinnant.github.io/date_algorithms.html
-fn ymd_to_days(year: i32, month: i32, day: i32) -> i32 {
-    let y = if month <= 2 { year - 1 } else { year };
-    let era = (if y >= 0 { y } else { y - 399 }) / 400;
-    let yoe = y - era * 400;
-    let m = month;
-    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day - 1;
-    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
-    era * 146097 + doe - 719468
-}
-
-/// Returns the day of week for a date: 0=Monday, 1=Tuesday, ... 5=Saturday, 6=Sunday.
-fn day_of_week(year: i32, month: i32, day: i32) -> i32 {
-    let days = ymd_to_days(year, month, day);
-    // 1970-01-01 was a Thursday (day 3 in 0=Mon scheme)
-    ((days % 7) + 7 + 3) % 7
-}
-
-/// If the given date falls on a weekend, moves it to the previous Friday.
-/// Returns (year, month, day) adjusted.
-fn adjust_for_weekend(year: i32, month: i32, day: i32) -> (i32, i32, i32) {
-    let dow = day_of_week(year, month, day);
-    let shift = match dow {
-        5 => 1, // Saturday → Friday (subtract 1 day)
-        6 => 2, // Sunday → Friday (subtract 2 days)
-        _ => 0,
-    };
-    if shift == 0 {
-        return (year, month, day);
-    }
-    let days = ymd_to_days(year, month, day) - shift;
-    days_to_ymd(days)
-}
-
-/// Converts days since Unix epoch back to (year, month, day).
-fn days_to_ymd(days: i32) -> (i32, i32, i32) {
-    let z = days + 719468;
-    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
-    let doe = z - era * 146097;
-    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
-    let y = yoe + era * 400;
-    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
-    let mp = (5 * doy + 2) / 153;
-    let d = doy - (153 * mp + 2) / 5 + 1;
-    let m = if mp < 10 { mp + 3 } else { mp - 9 };
-    let y = if m <= 2 { y + 1 } else { y };
-    (y, m, d)
-}
-
-/// Returns the start date of the current statement cycle for a card,
-/// given its renewal day and a reference date (YYYY-MM-DD).
-/// If the renewal day falls on a weekend, it is shifted to the previous Friday.
-fn cycle_start_date(renewal_day: i32, reference_date: &str) -> String {
-    let parts: Vec<&str> = reference_date.split('-').collect();
-    let year: i32 = parts[0].parse().unwrap();
-    let month: i32 = parts[1].parse().unwrap();
-    let day: i32 = parts[2].parse().unwrap();
-
-    // Compute the adjusted renewal date for this month
-    let (ay, am, ad) = adjust_for_weekend(year, month, renewal_day);
-
-    if day >= ad && am == month {
-        // Current cycle started this month (on the adjusted date)
-        format!("{:04}-{:02}-{:02}", ay, am, ad)
-    } else {
-        // Current cycle started last month
-        let (prev_y, prev_m) = if month == 1 { (year - 1, 12) } else { (year, month - 1) };
-        let (py, pm, pd) = adjust_for_weekend(prev_y, prev_m, renewal_day);
-        format!("{:04}-{:02}-{:02}", py, pm, pd)
-    }
+// ── Merchant partnerships ────────────────────────────────────────
+
+/// Gives a card `bonus_miles_per_dollar` extra miles per block at merchants
+/// matching `merchant_pattern` (see `rules::merchant_pattern`). The bonus
+/// applies to recommendations for purchases at the merchant and to spending
+/// recorded with its name.
+pub fn add_partnership(conn: &Connection, card_id: i64, merchant_pattern: &str, bonus_miles_per_dollar: f64) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO merchant_partnerships (card_id, merchant_pattern, bonus_miles_per_dollar) VALUES (?1, ?2, ?3)",
+        params![card_id, merchant_pattern, bonus_miles_per_dollar],
+    )?;
+    Ok(conn.last_insert_rowid())
 }
 
-pub fn best_card_for_category(
+pub fn list_partnerships(conn: &Connection, card_id: Option<i64>) -> Result<Vec<MerchantPartnership>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, merchant_pattern, bonus_miles_per_dollar FROM merchant_partnerships
+         WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY card_id, id",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(MerchantPartnership {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            merchant_pattern: row.get(2)?,
+            bonus_miles_per_dollar: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn remove_partnership(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM merchant_partnerships WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
+
+/// The card's partnership bonus at a merchant, if it has one there.
+fn partnership_at(conn: &Connection, card_id: i64, merchant: &str) -> Result<Option<MerchantPartnership>> {
+    Ok(rules::partnership_for(&list_partnerships(conn, Some(card_id))?, merchant).cloned())
+}
+
+// ── Promotions ───────────────────────────────────────────────────
+
+/// Gives a card `miles_per_dollar` miles per block, in place of its own
+/// rate, on purchases in `category` dated from `start_date` to `end_date`.
+/// Recommendations for such purchases consider the card even if it does not
+/// list the category. When promotions overlap, the highest rate applies.
+pub fn add_promotion(
     conn: &Connection,
+    card_id: i64,
     category: &str,
-    amount: f64,
-    payment_category: &str,
-    date: &str,
-) -> Result<Vec<CardRecommendation>> {
-    // Step 1: Find all cards that match the spending category AND payment category
-    let mut stmt = conn.prepare(
-        "SELECT DISTINCT c.id, c.name, c.miles_per_dollar, c.block_size,
-                (c.miles_per_dollar / c.block_size) AS effective_rate,
-                c.max_reward_limit, c.min_spend, c.statement_renewal_date
-         FROM cards c, json_each(c.categories) j, json_each(c.payment_categories) p
-         WHERE LOWER(j.value) = LOWER(?1)
-           AND LOWER(p.value) = LOWER(?2)
-         ORDER BY effective_rate DESC",
-    )?;
-
-    struct CandidateCard {
-        id: i64,
-        name: String,
-        miles_per_dollar: f64,
-        block_size: f64,
-        effective_rate: f64,
-        max_reward_limit: Option<f64>,
-        min_spend: Option<f64>,
-        statement_renewal_date: i32,
-    }
+    miles_per_dollar: f64,
+    start_date: &str,
+    end_date: &str,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO promotions (card_id, category, miles_per_dollar, start_date, end_date) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![card_id, category, miles_per_dollar, start_date, end_date],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
 
-    let rows = stmt.query_map(params![category, payment_category], |row| {
-        Ok(CandidateCard {
+pub fn list_promotions(conn: &Connection, card_id: Option<i64>) -> Result<Vec<Promotion>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, category, miles_per_dollar, start_date, end_date FROM promotions
+         WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY card_id, start_date, id",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(Promotion {
             id: row.get(0)?,
-            name: row.get(1)?,
-            miles_per_dollar: row.get(2)?,
-            block_size: row.get(3)?,
-            effective_rate: row.get(4)?,
-            max_reward_limit: row.get(5)?,
-            min_spend: row.get(6)?,
-            statement_renewal_date: row.get(7)?,
+            card_id: row.get(1)?,
+            category: row.get(2)?,
+            miles_per_dollar: row.get(3)?,
+            start_date: row.get(4)?,
+            end_date: row.get(5)?,
         })
     })?;
+    rows.collect()
+}
 
-    let candidates: Vec<CandidateCard> = rows.collect::<Result<Vec<_>>>()?;
+pub fn remove_promotion(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM promotions WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
 
-    let mut results = Vec::new();
+// ── Recurring spending ───────────────────────────────────────────
+
+/// Adds a monthly charge of `amount` on `day_of_month`, first due on the
+/// first such day on or after `start_date`.
+pub fn add_recurring(
+    conn: &Connection,
+    card_id: i64,
+    amount: f64,
+    category: &str,
+    day_of_month: i32,
+    merchant: Option<&str>,
+    start_date: &str,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO recurring (card_id, amount, category, day_of_month, merchant, next_date)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![card_id, amount, category, day_of_month, merchant, engine::next_monthly_date(day_of_month, start_date)],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
 
-    for card in &candidates {
-        let miles_this_txn = calculate_miles(amount, card.block_size, card.miles_per_dollar);
+pub fn list_recurring(conn: &Connection, card_id: Option<i64>) -> Result<Vec<Recurring>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, amount, category, day_of_month, merchant, next_date FROM recurring
+         WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY card_id, day_of_month, id",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(Recurring {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            amount: row.get(2)?,
+            category: row.get(3)?,
+            day_of_month: row.get(4)?,
+            merchant: row.get(5)?,
+            next_date: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
 
-        // Step 2: Check max_reward_limit — sum spending in the current cycle
-        let cycle_start = cycle_start_date(card.statement_renewal_date, date);
-        let cycle_total: f64 = conn.query_row(
-            "SELECT COALESCE(SUM(amount), 0.0) FROM spending
-             WHERE card_id = ?1 AND date >= ?2",
-            params![card.id, cycle_start],
-            |row| row.get(0),
-        )?;
+pub fn remove_recurring(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM recurring WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
 
-        let remaining_limit = card.max_reward_limit.map(|limit| (limit - cycle_total).max(0.0));
+/// Records every recurring charge due up to and including `through` as
+/// posted spending, earning what `add_spending` would give it, and moves
+/// each on to its next charge. All or nothing: a charge falling in a
+/// closed statement cycle fails the lot.
+pub fn apply_recurring(conn: &Connection, through: &str) -> Result<Vec<AppliedRecurring>> {
+    let tx = conn.unchecked_transaction()?;
+    let mut applied = Vec::new();
+    for recurring in list_recurring(&tx, None)? {
+        let mut date = recurring.next_date.clone();
+        while date.as_str() <= through {
+            let (spending_id, miles_earned) = add_spending(
+                &tx,
+                recurring.card_id,
+                recurring.amount,
+                &recurring.category,
+                &date,
+                recurring.merchant.as_deref(),
+                false,
+                None,
+            )?;
+            applied.push(AppliedRecurring {
+                recurring_id: recurring.id,
+                spending_id,
+                card_id: recurring.card_id,
+                amount: recurring.amount,
+                category: recurring.category.clone(),
+                date: date.clone(),
+                miles_earned,
+            });
+            date = engine::next_monthly_date(recurring.day_of_month, &engine::add_days(&date, 1));
+        }
+        if date != recurring.next_date {
+            tx.execute("UPDATE recurring SET next_date = ?1 WHERE id = ?2", params![date, recurring.id])?;
+        }
+    }
+    tx.commit()?;
+    Ok(applied)
+}
 
-        // Check if adding this amount would exceed the reward limit
-        let exceeded_limit = match remaining_limit {
-            Some(remaining) => amount > remaining,
-            None => false, // no limit
-        };
+// ── Merchant rules ───────────────────────────────────────────────
 
-        // Step 3: Check min_spend — has the card met its minimum spend this cycle?
-        let min_spend_met = match card.min_spend {
-            Some(min) => cycle_total >= min,
-            None => true, // no minimum
-        };
+/// Stores a rule giving merchants matching `pattern` (see
+/// `rules::stored_pattern`) the category `category`.
+pub fn add_merchant_rule(conn: &Connection, pattern: &str, syntax: PatternSyntax, category: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO merchant_rules (pattern, syntax, category) VALUES (?1, ?2, ?3)",
+        params![pattern, syntax.as_str(), category],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
 
-        // Determine eligibility and reason
-        let (eligible, reason) = if exceeded_limit {
-            (false, format!("Exceeds reward limit (${:.2} remaining)", remaining_limit.unwrap()))
-        } else if !min_spend_met {
-            let shortfall = card.min_spend.unwrap() - cycle_total;
-            (false, format!("Min spend not met (${:.2} more needed)", shortfall))
-        } else {
-            (true, "Eligible".to_string())
-        };
+/// Lists the stored rules oldest first, the order they are tried in.
+pub fn list_merchant_rules(conn: &Connection) -> Result<Vec<MerchantRule>> {
+    let mut stmt = conn.prepare("SELECT id, pattern, syntax, category FROM merchant_rules ORDER BY id")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MerchantRule {
+            id: row.get(0)?,
+            pattern: row.get(1)?,
+            syntax: PatternSyntax::from_db(&row.get::<_, String>(2)?),
+            category: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
 
-        results.push(CardRecommendation {
-            card_name: card.name.clone(),
-            miles_per_dollar: card.miles_per_dollar,
-            block_size: card.block_size,
-            effective_rate: card.effective_rate,
-            miles_earned: miles_this_txn,
-            remaining_limit,
-            eligible,
-            reason,
-        });
-    }
+pub fn remove_merchant_rule(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM merchant_rules WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
 
-    // Sort: eligible cards first (by effective_rate DESC), then ineligible cards
-    results.sort_by(|a, b| {
-        b.eligible.cmp(&a.eligible)
-            .then(b.effective_rate.partial_cmp(&a.effective_rate).unwrap())
-    });
+// ── Loyalty programs ─────────────────────────────────────────────
 
-    Ok(results)
+pub fn add_program(conn: &Connection, name: &str, kind: ProgramKind, expiry_months: Option<i32>) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO programs (name, kind, expiry_months) VALUES (?1, ?2, ?3)",
+        params![name, kind.as_str(), expiry_months],
+    )?;
+    Ok(conn.last_insert_rowid())
 }
 
-// ── Spending operations ──────────────────────────────────────────
+pub fn list_programs(conn: &Connection) -> Result<Vec<Program>> {
+    let mut stmt = conn.prepare("SELECT id, name, kind, expiry_months FROM programs ORDER BY name")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Program {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            kind: ProgramKind::from_db(&row.get::<_, String>(2)?),
+            expiry_months: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
 
-/// Calculates miles earned: floor(amount / block_size) * miles_per_dollar
-fn calculate_miles(amount: f64, block_size: f64, miles_per_dollar: f64) -> f64 {
-    (amount / block_size).floor() * miles_per_dollar
+/// Sets how many months a program's miles stay valid, or None for never.
+pub fn set_program_expiry(conn: &Connection, id: i64, expiry_months: Option<i32>) -> Result<bool> {
+    let changed = conn.execute("UPDATE programs SET expiry_months = ?1 WHERE id = ?2", params![expiry_months, id])?;
+    Ok(changed > 0)
 }
 
-pub fn add_spending(
-    conn: &Connection,
-    card_id: i64,
-    amount: f64,
-    category: &str,
-    date: &str,
-) -> Result<(i64, f64)> {
-    // Look up the card to calculate miles
-    let (miles_per_dollar, block_size): (f64, f64) = conn.query_row(
-        "SELECT miles_per_dollar, block_size FROM cards WHERE id = ?1",
-        params![card_id],
-        |row| Ok((row.get(0)?, row.get(1)?)),
+/// Removes a program and its transfers to and from other programs. Cards
+/// naming it keep the name.
+pub fn remove_program(conn: &Connection, id: i64) -> Result<bool> {
+    conn.execute("DELETE FROM program_transfers WHERE from_program_id = ?1 OR to_program_id = ?1", params![id])?;
+    let changed = conn.execute("DELETE FROM programs WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
+
+pub fn add_program_transfer(conn: &Connection, from_program_id: i64, to_program_id: i64, ratio: f64) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO program_transfers (from_program_id, to_program_id, ratio) VALUES (?1, ?2, ?3)",
+        params![from_program_id, to_program_id, ratio],
     )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn list_program_transfers(conn: &Connection) -> Result<Vec<ProgramTransfer>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.from_program_id, f.name, t.to_program_id, p.name, t.ratio
+         FROM program_transfers t
+         JOIN programs f ON f.id = t.from_program_id
+         JOIN programs p ON p.id = t.to_program_id
+         ORDER BY f.name, p.name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(ProgramTransfer {
+            id: row.get(0)?,
+            from_program_id: row.get(1)?,
+            from_program: row.get(2)?,
+            to_program_id: row.get(3)?,
+            to_program: row.get(4)?,
+            ratio: row.get(5)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn remove_program_transfer(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM program_transfers WHERE id = ?1", params![id])?;
+    Ok(changed > 0)
+}
+
+// ── FX rates ─────────────────────────────────────────────────────
 
-    let miles_earned = calculate_miles(amount, block_size, miles_per_dollar);
+/// Stores the home currency a unit of `currency` converts to, replacing
+/// any earlier rate for it.
+pub fn set_fx_rate(conn: &Connection, currency: &str, rate: f64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO fx_rates (currency, rate, updated_on) VALUES (?1, ?2, ?3)
+         ON CONFLICT(currency) DO UPDATE SET rate = excluded.rate, updated_on = excluded.updated_on",
+        params![currency, rate, today()],
+    )?;
+    Ok(())
+}
 
+/// Inserts a rate as it was exported, keeping the day it was last set.
+pub fn restore_fx_rate(conn: &Connection, rate: &FxRate) -> Result<()> {
     conn.execute(
-        "INSERT INTO spending (card_id, amount, category, date, miles_earned)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![card_id, amount, category, date, miles_earned],
+        "INSERT INTO fx_rates (currency, rate, updated_on) VALUES (?1, ?2, ?3)",
+        params![rate.currency, rate.rate, rate.updated_on],
     )?;
+    Ok(())
+}
 
-    Ok((conn.last_insert_rowid(), miles_earned))
+pub fn list_fx_rates(conn: &Connection) -> Result<Vec<FxRate>> {
+    let mut stmt = conn.prepare("SELECT currency, rate, updated_on FROM fx_rates ORDER BY currency")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(FxRate {
+            currency: row.get(0)?,
+            rate: row.get(1)?,
+            updated_on: row.get(2)?,
+        })
+    })?;
+    rows.collect()
 }
 
-pub fn list_spending(conn: &Connection, card_id: Option<i64>) -> Result<Vec<Spending>> {
-    let mut results = Vec::new();
+pub fn remove_fx_rate(conn: &Connection, currency: &str) -> Result<bool> {
+    let changed = conn.execute("DELETE FROM fx_rates WHERE currency = ?1", params![currency])?;
+    Ok(changed > 0)
+}
 
-    let map_row = |row: &rusqlite::Row| -> rusqlite::Result<Spending> {
-        Ok(Spending {
-            id: row.get(0)?,
-            card_id: row.get(1)?,
-            amount: row.get(2)?,
-            category: row.get(3)?,
-            date: row.get(4)?,
-            miles_earned: row.get(5)?,
+// ── Categories ───────────────────────────────────────────────────
+
+/// Adds a spending category to those known. Returns false if it is known
+/// already, in any case.
+pub fn add_category(conn: &Connection, name: &str) -> Result<bool> {
+    let changed = conn.execute("INSERT OR IGNORE INTO categories (name) VALUES (?1)", params![name])?;
+    Ok(changed > 0)
+}
+
+/// Sets (or with None clears) a category's monthly budget, adding the
+/// category if it is not in the table yet.
+pub fn set_category_budget(conn: &Connection, name: &str, budget: Option<f64>) -> Result<()> {
+    conn.execute(
+        "INSERT INTO categories (name, monthly_budget) VALUES (?1, ?2)
+         ON CONFLICT (name) DO UPDATE SET monthly_budget = excluded.monthly_budget",
+        params![name, budget],
+    )?;
+    Ok(())
+}
+
+/// The known spending categories: those added and those cards or spending
+/// use, each once whatever its case, with how many cards earn in it and
+/// how many transactions it has.
+pub fn list_categories(conn: &Connection) -> Result<Vec<Category>> {
+    let mut stmt = conn.prepare(
+        "WITH known AS (
+            SELECT MIN(name) AS name FROM (
+                SELECT name FROM categories
+                UNION SELECT j.value FROM cards c, json_each(c.categories) j
+                UNION SELECT category FROM spending
+            )
+            GROUP BY LOWER(name)
+         )
+         SELECT k.name,
+                (SELECT COUNT(*) FROM cards c
+                 WHERE EXISTS (SELECT 1 FROM json_each(c.categories) j WHERE LOWER(j.value) = LOWER(k.name))),
+                (SELECT COUNT(*) FROM spending s WHERE LOWER(s.category) = LOWER(k.name)),
+                (SELECT monthly_budget FROM categories c WHERE c.name = k.name)
+         FROM known k
+         ORDER BY LOWER(k.name)",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Category {
+            name: row.get(0)?,
+            cards: row.get(1)?,
+            transactions: row.get(2)?,
+            monthly_budget: row.get(3)?,
         })
-    };
+    })?;
+    rows.collect()
+}
 
-    if let Some(id) = card_id {
-        let mut stmt = conn.prepare(
-            "SELECT id, card_id, amount, category, date, miles_earned
-             FROM spending WHERE card_id = ?1 ORDER BY date DESC",
-        )?;
-        let rows = stmt.query_map(params![id], map_row)?;
-        for row in rows {
-            results.push(row?);
+/// Renames a category everywhere it is used: cards' categories, minimum
+/// spend exclusions and excluded categories, spending, promotions, merchant
+/// rules and the import review queue. Renaming to a category that exists
+/// merges the two. Transactions in a closed cycle keep their category, and
+/// so does the category list while any do. Returns the cards and
+/// transactions changed and the closed-cycle transactions left alone.
+pub fn rename_category(conn: &Connection, from: &str, to: &str) -> Result<(usize, usize, usize)> {
+    let tx = conn.unchecked_transaction()?;
+    let cards = {
+        let mut stmt = tx.prepare("SELECT id, categories, min_spend_exclusions, excluded_categories FROM cards")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+        })?;
+        rows.collect::<Result<Vec<_>>>()?
+    };
+    let mut cards_changed = 0;
+    for (id, categories, min_spend_exclusions, excluded_categories) in cards {
+        let [categories, min_spend_exclusions, excluded_categories] =
+            [categories, min_spend_exclusions, excluded_categories].map(|json| renamed_categories(&json, from, to));
+        if categories.is_some() || min_spend_exclusions.is_some() || excluded_categories.is_some() {
+            tx.execute(
+                "UPDATE cards SET categories = COALESCE(?1, categories),
+                        min_spend_exclusions = COALESCE(?2, min_spend_exclusions),
+                        excluded_categories = COALESCE(?3, excluded_categories)
+                 WHERE id = ?4",
+                params![categories, min_spend_exclusions, excluded_categories, id],
+            )?;
+            cards_changed += 1;
         }
-    } else {
-        let mut stmt = conn.prepare(
-            "SELECT id, card_id, amount, category, date, miles_earned
-             FROM spending ORDER BY date DESC",
-        )?;
-        let rows = stmt.query_map([], map_row)?;
-        for row in rows {
-            results.push(row?);
+    }
+    let transactions = tx.execute(
+        "UPDATE spending SET category = ?2
+         WHERE LOWER(category) = LOWER(?1)
+           AND NOT EXISTS (SELECT 1 FROM closed_cycles c
+                           WHERE c.card_id = spending.card_id AND spending.date BETWEEN c.cycle_start AND c.cycle_end)",
+        params![from, to],
+    )?;
+    let frozen: i64 =
+        tx.query_row("SELECT COUNT(*) FROM spending WHERE LOWER(category) = LOWER(?1)", params![from], |row| row.get(0))?;
+    tx.execute("UPDATE promotions SET category = ?2 WHERE LOWER(category) = LOWER(?1)", params![from, to])?;
+    tx.execute("UPDATE recurring SET category = ?2 WHERE LOWER(category) = LOWER(?1)", params![from, to])?;
+    tx.execute("UPDATE merchant_rules SET category = ?2 WHERE LOWER(category) = LOWER(?1)", params![from, to])?;
+    tx.execute(
+        "UPDATE import_review SET suggested_category = ?2 WHERE LOWER(suggested_category) = LOWER(?1)",
+        params![from, to],
+    )?;
+    // A budget moves with the category, unless the one merged into has its own
+    let budget: Option<f64> =
+        tx.query_row("SELECT MAX(monthly_budget) FROM categories WHERE name = ?1", params![from], |row| row.get(0))?;
+    if frozen == 0 {
+        tx.execute("DELETE FROM categories WHERE name = ?1", params![from])?;
+    }
+    tx.execute("INSERT OR IGNORE INTO categories (name) VALUES (?1)", params![to])?;
+    tx.execute(
+        "UPDATE categories SET monthly_budget = COALESCE(monthly_budget, ?2) WHERE name = ?1",
+        params![to, budget],
+    )?;
+    tx.commit()?;
+    Ok((cards_changed, transactions, frozen as usize))
+}
+
+/// A JSON list of categories with `from` renamed to `to`, keeping only the
+/// first if that leaves two of a name; None if `from` is not in the list.
+pub fn renamed_categories(json: &str, from: &str, to: &str) -> Option<String> {
+    let categories: Vec<String> = serde_json::from_str(json).unwrap_or_default();
+    if !categories.iter().any(|c| c.eq_ignore_ascii_case(from)) {
+        return None;
+    }
+    let mut renamed: Vec<String> = Vec::new();
+    for category in categories {
+        let category = if category.eq_ignore_ascii_case(from) { to.to_string() } else { category };
+        if !renamed.iter().any(|c| c.eq_ignore_ascii_case(&category)) {
+            renamed.push(category);
         }
     }
+    Some(serde_json::to_string(&renamed).expect("strings serialize to JSON"))
+}
 
-    Ok(results)
+// ── Rate versions ────────────────────────────────────────────────
+
+/// Overwrites all of a card's settings, its name and earn rates included,
+/// with those in `card`. Unlike `change_card_rates` the old rates are not
+/// kept, so this corrects a card rather than recording a change to it.
+pub fn update_card(conn: &Connection, card_id: i64, card: &NewCard) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE cards SET name = ?1, categories = ?2, payment_categories = ?3, miles_per_dollar = ?4,
+                miles_per_dollar_foreign = ?5, block_size = ?6, statement_renewal_date = ?7,
+                max_reward_limit = ?8, min_spend = ?9, posting_delay = ?10, bank_time_offset_minutes = ?11,
+                cutoff_hour = ?12, annual_fee = ?13, starting_balance = ?14, balance_alert = ?15,
+                cashback_percent = ?16, foreign_blocks = ?17, cap_unit = ?18, min_spend_exclusions = ?19,
+                program = ?20, fee_posting_month = ?21, reward_type = ?22, miles_expiry_months = ?23,
+                fx_fee_percent = ?24, excluded_categories = ?25, payment_due_days = ?26
+         WHERE id = ?27",
+        params![
+            card.name,
+            serde_json::to_string(&card.categories).expect("strings serialize to JSON"),
+            serde_json::to_string(&card.payment_categories).expect("strings serialize to JSON"),
+            card.miles_per_dollar,
+            card.miles_per_dollar_foreign,
+            card.block_size,
+            card.statement_renewal_date,
+            card.max_reward_limit,
+            card.min_spend,
+            card.posting_delay,
+            card.bank_time_offset_minutes,
+            card.cutoff_hour,
+            card.annual_fee,
+            card.starting_balance,
+            card.balance_alert,
+            card.cashback_percent,
+            card.foreign_blocks,
+            card.cap_unit.as_str(),
+            serde_json::to_string(&card.min_spend_exclusions).expect("strings serialize to JSON"),
+            card.program,
+            card.fee_posting_month,
+            card.reward_type.as_str(),
+            card.miles_expiry_months,
+            card.fx_fee_percent,
+            serde_json::to_string(&card.excluded_categories).expect("strings serialize to JSON"),
+            card.payment_due_days,
+            card_id
+        ],
+    )?;
+    settle_cycles(conn)?;
+    Ok(changed > 0)
+}
+
+/// Overwrites a card's settings other than its name and earn rates (which
+/// `change_card_rates` versions) with those in `card`.
+pub fn update_card_details(conn: &Connection, card_id: i64, card: &NewCard) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE cards SET categories = ?1, payment_categories = ?2, miles_per_dollar_foreign = ?3,
+                statement_renewal_date = ?4, posting_delay = ?5, bank_time_offset_minutes = ?6, cutoff_hour = ?7,
+                annual_fee = ?8, starting_balance = ?9, balance_alert = ?10, cashback_percent = ?11,
+                foreign_blocks = ?12, cap_unit = ?13, min_spend_exclusions = ?14,
+                program = ?15, fee_posting_month = ?16, reward_type = ?17, miles_expiry_months = ?18,
+                fx_fee_percent = ?19, excluded_categories = ?20, payment_due_days = ?21
+         WHERE id = ?22",
+        params![
+            serde_json::to_string(&card.categories).expect("strings serialize to JSON"),
+            serde_json::to_string(&card.payment_categories).expect("strings serialize to JSON"),
+            card.miles_per_dollar_foreign,
+            card.statement_renewal_date,
+            card.posting_delay,
+            card.bank_time_offset_minutes,
+            card.cutoff_hour,
+            card.annual_fee,
+            card.starting_balance,
+            card.balance_alert,
+            card.cashback_percent,
+            card.foreign_blocks,
+            card.cap_unit.as_str(),
+            serde_json::to_string(&card.min_spend_exclusions).expect("strings serialize to JSON"),
+            card.program,
+            card.fee_posting_month,
+            card.reward_type.as_str(),
+            card.miles_expiry_months,
+            card.fx_fee_percent,
+            serde_json::to_string(&card.excluded_categories).expect("strings serialize to JSON"),
+            card.payment_due_days,
+            card_id
+        ],
+    )?;
+    settle_cycles(conn)?;
+    Ok(changed > 0)
+}
+
+/// Changes a card's earn rates from `effective_from` onwards. The previous
+/// rates are kept in `card_rate_history` so queries for earlier dates still
+/// see the rates that applied then.
+pub fn change_card_rates(conn: &Connection, card_id: i64, effective_from: &str, rates: &CardRates) -> Result<bool> {
+    let Some(card) = get_card(conn, card_id)? else {
+        return Ok(false);
+    };
+    conn.execute(
+        "INSERT INTO card_rate_history (card_id, valid_until, miles_per_dollar, block_size, max_reward_limit, min_spend)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![card_id, effective_from, card.miles_per_dollar, card.block_size, card.max_reward_limit, card.min_spend],
+    )?;
+    conn.execute(
+        "UPDATE cards SET miles_per_dollar = ?1, block_size = ?2, max_reward_limit = ?3, min_spend = ?4
+         WHERE id = ?5",
+        params![rates.miles_per_dollar, rates.block_size, rates.max_reward_limit, rates.min_spend, card_id],
+    )?;
+    Ok(true)
+}
+
+/// Returns the most recent date a card's rates changed, if they ever have.
+pub fn last_rate_change(conn: &Connection, card_id: i64) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT MAX(valid_until) FROM card_rate_history WHERE card_id = ?1",
+        params![card_id],
+        |row| row.get(0),
+    )
+}
+
+/// Returns every card's earlier rates, oldest change first.
+pub fn list_rate_history(conn: &Connection) -> Result<Vec<RateChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, valid_until, miles_per_dollar, block_size, max_reward_limit, min_spend
+         FROM card_rate_history ORDER BY card_id, valid_until, id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(RateChange {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            valid_until: row.get(2)?,
+            rates: CardRates {
+                miles_per_dollar: row.get(3)?,
+                block_size: row.get(4)?,
+                max_reward_limit: row.get(5)?,
+                min_spend: row.get(6)?,
+            },
+        })
+    })?;
+    rows.collect()
+}
+
+/// Inserts earlier rates as they were exported, leaving the card's current
+/// rates alone.
+pub fn restore_rate_change(conn: &Connection, change: &RateChange) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO card_rate_history (card_id, valid_until, miles_per_dollar, block_size, max_reward_limit, min_spend)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            change.card_id,
+            change.valid_until,
+            change.rates.miles_per_dollar,
+            change.rates.block_size,
+            change.rates.max_reward_limit,
+            change.rates.min_spend
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Returns today's date (UTC) as YYYY-MM-DD.
+pub fn today() -> String {
+    // A clock set before 1970 reads as the epoch
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (y, m, d) = days_to_ymd((now / 86400) as i32);
+    format_date(y, m, d)
+}
+
+/// Returns the calendar date the bank assigns to a transaction made at local
+/// `date`, `local_minutes` after midnight (see `dates::parse_time`): the time
+/// is converted to the bank's clock, and anything at or after the card's
+/// cutoff hour rolls over to the next day.
+pub fn bank_transaction_date(card: &Card, date: &str, local_minutes: i32) -> String {
+    let bank_minutes = local_minutes + card.bank_time_offset_minutes;
+
+    let mut day_shift = bank_minutes.div_euclid(24 * 60);
+    let bank_hour = bank_minutes.rem_euclid(24 * 60) / 60;
+    if card.cutoff_hour > 0 && bank_hour >= card.cutoff_hour {
+        day_shift += 1;
+    }
+
+    let (y, m, d) = parse_date(date);
+    let (y, m, d) = days_to_ymd(ymd_to_days(y, m, d) + day_shift);
+    format_date(y, m, d)
+}
+
+/// Ranks the cards matching a category and payment category for a purchase
+/// on `date`. With `options.known_by`, only spending dated and recorded on or
+/// before that date counts, answering "what would the tracker have said then".
+/// Scheduled spending only counts with `options.include_scheduled`.
+pub fn best_card_for_category(
+    conn: &Connection,
+    category: &str,
+    amount: f64,
+    payment_category: &str,
+    date: &str,
+    options: &RecommendOptions,
+) -> Result<Vec<CardRecommendation>> {
+    let purchase = Purchase {
+        category,
+        payment_category,
+        amount,
+        merchant: options.merchant.as_deref(),
+        foreign_amount: options.foreign_amount,
+        foreign: options.is_foreign(),
+    };
+    let mut results = Vec::new();
+    let monthly_spends = if options.fee_adjusted {
+        average_monthly_spends(conn, date)?
+    } else {
+        HashMap::new()
+    };
+
+    for (card, cycle) in candidate_cycles(conn, category, payment_category, date, options)? {
+        // Step 3: Check the card's reward cap and minimum spend against this cycle
+        let monthly_spend = options
+            .fee_adjusted
+            .then(|| monthly_spends.get(&card.id).copied().unwrap_or(0.0));
+        let mut recommendation = recommend(&card, &purchase, &cycle.state(), monthly_spend, options.reward_value(card.reward_type));
+        if options.explain {
+            recommendation.explanation = explain(&card, &purchase, &cycle, monthly_spend, options, &recommendation);
+        }
+        results.push(recommendation);
+    }
+
+    rank_recommendations(&mut results);
+
+    Ok(results)
+}
+
+/// Suggests splitting a purchase between two cards when it would exceed a
+/// card's remaining cap (see `engine::suggest_split`).
+pub fn suggest_split(
+    conn: &Connection,
+    category: &str,
+    amount: f64,
+    payment_category: &str,
+    date: &str,
+    options: &RecommendOptions,
+) -> Result<Option<SplitSuggestion>> {
+    let candidates = candidate_cycles(conn, category, payment_category, date, options)?;
+    Ok(engine::suggest_split(&candidates, amount))
+}
+
+/// The cards matching a purchase's categories, with the rates that applied
+/// on `date`, each with its statement cycle and the spending already in it.
+/// One statement answers for every card, however many match.
+fn candidate_cycles(
+    conn: &Connection,
+    category: &str,
+    payment_category: &str,
+    date: &str,
+    options: &RecommendOptions,
+) -> Result<Vec<(CandidateCard, CycleWindow)>> {
+    // Step 1: Find all cards that match the spending category AND payment
+    // category, with the rates they had on `date` if they have since changed
+    // (the first rate version still valid then).
+    // Step 2: Total the spending in each card's cycle: the cycle_totals
+    // cache plus any transactions not yet settled into it. The cache cannot
+    // tell when rows were recorded, so replaying an earlier day sums the
+    // transactions.
+    let mut stmt = conn.prepare_cached(
+        "WITH matching AS (
+            SELECT c.*, statement_cycle_start(c.statement_renewal_date, ?3, c.cycle_overrides) AS cycle_start,
+                   statement_cycle_end(c.statement_renewal_date, ?3, c.cycle_overrides) AS cycle_end,
+                   (SELECT h.id FROM card_rate_history h WHERE h.card_id = c.id AND h.valid_until > ?3
+                    ORDER BY h.valid_until LIMIT 1) AS history_id
+            FROM cards c
+            WHERE (EXISTS (SELECT 1 FROM json_each(c.categories) j WHERE LOWER(j.value) = LOWER(?1))
+                   OR EXISTS (SELECT 1 FROM promotions pr
+                              WHERE pr.card_id = c.id AND LOWER(pr.category) = LOWER(?1)
+                                AND ?3 BETWEEN pr.start_date AND pr.end_date))
+              AND EXISTS (SELECT 1 FROM json_each(c.payment_categories) p WHERE LOWER(p.value) = LOWER(?2))
+              AND (c.closed_on IS NULL OR c.closed_on > ?3)
+         )
+         SELECT m.id, m.name,
+                COALESCE(h.miles_per_dollar, m.miles_per_dollar), COALESCE(h.block_size, m.block_size),
+                CASE WHEN h.id IS NULL THEN m.max_reward_limit ELSE h.max_reward_limit END,
+                CASE WHEN h.id IS NULL THEN m.min_spend ELSE h.min_spend END,
+                m.statement_renewal_date, m.annual_fee, h.id IS NOT NULL, m.cycle_start, m.cycle_end,
+                m.cashback_percent, m.foreign_blocks,
+                CASE WHEN ?4 IS NULL THEN
+                    ROUND((SELECT COALESCE(SUM(t.total), 0.0) FROM cycle_totals t
+                           WHERE t.card_id = m.id AND t.cycle_start = m.cycle_start AND (t.status = 'posted' OR ?5))
+                          + (SELECT COALESCE(SUM(s.amount), 0.0) FROM spending s
+                             WHERE s.card_id = m.id AND s.date >= m.cycle_start AND s.date <= m.cycle_end
+                               AND (s.cycle_card_id IS NOT s.card_id OR s.date NOT BETWEEN s.cycle_start AND s.cycle_end)
+                               AND (s.status = 'posted' OR ?5)), 2)
+                ELSE
+                    (SELECT COALESCE(SUM(s.amount), 0.0) FROM spending s
+                     WHERE s.card_id = m.id AND s.date >= m.cycle_start AND s.date <= m.cycle_end
+                       AND s.date <= ?4 AND COALESCE(s.recorded_on, s.date) <= ?4
+                       AND (s.status = 'posted' OR ?5))
+                END,
+                m.cap_unit,
+                CASE WHEN m.cap_unit = 'miles' THEN
+                    (SELECT COALESCE(SUM(s.miles_earned), 0.0) FROM spending s
+                     WHERE s.card_id = m.id AND s.date >= m.cycle_start AND s.date <= m.cycle_end
+                       AND (?4 IS NULL OR (s.date <= ?4 AND COALESCE(s.recorded_on, s.date) <= ?4))
+                       AND (s.status = 'posted' OR ?5))
+                ELSE 0.0 END,
+                m.min_spend_exclusions,
+                (SELECT COALESCE(SUM(s.amount), 0.0) FROM spending s
+                 WHERE s.card_id = m.id AND s.date >= m.cycle_start AND s.date <= m.cycle_end
+                   AND (?4 IS NULL OR (s.date <= ?4 AND COALESCE(s.recorded_on, s.date) <= ?4))
+                   AND (s.status = 'posted' OR ?5)
+                   AND LOWER(s.category) IN (SELECT LOWER(j.value) FROM json_each(m.min_spend_exclusions) j)),
+                m.reward_type, m.miles_per_dollar_foreign, m.fx_fee_percent,
+                EXISTS (SELECT 1 FROM json_each(m.excluded_categories) j WHERE LOWER(j.value) = LOWER(?1))
+         FROM matching m
+         LEFT JOIN card_rate_history h ON h.id = m.history_id
+         ORDER BY m.miles_per_dollar / m.block_size DESC",
+    )?;
+    let rows = stmt.query_map(
+        params![category, payment_category, date, options.known_by, options.include_scheduled],
+        |row| {
+            let card = CandidateCard {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                miles_per_dollar: row.get(2)?,
+                block_size: row.get(3)?,
+                max_reward_limit: row.get(4)?,
+                min_spend: row.get(5)?,
+                min_spend_exclusions: serde_json::from_str::<Vec<String>>(&row.get::<_, String>(16)?)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|c| c.to_lowercase())
+                    .collect(),
+                statement_renewal_date: row.get(6)?,
+                annual_fee: row.get(7)?,
+                reward_type: RewardType::from_db(&row.get::<_, String>(18)?),
+                cashback_percent: row.get(11)?,
+                foreign_blocks: row.get(12)?,
+                cap_unit: CapUnit::from_db(&row.get::<_, String>(14)?),
+                miles_per_dollar_foreign: row.get(19)?,
+                fx_fee_percent: row.get(20)?,
+                excluded_category: row.get(21)?,
+                foreign_rate: false,
+                historical_rates: row.get(8)?,
+                partnership: None,
+                promotion: None,
+            };
+            let cycle = CycleWindow {
+                start: row.get(9)?,
+                end: row.get(10)?,
+                total: row.get(13)?,
+                miles: row.get(15)?,
+                excluded: row.get(17)?,
+            };
+            Ok((card, cycle))
+        },
+    )?;
+    let mut cycles = rows.collect::<Result<Vec<_>>>()?;
+
+    for (card, cycle) in &mut cycles {
+        cycle.add_planned(card, &options.planned);
+        if options.is_foreign() {
+            card.use_foreign_rate();
+        }
+    }
+
+    let promotions = list_promotions(conn, None)?;
+    for (card, _) in &mut cycles {
+        card.promotion = engine::promotion_for(&promotions, card.id, category, date).cloned();
+    }
+
+    if let Some(merchant) = &options.merchant {
+        let partnerships = list_partnerships(conn, None)?;
+        for (card, _) in &mut cycles {
+            let own: Vec<MerchantPartnership> =
+                partnerships.iter().filter(|p| p.card_id == card.id).cloned().collect();
+            card.partnership = rules::partnership_for(&own, merchant).cloned();
+        }
+    }
+    Ok(cycles)
+}
+
+/// Returns each card's average spend per active month over the 12 months
+/// up to `date`, counting only months that had posted spending. Cards
+/// without such spending are left out.
+pub fn average_monthly_spends(conn: &Connection, date: &str) -> Result<HashMap<i64, f64>> {
+    let (year, month, day) = parse_date(date);
+    let window_start = format_date(year - 1, month, day);
+    let mut stmt = conn.prepare_cached(
+        "SELECT card_id, SUM(amount) / COUNT(DISTINCT substr(date, 1, 7)) FROM spending
+         WHERE date > ?1 AND date <= ?2 AND status = 'posted'
+         GROUP BY card_id",
+    )?;
+    let rows = stmt.query_map(params![window_start, date], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+// ── Spending operations ──────────────────────────────────────────
+
+/// Records a transaction and the miles it earns; a `foreign` one, charged
+/// in a foreign currency, earns at the card's foreign rate if it has one.
+/// `amount` is in the home currency; `original` keeps what was charged in
+/// another currency before conversion, and implies `foreign`.
+#[allow(clippy::too_many_arguments)]
+pub fn add_spending(
+    conn: &Connection,
+    card_id: i64,
+    amount: f64,
+    category: &str,
+    date: &str,
+    merchant: Option<&str>,
+    foreign: bool,
+    original: Option<&OriginalAmount>,
+) -> Result<(i64, f64)> {
+    insert_spending(conn, card_id, amount, category, date, merchant, None, foreign, original, STATUS_POSTED)
+}
+
+/// Records planned future spending. It is left out of balances, cycle totals
+/// and reconciliation until posted with `post_scheduled_spending`, but counts
+/// towards projections.
+#[allow(clippy::too_many_arguments)]
+pub fn schedule_spending(
+    conn: &Connection,
+    card_id: i64,
+    amount: f64,
+    category: &str,
+    date: &str,
+    merchant: Option<&str>,
+    foreign: bool,
+    original: Option<&OriginalAmount>,
+) -> Result<(i64, f64)> {
+    insert_spending(conn, card_id, amount, category, date, merchant, None, foreign, original, STATUS_SCHEDULED)
+}
+
+/// Marks a scheduled transaction as posted. Returns false if no scheduled
+/// transaction has that ID.
+pub fn post_scheduled_spending(conn: &Connection, id: i64) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE spending SET status = ?1, recorded_on = ?2 WHERE id = ?3 AND status = ?4",
+        params![STATUS_POSTED, today(), id, STATUS_SCHEDULED],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Works out what importing `rows` would do; see `import::plan_import`.
+fn plan_import(
+    conn: &Connection,
+    card_id: i64,
+    rows: &[ImportedRow],
+    profile: &ImportProfile,
+) -> Result<Vec<Option<CategoryDecision>>> {
+    let existing = list_spending(conn, Some(card_id), None)?;
+    let queued = list_review_queue(conn, Some(card_id))?;
+    let history = merchant_history(conn)?;
+    Ok(import::plan_import(rows, existing, queued, &history, profile))
+}
+
+/// Summarizes what `import_spending` would do without writing anything.
+pub fn preview_import(
+    conn: &Connection,
+    card_id: i64,
+    rows: &[ImportedRow],
+    profile: &ImportProfile,
+) -> Result<ImportPreview> {
+    let plan = plan_import(conn, card_id, rows, profile)?;
+    Ok(import::preview_plan(card_id, rows, plan))
+}
+
+/// Records statement rows against a card in a single transaction, so a
+/// failure part-way leaves nothing imported.
+///
+/// Duplicates of spending already on the card are skipped, and rows whose
+/// category cannot be decided confidently are held in the review queue
+/// instead of being recorded (see `plan_import`).
+pub fn import_spending(
+    conn: &Connection,
+    card_id: i64,
+    rows: &[ImportedRow],
+    profile: &ImportProfile,
+) -> Result<ImportSummary> {
+    let plan = plan_import(conn, card_id, rows, profile)?;
+    let mut summary = ImportSummary::default();
+
+    let tx = conn.unchecked_transaction()?;
+    for (row, decision) in rows.iter().zip(plan) {
+        match decision {
+            None => summary.duplicates += 1,
+            Some(CategoryDecision::Assign(category)) => {
+                insert_spending(
+                    &tx,
+                    card_id,
+                    row.amount,
+                    &category,
+                    &row.date,
+                    row.description.as_deref(),
+                    row.payment_category.as_deref(),
+                    false,
+                    None,
+                    STATUS_POSTED,
+                )?;
+                summary.imported += 1;
+            }
+            Some(CategoryDecision::Review(guess)) => {
+                let (suggested, confidence) = guess.unzip();
+                tx.execute(
+                    "INSERT INTO import_review (card_id, amount, date, description, suggested_category, confidence)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![card_id, row.amount, row.date, row.description, suggested, confidence],
+                )?;
+                summary.queued_for_review += 1;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(summary)
+}
+
+/// Counts how each merchant's past spending was categorized, for learning
+/// categories of newly imported rows.
+fn merchant_history(conn: &Connection) -> Result<MerchantHistory> {
+    let mut stmt = conn.prepare("SELECT description, category FROM spending WHERE description IS NOT NULL")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+    Ok(import::merchant_history(rows.collect::<Result<Vec<_>>>()?))
+}
+
+pub fn list_review_queue(conn: &Connection, card_id: Option<i64>) -> Result<Vec<ReviewItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, amount, date, description, suggested_category, confidence
+         FROM import_review
+         WHERE (?1 IS NULL OR card_id = ?1)
+         ORDER BY date, id",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(ReviewItem {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            amount: row.get(2)?,
+            date: row.get(3)?,
+            description: row.get(4)?,
+            suggested_category: row.get(5)?,
+            confidence: row.get(6)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Queues a statement row for review as it was exported.
+pub fn restore_review_item(conn: &Connection, item: &ReviewItem) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO import_review (card_id, amount, date, description, suggested_category, confidence)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![item.card_id, item.amount, item.date, item.description, item.suggested_category, item.confidence],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Records a queued row as spending with the chosen category (the suggestion
+/// if none is given) and removes it from the queue. Returns the new spending
+/// ID and miles, or None if the queue has no such row or nothing to go on.
+pub fn resolve_review(conn: &Connection, id: i64, category: Option<&str>) -> Result<Option<(i64, f64)>> {
+    let item = list_review_queue(conn, None)?.into_iter().find(|item| item.id == id);
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    let Some(category) = category.map(|c| c.to_string()).or(item.suggested_category) else {
+        return Ok(None);
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    let recorded = insert_spending(
+        &tx,
+        item.card_id,
+        item.amount,
+        &category,
+        &item.date,
+        item.description.as_deref(),
+        None,
+        false,
+        None,
+        STATUS_POSTED,
+    )?;
+    tx.execute("DELETE FROM import_review WHERE id = ?1", params![id])?;
+    tx.commit()?;
+    Ok(Some(recorded))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn insert_spending(
+    conn: &Connection,
+    card_id: i64,
+    amount: f64,
+    category: &str,
+    date: &str,
+    description: Option<&str>,
+    payment_category: Option<&str>,
+    foreign: bool,
+    original: Option<&OriginalAmount>,
+    status: &str,
+) -> Result<(i64, f64)> {
+    let foreign = foreign || original.is_some();
+    let (miles_earned, cashback) =
+        spending_rewards(conn, card_id, amount, category, date, description, foreign, original.map(|o| o.amount), None)?;
+
+    conn.execute(
+        "INSERT INTO spending (card_id, amount, category, date, miles_earned, recorded_on, status, description,
+                               payment_category, is_foreign, original_amount, original_currency, cashback)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            card_id,
+            amount,
+            category,
+            date,
+            miles_earned,
+            today(),
+            status,
+            description,
+            payment_category,
+            foreign,
+            original.map(|o| o.amount),
+            original.map(|o| &o.currency),
+            cashback
+        ],
+    )?;
+
+    let id = conn.last_insert_rowid();
+    settle_cycles(conn)?;
+    Ok((id, miles_earned))
+}
+
+/// The miles and, on a card with a cashback percent, the cash back a
+/// transaction earns on a card. Miles are at the card's current rates (its
+/// foreign rate for a foreign transaction, if it has one), or the rate of a
+/// promotion running for its category on its date, with the card's
+/// partnership bonus at the merchant (the description), if any. A card with
+/// foreign blocks counts them in `original_amount`, the amount in the
+/// purchase's own currency, when there is one. A category
+/// the card excludes earns nothing, and a cap in miles pays out only what
+/// the transaction's cycle has left of it, counting every transaction in
+/// the cycle but `except` (the one being recalculated).
+#[allow(clippy::too_many_arguments)]
+fn spending_rewards(
+    conn: &Connection,
+    card_id: i64,
+    amount: f64,
+    category: &str,
+    date: &str,
+    description: Option<&str>,
+    foreign: bool,
+    original_amount: Option<f64>,
+    except: Option<i64>,
+) -> Result<(f64, Option<f64>)> {
+    let (mut miles_per_dollar, block_size, excluded, cap, cap_unit, renewal_day, overrides, foreign_blocks, percent): (
+        f64,
+        f64,
+        bool,
+        Option<f64>,
+        String,
+        i32,
+        String,
+        bool,
+        Option<f64>,
+    ) = conn.query_row(
+        "SELECT CASE WHEN ?2 THEN COALESCE(miles_per_dollar_foreign, miles_per_dollar) ELSE miles_per_dollar END,
+                block_size,
+                EXISTS (SELECT 1 FROM json_each(excluded_categories) j WHERE LOWER(j.value) = LOWER(?3)),
+                max_reward_limit, cap_unit, statement_renewal_date, cycle_overrides, foreign_blocks, cashback_percent
+         FROM cards WHERE id = ?1",
+        params![card_id, foreign, category],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+            ))
+        },
+    )?;
+    if excluded {
+        return Ok((0.0, percent.map(|_| 0.0)));
+    }
+    let cashback = percent.map(|percent| engine::cashback(amount, percent));
+    if let Some(promotion) = engine::promotion_for(&list_promotions(conn, Some(card_id))?, card_id, category, date) {
+        miles_per_dollar = promotion.miles_per_dollar;
+    }
+    if let Some(merchant) = description
+        && let Some(partnership) = partnership_at(conn, card_id, merchant)?
+    {
+        miles_per_dollar += partnership.bonus_miles_per_dollar;
+    }
+    let block_amount = original_amount.filter(|_| foreign_blocks).unwrap_or(amount);
+    let miles = calculate_miles(block_amount, block_size, miles_per_dollar);
+    let Some(limit) = cap.filter(|_| CapUnit::from_db(&cap_unit) == CapUnit::Miles) else {
+        return Ok((miles, cashback));
+    };
+    let overrides: BTreeMap<String, CycleOverride> = serde_json::from_str(&overrides).unwrap_or_default();
+    let (start, end) = cycle_bounds(renewal_day, &overrides, date);
+    let earned: f64 = conn.query_row(
+        "SELECT COALESCE(SUM(miles_earned), 0.0) FROM spending
+         WHERE card_id = ?1 AND date >= ?2 AND date <= ?3 AND id IS NOT ?4",
+        params![card_id, start, end, except],
+        |row| row.get(0),
+    )?;
+    Ok((MilesCap { limit }.clamp(miles, &CycleState { spent: 0.0, miles: earned, excluded: 0.0 }), cashback))
+}
+
+/// Moves transactions to another card, recalculating their miles at that
+/// card's rates, in a single transaction so a closed cycle on either card
+/// leaves nothing moved. Returns the ID and new miles of each transaction
+/// moved; IDs with no transaction are left out.
+pub fn move_spending(conn: &Connection, ids: &[i64], to_card: i64) -> Result<Vec<(i64, f64)>> {
+    let tx = conn.unchecked_transaction()?;
+    let mut moved = Vec::new();
+    for &id in ids {
+        let row = {
+            let mut stmt =
+                tx.prepare_cached("SELECT amount, category, date, description, is_foreign, original_amount FROM spending WHERE id = ?1")?;
+            let mut rows = stmt.query_map(params![id], |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, bool>(4)?,
+                    row.get::<_, Option<f64>>(5)?,
+                ))
+            })?;
+            rows.next().transpose()?
+        };
+        let Some((amount, category, date, description, foreign, original)) = row else {
+            continue;
+        };
+        let (miles, cashback) =
+            spending_rewards(&tx, to_card, amount, &category, &date, description.as_deref(), foreign, original, Some(id))?;
+        tx.execute(
+            "UPDATE spending SET card_id = ?1, miles_earned = ?2, cashback = ?3 WHERE id = ?4",
+            params![to_card, miles, cashback, id],
+        )?;
+        moved.push((id, miles));
+    }
+    settle_cycles(&tx)?;
+    tx.commit()?;
+    Ok(moved)
+}
+
+/// Corrects a transaction's card, amount, category or date and recalculates
+/// its miles at the (possibly new) card's current rates, so cap and
+/// minimum-spend checks see what it really earns. Returns the new miles, or
+/// None if no transaction has that ID.
+pub fn edit_spending(conn: &Connection, id: i64, edit: &SpendingEdit) -> Result<Option<f64>> {
+    let row = {
+        let mut stmt =
+            conn.prepare("SELECT card_id, amount, category, date, description, is_foreign, original_amount FROM spending WHERE id = ?1")?;
+        let mut rows = stmt.query_map(params![id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, f64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, bool>(5)?,
+                row.get::<_, Option<f64>>(6)?,
+            ))
+        })?;
+        rows.next().transpose()?
+    };
+    let Some((card_id, amount, category, date, description, foreign, original)) = row else {
+        return Ok(None);
+    };
+    let card_id = edit.card_id.unwrap_or(card_id);
+    let amount = edit.amount.unwrap_or(amount);
+    let category = edit.category.clone().unwrap_or(category);
+    let date = edit.date.clone().unwrap_or(date);
+    let foreign = edit.foreign.unwrap_or(foreign);
+    let original = original.filter(|_| edit.amount.is_none());
+    let (miles, cashback) =
+        spending_rewards(conn, card_id, amount, &category, &date, description.as_deref(), foreign, original, Some(id))?;
+    // A corrected amount no longer matches the original currency's
+    conn.execute(
+        "UPDATE spending SET card_id = ?1, amount = ?2, category = ?3, date = ?4, miles_earned = ?5, is_foreign = ?6,
+                original_amount = CASE WHEN ?7 THEN NULL ELSE original_amount END,
+                original_currency = CASE WHEN ?7 THEN NULL ELSE original_currency END,
+                cashback = ?8
+         WHERE id = ?9",
+        params![card_id, amount, category, date, miles, foreign, edit.amount.is_some(), cashback, id],
+    )?;
+    settle_cycles(conn)?;
+    Ok(Some(miles))
+}
+
+/// Inserts a transaction as it was recorded elsewhere (e.g. from an export),
+/// keeping its miles rather than recalculating them at the card's current
+/// rates. It counts as known from its own date.
+pub fn restore_spending(conn: &Connection, spending: &Spending) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO spending (card_id, amount, category, date, miles_earned, status, description,
+                               payment_category, tag, is_foreign, original_amount, original_currency, recorded_on,
+                               cashback)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            spending.card_id,
+            spending.amount,
+            spending.category,
+            spending.date,
+            spending.miles_earned,
+            spending.status,
+            spending.description,
+            spending.payment_category,
+            spending.tag,
+            spending.foreign,
+            spending.original_amount,
+            spending.original_currency,
+            spending.recorded_on,
+            spending.cashback
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    settle_cycles(conn)?;
+    Ok(id)
+}
+
+/// Inserts many transactions as `restore_spending` does, in a single
+/// transaction, which is much quicker than one at a time.
+pub fn restore_spending_batch(conn: &Connection, spending: &[Spending]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for s in spending {
+        restore_spending(&tx, s)?;
+    }
+    tx.commit()
+}
+
+/// Lists spending, optionally only the transactions dated and recorded on or
+/// before `as_of`.
+pub fn list_spending(
+    conn: &Connection,
+    card_id: Option<i64>,
+    as_of: Option<&str>,
+) -> Result<Vec<Spending>> {
+    let mut results = Vec::new();
+    for_each_spending::<rusqlite::Error>(conn, card_id, as_of, &mut |spending| {
+        results.push(spending);
+        Ok(())
+    })?;
+    Ok(results)
+}
+
+/// Calls `f` with each row `list_spending` would return, in the same order,
+/// as it is read, so a long listing is never held in memory. Stops at the
+/// first error `f` returns.
+pub fn for_each_spending<E: From<rusqlite::Error>>(
+    conn: &Connection,
+    card_id: Option<i64>,
+    as_of: Option<&str>,
+    f: &mut dyn FnMut(Spending) -> std::result::Result<(), E>,
+) -> std::result::Result<(), E> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, amount, category, date, miles_earned, status, description, payment_category, tag,
+                is_foreign, original_amount, original_currency, recorded_on, cashback
+         FROM spending
+         WHERE (?1 IS NULL OR card_id = ?1)
+           AND (?2 IS NULL OR (date <= ?2 AND COALESCE(recorded_on, date) <= ?2))
+         ORDER BY date DESC, id DESC",
+    )?;
+    let rows = stmt.query_map(params![card_id, as_of], spending_from_row)?;
+
+    for row in rows {
+        f(row?)?;
+    }
+    Ok(())
+}
+
+/// Returns up to `limit` rows of `list_spending`, starting after `after`
+/// (from the start when None). Rows are found by seeking the (date, id)
+/// index rather than skipping an offset, so late pages cost the same as the
+/// first.
+pub fn list_spending_page(
+    conn: &Connection,
+    card_id: Option<i64>,
+    as_of: Option<&str>,
+    after: Option<&SpendingCursor>,
+    limit: usize,
+) -> Result<SpendingPage> {
+    // Only the filters in use go into the query: an `?n IS NULL OR` guard
+    // would stop SQLite from seeking the index to the cursor
+    let mut conditions = Vec::new();
+    let mut values: Vec<&dyn rusqlite::ToSql> = Vec::new();
+    if let Some(card_id) = &card_id {
+        values.push(card_id);
+        conditions.push(format!("card_id = ?{}", values.len()));
+    }
+    if let Some(as_of) = &as_of {
+        values.push(as_of);
+        conditions.push(format!("date <= ?{0} AND COALESCE(recorded_on, date) <= ?{0}", values.len()));
+    }
+    if let Some(after) = after {
+        values.push(&after.date);
+        values.push(&after.id);
+        conditions.push(format!("(date, id) < (?{}, ?{})", values.len() - 1, values.len()));
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    // One row past the page tells whether another page follows
+    let fetch = limit as i64 + 1;
+    values.push(&fetch);
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, card_id, amount, category, date, miles_earned, status, description, payment_category, tag,
+                is_foreign, original_amount, original_currency, recorded_on, cashback
+         FROM spending
+         {}
+         ORDER BY date DESC, id DESC
+         LIMIT ?{}",
+        filter,
+        values.len()
+    ))?;
+    let items = stmt
+        .query_map(values.as_slice(), spending_from_row)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(spending_page(items, limit))
+}
+
+/// Trims a page fetched with one extra row, pointing `next` at its last row
+/// when the extra row shows more follow.
+pub fn spending_page(mut items: Vec<Spending>, limit: usize) -> SpendingPage {
+    let next = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(|last| SpendingCursor {
+            date: last.date.clone(),
+            id: last.id,
+        })
+    } else {
+        None
+    };
+    SpendingPage { items, next }
+}
+
+fn spending_from_row(row: &rusqlite::Row) -> Result<Spending> {
+    Ok(Spending {
+        id: row.get(0)?,
+        card_id: row.get(1)?,
+        amount: row.get(2)?,
+        category: row.get(3)?,
+        date: row.get(4)?,
+        miles_earned: row.get(5)?,
+        status: row.get(6)?,
+        description: row.get(7)?,
+        payment_category: row.get(8)?,
+        tag: row.get(9)?,
+        foreign: row.get(10)?,
+        original_amount: row.get(11)?,
+        original_currency: row.get(12)?,
+        recorded_on: row.get(13)?,
+        cashback: row.get(14)?,
+    })
+}
+
+// ── Payment operations ───────────────────────────────────────────
+
+pub fn add_payment(conn: &Connection, card_id: i64, amount: f64, date: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO payments (card_id, amount, date) VALUES (?1, ?2, ?3)",
+        params![card_id, amount, date],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn list_payments(conn: &Connection, card_id: Option<i64>) -> Result<Vec<Payment>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, amount, date FROM payments
+         WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY date DESC",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(Payment {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            amount: row.get(2)?,
+            date: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Returns posted spending, charges and repayments per calendar month,
+/// oldest first, optionally for a single card and only within `period`.
+pub fn cash_flow(conn: &Connection, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<CashFlowMonth>> {
+    let mut stmt = conn.prepare(
+        "SELECT month, SUM(spent), SUM(charged), SUM(paid) FROM (
+             SELECT substr(date, 1, 7) AS month, date, amount AS spent, 0.0 AS charged, 0.0 AS paid
+             FROM spending WHERE (?1 IS NULL OR card_id = ?1) AND status = 'posted'
+             UNION ALL
+             SELECT substr(date, 1, 7), date, 0.0, amount, 0.0
+             FROM charges WHERE ?1 IS NULL OR card_id = ?1
+             UNION ALL
+             SELECT substr(date, 1, 7), date, 0.0, 0.0, amount
+             FROM payments WHERE ?1 IS NULL OR card_id = ?1
+         )
+         WHERE (?2 IS NULL OR date >= ?2) AND (?3 IS NULL OR date <= ?3)
+         GROUP BY month ORDER BY month",
+    )?;
+    let (from, to) = (period.map(|p| &p.from), period.map(|p| &p.to));
+    let rows = stmt.query_map(params![card_id, from, to], |row| {
+        let spending: f64 = row.get(1)?;
+        let charges: f64 = row.get(2)?;
+        let payments: f64 = row.get(3)?;
+        Ok(CashFlowMonth {
+            month: row.get(0)?,
+            spending,
+            charges,
+            payments,
+            net: spending + charges - payments,
+        })
+    })?;
+    rows.collect()
+}
+
+// ── Miles ledger ─────────────────────────────────────────────────
+
+/// Records a change of `miles` (negative to take miles off) to a card's
+/// balance, or to a program's pooled balance when no card is given.
+pub fn add_miles_entry(
+    conn: &Connection,
+    card_id: Option<i64>,
+    program: Option<&str>,
+    kind: &str,
+    miles: f64,
+    date: &str,
+    description: Option<&str>,
+) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO miles_ledger (card_id, program, kind, miles, date, description) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![card_id, program, kind, miles, date, description],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Lists the ledger's entries oldest first.
+pub fn list_miles_entries(conn: &Connection) -> Result<Vec<MilesEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, program, kind, miles, date, description FROM miles_ledger ORDER BY date, id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MilesEntry {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            program: row.get(2)?,
+            kind: row.get(3)?,
+            miles: row.get(4)?,
+            date: row.get(5)?,
+            description: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Records miles redeemed from a card or a program, with the cents each
+/// mile was worth, and takes them off the miles ledger.
+pub fn add_redemption(
+    conn: &Connection,
+    card_id: Option<i64>,
+    program: Option<&str>,
+    miles: f64,
+    cash_value: f64,
+    date: &str,
+    description: Option<&str>,
+) -> Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+    let entry_id = add_miles_entry(&tx, card_id, program, "redemption", -miles, date, description)?;
+    tx.execute(
+        "INSERT INTO redemptions (card_id, program, miles, cash_value, cents_per_mile, date, description, miles_entry_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![card_id, program, miles, cash_value, engine::cents_per_mile(miles, cash_value), date, description, entry_id],
+    )?;
+    let id = tx.last_insert_rowid();
+    tx.commit()?;
+    Ok(id)
+}
+
+/// Lists redemptions oldest first.
+pub fn list_redemptions(conn: &Connection) -> Result<Vec<Redemption>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.id, r.card_id, r.program, COALESCE(c.name, r.program, ''), r.date, r.miles, r.cash_value, r.cents_per_mile,
+                r.description, r.miles_entry_id
+         FROM redemptions r LEFT JOIN cards c ON c.id = r.card_id
+         ORDER BY r.date, r.id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Redemption {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            program: row.get(2)?,
+            account: row.get(3)?,
+            date: row.get(4)?,
+            miles: row.get(5)?,
+            cash_value: row.get(6)?,
+            cents_per_mile: row.get(7)?,
+            description: row.get(8)?,
+            miles_entry_id: row.get(9)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Inserts a redemption as it was exported, linked to its miles ledger
+/// entry, which must already have been restored.
+pub fn restore_redemption(conn: &Connection, redemption: &Redemption) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO redemptions (card_id, program, miles, cash_value, cents_per_mile, date, description, miles_entry_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            redemption.card_id,
+            redemption.program,
+            redemption.miles,
+            redemption.cash_value,
+            redemption.cents_per_mile,
+            redemption.date,
+            redemption.description,
+            redemption.miles_entry_id
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// ── Charge operations ────────────────────────────────────────────
+
+/// Records interest or a fee against a card. Charges count towards what is
+/// owed but never towards miles, caps or minimum spend.
+pub fn add_charge(conn: &Connection, card_id: i64, kind: &str, amount: f64, date: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO charges (card_id, kind, amount, date) VALUES (?1, ?2, ?3, ?4)",
+        params![card_id, kind, amount, date],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn list_charges(conn: &Connection, card_id: Option<i64>) -> Result<Vec<Charge>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, kind, amount, date FROM charges
+         WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY date DESC",
+    )?;
+    let rows = stmt.query_map(params![card_id], |row| {
+        Ok(Charge {
+            id: row.get(0)?,
+            card_id: row.get(1)?,
+            kind: row.get(2)?,
+            amount: row.get(3)?,
+            date: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Sets the balance a card carried before tracking started and the amount
+/// above which its outstanding balance should raise a warning. Either one
+/// given as None keeps its current value.
+pub fn set_balance_settings(
+    conn: &Connection,
+    card_id: i64,
+    starting_balance: Option<f64>,
+    balance_alert: Option<f64>,
+) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE cards SET starting_balance = COALESCE(?1, starting_balance), balance_alert = COALESCE(?2, balance_alert)
+         WHERE id = ?3",
+        params![starting_balance, balance_alert, card_id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Marks a card closed from `closed_on`. Its history stays; it is no longer
+/// recommended for purchases on or after that date.
+pub fn close_card(conn: &Connection, card_id: i64, closed_on: &str) -> Result<bool> {
+    let changed = conn.execute("UPDATE cards SET closed_on = ?1 WHERE id = ?2", params![closed_on, card_id])?;
+    Ok(changed > 0)
+}
+
+/// Replaces a card's statement cycle overrides, keyed by month (YYYY-MM).
+/// The card's cycle totals are rebuilt for the new boundaries.
+pub fn set_cycle_overrides(conn: &Connection, card_id: i64, overrides: &BTreeMap<String, CycleOverride>) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE cards SET cycle_overrides = ?1 WHERE id = ?2",
+        params![serde_json::to_string(overrides).expect("cycle overrides serialize to JSON"), card_id],
+    )?;
+    settle_cycles(conn)?;
+    Ok(changed > 0)
+}
+
+/// Returns what is owed on each card as of a date: its starting balance plus
+/// posted spending and charges, minus repayments.
+pub fn outstanding_balances(conn: &Connection, as_of: &str) -> Result<Vec<OutstandingBalance>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.name, c.starting_balance, c.balance_alert,
+                (SELECT COALESCE(SUM(amount), 0.0) FROM spending
+                 WHERE card_id = c.id AND date <= ?1 AND status = 'posted'),
+                (SELECT COALESCE(SUM(amount), 0.0) FROM charges
+                 WHERE card_id = c.id AND date <= ?1),
+                (SELECT COALESCE(SUM(amount), 0.0) FROM payments
+                 WHERE card_id = c.id AND date <= ?1)
+         FROM cards c ORDER BY c.id",
+    )?;
+    let rows = stmt.query_map(params![as_of], |row| {
+        let starting_balance: f64 = row.get(2)?;
+        let balance_alert: Option<f64> = row.get(3)?;
+        let spending: f64 = row.get(4)?;
+        let charges: f64 = row.get(5)?;
+        let payments: f64 = row.get(6)?;
+        let outstanding = starting_balance + spending + charges - payments;
+        Ok(OutstandingBalance {
+            card_id: row.get(0)?,
+            card_name: row.get(1)?,
+            starting_balance,
+            spending,
+            charges,
+            payments,
+            outstanding,
+            balance_alert,
+            over_alert: balance_alert.is_some_and(|alert| outstanding > alert),
+        })
+    })?;
+    rows.collect()
+}
+
+// ── Balance operations ───────────────────────────────────────────
+
+/// Returns each card's miles as of a date, split into credited miles (their
+/// statement has posted, after the card's posting delay) and pending miles.
+/// Spending dated or recorded after `as_of` is ignored; scheduled spending is
+/// reported separately as a projection.
+/// The row name, grouping and order of `spending_stats` per `by`, shared
+/// with the Postgres store. Categories group whatever their case.
+pub fn stats_grouping(by: StatsBy) -> (&'static str, &'static str, &'static str) {
+    match by {
+        StatsBy::Card => ("MIN(c.name)", "s.card_id", "s.card_id"),
+        StatsBy::Category => ("MIN(s.category)", "LOWER(s.category)", "SUM(s.amount) DESC, 1"),
+        StatsBy::Month => ("substr(s.date, 1, 7)", "substr(s.date, 1, 7)", "1"),
+    }
+}
+
+/// Builds a `stats` row from its totals, rounding the dollar figures.
+pub fn spending_stats_row(name: String, transactions: i64, spent: f64, average_spent: f64, miles: f64, average_miles: f64) -> SpendingStats {
+    SpendingStats {
+        name,
+        transactions,
+        total_spent: engine::round_cents(spent),
+        average_spent: engine::round_cents(average_spent),
+        total_miles: miles,
+        average_miles: engine::round_cents(average_miles),
+    }
+}
+
+/// Posted spending totalled and averaged per card, category or month,
+/// optionally on one card and in a period. Aggregated in SQL, so it does
+/// not read every transaction however many there are.
+pub fn spending_stats(conn: &Connection, by: StatsBy, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<SpendingStats>> {
+    let (name, group, order) = stats_grouping(by);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, COUNT(*), SUM(s.amount), AVG(s.amount), SUM(s.miles_earned), AVG(s.miles_earned)
+         FROM spending s JOIN cards c ON c.id = s.card_id
+         WHERE s.status = 'posted' AND (?1 IS NULL OR s.card_id = ?1)
+           AND (?2 IS NULL OR s.date >= ?2) AND (?3 IS NULL OR s.date <= ?3)
+         GROUP BY {} ORDER BY {}",
+        name, group, order
+    ))?;
+    let (from, to) = (period.map(|p| &p.from), period.map(|p| &p.to));
+    let rows = stmt.query_map(params![card_id, from, to], |row| {
+        Ok(spending_stats_row(row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+    })?;
+    rows.collect()
+}
+
+pub fn card_balances(conn: &Connection, as_of: &str) -> Result<Vec<CardBalance>> {
+    let cards = list_cards(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT date, miles_earned FROM spending
+         WHERE card_id = ?1 AND date <= ?2 AND COALESCE(recorded_on, date) <= ?2
+           AND status = 'posted'",
+    )?;
+    let mut scheduled_stmt = conn.prepare(
+        "SELECT COALESCE(SUM(miles_earned), 0.0) FROM spending
+         WHERE card_id = ?1 AND status = 'scheduled'",
+    )?;
+
+    let mut balances = Vec::new();
+    for card in &cards {
+        let overrides = card.cycle_overrides();
+        let rows = stmt.query_map(params![card.id, as_of], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+        })?;
+
+        let mut credited_miles = 0.0;
+        let mut pending_miles = 0.0;
+        for row in rows {
+            let (date, miles) = row?;
+            let posts_on = miles_posting_date(card.statement_renewal_date, &overrides, card.posting_delay, &date);
+            if posts_on.as_str() <= as_of {
+                credited_miles += miles;
+            } else {
+                pending_miles += miles;
+            }
+        }
+
+        let scheduled_miles: f64 = scheduled_stmt.query_row(params![card.id], |row| row.get(0))?;
+
+        balances.push(CardBalance {
+            card_id: card.id,
+            card_name: card.name.clone(),
+            credited_miles,
+            pending_miles,
+            total_miles: credited_miles + pending_miles,
+            scheduled_miles,
+        });
+    }
+    Ok(balances)
+}
+
+// ── Reconciliation ───────────────────────────────────────────────
+
+/// Compares the miles the tracker recorded for the statement cycle containing
+/// `reference_date` against the miles the bank reported for it.
+///
+/// Transactions are ranked by how closely their own miles explain the whole
+/// difference, so a single missing or double-counted transaction surfaces
+/// first. Returns `None` if the card does not exist.
+pub fn reconcile_cycle(
+    conn: &Connection,
+    card_id: i64,
+    reference_date: &str,
+    bank_miles: f64,
+) -> Result<Option<Reconciliation>> {
+    let Some(card) = get_card(conn, card_id)? else {
+        return Ok(None);
+    };
+
+    let (cycle_start, cycle_end) = card_cycle(&card, reference_date);
+
+    let mut stmt = conn.prepare(
+        "SELECT id, date, amount, category, miles_earned FROM spending
+         WHERE card_id = ?1 AND date >= ?2 AND date <= ?3 AND status = 'posted'
+         ORDER BY date",
+    )?;
+    let rows = stmt.query_map(params![card_id, cycle_start, cycle_end], |row| {
+        Ok(ReconcileSuspect {
+            spending_id: row.get(0)?,
+            date: row.get(1)?,
+            amount: row.get(2)?,
+            category: row.get(3)?,
+            miles_earned: row.get(4)?,
+            gap: 0.0,
+        })
+    })?;
+    let transactions: Vec<ReconcileSuspect> = rows.collect::<Result<Vec<_>>>()?;
+
+    let tracked_miles: f64 = transactions.iter().map(|t| t.miles_earned).sum();
+    let total_spend: f64 = transactions.iter().filter(|t| !card.excludes(&t.category)).map(|t| t.amount).sum();
+    let difference = bank_miles - tracked_miles;
+
+    // Some banks apply the block rounding to the statement total instead of per transaction
+    let statement_level_miles = calculate_miles(total_spend, card.block_size, card.miles_per_dollar);
+
+    let suspects = reconcile_suspects(transactions, difference);
+
+    Ok(Some(Reconciliation {
+        card_id,
+        card_name: card.name,
+        cycle_start,
+        cycle_end,
+        tracked_miles,
+        bank_miles,
+        difference,
+        statement_level_miles,
+        suspects,
+    }))
+}
+
+// ── Statement closing ────────────────────────────────────────────
+
+fn closed_cycle_from_row(row: &rusqlite::Row) -> Result<ClosedCycle> {
+    Ok(ClosedCycle {
+        id: row.get(0)?,
+        card_id: row.get(1)?,
+        cycle_start: row.get(2)?,
+        cycle_end: row.get(3)?,
+        total_spend: row.get(4)?,
+        total_miles: row.get(5)?,
+        transaction_count: row.get(6)?,
+        closed_on: row.get(7)?,
+    })
+}
+
+/// Closes the statement cycle containing `through`, normally its last day:
+/// stores its final spend and the miles it earns after the reward cap and
+/// minimum spend, and freezes its transactions against inserts, edits and
+/// deletes until it is reopened. The whole cycle is closed even when
+/// `through` falls earlier in it. Returns `None` if the
+/// card does not exist.
+pub fn close_cycle(conn: &Connection, card_id: i64, through: &str) -> Result<Option<ClosedCycle>> {
+    let Some(card) = get_card(conn, card_id)? else {
+        return Ok(None);
+    };
+
+    let (cycle_start, cycle_end) = card_cycle(&card, through);
+    let mut spending = Vec::new();
+    for_each_spending::<rusqlite::Error>(conn, Some(card_id), None, &mut |s| {
+        if s.status == STATUS_POSTED && s.date >= cycle_start && s.date <= cycle_end {
+            spending.push(s);
+        }
+        Ok(())
+    })?;
+    // The miles the cycle earns after its cap and minimum spend, as its
+    // statement shows them
+    let statement = statement::from_spending(&card, &cycle_start, &cycle_end, spending);
+    let (total_spend, total_miles, transaction_count) = (statement.spent, statement.earned, statement.lines.len() as i64);
+
+    conn.execute(
+        "INSERT INTO closed_cycles (card_id, cycle_start, cycle_end, total_spend, total_miles, transaction_count, closed_on)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![card_id, cycle_start, cycle_end, total_spend, total_miles, transaction_count, today()],
+    )?;
+
+    Ok(Some(ClosedCycle {
+        id: conn.last_insert_rowid(),
+        card_id,
+        cycle_start,
+        cycle_end,
+        total_spend,
+        total_miles,
+        transaction_count,
+        closed_on: today(),
+    }))
+}
+
+/// Returns the closed cycle of a card that contains the given date, if any.
+pub fn closed_cycle_containing(conn: &Connection, card_id: i64, date: &str) -> Result<Option<ClosedCycle>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, cycle_start, cycle_end, total_spend, total_miles, transaction_count, closed_on
+         FROM closed_cycles WHERE card_id = ?1 AND ?2 BETWEEN cycle_start AND cycle_end",
+    )?;
+    let mut rows = stmt.query_map(params![card_id, date], closed_cycle_from_row)?;
+    rows.next().transpose()
+}
+
+/// Reopens the closed cycle containing `date`, unlocking its transactions.
+/// Returns false if no closed cycle covers that date.
+pub fn reopen_cycle(conn: &Connection, card_id: i64, date: &str) -> Result<bool> {
+    let changed = conn.execute(
+        "DELETE FROM closed_cycles WHERE card_id = ?1 AND ?2 BETWEEN cycle_start AND cycle_end",
+        params![card_id, date],
+    )?;
+    Ok(changed > 0)
+}
+
+pub fn list_closed_cycles(conn: &Connection, card_id: Option<i64>) -> Result<Vec<ClosedCycle>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, card_id, cycle_start, cycle_end, total_spend, total_miles, transaction_count, closed_on
+         FROM closed_cycles WHERE ?1 IS NULL OR card_id = ?1
+         ORDER BY card_id, cycle_start",
+    )?;
+    let rows = stmt.query_map(params![card_id], closed_cycle_from_row)?;
+    rows.collect()
+}
+
+/// Inserts a closed cycle's frozen summary as it was exported.
+pub fn restore_closed_cycle(conn: &Connection, cycle: &ClosedCycle) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO closed_cycles (card_id, cycle_start, cycle_end, total_spend, total_miles, transaction_count, closed_on)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            cycle.card_id,
+            cycle.cycle_start,
+            cycle.cycle_end,
+            cycle.total_spend,
+            cycle.total_miles,
+            cycle.transaction_count,
+            cycle.closed_on
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dates::parse_time;
     use crate::models::{DEFAULT_CATEGORIES, DEFAULT_PAYMENT_CATEGORIES};
 
-    fn test_db() -> Connection {
-        let conn = Connection::open_in_memory().unwrap();
-        init_tables(&conn).unwrap();
-        conn
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_tables(&conn).unwrap();
+        conn
+    }
+
+    fn all_categories() -> Vec<String> {
+        DEFAULT_CATEGORIES.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn all_payment_categories() -> Vec<String> {
+        DEFAULT_PAYMENT_CATEGORIES.iter().map(|s| s.to_string()).collect()
+    }
+
+    /// Shorthand for tests: add a card with default payment categories
+    #[allow(clippy::too_many_arguments)]
+    fn add_test_card(
+        conn: &Connection,
+        name: &str,
+        categories: &[String],
+        miles_per_dollar: f64,
+        block_size: f64,
+        renewal: i32,
+        max_limit: Option<f64>,
+        min_spend: Option<f64>,
+    ) -> i64 {
+        let card = NewCard {
+            name: name.to_string(),
+            categories: categories.to_vec(),
+            payment_categories: all_payment_categories(),
+            miles_per_dollar,
+            block_size,
+            statement_renewal_date: renewal,
+            max_reward_limit: max_limit,
+            min_spend,
+            ..Default::default()
+        };
+        add_card(conn, &card).unwrap()
+    }
+
+    #[test]
+    fn test_read_only_open_needs_the_file_and_never_writes() {
+        let dir = std::env::temp_dir().join(format!("cc-tracker-read-only-{}", std::process::id()));
+        let path = dir.join("tracker.db");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(init_db(&path, OpenMode::ReadOnly).is_err());
+        assert!(!dir.exists());
+
+        let conn = init_db(&path, OpenMode::ReadWrite).unwrap();
+        conn.execute_batch("ALTER TABLE cards DROP COLUMN payment_due_days").unwrap();
+        drop(conn);
+        let conn = init_db(&path, OpenMode::ReadOnly).unwrap();
+        // The missing column is not added back, and nothing can be written
+        assert!(!table_columns(&conn, "cards").unwrap().contains(&"payment_due_days".to_string()));
+        assert!(conn.execute("DELETE FROM cards", []).is_err());
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // ── Card tests ───────────────────────────────────────────────
+
+    #[test]
+    fn test_add_card() {
+        let conn = test_db();
+
+        let card = NewCard {
+            name: "DBS Altitude".to_string(),
+            categories: vec!["dining".to_string(), "travel".to_string()],
+            payment_categories: vec!["contactless".to_string(), "online".to_string()],
+            miles_per_dollar: 3.0,
+            miles_per_dollar_foreign: Some(2.0),
+            block_size: 1.0,
+            statement_renewal_date: 15,
+            max_reward_limit: Some(5000.0),
+            min_spend: Some(800.0),
+            posting_delay: 1,
+            bank_time_offset_minutes: -480,
+            cutoff_hour: 22,
+            annual_fee: Some(192.6),
+            fee_posting_month: Some(6),
+            starting_balance: 0.0,
+            balance_alert: None,
+            reward_type: RewardType::Points,
+            cashback_percent: Some(1.5),
+            foreign_blocks: true,
+            cap_unit: CapUnit::Miles,
+            min_spend_exclusions: vec!["bills".to_string()],
+            program: Some("KrisFlyer".to_string()),
+            miles_expiry_months: Some(36),
+            fx_fee_percent: Some(3.25),
+            excluded_categories: vec!["Insurance".to_string()],
+            payment_due_days: Some(25),
+        };
+        let id = add_card(&conn, &card).unwrap();
+        assert_eq!(id, 1);
+
+        let cards = list_cards(&conn).unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].name, "DBS Altitude");
+        assert_eq!(cards[0].categories, r#"["dining","travel"]"#);
+        assert_eq!(cards[0].payment_categories, r#"["contactless","online"]"#);
+        assert_eq!(cards[0].miles_per_dollar, 3.0);
+        assert_eq!(cards[0].block_size, 1.0);
+        assert_eq!(cards[0].statement_renewal_date, 15);
+        assert_eq!(cards[0].miles_per_dollar_foreign, Some(2.0));
+        assert_eq!(cards[0].max_reward_limit, Some(5000.0));
+        assert_eq!(cards[0].min_spend, Some(800.0));
+        assert_eq!(cards[0].posting_delay, 1);
+        assert_eq!(cards[0].bank_time_offset_minutes, -480);
+        assert_eq!(cards[0].cashback_percent, Some(1.5));
+        assert!(cards[0].foreign_blocks);
+        assert_eq!(cards[0].cap_unit, CapUnit::Miles);
+        assert_eq!(cards[0].program.as_deref(), Some("KrisFlyer"));
+        assert_eq!(cards[0].excluded_from_min_spend(), vec!["bills"]);
+        assert_eq!(cards[0].cutoff_hour, 22);
+        assert_eq!(cards[0].annual_fee, Some(192.6));
+        assert_eq!(cards[0].fee_posting_month, Some(6));
+        assert_eq!(cards[0].miles_expiry_months, Some(36));
+        assert_eq!(cards[0].fx_fee_percent, Some(3.25));
+        assert_eq!(cards[0].payment_due_days, Some(25));
+        assert!(cards[0].excludes("insurance"));
+        assert_eq!(cards[0].reward_type, RewardType::Points);
+    }
+
+    #[test]
+    fn test_add_card_default_categories() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Generic Card", &all_categories(), 1.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        let results = best_card_for_category(&conn, "entertainment", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_list_cards_empty() {
+        let conn = test_db();
+        let cards = list_cards(&conn).unwrap();
+        assert!(cards.is_empty());
+    }
+
+    #[test]
+    fn test_list_cards_multiple() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Card B", &["travel".into()], 2.0, 1.0, 15, Some(1000.0), Some(500.0));
+        add_test_card(&conn, "Card C", &["groceries".into()], 10.0, 5.0, 20, None, None);
+
+        let cards = list_cards(&conn).unwrap();
+        assert_eq!(cards.len(), 3);
+    }
+
+    #[test]
+    fn test_remove_card() {
+        let conn = test_db();
+
+        let id = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        assert!(remove_card(&conn, id).unwrap());
+
+        let cards = list_cards(&conn).unwrap();
+        assert!(cards.is_empty());
+    }
+
+    #[test]
+    fn test_remove_card_deletes_spending() {
+        let conn = test_db();
+
+        let id = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_spending(&conn, id, 50.0, "dining", "2026-02-19", None, false, None).unwrap();
+
+        remove_card(&conn, id).unwrap();
+
+        let spending = list_spending(&conn, None, None).unwrap();
+        assert!(spending.is_empty());
+    }
+
+    #[test]
+    fn test_remove_card_nonexistent() {
+        let conn = test_db();
+        assert!(!remove_card(&conn, 999).unwrap());
+    }
+
+    // ── Best card tests ──────────────────────────────────────────
+
+    #[test]
+    fn test_best_card_single_match() {
+        let conn = test_db();
+
+        add_test_card(&conn, "DBS Altitude", &["dining".into(), "travel".into()], 3.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].card_name, "DBS Altitude");
+        assert_eq!(results[0].effective_rate, 3.0);
+        assert!(results[0].eligible);
+    }
+
+    #[test]
+    fn test_best_card_ranked_by_effective_rate() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Card B", &["dining".into()], 10.0, 5.0, 1, None, None);
+        add_test_card(&conn, "Card C", &["dining".into()], 4.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].card_name, "Card C");
+        assert_eq!(results[1].card_name, "Card A");
+        assert_eq!(results[2].card_name, "Card B");
+    }
+
+    #[test]
+    fn test_closed_card_not_recommended_from_closing_date() {
+        let conn = test_db();
+
+        let closed = add_test_card(&conn, "Closed", &["dining".into()], 4.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Open", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_spending(&conn, closed, 50.0, "dining", "2026-02-10", None, false, None).unwrap();
+        assert!(close_card(&conn, closed, "2026-02-19").unwrap());
+        assert!(!close_card(&conn, 999, "2026-02-19").unwrap());
+
+        let best = |date: &str| {
+            best_card_for_category(&conn, "dining", 10.0, "contactless", date, &RecommendOptions::default())
+                .unwrap()
+                .into_iter()
+                .map(|r| r.card_name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(best("2026-02-18"), ["Closed", "Open"]);
+        assert_eq!(best("2026-02-19"), ["Open"]);
+        // Its history stays
+        assert_eq!(list_spending(&conn, Some(closed), None).unwrap().len(), 1);
+        assert_eq!(get_card(&conn, closed).unwrap().unwrap().closed_on.as_deref(), Some("2026-02-19"));
+    }
+
+    #[test]
+    fn test_best_card_case_insensitive() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Card A", &["Dining".into()], 3.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_best_card_no_match() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_best_card_no_match_payment_category() {
+        let conn = test_db();
+
+        // Card only supports "contactless", query with "online"
+        let card = NewCard {
+            name: "Card A".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: vec!["contactless".into()],
+            miles_per_dollar: 3.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            ..Default::default()
+        };
+        add_card(&conn, &card).unwrap();
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_best_card_multi_category_card() {
+        let conn = test_db();
+
+        add_test_card(&conn, "Multi Card", &["dining".into(), "travel".into()], 2.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Dining Card", &["dining".into()], 4.0, 1.0, 1, None, None);
+
+        let dining = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(dining.len(), 2);
+        assert_eq!(dining[0].card_name, "Dining Card");
+
+        let travel = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(travel.len(), 1);
+        assert_eq!(travel[0].card_name, "Multi Card");
+    }
+
+    #[test]
+    fn test_best_card_exceeds_reward_limit() {
+        let conn = test_db();
+
+        // Card with $100 reward limit, renewal day 1
+        let card_id = add_test_card(&conn, "Limited Card", &["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
+        // Spend $90 already in this cycle
+        add_spending(&conn, card_id, 90.0, "dining", "2026-02-05", None, false, None).unwrap();
+
+        // Try to spend $20 more — exceeds the $100 limit ($10 remaining)
+        let results = best_card_for_category(&conn, "dining", 20.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].eligible);
+        assert!(results[0].reason.contains("Exceeds reward limit"));
+    }
+
+    #[test]
+    fn test_best_card_within_reward_limit() {
+        let conn = test_db();
+
+        // Card with $100 reward limit, renewal day 1
+        let card_id = add_test_card(&conn, "Limited Card", &["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
+        // Spend $50 already in this cycle
+        add_spending(&conn, card_id, 50.0, "dining", "2026-02-05", None, false, None).unwrap();
+
+        // Try to spend $30 more — within limit ($50 remaining)
+        let results = best_card_for_category(&conn, "dining", 30.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].eligible);
+        assert_eq!(results[0].remaining_limit, Some(50.0));
+    }
+
+    #[test]
+    fn test_best_card_min_spend_not_met() {
+        let conn = test_db();
+
+        // Card with $500 min spend, renewal day 1
+        add_test_card(&conn, "Min Spend Card", &["dining".into()], 4.0, 1.0, 1, None, Some(500.0));
+
+        // No spending yet — min spend not met
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].eligible);
+        assert!(results[0].reason.contains("Min spend not met"));
+    }
+
+    #[test]
+    fn test_best_card_min_spend_met() {
+        let conn = test_db();
+
+        // Card with $500 min spend, renewal day 1
+        let card_id = add_test_card(&conn, "Min Spend Card", &["dining".into()], 4.0, 1.0, 1, None, Some(500.0));
+        // Already spent $600 this cycle
+        add_spending(&conn, card_id, 600.0, "dining", "2026-02-05", None, false, None).unwrap();
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].eligible);
+    }
+
+    #[test]
+    fn test_best_card_eligible_sorted_first() {
+        let conn = test_db();
+
+        // Card A: high rate but min spend not met
+        add_test_card(&conn, "Card A", &["dining".into()], 10.0, 1.0, 1, None, Some(500.0));
+        // Card B: lower rate but no restrictions
+        add_test_card(&conn, "Card B", &["dining".into()], 2.0, 1.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 2);
+        // Card B should come first because it's eligible
+        assert_eq!(results[0].card_name, "Card B");
+        assert!(results[0].eligible);
+        // Card A is ineligible, sorted after
+        assert_eq!(results[1].card_name, "Card A");
+        assert!(!results[1].eligible);
+    }
+
+    #[test]
+    fn test_best_card_miles_earned_calculated() {
+        let conn = test_db();
+
+        // 10 miles per $5 block
+        add_test_card(&conn, "Card A", &["dining".into()], 10.0, 5.0, 1, None, None);
+
+        let results = best_card_for_category(&conn, "dining", 42.50, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        // floor(42.50 / 5.0) * 10.0 = 8 * 10 = 80
+        assert_eq!(results[0].miles_earned, 80.0);
+    }
+
+    thread_local! {
+        static STATEMENTS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    fn count_statement(_sql: &str) {
+        STATEMENTS.with(|count| count.set(count.get() + 1));
+    }
+
+    /// Adds `cards` dining cards with `transactions` spread over them and
+    /// the year up to 2026-02-19.
+    fn add_busy_cards(conn: &mut Connection, cards: usize, transactions: usize) -> Vec<i64> {
+        let ids: Vec<i64> = (0..cards)
+            .map(|n| {
+                let miles_per_dollar = 1.0 + n as f64 / 10.0;
+                add_test_card(conn, &format!("Card {}", n), &["dining".into()], miles_per_dollar, 1.0, 1 + n as i32 % 28, None, None)
+            })
+            .collect();
+        let tx = conn.transaction().unwrap();
+        {
+            let mut stmt = tx
+                .prepare("INSERT INTO spending (card_id, amount, category, date, miles_earned) VALUES (?1, ?2, 'dining', ?3, 0)")
+                .unwrap();
+            for n in 0..transactions {
+                let (y, m, d) = days_to_ymd(engine::ymd_to_days(2026, 2, 19) - (n % 365) as i32);
+                stmt.execute(params![ids[n % cards], 5.0 + (n % 50) as f64, format_date(y, m, d)]).unwrap();
+            }
+        }
+        tx.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_best_card_statements_do_not_grow_with_cards() {
+        let mut conn = test_db();
+        add_busy_cards(&mut conn, 60, 600);
+        let options = RecommendOptions {
+            fee_adjusted: true,
+            merchant: Some("Cafe".to_string()),
+            ..Default::default()
+        };
+
+        conn.trace(Some(count_statement));
+        for known_by in [None, Some("2026-02-19".to_string())] {
+            STATEMENTS.with(|count| count.set(0));
+            let options = RecommendOptions { known_by, ..options.clone() };
+            let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &options).unwrap();
+            assert_eq!(results.len(), 60);
+            // Candidates, monthly spends, promotions and partnerships
+            assert_eq!(STATEMENTS.with(|count| count.get()), 4);
+        }
+    }
+
+    /// Times recommendations against 60 cards and 100k transactions. Run with
+    /// `cargo test --release -- --ignored bench_best_card --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_best_card_many_cards() {
+        let mut conn = test_db();
+        add_busy_cards(&mut conn, 60, 100_000);
+        let runs = 200;
+        for (label, known_by) in [("cached totals", None), ("replayed totals", Some("2026-02-19".to_string()))] {
+            let options = RecommendOptions { known_by, ..Default::default() };
+            let started = std::time::Instant::now();
+            for _ in 0..runs {
+                best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &options).unwrap();
+            }
+            println!("best-card, {}: {:?} per call", label, started.elapsed() / runs);
+        }
+    }
+
+    // ── Cycle date / weekend tests ─────────────────────────────
+
+    #[test]
+    fn test_reward_limit_respects_weekend_cycle() {
+        let conn = test_db();
+
+        // Card with renewal day 15, which in Feb 2026 is a Sunday → adjusted to Feb 13 (Fri)
+        // max_reward_limit = $200
+        let card_id = add_test_card(&conn, "Weekend Card", &["dining".into()], 4.0, 1.0, 15, Some(200.0), None);
+
+        // Spend $150 on Feb 14 (after the adjusted cycle start of Feb 13)
+        add_spending(&conn, card_id, 150.0, "dining", "2026-02-14", None, false, None).unwrap();
+
+        // Query on Feb 19 for $60 — should exceed limit ($50 remaining)
+        let results = best_card_for_category(&conn, "dining", 60.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].eligible);
+        assert_eq!(results[0].remaining_limit, Some(50.0));
+    }
+
+    #[test]
+    fn test_spending_before_adjusted_cycle_not_counted() {
+        let conn = test_db();
+
+        // Card with renewal day 15, Feb 2026 → adjusted to Feb 13 (Fri)
+        let card_id = add_test_card(&conn, "Weekend Card", &["dining".into()], 4.0, 1.0, 15, Some(200.0), None);
+
+        // Spend $180 on Feb 12 (BEFORE the adjusted cycle start of Feb 13)
+        add_spending(&conn, card_id, 180.0, "dining", "2026-02-12", None, false, None).unwrap();
+
+        // Query on Feb 19 for $50 — previous cycle spending shouldn't count
+        let results = best_card_for_category(&conn, "dining", 50.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].eligible);
+        assert_eq!(results[0].remaining_limit, Some(200.0));
+    }
+
+    // ── Spending tests ───────────────────────────────────────────
+
+    #[test]
+    fn test_add_spending_block_size_1() {
+        let conn = test_db();
+
+        // 3 miles per $1 block
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+
+        let (id, miles) = add_spending(&conn, card_id, 42.50, "dining", "2026-02-19", None, false, None).unwrap();
+        assert_eq!(id, 1);
+        // floor(42.50 / 1.0) * 3.0 = 42 * 3 = 126
+        assert_eq!(miles, 126.0);
+    }
+
+    #[test]
+    fn test_add_spending_block_size_5() {
+        let conn = test_db();
+
+        // 10 miles per $5 block
+        let card_id = add_test_card(&conn, "Card B", &["dining".into()], 10.0, 5.0, 1, None, None);
+
+        let (_, miles) = add_spending(&conn, card_id, 42.50, "dining", "2026-02-19", None, false, None).unwrap();
+        // floor(42.50 / 5.0) * 10.0 = 8 * 10 = 80
+        assert_eq!(miles, 80.0);
+    }
+
+    #[test]
+    fn test_add_spending_below_block_size() {
+        let conn = test_db();
+
+        // 10 miles per $5 block, spend only $3
+        let card_id = add_test_card(&conn, "Card B", &["dining".into()], 10.0, 5.0, 1, None, None);
+
+        let (_, miles) = add_spending(&conn, card_id, 3.0, "dining", "2026-02-19", None, false, None).unwrap();
+        // floor(3.0 / 5.0) * 10.0 = 0 * 10 = 0
+        assert_eq!(miles, 0.0);
+    }
+
+    #[test]
+    fn test_list_spending_all() {
+        let conn = test_db();
+
+        let card_a = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        let card_b = add_test_card(&conn, "Card B", &["travel".into()], 2.0, 1.0, 1, None, None);
+
+        add_spending(&conn, card_a, 50.0, "dining", "2026-02-18", None, false, None).unwrap();
+        add_spending(&conn, card_b, 100.0, "travel", "2026-02-19", None, false, None).unwrap();
+
+        let all = list_spending(&conn, None, None).unwrap();
+        assert_eq!(all.len(), 2);
+        // Ordered by date DESC
+        assert_eq!(all[0].date, "2026-02-19");
+        assert_eq!(all[1].date, "2026-02-18");
+    }
+
+    #[test]
+    fn test_list_spending_by_card() {
+        let conn = test_db();
+
+        let card_a = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        let card_b = add_test_card(&conn, "Card B", &["travel".into()], 2.0, 1.0, 1, None, None);
+
+        add_spending(&conn, card_a, 50.0, "dining", "2026-02-18", None, false, None).unwrap();
+        add_spending(&conn, card_b, 100.0, "travel", "2026-02-19", None, false, None).unwrap();
+
+        let card_a_spending = list_spending(&conn, Some(card_a), None).unwrap();
+        assert_eq!(card_a_spending.len(), 1);
+        assert_eq!(card_a_spending[0].amount, 50.0);
+    }
+
+    #[test]
+    fn test_views_summarize_spending() {
+        let conn = test_db();
+        let card = add_test_card(&conn, "Card A", &["dining".into(), "travel".into()], 3.0, 1.0, 1, Some(500.0), Some(100.0));
+        add_spending(&conn, card, 80.0, "dining", "2026-04-10", None, false, None).unwrap();
+        add_spending(&conn, card, 40.0, "travel", "2026-04-20", None, false, None).unwrap();
+        schedule_spending(&conn, card, 25.0, "dining", "2026-04-28", None, false, None).unwrap();
+        add_spending(&conn, card, 10.0, "dining", "2026-05-04", None, false, None).unwrap();
+
+        let cycles: Vec<(String, String, f64, f64, f64, f64, bool)> = conn
+            .prepare(
+                "SELECT card_name, cycle_start, posted_spend, scheduled_spend, miles_earned,
+                        reward_limit_remaining, min_spend_met
+                 FROM v_cycle_summary ORDER BY cycle_start",
+            )
+            .unwrap()
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?, r.get(6)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            cycles,
+            vec![
+                ("Card A".to_string(), "2026-04-01".to_string(), 120.0, 25.0, 360.0, 380.0, true),
+                ("Card A".to_string(), "2026-05-01".to_string(), 10.0, 0.0, 30.0, 490.0, false),
+            ]
+        );
+
+        let rules: i64 = conn
+            .query_row("SELECT COUNT(*) FROM v_card_rules WHERE card_id = ?1", params![card], |r| r.get(0))
+            .unwrap();
+        assert_eq!(rules, 2 * all_payment_categories().len() as i64);
+    }
+
+    /// A card's spending in the cycle starting `cycle_start`, as cached in
+    /// `cycle_totals` once any changed transactions are settled.
+    fn cached_cycle_total(conn: &Connection, card_id: i64, cycle_start: &str, include_scheduled: bool) -> Result<f64> {
+        settle_cycles(conn)?;
+        conn.query_row(
+            "SELECT ROUND(COALESCE(SUM(total), 0.0), 2) FROM cycle_totals
+             WHERE card_id = ?1 AND cycle_start = ?2 AND (status = 'posted' OR ?3)",
+            params![card_id, cycle_start, include_scheduled],
+            |row| row.get(0),
+        )
+    }
+
+    #[test]
+    fn test_cycle_totals_follow_spending_changes() {
+        let conn = test_db();
+        let card = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, Some(500.0), None);
+        let resummed = |conn: &Connection, start: &str, end: &str| -> f64 {
+            conn.query_row(
+                "SELECT ROUND(COALESCE(SUM(amount), 0.0), 2) FROM spending
+                 WHERE card_id = ?1 AND date BETWEEN ?2 AND ?3 AND status = 'posted'",
+                params![card, start, end],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        let check = |conn: &Connection| {
+            for (start, end) in [("2026-04-01", "2026-04-30"), ("2026-05-01", "2026-05-31")] {
+                assert_eq!(cached_cycle_total(conn, card, start, false).unwrap(), resummed(conn, start, end));
+            }
+        };
+
+        add_spending(&conn, card, 100.1, "dining", "2026-04-10", None, false, None).unwrap();
+        add_spending(&conn, card, 50.2, "dining", "2026-04-20", None, false, None).unwrap();
+        let (scheduled, _) = schedule_spending(&conn, card, 70.0, "dining", "2026-05-04", None, false, None).unwrap();
+        check(&conn);
+        assert_eq!(cached_cycle_total(&conn, card, "2026-05-01", true).unwrap(), 70.0);
+
+        post_scheduled_spending(&conn, scheduled).unwrap();
+        conn.execute("UPDATE spending SET amount = 60.3, date = '2026-05-02' WHERE amount = 50.2", []).unwrap();
+        conn.execute("DELETE FROM spending WHERE amount = 100.1", []).unwrap();
+        check(&conn);
+        assert_eq!(cached_cycle_total(&conn, card, "2026-05-01", false).unwrap(), 130.3);
+
+        // Moving the renewal day regroups the card's spending into new cycles
+        conn.execute("UPDATE cards SET statement_renewal_date = 10 WHERE id = ?1", params![card]).unwrap();
+        assert_eq!(cached_cycle_total(&conn, card, "2026-05-01", false).unwrap(), 0.0);
+        assert_eq!(cached_cycle_total(&conn, card, "2026-04-10", false).unwrap(), 130.3);
+
+        // So does a cycle the bank moved
+        let moved = BTreeMap::from([(
+            "2026-04".to_string(),
+            CycleOverride {
+                start: "2026-04-15".to_string(),
+                end: "2026-05-05".to_string(),
+            },
+        )]);
+        assert!(set_cycle_overrides(&conn, card, &moved).unwrap());
+        assert_eq!(cached_cycle_total(&conn, card, "2026-04-15", false).unwrap(), 130.3);
+        assert_eq!(cached_cycle_total(&conn, card, "2026-04-10", false).unwrap(), 0.0);
+        let results =
+            best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-05-01", &RecommendOptions::default()).unwrap();
+        assert_eq!(results[0].remaining_limit, Some(500.0 - 130.3));
+    }
+
+    #[test]
+    fn test_other_sqlite_clients_can_change_spending() {
+        let dir = std::env::temp_dir().join(format!("cc-tracker-other-client-{}", std::process::id()));
+        let path = dir.join("tracker.db");
+        let _ = std::fs::remove_dir_all(&dir);
+        let conn = init_db(&path, OpenMode::ReadWrite).unwrap();
+        let a = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        let b = add_test_card(&conn, "Card B", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_spending(&conn, a, 40.0, "dining", "2026-04-10", None, false, None).unwrap();
+        add_spending(&conn, a, 25.0, "dining", "2026-04-12", None, false, None).unwrap();
+        drop(conn);
+
+        // A client without the app's SQL functions writes all the same
+        let other = Connection::open(&path).unwrap();
+        other
+            .execute_batch(
+                "INSERT INTO spending (card_id, amount, category, date, miles_earned)
+                 VALUES (1, 10.0, 'dining', '2026-04-20', 30.0);
+                 UPDATE spending SET amount = 30.0 WHERE amount = 40.0;
+                 UPDATE spending SET card_id = 2 WHERE amount = 25.0;
+                 DELETE FROM spending WHERE amount = 10.0;",
+            )
+            .unwrap();
+        drop(other);
+
+        let conn = init_db(&path, OpenMode::ReadWrite).unwrap();
+        assert_eq!(cached_cycle_total(&conn, a, "2026-04-01", false).unwrap(), 30.0);
+        assert_eq!(cached_cycle_total(&conn, b, "2026-04-01", false).unwrap(), 25.0);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_spending_pages_through_equal_dates() {
+        let conn = test_db();
+        let card = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        for date in ["2026-02-10", "2026-02-11", "2026-02-11", "2026-02-11", "2026-02-12"] {
+            add_spending(&conn, card, 10.0, "dining", date, None, false, None).unwrap();
+        }
+
+        let mut ids = Vec::new();
+        let mut after = None;
+        loop {
+            let page = list_spending_page(&conn, Some(card), None, after.as_ref(), 2).unwrap();
+            assert!(page.items.len() <= 2);
+            ids.extend(page.items.iter().map(|s| s.id));
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+
+        let all: Vec<i64> = list_spending(&conn, Some(card), None).unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, all);
+        assert_eq!(ids, vec![5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_for_each_spending_stops_at_callback_error() {
+        let conn = test_db();
+        let card = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        for day in 10..15 {
+            add_spending(&conn, card, 10.0, "dining", &format!("2026-02-{}", day), None, false, None).unwrap();
+        }
+
+        let mut dates = Vec::new();
+        let result = for_each_spending(&conn, Some(card), None, &mut |spending| {
+            dates.push(spending.date);
+            if dates.len() == 2 {
+                return Err(rusqlite::Error::QueryReturnedNoRows);
+            }
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(dates, vec!["2026-02-14", "2026-02-13"]);
     }
 
-    fn all_categories() -> Vec<String> {
-        DEFAULT_CATEGORIES.iter().map(|s| s.to_string()).collect()
+    #[test]
+    fn test_spending_miles_stored_correctly() {
+        let conn = test_db();
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 3.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 100.0, "dining", "2026-02-19", None, false, None).unwrap();
+
+        let spending = list_spending(&conn, Some(card_id), None).unwrap();
+        assert_eq!(spending[0].miles_earned, 300.0);
+    }
+
+    #[test]
+    fn test_partnership_bonus_applies_at_matching_merchants() {
+        let conn = test_db();
+        let partner = add_test_card(&conn, "Partner", &["groceries".into()], 1.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Other", &["groceries".into()], 2.0, 1.0, 1, None, None);
+        add_partnership(&conn, partner, "fair ?price", 3.0).unwrap();
+
+        let (_, miles) = add_spending(&conn, partner, 10.0, "groceries", "2026-02-05", Some("FAIRPRICE XTRA"), false, None).unwrap();
+        assert_eq!(miles, 40.0);
+        let (_, miles) = add_spending(&conn, partner, 10.0, "groceries", "2026-02-05", Some("COLD STORAGE"), false, None).unwrap();
+        assert_eq!(miles, 10.0);
+
+        let options = RecommendOptions {
+            merchant: Some("FairPrice Finest".to_string()),
+            ..Default::default()
+        };
+        let results = best_card_for_category(&conn, "groceries", 10.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!(results[0].card_name, "Partner");
+        assert_eq!(results[0].miles_earned, 40.0);
+        let results = best_card_for_category(&conn, "groceries", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results[0].card_name, "Other");
+
+        assert!(remove_card(&conn, partner).unwrap());
+        assert!(list_partnerships(&conn, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_foreign_spending_earns_the_foreign_rate() {
+        let conn = test_db();
+        let travel = NewCard {
+            name: "Travel".to_string(),
+            categories: vec!["shopping".to_string()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 1.0,
+            miles_per_dollar_foreign: Some(3.0),
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            ..Default::default()
+        };
+        let travel = add_card(&conn, &travel).unwrap();
+        let local = add_test_card(&conn, "Local", &["shopping".into()], 2.0, 1.0, 1, None, None);
+
+        let (id, miles) = add_spending(&conn, travel, 10.0, "shopping", "2026-02-05", None, true, None).unwrap();
+        assert_eq!(miles, 30.0);
+        let (_, miles) = add_spending(&conn, local, 10.0, "shopping", "2026-02-05", None, true, None).unwrap();
+        assert_eq!(miles, 20.0, "cards without a foreign rate earn their usual one");
+        assert!(list_spending(&conn, Some(travel), None).unwrap()[0].foreign);
+
+        // Moving and editing keep the flag unless the edit changes it
+        assert_eq!(move_spending(&conn, &[id], local).unwrap(), vec![(id, 20.0)]);
+        assert_eq!(move_spending(&conn, &[id], travel).unwrap(), vec![(id, 30.0)]);
+        let edit = SpendingEdit { foreign: Some(false), ..Default::default() };
+        assert_eq!(edit_spending(&conn, id, &edit).unwrap(), Some(10.0));
+        assert!(!list_spending(&conn, Some(travel), None).unwrap()[0].foreign);
+
+        let results = best_card_for_category(&conn, "shopping", 10.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results[0].card_name, "Local");
+        let options = RecommendOptions { foreign: true, explain: true, ..Default::default() };
+        let results = best_card_for_category(&conn, "shopping", 10.0, "online", "2026-02-19", &options).unwrap();
+        assert_eq!((results[0].card_name.as_str(), results[0].miles_earned), ("Travel", 30.0));
+        assert!(results[0].explanation.iter().any(|step| step.detail.contains("foreign rate")));
+    }
+
+    #[test]
+    fn test_fx_fee_ranks_foreign_purchases_by_net_value() {
+        let conn = test_db();
+        let travel = NewCard {
+            name: "Travel".to_string(),
+            categories: vec!["shopping".to_string()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 3.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            fx_fee_percent: Some(3.25),
+            ..Default::default()
+        };
+        add_card(&conn, &travel).unwrap();
+        let no_fee = NewCard { name: "No Fee".to_string(), miles_per_dollar: 1.2, fx_fee_percent: Some(0.0), ..travel };
+        add_card(&conn, &no_fee).unwrap();
+
+        // 300 mi x $0.015 - $3.25 fee = $1.25 against 120 mi x $0.015 = $1.80
+        let options = RecommendOptions { foreign: true, mile_value: Some(0.015), explain: true, ..Default::default() };
+        let results = best_card_for_category(&conn, "shopping", 100.0, "online", "2026-02-19", &options).unwrap();
+        assert_eq!((results[0].card_name.as_str(), results[0].value), ("No Fee", Some(1.8)));
+        assert_eq!((results[1].fx_fee, results[1].value), (Some(3.25), Some(1.25)));
+        assert!(results[1].explanation.iter().any(|step| step.detail.contains("- $3.25 FX fee")));
+
+        // Home-currency purchases carry no fee
+        let options = RecommendOptions { mile_value: Some(0.015), ..Default::default() };
+        let results = best_card_for_category(&conn, "shopping", 100.0, "online", "2026-02-19", &options).unwrap();
+        assert_eq!((results[0].card_name.as_str(), results[0].fx_fee), ("Travel", None));
+    }
+
+    #[test]
+    fn test_promotion_rate_applies_while_it_runs() {
+        let conn = test_db();
+        let promo = add_test_card(&conn, "Promo", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_test_card(&conn, "Other", &["groceries".into()], 2.0, 1.0, 1, None, None);
+        let id = add_promotion(&conn, promo, "Groceries", 5.0, "2026-04-01", "2026-06-30").unwrap();
+
+        let (_, miles) = add_spending(&conn, promo, 10.0, "groceries", "2026-06-30", None, false, None).unwrap();
+        assert_eq!(miles, 50.0);
+        let (_, miles) = add_spending(&conn, promo, 10.0, "groceries", "2026-07-01", None, false, None).unwrap();
+        assert_eq!(miles, 10.0);
+        let (late, miles) = add_spending(&conn, promo, 10.0, "dining", "2026-05-01", None, false, None).unwrap();
+        assert_eq!(miles, 10.0);
+        let edit = SpendingEdit { category: Some("groceries".to_string()), ..Default::default() };
+        assert_eq!(edit_spending(&conn, late, &edit).unwrap(), Some(50.0));
+
+        // The promotion makes the card a candidate for a category it does not list
+        let results = best_card_for_category(&conn, "groceries", 10.0, "contactless", "2026-05-15", &RecommendOptions::default()).unwrap();
+        assert_eq!((results[0].card_name.as_str(), results[0].miles_earned), ("Promo", 50.0));
+        assert_eq!(results[0].reason, format!("Eligible; promotion {} until 2026-06-30", id));
+        let results = best_card_for_category(&conn, "groceries", 10.0, "contactless", "2026-07-01", &RecommendOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].card_name, "Other");
+
+        assert!(remove_card(&conn, promo).unwrap());
+        assert!(list_promotions(&conn, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_apply_recurring_records_each_due_charge_once() {
+        let conn = test_db();
+        let card = add_test_card(&conn, "Subs", &["entertainment".into()], 2.0, 1.0, 1, None, None);
+        let id = add_recurring(&conn, card, 15.0, "entertainment", 31, Some("STREAMCO"), "2026-01-20").unwrap();
+        add_promotion(&conn, card, "entertainment", 4.0, "2026-03-01", "2026-03-31").unwrap();
+        assert_eq!(list_recurring(&conn, None).unwrap()[0].next_date, "2026-01-31");
+
+        let applied = apply_recurring(&conn, "2026-03-31").unwrap();
+        let dates: Vec<&str> = applied.iter().map(|a| a.date.as_str()).collect();
+        assert_eq!(dates, ["2026-01-31", "2026-02-28", "2026-03-31"]);
+        // Miles as add-spending gives them, promotion included
+        let miles: Vec<f64> = applied.iter().map(|a| a.miles_earned).collect();
+        assert_eq!(miles, [30.0, 30.0, 60.0]);
+        let spending = list_spending(&conn, Some(card), None).unwrap();
+        assert_eq!(spending.len(), 3);
+        assert_eq!(spending[0].description.as_deref(), Some("STREAMCO"));
+
+        // Nothing more is due until the next charge
+        assert!(apply_recurring(&conn, "2026-04-29").unwrap().is_empty());
+        assert_eq!(list_recurring(&conn, Some(card)).unwrap()[0].next_date, "2026-04-30");
+
+        assert!(remove_recurring(&conn, id).unwrap());
+        assert!(apply_recurring(&conn, "2026-06-30").unwrap().is_empty());
+        assert_eq!(list_spending(&conn, Some(card), None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_move_spending_recalculates_miles() {
+        let conn = test_db();
+        let wrong = add_test_card(&conn, "Wrong", &["groceries".into()], 1.0, 1.0, 1, None, None);
+        let right = add_test_card(&conn, "Right", &["groceries".into()], 2.0, 5.0, 1, None, None);
+        add_partnership(&conn, right, "fairprice", 1.0).unwrap();
+        let (a, _) = add_spending(&conn, wrong, 12.0, "groceries", "2026-02-05", Some("FAIRPRICE"), false, None).unwrap();
+        let (b, _) = add_spending(&conn, wrong, 12.0, "groceries", "2026-02-06", None, false, None).unwrap();
+
+        assert_eq!(move_spending(&conn, &[a, b, 999], right).unwrap(), [(a, 6.0), (b, 4.0)]);
+        assert!(list_spending(&conn, Some(wrong), None).unwrap().is_empty());
+        let total: f64 = conn
+            .query_row("SELECT total FROM cycle_totals WHERE card_id = ?1", params![right], |row| row.get(0))
+            .unwrap();
+        assert_eq!(total, 24.0);
+
+        // A closed cycle on either card leaves everything where it was
+        let (c, _) = add_spending(&conn, wrong, 10.0, "groceries", "2026-03-05", None, false, None).unwrap();
+        assert!(close_cycle(&conn, right, "2026-02-26").unwrap().is_some());
+        assert!(move_spending(&conn, &[c, a], wrong).is_err());
+        assert_eq!(list_spending(&conn, Some(wrong), None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_edit_spending_recalculates_miles() {
+        let conn = test_db();
+        let first = add_test_card(&conn, "First", &["dining".into()], 2.0, 1.0, 1, None, None);
+        let second = add_test_card(&conn, "Second", &["dining".into()], 4.0, 5.0, 1, Some(100.0), None);
+        let (id, miles) = add_spending(&conn, first, 42.0, "dining", "2026-02-05", None, false, None).unwrap();
+        assert_eq!(miles, 84.0);
+
+        let amount = SpendingEdit { amount: Some(24.0), ..Default::default() };
+        assert_eq!(edit_spending(&conn, id, &amount).unwrap(), Some(48.0));
+        let moved = SpendingEdit {
+            card_id: Some(second),
+            category: Some("travel".to_string()),
+            date: Some("2026-02-07".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(edit_spending(&conn, id, &moved).unwrap(), Some(16.0));
+        let spending = &list_spending(&conn, Some(second), None).unwrap()[0];
+        assert_eq!((spending.amount, spending.category.as_str(), spending.date.as_str()), (24.0, "travel", "2026-02-07"));
+        assert_eq!(edit_spending(&conn, 999, &amount).unwrap(), None);
+
+        // A closed cycle keeps the transaction as it is
+        assert!(close_cycle(&conn, second, "2026-02-26").unwrap().is_some());
+        assert!(edit_spending(&conn, id, &amount).is_err());
+    }
+
+    // ── Balance tests ────────────────────────────────────────────
+
+    /// Shorthand for tests: add a card that posts miles `delay` statements late
+    fn add_delayed_card(conn: &Connection, renewal: i32, delay: i32) -> i64 {
+        let card = NewCard {
+            name: "Delayed Card".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 1.0,
+            block_size: 1.0,
+            statement_renewal_date: renewal,
+            posting_delay: delay,
+            ..Default::default()
+        };
+        add_card(conn, &card).unwrap()
+    }
+
+    #[test]
+    fn test_balances_split_credited_and_pending() {
+        let conn = test_db();
+
+        let card_id = add_delayed_card(&conn, 2, 1);
+        // Posts on Apr 2 (cycle closes Mar 2, one statement delay)
+        let (first, _) = add_spending(&conn, card_id, 100.0, "dining", "2026-02-05", None, false, None).unwrap();
+        // Posts on May 1 (May 2 is a Saturday → Fri May 1)
+        let (second, _) = add_spending(&conn, card_id, 50.0, "dining", "2026-03-10", None, false, None).unwrap();
+        set_recorded_on(&conn, first, "2026-02-05");
+        set_recorded_on(&conn, second, "2026-03-10");
+
+        let balances = card_balances(&conn, "2026-04-10").unwrap();
+        assert_eq!(balances.len(), 1);
+        assert_eq!(balances[0].credited_miles, 100.0);
+        assert_eq!(balances[0].pending_miles, 50.0);
+        assert_eq!(balances[0].total_miles, 150.0);
+    }
+
+    #[test]
+    fn test_balances_ignore_future_spending() {
+        let conn = test_db();
+
+        let card_id = add_delayed_card(&conn, 2, 0);
+        let (id, _) = add_spending(&conn, card_id, 100.0, "dining", "2026-02-05", None, false, None).unwrap();
+        set_recorded_on(&conn, id, "2026-02-05");
+        add_spending(&conn, card_id, 70.0, "dining", "2026-03-20", None, false, None).unwrap();
+
+        let balances = card_balances(&conn, "2026-03-05").unwrap();
+        assert_eq!(balances[0].credited_miles, 100.0);
+        assert_eq!(balances[0].pending_miles, 0.0);
+    }
+
+    // ── Reconciliation tests ─────────────────────────────────────
+
+    #[test]
+    fn test_reconcile_matches_bank() {
+        let conn = test_db();
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 30.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, card_id, 20.0, "dining", "2026-02-10", None, false, None).unwrap();
+
+        let rec = reconcile_cycle(&conn, card_id, "2026-02-19", 100.0).unwrap().unwrap();
+        assert_eq!(rec.cycle_start, "2026-02-02");
+        assert_eq!(rec.cycle_end, "2026-03-01");
+        assert_eq!(rec.tracked_miles, 100.0);
+        assert_eq!(rec.difference, 0.0);
+        assert!(rec.suspects.is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_ranks_likely_transaction_first() {
+        let conn = test_db();
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 30.0, "dining", "2026-02-05", None, false, None).unwrap();
+        let suspect_id = add_spending(&conn, card_id, 20.0, "dining", "2026-02-10", None, false, None).unwrap().0;
+        add_spending(&conn, card_id, 75.0, "dining", "2026-02-12", None, false, None).unwrap();
+        // Previous cycle, must be ignored
+        add_spending(&conn, card_id, 500.0, "dining", "2026-01-20", None, false, None).unwrap();
+
+        // Bank is missing the $20 transaction (40 miles)
+        let rec = reconcile_cycle(&conn, card_id, "2026-02-19", 210.0).unwrap().unwrap();
+        assert_eq!(rec.tracked_miles, 250.0);
+        assert_eq!(rec.difference, -40.0);
+        assert_eq!(rec.suspects.len(), 3);
+        assert_eq!(rec.suspects[0].spending_id, suspect_id);
+        assert_eq!(rec.suspects[0].gap, 0.0);
     }
 
-    fn all_payment_categories() -> Vec<String> {
-        DEFAULT_PAYMENT_CATEGORIES.iter().map(|s| s.to_string()).collect()
+    #[test]
+    fn test_reconcile_statement_level_rounding() {
+        let conn = test_db();
+
+        // 10 miles per $5 block: per-transaction rounding loses miles
+        let card_id = add_test_card(&conn, "Card B", &["dining".into()], 10.0, 5.0, 2, None, None);
+        add_spending(&conn, card_id, 7.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, card_id, 8.0, "dining", "2026-02-06", None, false, None).unwrap();
+
+        let rec = reconcile_cycle(&conn, card_id, "2026-02-19", 30.0).unwrap().unwrap();
+        assert_eq!(rec.tracked_miles, 20.0);
+        assert_eq!(rec.statement_level_miles, 30.0);
     }
 
-    /// Shorthand for tests: add a card with default payment categories
-    fn add_test_card(
-        conn: &Connection,
-        name: &str,
-        categories: &[String],
-        miles_per_dollar: f64,
-        block_size: f64,
-        renewal: i32,
-        max_limit: Option<f64>,
-        min_spend: Option<f64>,
-    ) -> i64 {
-        add_card(conn, name, categories, &all_payment_categories(), miles_per_dollar, None, block_size, renewal, max_limit, min_spend).unwrap()
+    #[test]
+    fn test_reconcile_unknown_card() {
+        let conn = test_db();
+        assert!(reconcile_cycle(&conn, 42, "2026-02-19", 0.0).unwrap().is_none());
     }
 
-    // ── Card tests ───────────────────────────────────────────────
+    // ── Statement closing tests ──────────────────────────────────
 
     #[test]
-    fn test_add_card() {
+    fn test_close_cycle_stores_summary() {
         let conn = test_db();
 
-        let cats = vec!["dining".to_string(), "travel".to_string()];
-        let pay_cats = vec!["contactless".to_string(), "online".to_string()];
-        let id = add_card(&conn, "DBS Altitude", &cats, &pay_cats, 3.0, Some(2.0), 1.0, 15, Some(5000.0), Some(800.0)).unwrap();
-        assert_eq!(id, 1);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 30.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, card_id, 20.0, "dining", "2026-02-10", None, false, None).unwrap();
+        add_spending(&conn, card_id, 99.0, "dining", "2026-01-20", None, false, None).unwrap();
 
-        let cards = list_cards(&conn).unwrap();
-        assert_eq!(cards.len(), 1);
-        assert_eq!(cards[0].name, "DBS Altitude");
-        assert_eq!(cards[0].categories, r#"["dining","travel"]"#);
-        assert_eq!(cards[0].payment_categories, r#"["contactless","online"]"#);
-        assert_eq!(cards[0].miles_per_dollar, 3.0);
-        assert_eq!(cards[0].block_size, 1.0);
-        assert_eq!(cards[0].statement_renewal_date, 15);
-        assert_eq!(cards[0].miles_per_dollar_foreign, Some(2.0));
-        assert_eq!(cards[0].max_reward_limit, Some(5000.0));
-        assert_eq!(cards[0].min_spend, Some(800.0));
+        let closed = close_cycle(&conn, card_id, "2026-03-01").unwrap().unwrap();
+        assert_eq!(closed.cycle_start, "2026-02-02");
+        assert_eq!(closed.cycle_end, "2026-03-01");
+        assert_eq!(closed.total_spend, 50.0);
+        assert_eq!(closed.total_miles, 100.0);
+        assert_eq!(closed.transaction_count, 2);
+
+        assert_eq!(list_closed_cycles(&conn, Some(card_id)).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_add_card_default_categories() {
+    fn test_close_cycle_stores_the_miles_its_statement_earns() {
         let conn = test_db();
 
-        let cats = all_categories();
-        add_card(&conn, "Generic Card", &cats, &all_payment_categories(), 1.0, None, 1.0, 1, None, None).unwrap();
-
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        let results = best_card_for_category(&conn, "entertainment", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, Some(100.0), None);
+        add_spending(&conn, card_id, 80.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, card_id, 50.0, "dining", "2026-02-10", None, false, None).unwrap();
+        let closed = close_cycle(&conn, card_id, "2026-03-01").unwrap().unwrap();
+        assert_eq!((closed.total_spend, closed.total_miles), (130.0, 200.0));
+
+        // A cycle short of its minimum spend earns nothing
+        let short = add_test_card(&conn, "Card B", &["dining".into()], 2.0, 1.0, 2, None, Some(500.0));
+        add_spending(&conn, short, 80.0, "dining", "2026-02-05", None, false, None).unwrap();
+        let closed = close_cycle(&conn, short, "2026-03-01").unwrap().unwrap();
+        assert_eq!((closed.total_spend, closed.total_miles, closed.transaction_count), (80.0, 0.0, 1));
     }
 
     #[test]
-    fn test_list_cards_empty() {
+    fn test_close_cycle_closes_the_whole_cycle_from_a_day_in_it() {
         let conn = test_db();
-        let cards = list_cards(&conn).unwrap();
-        assert!(cards.is_empty());
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 30.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, card_id, 20.0, "dining", "2026-02-20", None, false, None).unwrap();
+
+        let closed = close_cycle(&conn, card_id, "2026-02-10").unwrap().unwrap();
+        assert_eq!((closed.cycle_start.as_str(), closed.cycle_end.as_str()), ("2026-02-02", "2026-03-01"));
+        assert_eq!((closed.total_spend, closed.transaction_count), (50.0, 2));
     }
 
     #[test]
-    fn test_list_cards_multiple() {
+    fn test_closed_cycle_rejects_new_spending() {
         let conn = test_db();
 
-        add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        add_test_card(&conn, "Card B", &vec!["travel".into()], 2.0, 1.0, 15, Some(1000.0), Some(500.0));
-        add_test_card(&conn, "Card C", &vec!["groceries".into()], 10.0, 5.0, 20, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
 
-        let cards = list_cards(&conn).unwrap();
-        assert_eq!(cards.len(), 3);
+        let err = add_spending(&conn, card_id, 10.0, "dining", "2026-02-15", None, false, None).unwrap_err();
+        assert!(err.to_string().contains("statement cycle is closed"));
+
+        // Spending in the next cycle is unaffected
+        add_spending(&conn, card_id, 10.0, "dining", "2026-03-03", None, false, None).unwrap();
     }
 
     #[test]
-    fn test_remove_card() {
+    fn test_closed_cycle_rejects_edits_and_deletes() {
         let conn = test_db();
 
-        let id = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        assert!(remove_card(&conn, id).unwrap());
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        let (id, _) = add_spending(&conn, card_id, 10.0, "dining", "2026-02-15", None, false, None).unwrap();
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
 
-        let cards = list_cards(&conn).unwrap();
-        assert!(cards.is_empty());
+        assert!(conn.execute("UPDATE spending SET amount = 20.0 WHERE id = ?1", params![id]).is_err());
+        assert!(conn.execute("DELETE FROM spending WHERE id = ?1", params![id]).is_err());
     }
 
     #[test]
-    fn test_remove_card_deletes_spending() {
+    fn test_rename_category_leaves_closed_cycles_alone() {
         let conn = test_db();
 
-        let id = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        add_spending(&conn, id, 50.0, "dining", "2026-02-19").unwrap();
+        let card_id = add_test_card(&conn, "Card A", &["dining".into(), "Food".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 10.0, "dining", "2026-02-15", None, false, None).unwrap();
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
+        add_spending(&conn, card_id, 20.0, "dining", "2026-03-05", None, false, None).unwrap();
+
+        assert_eq!(rename_category(&conn, "Dining", "food").unwrap(), (1, 1, 1));
+        // Merging into a category the card has already keeps it once
+        assert_eq!(get_card(&conn, card_id).unwrap().unwrap().categories, r#"["food"]"#);
+        let categories: Vec<_> = list_spending(&conn, None, None).unwrap().into_iter().map(|s| s.category).collect();
+        assert_eq!(categories, ["food", "dining"]);
+        // The closed cycle's transaction still has its category to list under
+        assert!(list_categories(&conn).unwrap().iter().any(|c| c.name == "dining"));
+        assert_eq!(renamed_categories(r#"["travel"]"#, "dining", "food"), None);
+
+        reopen_cycle(&conn, card_id, "2026-02-15").unwrap();
+        assert_eq!(rename_category(&conn, "dining", "food").unwrap(), (0, 1, 0));
+        assert!(!list_categories(&conn).unwrap().iter().any(|c| c.name == "dining"));
+    }
 
-        remove_card(&conn, id).unwrap();
+    #[test]
+    fn test_reopen_cycle_unlocks_spending() {
+        let conn = test_db();
 
-        let spending = list_spending(&conn, None).unwrap();
-        assert!(spending.is_empty());
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
+        assert!(closed_cycle_containing(&conn, card_id, "2026-02-15").unwrap().is_some());
+
+        assert!(reopen_cycle(&conn, card_id, "2026-02-15").unwrap());
+        assert!(closed_cycle_containing(&conn, card_id, "2026-02-15").unwrap().is_none());
+        add_spending(&conn, card_id, 10.0, "dining", "2026-02-15", None, false, None).unwrap();
+
+        assert!(!reopen_cycle(&conn, card_id, "2026-02-15").unwrap());
     }
 
     #[test]
-    fn test_remove_card_nonexistent() {
+    fn test_remove_card_with_closed_cycle() {
         let conn = test_db();
-        assert!(!remove_card(&conn, 999).unwrap());
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 10.0, "dining", "2026-02-15", None, false, None).unwrap();
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
+
+        assert!(remove_card(&conn, card_id).unwrap());
+        assert!(list_spending(&conn, None, None).unwrap().is_empty());
+        assert!(list_closed_cycles(&conn, None).unwrap().is_empty());
     }
 
-    // ── Best card tests ──────────────────────────────────────────
+    // ── As-of tests ──────────────────────────────────────────────
+
+    fn set_recorded_on(conn: &Connection, spending_id: i64, recorded_on: &str) {
+        conn.execute(
+            "UPDATE spending SET recorded_on = ?1 WHERE id = ?2",
+            params![recorded_on, spending_id],
+        )
+        .unwrap();
+    }
 
     #[test]
-    fn test_best_card_single_match() {
+    fn test_list_spending_as_of_excludes_unknown_transactions() {
         let conn = test_db();
 
-        add_test_card(&conn, "DBS Altitude", &vec!["dining".into(), "travel".into()], 3.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        let (early, _) = add_spending(&conn, card_id, 10.0, "dining", "2026-01-10", None, false, None).unwrap();
+        // Dated before Jan 20 but only entered on Jan 25
+        let (late_entry, _) = add_spending(&conn, card_id, 20.0, "dining", "2026-01-15", None, false, None).unwrap();
+        let (after, _) = add_spending(&conn, card_id, 30.0, "dining", "2026-01-22", None, false, None).unwrap();
+        set_recorded_on(&conn, early, "2026-01-10");
+        set_recorded_on(&conn, late_entry, "2026-01-25");
+        set_recorded_on(&conn, after, "2026-01-22");
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].card_name, "DBS Altitude");
-        assert_eq!(results[0].effective_rate, 3.0);
-        assert!(results[0].eligible);
+        let as_of = list_spending(&conn, None, Some("2026-01-20")).unwrap();
+        assert_eq!(as_of.len(), 1);
+        assert_eq!(as_of[0].id, early);
+
+        assert_eq!(list_spending(&conn, None, None).unwrap().len(), 3);
     }
 
     #[test]
-    fn test_best_card_ranked_by_effective_rate() {
+    fn test_best_card_as_of_cap_headroom() {
         let conn = test_db();
 
-        add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        add_test_card(&conn, "Card B", &vec!["dining".into()], 10.0, 5.0, 1, None, None);
-        add_test_card(&conn, "Card C", &vec!["dining".into()], 4.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Limited Card", &["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
+        let (known, _) = add_spending(&conn, card_id, 30.0, "dining", "2026-01-12", None, false, None).unwrap();
+        let (unknown, _) = add_spending(&conn, card_id, 50.0, "dining", "2026-01-14", None, false, None).unwrap();
+        set_recorded_on(&conn, known, "2026-01-12");
+        set_recorded_on(&conn, unknown, "2026-01-28");
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 3);
-        assert_eq!(results[0].card_name, "Card C");
-        assert_eq!(results[1].card_name, "Card A");
-        assert_eq!(results[2].card_name, "Card B");
+        let options = RecommendOptions {
+            known_by: Some("2026-01-20".to_string()),
+            ..Default::default()
+        };
+        let then = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-01-20", &options).unwrap();
+        assert_eq!(then[0].remaining_limit, Some(70.0));
+
+        let now = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-01-20", &RecommendOptions::default()).unwrap();
+        assert_eq!(now[0].remaining_limit, Some(20.0));
     }
 
     #[test]
-    fn test_best_card_case_insensitive() {
+    fn test_best_card_uses_rates_in_force_on_date() {
         let conn = test_db();
 
-        add_test_card(&conn, "Card A", &vec!["Dining".into()], 3.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
+        let new_rates = CardRates {
+            miles_per_dollar: 2.0,
+            block_size: 1.0,
+            max_reward_limit: None,
+            min_spend: None,
+        };
+        assert!(change_card_rates(&conn, card_id, "2026-03-01", &new_rates).unwrap());
+        assert_eq!(last_rate_change(&conn, card_id).unwrap().as_deref(), Some("2026-03-01"));
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
+        let before = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-20", &RecommendOptions::default()).unwrap();
+        assert_eq!(before[0].miles_per_dollar, 4.0);
+        assert_eq!(before[0].remaining_limit, Some(100.0));
+
+        let after = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-03-05", &RecommendOptions::default()).unwrap();
+        assert_eq!(after[0].miles_per_dollar, 2.0);
+        assert_eq!(after[0].remaining_limit, None);
     }
 
     #[test]
-    fn test_best_card_no_match() {
+    fn test_balances_as_of_exclude_later_recorded() {
         let conn = test_db();
 
-        add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
+        let card_id = add_delayed_card(&conn, 2, 0);
+        let (id, _) = add_spending(&conn, card_id, 100.0, "dining", "2026-02-05", None, false, None).unwrap();
+        set_recorded_on(&conn, id, "2026-03-10");
 
-        let results = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19").unwrap();
-        assert!(results.is_empty());
+        assert_eq!(card_balances(&conn, "2026-03-05").unwrap()[0].total_miles, 0.0);
+        assert_eq!(card_balances(&conn, "2026-03-10").unwrap()[0].credited_miles, 100.0);
+    }
+
+    // ── Bank date tests ──────────────────────────────────────────
+
+    fn card_with_clock(offset_minutes: i32, cutoff_hour: i32) -> Card {
+        Card {
+            id: 1,
+            name: "Clock Card".to_string(),
+            categories: "[]".to_string(),
+            payment_categories: "[]".to_string(),
+            miles_per_dollar: 1.0,
+            miles_per_dollar_foreign: None,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            max_reward_limit: None,
+            min_spend: None,
+            posting_delay: 0,
+            bank_time_offset_minutes: offset_minutes,
+            cutoff_hour,
+            annual_fee: None,
+            fee_posting_month: None,
+            starting_balance: 0.0,
+            balance_alert: None,
+            reward_type: RewardType::Miles,
+            cashback_percent: None,
+            foreign_blocks: false,
+            cap_unit: CapUnit::Dollars,
+            min_spend_exclusions: "[]".to_string(),
+            program: None,
+            miles_expiry_months: None,
+            fx_fee_percent: None,
+            excluded_categories: "[]".to_string(),
+            payment_due_days: None,
+            closed_on: None,
+            cycle_overrides: "{}".to_string(),
+        }
     }
 
     #[test]
-    fn test_best_card_no_match_payment_category() {
-        let conn = test_db();
+    fn test_bank_date_default_clock() {
+        let card = card_with_clock(0, 0);
+        assert_eq!(bank_transaction_date(&card, "2026-02-12", parse_time("23:59").unwrap()), "2026-02-12");
+    }
 
-        // Card only supports "contactless", query with "online"
-        let cats = vec!["dining".into()];
-        let pay_cats = vec!["contactless".into()];
-        add_card(&conn, "Card A", &cats, &pay_cats, 3.0, None, 1.0, 1, None, None).unwrap();
+    #[test]
+    fn test_bank_date_timezone_ahead() {
+        // Bank 8 hours ahead: 18:00 local is 02:00 next day for the bank
+        let card = card_with_clock(8 * 60, 0);
+        assert_eq!(bank_transaction_date(&card, "2026-02-28", parse_time("18:00").unwrap()), "2026-03-01");
+    }
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "online", "2026-02-19").unwrap();
-        assert!(results.is_empty());
+    #[test]
+    fn test_bank_date_timezone_behind() {
+        // Bank 13 hours behind: 09:00 local on Mar 1 is still Feb 28 for the bank
+        let card = card_with_clock(-13 * 60, 0);
+        assert_eq!(bank_transaction_date(&card, "2026-03-01", parse_time("09:00").unwrap()), "2026-02-28");
     }
 
     #[test]
-    fn test_best_card_multi_category_card() {
+    fn test_bank_date_cutoff_hour() {
+        // Bank day rolls over at 22:00
+        let card = card_with_clock(0, 22);
+        assert_eq!(bank_transaction_date(&card, "2026-02-12", parse_time("21:59").unwrap()), "2026-02-12");
+        assert_eq!(bank_transaction_date(&card, "2026-02-12", parse_time("22:00").unwrap()), "2026-02-13");
+    }
+
+    #[test]
+    fn test_bank_date_moves_cycle() {
+        // Renewal day 13 (Fri Feb 13): a late-night spend on the 12th lands in the new cycle
+        let card = card_with_clock(0, 22);
+        let date = bank_transaction_date(&card, "2026-02-12", parse_time("23:30").unwrap());
+        assert_eq!(cycle_start_date(13, &date), "2026-02-13");
+    }
+
+    // ── Scheduled spending tests ─────────────────────────────────
+
+    #[test]
+    fn test_scheduled_spending_excluded_from_cycle_total() {
         let conn = test_db();
 
-        add_test_card(&conn, "Multi Card", &vec!["dining".into(), "travel".into()], 2.0, 1.0, 1, None, None);
-        add_test_card(&conn, "Dining Card", &vec!["dining".into()], 4.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Limited Card", &["travel".into()], 4.0, 1.0, 1, Some(500.0), None);
+        add_spending(&conn, card_id, 100.0, "travel", "2026-02-05", None, false, None).unwrap();
+        schedule_spending(&conn, card_id, 300.0, "travel", "2026-02-25", None, false, None).unwrap();
 
-        let dining = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(dining.len(), 2);
-        assert_eq!(dining[0].card_name, "Dining Card");
+        let current = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(current[0].remaining_limit, Some(400.0));
 
-        let travel = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(travel.len(), 1);
-        assert_eq!(travel[0].card_name, "Multi Card");
+        let options = RecommendOptions {
+            include_scheduled: true,
+            ..Default::default()
+        };
+        let projected = best_card_for_category(&conn, "travel", 10.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!(projected[0].remaining_limit, Some(100.0));
     }
 
     #[test]
-    fn test_best_card_exceeds_reward_limit() {
+    fn test_scheduled_spending_in_balances() {
         let conn = test_db();
 
-        // Card with $100 reward limit, renewal day 1
-        let card_id = add_test_card(&conn, "Limited Card", &vec!["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
-        // Spend $90 already in this cycle
-        add_spending(&conn, card_id, 90.0, "dining", "2026-02-05").unwrap();
+        let card_id = add_delayed_card(&conn, 2, 0);
+        let (id, _) = add_spending(&conn, card_id, 100.0, "dining", "2026-02-05", None, false, None).unwrap();
+        set_recorded_on(&conn, id, "2026-02-05");
+        let (scheduled_id, _) = schedule_spending(&conn, card_id, 40.0, "dining", "2026-02-07", None, false, None).unwrap();
+        set_recorded_on(&conn, scheduled_id, "2026-02-01");
 
-        // Try to spend $20 more — exceeds the $100 limit ($10 remaining)
-        let results = best_card_for_category(&conn, "dining", 20.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(!results[0].eligible);
-        assert!(results[0].reason.contains("Exceeds reward limit"));
+        let balances = card_balances(&conn, "2026-03-05").unwrap();
+        assert_eq!(balances[0].total_miles, 100.0);
+        assert_eq!(balances[0].scheduled_miles, 40.0);
     }
 
     #[test]
-    fn test_best_card_within_reward_limit() {
+    fn test_post_scheduled_spending() {
         let conn = test_db();
 
-        // Card with $100 reward limit, renewal day 1
-        let card_id = add_test_card(&conn, "Limited Card", &vec!["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
-        // Spend $50 already in this cycle
-        add_spending(&conn, card_id, 50.0, "dining", "2026-02-05").unwrap();
+        let card_id = add_test_card(&conn, "Card A", &["travel".into()], 2.0, 1.0, 1, None, None);
+        let (id, miles) = schedule_spending(&conn, card_id, 50.0, "travel", "2026-02-25", None, false, None).unwrap();
+        assert_eq!(miles, 100.0);
 
-        // Try to spend $30 more — within limit ($50 remaining)
-        let results = best_card_for_category(&conn, "dining", 30.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(results[0].eligible);
-        assert_eq!(results[0].remaining_limit, Some(50.0));
+        let spending = list_spending(&conn, None, None).unwrap();
+        assert_eq!(spending[0].status, STATUS_SCHEDULED);
+
+        assert!(post_scheduled_spending(&conn, id).unwrap());
+        assert!(!post_scheduled_spending(&conn, id).unwrap());
+        let spending = list_spending(&conn, None, None).unwrap();
+        assert_eq!(spending[0].status, STATUS_POSTED);
     }
 
     #[test]
-    fn test_best_card_min_spend_not_met() {
+    fn test_scheduled_spending_excluded_from_reconcile() {
         let conn = test_db();
 
-        // Card with $500 min spend, renewal day 1
-        add_test_card(&conn, "Min Spend Card", &vec!["dining".into()], 4.0, 1.0, 1, None, Some(500.0));
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        add_spending(&conn, card_id, 30.0, "dining", "2026-02-05", None, false, None).unwrap();
+        schedule_spending(&conn, card_id, 20.0, "dining", "2026-02-20", None, false, None).unwrap();
 
-        // No spending yet — min spend not met
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(!results[0].eligible);
-        assert!(results[0].reason.contains("Min spend not met"));
+        let rec = reconcile_cycle(&conn, card_id, "2026-02-19", 60.0).unwrap().unwrap();
+        assert_eq!(rec.tracked_miles, 60.0);
     }
 
+    // ── Fee-adjusted rate tests ──────────────────────────────────
+
     #[test]
-    fn test_best_card_min_spend_met() {
+    fn test_average_monthly_spend_counts_active_months() {
         let conn = test_db();
 
-        // Card with $500 min spend, renewal day 1
-        let card_id = add_test_card(&conn, "Min Spend Card", &vec!["dining".into()], 4.0, 1.0, 1, None, Some(500.0));
-        // Already spent $600 this cycle
-        add_spending(&conn, card_id, 600.0, "dining", "2026-02-05").unwrap();
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 100.0, "dining", "2026-01-10", None, false, None).unwrap();
+        add_spending(&conn, card_id, 50.0, "dining", "2026-01-20", None, false, None).unwrap();
+        add_spending(&conn, card_id, 150.0, "dining", "2026-02-10", None, false, None).unwrap();
+        // Outside the trailing 12 months
+        add_spending(&conn, card_id, 999.0, "dining", "2025-02-10", None, false, None).unwrap();
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(results[0].eligible);
+        assert_eq!(average_monthly_spends(&conn, "2026-02-19").unwrap()[&card_id], 150.0);
     }
 
     #[test]
-    fn test_best_card_eligible_sorted_first() {
+    fn test_best_card_fee_adjusted_ranking() {
         let conn = test_db();
 
-        // Card A: high rate but min spend not met
-        add_test_card(&conn, "Card A", &vec!["dining".into()], 10.0, 1.0, 1, None, Some(500.0));
-        // Card B: lower rate but no restrictions
-        add_test_card(&conn, "Card B", &vec!["dining".into()], 2.0, 1.0, 1, None, None);
+        let premium = NewCard {
+            name: "Premium".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 4.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            annual_fee: Some(600.0),
+            ..Default::default()
+        };
+        add_card(&conn, &premium).unwrap();
+        add_test_card(&conn, "No Fee", &["dining".into()], 3.0, 1.0, 1, None, None);
 
-        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 2);
-        // Card B should come first because it's eligible
-        assert_eq!(results[0].card_name, "Card B");
-        assert!(results[0].eligible);
-        // Card A is ineligible, sorted after
-        assert_eq!(results[1].card_name, "Card A");
-        assert!(!results[1].eligible);
+        let headline = best_card_for_category(&conn, "dining", 100.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(headline[0].card_name, "Premium");
+        assert!(headline[0].net_rate.is_none());
+
+        // $50/month fee against a $100 purchase: 4.0 * 100 / 150 ≈ 2.67 < 3.0
+        let options = RecommendOptions {
+            fee_adjusted: true,
+            ..Default::default()
+        };
+        let net = best_card_for_category(&conn, "dining", 100.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!(net[0].card_name, "No Fee");
+        assert_eq!(net[0].net_rate, Some(3.0));
+        assert!(net[1].net_rate.unwrap() < 3.0);
     }
 
     #[test]
-    fn test_best_card_miles_earned_calculated() {
+    fn test_best_card_ranks_cashback_by_value() {
         let conn = test_db();
 
-        // 10 miles per $5 block
-        add_test_card(&conn, "Card A", &vec!["dining".into()], 10.0, 5.0, 1, None, None);
-
-        let results = best_card_for_category(&conn, "dining", 42.50, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        // floor(42.50 / 5.0) * 10.0 = 8 * 10 = 80
-        assert_eq!(results[0].miles_earned, 80.0);
+        let cashback = NewCard {
+            name: "Cashback".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 0.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            cashback_percent: Some(5.0),
+            ..Default::default()
+        };
+        add_card(&conn, &cashback).unwrap();
+        add_test_card(&conn, "Miles", &["dining".into()], 2.0, 1.0, 1, None, None);
+
+        let by_miles = best_card_for_category(&conn, "dining", 80.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(by_miles[0].card_name, "Miles");
+        assert_eq!(by_miles[1].cashback, Some(4.0));
+        assert!(by_miles[1].value.is_none());
+
+        // 160 miles at 2 cents are $3.20, against $4.00 back
+        let options = RecommendOptions {
+            mile_value: Some(0.02),
+            ..Default::default()
+        };
+        let by_value = best_card_for_category(&conn, "dining", 80.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!(by_value[0].card_name, "Cashback");
+        assert_eq!((by_value[0].value, by_value[1].value), (Some(4.0), Some(3.2)));
     }
 
-    // ── Cycle date / weekend tests ─────────────────────────────
-
     #[test]
-    fn test_cycle_start_date_weekday() {
-        // 2026-02-15 is a Sunday, renewal day 15 → adjusted to Friday 13th
-        // Reference date Feb 19 (Thu) >= 13, so cycle started Feb 13
-        let start = cycle_start_date(15, "2026-02-19");
-        assert_eq!(start, "2026-02-13");
+    fn test_points_cards_are_valued_per_point() {
+        let conn = test_db();
+
+        let points = NewCard {
+            name: "Points".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 5.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            reward_type: RewardType::Points,
+            ..Default::default()
+        };
+        add_card(&conn, &points).unwrap();
+        add_test_card(&conn, "Miles", &["dining".into()], 2.0, 1.0, 1, None, None);
+
+        // 400 points at half a cent are $2.00, against 160 miles at 2 cents
+        let options = RecommendOptions {
+            mile_value: Some(0.02),
+            point_value: Some(0.005),
+            ..Default::default()
+        };
+        let ranked = best_card_for_category(&conn, "dining", 80.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!(ranked[0].card_name, "Miles");
+        assert_eq!((ranked[0].value, ranked[1].value), (Some(3.2), Some(2.0)));
+
+        // Without a point value, points are worth as much as miles
+        let options = RecommendOptions { point_value: None, ..options };
+        let ranked = best_card_for_category(&conn, "dining", 80.0, "contactless", "2026-02-19", &options).unwrap();
+        assert_eq!((ranked[0].card_name.as_str(), ranked[0].value), ("Points", Some(8.0)));
     }
 
     #[test]
-    fn test_cycle_start_date_saturday_adjustment() {
-        // 2026-02-14 is a Saturday, renewal day 14 → adjusted to Friday 13th
-        let start = cycle_start_date(14, "2026-02-19");
-        assert_eq!(start, "2026-02-13");
+    fn test_miles_cap_limits_miles_not_spend() {
+        let conn = test_db();
+
+        let miles_capped = NewCard {
+            name: "1000 mi cap".to_string(),
+            categories: vec!["dining".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 4.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            max_reward_limit: Some(1000.0),
+            cap_unit: CapUnit::Miles,
+            ..Default::default()
+        };
+        let id = add_card(&conn, &miles_capped).unwrap();
+        add_test_card(&conn, "Uncapped", &["dining".into()], 1.2, 1.0, 1, None, None);
+        add_spending(&conn, id, 240.0, "dining", "2026-02-05", None, false, None).unwrap();
+
+        // 960 miles earned leave 40: the purchase earns those and ranks by them
+        let results = best_card_for_category(&conn, "dining", 50.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results[0].card_name, "Uncapped");
+        let capped = &results[1];
+        assert!(capped.eligible);
+        assert_eq!(capped.reason, "Eligible, capped at 40 miles");
+        assert_eq!((capped.miles_earned, capped.effective_rate), (40.0, 0.8));
+        assert_eq!(capped.remaining_limit, Some(10.0));
+
+        // A $10 purchase fits under the cap in full
+        let small = best_card_for_category(&conn, "dining", 10.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!((small[0].card_name.as_str(), small[0].miles_earned), ("1000 mi cap", 40.0));
+
+        add_spending(&conn, id, 10.0, "dining", "2026-02-06", None, false, None).unwrap();
+        let results = best_card_for_category(&conn, "dining", 5.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert!(!results[1].eligible);
+        assert_eq!(results[1].reason, "Reward limit reached (1000 miles this cycle)");
     }
 
     #[test]
-    fn test_cycle_start_date_sunday_adjustment() {
-        // 2026-03-01 is a Sunday, renewal day 1 → adjusted to Friday Feb 27
-        // Reference date Mar 5 (Thu), renewal day 1 for March is Sun → Fri Feb 27
-        // day 5 >= adjusted day? adjusted_for March 1 → Feb 27, am=2, month=3, am != month
-        // So we go to "last month": Feb 1 is a Sunday → adjusted to Jan 30 (Fri)
-        // Wait, let me reconsider. The reference is Mar 5 and renewal day is 1.
-        // For this month (March): March 1 is Sunday → adjust to Feb 27 (Friday).
-        // am=2, month=3, am != month → falls through to "last month" branch.
-        // Actually the adjusted date moved to a different month, so we need to handle this.
-        // Let me verify: day=5, ad=27, am=2, month=3. day(5) >= ad(27) is false, so
-        // it goes to the else branch: prev month = Feb, renewal 1 → Feb 1 is Sunday → Jan 30 Fri.
-        // The cycle start should be Jan 30.
-        let start = cycle_start_date(1, "2026-03-05");
-        assert_eq!(start, "2026-01-30");
+    fn test_recorded_miles_stop_at_a_miles_cap() {
+        let conn = test_db();
+        let id = add_card(
+            &conn,
+            &NewCard {
+                name: "1000 mi cap".to_string(),
+                categories: vec!["dining".into()],
+                payment_categories: all_payment_categories(),
+                miles_per_dollar: 4.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit: Some(1000.0),
+                cap_unit: CapUnit::Miles,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let (first, miles) = add_spending(&conn, id, 400.0, "dining", "2026-02-05", None, false, None).unwrap();
+        assert_eq!(miles, 1000.0);
+        let (_, miles) = add_spending(&conn, id, 400.0, "dining", "2026-02-06", None, false, None).unwrap();
+        assert_eq!(miles, 0.0);
+        // The next cycle starts afresh
+        let (_, miles) = add_spending(&conn, id, 100.0, "dining", "2026-03-02", None, false, None).unwrap();
+        assert_eq!(miles, 400.0);
+
+        // Recalculating a transaction leaves its own miles out of what the cycle has earned
+        let edit = SpendingEdit { amount: Some(100.0), ..Default::default() };
+        assert_eq!(edit_spending(&conn, first, &edit).unwrap(), Some(400.0));
+        let summary: f64 = conn
+            .query_row(
+                "SELECT reward_limit_remaining FROM v_cycle_summary WHERE card_id = ?1 AND cycle_start = '2026-01-30'",
+                params![id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(summary, 600.0);
     }
 
     #[test]
-    fn test_cycle_start_date_no_adjustment() {
-        // 2026-02-02 is a Monday, renewal day 2 → no adjustment needed
-        let start = cycle_start_date(2, "2026-02-19");
-        assert_eq!(start, "2026-02-02");
+    fn test_min_spend_counts_only_qualifying_spend() {
+        let conn = test_db();
+
+        let card = NewCard {
+            name: "Min $500".to_string(),
+            categories: vec!["dining".into(), "bills".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 4.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            min_spend: Some(500.0),
+            min_spend_exclusions: vec!["Bills".to_string()],
+            ..Default::default()
+        };
+        let id = add_card(&conn, &card).unwrap();
+        add_spending(&conn, id, 300.0, "dining", "2026-02-05", None, false, None).unwrap();
+        add_spending(&conn, id, 400.0, "bills", "2026-02-06", None, false, None).unwrap();
+
+        // $700 spent, but the $400 of bills leave $200 to go
+        let options = RecommendOptions {
+            explain: true,
+            ..Default::default()
+        };
+        let results = best_card_for_category(&conn, "dining", 50.0, "online", "2026-02-19", &options).unwrap();
+        assert!(!results[0].eligible);
+        assert_eq!(results[0].reason, "Min spend not met ($200.00 more needed)");
+        let step = results[0].explanation.iter().find(|s| s.step == "min spend").unwrap();
+        assert_eq!(step.detail, "$500.00, $300.00 spent ($400.00 more in bills not counted): Min spend not met ($200.00 more needed)");
+
+        add_spending(&conn, id, 200.0, "dining", "2026-02-07", None, false, None).unwrap();
+        let results = best_card_for_category(&conn, "dining", 50.0, "online", "2026-02-19", &options).unwrap();
+        assert!(results[0].eligible);
     }
 
     #[test]
-    fn test_day_of_week() {
-        // Known dates for verification:
-        // 2026-02-19 is a Thursday (3)
-        assert_eq!(day_of_week(2026, 2, 19), 3);
-        // 2026-02-14 is a Saturday (5)
-        assert_eq!(day_of_week(2026, 2, 14), 5);
-        // 2026-02-15 is a Sunday (6)
-        assert_eq!(day_of_week(2026, 2, 15), 6);
-        // 2026-02-13 is a Friday (4)
-        assert_eq!(day_of_week(2026, 2, 13), 4);
-        // 2026-02-16 is a Monday (0)
-        assert_eq!(day_of_week(2026, 2, 16), 0);
+    fn test_foreign_blocks_count_in_purchase_currency() {
+        let conn = test_db();
+
+        let usd_blocks = NewCard {
+            name: "Per US$1".to_string(),
+            categories: vec!["travel".into()],
+            payment_categories: all_payment_categories(),
+            miles_per_dollar: 2.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            foreign_blocks: true,
+            ..Default::default()
+        };
+        add_card(&conn, &usd_blocks).unwrap();
+        add_test_card(&conn, "Per S$1", &["travel".into()], 1.8, 1.0, 1, None, None);
+
+        // US$100 charged as S$135: 200 miles against 243
+        let options = RecommendOptions {
+            foreign_amount: Some(100.0),
+            ..Default::default()
+        };
+        let results = best_card_for_category(&conn, "travel", 135.0, "online", "2026-02-19", &options).unwrap();
+        assert_eq!(results[0].card_name, "Per S$1");
+        assert_eq!((results[0].miles_earned, results[1].miles_earned), (243.0, 200.0));
+        assert!((results[1].effective_rate - 200.0 / 135.0).abs() < 1e-9);
+
+        // A home-currency purchase counts every card's blocks in it
+        let home = best_card_for_category(&conn, "travel", 135.0, "online", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!((home[0].card_name.as_str(), home[0].miles_earned), ("Per US$1", 270.0));
+
+        // Recorded spending counts the same blocks, and keeps counting them
+        // when edited or moved, until its amount is corrected
+        let usd = OriginalAmount { amount: 100.0, currency: "USD".to_string() };
+        let (id, miles) = add_spending(&conn, 1, 135.0, "travel", "2026-02-19", None, true, Some(&usd)).unwrap();
+        assert_eq!(miles, 200.0);
+        let edit = SpendingEdit { category: Some("travel".to_string()), ..Default::default() };
+        assert_eq!(edit_spending(&conn, id, &edit).unwrap(), Some(200.0));
+        assert_eq!(move_spending(&conn, &[id], 2).unwrap(), vec![(id, 243.0)]);
+        assert_eq!(move_spending(&conn, &[id], 1).unwrap(), vec![(id, 200.0)]);
+        let edit = SpendingEdit { amount: Some(140.0), ..Default::default() };
+        assert_eq!(edit_spending(&conn, id, &edit).unwrap(), Some(280.0));
     }
 
+    // ── Payment tests ────────────────────────────────────────────
+
     #[test]
-    fn test_reward_limit_respects_weekend_cycle() {
+    fn test_add_and_list_payments() {
         let conn = test_db();
 
-        // Card with renewal day 15, which in Feb 2026 is a Sunday → adjusted to Feb 13 (Fri)
-        // max_reward_limit = $200
-        let card_id = add_test_card(&conn, "Weekend Card", &vec!["dining".into()], 4.0, 1.0, 15, Some(200.0), None);
+        let card_a = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        let card_b = add_test_card(&conn, "Card B", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_payment(&conn, card_a, 200.0, "2026-02-10").unwrap();
+        add_payment(&conn, card_a, 50.0, "2026-03-10").unwrap();
+        add_payment(&conn, card_b, 75.0, "2026-02-12").unwrap();
 
-        // Spend $150 on Feb 14 (after the adjusted cycle start of Feb 13)
-        add_spending(&conn, card_id, 150.0, "dining", "2026-02-14").unwrap();
+        let payments = list_payments(&conn, Some(card_a)).unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0].date, "2026-03-10");
+        assert_eq!(list_payments(&conn, None).unwrap().len(), 3);
+    }
 
-        // Query on Feb 19 for $60 — should exceed limit ($50 remaining)
-        let results = best_card_for_category(&conn, "dining", 60.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(!results[0].eligible);
-        assert_eq!(results[0].remaining_limit, Some(50.0));
+    #[test]
+    fn test_cash_flow_separates_spend_and_payments() {
+        let conn = test_db();
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 120.0, "dining", "2026-01-15", None, false, None).unwrap();
+        add_spending(&conn, card_id, 80.0, "dining", "2026-02-03", None, false, None).unwrap();
+        schedule_spending(&conn, card_id, 500.0, "dining", "2026-02-20", None, false, None).unwrap();
+        add_payment(&conn, card_id, 120.0, "2026-02-05").unwrap();
+
+        let flow = cash_flow(&conn, Some(card_id), None).unwrap();
+        assert_eq!(flow.len(), 2);
+        assert_eq!(flow[0].month, "2026-01");
+        assert_eq!(flow[0].spending, 120.0);
+        assert_eq!(flow[0].payments, 0.0);
+        assert_eq!(flow[1].month, "2026-02");
+        assert_eq!(flow[1].spending, 80.0);
+        assert_eq!(flow[1].payments, 120.0);
+        assert_eq!(flow[1].net, -40.0);
+
+        // A period cuts months short: the payment on the 5th falls outside
+        let period = DateRange {
+            from: "2026-02-01".into(),
+            to: "2026-02-04".into(),
+        };
+        let flow = cash_flow(&conn, Some(card_id), Some(&period)).unwrap();
+        assert_eq!(flow.len(), 1);
+        assert_eq!(flow[0].spending, 80.0);
+        assert_eq!(flow[0].payments, 0.0);
     }
 
     #[test]
-    fn test_spending_before_adjusted_cycle_not_counted() {
+    fn test_spending_stats_group_by_card_category_and_month() {
         let conn = test_db();
 
-        // Card with renewal day 15, Feb 2026 → adjusted to Feb 13 (Fri)
-        let card_id = add_test_card(&conn, "Weekend Card", &vec!["dining".into()], 4.0, 1.0, 15, Some(200.0), None);
+        let a = add_test_card(&conn, "Card A", &["dining".into(), "travel".into()], 2.0, 1.0, 1, None, None);
+        let b = add_test_card(&conn, "Card B", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_spending(&conn, a, 100.0, "dining", "2026-01-15", None, false, None).unwrap();
+        add_spending(&conn, a, 50.0, "Dining", "2026-02-03", None, false, None).unwrap();
+        add_spending(&conn, a, 300.0, "travel", "2026-02-10", None, false, None).unwrap();
+        add_spending(&conn, b, 40.0, "dining", "2026-02-12", None, false, None).unwrap();
+        schedule_spending(&conn, b, 500.0, "dining", "2099-02-20", None, false, None).unwrap();
+
+        let by_card = spending_stats(&conn, StatsBy::Card, None, None).unwrap();
+        assert_eq!(by_card.len(), 2);
+        assert_eq!((by_card[0].name.as_str(), by_card[0].transactions, by_card[0].total_spent), ("Card A", 3, 450.0));
+        assert_eq!((by_card[0].average_spent, by_card[0].total_miles, by_card[0].average_miles), (150.0, 900.0, 300.0));
+        assert_eq!((by_card[1].transactions, by_card[1].total_spent), (1, 40.0));
+
+        // Categories group whatever their case, biggest first
+        let by_category = spending_stats(&conn, StatsBy::Category, None, None).unwrap();
+        let names: Vec<_> = by_category.iter().map(|s| (s.name.as_str(), s.transactions)).collect();
+        assert_eq!(names, vec![("travel", 1), ("Dining", 3)]);
+
+        let by_month = spending_stats(&conn, StatsBy::Month, Some(a), None).unwrap();
+        let months: Vec<_> = by_month.iter().map(|s| (s.name.as_str(), s.total_spent)).collect();
+        assert_eq!(months, vec![("2026-01", 100.0), ("2026-02", 350.0)]);
+        let period = DateRange { from: "2026-02-01".into(), to: "2026-02-28".into() };
+        assert_eq!(spending_stats(&conn, StatsBy::Month, None, Some(&period)).unwrap()[0].transactions, 3);
+    }
 
-        // Spend $180 on Feb 12 (BEFORE the adjusted cycle start of Feb 13)
-        add_spending(&conn, card_id, 180.0, "dining", "2026-02-12").unwrap();
+    #[test]
+    fn test_remove_card_deletes_payments() {
+        let conn = test_db();
 
-        // Query on Feb 19 for $50 — previous cycle spending shouldn't count
-        let results = best_card_for_category(&conn, "dining", 50.0, "contactless", "2026-02-19").unwrap();
-        assert_eq!(results.len(), 1);
-        assert!(results[0].eligible);
-        assert_eq!(results[0].remaining_limit, Some(200.0));
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_payment(&conn, card_id, 10.0, "2026-02-05").unwrap();
+        remove_card(&conn, card_id).unwrap();
+        assert!(list_payments(&conn, None).unwrap().is_empty());
     }
 
-    // ── Spending tests ───────────────────────────────────────────
+    // ── Outstanding balance tests ────────────────────────────────
 
     #[test]
-    fn test_add_spending_block_size_1() {
+    fn test_outstanding_balance() {
         let conn = test_db();
 
-        // 3 miles per $1 block
-        let card_id = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        assert!(set_balance_settings(&conn, card_id, Some(300.0), None).unwrap());
+        add_spending(&conn, card_id, 120.0, "dining", "2026-02-03", None, false, None).unwrap();
+        schedule_spending(&conn, card_id, 999.0, "dining", "2026-02-20", None, false, None).unwrap();
+        add_payment(&conn, card_id, 250.0, "2026-02-05").unwrap();
+        add_payment(&conn, card_id, 100.0, "2026-03-05").unwrap();
+
+        let balances = outstanding_balances(&conn, "2026-02-28").unwrap();
+        assert_eq!(balances[0].starting_balance, 300.0);
+        assert_eq!(balances[0].spending, 120.0);
+        assert_eq!(balances[0].payments, 250.0);
+        assert_eq!(balances[0].outstanding, 170.0);
+        assert!(!balances[0].over_alert);
+    }
 
-        let (id, miles) = add_spending(&conn, card_id, 42.50, "dining", "2026-02-19").unwrap();
-        assert_eq!(id, 1);
-        // floor(42.50 / 1.0) * 3.0 = 42 * 3 = 126
-        assert_eq!(miles, 126.0);
+    #[test]
+    fn test_outstanding_balance_alert() {
+        let conn = test_db();
+
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        set_balance_settings(&conn, card_id, None, Some(100.0)).unwrap();
+        add_spending(&conn, card_id, 80.0, "dining", "2026-02-03", None, false, None).unwrap();
+        assert!(!outstanding_balances(&conn, "2026-02-28").unwrap()[0].over_alert);
+        // Setting one leaves the other as it was
+        set_balance_settings(&conn, card_id, Some(10.0), None).unwrap();
+        let balances = outstanding_balances(&conn, "2026-02-28").unwrap();
+        assert_eq!((balances[0].starting_balance, balances[0].balance_alert), (10.0, Some(100.0)));
+        assert!(!balances[0].over_alert);
+        set_balance_settings(&conn, card_id, Some(0.0), None).unwrap();
+
+        add_spending(&conn, card_id, 40.0, "dining", "2026-02-04", None, false, None).unwrap();
+        let balances = outstanding_balances(&conn, "2026-02-28").unwrap();
+        assert_eq!(balances[0].outstanding, 120.0);
+        assert!(balances[0].over_alert);
     }
 
+    // ── Charge tests ─────────────────────────────────────────────
+
     #[test]
-    fn test_add_spending_block_size_5() {
+    fn test_charges_excluded_from_earnings() {
         let conn = test_db();
 
-        // 10 miles per $5 block
-        let card_id = add_test_card(&conn, "Card B", &vec!["dining".into()], 10.0, 5.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Limited Card", &["dining".into()], 4.0, 1.0, 1, Some(100.0), None);
+        add_charge(&conn, card_id, "interest", 80.0, "2026-02-05").unwrap();
 
-        let (_, miles) = add_spending(&conn, card_id, 42.50, "dining", "2026-02-19").unwrap();
-        // floor(42.50 / 5.0) * 10.0 = 8 * 10 = 80
-        assert_eq!(miles, 80.0);
+        let results = best_card_for_category(&conn, "dining", 10.0, "contactless", "2026-02-19", &RecommendOptions::default()).unwrap();
+        assert_eq!(results[0].remaining_limit, Some(100.0));
+        assert!(list_spending(&conn, None, None).unwrap().is_empty());
+        assert_eq!(list_charges(&conn, Some(card_id)).unwrap()[0].kind, "interest");
     }
 
     #[test]
-    fn test_add_spending_below_block_size() {
+    fn test_charges_in_cash_flow_and_outstanding() {
         let conn = test_db();
 
-        // 10 miles per $5 block, spend only $3
-        let card_id = add_test_card(&conn, "Card B", &vec!["dining".into()], 10.0, 5.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 1.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 100.0, "dining", "2026-02-03", None, false, None).unwrap();
+        add_charge(&conn, card_id, "late_fee", 25.0, "2026-02-20").unwrap();
+        add_payment(&conn, card_id, 50.0, "2026-02-21").unwrap();
 
-        let (_, miles) = add_spending(&conn, card_id, 3.0, "dining", "2026-02-19").unwrap();
-        // floor(3.0 / 5.0) * 10.0 = 0 * 10 = 0
-        assert_eq!(miles, 0.0);
+        let flow = cash_flow(&conn, Some(card_id), None).unwrap();
+        assert_eq!(flow[0].charges, 25.0);
+        assert_eq!(flow[0].net, 75.0);
+
+        let balances = outstanding_balances(&conn, "2026-02-28").unwrap();
+        assert_eq!(balances[0].charges, 25.0);
+        assert_eq!(balances[0].outstanding, 75.0);
+    }
+
+    // ── Import tests ─────────────────────────────────────────────
+
+    fn import_profile() -> ImportProfile {
+        toml::from_str(
+            r#"
+            [columns]
+            date = "Date"
+            amount = "Amount"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn imported(date: &str, amount: f64, description: &str) -> ImportedRow {
+        ImportedRow {
+            date: date.to_string(),
+            amount,
+            description: Some(description.to_string()),
+            category: None,
+            payment_category: None,
+        }
     }
 
     #[test]
-    fn test_list_spending_all() {
+    fn test_import_spending() {
         let conn = test_db();
 
-        let card_a = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        let card_b = add_test_card(&conn, "Card B", &vec!["travel".into()], 2.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 1, None, None);
+        let rows = vec![
+            ImportedRow {
+                date: "2026-02-05".to_string(),
+                amount: 42.5,
+                description: Some("COLD STORAGE".to_string()),
+                category: Some("groceries".to_string()),
+                payment_category: None,
+            },
+            ImportedRow {
+                date: "2026-02-06".to_string(),
+                amount: 10.0,
+                description: None,
+                category: Some("dining".to_string()),
+                payment_category: None,
+            },
+        ];
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(summary.imported, 2);
+        assert_eq!(summary.duplicates, 0);
+
+        let spending = list_spending(&conn, Some(card_id), None).unwrap();
+        assert_eq!(spending.len(), 2);
+        assert_eq!(spending[1].description.as_deref(), Some("COLD STORAGE"));
+        assert_eq!(spending[1].miles_earned, 84.0);
+    }
 
-        add_spending(&conn, card_a, 50.0, "dining", "2026-02-18").unwrap();
-        add_spending(&conn, card_b, 100.0, "travel", "2026-02-19").unwrap();
+    #[test]
+    fn test_import_spending_is_atomic() {
+        let conn = test_db();
 
-        let all = list_spending(&conn, None).unwrap();
-        assert_eq!(all.len(), 2);
-        // Ordered by date DESC
-        assert_eq!(all[0].date, "2026-02-19");
-        assert_eq!(all[1].date, "2026-02-18");
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 2, None, None);
+        close_cycle(&conn, card_id, "2026-03-01").unwrap();
+        let rows = vec![
+            ImportedRow {
+                date: "2026-03-05".to_string(),
+                amount: 10.0,
+                description: None,
+                category: Some("dining".to_string()),
+                payment_category: None,
+            },
+            // Falls in the closed cycle and aborts the whole import
+            ImportedRow {
+                date: "2026-02-10".to_string(),
+                amount: 10.0,
+                description: None,
+                category: Some("dining".to_string()),
+                payment_category: None,
+            },
+        ];
+        assert!(import_spending(&conn, card_id, &rows, &import_profile()).is_err());
+        assert!(list_spending(&conn, None, None).unwrap().is_empty());
     }
 
     #[test]
-    fn test_list_spending_by_card() {
+    fn test_import_spending_skips_duplicates() {
         let conn = test_db();
 
-        let card_a = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        let card_b = add_test_card(&conn, "Card B", &vec!["travel".into()], 2.0, 1.0, 1, None, None);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 12.0, "dining", "2026-02-05", None, false, None).unwrap();
+        let coffee = ImportedRow {
+            date: "2026-02-05".to_string(),
+            amount: 12.0,
+            description: Some("KOPI".to_string()),
+            category: Some("dining".to_string()),
+            payment_category: None,
+        };
+        // Two identical charges on the statement, one already entered by hand
+        let rows = vec![coffee.clone(), coffee];
+
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.duplicates, 1);
+        assert_eq!(list_spending(&conn, Some(card_id), None).unwrap().len(), 2);
+
+        // Re-importing the same statement adds nothing
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.duplicates, 2);
+    }
 
-        add_spending(&conn, card_a, 50.0, "dining", "2026-02-18").unwrap();
-        add_spending(&conn, card_b, 100.0, "travel", "2026-02-19").unwrap();
+    #[test]
+    fn test_import_spending_skips_rows_awaiting_review() {
+        let conn = test_db();
 
-        let card_a_spending = list_spending(&conn, Some(card_a)).unwrap();
-        assert_eq!(card_a_spending.len(), 1);
-        assert_eq!(card_a_spending[0].amount, 50.0);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 1, None, None);
+        let rows = vec![imported("2026-02-07", 9.0, "NEW CAFE"), imported("2026-02-07", 9.0, "NEW CAFE")];
+        let summary = import_spending(&conn, card_id, &rows[..1], &import_profile()).unwrap();
+        assert_eq!(summary.queued_for_review, 1);
+
+        // The queued row absorbs one of the two, the other is a new charge
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!((summary.duplicates, summary.queued_for_review), (1, 1));
+        assert_eq!(list_review_queue(&conn, Some(card_id)).unwrap().len(), 2);
     }
 
     #[test]
-    fn test_spending_miles_stored_correctly() {
+    fn test_import_learns_merchant_categories() {
         let conn = test_db();
 
-        let card_id = add_test_card(&conn, "Card A", &vec!["dining".into()], 3.0, 1.0, 1, None, None);
-        add_spending(&conn, card_id, 100.0, "dining", "2026-02-19").unwrap();
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 1, None, None);
+        let history = vec![
+            ImportedRow { category: Some("groceries".to_string()), ..imported("2026-01-05", 30.0, "COLD STORAGE #12") },
+            ImportedRow { category: Some("shopping".to_string()), ..imported("2026-01-06", 20.0, "AMAZON") },
+            ImportedRow { category: Some("entertainment".to_string()), ..imported("2026-01-07", 15.0, "AMAZON") },
+        ];
+        import_spending(&conn, card_id, &history, &import_profile()).unwrap();
+
+        let rows = vec![
+            imported("2026-02-05", 42.0, "Cold Storage #99"),
+            imported("2026-02-06", 25.0, "AMAZON"),
+            imported("2026-02-07", 9.0, "NEW CAFE"),
+        ];
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(summary.imported, 1);
+        assert_eq!(summary.queued_for_review, 2);
+
+        let spending = list_spending(&conn, Some(card_id), None).unwrap();
+        assert_eq!(spending[0].category, "groceries");
+
+        let queue = list_review_queue(&conn, Some(card_id)).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[0].description.as_deref(), Some("AMAZON"));
+        assert_eq!(queue[0].confidence, Some(0.5));
+        assert_eq!(queue[1].suggested_category, None);
+
+        // Accept the suggestion for one, pick a category for the other
+        assert!(resolve_review(&conn, queue[0].id, None).unwrap().is_some());
+        assert!(resolve_review(&conn, queue[1].id, None).unwrap().is_none());
+        let (_, miles) = resolve_review(&conn, queue[1].id, Some("dining")).unwrap().unwrap();
+        assert_eq!(miles, 18.0);
+        assert!(list_review_queue(&conn, None).unwrap().is_empty());
+        assert_eq!(list_spending(&conn, Some(card_id), None).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_preview_import_writes_nothing() {
+        let conn = test_db();
 
-        let spending = list_spending(&conn, Some(card_id)).unwrap();
-        assert_eq!(spending[0].miles_earned, 300.0);
+        let card_id = add_test_card(&conn, "Card A", &["dining".into()], 2.0, 1.0, 1, None, None);
+        add_spending(&conn, card_id, 12.0, "dining", "2026-02-05", None, false, None).unwrap();
+        let rows = vec![
+            imported("2026-02-05", 12.0, "KOPI"),
+            ImportedRow { category: Some("dining".to_string()), ..imported("2026-02-06", 20.0, "SUSHI") },
+            ImportedRow { category: Some("dining".to_string()), ..imported("2026-02-07", 30.0, "RAMEN") },
+            ImportedRow { category: Some("travel".to_string()), ..imported("2026-02-08", 100.0, "SIA") },
+            imported("2026-02-09", 9.0, "NEW CAFE"),
+            imported("2026-02-10", 7.0, "NEW CAFE"),
+        ];
+
+        let preview = preview_import(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(preview.rows, 6);
+        assert_eq!(preview.to_import, 3);
+        assert_eq!(preview.to_review, 2);
+        assert_eq!(preview.total_amount, 150.0);
+        assert_eq!(preview.duplicates.len(), 1);
+        assert_eq!(preview.categories.len(), 2);
+        assert_eq!(preview.categories[0].category, "dining");
+        assert_eq!(preview.categories[0].count, 2);
+        assert_eq!(preview.categories[0].amount, 50.0);
+        assert_eq!(preview.unmapped_merchants, vec!["NEW CAFE".to_string()]);
+
+        assert_eq!(list_spending(&conn, Some(card_id), None).unwrap().len(), 1);
+        assert!(list_review_queue(&conn, None).unwrap().is_empty());
+
+        let summary = import_spending(&conn, card_id, &rows, &import_profile()).unwrap();
+        assert_eq!(summary.imported, preview.to_import);
+        assert_eq!(summary.queued_for_review, preview.to_review);
     }
 }
@@ -0,0 +1,457 @@
+//! Monthly digests: a markdown summary of a month's spending and miles, the
+//! miles its purchases missed by going on the wrong card, and the fees and
+//! deadlines of the month after. `digest_command` in config.toml sends them,
+//! e.g. by mail, and the server sends each month's once it has ended.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use crate::db::{today, STATUS_POSTED, STATUS_SCHEDULED};
+use crate::engine::{card_cycle, days_to_ymd, format_date, parse_date, ymd_to_days};
+use crate::error::Result;
+use crate::models::{Card, RecommendOptions, Spending};
+use crate::store::{AsyncStore, Store};
+
+/// Purchases listed under "Missed miles"; the rest only count towards the total
+const MAX_MISSED_ROWS: usize = 10;
+
+pub const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October",
+    "November", "December",
+];
+
+/// Parses a YYYY-MM month into (year, month).
+pub fn parse_month(month: &str) -> std::result::Result<(i32, i32), String> {
+    let invalid = || format!("Invalid month '{}'; use YYYY-MM, e.g. 2026-02", month);
+    let (year, number) = month.split_once('-').ok_or_else(invalid)?;
+    let year: i32 = year.parse().map_err(|_| invalid())?;
+    let number: i32 = number.parse().map_err(|_| invalid())?;
+    if year < 1000 || !(1..=12).contains(&number) {
+        return Err(invalid());
+    }
+    Ok((year, number))
+}
+
+/// The month before the one containing `date`, as YYYY-MM.
+pub fn previous_month(date: &str) -> String {
+    let (year, month, _) = parse_date(date);
+    let (year, month) = if month == 1 { (year - 1, 12) } else { (year, month - 1) };
+    format!("{:04}-{:02}", year, month)
+}
+
+/// The first and last day of a month.
+pub fn month_bounds(year: i32, month: i32) -> (String, String) {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let (y, m, d) = days_to_ymd(ymd_to_days(next_year, next_month, 1) - 1);
+    (format_date(year, month, 1), format_date(y, m, d))
+}
+
+/// The digest's title, also used as the mail subject.
+pub fn subject(year: i32, month: i32) -> String {
+    format!("Miles digest for {} {}", MONTH_NAMES[month as usize - 1], year)
+}
+
+#[derive(Tabled)]
+struct CardRow {
+    card: String,
+    spent: String,
+    miles: String,
+    transactions: usize,
+}
+
+#[derive(Tabled)]
+struct MissedRow {
+    date: String,
+    merchant: String,
+    amount: String,
+    card_used: String,
+    miles: String,
+    better_card: String,
+    its_miles: String,
+}
+
+/// Builds the digest for a month as markdown.
+pub fn build<S: Store + ?Sized>(store: &S, year: i32, month: i32) -> Result<String> {
+    let (first, last) = month_bounds(year, month);
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    let (next_first, next_last) = month_bounds(next_year, next_month);
+
+    let cards = store.list_cards()?;
+    let card_name = |id: i64| {
+        cards.iter().find(|c| c.id == id).map_or_else(|| format!("Card {}", id), |c| c.name.clone())
+    };
+
+    // The month's spending and what follows it, which the upcoming
+    // deadlines need; the cycle they are in starts within the month
+    let mut spending = Vec::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.date >= first {
+            spending.push(s);
+        }
+        Ok(())
+    })?;
+    spending.sort_by(|a, b| (&a.date, a.id).cmp(&(&b.date, b.id)));
+    let posted: Vec<&Spending> = spending
+        .iter()
+        .filter(|s| s.status == STATUS_POSTED && s.date <= last)
+        .collect();
+
+    let mut out = format!("# {}\n\n## Highlights\n\n", subject(year, month));
+    if posted.is_empty() {
+        out.push_str("- No spending recorded.\n");
+    } else {
+        let spent: f64 = posted.iter().map(|s| s.amount).sum();
+        let miles: f64 = posted.iter().map(|s| s.miles_earned).sum();
+        out.push_str(&format!(
+            "- Spent ${:.2} over {} transactions and earned {:.0} miles ({:.2} mi/$).\n",
+            spent,
+            posted.len(),
+            miles,
+            miles / spent.max(0.01)
+        ));
+
+        let mut by_category: BTreeMap<&str, f64> = BTreeMap::new();
+        let mut by_card: BTreeMap<i64, (f64, f64, usize)> = BTreeMap::new();
+        for s in &posted {
+            *by_category.entry(&s.category).or_default() += s.amount;
+            let card = by_card.entry(s.card_id).or_default();
+            card.0 += s.amount;
+            card.1 += s.miles_earned;
+            card.2 += 1;
+        }
+        if let Some((category, amount)) = by_category.iter().max_by(|a, b| a.1.total_cmp(b.1)) {
+            out.push_str(&format!("- Most spent on {}: ${:.2}.\n", category, amount));
+        }
+        if let Some((card_id, (_, miles, _))) = by_card.iter().max_by(|a, b| a.1.1.total_cmp(&b.1.1)) {
+            out.push_str(&format!("- Most miles from {}: {:.0}.\n", card_name(*card_id), miles));
+        }
+
+        let rows: Vec<CardRow> = by_card
+            .iter()
+            .map(|(card_id, (spent, miles, count))| CardRow {
+                card: card_name(*card_id),
+                spent: format!("${:.2}", spent),
+                miles: format!("{:.0}", miles),
+                transactions: *count,
+            })
+            .collect();
+        out.push_str(&format!("\n{}\n", Table::new(rows).with(Style::markdown())));
+    }
+
+    out.push_str("\n## Missed miles\n\n");
+    let mut missed = Vec::new();
+    for s in &posted {
+        let Some(card) = cards.iter().find(|c| c.id == s.card_id) else { continue };
+        if let Some((better_card, its_miles)) = best_alternative(store, s, card)?
+            && better_card != card.name
+            && its_miles > s.miles_earned
+        {
+            missed.push((s, better_card, its_miles));
+        }
+    }
+    if missed.is_empty() {
+        out.push_str("None: every purchase went on the best card for it.\n");
+    } else {
+        let total: f64 = missed.iter().map(|(s, _, its_miles)| its_miles - s.miles_earned).sum();
+        out.push_str(&format!(
+            "{} of the month's purchases could have earned {:.0} more miles on another card.\n",
+            missed.len(),
+            total
+        ));
+        missed.sort_by(|a, b| (b.2 - b.0.miles_earned).total_cmp(&(a.2 - a.0.miles_earned)));
+        let rows: Vec<MissedRow> = missed
+            .iter()
+            .take(MAX_MISSED_ROWS)
+            .map(|(s, better_card, its_miles)| MissedRow {
+                date: s.date.clone(),
+                merchant: s.description.clone().unwrap_or_else(|| s.category.clone()),
+                amount: format!("${:.2}", s.amount),
+                card_used: card_name(s.card_id),
+                miles: format!("{:.0}", s.miles_earned),
+                better_card: better_card.clone(),
+                its_miles: format!("{:.0}", its_miles),
+            })
+            .collect();
+        out.push_str(&format!("\n{}\n", Table::new(rows).with(Style::markdown())));
+        if missed.len() > MAX_MISSED_ROWS {
+            out.push_str(&format!("\n...and {} more.\n", missed.len() - MAX_MISSED_ROWS));
+        }
+    }
+
+    out.push_str(&format!(
+        "\n## Coming up in {} {}\n\n",
+        MONTH_NAMES[next_month as usize - 1],
+        next_year
+    ));
+    let mut upcoming = Vec::new();
+    for card in &cards {
+        if let Some(minimum) = card.min_spend
+            && !card.is_closed_on(&next_first)
+        {
+            let (start, end) = card_cycle(card, &next_first);
+            let excluded = card.excluded_from_min_spend();
+            let spent: f64 = spending
+                .iter()
+                .filter(|s| s.card_id == card.id && s.status == STATUS_POSTED && s.date >= start && s.date <= end)
+                .filter(|s| !excluded.contains(&s.category.to_lowercase()))
+                .map(|s| s.amount)
+                .sum();
+            if end <= next_last && spent < minimum {
+                upcoming.push((
+                    end.clone(),
+                    format!(
+                        "- **{}**: min spend of ${:.2} by {}, ${:.2} to go\n",
+                        card.name,
+                        minimum,
+                        end,
+                        minimum - spent
+                    ),
+                ));
+            }
+        }
+        if let Some(due) = annual_fee_due(store, card, &next_first)?
+            && due <= next_last
+        {
+            upcoming.push((
+                due.clone(),
+                format!(
+                    "- **{}**: annual fee of ${:.2} due around {}\n",
+                    card.name,
+                    card.annual_fee.unwrap_or(0.0),
+                    due
+                ),
+            ));
+        }
+    }
+    for s in spending.iter().filter(|s| s.status == STATUS_SCHEDULED && s.date >= next_first && s.date <= next_last) {
+        upcoming.push((
+            s.date.clone(),
+            format!(
+                "- **{}**: ${:.2} of {} scheduled on {}\n",
+                card_name(s.card_id),
+                s.amount,
+                s.category,
+                s.date
+            ),
+        ));
+    }
+    if upcoming.is_empty() {
+        out.push_str("- Nothing due.\n");
+    }
+    upcoming.sort();
+    for (_, line) in upcoming {
+        out.push_str(&line);
+    }
+    Ok(out)
+}
+
+/// The card the tracker would have recommended for a transaction on its
+/// day, with the miles it would have earned there. Without a payment
+/// category this is the least of what each of the used card's payment
+/// categories gives, so a purchase only counts as missed if a better card
+/// existed however it was paid.
+fn best_alternative<S: Store + ?Sized>(store: &S, spending: &Spending, card: &Card) -> Result<Option<(String, f64)>> {
+    let payment_categories = match &spending.payment_category {
+        Some(payment_category) => vec![payment_category.clone()],
+        None => serde_json::from_str(&card.payment_categories).unwrap_or_default(),
+    };
+    let options = RecommendOptions {
+        known_by: Some(spending.date.clone()),
+        merchant: spending.description.clone(),
+        ..RecommendOptions::default()
+    };
+
+    let mut least: Option<(String, f64)> = None;
+    for payment_category in payment_categories {
+        let best = store
+            .best_card_for_category(&spending.category, spending.amount, &payment_category, &spending.date, &options)?
+            .into_iter()
+            .filter(|r| r.eligible)
+            .max_by(|a, b| a.miles_earned.total_cmp(&b.miles_earned));
+        let Some(best) = best else { return Ok(None) };
+        if least.as_ref().is_none_or(|(_, miles)| best.miles_earned < *miles) {
+            least = Some((best.card_name, best.miles_earned));
+        }
+    }
+    Ok(least)
+}
+
+/// When a card's next annual fee is expected on or after `from`: a year on
+/// from the last one charged or, before any is recorded, the first of the
+/// card's fee posting month. None if the card has no fee, neither is known,
+/// or the card is closed by then.
+pub fn annual_fee_due<S: Store + ?Sized>(store: &S, card: &Card, from: &str) -> Result<Option<String>> {
+    if card.annual_fee.is_none() {
+        return Ok(None);
+    }
+    let last_charged = store
+        .list_charges(Some(card.id))?
+        .into_iter()
+        .filter(|c| c.kind == "annual_fee")
+        .map(|c| c.date)
+        .max();
+    let Some(last_charged) = last_charged else {
+        let Some(month) = card.fee_posting_month else { return Ok(None) };
+        let (year, _, _) = parse_date(from);
+        let mut due = format_date(year, month, 1);
+        if due.as_str() < from {
+            due = format_date(year + 1, month, 1);
+        }
+        return Ok((!card.is_closed_on(&due)).then_some(due));
+    };
+
+    let (mut year, month, day) = parse_date(&last_charged);
+    loop {
+        year += 1;
+        // A fee charged on Feb 29 falls due on Feb 28 in other years
+        let (y, m, d) = days_to_ymd(ymd_to_days(year, month, 1) + day - 1);
+        let due = if m == month { format_date(y, m, d) } else { month_bounds(year, month).1 };
+        if due.as_str() >= from {
+            return Ok((!card.is_closed_on(&due)).then_some(due));
+        }
+    }
+}
+
+/// Runs `command` with the shell, passing the digest on stdin and its
+/// subject in `CC_TRACKER_DIGEST_SUBJECT`, e.g.
+/// `mail -s "$CC_TRACKER_DIGEST_SUBJECT" me@example.com`.
+pub fn send(command: &str, subject: &str, markdown: &str) -> std::result::Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CC_TRACKER_DIGEST_SUBJECT", subject)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run digest command: {}", e))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(markdown.as_bytes())
+        .map_err(|e| format!("Failed to write to digest command: {}", e))?;
+    let status = child.wait().map_err(|e| format!("Failed to run digest command: {}", e))?;
+    if !status.success() {
+        return Err(format!("Digest command failed ({})", status));
+    }
+    Ok(())
+}
+
+/// How often the server checks whether last month's digest is due
+const DIGEST_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Sends each month's digest with `command` once the month has ended,
+/// for the server. The last month sent is kept in `sent_file`, so a
+/// restart does not send it again; failures are logged and retried at the
+/// next check.
+pub async fn send_monthly(store: AsyncStore, command: String, sent_file: &Path) {
+    let mut interval = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let month = previous_month(&today());
+        if std::fs::read_to_string(sent_file).is_ok_and(|sent| sent.trim() == month) {
+            continue;
+        }
+        let (year, number) = parse_month(&month).expect("previous_month is YYYY-MM");
+        let markdown = match store.call(move |s| build(s, year, number)).await {
+            Ok(markdown) => markdown,
+            Err(e) => {
+                tracing::warn!(%month, error = %e, "failed to build digest");
+                continue;
+            }
+        };
+        let command = command.clone();
+        let sent = tokio::task::spawn_blocking(move || send(&command, &subject(year, number), &markdown))
+            .await
+            .expect("digest command does not panic");
+        match sent.and_then(|()| {
+            std::fs::write(sent_file, &month).map_err(|e| format!("Failed to write {}: {}", sent_file.display(), e))
+        }) {
+            Ok(()) => tracing::info!(%month, "sent digest"),
+            Err(e) => tracing::warn!(%month, error = %e, "failed to send digest"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    fn card(conn: &Connection, name: &str, miles_per_dollar: f64, min_spend: Option<f64>, annual_fee: Option<f64>) -> i64 {
+        db::add_card(
+            conn,
+            &NewCard {
+                name: name.to_string(),
+                categories: vec!["dining".into()],
+                payment_categories: vec!["contactless".into()],
+                miles_per_dollar,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                min_spend,
+                annual_fee,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_digest_summarizes_month() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let low = card(&conn, "Low", 1.0, Some(500.0), Some(120.0));
+        let high = card(&conn, "High", 4.0, None, None);
+        db::add_spending(&conn, low, 25.0, "dining", "2026-02-05", Some("NOODLE BAR"), false, None).unwrap();
+        db::add_spending(&conn, high, 40.0, "dining", "2026-02-10", None, false, None).unwrap();
+        db::add_spending(&conn, high, 99.0, "dining", "2026-01-10", None, false, None).unwrap();
+        db::add_charge(&conn, low, "annual_fee", 120.0, "2025-03-12").unwrap();
+
+        let digest = build(&conn, 2026, 2).unwrap();
+        assert!(digest.starts_with("# Miles digest for February 2026\n"));
+        assert!(digest.contains("- Spent $65.00 over 2 transactions and earned 185 miles (2.85 mi/$).\n"));
+        assert!(digest.contains("- Most miles from High: 160.\n"));
+        assert!(digest.contains("1 of the month's purchases could have earned 75 more miles on another card.\n"));
+        assert!(digest.contains("| 2026-02-05 | NOODLE BAR | $25.00 | Low       | 25    | High        | 100       |"));
+        // Low's fee is a year on from the last; its cycle Feb 27 - Mar 31 has no spending yet
+        assert!(digest.ends_with(
+            "- **Low**: annual fee of $120.00 due around 2026-03-12\n\
+             - **Low**: min spend of $500.00 by 2026-03-31, $500.00 to go\n"
+        ));
+    }
+
+    #[test]
+    fn test_annual_fee_due_from_posting_month() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let id = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Posting".into(),
+                miles_per_dollar: 1.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                annual_fee: Some(90.0),
+                fee_posting_month: Some(4),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let card = db::get_card(&conn, id).unwrap().unwrap();
+        assert_eq!(annual_fee_due(&conn, &card, "2026-03-01").unwrap().as_deref(), Some("2026-04-01"));
+        assert_eq!(annual_fee_due(&conn, &card, "2026-05-01").unwrap().as_deref(), Some("2027-04-01"));
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(parse_month("2026-02"), Ok((2026, 2)));
+        assert!(parse_month("2026-13").is_err());
+        assert!(parse_month("Feb 2026").is_err());
+        assert_eq!(previous_month("2026-01-15"), "2025-12");
+    }
+}
@@ -0,0 +1,131 @@
+//! `due`: when each card's statements must be paid and how much is still
+//! owed on them, so a balance is cleared before it draws interest. A card's
+//! payment falls `payment_due_days` after its statement closes.
+
+
+use crate::engine::{add_days, card_cycle, days_between, round_cents};
+use crate::error::Result;
+use crate::models::{Card, OutstandingBalance, PaymentDue};
+use crate::store::Store;
+
+/// The card's balance as of `date`.
+fn balance_on<S: Store + ?Sized>(store: &S, card: &Card, date: &str) -> Result<Option<OutstandingBalance>> {
+    Ok(store.outstanding_balances(date)?.into_iter().find(|b| b.card_id == card.id))
+}
+
+/// Each card's last closed statement, if it is still to be paid or was due
+/// on or after `date`, and the statement its current cycle will close
+/// with, for the cards with a payment due date (or just `card`). What the
+/// closed statement still owes is left out of the open one's `owed`, so the
+/// rows add up to the balance.
+pub fn upcoming<S: Store + ?Sized>(store: &S, card: Option<&Card>, date: &str) -> Result<Vec<PaymentDue>> {
+    let cards: Vec<Card> = match card {
+        Some(card) => vec![card.clone()],
+        None => store.list_cards()?,
+    };
+    let now = store.outstanding_balances(date)?;
+    let mut rows = Vec::new();
+    for card in &cards {
+        let Some(due_days) = card.payment_due_days else {
+            continue;
+        };
+        let Some(current) = now.iter().find(|b| b.card_id == card.id) else {
+            continue;
+        };
+        let (start, end) = card_cycle(card, date);
+        let (_, closed) = card_cycle(card, &add_days(&start, -1));
+        // Owed on the closed statement, which the open one carries forward
+        let mut carried = 0.0;
+        if let Some(statement) = balance_on(store, card, &closed)? {
+            let due_date = add_days(&closed, due_days);
+            let paid = current.payments - statement.payments;
+            let owed = (statement.outstanding - paid).max(0.0);
+            carried = owed;
+            let status = if owed <= 0.0 {
+                "paid"
+            } else if due_date.as_str() < date {
+                "overdue"
+            } else {
+                "due"
+            };
+            if due_date.as_str() >= date || owed > 0.0 {
+                rows.push(PaymentDue {
+                    card_name: card.name.clone(),
+                    days_left: days_between(date, &due_date),
+                    statement_date: closed,
+                    due_date,
+                    statement_balance: round_cents(statement.outstanding),
+                    paid: round_cents(paid),
+                    owed: round_cents(owed),
+                    status: status.to_string(),
+                });
+            }
+        }
+        if !card.is_closed_on(date) {
+            let due_date = add_days(&end, due_days);
+            rows.push(PaymentDue {
+                card_name: card.name.clone(),
+                days_left: days_between(date, &due_date),
+                statement_date: end,
+                due_date,
+                statement_balance: round_cents(current.outstanding),
+                paid: 0.0,
+                owed: round_cents((current.outstanding - carried).max(0.0)),
+                status: "open".to_string(),
+            });
+        }
+    }
+    rows.sort_by(|a, b| a.due_date.cmp(&b.due_date).then_with(|| a.card_name.cmp(&b.card_name)));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_due_splits_the_balance_between_closed_and_open_statements() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str, payment_due_days| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    categories: vec!["dining".to_string()],
+                    payment_categories: vec!["contactless".to_string()],
+                    miles_per_dollar: 1.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    payment_due_days,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let id = card("Card A", Some(20));
+        card("No Due Date", None);
+        // The statement closing on Mar 31 is due on Apr 20
+        db::add_spending(&conn, id, 300.0, "dining", "2026-03-10", None, false, None).unwrap();
+        db::add_spending(&conn, id, 100.0, "dining", "2026-04-05", None, false, None).unwrap();
+        db::add_payment(&conn, id, 120.0, "2026-04-08").unwrap();
+
+        let rows = upcoming(&conn, None, "2026-04-15").unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].statement_date.as_str(), rows[0].due_date.as_str(), rows[0].days_left), ("2026-03-31", "2026-04-20", 5));
+        assert_eq!((rows[0].statement_balance, rows[0].paid, rows[0].owed, rows[0].status.as_str()), (300.0, 120.0, 180.0, "due"));
+        assert_eq!((rows[1].due_date.as_str(), rows[1].statement_balance, rows[1].owed, rows[1].status.as_str()), ("2026-05-20", 280.0, 100.0, "open"));
+
+        let rows = upcoming(&conn, None, "2026-04-25").unwrap();
+        assert_eq!((rows[0].status.as_str(), rows[0].days_left), ("overdue", -5));
+
+        // Once paid after its due date, the closed statement drops off
+        db::add_payment(&conn, id, 180.0, "2026-04-26").unwrap();
+        let rows = upcoming(&conn, None, "2026-04-27").unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].status.as_str(), rows[0].owed), ("open", 100.0));
+    }
+}
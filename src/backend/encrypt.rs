@@ -0,0 +1,184 @@
+//! Passphrase-encrypted exports, for backups kept on storage you do not
+//! trust. The export JSON is sealed in a versioned JSON envelope: a key is
+//! derived from the passphrase with PBKDF2-HMAC-SHA256 and the data is
+//! encrypted with XChaCha20-Poly1305, which also authenticates the
+//! envelope's parameters, so a wrong passphrase or a changed file is
+//! refused rather than restored as garbage.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::{AeadInOut, KeyInit, Tag, XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::io::{BufRead, Write};
+
+use crate::export::{check_stamp, to_versioned_json};
+
+pub const ENCRYPTED_FORMAT: &str = "cc-tracker-encrypted-export";
+/// Described by `schemas/cc-tracker-encrypted-export.v1.schema.json`
+pub const ENCRYPTED_VERSION: u32 = 1;
+
+/// Environment variable the passphrase is read from before asking for it
+pub const PASSPHRASE_VAR: &str = "CC_TRACKER_PASSPHRASE";
+
+/// PBKDF2 rounds for new files; each file records its own
+const ITERATIONS: u32 = 600_000;
+/// The most rounds a file may ask for, so a doctored one cannot keep the
+/// key derivation busy for hours
+const MAX_ITERATIONS: u32 = 10 * ITERATIONS;
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "xchacha20-poly1305";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// The envelope after its `format` stamp. Binary fields are base64.
+#[derive(Debug, Serialize, Deserialize)]
+struct Sealed {
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+    mac: String,
+}
+
+/// The cipher keyed with PBKDF2-HMAC-SHA256 of the passphrase.
+fn cipher(passphrase: &str, salt: &[u8], iterations: u32) -> XChaCha20Poly1305 {
+    let key = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, iterations);
+    XChaCha20Poly1305::new(&key.into())
+}
+
+/// The envelope's parameters, authenticated along with the ciphertext.
+fn associated_data(iterations: u32, salt: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for part in [ENCRYPTED_FORMAT.as_bytes(), &ENCRYPTED_VERSION.to_be_bytes(), KDF.as_bytes(), CIPHER.as_bytes()] {
+        data.extend_from_slice(part);
+    }
+    data.extend_from_slice(&iterations.to_be_bytes());
+    data.extend_from_slice(salt);
+    data
+}
+
+fn seal(plaintext: &str, passphrase: &str, iterations: u32, salt: &[u8], nonce: &[u8; NONCE_LEN]) -> String {
+    let mut ciphertext = plaintext.as_bytes().to_vec();
+    let tag = cipher(passphrase, salt, iterations)
+        .encrypt_inout_detached(
+            &XNonce::from(*nonce),
+            &associated_data(iterations, salt),
+            ciphertext.as_mut_slice().into(),
+        )
+        .expect("an export fits in one message");
+    let sealed = Sealed {
+        kdf: KDF.to_string(),
+        iterations,
+        salt: BASE64.encode(salt),
+        cipher: CIPHER.to_string(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(&ciphertext),
+        mac: BASE64.encode(tag),
+    };
+    to_versioned_json(ENCRYPTED_FORMAT, ENCRYPTED_VERSION, &sealed)
+}
+
+/// Encrypts an export with a passphrase, using a fresh random salt and nonce.
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<String, String> {
+    let (mut salt, mut nonce) = ([0u8; SALT_LEN], [0u8; NONCE_LEN]);
+    for random in [&mut salt[..], &mut nonce[..]] {
+        getrandom::fill(random).map_err(|e| format!("No randomness for encryption: {}", e))?;
+    }
+    Ok(seal(plaintext, passphrase, ITERATIONS, &salt, &nonce))
+}
+
+/// Whether a file is an encrypted export rather than a plain one.
+pub fn is_encrypted(text: &str) -> bool {
+    #[derive(Deserialize)]
+    struct Stamp {
+        format: Option<String>,
+    }
+    serde_json::from_str::<Stamp>(text).is_ok_and(|stamp| stamp.format.as_deref() == Some(ENCRYPTED_FORMAT))
+}
+
+/// Decrypts an encrypted export back into the export JSON.
+pub fn decrypt(text: &str, passphrase: &str) -> Result<String, String> {
+    check_stamp(text, ENCRYPTED_FORMAT, ENCRYPTED_VERSION, "encrypted export")?;
+    let sealed: Sealed = serde_json::from_str(text).map_err(|e| format!("Invalid encrypted export: {}", e))?;
+    if sealed.kdf != KDF || sealed.cipher != CIPHER {
+        return Err(format!("Unsupported encryption {} with {}", sealed.cipher, sealed.kdf));
+    }
+    if !(1..=MAX_ITERATIONS).contains(&sealed.iterations) {
+        return Err(format!("Invalid encrypted export: iterations must be 1-{}", MAX_ITERATIONS));
+    }
+    let decode = |field: &str, value: &str| {
+        BASE64.decode(value).map_err(|_| format!("Invalid encrypted export: {} is not base64", field))
+    };
+    let (salt, nonce) = (decode("salt", &sealed.salt)?, decode("nonce", &sealed.nonce)?);
+    let (mut data, tag) = (decode("ciphertext", &sealed.ciphertext)?, decode("mac", &sealed.mac)?);
+    let nonce = XNonce::try_from(nonce.as_slice())
+        .map_err(|_| "Invalid encrypted export: nonce must be 24 bytes".to_string())?;
+    let tag =
+        Tag::try_from(tag.as_slice()).map_err(|_| "Invalid encrypted export: mac must be 16 bytes".to_string())?;
+
+    cipher(passphrase, &salt, sealed.iterations)
+        .decrypt_inout_detached(&nonce, &associated_data(sealed.iterations, &salt), data.as_mut_slice().into(), &tag)
+        .map_err(|_| "Wrong passphrase, or the file has been changed".to_string())?;
+    String::from_utf8(data).map_err(|_| "Invalid encrypted export: the contents are not text".to_string())
+}
+
+/// The passphrase from `CC_TRACKER_PASSPHRASE`, or else typed on stdin;
+/// `confirm` asks for a typed one twice, for encrypting.
+pub fn passphrase(confirm: bool) -> Result<String, String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_VAR)
+        && !passphrase.is_empty()
+    {
+        return Ok(passphrase);
+    }
+    let mut lines = std::io::stdin().lock().lines();
+    let mut ask = |prompt: &str| -> Result<String, String> {
+        eprint!("{}: ", prompt);
+        std::io::stderr().flush().ok();
+        match lines.next() {
+            Some(Ok(line)) if !line.is_empty() => Ok(line),
+            _ => Err(format!("No passphrase given; type one or set {}", PASSPHRASE_VAR)),
+        }
+    };
+    let passphrase = ask("Passphrase")?;
+    if confirm && ask("Repeat passphrase")? != passphrase {
+        return Err("The passphrases differ".to_string());
+    }
+    Ok(passphrase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_export_round_trips_and_detects_tampering() {
+        let export = "{\"format\": \"cc-tracker-export\", \"cards\": []}";
+        let sealed = seal(export, "correct horse", 10, &[1; SALT_LEN], &[2; NONCE_LEN]);
+        assert!(is_encrypted(&sealed));
+        assert!(!is_encrypted(export));
+        assert!(!sealed.contains("cards"));
+        assert_eq!(decrypt(&sealed, "correct horse").unwrap(), export);
+        assert_eq!(decrypt(&sealed, "wrong horse").unwrap_err(), "Wrong passphrase, or the file has been changed");
+
+        let mut envelope: serde_json::Value = serde_json::from_str(&sealed).unwrap();
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../schemas/cc-tracker-encrypted-export.v1.schema.json")).unwrap();
+        let mut fields: Vec<&String> = envelope.as_object().unwrap().keys().collect();
+        let mut required: Vec<&str> = schema["required"].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+        fields.sort();
+        required.sort();
+        assert_eq!(fields, required);
+
+        envelope["iterations"] = 9.into();
+        let err = decrypt(&envelope.to_string(), "correct horse").unwrap_err();
+        assert_eq!(err, "Wrong passphrase, or the file has been changed");
+
+        // Refused before any key is derived
+        envelope["iterations"] = (MAX_ITERATIONS + 1).into();
+        let err = decrypt(&envelope.to_string(), "correct horse").unwrap_err();
+        assert_eq!(err, "Invalid encrypted export: iterations must be 1-6000000");
+    }
+}
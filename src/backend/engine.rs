@@ -0,0 +1,1318 @@
+use std::collections::BTreeMap;
+
+use crate::models::{
+    CapUnit, Card, CardRecommendation, CycleOverride, ExplainStep, MerchantPartnership, PlannedSpend, Promotion, ReconcileSuspect, RecommendOptions, RewardType, SplitPart,
+    SplitSuggestion,
+};
+
+// ── Dates and statement cycles ───────────────────────────────────
+
+/// Converts a (year, month, day) to days since Unix epoch using the
+/// algorithm from <http://howardhinnant.github.io/date_algorithms.html>
+pub fn ymd_to_days(year: i32, month: i32, day: i32) -> i32 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let m = month;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Returns the day of week for a date: 0=Monday, 1=Tuesday, ... 5=Saturday, 6=Sunday.
+fn day_of_week(year: i32, month: i32, day: i32) -> i32 {
+    let days = ymd_to_days(year, month, day);
+    // 1970-01-01 was a Thursday (day 3 in 0=Mon scheme)
+    ((days % 7) + 7 + 3) % 7
+}
+
+/// If the given date falls on a weekend, moves it to the previous Friday.
+/// Returns (year, month, day) adjusted.
+fn adjust_for_weekend(year: i32, month: i32, day: i32) -> (i32, i32, i32) {
+    let dow = day_of_week(year, month, day);
+    let shift = match dow {
+        5 => 1, // Saturday → Friday (subtract 1 day)
+        6 => 2, // Sunday → Friday (subtract 2 days)
+        _ => 0,
+    };
+    if shift == 0 {
+        return (year, month, day);
+    }
+    let days = ymd_to_days(year, month, day) - shift;
+    days_to_ymd(days)
+}
+
+/// Converts days since Unix epoch back to (year, month, day).
+pub fn days_to_ymd(days: i32) -> (i32, i32, i32) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = if m <= 2 { y + 1 } else { y };
+    (y, m, d)
+}
+
+/// Parses a YYYY-MM-DD string into (year, month, day). Dates are checked
+/// where they enter the tracker, so a part that does not parse (only
+/// possible in a hand-edited database) reads as 0 rather than panicking.
+pub fn parse_date(date: &str) -> (i32, i32, i32) {
+    let mut parts = date.splitn(3, '-').map(|part| part.parse().unwrap_or(0));
+    let mut next = || parts.next().unwrap_or(0);
+    (next(), next(), next())
+}
+
+/// Formats (year, month, day) as YYYY-MM-DD.
+pub fn format_date(year: i32, month: i32, day: i32) -> String {
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Moves a YYYY-MM-DD date by `days` (backward if negative).
+pub fn add_days(date: &str, days: i32) -> String {
+    let (y, m, d) = parse_date(date);
+    let (y, m, d) = days_to_ymd(ymd_to_days(y, m, d) + days);
+    format_date(y, m, d)
+}
+
+/// Days from one YYYY-MM-DD date to another (negative if `to` is earlier).
+pub fn days_between(from: &str, to: &str) -> i32 {
+    let days = |date: &str| {
+        let (y, m, d) = parse_date(date);
+        ymd_to_days(y, m, d)
+    };
+    days(to) - days(from)
+}
+
+/// Moves (year, month) forward by `n` months (or backward if negative).
+fn add_months(year: i32, month: i32, n: i32) -> (i32, i32) {
+    let index = year * 12 + (month - 1) + n;
+    (index.div_euclid(12), index.rem_euclid(12) + 1)
+}
+
+/// Returns the date miles credited on `credited_on` expire when they last
+/// `months`: the end of the month that many months later.
+pub fn expiry_date(credited_on: &str, months: i32) -> String {
+    let (year, month, _) = parse_date(credited_on);
+    let (y, m) = add_months(year, month, months + 1);
+    let (y, m, d) = days_to_ymd(ymd_to_days(y, m, 1) - 1);
+    format_date(y, m, d)
+}
+
+/// The first date on or after `date` falling on `day_of_month`, or on the
+/// last day of a month too short to have it.
+pub fn next_monthly_date(day_of_month: i32, date: &str) -> String {
+    let (year, month, day) = parse_date(date);
+    let on_day = |(y, m): (i32, i32)| {
+        let (next_year, next_month) = add_months(y, m, 1);
+        let last = days_to_ymd(ymd_to_days(next_year, next_month, 1) - 1).2;
+        (y, m, day_of_month.min(last))
+    };
+    let (y, m, d) = on_day((year, month));
+    if d >= day {
+        return format_date(y, m, d);
+    }
+    let (y, m, d) = on_day(add_months(year, month, 1));
+    format_date(y, m, d)
+}
+
+/// Returns the (year, month) whose renewal date started the statement cycle
+/// containing the reference date.
+fn cycle_month(renewal_day: i32, reference_date: &str) -> (i32, i32) {
+    let (year, month, _) = parse_date(reference_date);
+
+    // The weekend shift can pull next month's renewal back into this month
+    // (Sun Mar 1 → Fri Feb 27), so the latest renewal on or before the
+    // reference date may be next month's, this month's or last month's.
+    for offset in [1, 0] {
+        let (y, m) = add_months(year, month, offset);
+        if renewal_date_in(renewal_day, y, m).as_str() <= reference_date {
+            return (y, m);
+        }
+    }
+    add_months(year, month, -1)
+}
+
+/// Returns the weekend-adjusted renewal date for a given (year, month).
+fn renewal_date_in(renewal_day: i32, year: i32, month: i32) -> String {
+    let (y, m, d) = adjust_for_weekend(year, month, renewal_day);
+    format_date(y, m, d)
+}
+
+/// Returns the start date of the current statement cycle for a card,
+/// given its renewal day and a reference date (YYYY-MM-DD).
+/// If the renewal day falls on a weekend, it is shifted to the previous Friday.
+pub fn cycle_start_date(renewal_day: i32, reference_date: &str) -> String {
+    let (year, month) = cycle_month(renewal_day, reference_date);
+    let start = renewal_date_in(renewal_day, year, month);
+    tracing::debug!(renewal_day, reference_date, cycle_start = %start, "statement cycle window");
+    start
+}
+
+/// Returns the start date of the statement cycle `n` cycles after the one
+/// containing the reference date (n = 1 is the next statement).
+fn nth_cycle_start_date(renewal_day: i32, reference_date: &str, n: i32) -> String {
+    let (year, month) = cycle_month(renewal_day, reference_date);
+    let (year, month) = add_months(year, month, n);
+    renewal_date_in(renewal_day, year, month)
+}
+
+/// Returns the last day of the statement cycle containing the reference date.
+pub fn cycle_end_date(renewal_day: i32, reference_date: &str) -> String {
+    let next_start = nth_cycle_start_date(renewal_day, reference_date, 1);
+    let (y, m, d) = parse_date(&next_start);
+    let (y, m, d) = days_to_ymd(ymd_to_days(y, m, d) - 1);
+    format_date(y, m, d)
+}
+
+/// Returns the first and last day of the statement cycle containing the
+/// reference date, where `overrides` (cycles the bank moved, by the month
+/// they are for) take precedence: the cycle before a moved one ends the day
+/// before it starts, and the cycle after starts the day after it ends.
+pub fn cycle_bounds(
+    renewal_day: i32,
+    overrides: &BTreeMap<String, CycleOverride>,
+    reference_date: &str,
+) -> (String, String) {
+    if overrides.is_empty() {
+        return (cycle_start_date(renewal_day, reference_date), cycle_end_date(renewal_day, reference_date));
+    }
+    // Months are counted from year 0 so that neighbours are index ± 1
+    let month_of = |index: i32| (index.div_euclid(12), index.rem_euclid(12) + 1);
+    let moved = |index: i32| {
+        let (year, month) = month_of(index);
+        overrides.get(&format!("{:04}-{:02}", year, month))
+    };
+    let start_of = |index: i32| match (moved(index), moved(index - 1)) {
+        (Some(cycle), _) => cycle.start.clone(),
+        (None, Some(previous)) => add_days(&previous.end, 1),
+        (None, None) => {
+            let (year, month) = month_of(index);
+            renewal_date_in(renewal_day, year, month)
+        }
+    };
+    let end_of = |index: i32| match moved(index) {
+        Some(cycle) => cycle.end.clone(),
+        None => add_days(&start_of(index + 1), -1),
+    };
+
+    let (year, month, _) = parse_date(reference_date);
+    let index = year * 12 + month - 1;
+    for index in index - 2..=index + 2 {
+        let (start, end) = (start_of(index), end_of(index));
+        if start.as_str() <= reference_date && reference_date <= end.as_str() {
+            return (start, end);
+        }
+    }
+    // A date in a gap the overrides leave keeps its computed cycle
+    (cycle_start_date(renewal_day, reference_date), cycle_end_date(renewal_day, reference_date))
+}
+
+/// A card's statement cycle containing the reference date (see `cycle_bounds`).
+pub fn card_cycle(card: &Card, reference_date: &str) -> (String, String) {
+    cycle_bounds(card.statement_renewal_date, &card.cycle_overrides(), reference_date)
+}
+
+/// A card's statement cycle for a month: the one its renewal in that month
+/// starts, or the dates the bank set for the month (see `cycle_bounds`).
+pub fn statement_cycle(card: &Card, year: i32, month: i32) -> (String, String) {
+    let overrides = card.cycle_overrides();
+    let reference = match overrides.get(&format!("{:04}-{:02}", year, month)) {
+        Some(cycle) => cycle.start.clone(),
+        None => renewal_date_in(card.statement_renewal_date, year, month),
+    };
+    cycle_bounds(card.statement_renewal_date, &overrides, &reference)
+}
+
+/// The date a renewal on `renewal_date` was moved from, if the renewal day
+/// fell on a weekend and was brought forward to the Friday before.
+pub fn renewal_moved_from(renewal_day: i32, renewal_date: &str) -> Option<String> {
+    (1..=2).map(|shift| add_days(renewal_date, shift)).find(|date| {
+        let (y, m, d) = parse_date(date);
+        d == renewal_day && day_of_week(y, m, d) >= 5
+    })
+}
+
+/// Returns the date a transaction's miles are credited: the statement that
+/// closes its cycle, pushed back by the card's posting delay.
+pub fn miles_posting_date(
+    renewal_day: i32,
+    overrides: &BTreeMap<String, CycleOverride>,
+    posting_delay: i32,
+    txn_date: &str,
+) -> String {
+    if overrides.is_empty() {
+        return nth_cycle_start_date(renewal_day, txn_date, 1 + posting_delay);
+    }
+    let mut date = txn_date.to_string();
+    for _ in 0..=posting_delay {
+        date = add_days(&cycle_bounds(renewal_day, overrides, &date).1, 1);
+    }
+    date
+}
+
+// ── Earning ──────────────────────────────────────────────────────
+
+/// Calculates miles earned: floor(amount / block_size) * miles_per_dollar
+pub fn calculate_miles(amount: f64, block_size: f64, miles_per_dollar: f64) -> f64 {
+    (amount / block_size).floor() * miles_per_dollar
+}
+
+/// Cash back on a purchase at `percent`, to the cent
+pub fn cashback(amount: f64, percent: f64) -> f64 {
+    round_cents(amount * percent / 100.0)
+}
+
+/// The promotion with the highest rate that a card runs for the category
+/// on `date`.
+pub fn promotion_for<'a>(promotions: &'a [Promotion], card_id: i64, category: &str, date: &str) -> Option<&'a Promotion> {
+    promotions
+        .iter()
+        .filter(|p| {
+            p.card_id == card_id
+                && p.category.eq_ignore_ascii_case(category)
+                && p.start_date.as_str() <= date
+                && date <= p.end_date.as_str()
+        })
+        .max_by(|a, b| a.miles_per_dollar.total_cmp(&b.miles_per_dollar))
+}
+
+/// Amortizes a card's annual fee across a typical month of spend:
+/// `rate * spend / (spend + fee / 12)`. Falls back to the purchase amount as
+/// the monthly spend when the card has no history.
+pub fn fee_adjusted_rate(effective_rate: f64, annual_fee: Option<f64>, monthly_spend: f64, amount: f64) -> f64 {
+    let monthly_fee = annual_fee.unwrap_or(0.0) / 12.0;
+    let spend = if monthly_spend > 0.0 { monthly_spend } else { amount };
+    if spend + monthly_fee <= 0.0 {
+        return effective_rate;
+    }
+    effective_rate * spend / (spend + monthly_fee)
+}
+
+// ── Reward rules ─────────────────────────────────────────────────
+
+/// Spending already counted in the statement cycle a purchase falls in.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CycleState {
+    pub spent: f64,
+    /// Miles earned by that spending; only totalled for caps in miles
+    pub miles: f64,
+    /// The part of `spent` in categories that do not count towards the
+    /// minimum spend
+    pub excluded: f64,
+}
+
+/// A condition a purchase must meet to earn a card's rate. Rules only see
+/// in-memory inputs, so a new kind of rule needs no SQL and can be tested on
+/// its own.
+pub trait RewardRule {
+    /// None if a purchase of `amount` passes, otherwise why it does not.
+    fn check(&self, amount: f64, cycle: &CycleState) -> Option<String>;
+
+    /// The rule and its threshold, for logs.
+    fn describe(&self) -> String;
+}
+
+/// Miles stop accruing once the cycle's spend reaches `limit`.
+pub struct RewardCap {
+    pub limit: f64,
+}
+
+impl RewardCap {
+    /// Spend left before the cap is reached.
+    pub fn remaining(&self, cycle: &CycleState) -> f64 {
+        (self.limit - cycle.spent).max(0.0)
+    }
+}
+
+impl RewardRule for RewardCap {
+    fn check(&self, amount: f64, cycle: &CycleState) -> Option<String> {
+        let remaining = self.remaining(cycle);
+        (amount > remaining).then(|| format!("Exceeds reward limit (${:.2} remaining)", remaining))
+    }
+
+    fn describe(&self) -> String {
+        format!("reward cap ${:.2}", self.limit)
+    }
+}
+
+/// Miles stop accruing once the cycle's miles reach `limit`. Unlike a cap on
+/// spend, a purchase that crosses it still earns the miles left, so it only
+/// fails once nothing is left.
+pub struct MilesCap {
+    pub limit: f64,
+}
+
+impl MilesCap {
+    /// Miles left before the cap is reached.
+    pub fn remaining(&self, cycle: &CycleState) -> f64 {
+        (self.limit - cycle.miles).max(0.0)
+    }
+
+    /// The part of `miles` the cap still pays out.
+    pub fn clamp(&self, miles: f64, cycle: &CycleState) -> f64 {
+        miles.min(self.remaining(cycle))
+    }
+}
+
+impl RewardRule for MilesCap {
+    fn check(&self, _amount: f64, cycle: &CycleState) -> Option<String> {
+        (self.remaining(cycle) <= 0.0).then(|| format!("Reward limit reached ({:.0} miles this cycle)", self.limit))
+    }
+
+    fn describe(&self) -> String {
+        format!("reward cap {:.0} miles", self.limit)
+    }
+}
+
+/// The card's rate only applies once the cycle's spend reaches `minimum`.
+pub struct MinSpend {
+    pub minimum: f64,
+}
+
+impl MinSpend {
+    /// Spend counting towards the minimum.
+    pub fn qualifying(cycle: &CycleState) -> f64 {
+        cycle.spent - cycle.excluded
+    }
+}
+
+impl RewardRule for MinSpend {
+    fn check(&self, _amount: f64, cycle: &CycleState) -> Option<String> {
+        let qualifying = MinSpend::qualifying(cycle);
+        (qualifying < self.minimum)
+            .then(|| format!("Min spend not met (${:.2} more needed)", self.minimum - qualifying))
+    }
+
+    fn describe(&self) -> String {
+        format!("min spend ${:.2}", self.minimum)
+    }
+}
+
+/// The rules for a card with the given cap and minimum spend, in the order
+/// they are checked.
+pub fn card_rules(max_reward_limit: Option<f64>, cap_unit: CapUnit, min_spend: Option<f64>) -> Vec<Box<dyn RewardRule>> {
+    let mut rules: Vec<Box<dyn RewardRule>> = Vec::new();
+    match (max_reward_limit, cap_unit) {
+        (Some(limit), CapUnit::Dollars) => rules.push(Box::new(RewardCap { limit })),
+        (Some(limit), CapUnit::Miles) => rules.push(Box::new(MilesCap { limit })),
+        (None, _) => {}
+    }
+    if let Some(minimum) = min_spend {
+        rules.push(Box::new(MinSpend { minimum }));
+    }
+    rules
+}
+
+/// Checks a purchase against rules in order. Returns whether it earns the
+/// card's rate and, if not, the first failing rule's reason.
+pub fn evaluate(rules: &[Box<dyn RewardRule>], amount: f64, cycle: &CycleState) -> (bool, String) {
+    let failure = rules.iter().find_map(|rule| {
+        let failure = rule.check(amount, cycle);
+        tracing::debug!(
+            rule = %rule.describe(),
+            amount,
+            cycle_spent = cycle.spent,
+            outcome = failure.as_deref().unwrap_or("pass"),
+            "reward rule"
+        );
+        failure
+    });
+    match failure {
+        Some(reason) => (false, reason),
+        None => (true, "Eligible".to_string()),
+    }
+}
+
+/// A card matching a purchase's categories, with the rates that applied on
+/// the purchase date.
+#[derive(Debug, Clone)]
+pub struct CandidateCard {
+    pub id: i64,
+    pub name: String,
+    pub miles_per_dollar: f64,
+    pub block_size: f64,
+    pub max_reward_limit: Option<f64>,
+    pub cap_unit: CapUnit,
+    pub min_spend: Option<f64>,
+    /// Categories that do not count towards the minimum spend, lowercased
+    pub min_spend_exclusions: Vec<String>,
+    pub statement_renewal_date: i32,
+    pub annual_fee: Option<f64>,
+    pub reward_type: RewardType,
+    pub cashback_percent: Option<f64>,
+    /// Whether blocks are counted in a foreign purchase's own currency
+    /// ("per US$1") rather than the home currency
+    pub foreign_blocks: bool,
+    /// Miles per block on purchases in a foreign currency, if the card
+    /// earns a different rate on them
+    pub miles_per_dollar_foreign: Option<f64>,
+    /// Whether miles_per_dollar is the foreign rate, for a foreign purchase
+    pub foreign_rate: bool,
+    /// Foreign transaction fee in percent of a foreign purchase
+    pub fx_fee_percent: Option<f64>,
+    /// Whether the card excludes the purchase's category, earning nothing
+    /// on it
+    pub excluded_category: bool,
+    /// Whether the rates above are older ones the card has since changed
+    pub historical_rates: bool,
+    /// The card's partnership with the purchase's merchant, if any
+    pub partnership: Option<MerchantPartnership>,
+    /// The card's promotion for the purchase's category on its date, if any
+    pub promotion: Option<Promotion>,
+}
+
+impl CandidateCard {
+    /// Switches the card to its foreign rate, if it has one, for a purchase
+    /// charged in a foreign currency.
+    pub fn use_foreign_rate(&mut self) {
+        if let Some(rate) = self.miles_per_dollar_foreign {
+            self.miles_per_dollar = rate;
+            self.foreign_rate = true;
+        }
+    }
+
+    /// Miles per block for this purchase: the card's rate, or its
+    /// promotional rate while one runs, plus any partnership bonus at the
+    /// merchant; nothing in a category the card excludes.
+    pub fn earn_rate(&self) -> f64 {
+        if self.excluded_category {
+            return 0.0;
+        }
+        self.promotion.as_ref().map_or(self.miles_per_dollar, |p| p.miles_per_dollar)
+            + self.partnership.as_ref().map_or(0.0, |p| p.bonus_miles_per_dollar)
+    }
+
+    /// The amount this card counts blocks in: the foreign amount of a
+    /// foreign purchase if the card's blocks are in that currency, otherwise
+    /// the home amount.
+    pub fn block_amount(&self, purchase: &Purchase) -> f64 {
+        match purchase.foreign_amount {
+            Some(foreign_amount) if self.foreign_blocks => foreign_amount,
+            _ => purchase.amount,
+        }
+    }
+
+    /// The cap and minimum-spend rules, with the rates above.
+    pub fn rules(&self) -> Vec<Box<dyn RewardRule>> {
+        card_rules(self.max_reward_limit, self.cap_unit, self.min_spend)
+    }
+
+    /// `miles` cut down to what is left under a cap in miles.
+    pub fn capped_miles(&self, miles: f64, cycle: &CycleState) -> f64 {
+        match (self.max_reward_limit, self.cap_unit) {
+            (Some(limit), CapUnit::Miles) => miles.min(MilesCap { limit }.remaining(cycle)),
+            _ => miles,
+        }
+    }
+
+    /// Spend left before the cap is reached; for a cap in miles, the spend
+    /// that earns the miles left.
+    pub fn spend_left(&self, cycle: &CycleState) -> Option<f64> {
+        let limit = self.max_reward_limit?;
+        match self.cap_unit {
+            CapUnit::Dollars => Some(RewardCap { limit }.remaining(cycle)),
+            CapUnit::Miles if self.earn_rate() > 0.0 => {
+                Some(round_cents(MilesCap { limit }.remaining(cycle) / self.earn_rate() * self.block_size))
+            }
+            CapUnit::Miles => None,
+        }
+    }
+}
+
+/// Scores one candidate card for a purchase, given the spending already in
+/// its cycle. `monthly_spend` is only given when ranking by the
+/// fee-adjusted rate, and `reward_value` (dollars per mile, or per point
+/// for a points card) when ranking by dollar value. The
+/// effective rate is per home-currency dollar, so cards counting blocks in
+/// a foreign currency rank fairly against the rest, and a cap in miles
+/// that cuts the miles earned lowers it to match. On a foreign purchase
+/// the card's FX fee comes off the value, which then ranks by net value.
+/// A card excluding the purchase's category earns nothing on it and ranks
+/// with the ineligible cards.
+pub fn recommend(
+    card: &CandidateCard,
+    purchase: &Purchase,
+    cycle: &CycleState,
+    monthly_spend: Option<f64>,
+    reward_value: Option<f64>,
+) -> CardRecommendation {
+    let amount = purchase.amount;
+    let block_amount = card.block_amount(purchase);
+    let mut effective_rate = card.earn_rate() / card.block_size;
+    if block_amount != amount && amount > 0.0 {
+        effective_rate *= block_amount / amount;
+    }
+    let uncapped_miles = calculate_miles(block_amount, card.block_size, card.earn_rate());
+    let miles_earned = card.capped_miles(uncapped_miles, cycle);
+    if miles_earned < uncapped_miles && amount > 0.0 {
+        effective_rate = miles_earned / amount;
+    }
+    let cashback = card.cashback_percent.filter(|_| !card.excluded_category).map(|percent| cashback(amount, percent));
+    let fx_fee = purchase
+        .foreign
+        .then(|| round_cents(amount * card.fx_fee_percent.unwrap_or(0.0) / 100.0));
+    let (mut eligible, mut reason) = evaluate(&card.rules(), amount, cycle);
+    if eligible && miles_earned < uncapped_miles {
+        reason = format!("Eligible, capped at {:.0} miles", miles_earned);
+    }
+    if let Some(promotion) = &card.promotion {
+        reason = format!("{}; promotion {} until {}", reason, promotion.id, promotion.end_date);
+    }
+    if card.excluded_category {
+        eligible = false;
+        reason = format!("Excludes {}: earns nothing", purchase.category);
+    }
+    tracing::debug!(card = %card.name, amount, cycle_spent = cycle.spent, eligible, %reason, "recommendation");
+
+    CardRecommendation {
+        card_id: card.id,
+        card_name: card.name.clone(),
+        miles_per_dollar: card.earn_rate(),
+        block_size: card.block_size,
+        effective_rate,
+        net_rate: monthly_spend.map(|spend| fee_adjusted_rate(effective_rate, card.annual_fee, spend, amount)),
+        miles_earned,
+        cashback,
+        fx_fee,
+        value: reward_value
+            .map(|value| round_cents(miles_earned * value + cashback.unwrap_or(0.0) - fx_fee.unwrap_or(0.0))),
+        remaining_limit: card.spend_left(cycle),
+        eligible,
+        reason,
+        explanation: Vec::new(),
+    }
+}
+
+/// The purchase a recommendation is for
+pub struct Purchase<'a> {
+    pub category: &'a str,
+    pub payment_category: &'a str,
+    pub amount: f64,
+    pub merchant: Option<&'a str>,
+    /// The amount in the foreign currency it is charged in, for a foreign
+    /// purchase
+    pub foreign_amount: Option<f64>,
+    /// Charged in a foreign currency, so cards charge their FX fee on it
+    pub foreign: bool,
+}
+
+/// The statement cycle a purchase falls in and the spending already in it
+pub struct CycleWindow {
+    pub start: String,
+    pub end: String,
+    pub total: f64,
+    /// Miles earned in the cycle; only totalled for caps in miles
+    pub miles: f64,
+    /// Spend in the cycle that does not count towards the minimum spend
+    pub excluded: f64,
+}
+
+impl CycleWindow {
+    /// Adds the planned spending on `card` that falls in this cycle.
+    pub fn add_planned(&mut self, card: &CandidateCard, planned: &[PlannedSpend]) {
+        for p in planned.iter().filter(|p| p.card_id == card.id && p.date >= self.start && p.date <= self.end) {
+            self.total += p.amount;
+            self.miles += p.miles;
+            if card.min_spend_exclusions.contains(&p.category.to_lowercase()) {
+                self.excluded += p.amount;
+            }
+        }
+    }
+
+    pub fn state(&self) -> CycleState {
+        CycleState {
+            spent: self.total,
+            miles: self.miles,
+            excluded: self.excluded,
+        }
+    }
+}
+
+/// Every step behind `recommendation`, the result of `recommend` for this
+/// card and purchase, for `best-card --explain`.
+pub fn explain(
+    card: &CandidateCard,
+    purchase: &Purchase,
+    cycle: &CycleWindow,
+    monthly_spend: Option<f64>,
+    options: &RecommendOptions,
+    recommendation: &CardRecommendation,
+) -> Vec<ExplainStep> {
+    let step = |step: &str, detail: String| ExplainStep {
+        step: step.to_string(),
+        detail,
+    };
+    let state = cycle.state();
+    let mut steps = vec![
+        step(
+            "matched",
+            format!("category {}, payment category {}", purchase.category, purchase.payment_category),
+        ),
+        step(
+            "rate",
+            format!(
+                "{} mi per ${} block = {} mi/$ ({})",
+                card.miles_per_dollar,
+                card.block_size,
+                card.miles_per_dollar / card.block_size,
+                match (card.foreign_rate, card.historical_rates) {
+                    (true, _) => "foreign rate",
+                    (false, true) => "rates in effect then, changed since",
+                    (false, false) => "current rates",
+                }
+            ),
+        ),
+    ];
+    if let Some(promotion) = &card.promotion {
+        steps.push(step(
+            "promotion",
+            format!(
+                "{} mi per block on {} from {} to {} (promotion {}) in place of {} mi",
+                promotion.miles_per_dollar,
+                promotion.category,
+                promotion.start_date,
+                promotion.end_date,
+                promotion.id,
+                card.miles_per_dollar
+            ),
+        ));
+    }
+    if let (Some(merchant), Some(partnership)) = (purchase.merchant, &card.partnership) {
+        steps.push(step(
+            "merchant bonus",
+            format!(
+                "+{} mi per block at {} (partnership '{}') = {} mi/$",
+                partnership.bonus_miles_per_dollar,
+                merchant,
+                partnership.merchant_pattern,
+                recommendation.effective_rate
+            ),
+        ));
+    }
+    steps.extend([
+        step(
+            "block math",
+            if card.block_amount(purchase) != purchase.amount {
+                format!(
+                    "floor({:.2} foreign / {} foreign) = {} blocks x {} mi = {} mi",
+                    card.block_amount(purchase),
+                    card.block_size,
+                    (card.block_amount(purchase) / card.block_size).floor(),
+                    card.earn_rate(),
+                    recommendation.miles_earned
+                )
+            } else {
+                format!(
+                    "floor(${:.2} / ${}) = {} blocks x {} mi = {} mi",
+                    purchase.amount,
+                    card.block_size,
+                    (purchase.amount / card.block_size).floor(),
+                    card.earn_rate(),
+                    recommendation.miles_earned
+                )
+            },
+        ),
+        step(
+            "cycle window",
+            format!("{} to {} (renews on day {})", cycle.start, cycle.end, card.statement_renewal_date),
+        ),
+        step(
+            "cycle total",
+            format!(
+                "${:.2} in {}{}",
+                cycle.total,
+                if options.include_scheduled { "posted and scheduled spending" } else { "posted spending" },
+                match &options.known_by {
+                    Some(known_by) => format!(" known by {}", known_by),
+                    None => String::new(),
+                }
+            ),
+        ),
+    ]);
+
+    steps.push(step(
+        "reward cap",
+        match (card.max_reward_limit, card.cap_unit) {
+            (None, _) => "none".to_string(),
+            (Some(limit), CapUnit::Dollars) => {
+                let cap = RewardCap { limit };
+                match cap.check(purchase.amount, &state) {
+                    Some(reason) => format!("${:.2}: {}", limit, reason),
+                    None => format!("${:.2}, ${:.2} remaining: fits", limit, cap.remaining(&state)),
+                }
+            }
+            (Some(limit), CapUnit::Miles) => {
+                let cap = MilesCap { limit };
+                let remaining = cap.remaining(&state);
+                match cap.check(purchase.amount, &state) {
+                    Some(reason) => format!("{:.0} mi: {}", limit, reason),
+                    None if recommendation.miles_earned < remaining => {
+                        format!("{:.0} mi, {:.0} mi remaining: fits", limit, remaining)
+                    }
+                    None => format!("{:.0} mi, {:.0} mi remaining: earns only those", limit, remaining),
+                }
+            }
+        },
+    ));
+    steps.push(step(
+        "min spend",
+        match card.min_spend {
+            None => "none".to_string(),
+            Some(minimum) => {
+                let outcome = MinSpend { minimum }.check(purchase.amount, &state).unwrap_or_else(|| "met".to_string());
+                if cycle.excluded > 0.0 {
+                    format!(
+                        "${:.2}, ${:.2} spent (${:.2} more in {} not counted): {}",
+                        minimum,
+                        MinSpend::qualifying(&state),
+                        cycle.excluded,
+                        card.min_spend_exclusions.join(", "),
+                        outcome
+                    )
+                } else {
+                    format!("${:.2}, ${:.2} spent: {}", minimum, cycle.total, outcome)
+                }
+            }
+        },
+    ));
+
+    if let Some(cashback) = recommendation.cashback {
+        steps.push(step(
+            "cashback",
+            format!("{}% of ${:.2} = ${:.2}", card.cashback_percent.unwrap_or(0.0), purchase.amount, cashback),
+        ));
+    }
+    if let Some(fx_fee) = recommendation.fx_fee {
+        steps.push(step(
+            "FX fee",
+            format!("{}% of ${:.2} = ${:.2}", card.fx_fee_percent.unwrap_or(0.0), purchase.amount, fx_fee),
+        ));
+    }
+    if let (Some(reward_value), Some(value)) = (options.reward_value(card.reward_type), recommendation.value) {
+        steps.push(step(
+            "value",
+            format!(
+                "{} {} x ${} + ${:.2} cashback{} = ${:.2}",
+                recommendation.miles_earned,
+                if card.reward_type == RewardType::Points { "pts" } else { "mi" },
+                reward_value,
+                recommendation.cashback.unwrap_or(0.0),
+                recommendation.fx_fee.map_or_else(String::new, |fee| format!(" - ${:.2} FX fee", fee)),
+                value
+            ),
+        ));
+    }
+
+    if let (Some(spend), Some(net_rate)) = (monthly_spend, recommendation.net_rate) {
+        let monthly_fee = card.annual_fee.unwrap_or(0.0) / 12.0;
+        let spend = if spend > 0.0 { spend } else { purchase.amount };
+        steps.push(step(
+            "annual fee",
+            format!(
+                "{} mi/$ x ${:.2} / (${:.2} + ${:.2} fee per month) = {:.4} mi/$",
+                recommendation.effective_rate, spend, spend, monthly_fee, net_rate
+            ),
+        ));
+    }
+
+    steps.push(step(
+        "result",
+        if let (true, Some(value)) = (recommendation.eligible, recommendation.value) {
+            format!("eligible, ranked by value ${:.2}", value)
+        } else if recommendation.eligible {
+            format!(
+                "eligible, ranked by {} {:.4} mi/$",
+                if recommendation.net_rate.is_some() { "net rate" } else { "effective rate" },
+                recommendation.net_rate.unwrap_or(recommendation.effective_rate)
+            )
+        } else {
+            format!("not eligible ({}), ranked after eligible cards", recommendation.reason)
+        },
+    ));
+    steps
+}
+
+/// Suggests charging a purchase that would exceed a card's cap to that card
+/// only up to the cap, and the rest to another card, when the two together
+/// earn more than any single eligible card. The capped share is rounded down
+/// to whole blocks, since a partial block would earn nothing there.
+pub fn suggest_split(candidates: &[(CandidateCard, CycleWindow)], amount: f64) -> Option<SplitSuggestion> {
+    // Miles a share earns on a card, if it passes the card's rules
+    let earns = |card: &CandidateCard, cycle: &CycleWindow, share: f64| {
+        let (eligible, _) = evaluate(&card.rules(), share, &cycle.state());
+        eligible.then(|| card.capped_miles(calculate_miles(share, card.block_size, card.earn_rate()), &cycle.state()))
+    };
+    let part = |card: &CandidateCard, amount: f64, miles_earned: f64| SplitPart {
+        card_name: card.name.clone(),
+        amount,
+        miles_earned,
+    };
+
+    let mut best: Option<SplitSuggestion> = None;
+    for (first, first_cycle) in candidates {
+        let Some(remaining) = first.spend_left(&first_cycle.state()) else { continue };
+        if amount <= remaining {
+            continue;
+        }
+        let first_amount = round_cents((remaining / first.block_size).floor() * first.block_size);
+        if first_amount <= 0.0 {
+            continue;
+        }
+        let Some(first_miles) = earns(first, first_cycle, first_amount) else { continue };
+        let rest = round_cents(amount - first_amount);
+
+        for (second, second_cycle) in candidates.iter().filter(|(card, _)| card.id != first.id) {
+            let Some(second_miles) = earns(second, second_cycle, rest) else { continue };
+            let total_miles = first_miles + second_miles;
+            if best.as_ref().is_none_or(|b| total_miles > b.total_miles) {
+                best = Some(SplitSuggestion {
+                    parts: vec![part(first, first_amount, first_miles), part(second, rest, second_miles)],
+                    total_miles,
+                    single_card: None,
+                    single_card_miles: 0.0,
+                });
+            }
+        }
+    }
+
+    let mut split = best?;
+    let single = candidates
+        .iter()
+        .filter_map(|(card, cycle)| earns(card, cycle, amount).map(|miles| (card, miles)))
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((card, miles)) = single {
+        split.single_card = Some(card.name.clone());
+        split.single_card_miles = miles;
+    }
+    (split.total_miles > split.single_card_miles).then_some(split)
+}
+
+/// What a redemption got for each mile, in cents.
+pub fn cents_per_mile(miles: f64, cash_value: f64) -> f64 {
+    if miles <= 0.0 {
+        return 0.0;
+    }
+    round_cents(cash_value * 100.0 / miles)
+}
+
+pub fn round_cents(amount: f64) -> f64 {
+    (amount * 100.0).round() / 100.0
+}
+
+/// Sorts recommendations: eligible cards first, each group by rate DESC. The
+/// net rate replaces the headline rate when fee-adjusted, and the dollar
+/// value replaces both when valuing miles.
+pub fn rank_recommendations(results: &mut [CardRecommendation]) {
+    let rank_rate = |r: &CardRecommendation| r.value.or(r.net_rate).unwrap_or(r.effective_rate);
+    results.sort_by(|a, b| {
+        b.eligible.cmp(&a.eligible)
+            .then(rank_rate(b).total_cmp(&rank_rate(a)))
+    });
+}
+
+/// Maximum number of suspect transactions reported by a reconciliation.
+const MAX_RECONCILE_SUSPECTS: usize = 5;
+
+/// Picks the cycle's transactions most likely to explain a miles difference:
+/// ranked by how closely their own miles match the whole difference, so a
+/// single missing or double-counted transaction surfaces first.
+pub fn reconcile_suspects(mut transactions: Vec<ReconcileSuspect>, difference: f64) -> Vec<ReconcileSuspect> {
+    if difference == 0.0 {
+        return Vec::new();
+    }
+    for txn in &mut transactions {
+        txn.gap = (txn.miles_earned - difference.abs()).abs();
+    }
+    transactions.sort_by(|a, b| a.gap.total_cmp(&b.gap));
+    transactions.truncate(MAX_RECONCILE_SUSPECTS);
+    transactions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every date from `start` for `days` days.
+    fn dates_from(start: &str, days: i32) -> impl Iterator<Item = String> {
+        let (y, m, d) = parse_date(start);
+        let first = ymd_to_days(y, m, d);
+        (first..first + days).map(|n| {
+            let (y, m, d) = days_to_ymd(n);
+            format_date(y, m, d)
+        })
+    }
+
+    #[test]
+    fn test_parse_date_does_not_panic_on_a_malformed_date() {
+        assert_eq!(parse_date("2026-02-14"), (2026, 2, 14));
+        assert_eq!(parse_date("2026-02"), (2026, 2, 0));
+        assert_eq!(parse_date("14/02/2026"), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_next_monthly_date_clamps_to_short_months() {
+        assert_eq!(next_monthly_date(15, "2026-04-10"), "2026-04-15");
+        assert_eq!(next_monthly_date(15, "2026-04-15"), "2026-04-15");
+        assert_eq!(next_monthly_date(15, "2026-04-16"), "2026-05-15");
+        assert_eq!(next_monthly_date(31, "2026-02-01"), "2026-02-28");
+        assert_eq!(next_monthly_date(31, "2026-03-01"), "2026-03-31");
+        assert_eq!(next_monthly_date(5, "2026-12-20"), "2027-01-05");
+    }
+
+    #[test]
+    fn test_cycle_start_date_weekday() {
+        // 2026-02-15 is a Sunday, renewal day 15 → adjusted to Friday 13th
+        // Reference date Feb 19 (Thu) >= 13, so cycle started Feb 13
+        let start = cycle_start_date(15, "2026-02-19");
+        assert_eq!(start, "2026-02-13");
+    }
+
+    #[test]
+    fn test_cycle_start_date_saturday_adjustment() {
+        // 2026-02-14 is a Saturday, renewal day 14 → adjusted to Friday 13th
+        let start = cycle_start_date(14, "2026-02-19");
+        assert_eq!(start, "2026-02-13");
+    }
+
+    #[test]
+    fn test_cycle_start_date_sunday_adjustment() {
+        // 2026-03-01 is a Sunday, renewal day 1 → March's cycle starts Fri Feb 27,
+        // so Mar 5 is in the cycle that began in February
+        let start = cycle_start_date(1, "2026-03-05");
+        assert_eq!(start, "2026-02-27");
+        // Feb 1 is also a Sunday → Fri Jan 30, which starts February's cycle
+        assert_eq!(cycle_start_date(1, "2026-01-30"), "2026-01-30");
+        assert_eq!(cycle_end_date(1, "2026-01-29"), "2026-01-29");
+    }
+
+    #[test]
+    fn test_cycle_start_date_no_adjustment() {
+        // 2026-02-02 is a Monday, renewal day 2 → no adjustment needed
+        let start = cycle_start_date(2, "2026-02-19");
+        assert_eq!(start, "2026-02-02");
+    }
+
+    #[test]
+    fn test_day_of_week() {
+        // Known dates for verification:
+        // 2026-02-19 is a Thursday (3)
+        assert_eq!(day_of_week(2026, 2, 19), 3);
+        // 2026-02-14 is a Saturday (5)
+        assert_eq!(day_of_week(2026, 2, 14), 5);
+        // 2026-02-15 is a Sunday (6)
+        assert_eq!(day_of_week(2026, 2, 15), 6);
+        // 2026-02-13 is a Friday (4)
+        assert_eq!(day_of_week(2026, 2, 13), 4);
+        // 2026-02-16 is a Monday (0)
+        assert_eq!(day_of_week(2026, 2, 16), 0);
+    }
+
+    #[test]
+    fn test_miles_posting_date_no_delay() {
+        // Spend on Feb 5, renewal day 2 (Mon Feb 2) → statement closes Mar 2 (Mon)
+        assert_eq!(miles_posting_date(2, &BTreeMap::new(), 0, "2026-02-05"), "2026-03-02");
+    }
+
+    #[test]
+    fn test_miles_posting_date_with_delay() {
+        // One statement late: Apr 2, 2026 is a Thursday
+        assert_eq!(miles_posting_date(2, &BTreeMap::new(), 1, "2026-02-05"), "2026-04-02");
+        // Two statements late: May 2, 2026 is a Saturday → Fri May 1
+        assert_eq!(miles_posting_date(2, &BTreeMap::new(), 2, "2026-02-05"), "2026-05-01");
+    }
+
+    #[test]
+    fn test_cycle_end_date() {
+        // Cycle starting Mon Feb 2 ends the day before Mon Mar 2
+        assert_eq!(cycle_end_date(2, "2026-02-19"), "2026-03-01");
+    }
+
+    #[test]
+    fn test_cycle_bounds_with_override() {
+        // The bank closed March's statement on the 4th instead of Mon Mar 2
+        let moved = BTreeMap::from([(
+            "2026-03".to_string(),
+            CycleOverride {
+                start: "2026-03-04".to_string(),
+                end: "2026-04-01".to_string(),
+            },
+        )]);
+        let bounds = |date: &str| {
+            let (start, end) = cycle_bounds(2, &moved, date);
+            (start, end)
+        };
+        let days = |start: &str, end: &str| (start.to_string(), end.to_string());
+        // February's cycle runs on until the override starts
+        assert_eq!(bounds("2026-03-03"), days("2026-02-02", "2026-03-03"));
+        assert_eq!(bounds("2026-03-04"), days("2026-03-04", "2026-04-01"));
+        assert_eq!(bounds("2026-04-01"), days("2026-03-04", "2026-04-01"));
+        // April's starts the day after; May 2 is a Saturday, so it ends Apr 30
+        assert_eq!(bounds("2026-04-02"), days("2026-04-02", "2026-04-30"));
+        assert_eq!(cycle_bounds(2, &BTreeMap::new(), "2026-03-03"), days("2026-03-02", "2026-04-01"));
+        // Miles for February spend post at the moved statement
+        assert_eq!(miles_posting_date(2, &moved, 0, "2026-02-05"), "2026-03-04");
+        assert_eq!(miles_posting_date(2, &moved, 1, "2026-02-05"), "2026-04-02");
+    }
+
+    #[test]
+    fn test_fee_adjusted_rate() {
+        // $240 fee → $20/month; $180 monthly spend: 4.0 * 180 / 200 = 3.6
+        assert_eq!(fee_adjusted_rate(4.0, Some(240.0), 180.0, 10.0), 3.6);
+        // No fee leaves the rate unchanged
+        assert_eq!(fee_adjusted_rate(4.0, None, 180.0, 10.0), 4.0);
+        // No history: amortize against the purchase itself
+        assert_eq!(fee_adjusted_rate(2.0, Some(120.0), 0.0, 10.0), 1.0);
+    }
+
+    // ── Properties ───────────────────────────────────────────────
+
+    #[test]
+    fn prop_every_date_falls_in_its_cycle() {
+        for renewal_day in 1..=28 {
+            for date in dates_from("2025-12-01", 450) {
+                let start = cycle_start_date(renewal_day, &date);
+                let end = cycle_end_date(renewal_day, &date);
+                assert!(start <= date && date <= end, "{} not in {}..{} (day {})", date, start, end, renewal_day);
+
+                // Cycles start on weekdays and follow on without gaps
+                let (y, m, d) = parse_date(&start);
+                assert!(day_of_week(y, m, d) < 5);
+                let (y, m, d) = parse_date(&end);
+                let (y, m, d) = days_to_ymd(ymd_to_days(y, m, d) + 1);
+                assert_eq!(format_date(y, m, d), nth_cycle_start_date(renewal_day, &date, 1));
+            }
+        }
+    }
+
+    #[test]
+    fn prop_miles_never_decrease_with_amount() {
+        for block_size in [1.0, 5.0, 10.0] {
+            let mut previous = 0.0;
+            for cents in (0..200_000).step_by(37) {
+                let amount = cents as f64 / 100.0;
+                let miles = calculate_miles(amount, block_size, 4.0);
+                assert!(miles >= previous);
+                assert!(miles <= amount / block_size * 4.0);
+                assert!(miles > (amount / block_size - 1.0) * 4.0);
+                previous = miles;
+            }
+        }
+    }
+
+    #[test]
+    fn prop_cap_admits_exactly_the_remaining_headroom() {
+        let cap = RewardCap { limit: 1000.0 };
+        for spent in (0..1200).step_by(50) {
+            let cycle = CycleState { spent: spent as f64, ..CycleState::default() };
+            let remaining = cap.remaining(&cycle);
+            assert!(remaining >= 0.0);
+            for amount in (0..1200).step_by(25) {
+                let amount = amount as f64;
+                assert_eq!(cap.check(amount, &cycle).is_none(), amount <= remaining);
+            }
+        }
+    }
+
+    #[test]
+    fn test_evaluate_reports_first_failing_rule() {
+        let rules = card_rules(Some(500.0), CapUnit::Dollars, Some(800.0));
+        // Both fail: the cap is checked first
+        let (eligible, reason) = evaluate(&rules, 600.0, &CycleState { spent: 100.0, ..CycleState::default() });
+        assert!(!eligible);
+        assert!(reason.starts_with("Exceeds reward limit"));
+
+        let (eligible, reason) = evaluate(&rules, 50.0, &CycleState { spent: 100.0, ..CycleState::default() });
+        assert!(!eligible);
+        assert_eq!(reason, "Min spend not met ($700.00 more needed)");
+
+        let (eligible, reason) = evaluate(&card_rules(None, CapUnit::Dollars, None), 1e9, &CycleState::default());
+        assert!(eligible);
+        assert_eq!(reason, "Eligible");
+    }
+
+    #[test]
+    fn test_explain_shows_each_decision_step() {
+        let card = CandidateCard {
+            id: 1,
+            name: "Capped".to_string(),
+            miles_per_dollar: 4.0,
+            block_size: 5.0,
+            max_reward_limit: Some(100.0),
+            cap_unit: CapUnit::Dollars,
+            min_spend: Some(50.0),
+            min_spend_exclusions: Vec::new(),
+            statement_renewal_date: 1,
+            annual_fee: None,
+            reward_type: RewardType::Miles,
+            cashback_percent: None,
+            foreign_blocks: false,
+            miles_per_dollar_foreign: None,
+            foreign_rate: false,
+            fx_fee_percent: None,
+            excluded_category: false,
+            historical_rates: false,
+            partnership: None,
+            promotion: None,
+        };
+        let purchase = Purchase {
+            category: "dining",
+            payment_category: "online",
+            amount: 42.0,
+            merchant: None,
+            foreign_amount: None,
+            foreign: false,
+        };
+        let cycle = CycleWindow {
+            start: "2026-10-01".to_string(),
+            end: "2026-10-29".to_string(),
+            total: 80.0,
+            miles: 0.0,
+            excluded: 0.0,
+        };
+        let recommendation = recommend(&card, &purchase, &cycle.state(), None, None);
+        let steps = explain(&card, &purchase, &cycle, None, &RecommendOptions::default(), &recommendation);
+
+        let detail = |name: &str| steps.iter().find(|s| s.step == name).map(|s| s.detail.as_str()).unwrap();
+        assert_eq!(detail("block math"), "floor($42.00 / $5) = 8 blocks x 4 mi = 32 mi");
+        assert_eq!(detail("cycle window"), "2026-10-01 to 2026-10-29 (renews on day 1)");
+        assert_eq!(detail("cycle total"), "$80.00 in posted spending");
+        assert_eq!(detail("reward cap"), "$100.00: Exceeds reward limit ($20.00 remaining)");
+        assert_eq!(detail("min spend"), "$50.00, $80.00 spent: met");
+        assert!(detail("result").starts_with("not eligible"));
+        assert!(!steps.iter().any(|s| s.step == "annual fee"));
+    }
+
+    fn candidate(id: i64, miles_per_dollar: f64, max_reward_limit: Option<f64>, spent: f64) -> (CandidateCard, CycleWindow) {
+        let card = CandidateCard {
+            id,
+            name: format!("Card {}", id),
+            miles_per_dollar,
+            block_size: 1.0,
+            max_reward_limit,
+            cap_unit: CapUnit::Dollars,
+            min_spend: None,
+            min_spend_exclusions: Vec::new(),
+            statement_renewal_date: 1,
+            annual_fee: None,
+            reward_type: RewardType::Miles,
+            cashback_percent: None,
+            foreign_blocks: false,
+            miles_per_dollar_foreign: None,
+            foreign_rate: false,
+            fx_fee_percent: None,
+            excluded_category: false,
+            historical_rates: false,
+            partnership: None,
+            promotion: None,
+        };
+        let cycle = CycleWindow {
+            start: "2026-10-01".to_string(),
+            end: "2026-10-29".to_string(),
+            total: spent,
+            miles: 0.0,
+            excluded: 0.0,
+        };
+        (card, cycle)
+    }
+
+    #[test]
+    fn test_split_fills_cap_then_uses_next_card() {
+        let candidates = vec![candidate(1, 4.0, Some(100.0), 80.0), candidate(2, 1.2, None, 0.0)];
+        let split = suggest_split(&candidates, 60.5).unwrap();
+        assert_eq!(
+            split.parts,
+            vec![
+                SplitPart { card_name: "Card 1".to_string(), amount: 20.0, miles_earned: 80.0 },
+                SplitPart { card_name: "Card 2".to_string(), amount: 40.5, miles_earned: 48.0 },
+            ]
+        );
+        assert_eq!(split.total_miles, 128.0);
+        assert_eq!(split.single_card.as_deref(), Some("Card 2"));
+        assert_eq!(split.single_card_miles, 72.0);
+    }
+
+    #[test]
+    fn test_no_split_when_one_card_does_better() {
+        // The capped card earns less per dollar than the uncapped one
+        let candidates = vec![candidate(1, 1.0, Some(100.0), 80.0), candidate(2, 1.2, None, 0.0)];
+        assert_eq!(suggest_split(&candidates, 60.0), None);
+        // Nothing is exceeded
+        let candidates = vec![candidate(1, 4.0, Some(100.0), 0.0), candidate(2, 1.2, None, 0.0)];
+        assert_eq!(suggest_split(&candidates, 60.0), None);
+    }
+
+    #[test]
+    fn test_partnership_bonus_adds_to_rate() {
+        let (mut card, cycle) = candidate(1, 4.0, None, 0.0);
+        card.block_size = 5.0;
+        card.partnership = Some(MerchantPartnership {
+            id: 1,
+            card_id: 1,
+            merchant_pattern: "fairprice".to_string(),
+            bonus_miles_per_dollar: 6.0,
+        });
+        let purchase = Purchase {
+            category: "groceries",
+            payment_category: "contactless",
+            amount: 42.0,
+            merchant: Some("FairPrice Xtra"),
+            foreign_amount: None,
+            foreign: false,
+        };
+        let recommendation = recommend(&card, &purchase, &cycle.state(), None, None);
+        assert_eq!(recommendation.effective_rate, 2.0);
+        assert_eq!(recommendation.miles_earned, 80.0);
+
+        let steps = explain(&card, &purchase, &cycle, None, &RecommendOptions::default(), &recommendation);
+        let detail = |name: &str| steps.iter().find(|s| s.step == name).map(|s| s.detail.as_str()).unwrap();
+        assert_eq!(detail("rate"), "4 mi per $5 block = 0.8 mi/$ (current rates)");
+        assert_eq!(detail("merchant bonus"), "+6 mi per block at FairPrice Xtra (partnership 'fairprice') = 2 mi/$");
+        assert_eq!(detail("block math"), "floor($42.00 / $5) = 8 blocks x 10 mi = 80 mi");
+    }
+
+    #[test]
+    fn test_promotion_replaces_rate_while_it_runs() {
+        let promotion = |id: i64, category: &str, rate: f64, start: &str, end: &str| Promotion {
+            id,
+            card_id: 1,
+            category: category.to_string(),
+            miles_per_dollar: rate,
+            start_date: start.to_string(),
+            end_date: end.to_string(),
+        };
+        let promotions = vec![
+            promotion(1, "groceries", 5.0, "2026-04-01", "2026-06-30"),
+            promotion(2, "Groceries", 6.0, "2026-05-01", "2026-05-31"),
+            promotion(3, "dining", 8.0, "2026-04-01", "2026-06-30"),
+        ];
+        assert_eq!(promotion_for(&promotions, 1, "groceries", "2026-04-01").map(|p| p.id), Some(1));
+        assert_eq!(promotion_for(&promotions, 1, "groceries", "2026-05-15").map(|p| p.id), Some(2));
+        assert_eq!(promotion_for(&promotions, 1, "groceries", "2026-06-30").map(|p| p.id), Some(1));
+        assert!(promotion_for(&promotions, 1, "groceries", "2026-07-01").is_none());
+        assert!(promotion_for(&promotions, 2, "groceries", "2026-05-15").is_none());
+
+        let (mut card, cycle) = candidate(1, 1.0, None, 0.0);
+        card.promotion = promotions.into_iter().next();
+        let purchase = Purchase {
+            category: "groceries",
+            payment_category: "contactless",
+            amount: 40.0,
+            merchant: None,
+            foreign_amount: None,
+            foreign: false,
+        };
+        let recommendation = recommend(&card, &purchase, &cycle.state(), None, None);
+        assert_eq!(recommendation.miles_earned, 200.0);
+        assert_eq!(recommendation.reason, "Eligible; promotion 1 until 2026-06-30");
+        let steps = explain(&card, &purchase, &cycle, None, &RecommendOptions::default(), &recommendation);
+        let detail = |name: &str| steps.iter().find(|s| s.step == name).map(|s| s.detail.as_str()).unwrap();
+        assert_eq!(
+            detail("promotion"),
+            "5 mi per block on groceries from 2026-04-01 to 2026-06-30 (promotion 1) in place of 1 mi"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Why a command or a library call failed, with the exit code the CLI
+//! reports it with.
+
+use thiserror::Error;
+
+/// A failure: of the database itself, or a refusal of what was asked for.
+/// Displays as a message fit to show the user as is.
+#[derive(Debug, Error)]
+pub enum CcTrackerError {
+    /// The SQLite database could not be read or written
+    #[error("Database error: {0}")]
+    Database(rusqlite::Error),
+    /// The Postgres server could not be reached or refused a query, as
+    /// `pg::describe_error` puts it
+    #[error("Database error: {0}")]
+    Postgres(String),
+    /// An ID names no record, e.g. `what` "card" for an unknown card ID
+    #[error("No {what} found with ID {id}")]
+    NotFound { what: &'static str, id: i64 },
+    /// A name matches no record, e.g. `what` "card" for an unknown card
+    /// name, with the closest known name if one looks like it was meant
+    #[error("No {what} named '{name}'{}", did_you_mean(.suggestion))]
+    UnknownName { what: &'static str, name: String, suggestion: Option<String> },
+    /// A date that is not a real day in YYYY-MM-DD (or the configured
+    /// layout), with the layouts accepted
+    #[error("{0}")]
+    InvalidDate(String),
+    /// Any other input that was refused, with the reason
+    #[error("{0}")]
+    Invalid(String),
+}
+
+impl CcTrackerError {
+    /// Builds the error for an unknown card ID.
+    pub fn card_not_found(id: i64) -> CcTrackerError {
+        CcTrackerError::NotFound { what: "card", id }
+    }
+
+    /// Builds the error for a write into a closed statement cycle.
+    pub fn cycle_closed() -> CcTrackerError {
+        CcTrackerError::Invalid("The statement cycle is closed; reopen it first with close-cycle --reopen".to_string())
+    }
+
+    /// The process exit code for the failure. Codes 2 and 3 are taken by
+    /// `best-card`'s outcomes, so scripts can tell every case apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CcTrackerError::Database(_) | CcTrackerError::Postgres(_) => 1,
+            CcTrackerError::Invalid(_) => 4,
+            CcTrackerError::NotFound { .. } | CcTrackerError::UnknownName { .. } => 5,
+            CcTrackerError::InvalidDate(_) => 6,
+        }
+    }
+}
+
+/// What the triggers guarding closed statement cycles abort with, in
+/// SQLite and Postgres alike.
+pub const CYCLE_CLOSED: &str = "statement cycle is closed; reopen it first";
+
+/// A write refused because it touches a closed statement cycle is a
+/// refusal of what was asked for, not a database failure.
+impl From<rusqlite::Error> for CcTrackerError {
+    fn from(error: rusqlite::Error) -> CcTrackerError {
+        match &error {
+            rusqlite::Error::SqliteFailure(_, Some(message)) if message == CYCLE_CLOSED => {
+                CcTrackerError::cycle_closed()
+            }
+            _ => CcTrackerError::Database(error),
+        }
+    }
+}
+
+fn did_you_mean(suggestion: &Option<String>) -> String {
+    suggestion.as_ref().map_or_else(String::new, |name| format!("; did you mean '{}'?", name))
+}
+
+/// Shorthand for results failing with a [`CcTrackerError`].
+pub type Result<T> = std::result::Result<T, CcTrackerError>;
@@ -0,0 +1,1379 @@
+//! Versioned JSON files: full-database exports, shared cards and import
+//! previews; plus spending as CSV for spreadsheets.
+//!
+//! Every JSON file the tracker writes carries `format` and `format_version`,
+//! and its layout is published as a JSON Schema under `schemas/` for other
+//! tools to read and write. Bump the version, and add a schema, whenever a
+//! layout changes in a way older readers would misread.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+
+use crate::db::{today, STATUS_POSTED, STATUS_SCHEDULED};
+use crate::error;
+use crate::import::parse_date_with_format;
+use crate::engine::cents_per_mile;
+use crate::models::{
+    CapUnit, Card, CardRates, ClosedCycle, CycleOverride, FxRate, NewCard, PatternSyntax, ProgramKind, RateChange, Redemption,
+    ReviewItem, RewardType, Spending, CHARGE_KINDS,
+};
+use crate::store::Store;
+
+pub const EXPORT_FORMAT: &str = "cc-tracker-export";
+/// Described by `schemas/cc-tracker-export.v2.schema.json`. Version 1
+/// files (`cc-tracker-export.v1.schema.json`) are still read: the sections
+/// and fields version 2 added are all optional.
+pub const EXPORT_VERSION: u32 = 2;
+
+pub const CARD_FORMAT: &str = "cc-tracker-card";
+/// Described by `schemas/cc-tracker-card.v2.schema.json`; version 1 files
+/// are still read, as with exports
+pub const CARD_VERSION: u32 = 2;
+
+pub const PREVIEW_FORMAT: &str = "cc-tracker-import-preview";
+/// Described by `schemas/cc-tracker-import-preview.v1.schema.json`
+pub const PREVIEW_VERSION: u32 = 1;
+
+/// Serializes `data` as pretty JSON with the format stamp fields first.
+pub fn to_versioned_json<T: Serialize>(format: &str, format_version: u32, data: &T) -> String {
+    #[derive(Serialize)]
+    struct Versioned<'a, T> {
+        format: &'a str,
+        format_version: u32,
+        #[serde(flatten)]
+        data: &'a T,
+    }
+    serde_json::to_string_pretty(&Versioned { format, format_version, data }).expect("data serializes to JSON")
+}
+
+/// Columns of `spending_csv`
+const SPENDING_CSV_HEADER: [&str; 11] = [
+    "id",
+    "date",
+    "card_id",
+    "card_name",
+    "amount",
+    "category",
+    "miles_earned",
+    "status",
+    "description",
+    "payment_category",
+    "tag",
+];
+
+/// Spending as CSV with a header row, one transaction per line with its
+/// card's name; missing values are left empty.
+pub fn spending_csv(spending: &[Spending], cards: &[Card]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(SPENDING_CSV_HEADER).expect("CSV writes to memory");
+    for s in spending {
+        let card_name = cards.iter().find(|c| c.id == s.card_id).map_or("", |c| c.name.as_str());
+        writer
+            .write_record([
+                s.id.to_string().as_str(),
+                &s.date,
+                &s.card_id.to_string(),
+                card_name,
+                &s.amount.to_string(),
+                &s.category,
+                &s.miles_earned.to_string(),
+                &s.status,
+                s.description.as_deref().unwrap_or_default(),
+                s.payment_category.as_deref().unwrap_or_default(),
+                s.tag.as_deref().unwrap_or_default(),
+            ])
+            .expect("CSV writes to memory");
+    }
+    String::from_utf8(writer.into_inner().expect("CSV writes to memory")).expect("CSV is UTF-8")
+}
+
+/// A whole tracker database. Cards, programs and miles ledger entries are
+/// referenced by their `id` within the file; restoring assigns new IDs.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    pub format: String,
+    pub format_version: u32,
+    /// YYYY-MM-DD
+    pub exported_on: String,
+    pub cards: Vec<ExportCard>,
+    pub spending: Vec<ExportSpending>,
+    pub payments: Vec<ExportPayment>,
+    pub charges: Vec<ExportCharge>,
+    // The sections below are absent from exports written before they were
+    // included
+    #[serde(default)]
+    pub rate_history: Vec<ExportRateChange>,
+    #[serde(default)]
+    pub closed_cycles: Vec<ExportClosedCycle>,
+    #[serde(default)]
+    pub review_queue: Vec<ExportReviewItem>,
+    #[serde(default)]
+    pub partnerships: Vec<ExportPartnership>,
+    #[serde(default)]
+    pub promotions: Vec<ExportPromotion>,
+    #[serde(default)]
+    pub recurring: Vec<ExportRecurring>,
+    #[serde(default)]
+    pub merchant_rules: Vec<ExportMerchantRule>,
+    #[serde(default)]
+    pub programs: Vec<ExportProgram>,
+    #[serde(default)]
+    pub program_transfers: Vec<ExportProgramTransfer>,
+    #[serde(default)]
+    pub miles_ledger: Vec<ExportMilesEntry>,
+    #[serde(default)]
+    pub redemptions: Vec<ExportRedemption>,
+    #[serde(default)]
+    pub fx_rates: Vec<ExportFxRate>,
+    #[serde(default)]
+    pub categories: Vec<ExportCategory>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportCard {
+    pub id: i64,
+    pub name: String,
+    pub categories: Vec<String>,
+    pub payment_categories: Vec<String>,
+    pub miles_per_dollar: f64,
+    pub miles_per_dollar_foreign: Option<f64>,
+    pub block_size: f64,
+    pub statement_renewal_date: i32,
+    pub max_reward_limit: Option<f64>,
+    pub min_spend: Option<f64>,
+    pub posting_delay: i32,
+    pub bank_time_offset_minutes: i32,
+    pub cutoff_hour: i32,
+    pub annual_fee: Option<f64>,
+    /// Absent from exports written before fee posting months
+    #[serde(default)]
+    pub fee_posting_month: Option<i32>,
+    pub starting_balance: f64,
+    pub balance_alert: Option<f64>,
+    /// Absent from exports written before cards had a reward type
+    #[serde(default)]
+    pub reward_type: RewardType,
+    /// Absent from exports written before cashback cards
+    #[serde(default)]
+    pub cashback_percent: Option<f64>,
+    /// Absent from exports written before foreign-currency blocks
+    #[serde(default)]
+    pub foreign_blocks: bool,
+    /// Absent from exports written before caps had a unit
+    #[serde(default)]
+    pub cap_unit: CapUnit,
+    /// Absent from exports written before min-spend exclusions
+    #[serde(default)]
+    pub min_spend_exclusions: Vec<String>,
+    /// Absent from exports written before loyalty programs
+    #[serde(default)]
+    pub program: Option<String>,
+    /// Absent from exports written before miles expiry
+    #[serde(default)]
+    pub miles_expiry_months: Option<i32>,
+    /// Absent from exports written before foreign transaction fees
+    #[serde(default)]
+    pub fx_fee_percent: Option<f64>,
+    /// Absent from exports written before category exclusions
+    #[serde(default)]
+    pub excluded_categories: Vec<String>,
+    /// Absent from exports written before payment due dates
+    #[serde(default)]
+    pub payment_due_days: Option<i32>,
+    /// Absent from exports written before cards could be closed
+    #[serde(default)]
+    pub closed_on: Option<String>,
+    /// Absent from exports written before cycle overrides
+    #[serde(default)]
+    pub cycle_overrides: BTreeMap<String, CycleOverride>,
+}
+
+impl ExportCard {
+    /// The card's settings, as restoring adds it
+    pub fn new_card(&self) -> NewCard {
+        NewCard {
+            name: self.name.clone(),
+            categories: self.categories.clone(),
+            payment_categories: self.payment_categories.clone(),
+            miles_per_dollar: self.miles_per_dollar,
+            miles_per_dollar_foreign: self.miles_per_dollar_foreign,
+            block_size: self.block_size,
+            statement_renewal_date: self.statement_renewal_date,
+            max_reward_limit: self.max_reward_limit,
+            min_spend: self.min_spend,
+            posting_delay: self.posting_delay,
+            bank_time_offset_minutes: self.bank_time_offset_minutes,
+            cutoff_hour: self.cutoff_hour,
+            annual_fee: self.annual_fee,
+            fee_posting_month: self.fee_posting_month,
+            starting_balance: self.starting_balance,
+            balance_alert: self.balance_alert,
+            reward_type: self.reward_type,
+            cashback_percent: self.cashback_percent,
+            foreign_blocks: self.foreign_blocks,
+            cap_unit: self.cap_unit,
+            min_spend_exclusions: self.min_spend_exclusions.clone(),
+            program: self.program.clone(),
+            miles_expiry_months: self.miles_expiry_months,
+            fx_fee_percent: self.fx_fee_percent,
+            excluded_categories: self.excluded_categories.clone(),
+            payment_due_days: self.payment_due_days,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportSpending {
+    pub card_id: i64,
+    pub date: String,
+    pub amount: f64,
+    pub category: String,
+    /// Kept as recorded, since the card's rates may have changed since
+    pub miles_earned: f64,
+    pub status: String,
+    pub description: Option<String>,
+    pub payment_category: Option<String>,
+    pub tag: Option<String>,
+    /// Absent from exports written before foreign-currency spending
+    #[serde(default)]
+    pub foreign: bool,
+    /// Absent from exports written before multi-currency spending
+    #[serde(default)]
+    pub original_amount: Option<f64>,
+    #[serde(default)]
+    pub original_currency: Option<String>,
+    /// Absent from exports written before entry dates were exported
+    #[serde(default)]
+    pub recorded_on: Option<String>,
+    /// Kept as recorded, like miles; absent from exports written before
+    /// cash back was stored
+    #[serde(default)]
+    pub cashback: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportPayment {
+    pub card_id: i64,
+    pub date: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportCharge {
+    pub card_id: i64,
+    pub kind: String,
+    pub date: String,
+    pub amount: f64,
+}
+
+/// Rates a card had until `valid_until`, when they were changed
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportRateChange {
+    pub card_id: i64,
+    pub valid_until: String,
+    pub miles_per_dollar: f64,
+    pub block_size: f64,
+    pub max_reward_limit: Option<f64>,
+    pub min_spend: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportClosedCycle {
+    pub card_id: i64,
+    pub cycle_start: String,
+    pub cycle_end: String,
+    pub total_spend: f64,
+    pub total_miles: f64,
+    pub transaction_count: i64,
+    pub closed_on: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportReviewItem {
+    pub card_id: i64,
+    pub date: String,
+    pub amount: f64,
+    pub description: Option<String>,
+    pub suggested_category: Option<String>,
+    pub confidence: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportPartnership {
+    pub card_id: i64,
+    pub merchant_pattern: String,
+    pub bonus_miles_per_dollar: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportPromotion {
+    pub card_id: i64,
+    pub category: String,
+    pub miles_per_dollar: f64,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportRecurring {
+    pub card_id: i64,
+    pub amount: f64,
+    pub category: String,
+    pub day_of_month: i32,
+    pub merchant: Option<String>,
+    pub next_date: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportMerchantRule {
+    pub pattern: String,
+    pub syntax: PatternSyntax,
+    pub category: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportProgram {
+    pub id: i64,
+    pub name: String,
+    pub kind: ProgramKind,
+    pub expiry_months: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportProgramTransfer {
+    pub from_program_id: i64,
+    pub to_program_id: i64,
+    pub ratio: f64,
+}
+
+/// A miles adjustment or redemption; `card_id` is None for miles pooled in
+/// `program`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportMilesEntry {
+    pub id: i64,
+    pub card_id: Option<i64>,
+    pub program: Option<String>,
+    pub kind: String,
+    pub miles: f64,
+    pub date: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportRedemption {
+    pub card_id: Option<i64>,
+    pub program: Option<String>,
+    pub date: String,
+    pub miles: f64,
+    pub cash_value: f64,
+    pub description: Option<String>,
+    /// The `miles_ledger` entry taking the miles off
+    pub miles_entry_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportFxRate {
+    pub currency: String,
+    pub rate: f64,
+    pub updated_on: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportCategory {
+    pub name: String,
+    pub monthly_budget: Option<f64>,
+}
+
+/// Reads everything in a store into an export, oldest records first.
+pub fn export(store: &(impl Store + ?Sized)) -> error::Result<Export> {
+    let cards = store
+        .list_cards()?
+        .into_iter()
+        .map(|card| ExportCard {
+            cycle_overrides: card.cycle_overrides(),
+            id: card.id,
+            name: card.name,
+            categories: serde_json::from_str(&card.categories).unwrap_or_default(),
+            payment_categories: serde_json::from_str(&card.payment_categories).unwrap_or_default(),
+            miles_per_dollar: card.miles_per_dollar,
+            miles_per_dollar_foreign: card.miles_per_dollar_foreign,
+            block_size: card.block_size,
+            statement_renewal_date: card.statement_renewal_date,
+            max_reward_limit: card.max_reward_limit,
+            min_spend: card.min_spend,
+            posting_delay: card.posting_delay,
+            bank_time_offset_minutes: card.bank_time_offset_minutes,
+            cutoff_hour: card.cutoff_hour,
+            annual_fee: card.annual_fee,
+            fee_posting_month: card.fee_posting_month,
+            starting_balance: card.starting_balance,
+            balance_alert: card.balance_alert,
+            reward_type: card.reward_type,
+            cashback_percent: card.cashback_percent,
+            foreign_blocks: card.foreign_blocks,
+            cap_unit: card.cap_unit,
+            min_spend_exclusions: serde_json::from_str(&card.min_spend_exclusions).unwrap_or_default(),
+            program: card.program.clone(),
+            miles_expiry_months: card.miles_expiry_months,
+            fx_fee_percent: card.fx_fee_percent,
+            excluded_categories: serde_json::from_str(&card.excluded_categories).unwrap_or_default(),
+            payment_due_days: card.payment_due_days,
+            closed_on: card.closed_on.clone(),
+        })
+        .collect();
+    let spending = store
+        .list_spending(None, None)?
+        .into_iter()
+        .rev()
+        .map(|s| ExportSpending {
+            card_id: s.card_id,
+            date: s.date,
+            amount: s.amount,
+            category: s.category,
+            miles_earned: s.miles_earned,
+            status: s.status,
+            description: s.description,
+            payment_category: s.payment_category,
+            tag: s.tag,
+            foreign: s.foreign,
+            original_amount: s.original_amount,
+            original_currency: s.original_currency,
+            recorded_on: s.recorded_on,
+            cashback: s.cashback,
+        })
+        .collect();
+    let payments = store
+        .list_payments(None)?
+        .into_iter()
+        .rev()
+        .map(|p| ExportPayment {
+            card_id: p.card_id,
+            date: p.date,
+            amount: p.amount,
+        })
+        .collect();
+    let charges = store
+        .list_charges(None)?
+        .into_iter()
+        .rev()
+        .map(|c| ExportCharge {
+            card_id: c.card_id,
+            kind: c.kind,
+            date: c.date,
+            amount: c.amount,
+        })
+        .collect();
+    let rate_history = store
+        .list_rate_history()?
+        .into_iter()
+        .map(|h| ExportRateChange {
+            card_id: h.card_id,
+            valid_until: h.valid_until,
+            miles_per_dollar: h.rates.miles_per_dollar,
+            block_size: h.rates.block_size,
+            max_reward_limit: h.rates.max_reward_limit,
+            min_spend: h.rates.min_spend,
+        })
+        .collect();
+    let closed_cycles = store
+        .list_closed_cycles(None)?
+        .into_iter()
+        .map(|c| ExportClosedCycle {
+            card_id: c.card_id,
+            cycle_start: c.cycle_start,
+            cycle_end: c.cycle_end,
+            total_spend: c.total_spend,
+            total_miles: c.total_miles,
+            transaction_count: c.transaction_count,
+            closed_on: c.closed_on,
+        })
+        .collect();
+    let review_queue = store
+        .list_review_queue(None)?
+        .into_iter()
+        .map(|r| ExportReviewItem {
+            card_id: r.card_id,
+            date: r.date,
+            amount: r.amount,
+            description: r.description,
+            suggested_category: r.suggested_category,
+            confidence: r.confidence,
+        })
+        .collect();
+    let partnerships = store
+        .list_partnerships(None)?
+        .into_iter()
+        .map(|p| ExportPartnership {
+            card_id: p.card_id,
+            merchant_pattern: p.merchant_pattern,
+            bonus_miles_per_dollar: p.bonus_miles_per_dollar,
+        })
+        .collect();
+    let promotions = store
+        .list_promotions(None)?
+        .into_iter()
+        .map(|p| ExportPromotion {
+            card_id: p.card_id,
+            category: p.category,
+            miles_per_dollar: p.miles_per_dollar,
+            start_date: p.start_date,
+            end_date: p.end_date,
+        })
+        .collect();
+    let recurring = store
+        .list_recurring(None)?
+        .into_iter()
+        .map(|r| ExportRecurring {
+            card_id: r.card_id,
+            amount: r.amount,
+            category: r.category,
+            day_of_month: r.day_of_month,
+            merchant: r.merchant,
+            next_date: r.next_date,
+        })
+        .collect();
+    let merchant_rules = store
+        .list_merchant_rules()?
+        .into_iter()
+        .map(|r| ExportMerchantRule {
+            pattern: r.pattern,
+            syntax: r.syntax,
+            category: r.category,
+        })
+        .collect();
+    let programs = store
+        .list_programs()?
+        .into_iter()
+        .map(|p| ExportProgram {
+            id: p.id,
+            name: p.name,
+            kind: p.kind,
+            expiry_months: p.expiry_months,
+        })
+        .collect();
+    let program_transfers = store
+        .list_program_transfers()?
+        .into_iter()
+        .map(|t| ExportProgramTransfer {
+            from_program_id: t.from_program_id,
+            to_program_id: t.to_program_id,
+            ratio: t.ratio,
+        })
+        .collect();
+    let miles_ledger = store
+        .list_miles_entries()?
+        .into_iter()
+        .map(|e| ExportMilesEntry {
+            id: e.id,
+            card_id: e.card_id,
+            program: e.program,
+            kind: e.kind,
+            miles: e.miles,
+            date: e.date,
+            description: e.description,
+        })
+        .collect();
+    let redemptions = store
+        .list_redemptions()?
+        .into_iter()
+        .map(|r| ExportRedemption {
+            card_id: r.card_id,
+            program: r.program,
+            date: r.date,
+            miles: r.miles,
+            cash_value: r.cash_value,
+            description: r.description,
+            miles_entry_id: r.miles_entry_id,
+        })
+        .collect();
+    let fx_rates = store
+        .list_fx_rates()?
+        .into_iter()
+        .map(|r| ExportFxRate {
+            currency: r.currency,
+            rate: r.rate,
+            updated_on: r.updated_on,
+        })
+        .collect();
+    let categories = store
+        .list_categories()?
+        .into_iter()
+        .map(|c| ExportCategory {
+            name: c.name,
+            monthly_budget: c.monthly_budget,
+        })
+        .collect();
+
+    Ok(Export {
+        format: EXPORT_FORMAT.to_string(),
+        format_version: EXPORT_VERSION,
+        exported_on: today(),
+        cards,
+        spending,
+        payments,
+        charges,
+        rate_history,
+        closed_cycles,
+        review_queue,
+        partnerships,
+        promotions,
+        recurring,
+        merchant_rules,
+        programs,
+        program_transfers,
+        miles_ledger,
+        redemptions,
+        fx_rates,
+        categories,
+    })
+}
+
+/// Scrubs an export for sharing, e.g. as a reproduction database in a bug
+/// report. Merchant names become "Merchant 1", "Merchant 2"... (the same
+/// merchant keeps the same name), long digit runs in card names such as
+/// last-4s become "XXXX", and every money amount, block sizes and limits
+/// included, is multiplied by `factor`. Scaling blocks with the amounts
+/// keeps the miles, caps and minimum-spend outcomes of the original.
+pub fn anonymize(export: &mut Export, factor: f64) {
+    let scale = |amount: f64| (amount * factor * 100.0).round() / 100.0;
+    let digits = Regex::new(r"\d{4,}").expect("valid regex");
+
+    for card in &mut export.cards {
+        card.name = digits.replace_all(&card.name, "XXXX").into_owned();
+        card.block_size = scale(card.block_size);
+        // A cap in miles is not money
+        if card.cap_unit == CapUnit::Dollars {
+            card.max_reward_limit = card.max_reward_limit.map(scale);
+        }
+        card.min_spend = card.min_spend.map(scale);
+        card.annual_fee = card.annual_fee.map(scale);
+        card.starting_balance = scale(card.starting_balance);
+        card.balance_alert = card.balance_alert.map(scale);
+    }
+    let dollar_caps: Vec<i64> = export.cards.iter().filter(|c| c.cap_unit == CapUnit::Dollars).map(|c| c.id).collect();
+    for h in &mut export.rate_history {
+        h.block_size = scale(h.block_size);
+        if dollar_caps.contains(&h.card_id) {
+            h.max_reward_limit = h.max_reward_limit.map(scale);
+        }
+        h.min_spend = h.min_spend.map(scale);
+    }
+    let mut merchants: HashMap<String, String> = HashMap::new();
+    let mut merchant = |name: &mut Option<String>| {
+        if let Some(original) = name.take() {
+            let next = format!("Merchant {}", merchants.len() + 1);
+            *name = Some(merchants.entry(original).or_insert(next).clone());
+        }
+    };
+    // Tags are free text too, so each gets a placeholder the same way
+    let mut tags: HashMap<String, String> = HashMap::new();
+    for s in &mut export.spending {
+        s.amount = scale(s.amount);
+        s.original_amount = s.original_amount.map(scale);
+        s.cashback = s.cashback.map(scale);
+        merchant(&mut s.description);
+        if let Some(original) = s.tag.take() {
+            let next = format!("Tag {}", tags.len() + 1);
+            s.tag = Some(tags.entry(original).or_insert(next).clone());
+        }
+    }
+    for r in &mut export.review_queue {
+        r.amount = scale(r.amount);
+        merchant(&mut r.description);
+    }
+    for r in &mut export.recurring {
+        r.amount = scale(r.amount);
+        merchant(&mut r.merchant);
+    }
+    for p in &mut export.payments {
+        p.amount = scale(p.amount);
+    }
+    for c in &mut export.charges {
+        c.amount = scale(c.amount);
+    }
+    for c in &mut export.closed_cycles {
+        c.total_spend = scale(c.total_spend);
+    }
+    for r in &mut export.redemptions {
+        r.cash_value = scale(r.cash_value);
+    }
+    for c in &mut export.categories {
+        c.monthly_budget = c.monthly_budget.map(scale);
+    }
+}
+
+/// A scale factor for `anonymize` between 0.5 and 2, different each run so
+/// the original amounts cannot be worked back from a known one.
+pub fn random_scale_factor() -> f64 {
+    let random = std::collections::hash_map::RandomState::new().build_hasher().finish();
+    0.5 + 1.5 * (random as f64 / u64::MAX as f64)
+}
+
+/// Parses and validates an export file. Errors name the offending record,
+/// e.g. `spending[3].card_id: no card with ID 7 in this file`, and list
+/// every problem found rather than only the first.
+pub fn parse(text: &str) -> Result<Export, String> {
+    check_stamp(text, EXPORT_FORMAT, EXPORT_VERSION, "export")?;
+    let export: Export = serde_json::from_str(text).map_err(|e| format!("Invalid export: {}", e))?;
+    let problems = validate(&export);
+    if problems.is_empty() {
+        Ok(export)
+    } else {
+        Err(format!("Invalid export:\n  {}", problems.join("\n  ")))
+    }
+}
+
+/// Checks a versioned JSON file's stamp before its layout, so a file from a
+/// newer version gets an error about versions instead of about fields it
+/// added. `noun` names the kind of file in errors.
+pub fn check_stamp(text: &str, format: &str, supported: u32, noun: &str) -> Result<(), String> {
+    #[derive(Deserialize)]
+    struct Stamp {
+        format: Option<String>,
+        format_version: Option<u32>,
+    }
+    let stamp: Stamp = serde_json::from_str(text).map_err(|e| format!("Not a JSON {}: {}", noun, e))?;
+    if stamp.format.as_deref() != Some(format) {
+        return Err(format!("Not a cc-tracker {}: \"format\" must be \"{}\"", noun, format));
+    }
+    match stamp.format_version {
+        None => Err("Missing \"format_version\"".to_string()),
+        Some(version) if version > supported => Err(format!(
+            "This {}'s format version {} is newer than this cc-tracker supports (up to {}); upgrade to read it",
+            noun, version, supported
+        )),
+        Some(version) if version < 1 => Err(format!("Unknown {} format version {}", noun, version)),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Checks what the JSON layout alone cannot: dates, value ranges and that
+/// records reference cards in the file.
+fn validate(export: &Export) -> Vec<String> {
+    let is_date = |value: &str| parse_date_with_format(value, "YYYY-MM-DD").as_deref() == Some(value);
+    let mut problems = Vec::new();
+    let mut card_ids = Vec::new();
+
+    for (i, card) in export.cards.iter().enumerate() {
+        if card_ids.contains(&card.id) {
+            problems.push(format!("cards[{}].id: {} is used by another card", i, card.id));
+        }
+        card_ids.push(card.id);
+        problems.extend(card.new_card().problems().into_iter().map(|problem| format!("cards[{}].{}", i, problem)));
+    }
+
+    let card_ref = |kind: &str, i: usize, card_id: i64| {
+        (!card_ids.contains(&card_id)).then(|| format!("{}[{}].card_id: no card with ID {} in this file", kind, i, card_id))
+    };
+    let date = |kind: &str, i: usize, field: &str, value: &str| {
+        (!is_date(value)).then(|| format!("{}[{}].{}: \"{}\" is not a YYYY-MM-DD date", kind, i, field, value))
+    };
+    let mut check_record = |kind: &str, i: usize, card_id: i64, value: &str, amount: f64| {
+        problems.extend(card_ref(kind, i, card_id));
+        problems.extend(date(kind, i, "date", value));
+        if amount < 0.0 {
+            problems.push(format!("{}[{}].amount: must not be negative", kind, i));
+        }
+    };
+    for (i, s) in export.spending.iter().enumerate() {
+        check_record("spending", i, s.card_id, &s.date, s.amount);
+    }
+    for (i, p) in export.payments.iter().enumerate() {
+        check_record("payments", i, p.card_id, &p.date, p.amount);
+    }
+    for (i, c) in export.charges.iter().enumerate() {
+        check_record("charges", i, c.card_id, &c.date, c.amount);
+    }
+    for (i, r) in export.review_queue.iter().enumerate() {
+        check_record("review_queue", i, r.card_id, &r.date, r.amount);
+    }
+
+    for (i, r) in export.recurring.iter().enumerate() {
+        problems.extend(card_ref("recurring", i, r.card_id));
+        problems.extend(date("recurring", i, "next_date", &r.next_date));
+        if r.amount < 0.0 {
+            problems.push(format!("recurring[{}].amount: must not be negative", i));
+        }
+        if !(1..=31).contains(&r.day_of_month) {
+            problems.push(format!("recurring[{}].day_of_month: must be 1-31", i));
+        }
+    }
+    for (i, h) in export.rate_history.iter().enumerate() {
+        problems.extend(card_ref("rate_history", i, h.card_id));
+        problems.extend(date("rate_history", i, "valid_until", &h.valid_until));
+        if h.block_size <= 0.0 {
+            problems.push(format!("rate_history[{}].block_size: must be positive", i));
+        }
+    }
+    for (i, c) in export.closed_cycles.iter().enumerate() {
+        problems.extend(card_ref("closed_cycles", i, c.card_id));
+        problems.extend(date("closed_cycles", i, "cycle_start", &c.cycle_start));
+        problems.extend(date("closed_cycles", i, "cycle_end", &c.cycle_end));
+    }
+    for (i, p) in export.partnerships.iter().enumerate() {
+        problems.extend(card_ref("partnerships", i, p.card_id));
+    }
+    for (i, p) in export.promotions.iter().enumerate() {
+        problems.extend(card_ref("promotions", i, p.card_id));
+        problems.extend(date("promotions", i, "start_date", &p.start_date));
+        problems.extend(date("promotions", i, "end_date", &p.end_date));
+    }
+
+    let mut program_ids = Vec::new();
+    for (i, p) in export.programs.iter().enumerate() {
+        if program_ids.contains(&p.id) {
+            problems.push(format!("programs[{}].id: {} is used by another program", i, p.id));
+        }
+        program_ids.push(p.id);
+    }
+    for (i, t) in export.program_transfers.iter().enumerate() {
+        for (field, id) in [("from_program_id", t.from_program_id), ("to_program_id", t.to_program_id)] {
+            if !program_ids.contains(&id) {
+                problems.push(format!("program_transfers[{}].{}: no program with ID {} in this file", i, field, id));
+            }
+        }
+    }
+    let mut entry_ids = Vec::new();
+    for (i, e) in export.miles_ledger.iter().enumerate() {
+        if entry_ids.contains(&e.id) {
+            problems.push(format!("miles_ledger[{}].id: {} is used by another entry", i, e.id));
+        }
+        entry_ids.push(e.id);
+        problems.extend(e.card_id.and_then(|card_id| card_ref("miles_ledger", i, card_id)));
+        problems.extend(date("miles_ledger", i, "date", &e.date));
+        if e.kind != "adjustment" && e.kind != "redemption" {
+            problems.push(format!("miles_ledger[{}].kind: must be \"adjustment\" or \"redemption\"", i));
+        }
+    }
+    for (i, r) in export.redemptions.iter().enumerate() {
+        problems.extend(r.card_id.and_then(|card_id| card_ref("redemptions", i, card_id)));
+        problems.extend(date("redemptions", i, "date", &r.date));
+        if let Some(id) = r.miles_entry_id.filter(|id| !entry_ids.contains(id)) {
+            problems.push(format!("redemptions[{}].miles_entry_id: no miles_ledger entry with ID {} in this file", i, id));
+        }
+    }
+    for (i, r) in export.fx_rates.iter().enumerate() {
+        problems.extend(date("fx_rates", i, "updated_on", &r.updated_on));
+    }
+
+    for (i, s) in export.spending.iter().enumerate() {
+        if s.status != STATUS_POSTED && s.status != STATUS_SCHEDULED {
+            problems.push(format!("spending[{}].status: must be \"posted\" or \"scheduled\"", i));
+        }
+    }
+    for (i, c) in export.charges.iter().enumerate() {
+        if !CHARGE_KINDS.contains(&c.kind.as_str()) {
+            problems.push(format!("charges[{}].kind: must be one of {}", i, CHARGE_KINDS.join(", ")));
+        }
+    }
+    problems
+}
+
+/// Record counts restored from an export
+#[derive(Debug, Default)]
+pub struct RestoreSummary {
+    pub cards: usize,
+    pub spending: usize,
+    pub payments: usize,
+    pub charges: usize,
+    /// Everything else: rate history, closed cycles, rules, programs, miles
+    /// ledger entries and the like
+    pub other: usize,
+}
+
+/// Loads a validated export into a store that has no cards yet, all in one
+/// transaction, so a restore that fails leaves the store empty to retry.
+/// Use `merge-db` to combine databases instead.
+pub fn restore(store: &(impl Store + ?Sized), export: &Export) -> error::Result<RestoreSummary> {
+    store.restore_export(export)
+}
+
+/// Inserts every record of an export, mapping the file's IDs to the new
+/// ones. Stores call this from `restore_export` inside a transaction.
+pub fn restore_records<S: Store + ?Sized>(store: &S, export: &Export) -> error::Result<RestoreSummary> {
+    let mut ids = HashMap::new();
+    for card in &export.cards {
+        let new_card = card.new_card();
+        let id = store.add_card(&new_card)?;
+        if let Some(closed_on) = &card.closed_on {
+            store.close_card(id, closed_on)?;
+        }
+        if !card.cycle_overrides.is_empty() {
+            store.set_cycle_overrides(id, &card.cycle_overrides)?;
+        }
+        ids.insert(card.id, id);
+    }
+    for h in &export.rate_history {
+        store.restore_rate_change(&RateChange {
+            id: 0,
+            card_id: ids[&h.card_id],
+            valid_until: h.valid_until.clone(),
+            rates: CardRates {
+                miles_per_dollar: h.miles_per_dollar,
+                block_size: h.block_size,
+                max_reward_limit: h.max_reward_limit,
+                min_spend: h.min_spend,
+            },
+        })?;
+    }
+
+    let mut program_ids = HashMap::new();
+    for p in &export.programs {
+        program_ids.insert(p.id, store.add_program(&p.name, p.kind, p.expiry_months)?);
+    }
+    for t in &export.program_transfers {
+        store.add_program_transfer(program_ids[&t.from_program_id], program_ids[&t.to_program_id], t.ratio)?;
+    }
+    for c in &export.categories {
+        match c.monthly_budget {
+            Some(_) => store.set_category_budget(&c.name, c.monthly_budget)?,
+            None => {
+                store.add_category(&c.name)?;
+            }
+        }
+    }
+    for p in &export.partnerships {
+        store.add_partnership(ids[&p.card_id], &p.merchant_pattern, p.bonus_miles_per_dollar)?;
+    }
+    for p in &export.promotions {
+        store.add_promotion(ids[&p.card_id], &p.category, p.miles_per_dollar, &p.start_date, &p.end_date)?;
+    }
+    for r in &export.recurring {
+        // Starting from the next charge keeps it as the next charge
+        store.add_recurring(ids[&r.card_id], r.amount, &r.category, r.day_of_month, r.merchant.as_deref(), &r.next_date)?;
+    }
+    for r in &export.merchant_rules {
+        store.add_merchant_rule(&r.pattern, r.syntax, &r.category)?;
+    }
+    for r in &export.fx_rates {
+        store.restore_fx_rate(&FxRate {
+            currency: r.currency.clone(),
+            rate: r.rate,
+            updated_on: r.updated_on.clone(),
+        })?;
+    }
+
+    for s in &export.spending {
+        store.restore_spending(&Spending {
+            id: 0,
+            card_id: ids[&s.card_id],
+            amount: s.amount,
+            category: s.category.clone(),
+            date: s.date.clone(),
+            miles_earned: s.miles_earned,
+            status: s.status.clone(),
+            description: s.description.clone(),
+            payment_category: s.payment_category.clone(),
+            tag: s.tag.clone(),
+            foreign: s.foreign,
+            original_amount: s.original_amount,
+            original_currency: s.original_currency.clone(),
+            recorded_on: s.recorded_on.clone(),
+            cashback: s.cashback,
+        })?;
+    }
+    for p in &export.payments {
+        store.add_payment(ids[&p.card_id], p.amount, &p.date)?;
+    }
+    for c in &export.charges {
+        store.add_charge(ids[&c.card_id], &c.kind, c.amount, &c.date)?;
+    }
+
+    let mut entry_ids = HashMap::new();
+    for e in &export.miles_ledger {
+        let card_id = e.card_id.map(|id| ids[&id]);
+        let id = store.add_miles_entry(card_id, e.program.as_deref(), &e.kind, e.miles, &e.date, e.description.as_deref())?;
+        entry_ids.insert(e.id, id);
+    }
+    for r in &export.redemptions {
+        store.restore_redemption(&Redemption {
+            id: 0,
+            card_id: r.card_id.map(|id| ids[&id]),
+            program: r.program.clone(),
+            account: String::new(),
+            date: r.date.clone(),
+            miles: r.miles,
+            cash_value: r.cash_value,
+            cents_per_mile: cents_per_mile(r.miles, r.cash_value),
+            description: r.description.clone(),
+            miles_entry_id: r.miles_entry_id.map(|id| entry_ids[&id]),
+        })?;
+    }
+    for r in &export.review_queue {
+        store.restore_review_item(&ReviewItem {
+            id: 0,
+            card_id: ids[&r.card_id],
+            amount: r.amount,
+            date: r.date.clone(),
+            description: r.description.clone(),
+            suggested_category: r.suggested_category.clone(),
+            confidence: r.confidence,
+        })?;
+    }
+    // Last, as spending cannot be added to a closed cycle
+    for c in &export.closed_cycles {
+        store.restore_closed_cycle(&ClosedCycle {
+            id: 0,
+            card_id: ids[&c.card_id],
+            cycle_start: c.cycle_start.clone(),
+            cycle_end: c.cycle_end.clone(),
+            total_spend: c.total_spend,
+            total_miles: c.total_miles,
+            transaction_count: c.transaction_count,
+            closed_on: c.closed_on.clone(),
+        })?;
+    }
+
+    Ok(RestoreSummary {
+        cards: export.cards.len(),
+        spending: export.spending.len(),
+        payments: export.payments.len(),
+        charges: export.charges.len(),
+        other: export.rate_history.len()
+            + export.closed_cycles.len()
+            + export.review_queue.len()
+            + export.partnerships.len()
+            + export.promotions.len()
+            + export.recurring.len()
+            + export.merchant_rules.len()
+            + export.programs.len()
+            + export.program_transfers.len()
+            + export.miles_ledger.len()
+            + export.redemptions.len()
+            + export.fx_rates.len()
+            + export.categories.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::OriginalAmount;
+    use rusqlite::Connection;
+
+    fn test_store() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        conn
+    }
+
+    fn sample_store() -> Connection {
+        let conn = test_store();
+        let card = NewCard {
+            name: "Card A".to_string(),
+            categories: vec!["dining".to_string()],
+            payment_categories: vec!["online".to_string()],
+            miles_per_dollar: 4.0,
+            block_size: 5.0,
+            statement_renewal_date: 1,
+            ..NewCard::default()
+        };
+        let id = db::add_card(&conn, &card).unwrap();
+        db::add_spending(&conn, id, 42.0, "dining", "2025-03-05", None, false, None).unwrap();
+        db::schedule_spending(&conn, id, 10.0, "dining", "2025-03-20", None, false, None).unwrap();
+        db::add_payment(&conn, id, 30.0, "2025-03-10").unwrap();
+        db::add_charge(&conn, id, "interest", 2.5, "2025-03-15").unwrap();
+        conn
+    }
+
+    /// The sample plus a record in every other table
+    fn full_store() -> Connection {
+        let conn = sample_store();
+        db::change_card_rates(&conn, 1, "2025-04-01", &CardRates {
+            miles_per_dollar: 1.0,
+            block_size: 1.0,
+            max_reward_limit: None,
+            min_spend: None,
+        })
+        .unwrap();
+        db::close_cycle(&conn, 1, "2025-02-27").unwrap();
+        db::restore_review_item(&conn, &ReviewItem {
+            id: 0,
+            card_id: 1,
+            amount: 18.0,
+            date: "2025-03-12".to_string(),
+            description: Some("GRAB".to_string()),
+            suggested_category: Some("transport".to_string()),
+            confidence: Some(0.5),
+        })
+        .unwrap();
+        db::add_partnership(&conn, 1, "STARBUCKS*", 2.0).unwrap();
+        db::add_promotion(&conn, 1, "dining", 6.0, "2025-03-01", "2025-03-31").unwrap();
+        db::add_recurring(&conn, 1, 15.0, "bills", 5, Some("NETFLIX"), "2025-03-01").unwrap();
+        db::add_merchant_rule(&conn, "GRAB*", PatternSyntax::Glob, "transport").unwrap();
+        let krisflyer = db::add_program(&conn, "KrisFlyer", ProgramKind::Airline, Some(36)).unwrap();
+        let rewards = db::add_program(&conn, "Rewards", ProgramKind::Bank, None).unwrap();
+        db::add_program_transfer(&conn, rewards, krisflyer, 0.5).unwrap();
+        db::add_miles_entry(&conn, Some(1), None, "adjustment", 5.0, "2025-03-11", Some("bank rounding")).unwrap();
+        db::add_redemption(&conn, None, Some("KrisFlyer"), 1000.0, 25.0, "2025-03-14", Some("upgrade")).unwrap();
+        db::set_fx_rate(&conn, "JPY", 0.009).unwrap();
+        db::set_category_budget(&conn, "dining", Some(300.0)).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_export_round_trips_through_restore() {
+        let source = sample_store();
+        // Miles are restored as recorded, not recalculated at current rates
+        db::change_card_rates(&source, 1, "2025-04-01", &crate::models::CardRates {
+            miles_per_dollar: 1.0,
+            block_size: 1.0,
+            max_reward_limit: None,
+            min_spend: None,
+        })
+        .unwrap();
+        let json = serde_json::to_string(&export(&source).unwrap()).unwrap();
+
+        let target = test_store();
+        let summary = restore(&target, &parse(&json).unwrap()).unwrap();
+        assert_eq!((summary.cards, summary.spending, summary.payments, summary.charges), (1, 2, 1, 1));
+
+        let spending = db::list_spending(&target, None, None).unwrap();
+        assert_eq!(spending.len(), 2);
+        assert_eq!(spending[1].miles_earned, 32.0);
+        assert_eq!(spending[0].status, STATUS_SCHEDULED);
+        assert_eq!(db::list_charges(&target, None).unwrap()[0].kind, "interest");
+    }
+
+    #[test]
+    fn test_export_carries_every_table() {
+        let source = full_store();
+        db::post_scheduled_spending(&source, 2).unwrap();
+        let exported = export(&source).unwrap();
+        let target = test_store();
+        let summary = restore(&target, &parse(&serde_json::to_string(&exported).unwrap()).unwrap()).unwrap();
+        assert_eq!(summary.other, 14 + exported.categories.len());
+
+        // Restoring then exporting again gives the same file
+        let mut again = serde_json::to_value(export(&target).unwrap()).unwrap();
+        let exported = serde_json::to_value(&exported).unwrap();
+        again["exported_on"] = exported["exported_on"].clone();
+        assert_eq!(again, exported);
+
+        assert_eq!(db::list_spending(&target, None, None).unwrap()[0].recorded_on, Some(today()));
+        assert_eq!(db::last_rate_change(&target, 1).unwrap().as_deref(), Some("2025-04-01"));
+        assert!(db::closed_cycle_containing(&target, 1, "2025-02-10").unwrap().is_some());
+        let redemption = &db::list_redemptions(&target).unwrap()[0];
+        let entry = db::list_miles_entries(&target).unwrap().into_iter().find(|e| e.kind == "redemption").unwrap();
+        assert_eq!((redemption.program.as_deref(), redemption.miles_entry_id), (Some("KrisFlyer"), Some(entry.id)));
+    }
+
+    #[test]
+    fn test_failed_restore_leaves_the_store_empty() {
+        let mut data = export(&full_store()).unwrap();
+        // A cycle can only be closed once, so the second insert fails after
+        // everything else has gone in
+        let cycle = &data.closed_cycles[0];
+        data.closed_cycles.push(ExportClosedCycle {
+            card_id: cycle.card_id,
+            cycle_start: cycle.cycle_start.clone(),
+            cycle_end: cycle.cycle_end.clone(),
+            total_spend: 0.0,
+            total_miles: 0.0,
+            transaction_count: 0,
+            closed_on: cycle.closed_on.clone(),
+        });
+
+        let target = test_store();
+        assert!(restore(&target, &data).is_err());
+        assert!(db::list_cards(&target).unwrap().is_empty());
+        assert!(db::list_spending(&target, None, None).unwrap().is_empty());
+        assert!(db::list_programs(&target).unwrap().is_empty());
+
+        data.closed_cycles.pop();
+        assert_eq!(restore(&target, &data).unwrap().cards, 1);
+    }
+
+    #[test]
+    fn test_spending_csv_quotes_descriptions() {
+        let store = sample_store();
+        db::add_spending(&store, 1, 12.5, "dining", "2025-03-06", Some("TOAST BOX, RAFFLES"), false, None).unwrap();
+        let mut spending = db::list_spending(&store, None, None).unwrap();
+        spending.reverse();
+        assert_eq!(
+            spending_csv(&spending, &db::list_cards(&store).unwrap()),
+            "id,date,card_id,card_name,amount,category,miles_earned,status,description,payment_category,tag\n\
+             1,2025-03-05,1,Card A,42,dining,32,posted,,,\n\
+             3,2025-03-06,1,Card A,12.5,dining,8,posted,\"TOAST BOX, RAFFLES\",,\n\
+             2,2025-03-20,1,Card A,10,dining,8,scheduled,,,\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_newer_and_foreign_files() {
+        let err = parse(r#"{"format": "cc-tracker-export", "format_version": 3, "extra": true}"#).unwrap_err();
+        assert!(err.contains("newer than this cc-tracker supports"), "{}", err);
+
+        let err = parse(r#"{"format": "something-else", "format_version": 1}"#).unwrap_err();
+        assert!(err.contains("Not a cc-tracker export"), "{}", err);
+
+        let err = parse(r#"{"format": "cc-tracker-export", "format_version": 1, "cards": []}"#).unwrap_err();
+        assert!(err.contains("missing field `exported_on`"), "{}", err);
+    }
+
+    #[test]
+    fn test_version_1_exports_are_still_read() {
+        let json = r#"{
+            "format": "cc-tracker-export", "format_version": 1, "exported_on": "2025-03-31",
+            "cards": [{"id": 7, "name": "Card A", "categories": ["dining"], "payment_categories": ["online"],
+                       "miles_per_dollar": 4.0, "block_size": 5.0, "statement_renewal_date": 1, "posting_delay": 0,
+                       "bank_time_offset_minutes": 0, "cutoff_hour": 0, "starting_balance": 0.0}],
+            "spending": [{"card_id": 7, "date": "2025-03-05", "amount": 42.0, "category": "dining",
+                          "miles_earned": 32.0, "status": "posted"}],
+            "payments": [{"card_id": 7, "date": "2025-03-20", "amount": 42.0}],
+            "charges": []
+        }"#;
+        let target = Connection::open_in_memory().unwrap();
+        db::init_tables(&target).unwrap();
+        let summary = restore(&target, &parse(json).unwrap()).unwrap();
+        assert_eq!((summary.cards, summary.spending, summary.payments), (1, 1, 1));
+        let card = db::list_cards(&target).unwrap().remove(0);
+        assert_eq!((card.reward_type, card.cashback_percent, card.foreign_blocks), (RewardType::Miles, None, false));
+        assert_eq!(db::list_spending(&target, None, None).unwrap()[0].miles_earned, 32.0);
+    }
+
+    #[test]
+    fn test_parse_lists_every_invalid_record() {
+        let mut export = export(&sample_store()).unwrap();
+        export.spending[0].card_id = 7;
+        export.payments[0].date = "2025-13-01".to_string();
+        export.charges[0].kind = "cashback".to_string();
+        export.cards[0].cutoff_hour = 24;
+
+        let err = parse(&serde_json::to_string(&export).unwrap()).unwrap_err();
+        assert!(err.contains("spending[0].card_id: no card with ID 7 in this file"), "{}", err);
+        assert!(err.contains("payments[0].date: \"2025-13-01\" is not a YYYY-MM-DD date"), "{}", err);
+        assert!(err.contains("charges[0].kind: must be one of"), "{}", err);
+        assert!(err.contains("cards[0].cutoff_hour: must be 0-23"), "{}", err);
+    }
+
+    /// The published schema must describe exactly the fields this version writes.
+    #[test]
+    fn test_schema_matches_export_layout() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../schemas/cc-tracker-export.v2.schema.json")).unwrap();
+        let export = serde_json::to_value(export(&full_store()).unwrap()).unwrap();
+
+        let properties = |schema: &serde_json::Value| {
+            let mut keys: Vec<String> = schema["properties"].as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        let fields = |value: &serde_json::Value| {
+            let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+        assert_eq!(properties(&schema), fields(&export));
+        assert_eq!(schema["properties"]["format_version"]["const"], EXPORT_VERSION);
+        for (entity, definition) in [
+            ("cards", "card"),
+            ("spending", "spending"),
+            ("payments", "payment"),
+            ("charges", "charge"),
+            ("rate_history", "rate_change"),
+            ("closed_cycles", "closed_cycle"),
+            ("review_queue", "review_item"),
+            ("partnerships", "partnership"),
+            ("promotions", "promotion"),
+            ("recurring", "recurring"),
+            ("merchant_rules", "merchant_rule"),
+            ("programs", "program"),
+            ("program_transfers", "program_transfer"),
+            ("miles_ledger", "miles_entry"),
+            ("redemptions", "redemption"),
+            ("fx_rates", "fx_rate"),
+            ("categories", "category"),
+        ] {
+            let definition = &schema["$defs"][definition];
+            assert_eq!(properties(definition), fields(&export[entity][0]), "{}", entity);
+        }
+    }
+
+    #[test]
+    fn test_preview_is_stamped_per_its_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(include_str!("../../schemas/cc-tracker-import-preview.v1.schema.json")).unwrap();
+        let json = to_versioned_json(PREVIEW_FORMAT, PREVIEW_VERSION, &crate::models::ImportPreview::default());
+        let preview: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(preview["format"], PREVIEW_FORMAT);
+        let mut fields: Vec<&String> = preview.as_object().unwrap().keys().collect();
+        let mut required: Vec<&str> = schema["required"].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+        fields.sort();
+        required.sort();
+        assert_eq!(fields, required);
+    }
+
+    #[test]
+    fn test_anonymize_scrubs_names_and_scales_amounts() {
+        let store = sample_store();
+        db::add_card(&store, &NewCard {
+            name: "Altitude ending 4821".to_string(),
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            max_reward_limit: Some(1000.0),
+            ..NewCard::default()
+        })
+        .unwrap();
+        db::add_card(&store, &NewCard {
+            name: "Rewards".to_string(),
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            max_reward_limit: Some(2000.0),
+            cap_unit: CapUnit::Miles,
+            ..NewCard::default()
+        })
+        .unwrap();
+        for (date, merchant) in [("2025-03-06", "COLD STORAGE"), ("2025-03-07", "SHELL"), ("2025-03-08", "COLD STORAGE")] {
+            db::add_spending(&store, 1, 10.0, "dining", date, Some(merchant), false, None).unwrap();
+        }
+        let yen = OriginalAmount { amount: 1500.0, currency: "JPY".to_string() };
+        db::add_spending(&store, 1, 13.5, "dining", "2025-03-09", None, true, Some(&yen)).unwrap();
+        let mut data = export(&store).unwrap();
+        for (s, tag) in data.spending.iter_mut().zip(["trip to Osaka", "merged", "trip to Osaka"]) {
+            s.tag = Some(tag.to_string());
+        }
+        anonymize(&mut data, 1.5);
+
+        assert_eq!(data.cards[1].name, "Altitude ending XXXX");
+        assert_eq!(data.cards[0].block_size, 7.5);
+        assert_eq!(data.cards[1].max_reward_limit, Some(1500.0));
+        assert_eq!(data.cards[2].max_reward_limit, Some(2000.0));
+        assert_eq!(data.spending[0].amount, 63.0);
+        // Same merchant, same placeholder
+        let merchants: Vec<Option<&str>> = data.spending.iter().map(|s| s.description.as_deref()).collect();
+        assert_eq!(merchants, [None, Some("Merchant 1"), Some("Merchant 2"), Some("Merchant 1"), None, None]);
+        let tags: Vec<Option<&str>> = data.spending.iter().map(|s| s.tag.as_deref()).collect();
+        assert_eq!(tags, [Some("Tag 1"), Some("Tag 2"), Some("Tag 1"), None, None, None]);
+        let foreign = data.spending.iter().find(|s| s.original_currency.is_some()).unwrap();
+        assert_eq!((foreign.amount, foreign.original_amount), (20.25, Some(2250.0)));
+        assert_eq!(data.payments[0].amount, 45.0);
+        assert_eq!(data.charges[0].amount, 3.75);
+        assert!(validate(&data).is_empty());
+
+        let factor = random_scale_factor();
+        assert!((0.5..=2.0).contains(&factor));
+    }
+}
@@ -0,0 +1,127 @@
+//! Fees and charges against rewards: whether each card earned more over a
+//! year than it cost to keep, for `card-value`.
+
+use std::collections::HashMap;
+
+use crate::engine::{format_date, round_cents};
+use crate::error::Result;
+use crate::models::{Card, CardValue};
+use crate::store::Store;
+
+/// Each card's posted miles and cash back over a calendar year, as they
+/// were recorded, valued at `mile_value` dollars a mile, less its annual fee
+/// and every other charge (interest, late and FX fees) for the year. Cards
+/// closed before the year began are left out.
+pub fn card_values<S: Store + ?Sized>(store: &S, year: i32, mile_value: f64) -> Result<Vec<CardValue>> {
+    let (start, end) = (format_date(year, 1, 1), format_date(year, 12, 31));
+    let cards: Vec<Card> = store.list_cards()?.into_iter().filter(|card| !card.is_closed_on(&start)).collect();
+
+    let mut earned: HashMap<i64, (f64, f64)> = HashMap::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status == "posted" && s.date >= start && s.date <= end {
+            let totals = earned.entry(s.card_id).or_default();
+            totals.0 += s.miles_earned;
+            totals.1 += s.cashback.unwrap_or(0.0);
+        }
+        Ok(())
+    })?;
+    // (annual fees, everything else) charged in the year
+    let mut charged: HashMap<i64, (Option<f64>, f64)> = HashMap::new();
+    for charge in store.list_charges(None)? {
+        if charge.date >= start && charge.date <= end {
+            let totals = charged.entry(charge.card_id).or_default();
+            if charge.kind == "annual_fee" {
+                *totals.0.get_or_insert(0.0) += charge.amount;
+            } else {
+                totals.1 += charge.amount;
+            }
+        }
+    }
+
+    Ok(cards
+        .iter()
+        .map(|card| {
+            let (miles_earned, cashback) = earned.get(&card.id).copied().unwrap_or_default();
+            let cashback = round_cents(cashback);
+            let miles_value = round_cents(miles_earned * mile_value);
+            let (fees, other_charges) = charged.get(&card.id).copied().unwrap_or_default();
+            let annual_fee = fees.unwrap_or_else(|| expected_fee(card, year));
+            let other_charges = round_cents(other_charges);
+            let net_value = round_cents(miles_value + cashback - annual_fee - other_charges);
+            CardValue {
+                card_id: card.id,
+                card_name: card.name.clone(),
+                miles_earned,
+                miles_value,
+                cashback,
+                annual_fee,
+                other_charges,
+                net_value,
+                verdict: if net_value >= 0.0 { "keep" } else { "review" }.to_string(),
+            }
+        })
+        .collect())
+}
+
+/// The card's annual fee for a year in which none was recorded: nothing if
+/// the card is closed by the month the fee posts in.
+fn expected_fee(card: &Card, year: i32) -> f64 {
+    let Some(fee) = card.annual_fee else { return 0.0 };
+    let posts_on = format_date(year, card.fee_posting_month.unwrap_or(1), 1);
+    if card.is_closed_on(&posts_on) { 0.0 } else { fee }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    fn card(conn: &Connection, name: &str, annual_fee: Option<f64>, fee_posting_month: Option<i32>) -> i64 {
+        db::add_card(
+            conn,
+            &NewCard {
+                name: name.to_string(),
+                categories: vec!["dining".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 2.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                annual_fee,
+                fee_posting_month,
+                ..NewCard::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_card_values_net_rewards_against_fees() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let keeper = card(&conn, "Keeper", Some(100.0), Some(3));
+        let costly = card(&conn, "Costly", Some(200.0), None);
+        let closed = card(&conn, "Closed mid-year", Some(50.0), Some(9));
+        db::add_spending(&conn, keeper, 4000.0, "dining", "2026-02-05", None, false, None).unwrap();
+        db::add_spending(&conn, keeper, 500.0, "dining", "2025-12-30", None, false, None).unwrap();
+        db::add_spending(&conn, costly, 1000.0, "dining", "2026-05-05", None, false, None).unwrap();
+        // A recorded fee replaces the expected one
+        db::add_charge(&conn, costly, "annual_fee", 180.0, "2026-04-02").unwrap();
+        db::add_charge(&conn, keeper, "interest", 12.5, "2026-03-15").unwrap();
+        db::add_charge(&conn, keeper, "late_fee", 7.5, "2026-03-15").unwrap();
+        db::add_charge(&conn, keeper, "fx_fee", 99.0, "2025-12-31").unwrap();
+        db::close_card(&conn, closed, "2026-06-30").unwrap();
+
+        let values = card_values(&conn, 2026, 0.02).unwrap();
+        let value = |id: i64| values.iter().find(|v| v.card_id == id).unwrap();
+        assert_eq!((value(keeper).miles_earned, value(keeper).miles_value), (8000.0, 160.0));
+        // Interest and late fees cost as much as the annual fee does
+        assert_eq!((value(keeper).annual_fee, value(keeper).other_charges), (100.0, 20.0));
+        assert_eq!((value(keeper).net_value, value(keeper).verdict.as_str()), (40.0, "keep"));
+        assert_eq!((value(costly).annual_fee, value(costly).other_charges, value(costly).net_value), (180.0, 0.0, -140.0));
+        assert_eq!(value(costly).verdict, "review");
+        assert_eq!(value(closed).annual_fee, 0.0);
+        assert_eq!(card_values(&conn, 2027, 0.02).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,181 @@
+//! `forecast`: where each card's statement cycle is heading by its last
+//! day. Each category is expected to keep going onto a card at its daily
+//! average there over a recent window, so a card's minimum-spend exclusions
+//! and the categories it earns nothing on are projected exactly.
+
+use std::collections::BTreeMap;
+
+use crate::dates;
+use crate::db::STATUS_POSTED;
+use crate::engine::{add_days, card_cycle, days_between, round_cents};
+use crate::error::Result;
+use crate::models::{CapUnit, Card, CycleForecast};
+use crate::store::Store;
+
+/// Days of history the daily averages are taken over by default
+pub const DEFAULT_LOOKBACK_DAYS: i32 = 90;
+
+/// The date `needed` more is reached going up by `per_day` from `date`, if
+/// it is reached within `days_left` days.
+fn reached_on(date: &str, needed: f64, per_day: f64, days_left: i32) -> Option<String> {
+    if per_day <= 0.0 {
+        return None;
+    }
+    let days = (needed / per_day).ceil() as i32;
+    (days <= days_left).then(|| add_days(date, days.max(0)))
+}
+
+/// What the cycle's projected spend means for the minimum spend.
+fn min_spend_outlook(card: &Card, date: &str, days_left: i32, qualifying: f64, per_day: f64) -> Option<String> {
+    let minimum = card.min_spend?;
+    if qualifying >= minimum {
+        return Some("met".to_string());
+    }
+    Some(match reached_on(date, minimum - qualifying, per_day, days_left) {
+        Some(on) => format!("met around {}", dates::display(&on)),
+        None => format!("${:.2} short", minimum - qualifying - per_day * days_left as f64),
+    })
+}
+
+/// What the cycle's projected spend (or miles) means for the reward cap.
+fn cap_outlook(card: &Card, date: &str, days_left: i32, so_far: f64, per_day: f64) -> Option<String> {
+    let cap = card.max_reward_limit?;
+    let projected = so_far + per_day * days_left as f64;
+    let amount = |value: f64| match card.cap_unit {
+        CapUnit::Dollars => format!("${:.2}", value),
+        CapUnit::Miles => format!("{:.0} miles", value),
+    };
+    Some(if so_far >= cap {
+        "reached".to_string()
+    } else if let Some(on) = reached_on(date, cap - so_far, per_day, days_left)
+        && projected > cap
+    {
+        format!("runs out around {}, {} over", dates::display(&on), amount(projected - cap))
+    } else {
+        format!("within cap, {} to spare", amount(cap - projected))
+    })
+}
+
+/// Forecasts each open card's cycle containing `date` (or just `card`'s),
+/// from its daily average per category over the `lookback_days` up to and
+/// including `date`.
+pub fn forecast<S: Store + ?Sized>(
+    store: &S,
+    card: Option<&Card>,
+    date: &str,
+    lookback_days: i32,
+) -> Result<Vec<CycleForecast>> {
+    let cards: Vec<Card> = match card {
+        Some(card) => vec![card.clone()],
+        None => store.list_cards()?.into_iter().filter(|c| !c.is_closed_on(date)).collect(),
+    };
+    let since = add_days(date, 1 - lookback_days);
+    let mut rows = Vec::new();
+    for card in &cards {
+        let (start, end) = card_cycle(card, date);
+        let excluded = card.excluded_from_min_spend();
+        let (mut spent, mut qualifying, mut miles) = (0.0, 0.0, 0.0);
+        // Spend in the lookback window per category, keyed in lower case
+        let mut history: BTreeMap<String, f64> = BTreeMap::new();
+        store.for_each_spending(Some(card.id), None, &mut |s| {
+            if s.status != STATUS_POSTED || s.date.as_str() > date {
+                return Ok(());
+            }
+            if s.date >= start {
+                spent += s.amount;
+                miles += s.miles_earned;
+                if !excluded.contains(&s.category.to_lowercase()) {
+                    qualifying += s.amount;
+                }
+            }
+            if s.date >= since {
+                *history.entry(s.category.to_lowercase()).or_default() += s.amount;
+            }
+            Ok(())
+        })?;
+
+        let (mut per_day, mut qualifying_per_day, mut miles_per_day) = (0.0, 0.0, 0.0);
+        for (category, total) in &history {
+            let rate = total / lookback_days as f64;
+            per_day += rate;
+            if !excluded.contains(category) {
+                qualifying_per_day += rate;
+            }
+            if !card.excludes(category) && card.block_size > 0.0 {
+                miles_per_day += rate * card.miles_per_dollar / card.block_size;
+            }
+        }
+        let days_left = days_between(date, &end);
+        let cap = match card.cap_unit {
+            CapUnit::Dollars => cap_outlook(card, date, days_left, spent, per_day),
+            CapUnit::Miles => cap_outlook(card, date, days_left, miles, miles_per_day),
+        };
+        rows.push(CycleForecast {
+            card_name: card.name.clone(),
+            days_left,
+            spent: round_cents(spent),
+            per_day: round_cents(per_day),
+            projected: round_cents(spent + per_day * days_left as f64),
+            min_spend: min_spend_outlook(card, date, days_left, qualifying, qualifying_per_day),
+            cap,
+            cycle_end: end,
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_forecast_projects_per_category_against_min_spend_and_cap() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str, max_reward_limit, min_spend| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    categories: vec!["dining".to_string(), "bills".to_string()],
+                    payment_categories: vec!["contactless".to_string()],
+                    miles_per_dollar: 1.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    max_reward_limit,
+                    min_spend,
+                    min_spend_exclusions: vec!["bills".to_string()],
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let capped = card("Capped", Some(1000.0), None);
+        let bonus = card("Bonus", None, Some(800.0));
+        // $20 a day of dining on the capped card over the last 30 days
+        db::add_spending(&conn, capped, 300.0, "dining", "2026-03-20", None, false, None).unwrap();
+        db::add_spending(&conn, capped, 300.0, "dining", "2026-04-10", None, false, None).unwrap();
+        // $10 a day of dining and $20 of bills, which do not count, on the other
+        db::add_spending(&conn, bonus, 300.0, "dining", "2026-04-02", None, false, None).unwrap();
+        db::add_spending(&conn, bonus, 600.0, "bills", "2026-04-03", None, false, None).unwrap();
+
+        let rows = forecast(&conn, None, "2026-04-15", 30).unwrap();
+        assert_eq!((rows[0].card_name.as_str(), rows[0].cycle_end.as_str(), rows[0].days_left), ("Capped", "2026-04-30", 15));
+        // $300 spent, and $300 more to come at $20 a day
+        assert_eq!((rows[0].spent, rows[0].per_day, rows[0].projected), (300.0, 20.0, 600.0));
+        assert_eq!(rows[0].cap.as_deref(), Some("within cap, $400.00 to spare"));
+        assert_eq!(rows[1].min_spend.as_deref(), Some("$350.00 short"));
+
+        // Spending picks up: the cap runs out and the minimum is met in time
+        db::add_spending(&conn, capped, 500.0, "dining", "2026-04-11", None, false, None).unwrap();
+        db::add_spending(&conn, bonus, 300.0, "dining", "2026-04-12", None, false, None).unwrap();
+        let rows = forecast(&conn, None, "2026-04-15", 30).unwrap();
+        assert_eq!(rows[0].cap.as_deref(), Some("runs out around 2026-04-21, $350.00 over"));
+        assert_eq!(rows[1].min_spend.as_deref(), Some("met around 2026-04-25"));
+        let only = forecast(&conn, Some(&db::get_card(&conn, capped).unwrap().unwrap()), "2026-04-01", 30).unwrap();
+        assert_eq!(only.len(), 1);
+    }
+}
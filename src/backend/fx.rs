@@ -0,0 +1,49 @@
+//! Spending in foreign currencies: converting what was charged to the home
+//! currency every balance, cap and report is kept in, at a rate given with
+//! the transaction or stored with `fx-rates set`.
+
+use crate::engine::round_cents;
+use crate::error::{CcTrackerError, Result};
+use crate::models::OriginalAmount;
+use crate::store::Store;
+
+/// A currency's ISO code in upper case, e.g. "JPY" for "jpy". Fails unless
+/// it is three letters.
+pub fn currency_code(code: &str) -> Result<String> {
+    let code = code.trim();
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(CcTrackerError::Invalid(format!(
+            "'{}' is not a currency code; use three letters, e.g. JPY",
+            code
+        )));
+    }
+    Ok(code.to_ascii_uppercase())
+}
+
+/// Converts `amount` in `currency` to the home currency at `rate` if given,
+/// or else at the rate stored for the currency. Returns the home amount,
+/// rounded to the cent, with the original to keep alongside it.
+pub fn to_home<S: Store + ?Sized>(
+    store: &S,
+    amount: f64,
+    currency: &str,
+    rate: Option<f64>,
+) -> Result<(f64, OriginalAmount)> {
+    let currency = currency_code(currency)?;
+    let rate = match rate {
+        Some(rate) if rate > 0.0 => rate,
+        Some(_) => return Err(CcTrackerError::Invalid("The FX rate must be above 0".to_string())),
+        None => store
+            .list_fx_rates()?
+            .into_iter()
+            .find(|fx| fx.currency == currency)
+            .map(|fx| fx.rate)
+            .ok_or_else(|| {
+                CcTrackerError::Invalid(format!(
+                    "No FX rate stored for {}; pass --fx-rate or add one with `fx-rates set`",
+                    currency
+                ))
+            })?,
+    };
+    Ok((round_cents(amount * rate), OriginalAmount { amount, currency }))
+}
@@ -0,0 +1,807 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::dates::ISO_FORMAT;
+use crate::engine::{parse_date, ymd_to_days};
+use crate::models::{CategoryTotal, ImportPreview, PreviewRow, ReviewItem, Spending};
+use crate::period::valid_date;
+
+/// How to read one bank's CSV statement export.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportProfile {
+    /// Field delimiter (defaults to ',')
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// Date layout using YYYY, MM and DD, e.g. "DD/MM/YYYY"; defaults to the
+    /// config's `date_format`, or else "YYYY-MM-DD"
+    pub date_format: Option<String>,
+    /// "positive" if purchases are positive amounts, "negative" if they are negative
+    #[serde(default = "default_amount_sign")]
+    pub amount_sign: String,
+    /// Card that imported rows are recorded against unless overridden
+    pub card_id: Option<i64>,
+    /// Category for rows without one and with no history for the merchant
+    pub default_category: Option<String>,
+    /// Share of a merchant's past transactions (0 to 1) that must agree on a
+    /// category before it is assigned automatically; below this the row goes
+    /// to the review queue
+    #[serde(default = "default_category_confidence")]
+    pub category_confidence: f64,
+    pub columns: ColumnMapping,
+    /// How imported rows are matched against spending already on the card
+    #[serde(default)]
+    pub dedupe: DedupeRules,
+}
+
+/// Header names of the statement columns.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnMapping {
+    pub date: String,
+    pub amount: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+}
+
+/// A transaction field that can be compared when looking for duplicates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeField {
+    Date,
+    Amount,
+    Category,
+    Description,
+}
+
+/// Criteria for treating an imported row as a duplicate of existing spending.
+///
+/// The defaults match on the same date and the exact amount. Widen the
+/// tolerances for banks whose statement dates or amounts drift from what was
+/// entered by hand, or add `description` for banks that list many identical
+/// charges.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DedupeRules {
+    /// Set to false to import every row
+    pub enabled: bool,
+    /// Fields that must all match
+    pub fields: Vec<DedupeField>,
+    /// Largest amount difference still treated as the same charge
+    pub amount_tolerance: f64,
+    /// Largest number of days between the two dates
+    pub date_window_days: i32,
+    /// Minimum description similarity from 0 to 1 (1 = identical ignoring case and punctuation)
+    pub description_similarity: f64,
+}
+
+impl Default for DedupeRules {
+    fn default() -> Self {
+        DedupeRules {
+            enabled: true,
+            fields: vec![DedupeField::Date, DedupeField::Amount],
+            amount_tolerance: 0.0,
+            date_window_days: 0,
+            description_similarity: 1.0,
+        }
+    }
+}
+
+impl DedupeRules {
+    /// Whether `row` matches an existing transaction whose date is `days_apart`
+    /// days away. A missing description or category on the imported side, or
+    /// a missing description on the existing side, does not prevent a match,
+    /// since hand-entered spending usually has none.
+    pub fn is_duplicate(&self, row: &ImportedRow, existing: &Spending, days_apart: i32) -> bool {
+        self.matches(row, existing.amount, Some(&existing.category), existing.description.as_deref(), days_apart)
+    }
+
+    /// Whether `row` matches a row of an earlier import still waiting in the
+    /// review queue, `days_apart` days away. Its category is not decided
+    /// yet, so it never prevents a match.
+    pub fn is_queued(&self, row: &ImportedRow, queued: &ReviewItem, days_apart: i32) -> bool {
+        self.matches(row, queued.amount, None, queued.description.as_deref(), days_apart)
+    }
+
+    fn matches(
+        &self,
+        row: &ImportedRow,
+        amount: f64,
+        category: Option<&str>,
+        description: Option<&str>,
+        days_apart: i32,
+    ) -> bool {
+        self.enabled
+            && self.fields.iter().all(|field| match field {
+                DedupeField::Date => days_apart.abs() <= self.date_window_days,
+                // Small epsilon so a zero tolerance survives float rounding
+                DedupeField::Amount => (row.amount - amount).abs() <= self.amount_tolerance + 1e-9,
+                DedupeField::Category => match (&row.category, category) {
+                    (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+                    _ => true,
+                },
+                DedupeField::Description => match (&row.description, description) {
+                    (Some(a), Some(b)) => description_similarity(a, b) >= self.description_similarity,
+                    _ => true,
+                },
+            })
+    }
+}
+
+/// Uppercases and keeps only letters and digits, single-spaced.
+fn normalize_description(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_ascii_uppercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Key identifying a merchant across statements: the normalized description
+/// without purely numeric words such as store or reference numbers.
+pub fn merchant_key(description: &str) -> String {
+    normalize_description(description)
+        .split(' ')
+        .filter(|w| !w.chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// How often each category was used per merchant key.
+pub type MerchantHistory = HashMap<String, HashMap<String, usize>>;
+
+/// Counts how each merchant's past spending was categorized, from
+/// (description, category) pairs.
+pub fn merchant_history<I: IntoIterator<Item = (String, String)>>(spending: I) -> MerchantHistory {
+    let mut history = MerchantHistory::new();
+    for (description, category) in spending {
+        *history
+            .entry(merchant_key(&description))
+            .or_default()
+            .entry(category)
+            .or_default() += 1;
+    }
+    history
+}
+
+/// The category most often used for this description's merchant and the share
+/// of its transactions that used it, or None if the merchant is new.
+pub fn learned_category(history: &MerchantHistory, description: &str) -> Option<(String, f64)> {
+    let counts = history.get(&merchant_key(description))?;
+    let total: usize = counts.values().sum();
+    // Ties go to the alphabetically first category so results are stable
+    let (category, count) = counts
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))?;
+    Some((category.clone(), *count as f64 / total as f64))
+}
+
+/// Where an imported row's category comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CategoryDecision {
+    /// Record the row with this category
+    Assign(String),
+    /// Hold the row for review, with the best guess and its confidence if any
+    Review(Option<(String, f64)>),
+}
+
+/// Picks the category for an imported row: the statement's own category,
+/// then the merchant's history when it is confident enough, then the
+/// profile's default for merchants never seen before. Anything else needs a
+/// human to decide.
+pub fn decide_category(row: &ImportedRow, history: &MerchantHistory, profile: &ImportProfile) -> CategoryDecision {
+    if let Some(category) = &row.category {
+        return CategoryDecision::Assign(category.clone());
+    }
+    match row.description.as_deref().and_then(|d| learned_category(history, d)) {
+        Some((category, confidence)) if confidence >= profile.category_confidence => {
+            CategoryDecision::Assign(category)
+        }
+        Some(guess) => CategoryDecision::Review(Some(guess)),
+        None => match &profile.default_category {
+            Some(category) => CategoryDecision::Assign(category.clone()),
+            None => CategoryDecision::Review(None),
+        },
+    }
+}
+
+/// Works out what importing `rows` onto a card holding `existing` spending,
+/// with `queued` rows of earlier imports awaiting review, would do, in row
+/// order: None for rows matching either (per the profile's dedupe rules),
+/// otherwise the category decision.
+///
+/// Each existing transaction or queued row absorbs at most one imported
+/// row, so repeated identical charges on a statement are still imported.
+pub fn plan_import(
+    rows: &[ImportedRow],
+    mut existing: Vec<Spending>,
+    mut queued: Vec<ReviewItem>,
+    history: &MerchantHistory,
+    profile: &ImportProfile,
+) -> Vec<Option<CategoryDecision>> {
+    let days = |date: &str| {
+        let (y, m, d) = parse_date(date);
+        ymd_to_days(y, m, d)
+    };
+    let mut plan = Vec::new();
+    for row in rows {
+        let row_days = days(&row.date);
+        if let Some(index) =
+            existing.iter().position(|spending| profile.dedupe.is_duplicate(row, spending, row_days - days(&spending.date)))
+        {
+            existing.swap_remove(index);
+            plan.push(None);
+        } else if let Some(index) =
+            queued.iter().position(|item| profile.dedupe.is_queued(row, item, row_days - days(&item.date)))
+        {
+            queued.swap_remove(index);
+            plan.push(None);
+        } else {
+            plan.push(Some(decide_category(row, history, profile)));
+        }
+    }
+    plan
+}
+
+/// Summarizes an import plan (see `plan_import`) for confirmation.
+pub fn preview_plan(card_id: i64, rows: &[ImportedRow], plan: Vec<Option<CategoryDecision>>) -> ImportPreview {
+    let mut preview = ImportPreview {
+        card_id,
+        rows: rows.len(),
+        ..ImportPreview::default()
+    };
+
+    for (row, decision) in rows.iter().zip(plan) {
+        match decision {
+            None => preview.duplicates.push(PreviewRow {
+                date: row.date.clone(),
+                amount: row.amount,
+                description: row.description.clone(),
+            }),
+            Some(CategoryDecision::Assign(category)) => {
+                preview.to_import += 1;
+                preview.total_amount += row.amount;
+                match preview.categories.iter_mut().find(|t| t.category == category) {
+                    Some(total) => {
+                        total.count += 1;
+                        total.amount += row.amount;
+                    }
+                    None => preview.categories.push(CategoryTotal {
+                        category,
+                        count: 1,
+                        amount: row.amount,
+                    }),
+                }
+            }
+            Some(CategoryDecision::Review(_)) => {
+                preview.to_review += 1;
+                let merchant = row.description.clone().unwrap_or_else(|| "(no description)".to_string());
+                if !preview.unmapped_merchants.contains(&merchant) {
+                    preview.unmapped_merchants.push(merchant);
+                }
+            }
+        }
+    }
+    preview.categories.sort_by(|a, b| a.category.cmp(&b.category));
+    preview.unmapped_merchants.sort();
+    preview
+}
+
+/// Similarity of two descriptions from 0 to 1, based on the edit distance
+/// between their normalized forms.
+pub fn description_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalize_description(a).chars().collect();
+    let b: Vec<char> = normalize_description(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    // Levenshtein distance, one row at a time
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// Built-in profiles for common issuers' card CSV exports, written as they
+/// would be in config.toml. `--profile` falls back to them, and a profile
+/// in config.toml can start from one with `preset = "dbs"`.
+const PRESETS: &[(&str, &str)] = &[
+    (
+        "amex",
+        r#"
+        date_format = "MM/DD/YYYY"
+        columns = { date = "Date", amount = "Amount", description = "Description" }
+        "#,
+    ),
+    (
+        "chase",
+        r#"
+        date_format = "MM/DD/YYYY"
+        amount_sign = "negative"
+        columns = { date = "Transaction Date", amount = "Amount", description = "Description" }
+        "#,
+    ),
+    (
+        "citi",
+        r#"
+        date_format = "DD/MM/YYYY"
+        columns = { date = "Date", amount = "Amount", description = "Description" }
+        "#,
+    ),
+    (
+        "dbs",
+        r#"
+        date_format = "DD/MM/YYYY"
+        amount_sign = "negative"
+        columns = { date = "Transaction Date", amount = "Amount", description = "Description" }
+        "#,
+    ),
+    (
+        "uob",
+        r#"
+        date_format = "DD/MM/YYYY"
+        columns = { date = "Transaction Date", amount = "Transaction Amount(Local)", description = "Description" }
+        "#,
+    ),
+];
+
+/// Names of the built-in presets, alphabetically.
+pub fn preset_names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, _)| *name).collect()
+}
+
+fn preset_table(name: &str) -> Option<toml::Table> {
+    let (_, text) = PRESETS.iter().find(|(preset, _)| preset.eq_ignore_ascii_case(name))?;
+    Some(text.parse().expect("built-in presets are valid TOML"))
+}
+
+/// The built-in preset of this name, case-insensitively.
+pub fn preset(name: &str) -> Option<ImportProfile> {
+    let table = preset_table(name)?;
+    Some(table.try_into().expect("built-in presets are valid profiles"))
+}
+
+/// Reads `[import_profiles]`; see `profile_from_table`.
+pub fn deserialize_profiles<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, ImportProfile>, D::Error> {
+    use serde::de::Error;
+
+    let tables: HashMap<String, toml::Table> = HashMap::deserialize(deserializer)?;
+    let mut profiles = HashMap::new();
+    for (name, table) in tables {
+        let profile = profile_from_table(&format!("import_profiles.{}", name), table).map_err(D::Error::custom)?;
+        profiles.insert(name, profile);
+    }
+    Ok(profiles)
+}
+
+/// Reads a mapping file passed with `--mapping`: a single import profile,
+/// with the keys of an `[import_profiles]` entry at the top level.
+pub fn parse_mapping(text: &str, source: &str) -> Result<ImportProfile, String> {
+    let table: toml::Table = text.parse().map_err(|e| format!("{}: {}", source, e))?;
+    profile_from_table(source, table)
+}
+
+/// Reads a profile, laying it over the `preset` it names, if any: its own
+/// keys win, and `columns` and `dedupe` are merged key by key. `source`
+/// names where it came from in errors.
+fn profile_from_table(source: &str, mut table: toml::Table) -> Result<ImportProfile, String> {
+    if let Some(preset) = table.remove("preset") {
+        let preset = preset.as_str().unwrap_or_default();
+        let mut base = preset_table(preset).ok_or_else(|| {
+            format!("{}: unknown preset '{}' (presets: {})", source, preset, preset_names().join(", "))
+        })?;
+        for (key, value) in table {
+            match (base.get_mut(&key), value) {
+                (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => base.extend(overrides),
+                (_, value) => {
+                    base.insert(key, value);
+                }
+            }
+        }
+        table = base;
+    }
+    table.try_into().map_err(|e| format!("{}: {}", source, e))
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_amount_sign() -> String {
+    "positive".to_string()
+}
+
+fn default_category_confidence() -> f64 {
+    0.8
+}
+
+/// A statement row normalized for insertion as spending.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedRow {
+    /// YYYY-MM-DD
+    pub date: String,
+    /// Always positive
+    pub amount: f64,
+    pub description: Option<String>,
+    /// From the statement's category column or a categorization rule
+    pub category: Option<String>,
+    /// Set by a categorization rule
+    pub payment_category: Option<String>,
+}
+
+/// Parses a date laid out per `format` (YYYY, MM and DD separated by any
+/// non-digit characters) into YYYY-MM-DD. Each part must have as many
+/// digits as its token allows, so a date in another layout is rejected
+/// rather than read as a wrong date; so is a day its month does not have.
+pub fn parse_date_with_format(value: &str, format: &str) -> Option<String> {
+    let tokens: Vec<&str> = format
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let parts: Vec<&str> = value
+        .trim()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if tokens.len() != 3 || parts.len() != 3 {
+        return None;
+    }
+
+    let (mut year, mut month, mut day) = (None, None, None);
+    for (token, part) in tokens.iter().zip(&parts) {
+        let number: i32 = part.parse().ok()?;
+        match (token.to_ascii_uppercase().as_str(), part.len()) {
+            ("YYYY", 4) => year = Some(number),
+            ("YY", 2) => year = Some(2000 + number),
+            ("MM" | "M", 1..=2) => month = Some(number),
+            ("DD" | "D", 1..=2) => day = Some(number),
+            _ => return None,
+        }
+    }
+
+    let date = format!("{:04}-{:02}-{:02}", year?, month?, day?);
+    valid_date(&date).then_some(date)
+}
+
+/// Parses an amount such as "$1,234.50", "-12.00" or "(12.00)".
+fn parse_amount(value: &str) -> Option<f64> {
+    let trimmed = value.trim();
+    let negative = trimmed.starts_with('-') || (trimmed.starts_with('(') && trimmed.ends_with(')'));
+    let digits: String = trimmed
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let amount: f64 = digits.parse().ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Reads a statement CSV (with a header row) using the profile's mapping.
+///
+/// Rows whose amount has the opposite sign to purchases (refunds, payments)
+/// are skipped. Returns the parsed rows, or an error naming the first row that
+/// could not be read.
+pub fn parse_statement<R: Read>(reader: R, profile: &ImportProfile) -> Result<Vec<ImportedRow>, String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .delimiter(profile.delimiter as u8)
+        .flexible(true)
+        .from_reader(reader);
+
+    let headers = csv_reader.headers().map_err(|e| e.to_string())?.clone();
+    let column = |name: &str| -> Result<usize, String> {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("Column '{}' not found in statement header", name))
+    };
+    let date_col = column(&profile.columns.date)?;
+    let amount_col = column(&profile.columns.amount)?;
+    let description_col = profile.columns.description.as_deref().map(column).transpose()?;
+    let category_col = profile.columns.category.as_deref().map(column).transpose()?;
+    let purchases_negative = profile.amount_sign.eq_ignore_ascii_case("negative");
+    let date_format = profile.date_format.as_deref().unwrap_or(ISO_FORMAT);
+
+    let mut rows = Vec::new();
+    for (index, record) in csv_reader.records().enumerate() {
+        // +2: one for the header, one for 1-based line numbers
+        let line = index + 2;
+        let record = record.map_err(|e| format!("Line {}: {}", line, e))?;
+        let field = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let date = parse_date_with_format(field(date_col), date_format).ok_or_else(|| {
+            format!("Line {}: cannot read date '{}' as {}", line, field(date_col), date_format)
+        })?;
+        let raw_amount = parse_amount(field(amount_col))
+            .ok_or_else(|| format!("Line {}: cannot read amount '{}'", line, field(amount_col)))?;
+        let amount = if purchases_negative { -raw_amount } else { raw_amount };
+        if amount <= 0.0 {
+            continue;
+        }
+
+        let description = description_col
+            .map(field)
+            .filter(|d| !d.is_empty())
+            .map(|d| d.to_string());
+        let category = category_col
+            .map(field)
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_lowercase());
+
+        rows.push(ImportedRow {
+            date,
+            amount,
+            description,
+            category,
+            payment_category: None,
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dbs_profile() -> ImportProfile {
+        ImportProfile {
+            delimiter: ',',
+            date_format: Some("DD/MM/YYYY".to_string()),
+            amount_sign: "negative".to_string(),
+            card_id: Some(1),
+            default_category: Some("shopping".to_string()),
+            category_confidence: 0.8,
+            columns: ColumnMapping {
+                date: "Transaction Date".to_string(),
+                amount: "Amount".to_string(),
+                description: Some("Description".to_string()),
+                category: None,
+            },
+            dedupe: DedupeRules::default(),
+        }
+    }
+
+    fn existing(amount: f64, category: &str, description: Option<&str>) -> Spending {
+        Spending {
+            id: 1,
+            card_id: 1,
+            amount,
+            category: category.to_string(),
+            date: "2026-02-05".to_string(),
+            miles_earned: 0.0,
+            status: "posted".to_string(),
+            description: description.map(|d| d.to_string()),
+            payment_category: None,
+            tag: None,
+            foreign: false,
+            original_amount: None,
+            original_currency: None,
+            recorded_on: None,
+            cashback: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_date_with_format() {
+        assert_eq!(parse_date_with_format("05/02/2026", "DD/MM/YYYY").as_deref(), Some("2026-02-05"));
+        assert_eq!(parse_date_with_format("2026-02-05", "YYYY-MM-DD").as_deref(), Some("2026-02-05"));
+        assert_eq!(parse_date_with_format("02-05-26", "MM-DD-YY").as_deref(), Some("2026-02-05"));
+        assert_eq!(parse_date_with_format("31/13/2026", "DD/MM/YYYY"), None);
+        assert_eq!(parse_date_with_format("31/02/2026", "DD/MM/YYYY"), None);
+        assert_eq!(parse_date_with_format("29/02/2028", "DD/MM/YYYY").as_deref(), Some("2028-02-29"));
+        assert_eq!(parse_date_with_format("yesterday", "DD/MM/YYYY"), None);
+        // A day-first date read with the default layout is refused, not mangled
+        assert_eq!(parse_date_with_format("12/03/26", "YYYY-MM-DD"), None);
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("$1,234.50"), Some(1234.5));
+        assert_eq!(parse_amount("-12.00"), Some(-12.0));
+        assert_eq!(parse_amount("(12.00)"), Some(-12.0));
+        assert_eq!(parse_amount("n/a"), None);
+    }
+
+    #[test]
+    fn test_parse_statement_with_profile() {
+        let csv = "Transaction Date,Description,Amount\n\
+                   05/02/2026,COLD STORAGE,-42.50\n\
+                   06/02/2026,PAYMENT THANK YOU,500.00\n\
+                   07/02/2026,GRAB RIDE,\"-1,012.00\"\n";
+        let rows = parse_statement(csv.as_bytes(), &dbs_profile()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].date, "2026-02-05");
+        assert_eq!(rows[0].amount, 42.5);
+        assert_eq!(rows[0].description.as_deref(), Some("COLD STORAGE"));
+        assert_eq!(rows[0].category, None);
+        assert_eq!(rows[1].amount, 1012.0);
+    }
+
+    #[test]
+    fn test_parse_statement_reports_bad_rows() {
+        let csv = "Transaction Date,Description,Amount\n2026/02/05,X,-1.00\n";
+        let err = parse_statement(csv.as_bytes(), &dbs_profile()).unwrap_err();
+        assert!(err.starts_with("Line 2"));
+
+        let csv = "Date,Amount\n05/02/2026,-1.00\n";
+        let err = parse_statement(csv.as_bytes(), &dbs_profile()).unwrap_err();
+        assert!(err.contains("Transaction Date"));
+    }
+
+    #[test]
+    fn test_profile_from_toml() {
+        let profile: ImportProfile = toml::from_str(
+            r#"
+            date_format = "DD/MM/YYYY"
+            amount_sign = "negative"
+            card_id = 2
+            [columns]
+            date = "Date"
+            amount = "Amount"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(profile.delimiter, ',');
+        assert_eq!(profile.card_id, Some(2));
+        assert!(profile.columns.category.is_none());
+    }
+
+    #[test]
+    fn test_presets_and_profiles_built_on_them() {
+        for name in preset_names() {
+            assert!(preset(name).is_some(), "{}", name);
+        }
+        let chase = preset("Chase").unwrap();
+        let csv = "Transaction Date,Post Date,Description,Category,Type,Amount,Memo\n\
+                   02/05/2026,02/06/2026,STARBUCKS 1234,Food & Drink,Sale,-5.75,\n\
+                   02/07/2026,02/07/2026,Payment Thank You,,Payment,500.00,\n";
+        let rows = parse_statement(csv.as_bytes(), &chase).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].date.as_str(), rows[0].amount), ("2026-02-05", 5.75));
+
+        let config: crate::config::Config = toml::from_str(
+            r#"
+            [import_profiles.mine]
+            preset = "dbs"
+            card_id = 2
+            columns = { description = "Merchant" }
+            "#,
+        )
+        .unwrap();
+        let mine = &config.import_profiles["mine"];
+        assert_eq!((mine.card_id, mine.amount_sign.as_str()), (Some(2), "negative"));
+        assert_eq!(mine.columns.date, "Transaction Date");
+        assert_eq!(mine.columns.description.as_deref(), Some("Merchant"));
+
+        let err = toml::from_str::<crate::config::Config>("[import_profiles.x]\npreset = \"hsbc\"\n").unwrap_err();
+        assert!(err.to_string().contains("import_profiles.x: unknown preset 'hsbc'"), "{}", err);
+    }
+
+    #[test]
+    fn test_mapping_file() {
+        let mapping = parse_mapping(
+            r#"
+            date_format = "DD-MM-YYYY"
+            amount_sign = "negative"
+            [columns]
+            date = "Posted"
+            amount = "Debit"
+            description = "Details"
+            category = "Type"
+            "#,
+            "mapping.toml",
+        )
+        .unwrap();
+        let csv = "Posted,Details,Type,Debit\n05-02-2026,TOAST BOX,dining,-4.20\n";
+        let rows = parse_statement(csv.as_bytes(), &mapping).unwrap();
+        assert_eq!((rows[0].date.as_str(), rows[0].amount), ("2026-02-05", 4.2));
+        assert_eq!(rows[0].category.as_deref(), Some("dining"));
+
+        let uob = parse_mapping("preset = \"uob\"\ncard_id = 3\n", "uob.toml").unwrap();
+        assert_eq!(uob.card_id, Some(3));
+        let err = parse_mapping("[columns]\ndate = \"Date\"\n", "bad.toml").unwrap_err();
+        assert!(err.starts_with("bad.toml: "), "{}", err);
+    }
+
+    #[test]
+    fn test_description_similarity() {
+        assert_eq!(description_similarity("Grab*Ride", "GRAB RIDE"), 1.0);
+        assert!(description_similarity("COLD STORAGE #123", "COLD STORAGE #124") > 0.9);
+        assert!(description_similarity("COLD STORAGE", "NETFLIX") < 0.5);
+    }
+
+    #[test]
+    fn test_default_dedupe_matches_date_and_amount() {
+        let rules = DedupeRules::default();
+        let row = ImportedRow {
+            date: "2026-02-05".to_string(),
+            amount: 42.5,
+            description: Some("COLD STORAGE".to_string()),
+            category: Some("groceries".to_string()),
+            payment_category: None,
+        };
+        assert!(rules.is_duplicate(&row, &existing(42.5, "dining", None), 0));
+        assert!(!rules.is_duplicate(&row, &existing(42.5, "dining", None), 1));
+        assert!(!rules.is_duplicate(&row, &existing(42.51, "dining", None), 0));
+
+        let disabled = DedupeRules { enabled: false, ..DedupeRules::default() };
+        assert!(!disabled.is_duplicate(&row, &existing(42.5, "dining", None), 0));
+    }
+
+    #[test]
+    fn test_lenient_dedupe_from_toml() {
+        let rules: DedupeRules = toml::from_str(
+            r#"
+            fields = ["date", "amount", "description"]
+            amount_tolerance = 0.5
+            date_window_days = 2
+            description_similarity = 0.8
+            "#,
+        )
+        .unwrap();
+        assert!(rules.enabled);
+        let row = ImportedRow {
+            date: "2026-02-07".to_string(),
+            amount: 42.5,
+            description: Some("COLD STORAGE #124".to_string()),
+            category: Some("groceries".to_string()),
+            payment_category: None,
+        };
+        assert!(rules.is_duplicate(&row, &existing(42.2, "dining", Some("Cold Storage 123")), 2));
+        assert!(!rules.is_duplicate(&row, &existing(42.2, "dining", Some("NETFLIX")), 2));
+        assert!(!rules.is_duplicate(&row, &existing(42.2, "dining", None), 3));
+        assert!(toml::from_str::<DedupeRules>(r#"fields = ["merchant"]"#).is_err());
+    }
+
+    #[test]
+    fn test_merchant_key_ignores_store_numbers() {
+        assert_eq!(merchant_key("Cold Storage #123"), "COLD STORAGE");
+        assert_eq!(merchant_key("COLD STORAGE 456"), "COLD STORAGE");
+    }
+
+    #[test]
+    fn test_decide_category() {
+        let profile = dbs_profile();
+        let mut history = MerchantHistory::new();
+        history.insert("COLD STORAGE".to_string(), HashMap::from([("groceries".to_string(), 9)]));
+        history.insert(
+            "AMAZON".to_string(),
+            HashMap::from([("shopping".to_string(), 3), ("entertainment".to_string(), 2)]),
+        );
+        let row = |description: &str, category: Option<&str>| ImportedRow {
+            date: "2026-02-05".to_string(),
+            amount: 10.0,
+            description: Some(description.to_string()),
+            category: category.map(|c| c.to_string()),
+            payment_category: None,
+        };
+
+        assert_eq!(
+            decide_category(&row("COLD STORAGE #77", None), &history, &profile),
+            CategoryDecision::Assign("groceries".to_string())
+        );
+        assert_eq!(
+            decide_category(&row("AMAZON", None), &history, &profile),
+            CategoryDecision::Review(Some(("shopping".to_string(), 0.6)))
+        );
+        assert_eq!(
+            decide_category(&row("AMAZON", Some("travel")), &history, &profile),
+            CategoryDecision::Assign("travel".to_string())
+        );
+        assert_eq!(
+            decide_category(&row("NEW SHOP", None), &history, &profile),
+            CategoryDecision::Assign("shopping".to_string())
+        );
+        let no_default = ImportProfile { default_category: None, ..profile };
+        assert_eq!(
+            decide_category(&row("NEW SHOP", None), &history, &no_default),
+            CategoryDecision::Review(None)
+        );
+    }
+}
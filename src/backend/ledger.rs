@@ -0,0 +1,359 @@
+//! The miles ledger: each card's or program's balance as the bank should
+//! show it. Spending credits its miles once its statement posts,
+//! redemptions take miles off, and `adjust-miles` corrects the balance to
+//! what the bank shows. Miles expire batch by batch, under the card's or its
+//! program's expiry policy, with redemptions spending the soonest to expire.
+
+use std::collections::BTreeMap;
+
+use crate::engine::{days_between, expiry_date, miles_posting_date, round_cents};
+use crate::error::Result;
+use crate::models::{Card, ExpiringMiles, LedgerLine, MilesAccount, RewardType};
+use crate::pool::BalancesBy;
+use crate::store::Store;
+
+/// Whose miles a ledger entry changes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Account {
+    Card(i64),
+    /// A loyalty program, pooling the miles of the cards in it
+    Program(String),
+}
+
+impl Account {
+    /// The card ID or program name a ledger entry records the account by
+    pub fn parts(&self) -> (Option<i64>, Option<&str>) {
+        match self {
+            Account::Card(id) => (Some(*id), None),
+            Account::Program(name) => (None, Some(name.as_str())),
+        }
+    }
+}
+
+/// A change to a balance: miles credited from a statement's spending, or
+/// an entry from `adjust-miles` or a redemption
+struct Movement {
+    card_id: Option<i64>,
+    program: Option<String>,
+    date: String,
+    kind: String,
+    description: String,
+    miles: f64,
+}
+
+impl Movement {
+    fn belongs_to(&self, account: &Account, cards: &[Card]) -> bool {
+        match account {
+            Account::Card(id) => self.card_id == Some(*id),
+            Account::Program(name) => match self.card_id {
+                Some(card_id) => cards
+                    .iter()
+                    .any(|card| card.id == card_id && card.program.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(name))),
+                None => self.program.as_deref().is_some_and(|p| p.eq_ignore_ascii_case(name)),
+            },
+        }
+    }
+}
+
+/// Every change to a balance up to `as_of`, oldest first. Spending's miles
+/// are credited together on the date its statement posts them.
+fn movements<S: Store + ?Sized>(store: &S, cards: &[Card], as_of: &str) -> Result<Vec<Movement>> {
+    let mut credited: BTreeMap<(String, i64), (f64, usize)> = BTreeMap::new();
+    store.for_each_spending(None, None, &mut |s| {
+        let Some(card) = cards.iter().find(|card| card.id == s.card_id) else { return Ok(()) };
+        if s.status != "posted" || s.date.as_str() > as_of {
+            return Ok(());
+        }
+        let posts_on = miles_posting_date(card.statement_renewal_date, &card.cycle_overrides(), card.posting_delay, &s.date);
+        if posts_on.as_str() <= as_of {
+            let credit = credited.entry((posts_on, card.id)).or_default();
+            credit.0 += s.miles_earned;
+            credit.1 += 1;
+        }
+        Ok(())
+    })?;
+
+    let mut movements: Vec<Movement> = credited
+        .into_iter()
+        .map(|((date, card_id), (miles, purchases))| {
+            let card_name = cards.iter().find(|card| card.id == card_id).map_or("", |card| card.name.as_str());
+            Movement {
+                card_id: Some(card_id),
+                program: None,
+                date,
+                kind: "earned".to_string(),
+                description: format!("{} purchase{} on {}", purchases, if purchases == 1 { "" } else { "s" }, card_name),
+                miles: round_cents(miles),
+            }
+        })
+        .collect();
+    for entry in store.list_miles_entries()? {
+        if entry.date.as_str() > as_of {
+            continue;
+        }
+        movements.push(Movement {
+            card_id: entry.card_id,
+            program: entry.program,
+            date: entry.date,
+            kind: entry.kind,
+            description: entry.description.unwrap_or_else(|| "-".to_string()),
+            miles: entry.miles,
+        });
+    }
+    // Stable, so a day's credits come before its entries
+    movements.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(movements)
+}
+
+/// Each account's balance on `as_of`: one per card, or with `by` program
+/// one per program pooling its cards' miles (a card in no program keeps
+/// its own). Programs with entries of their own but no cards get a row too.
+/// Cashback cards earn no miles, so they have no account.
+pub fn accounts<S: Store + ?Sized>(store: &S, as_of: &str, by: BalancesBy) -> Result<Vec<MilesAccount>> {
+    let cards: Vec<Card> =
+        store.list_cards()?.into_iter().filter(|card| card.reward_type != RewardType::Cashback).collect();
+    let name_of = |card: &Card| match (by, &card.program) {
+        (BalancesBy::Program, Some(program)) => program.clone(),
+        _ => card.name.clone(),
+    };
+    // In the order accounts are first seen, names matched in any case
+    let mut rows: Vec<MilesAccount> = Vec::new();
+    fn row_for(rows: &mut Vec<MilesAccount>, name: String) -> &mut MilesAccount {
+        let at = match rows.iter().position(|row| row.account.eq_ignore_ascii_case(&name)) {
+            Some(at) => at,
+            None => {
+                rows.push(blank(name));
+                rows.len() - 1
+            }
+        };
+        &mut rows[at]
+    }
+    for card in &cards {
+        row_for(&mut rows, name_of(card));
+    }
+    for movement in movements(store, &cards, as_of)? {
+        let name = match movement.card_id {
+            Some(card_id) => match cards.iter().find(|card| card.id == card_id) {
+                Some(card) => name_of(card),
+                None => continue,
+            },
+            None => movement.program.clone().unwrap_or_default(),
+        };
+        let row = row_for(&mut rows, name);
+        match movement.kind.as_str() {
+            "earned" => row.earned = round_cents(row.earned + movement.miles),
+            "redemption" => row.redeemed = round_cents(row.redeemed - movement.miles),
+            _ => row.adjusted = round_cents(row.adjusted + movement.miles),
+        }
+        row.balance = round_cents(row.balance + movement.miles);
+    }
+    Ok(rows)
+}
+
+fn blank(account: String) -> MilesAccount {
+    MilesAccount {
+        account,
+        earned: 0.0,
+        redeemed: 0.0,
+        adjusted: 0.0,
+        balance: 0.0,
+    }
+}
+
+/// The account's changes up to `as_of`, oldest first, each with the
+/// balance after it.
+pub fn statement<S: Store + ?Sized>(store: &S, account: &Account, as_of: &str) -> Result<Vec<LedgerLine>> {
+    let cards = store.list_cards()?;
+    let mut balance = 0.0;
+    Ok(movements(store, &cards, as_of)?
+        .into_iter()
+        .filter(|movement| movement.belongs_to(account, &cards))
+        .map(|movement| {
+            balance = round_cents(balance + movement.miles);
+            LedgerLine {
+                date: movement.date,
+                kind: movement.kind,
+                description: movement.description,
+                miles: movement.miles,
+                balance,
+            }
+        })
+        .collect())
+}
+
+/// The account's balance on `as_of`.
+pub fn balance<S: Store + ?Sized>(store: &S, account: &Account, as_of: &str) -> Result<f64> {
+    Ok(statement(store, account, as_of)?.last().map_or(0.0, |line| line.balance))
+}
+
+/// The unspent miles that expire after `as_of` and no later than `until`,
+/// soonest first. A card's own `miles_expiry_months` applies to its miles,
+/// or else its program's; miles without either never expire. Redemptions
+/// and downward corrections take the miles closest to expiring first.
+pub fn expiring<S: Store + ?Sized>(store: &S, as_of: &str, until: &str) -> Result<Vec<ExpiringMiles>> {
+    let cards = store.list_cards()?;
+    let programs = store.list_programs()?;
+    let months_for = |movement: &Movement| {
+        let program = match movement.card_id {
+            Some(card_id) => {
+                let card = cards.iter().find(|card| card.id == card_id)?;
+                if card.miles_expiry_months.is_some() {
+                    return card.miles_expiry_months;
+                }
+                card.program.clone()?
+            }
+            None => movement.program.clone()?,
+        };
+        programs.iter().find(|p| p.name.eq_ignore_ascii_case(&program)).and_then(|p| p.expiry_months)
+    };
+
+    struct Batch {
+        movement: Movement,
+        expires_on: Option<String>,
+        left: f64,
+    }
+    let mut batches: Vec<Batch> = Vec::new();
+    for movement in movements(store, &cards, as_of)? {
+        if movement.miles > 0.0 {
+            let expires_on = months_for(&movement).map(|months| expiry_date(&movement.date, months));
+            batches.push(Batch { left: movement.miles, expires_on, movement });
+            continue;
+        }
+        let account = match (movement.card_id, &movement.program) {
+            (Some(card_id), _) => Account::Card(card_id),
+            (None, Some(program)) => Account::Program(program.clone()),
+            (None, None) => continue,
+        };
+        let mut open: Vec<&mut Batch> = batches
+            .iter_mut()
+            .filter(|batch| batch.left > 0.0 && batch.movement.belongs_to(&account, &cards))
+            .filter(|batch| batch.expires_on.as_deref().is_none_or(|expires_on| expires_on >= movement.date.as_str()))
+            .collect();
+        // Stable, so batches expiring together are spent oldest first
+        open.sort_by_key(|batch| (batch.expires_on.is_none(), batch.expires_on.clone()));
+        let mut owed = -movement.miles;
+        for batch in open {
+            let used = owed.min(batch.left);
+            batch.left = round_cents(batch.left - used);
+            owed -= used;
+            if owed <= 0.0 {
+                break;
+            }
+        }
+    }
+
+    let mut rows: Vec<ExpiringMiles> = batches
+        .into_iter()
+        .filter(|batch| batch.left > 0.0)
+        .filter_map(|batch| {
+            let expires_on = batch.expires_on?;
+            if expires_on.as_str() <= as_of || expires_on.as_str() > until {
+                return None;
+            }
+            let account = match batch.movement.card_id {
+                Some(card_id) => cards.iter().find(|card| card.id == card_id)?.name.clone(),
+                None => batch.movement.program.clone()?,
+            };
+            Some(ExpiringMiles {
+                account,
+                credited_on: batch.movement.date,
+                days_left: days_between(as_of, &expires_on),
+                expires_on,
+                miles: batch.left,
+            })
+        })
+        .collect();
+    rows.sort_by(|a, b| a.expires_on.cmp(&b.expires_on));
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::{NewCard, ProgramKind};
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_ledger_credits_posted_miles_and_entries() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str, program: Option<&str>| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    miles_per_dollar: 2.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    program: program.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let altitude = card("Altitude", Some("KrisFlyer"));
+        let rewards = card("Rewards", Some("krisflyer"));
+        db::add_spending(&conn, altitude, 100.0, "dining", "2026-01-05", None, false, None).unwrap();
+        db::add_spending(&conn, altitude, 50.0, "dining", "2026-01-20", None, false, None).unwrap();
+        db::add_spending(&conn, rewards, 10.0, "dining", "2026-02-03", None, false, None).unwrap();
+        db::add_miles_entry(&conn, Some(altitude), None, "adjustment", -20.0, "2026-02-10", Some("bank rounding")).unwrap();
+        db::add_miles_entry(&conn, None, Some("KrisFlyer"), "redemption", -100.0, "2026-02-15", None).unwrap();
+
+        // January's 300 miles post on Friday 2026-01-30 (the 1st is a Sunday);
+        // February's are still pending
+        let lines = statement(&conn, &Account::Card(altitude), "2026-02-20").unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].date.as_str(), lines[0].description.as_str()), ("2026-01-30", "2 purchases on Altitude"));
+        assert_eq!((lines[1].miles, lines[1].balance), (-20.0, 280.0));
+
+        let by_card = accounts(&conn, "2026-02-20", BalancesBy::Card).unwrap();
+        let names: Vec<&str> = by_card.iter().map(|a| a.account.as_str()).collect();
+        assert_eq!(names, ["Altitude", "Rewards", "KrisFlyer"]);
+        assert_eq!((by_card[0].earned, by_card[0].adjusted, by_card[0].balance), (300.0, -20.0, 280.0));
+
+        let by_program = accounts(&conn, "2026-03-05", BalancesBy::Program).unwrap();
+        assert_eq!(by_program.len(), 1);
+        assert_eq!((by_program[0].earned, by_program[0].redeemed, by_program[0].balance), (320.0, 100.0, 200.0));
+        assert_eq!(balance(&conn, &Account::Program("KRISFLYER".into()), "2026-03-05").unwrap(), 200.0);
+    }
+
+    #[test]
+    fn test_expiring_miles_spends_the_soonest_first() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        db::add_program(&conn, "KrisFlyer", ProgramKind::Airline, Some(12)).unwrap();
+        let card = |name: &str, miles_expiry_months: Option<i32>| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    miles_per_dollar: 1.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 10,
+                    program: Some("KrisFlyer".to_string()),
+                    miles_expiry_months,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let altitude = card("Altitude", None);
+        let rewards = card("Rewards", Some(24));
+        // Credited on 2025-02-10 and 2025-03-10
+        db::add_spending(&conn, altitude, 1000.0, "dining", "2025-01-15", None, false, None).unwrap();
+        db::add_spending(&conn, altitude, 500.0, "dining", "2025-02-15", None, false, None).unwrap();
+        db::add_spending(&conn, rewards, 700.0, "dining", "2025-01-15", None, false, None).unwrap();
+        db::add_miles_entry(&conn, None, Some("KrisFlyer"), "redemption", -1200.0, "2025-06-01", None).unwrap();
+
+        // The redemption takes Altitude's February batch and 200 of March's,
+        // leaving Rewards' miles, which last two years, untouched
+        let rows = expiring(&conn, "2026-01-01", "2026-04-30").unwrap();
+        let got: Vec<(&str, &str, f64)> = rows.iter().map(|r| (r.account.as_str(), r.expires_on.as_str(), r.miles)).collect();
+        assert_eq!(got, [("Altitude", "2026-03-31", 300.0)]);
+        assert_eq!(rows[0].days_left, 89);
+
+        let later = expiring(&conn, "2026-04-01", "2027-03-01").unwrap();
+        assert_eq!((later[0].account.as_str(), later[0].expires_on.as_str(), later[0].miles), ("Rewards", "2027-02-28", 700.0));
+    }
+}
@@ -0,0 +1,70 @@
+//! Credit card miles tracking: cards and their reward rules, spending and
+//! the miles it earns, best-card recommendations and balance reports.
+//!
+//! [`CcTracker`] is the entry point for embedding the tracker in another
+//! program; the modules underneath are what the `backend` binary's CLI and
+//! REST API are built on.
+//!
+//! ```no_run
+//! use cc_tracker::CcTracker;
+//! use cc_tracker::models::{NewCard, RecommendOptions};
+//!
+//! let tracker = CcTracker::open("cc_tracker.db")?;
+//! let card_id = tracker.add_card(&NewCard {
+//!     name: "DBS Altitude".to_string(),
+//!     categories: vec!["dining".to_string()],
+//!     payment_categories: vec!["contactless".to_string()],
+//!     miles_per_dollar: 3.0,
+//!     block_size: 1.0,
+//!     statement_renewal_date: 15,
+//!     ..NewCard::default()
+//! })?;
+//! tracker.record_spending(card_id, 42.50, "dining", "2026-03-05", None)?;
+//! let ranked = tracker.recommend("dining", 50.0, "contactless", "2026-03-06", &RecommendOptions::default())?;
+//! # Ok::<(), cc_tracker::CcTrackerError>(())
+//! ```
+
+pub mod backup;
+pub mod basket;
+pub mod budget;
+pub mod bench;
+pub mod card_file;
+pub mod chart;
+pub mod cli;
+pub mod compare;
+pub mod config;
+pub mod dates;
+pub mod db;
+pub mod digest;
+pub mod due;
+pub mod encrypt;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod fees;
+pub mod forecast;
+pub mod fx;
+pub mod import;
+pub mod ledger;
+pub mod merge;
+pub mod models;
+pub mod overview;
+pub mod period;
+#[cfg(feature = "postgres")]
+pub mod pg;
+pub mod pivot;
+pub mod pool;
+pub mod report;
+pub mod rollover;
+pub mod rules;
+pub mod statement;
+pub mod store;
+pub mod suggest;
+pub mod template;
+mod tracker;
+pub mod trip;
+pub mod velocity;
+pub mod whatif;
+
+pub use error::CcTrackerError;
+pub use tracker::CcTracker;
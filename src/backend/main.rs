@@ -1,6 +1,3 @@
-mod db;
-mod models;
-
 use axum::{
     extract::{Query, State},
     http::StatusCode,
@@ -8,18 +5,30 @@ use axum::{
     routing::{delete, get, post},
     Json, Router,
 };
-use rusqlite::Connection;
+use clap::Parser;
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
 use tower_http::cors::{Any, CorsLayer};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use models::{Card, CardRecommendation, Spending, DEFAULT_CATEGORIES, DEFAULT_PAYMENT_CATEGORIES};
+use cc_tracker::{cli, config, dates, db, digest, fx, models, store, CcTrackerError};
+#[cfg(feature = "postgres")]
+use cc_tracker::pg;
+use models::{
+    CapUnit, Card, CardBalance, CardRecommendation, NewCard, RecommendOptions, RewardType, Spending,
+    SpendingCursor, SpendingPage, SplitSuggestion,
+};
+use db::OpenMode;
+use store::{AsyncStore, SharedStore};
 
 /// Shared application state
 #[derive(Clone)]
 struct AppState {
-    db: Arc<Mutex<Connection>>,
+    store: AsyncStore,
+    /// Settings from config.toml, for the defaults of cards added without categories
+    config: Arc<config::Config>,
 }
 
 /// Request body for adding a new card
@@ -36,6 +45,32 @@ struct AddCardRequest {
     renewal_date: i32,
     max_reward_limit: Option<f64>,
     min_spend: Option<f64>,
+    #[serde(default)]
+    posting_delay: i32,
+    #[serde(default)]
+    bank_time_offset_minutes: i32,
+    #[serde(default)]
+    cutoff_hour: i32,
+    annual_fee: Option<f64>,
+    fee_posting_month: Option<i32>,
+    #[serde(default)]
+    starting_balance: f64,
+    balance_alert: Option<f64>,
+    #[serde(default)]
+    reward_type: RewardType,
+    cashback_percent: Option<f64>,
+    #[serde(default)]
+    foreign_blocks: bool,
+    #[serde(default)]
+    cap_unit: CapUnit,
+    #[serde(default)]
+    min_spend_exclusions: Vec<String>,
+    program: Option<String>,
+    miles_expiry_months: Option<i32>,
+    fx_fee_percent: Option<f64>,
+    #[serde(default)]
+    excluded_categories: Vec<String>,
+    payment_due_days: Option<i32>,
 }
 
 /// Response after adding a card
@@ -52,6 +87,21 @@ struct AddSpendingRequest {
     amount: f64,
     category: String,
     date: String,
+    /// Local time (HH:MM); when given, the date follows the card's bank clock
+    time: Option<String>,
+    /// Planned future spending that has not been charged yet
+    #[serde(default)]
+    scheduled: bool,
+    /// Merchant, kept as the description; earns partnership bonuses there
+    merchant: Option<String>,
+    /// Charged in a foreign currency; earns at the card's foreign rate
+    #[serde(default)]
+    foreign: bool,
+    /// Currency `amount` is in (e.g. "JPY"), converted to the home currency;
+    /// implies `foreign`
+    currency: Option<String>,
+    /// Home currency per unit of `currency`, defaults to its stored rate
+    fx_rate: Option<f64>,
 }
 
 /// Response after adding spending
@@ -59,6 +109,8 @@ struct AddSpendingRequest {
 struct AddSpendingResponse {
     id: i64,
     miles_earned: f64,
+    /// Cash back earned, on a card that pays it
+    cashback: Option<f64>,
     message: String,
 }
 
@@ -70,96 +122,148 @@ struct BestCardQuery {
     payment_category: String,
     #[serde(default = "default_date")]
     date: String,
+    /// Only count spending known by this date
+    as_of: Option<String>,
+    /// Count scheduled spending towards caps and min spend
+    #[serde(default)]
+    include_scheduled: bool,
+    /// Rank by the annual-fee-adjusted net rate
+    #[serde(default)]
+    fee_adjusted: bool,
+    /// Include each card's decision steps as `explanation`
+    #[serde(default)]
+    explain: bool,
+    /// Merchant the purchase is at, for partnership bonuses
+    merchant: Option<String>,
+    /// Rank by dollar value, miles at this many dollars each
+    mile_value: Option<f64>,
+    /// Dollars a point of a points card is worth, defaults to mile_value
+    point_value: Option<f64>,
+    /// Charged in a foreign currency, so cards earn their foreign rate
+    #[serde(default)]
+    foreign: bool,
+    /// The amount in the foreign currency it is charged in
+    foreign_amount: Option<f64>,
 }
 
 /// Query parameters for list spending endpoint
 #[derive(Deserialize)]
 struct ListSpendingQuery {
     card_id: Option<i64>,
+    as_of: Option<String>,
 }
 
+/// Query parameters for the paged spending endpoint. `after_date` and
+/// `after_id` come from the previous page's `next`.
+#[derive(Deserialize)]
+struct SpendingPageQuery {
+    card_id: Option<i64>,
+    as_of: Option<String>,
+    after_date: Option<String>,
+    after_id: Option<i64>,
+    #[serde(default = "default_page_size")]
+    limit: usize,
+}
+
+fn default_page_size() -> usize {
+    100
+}
+
+/// Largest page the paged endpoints return
+const MAX_PAGE_SIZE: usize = 1000;
+
 /// Query parameters for delete card endpoint
 #[derive(Deserialize)]
 struct DeleteCardQuery {
     id: i64,
 }
 
-fn default_date() -> String {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let days = (now / 86400) as i64;
-    let (y, m, d) = days_to_ymd(days);
-    format!("{:04}-{:02}-{:02}", y, m, d)
+/// Query parameters for balances endpoint
+#[derive(Deserialize)]
+struct BalancesQuery {
+    #[serde(default = "default_date")]
+    as_of: String,
 }
 
-/// Convert days since Unix epoch to (year, month, day)
-fn days_to_ymd(days: i64) -> (i64, i64, i64) {
-    let z = days + 719468;
-    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
-    let doe = z - era * 146097;
-    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
-    let y = yoe + era * 400;
-    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
-    let mp = (5 * doy + 2) / 153;
-    let d = doy - (153 * mp + 2) / 5 + 1;
-    let m = if mp < 10 { mp + 3 } else { mp - 9 };
-    let y = if m <= 2 { y + 1 } else { y };
-    (y, m, d)
+fn default_date() -> String {
+    db::today()
 }
 
 // ==================== API Handlers ====================
 
+fn internal_error(e: CcTrackerError) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// A failed write: refused, as one into a closed statement cycle is, or
+/// an internal error.
+fn write_error(e: CcTrackerError) -> (StatusCode, String) {
+    match e {
+        CcTrackerError::Invalid(_) => (StatusCode::CONFLICT, e.to_string()),
+        e => internal_error(e),
+    }
+}
+
 /// POST /api/cards - Add a new card
 async fn add_card(
     State(state): State<AppState>,
     Json(payload): Json<AddCardRequest>,
 ) -> Result<Json<AddCardResponse>, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-
     let categories = if payload.categories.is_empty() {
-        DEFAULT_CATEGORIES.iter().map(|s| s.to_string()).collect()
+        let config = Arc::clone(&state.config);
+        state.store.call(move |store| config.default_categories(store)).await.map_err(internal_error)?
     } else {
         payload.categories
     };
 
     let payment_categories = if payload.payment_categories.is_empty() {
-        DEFAULT_PAYMENT_CATEGORIES
-            .iter()
-            .map(|s| s.to_string())
-            .collect()
+        state.config.default_payment_categories()
     } else {
         payload.payment_categories
     };
+    let card = NewCard {
+        name: payload.name,
+        categories,
+        payment_categories,
+        miles_per_dollar: payload.miles_per_dollar,
+        miles_per_dollar_foreign: payload.miles_per_dollar_foreign,
+        block_size: payload.block_size,
+        statement_renewal_date: payload.renewal_date,
+        max_reward_limit: payload.max_reward_limit,
+        min_spend: payload.min_spend,
+        posting_delay: payload.posting_delay,
+        bank_time_offset_minutes: payload.bank_time_offset_minutes,
+        cutoff_hour: payload.cutoff_hour,
+        annual_fee: payload.annual_fee,
+        fee_posting_month: payload.fee_posting_month,
+        starting_balance: payload.starting_balance,
+        balance_alert: payload.balance_alert,
+        reward_type: payload.reward_type,
+        cashback_percent: payload.cashback_percent,
+        foreign_blocks: payload.foreign_blocks,
+        cap_unit: payload.cap_unit,
+        min_spend_exclusions: payload.min_spend_exclusions,
+        program: payload.program,
+        miles_expiry_months: payload.miles_expiry_months,
+        fx_fee_percent: payload.fx_fee_percent,
+        excluded_categories: payload.excluded_categories,
+        payment_due_days: payload.payment_due_days,
+    };
+    let problems = card.problems();
+    if !problems.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid card settings: {}", problems.join("; "))));
+    }
+    let message = format!("Added card '{}'", card.name);
+    let id = state.store.call(move |store| store.add_card(&card)).await.map_err(internal_error)?;
 
-    let id = db::add_card(
-        &conn,
-        &payload.name,
-        &categories,
-        &payment_categories,
-        payload.miles_per_dollar,
-        payload.miles_per_dollar_foreign,
-        payload.block_size,
-        payload.renewal_date,
-        payload.max_reward_limit,
-        payload.min_spend,
-    )
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    Ok(Json(AddCardResponse {
-        id,
-        message: format!("Added card '{}'", payload.name),
-    }))
+    Ok(Json(AddCardResponse { id, message }))
 }
 
 /// GET /api/cards - List all cards
 async fn list_cards(
     State(state): State<AppState>,
 ) -> Result<Json<Vec<Card>>, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-    let cards = db::list_cards(&conn)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let cards = state.store.call(|store| store.list_cards()).await.map_err(internal_error)?;
     Ok(Json(cards))
 }
 
@@ -168,9 +272,8 @@ async fn delete_card(
     State(state): State<AppState>,
     Query(params): Query<DeleteCardQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-    let removed = db::remove_card(&conn, params.id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let id = params.id;
+    let removed = state.store.call(move |store| store.remove_card(id)).await.map_err(write_error)?;
 
     if removed {
         Ok((
@@ -190,39 +293,137 @@ async fn best_card(
     State(state): State<AppState>,
     Query(params): Query<BestCardQuery>,
 ) -> Result<Json<Vec<CardRecommendation>>, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-    let results = db::best_card_for_category(
-        &conn,
-        &params.category,
-        params.amount,
-        &params.payment_category,
-        &params.date,
-    )
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let date = params.as_of.clone().unwrap_or(params.date);
+    let options = RecommendOptions {
+        known_by: params.as_of,
+        include_scheduled: params.include_scheduled,
+        fee_adjusted: params.fee_adjusted,
+        explain: params.explain,
+        merchant: params.merchant.clone(),
+        mile_value: params.mile_value,
+        point_value: params.point_value,
+        foreign: params.foreign,
+        foreign_amount: params.foreign_amount,
+        ..RecommendOptions::default()
+    };
+    let results = state
+        .store
+        .call(move |store| {
+            store.best_card_for_category(
+                &params.category,
+                params.amount,
+                &params.payment_category,
+                &date,
+                &options,
+            )
+        })
+        .await
+        .map_err(internal_error)?;
     Ok(Json(results))
 }
 
+/// GET /api/best-card/split - A two-card split for a purchase that would
+/// exceed a card's cap, when it beats every single card (null otherwise)
+async fn best_card_split(
+    State(state): State<AppState>,
+    Query(params): Query<BestCardQuery>,
+) -> Result<Json<Option<SplitSuggestion>>, (StatusCode, String)> {
+    let date = params.as_of.clone().unwrap_or(params.date);
+    let options = RecommendOptions {
+        known_by: params.as_of,
+        include_scheduled: params.include_scheduled,
+        merchant: params.merchant.clone(),
+        ..RecommendOptions::default()
+    };
+    let split = state
+        .store
+        .call(move |store| {
+            store.suggest_split(&params.category, params.amount, &params.payment_category, &date, &options)
+        })
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(split))
+}
+
 /// POST /api/spending - Add a spending transaction
 async fn add_spending(
     State(state): State<AppState>,
     Json(payload): Json<AddSpendingRequest>,
 ) -> Result<Json<AddSpendingResponse>, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-    let (id, miles) = db::add_spending(
-        &conn,
-        payload.card_id,
-        payload.amount,
-        &payload.category,
-        &payload.date,
-    )
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (amount, original) = match payload.currency.clone() {
+        Some(currency) => {
+            let (amount, rate) = (payload.amount, payload.fx_rate);
+            let converted = state.store.call(move |store| fx::to_home(store, amount, &currency, rate)).await;
+            match converted {
+                Ok((home, original)) => (home, Some(original)),
+                Err(e @ (CcTrackerError::Database(_) | CcTrackerError::Postgres(_))) => return Err(internal_error(e)),
+                Err(e) => return Err((StatusCode::BAD_REQUEST, e.to_string())),
+            }
+        }
+        None => (payload.amount, None),
+    };
+    let time = payload
+        .time
+        .as_deref()
+        .map(dates::parse_time)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let (card_id, category) = (payload.card_id, payload.category.clone());
+    let recorded = state
+        .store
+        .call(move |store| {
+            let Some(card) = store.get_card(payload.card_id)? else {
+                return Ok(None);
+            };
+            let mut date = payload.date.clone();
+            if let Some(time) = time {
+                date = db::bank_transaction_date(&card, &payload.date, time);
+            }
+            let excluded = card.excludes(&payload.category);
+            let reward = (card.reward_type, card.cashback_on(amount, &payload.category));
+
+            if payload.scheduled {
+                store
+                    .schedule_spending(
+                        payload.card_id,
+                        amount,
+                        &payload.category,
+                        &date,
+                        payload.merchant.as_deref(),
+                        payload.foreign,
+                        original.as_ref(),
+                    )
+                    .map(|recorded| Some((recorded, excluded, reward)))
+            } else {
+                store
+                    .add_spending(
+                        payload.card_id,
+                        amount,
+                        &payload.category,
+                        &date,
+                        payload.merchant.as_deref(),
+                        payload.foreign,
+                        original.as_ref(),
+                    )
+                    .map(|recorded| Some((recorded, excluded, reward)))
+            }
+        })
+        .await
+        .map_err(write_error)?;
+    let ((id, miles), excluded, (reward_type, cashback)) =
+        recorded.ok_or((StatusCode::NOT_FOUND, format!("No card found with ID {}", card_id)))?;
 
     Ok(Json(AddSpendingResponse {
         id,
         miles_earned: miles,
+        cashback,
         message: format!(
-            "Recorded ${:.2} on card {} for '{}' — earned {:.0} miles",
-            payload.amount, payload.card_id, payload.category, miles
+            "Recorded ${:.2} on card {} for '{}' — earned {}{}",
+            amount,
+            card_id,
+            category,
+            reward_type.describe(miles, cashback),
+            if excluded { " (the card excludes this category)" } else { "" }
         ),
     }))
 }
@@ -232,34 +433,204 @@ async fn list_spending(
     State(state): State<AppState>,
     Query(params): Query<ListSpendingQuery>,
 ) -> Result<Json<Vec<Spending>>, (StatusCode, String)> {
-    let conn = state.db.lock().unwrap();
-    let spending = db::list_spending(&conn, params.card_id)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let spending = state
+        .store
+        .call(move |store| store.list_spending(params.card_id, params.as_of.as_deref()))
+        .await
+        .map_err(internal_error)?;
     Ok(Json(spending))
 }
 
+/// GET /api/spending/page - One page of spending, newest first
+async fn spending_page(
+    State(state): State<AppState>,
+    Query(params): Query<SpendingPageQuery>,
+) -> Result<Json<SpendingPage>, (StatusCode, String)> {
+    let after = match (params.after_date, params.after_id) {
+        (Some(date), Some(id)) => Some(SpendingCursor { date, id }),
+        (None, None) => None,
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "after_date and after_id must be given together".to_string(),
+            ))
+        }
+    };
+    if params.limit == 0 || params.limit > MAX_PAGE_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {}", MAX_PAGE_SIZE),
+        ));
+    }
+    let page = state
+        .store
+        .call(move |store| {
+            store.list_spending_page(params.card_id, params.as_of.as_deref(), after.as_ref(), params.limit)
+        })
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(page))
+}
+
+/// GET /api/balances - Credited and pending miles per card
+async fn balances(
+    State(state): State<AppState>,
+    Query(params): Query<BalancesQuery>,
+) -> Result<Json<Vec<CardBalance>>, (StatusCode, String)> {
+    let balances = state
+        .store
+        .call(move |store| store.card_balances(&params.as_of))
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(balances))
+}
+
 /// GET /api/health - Health check endpoint
 async fn health_check() -> &'static str {
     "OK"
 }
 
-#[tokio::main]
-async fn main() {
-    // Initialize tracing
+fn main() {
+    let cli = cli::Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(arg_error_code(&e));
+    });
+
+    if let Err(e) = init_logging(cli.verbose, cli.log_file.as_deref()) {
+        eprintln!("Failed to open log file: {}", e);
+        std::process::exit(1);
+    }
+
+    // Initialize database
+    let mode = if cli.read_only || cli.command.as_ref().is_some_and(cli::Command::is_query) {
+        OpenMode::ReadOnly
+    } else {
+        OpenMode::ReadWrite
+    };
+    let store = open_store(cli.db.clone(), mode).unwrap_or_else(|e| {
+        eprintln!("Failed to initialize database: {}", e);
+        std::process::exit(1);
+    });
+    if mode == OpenMode::ReadWrite && config::loaded().is_some_and(|c| c.apply_recurring) {
+        match store.apply_recurring(&db::today()) {
+            Ok(applied) if !applied.is_empty() => tracing::info!("Recorded {} recurring charges", applied.len()),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to apply recurring charges: {}", e),
+        }
+    }
+
+    match cli.command {
+        None | Some(cli::Command::Serve) => {
+            // Started only for the server, as the Postgres client cannot
+            // connect from inside an async runtime
+            let runtime = tokio::runtime::Runtime::new().expect("Failed to start async runtime");
+            runtime.block_on(serve(store));
+        }
+        Some(command) => {
+            match cli::run(store.as_ref(), command, cli.output) {
+                Ok(cli::Outcome::Success) => {}
+                Ok(outcome) => std::process::exit(outcome as i32),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(e.exit_code());
+                }
+            }
+        }
+    }
+}
+
+/// The exit code for arguments clap refused: 0 for `--help` and `--version`,
+/// the error's own code when a value parser refused a date, and 4 for any
+/// other input, keeping 2 and 3 for best-card's outcomes.
+fn arg_error_code(e: &clap::Error) -> i32 {
+    if !e.use_stderr() {
+        return 0;
+    }
+    match std::error::Error::source(e).and_then(|source| source.downcast_ref::<CcTrackerError>()) {
+        Some(error) => error.exit_code(),
+        None => CcTrackerError::Invalid(String::new()).exit_code(),
+    }
+}
+
+/// Sends logs to stderr, or appends them to `log_file`. Each `-v` adds
+/// detail: cycle windows and rule evaluations, then the SQL executed.
+fn init_logging(verbose: u8, log_file: Option<&Path>) -> std::io::Result<()> {
+    let default_filter = match verbose {
+        0 => "backend=info,cc_tracker=info,tower_http=debug",
+        1 => "backend=debug,cc_tracker=debug,tower_http=debug",
+        _ => "backend=trace,cc_tracker=trace,tower_http=debug,tokio_postgres=debug",
+    };
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| default_filter.into());
+
+    let writer = match log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
+
     tracing_subscriber::registry()
+        .with(filter)
         .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "cc_tracker_rust=debug,tower_http=debug".into()),
+            tracing_subscriber::fmt::layer()
+                .with_writer(writer)
+                .with_ansi(log_file.is_none()),
         )
-        .with(tracing_subscriber::fmt::layer())
         .init();
+    Ok(())
+}
 
-    // Initialize database
-    let conn = db::init_db().expect("Failed to initialize database");
+/// Opens the database: the SQLite file from `--db` or `CC_TRACKER_DB` when
+/// given, else a configured Postgres URL, else the configured or default
+/// SQLite file.
+fn open_store(db: Option<PathBuf>, mode: OpenMode) -> Result<SharedStore, String> {
+    let config = config::load()?;
+    let path = db.or_else(|| std::env::var_os("CC_TRACKER_DB").map(PathBuf::from));
+    let url = match path {
+        Some(_) => None,
+        None => std::env::var("CC_TRACKER_DATABASE_URL").ok().or(config.database_url),
+    };
+    match url {
+        Some(url) => open_postgres(&url, mode),
+        None => {
+            let path = path
+                .or(config.database_path)
+                .unwrap_or_else(|| config::data_dir().join("cc_tracker.db"));
+            Ok(Box::new(db::init_db(&path, mode).map_err(|e| e.to_string())?))
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+fn open_postgres(url: &str, mode: OpenMode) -> Result<SharedStore, String> {
+    Ok(Box::new(pg::PgStore::connect(url, mode).map_err(|e| pg::describe_error(&e))?))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres(_url: &str, _mode: OpenMode) -> Result<SharedStore, String> {
+    Err("a database URL is configured but this build has no Postgres support \
+         (rebuild with --features postgres)"
+        .to_string())
+}
+
+/// Runs the REST API server until it is stopped.
+async fn serve(store: SharedStore) {
+    let config = config::load().unwrap_or_else(|e| {
+        tracing::warn!("{}; using the default settings", e);
+        config::Config::default()
+    });
     let state = AppState {
-        db: Arc::new(Mutex::new(conn)),
+        store: AsyncStore::new(store),
+        config: Arc::new(config),
     };
 
+    if let Some(command) = state.config.digest_command.clone() {
+        let store = state.store.clone();
+        tokio::spawn(async move { digest::send_monthly(store, command, &config::digest_sent_path()).await });
+    }
+
     // Configure CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -273,16 +644,25 @@ async fn main() {
         .route("/api/cards", get(list_cards))
         .route("/api/cards", delete(delete_card))
         .route("/api/best-card", get(best_card))
+        .route("/api/best-card/split", get(best_card_split))
         .route("/api/spending", post(add_spending))
         .route("/api/spending", get(list_spending))
+        .route("/api/spending/page", get(spending_page))
+        .route("/api/balances", get(balances))
         .layer(cors)
         .layer(tower_http::trace::TraceLayer::new_for_http())
         .with_state(state);
 
     // Start server
     let addr = "127.0.0.1:3000";
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap_or_else(|e| {
+        eprintln!("Failed to listen on {}: {}", addr, e);
+        std::process::exit(1);
+    });
     tracing::info!("🚀 Server listening on http://{}", addr);
 
-    axum::serve(listener, app).await.unwrap();
+    if let Err(e) = axum::serve(listener, app).await {
+        eprintln!("Server failed: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,482 @@
+use clap::ValueEnum;
+use rusqlite::types::Value;
+use rusqlite::{Connection, Result, params_from_iter};
+use std::collections::{HashMap, HashSet};
+
+use crate::db;
+use crate::models::MergeReport;
+
+/// How to settle a record present in both databases with different contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergeStrategy {
+    /// Take whichever side changed most recently (ties keep this database's)
+    KeepNewest,
+    /// Leave this database's record untouched
+    KeepLocal,
+    /// Keep both, adding the other database's record as a tagged copy
+    DuplicateAndTag,
+}
+
+pub struct MergeOptions {
+    pub cards: MergeStrategy,
+    pub spending: MergeStrategy,
+    /// Label for duplicated records: appended to card names, stored in spending's tag
+    pub tag: String,
+}
+
+/// How one table's rows are matched and compared across databases.
+struct Entity {
+    table: &'static str,
+    /// Columns identifying the same record in both databases
+    key: &'static [&'static str],
+    /// Columns whose differences make a conflict
+    content: &'static [&'static str],
+    /// Whether the table tracks updated_at, which keep-newest needs
+    timestamped: bool,
+}
+
+const CARDS: Entity = Entity {
+    table: "cards",
+    key: &["name"],
+    content: &[
+        "categories",
+        "payment_categories",
+        "miles_per_dollar",
+        "miles_per_dollar_foreign",
+        "block_size",
+        "statement_renewal_date",
+        "max_reward_limit",
+        "min_spend",
+        "posting_delay",
+        "bank_time_offset_minutes",
+        "cutoff_hour",
+        "annual_fee",
+        "fee_posting_month",
+        "starting_balance",
+        "balance_alert",
+        "reward_type",
+        "cashback_percent",
+        "foreign_blocks",
+        "cap_unit",
+        "min_spend_exclusions",
+        "program",
+        "miles_expiry_months",
+        "fx_fee_percent",
+        "excluded_categories",
+        "payment_due_days",
+        "closed_on",
+        "cycle_overrides",
+    ],
+    timestamped: true,
+};
+
+const RATE_HISTORY: Entity = Entity {
+    table: "card_rate_history",
+    key: &["card_id", "valid_until"],
+    content: &["miles_per_dollar", "block_size", "max_reward_limit", "min_spend"],
+    timestamped: false,
+};
+
+const SPENDING: Entity = Entity {
+    table: "spending",
+    key: &["card_id", "date", "amount", "description"],
+    content: &[
+        "category",
+        "payment_category",
+        "status",
+        "miles_earned",
+        "recorded_on",
+        "is_foreign",
+        "original_amount",
+        "original_currency",
+        "cashback",
+    ],
+    timestamped: true,
+};
+
+// Payments and charges are never edited, so every column is part of the key
+// and the only possible outcomes are identical or added.
+const PAYMENTS: Entity = Entity {
+    table: "payments",
+    key: &["card_id", "amount", "date"],
+    content: &[],
+    timestamped: false,
+};
+
+const CHARGES: Entity = Entity {
+    table: "charges",
+    key: &["card_id", "kind", "amount", "date"],
+    content: &[],
+    timestamped: false,
+};
+
+struct Row {
+    id: i64,
+    /// Key columns followed by content columns
+    values: Vec<Value>,
+    updated_at: Option<String>,
+}
+
+/// Value to read for a column the other database predates.
+fn missing_column_default(column: &str) -> &'static str {
+    match column {
+        "posting_delay" | "bank_time_offset_minutes" | "cutoff_hour" | "starting_balance" | "foreign_blocks"
+        | "is_foreign" => "0",
+        "status" => "'posted'",
+        "cap_unit" => "'dollars'",
+        "reward_type" => "'miles'",
+        "min_spend_exclusions" | "excluded_categories" => "'[]'",
+        "cycle_overrides" => "'{}'",
+        _ => "NULL",
+    }
+}
+
+fn read_rows(conn: &Connection, entity: &Entity) -> Result<Vec<Row>> {
+    let existing = db::table_columns(conn, entity.table)?;
+    if existing.is_empty() {
+        return Ok(Vec::new());
+    }
+    let column = |name: &str| -> String {
+        if existing.iter().any(|c| c == name) {
+            name.to_string()
+        } else {
+            missing_column_default(name).to_string()
+        }
+    };
+
+    let mut columns: Vec<String> = entity.key.iter().chain(entity.content).map(|c| column(c)).collect();
+    columns.push(if entity.timestamped { column("updated_at") } else { "NULL".to_string() });
+    let width = entity.key.len() + entity.content.len();
+
+    let mut stmt = conn.prepare(&format!("SELECT id, {} FROM {} ORDER BY id", columns.join(", "), entity.table))?;
+    let rows = stmt.query_map([], |row| {
+        let values = (0..width).map(|i| row.get(i + 1)).collect::<Result<Vec<Value>>>()?;
+        Ok(Row {
+            id: row.get(0)?,
+            values,
+            updated_at: row.get(width + 1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Result of merging one table.
+struct EntityMerge {
+    report: MergeReport,
+    /// Other database's row ID -> this database's row ID
+    ids: HashMap<i64, i64>,
+    /// Other database's row IDs that were inserted as new rows here
+    inserted: HashSet<i64>,
+}
+
+/// Merges one table from `other` into `conn`. Card IDs in the other database
+/// are translated through `card_ids`; rows for cards missing from it are
+/// skipped.
+fn merge_entity(
+    conn: &Connection,
+    other: &Connection,
+    entity: &Entity,
+    strategy: MergeStrategy,
+    tag: &str,
+    card_ids: Option<&HashMap<i64, i64>>,
+) -> Result<EntityMerge> {
+    let local_rows = read_rows(conn, entity)?;
+    let mut used = vec![false; local_rows.len()];
+    let key_len = entity.key.len();
+    let card_column = entity.key.iter().position(|c| *c == "card_id");
+
+    let mut merged = EntityMerge {
+        report: MergeReport {
+            entity: entity.table.to_string(),
+            ..MergeReport::default()
+        },
+        ids: HashMap::new(),
+        inserted: HashSet::new(),
+    };
+
+    for mut remote in read_rows(other, entity)? {
+        if let (Some(col), Some(card_ids)) = (card_column, card_ids) {
+            let Value::Integer(remote_card) = remote.values[col] else {
+                continue;
+            };
+            let Some(local_card) = card_ids.get(&remote_card) else {
+                continue;
+            };
+            remote.values[col] = Value::Integer(*local_card);
+        }
+        let tagged = tagged_values(entity, &remote.values, tag);
+
+        // Already merged, either as-is or as an earlier tagged copy
+        let identical = local_rows.iter().enumerate().position(|(i, local)| {
+            !used[i] && (local.values == remote.values || local.values == tagged)
+        });
+        if let Some(i) = identical {
+            used[i] = true;
+            merged.ids.insert(remote.id, local_rows[i].id);
+            merged.report.identical += 1;
+            continue;
+        }
+
+        let conflict = local_rows
+            .iter()
+            .enumerate()
+            .position(|(i, local)| !used[i] && local.values[..key_len] == remote.values[..key_len]);
+        let Some(i) = conflict else {
+            let id = insert_row(conn, entity, &remote.values, remote.updated_at.as_deref(), None)?;
+            merged.ids.insert(remote.id, id);
+            merged.inserted.insert(remote.id);
+            merged.report.added += 1;
+            continue;
+        };
+
+        used[i] = true;
+        let local = &local_rows[i];
+        match strategy {
+            // A missing timestamp sorts before any real one
+            MergeStrategy::KeepNewest if remote.updated_at > local.updated_at => {
+                update_content(conn, entity, local.id, &remote)?;
+                merged.ids.insert(remote.id, local.id);
+                merged.report.replaced += 1;
+            }
+            MergeStrategy::KeepLocal | MergeStrategy::KeepNewest => {
+                merged.ids.insert(remote.id, local.id);
+                merged.report.kept_local += 1;
+            }
+            MergeStrategy::DuplicateAndTag => {
+                let tag_column = (entity.table == SPENDING.table).then_some(tag);
+                let id = insert_row(conn, entity, &tagged, remote.updated_at.as_deref(), tag_column)?;
+                merged.ids.insert(remote.id, id);
+                merged.inserted.insert(remote.id);
+                merged.report.duplicated += 1;
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// The values a tagged copy of the row is stored with: cards get the tag
+/// appended to their name so both versions can coexist; other tables keep
+/// the tag in a separate column.
+fn tagged_values(entity: &Entity, values: &[Value], tag: &str) -> Vec<Value> {
+    let mut tagged = values.to_vec();
+    if entity.table == CARDS.table
+        && let Value::Text(name) = &values[0]
+    {
+        tagged[0] = Value::Text(format!("{} [{}]", name, tag));
+    }
+    tagged
+}
+
+fn insert_row(
+    conn: &Connection,
+    entity: &Entity,
+    values: &[Value],
+    updated_at: Option<&str>,
+    tag: Option<&str>,
+) -> Result<i64> {
+    let mut columns: Vec<&str> = entity.key.iter().chain(entity.content).copied().collect();
+    let mut params = values.to_vec();
+    if entity.timestamped {
+        columns.push("updated_at");
+        params.push(updated_at.map_or(Value::Null, |u| Value::Text(u.to_string())));
+    }
+    if let Some(tag) = tag {
+        columns.push("tag");
+        params.push(Value::Text(tag.to_string()));
+    }
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    conn.execute(
+        &format!("INSERT INTO {} ({}) VALUES ({})", entity.table, columns.join(", "), placeholders),
+        params_from_iter(params),
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn update_content(conn: &Connection, entity: &Entity, local_id: i64, remote: &Row) -> Result<()> {
+    let assignments: Vec<String> = entity
+        .content
+        .iter()
+        .map(|c| format!("{} = ?", c))
+        .chain(std::iter::once("updated_at = ?".to_string()))
+        .collect();
+    let mut params = remote.values[entity.key.len()..].to_vec();
+    params.push(remote.updated_at.clone().map_or(Value::Null, Value::Text));
+    params.push(Value::Integer(local_id));
+    conn.execute(
+        &format!("UPDATE {} SET {} WHERE id = ?", entity.table, assignments.join(", ")),
+        params_from_iter(params),
+    )?;
+    Ok(())
+}
+
+/// Merges cards, spending, payments and charges from `other` into `conn` in
+/// a single transaction. Records are matched by their natural keys (card
+/// name; card, date, amount and description for spending), so merging the
+/// same database twice changes nothing. Conflicting cards and spending are
+/// settled per `options`; rate history is copied only for cards that are
+/// new to this database. Closed cycles and the import review queue are not
+/// merged.
+pub fn merge_database(conn: &Connection, other: &Connection, options: &MergeOptions) -> Result<Vec<MergeReport>> {
+    let tx = conn.unchecked_transaction()?;
+
+    let cards = merge_entity(&tx, other, &CARDS, options.cards, &options.tag, None)?;
+
+    // Only cards created by this merge take the other database's rate history
+    let new_card_ids: HashMap<i64, i64> = cards
+        .ids
+        .iter()
+        .filter(|(remote, _)| cards.inserted.contains(remote))
+        .map(|(remote, local)| (*remote, *local))
+        .collect();
+    merge_entity(&tx, other, &RATE_HISTORY, MergeStrategy::KeepLocal, &options.tag, Some(&new_card_ids))?;
+
+    let mut reports = vec![cards.report];
+    for (entity, strategy) in [
+        (&SPENDING, options.spending),
+        (&PAYMENTS, MergeStrategy::KeepLocal),
+        (&CHARGES, MergeStrategy::KeepLocal),
+    ] {
+        let merged = merge_entity(&tx, other, entity, strategy, &options.tag, Some(&cards.ids))?;
+        reports.push(merged.report);
+    }
+    db::settle_cycles(&tx)?;
+
+    tx.commit()?;
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NewCard;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        conn
+    }
+
+    fn add_card(conn: &Connection, name: &str, miles_per_dollar: f64) -> i64 {
+        let card = NewCard {
+            name: name.to_string(),
+            categories: vec!["dining".to_string()],
+            miles_per_dollar,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            ..Default::default()
+        };
+        db::add_card(conn, &card).unwrap()
+    }
+
+    fn options(cards: MergeStrategy, spending: MergeStrategy) -> MergeOptions {
+        MergeOptions {
+            cards,
+            spending,
+            tag: "laptop".to_string(),
+        }
+    }
+
+    fn set_updated_at(conn: &Connection, table: &str, id: i64, at: &str) {
+        conn.execute(&format!("UPDATE {} SET updated_at = ?1 WHERE id = ?2", table), rusqlite::params![at, id])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_merge_copies_new_records_once() {
+        let local = test_db();
+        let other = test_db();
+        add_card(&other, "Filler", 1.0);
+        let card = add_card(&other, "Card A", 2.0);
+        db::add_spending(&other, card, 10.0, "dining", "2026-02-05", None, false, None).unwrap();
+        db::add_payment(&other, card, 10.0, "2026-02-20").unwrap();
+        db::add_charge(&other, card, "interest", 1.5, "2026-02-21").unwrap();
+
+        let keep_local = options(MergeStrategy::KeepLocal, MergeStrategy::KeepLocal);
+        let reports = merge_database(&local, &other, &keep_local).unwrap();
+        assert_eq!(reports[0].added, 2);
+        assert!(reports[1..].iter().all(|r| r.added == 1));
+
+        // Spending lands on the local copy of the card, not the other database's ID
+        let cards = db::list_cards(&local).unwrap();
+        let local_card = cards.iter().find(|c| c.name == "Card A").unwrap().id;
+        let spending = db::list_spending(&local, None, None).unwrap();
+        assert_eq!(spending[0].card_id, local_card);
+
+        // Merging again changes nothing
+        let reports = merge_database(&local, &other, &keep_local).unwrap();
+        assert!(reports.iter().all(|r| r.added == 0 && r.identical > 0));
+    }
+
+    #[test]
+    fn test_card_conflict_strategies() {
+        let local = test_db();
+        let other = test_db();
+        let local_card = add_card(&local, "Card A", 2.0);
+        let other_card = add_card(&other, "Card A", 4.0);
+        set_updated_at(&local, "cards", local_card, "2026-01-01 00:00:00.000");
+        set_updated_at(&other, "cards", other_card, "2026-03-01 00:00:00.000");
+
+        let reports = merge_database(&local, &other, &options(MergeStrategy::KeepLocal, MergeStrategy::KeepLocal)).unwrap();
+        assert_eq!(reports[0].kept_local, 1);
+        assert_eq!(db::get_card(&local, local_card).unwrap().unwrap().miles_per_dollar, 2.0);
+
+        let reports = merge_database(&local, &other, &options(MergeStrategy::DuplicateAndTag, MergeStrategy::KeepLocal)).unwrap();
+        assert_eq!(reports[0].duplicated, 1);
+        let names: Vec<String> = db::list_cards(&local).unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["Card A".to_string(), "Card A [laptop]".to_string()]);
+
+        let reports = merge_database(&local, &other, &options(MergeStrategy::KeepNewest, MergeStrategy::KeepLocal)).unwrap();
+        // The tagged copy from the previous merge now counts as identical
+        assert_eq!(reports[0].identical, 1);
+        assert_eq!(reports[0].replaced, 0);
+
+        let fresh = test_db();
+        let fresh_card = add_card(&fresh, "Card A", 2.0);
+        set_updated_at(&fresh, "cards", fresh_card, "2026-01-01 00:00:00.000");
+        let reports = merge_database(&fresh, &other, &options(MergeStrategy::KeepNewest, MergeStrategy::KeepLocal)).unwrap();
+        assert_eq!(reports[0].replaced, 1);
+        assert_eq!(db::get_card(&fresh, fresh_card).unwrap().unwrap().miles_per_dollar, 4.0);
+    }
+
+    #[test]
+    fn test_spending_duplicate_and_tag() {
+        let local = test_db();
+        let other = test_db();
+        let local_card = add_card(&local, "Card A", 2.0);
+        let other_card = add_card(&other, "Card A", 2.0);
+        db::add_spending(&local, local_card, 10.0, "dining", "2026-02-05", None, false, None).unwrap();
+        db::add_spending(&other, other_card, 10.0, "groceries", "2026-02-05", None, false, None).unwrap();
+
+        let strategies = options(MergeStrategy::KeepLocal, MergeStrategy::DuplicateAndTag);
+        let reports = merge_database(&local, &other, &strategies).unwrap();
+        assert_eq!(reports[1].duplicated, 1);
+        let spending = db::list_spending(&local, None, None).unwrap();
+        assert_eq!(spending.len(), 2);
+        let copy = spending.iter().find(|s| s.category == "groceries").unwrap();
+        assert_eq!(copy.tag.as_deref(), Some("laptop"));
+
+        let reports = merge_database(&local, &other, &strategies).unwrap();
+        assert_eq!(reports[1].identical, 1);
+        assert_eq!(db::list_spending(&local, None, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_merge_reads_older_schema() {
+        let local = test_db();
+        let other = Connection::open_in_memory().unwrap();
+        other
+            .execute_batch(
+                "CREATE TABLE cards (id INTEGER PRIMARY KEY, name TEXT, categories TEXT, payment_categories TEXT,
+                    miles_per_dollar REAL, miles_per_dollar_foreign REAL, block_size REAL,
+                    statement_renewal_date INTEGER, max_reward_limit REAL, min_spend REAL);
+                 INSERT INTO cards VALUES (1, 'Old Card', '[]', '[]', 1.2, NULL, 1.0, 15, NULL, NULL);",
+            )
+            .unwrap();
+
+        let reports = merge_database(&local, &other, &options(MergeStrategy::KeepLocal, MergeStrategy::KeepLocal)).unwrap();
+        assert_eq!(reports[0].added, 1);
+        let card = &db::list_cards(&local).unwrap()[0];
+        assert_eq!(card.name, "Old Card");
+        assert_eq!(card.posting_delay, 0);
+    }
+}
@@ -1,6 +1,11 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use tabled::Tabled;
 
+use crate::dates;
+
+/// The categories a new database starts with; `category add` adds more
 pub const DEFAULT_CATEGORIES: &[&str] = &[
     "dining",
     "travel",
@@ -16,6 +21,9 @@ pub const DEFAULT_PAYMENT_CATEGORIES: &[&str] = &[
     "online",
 ];
 
+/// Kinds of non-earning charges a card can incur
+pub const CHARGE_KINDS: &[&str] = &["interest", "late_fee", "fx_fee", "annual_fee", "other"];
+
 fn display_option_f64(val: &Option<f64>) -> String {
     match val {
         Some(v) => v.to_string(),
@@ -23,6 +31,39 @@ fn display_option_f64(val: &Option<f64>) -> String {
     }
 }
 
+fn display_option_usize(val: &Option<usize>) -> String {
+    match val {
+        Some(v) => v.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn display_option_i32(val: &Option<i32>) -> String {
+    match val {
+        Some(v) => v.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn display_option_i64(val: &Option<i64>) -> String {
+    match val {
+        Some(v) => v.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn display_option_str(val: &Option<String>) -> String {
+    val.clone().unwrap_or_else(|| "-".to_string())
+}
+
+fn display_date(date: &str) -> String {
+    dates::display(date)
+}
+
+fn display_option_date(val: &Option<String>) -> String {
+    val.as_deref().map_or_else(|| "-".to_string(), dates::display)
+}
+
 #[derive(Debug, Clone, Serialize, Tabled)]
 pub struct Card {
     pub id: i64,
@@ -41,20 +82,451 @@ pub struct Card {
     pub max_reward_limit: Option<f64>,
     #[tabled(display_with = "display_option_f64")]
     pub min_spend: Option<f64>,
+    /// Number of statements after the spend's own statement before its miles are credited
+    pub posting_delay: i32,
+    /// Minutes the bank's clock is ahead of the user's (negative if behind)
+    pub bank_time_offset_minutes: i32,
+    /// Hour of the bank's day (1-23) from which transactions count towards the next day; 0 = midnight
+    pub cutoff_hour: i32,
+    #[tabled(display_with = "display_option_f64")]
+    pub annual_fee: Option<f64>,
+    /// Month (1-12) the annual fee is charged in
+    #[tabled(display_with = "display_option_i32")]
+    pub fee_posting_month: Option<i32>,
+    /// Balance owed before the first tracked transaction
+    pub starting_balance: f64,
+    /// Warn when the outstanding balance exceeds this amount
+    #[tabled(display_with = "display_option_f64")]
+    pub balance_alert: Option<f64>,
+    /// What the card earns: miles, bank points or cash back
+    pub reward_type: RewardType,
+    /// Percent of each purchase paid back as cash, for cashback cards
+    #[tabled(display_with = "display_option_f64")]
+    pub cashback_percent: Option<f64>,
+    /// Whether blocks are counted in a foreign purchase's own currency
+    pub foreign_blocks: bool,
+    /// Whether max_reward_limit caps the cycle's spend or its miles
+    pub cap_unit: CapUnit,
+    /// JSON array of spending categories that do not count towards min_spend
+    /// (e.g. ["bills", "balance transfer"])
+    pub min_spend_exclusions: String,
+    /// Loyalty program the card's miles go to (e.g. "KrisFlyer"); cards in
+    /// the same program pool their balances
+    #[tabled(display_with = "display_option_str")]
+    pub program: Option<String>,
+    /// Months miles earned on the card stay valid for, overriding its
+    /// program's; None to follow the program
+    #[tabled(display_with = "display_option_i32")]
+    pub miles_expiry_months: Option<i32>,
+    /// Foreign transaction fee in percent of a foreign purchase (e.g. 3.25)
+    #[tabled(display_with = "display_option_f64")]
+    pub fx_fee_percent: Option<f64>,
+    /// JSON array of spending categories the card earns nothing on
+    /// (e.g. ["insurance", "utilities"])
+    pub excluded_categories: String,
+    /// Days after a statement closes that its payment is due; None when
+    /// not known
+    #[tabled(display_with = "display_option_i32")]
+    pub payment_due_days: Option<i32>,
+    /// Date the card was closed (YYYY-MM-DD); None while it is open
+    #[tabled(display_with = "display_option_date")]
+    pub closed_on: Option<String>,
+    /// JSON object of statement cycles the bank moved, by the month the
+    /// cycle is for (e.g. {"2026-03": {"start": "2026-03-03", "end": "2026-04-01"}})
+    pub cycle_overrides: String,
+}
+
+impl Card {
+    /// The categories that do not count towards the minimum spend, lowercased.
+    pub fn excluded_from_min_spend(&self) -> Vec<String> {
+        let categories: Vec<String> = serde_json::from_str(&self.min_spend_exclusions).unwrap_or_default();
+        categories.iter().map(|c| c.to_lowercase()).collect()
+    }
+
+    /// Whether the card earns nothing on `category`.
+    pub fn excludes(&self, category: &str) -> bool {
+        let categories: Vec<String> = serde_json::from_str(&self.excluded_categories).unwrap_or_default();
+        categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+
+    /// Cash back on a purchase of `amount` in `category`, if the card pays
+    /// any; nothing in a category it excludes.
+    pub fn cashback_on(&self, amount: f64, category: &str) -> Option<f64> {
+        let percent = self.cashback_percent?;
+        Some(if self.excludes(category) { 0.0 } else { crate::engine::cashback(amount, percent) })
+    }
+
+    /// The statement cycles the bank moved, by month (YYYY-MM).
+    pub fn cycle_overrides(&self) -> BTreeMap<String, CycleOverride> {
+        serde_json::from_str(&self.cycle_overrides).unwrap_or_default()
+    }
+
+    /// Whether the card had been closed by `date`
+    pub fn is_closed_on(&self, date: &str) -> bool {
+        self.closed_on.as_deref().is_some_and(|closed_on| closed_on <= date)
+    }
+}
+
+/// A statement cycle whose dates the bank set itself, taking precedence over
+/// the card's renewal day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CycleOverride {
+    pub start: String,
+    pub end: String,
+}
+
+/// What a card's reward cap limits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CapUnit {
+    /// Spend in the statement cycle: purchases past it earn nothing
+    #[default]
+    Dollars,
+    /// Miles earned in the statement cycle: purchases earn up to it
+    Miles,
+}
+
+impl CapUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CapUnit::Dollars => "dollars",
+            CapUnit::Miles => "miles",
+        }
+    }
+
+    /// Reads the stored unit; rows written before caps had a unit are in dollars.
+    pub fn from_db(value: &str) -> CapUnit {
+        if value == "miles" { CapUnit::Miles } else { CapUnit::Dollars }
+    }
+}
+
+impl fmt::Display for CapUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What a card's purchases earn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RewardType {
+    /// Airline miles, at miles_per_dollar per block
+    #[default]
+    Miles,
+    /// Bank points, at miles_per_dollar points per block
+    Points,
+    /// Cash back, at cashback_percent of each purchase
+    Cashback,
+}
+
+impl RewardType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RewardType::Miles => "miles",
+            RewardType::Points => "points",
+            RewardType::Cashback => "cashback",
+        }
+    }
+
+    /// Reads the stored type; cards added before cards had one earn miles.
+    pub fn from_db(value: &str) -> RewardType {
+        match value {
+            "points" => RewardType::Points,
+            "cashback" => RewardType::Cashback,
+            _ => RewardType::Miles,
+        }
+    }
+
+    /// The unit the card's rewards are counted in
+    pub fn unit(self) -> &'static str {
+        match self {
+            RewardType::Miles => "miles",
+            RewardType::Points => "points",
+            RewardType::Cashback => "dollars",
+        }
+    }
+
+    /// What a purchase earned, in words: e.g. "120 miles" or "$1.50 cash back"
+    pub fn describe(self, miles: f64, cashback: Option<f64>) -> String {
+        match self {
+            RewardType::Cashback => format!("${:.2} cash back", cashback.unwrap_or(0.0)),
+            RewardType::Miles | RewardType::Points => format!("{:.0} {}", miles, self.unit()),
+        }
+    }
+}
+
+impl fmt::Display for RewardType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What `stats` has one row per
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsBy {
+    #[default]
+    Card,
+    Category,
+    /// Calendar month, YYYY-MM
+    Month,
+}
+
+/// How a stored merchant rule's pattern is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PatternSyntax {
+    /// `*` stands for any run of characters and `?` for one, and the
+    /// pattern must cover the whole description
+    #[default]
+    Glob,
+    /// A regular expression, which may match anywhere in the description
+    Regex,
+}
+
+impl PatternSyntax {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PatternSyntax::Glob => "glob",
+            PatternSyntax::Regex => "regex",
+        }
+    }
+
+    pub fn from_db(value: &str) -> PatternSyntax {
+        if value == "regex" { PatternSyntax::Regex } else { PatternSyntax::Glob }
+    }
+}
+
+impl fmt::Display for PatternSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Input for creating a card
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct NewCard {
+    pub name: String,
+    pub categories: Vec<String>,
+    pub payment_categories: Vec<String>,
+    pub miles_per_dollar: f64,
+    pub miles_per_dollar_foreign: Option<f64>,
+    pub block_size: f64,
+    pub statement_renewal_date: i32,
+    pub max_reward_limit: Option<f64>,
+    pub min_spend: Option<f64>,
+    pub posting_delay: i32,
+    pub bank_time_offset_minutes: i32,
+    pub cutoff_hour: i32,
+    pub annual_fee: Option<f64>,
+    pub fee_posting_month: Option<i32>,
+    pub starting_balance: f64,
+    pub balance_alert: Option<f64>,
+    pub reward_type: RewardType,
+    pub cashback_percent: Option<f64>,
+    pub foreign_blocks: bool,
+    pub cap_unit: CapUnit,
+    pub min_spend_exclusions: Vec<String>,
+    pub program: Option<String>,
+    pub miles_expiry_months: Option<i32>,
+    pub fx_fee_percent: Option<f64>,
+    pub excluded_categories: Vec<String>,
+    pub payment_due_days: Option<i32>,
+}
+
+impl NewCard {
+    /// What is wrong with the settings, one problem per field named as in a
+    /// card file, e.g. "block_size: must be positive"; empty if nothing is.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.name.trim().is_empty() {
+            problems.push("name: must not be empty".to_string());
+        }
+        if !(self.block_size.is_finite() && self.block_size > 0.0) {
+            problems.push("block_size: must be positive".to_string());
+        }
+        let amounts = [
+            ("miles_per_dollar", Some(self.miles_per_dollar)),
+            ("miles_per_dollar_foreign", self.miles_per_dollar_foreign),
+            ("max_reward_limit", self.max_reward_limit),
+            ("min_spend", self.min_spend),
+            ("annual_fee", self.annual_fee),
+            ("starting_balance", Some(self.starting_balance)),
+            ("balance_alert", self.balance_alert),
+        ];
+        for (field, amount) in amounts {
+            if amount.is_some_and(|amount| !(amount.is_finite() && amount >= 0.0)) {
+                problems.push(format!("{}: must be a number, 0 or more", field));
+            }
+        }
+        if !(1..=31).contains(&self.statement_renewal_date) {
+            problems.push("statement_renewal_date: must be 1-31".to_string());
+        }
+        if self.posting_delay < 0 {
+            problems.push("posting_delay: must be 0 or more".to_string());
+        }
+        if !(0..=23).contains(&self.cutoff_hour) {
+            problems.push("cutoff_hour: must be 0-23".to_string());
+        }
+        if self.fee_posting_month.is_some_and(|month| !(1..=12).contains(&month)) {
+            problems.push("fee_posting_month: must be 1-12".to_string());
+        }
+        if self.miles_expiry_months.is_some_and(|months| months < 1) {
+            problems.push("miles_expiry_months: must be at least 1".to_string());
+        }
+        if self.cashback_percent.is_some_and(|percent| !(0.0..=100.0).contains(&percent)) {
+            problems.push("cashback_percent: must be 0-100".to_string());
+        }
+        if self.fx_fee_percent.is_some_and(|percent| !(0.0..=100.0).contains(&percent)) {
+            problems.push("fx_fee_percent: must be 0-100".to_string());
+        }
+        if self.payment_due_days.is_some_and(|days| !(0..=60).contains(&days)) {
+            problems.push("payment_due_days: must be 0-60".to_string());
+        }
+        if self.reward_type == RewardType::Cashback && self.cashback_percent.is_none() {
+            problems.push("cashback_percent: required for a cashback card".to_string());
+        }
+        problems
+    }
+}
+
+/// The rate-related fields of a card that are versioned over time
+#[derive(Debug, Clone)]
+pub struct CardRates {
+    pub miles_per_dollar: f64,
+    pub block_size: f64,
+    pub max_reward_limit: Option<f64>,
+    pub min_spend: Option<f64>,
+}
+
+/// Rates a card had before a change, valid until (excluding) `valid_until`
+#[derive(Debug, Clone)]
+pub struct RateChange {
+    pub id: i64,
+    pub card_id: i64,
+    /// YYYY-MM-DD the next rates took effect
+    pub valid_until: String,
+    pub rates: CardRates,
+}
+
+/// Knobs for `best_card_for_category` beyond the purchase itself
+#[derive(Debug, Clone, Default)]
+pub struct RecommendOptions {
+    /// Only count spending dated and recorded on or before this date
+    pub known_by: Option<String>,
+    /// Count scheduled spending towards cycle totals (projection mode)
+    pub include_scheduled: bool,
+    /// Rank by the annual-fee-adjusted net rate instead of the headline rate
+    pub fee_adjusted: bool,
+    /// Attach each card's decision steps to its recommendation
+    pub explain: bool,
+    /// Merchant the purchase is at, for cards' partnership bonuses there
+    pub merchant: Option<String>,
+    /// Rank by the dollar value of each card's miles (at this many dollars
+    /// a mile) and cashback, so cashback cards compete with miles cards
+    pub mile_value: Option<f64>,
+    /// Dollars a point is worth when ranking by value, for points cards;
+    /// defaults to `mile_value`
+    pub point_value: Option<f64>,
+    /// The purchase is charged in a foreign currency, so cards earn their
+    /// foreign rate
+    pub foreign: bool,
+    /// The purchase's amount in the foreign currency it is charged in, for
+    /// cards that count blocks in that currency
+    pub foreign_amount: Option<f64>,
+    /// Spending not recorded yet that counts towards cycle totals as if it
+    /// were, such as a trip's earlier expenses while planning it
+    pub planned: Vec<PlannedSpend>,
+}
+
+impl RecommendOptions {
+    /// Whether the purchase is charged in a foreign currency, as `foreign`
+    /// says or a foreign amount implies.
+    pub fn is_foreign(&self) -> bool {
+        self.foreign || self.foreign_amount.is_some()
+    }
+
+    /// Dollars one of a card's reward units is worth when ranking by value:
+    /// `point_value` for points cards and `mile_value` for the rest. None
+    /// unless ranking by value.
+    pub fn reward_value(&self, reward_type: RewardType) -> Option<f64> {
+        let mile_value = self.mile_value?;
+        Some(match reward_type {
+            RewardType::Points => self.point_value.unwrap_or(mile_value),
+            RewardType::Miles | RewardType::Cashback => mile_value,
+        })
+    }
+}
+
+/// A purchase being planned on a card
+#[derive(Debug, Clone)]
+pub struct PlannedSpend {
+    pub card_id: i64,
+    /// YYYY-MM-DD
+    pub date: String,
+    pub amount: f64,
+    pub category: String,
+    /// Miles the purchase earns there, counted against a cap in miles
+    pub miles: f64,
 }
 
 /// Used for the "best-card" query result
 #[derive(Debug, Clone, Serialize, Tabled)]
 pub struct CardRecommendation {
+    #[tabled(skip)]
+    pub card_id: i64,
     pub card_name: String,
     pub miles_per_dollar: f64,
     pub block_size: f64,
     pub effective_rate: f64,
+    /// Effective rate after amortizing the annual fee (only when fee-adjusted)
+    #[tabled(display_with = "display_option_f64")]
+    pub net_rate: Option<f64>,
     pub miles_earned: f64,
+    /// Cash back on the purchase, for cashback cards
+    #[tabled(display_with = "display_option_f64")]
+    pub cashback: Option<f64>,
+    /// FX fee the card charges on the purchase (only for foreign purchases)
+    #[tabled(display_with = "display_option_f64")]
+    pub fx_fee: Option<f64>,
+    /// Dollar value of the miles and cashback, less any FX fee (only when
+    /// valuing miles)
+    #[tabled(display_with = "display_option_f64")]
+    pub value: Option<f64>,
     #[tabled(display_with = "display_option_f64")]
     pub remaining_limit: Option<f64>,
     pub eligible: bool,
     pub reason: String,
+    /// How the recommendation was reached (only when explaining)
+    #[tabled(skip)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<ExplainStep>,
+}
+
+/// A purchase split across two cards: the first takes as much as its
+/// remaining cap allows, the second the rest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitSuggestion {
+    pub parts: Vec<SplitPart>,
+    pub total_miles: f64,
+    /// The best eligible card for the whole purchase, if any, to compare with
+    pub single_card: Option<String>,
+    pub single_card_miles: f64,
+}
+
+/// One card's share of a split purchase
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct SplitPart {
+    pub card_name: String,
+    pub amount: f64,
+    pub miles_earned: f64,
+}
+
+/// One step of a best-card decision, e.g. the cycle window used
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct ExplainStep {
+    pub step: String,
+    pub detail: String,
 }
 
 #[derive(Debug, Clone, Serialize, Tabled)]
@@ -64,7 +536,844 @@ pub struct Spending {
     pub amount: f64,
     pub category: String,
     /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
     pub date: String,
     /// Miles earned from this transaction
     pub miles_earned: f64,
+    /// "posted", or "scheduled" for planned future spending not yet charged
+    pub status: String,
+    /// Merchant / statement description, if known
+    #[tabled(display_with = "display_option_str")]
+    pub description: Option<String>,
+    /// How the card was used (e.g. "online"), if known
+    #[tabled(display_with = "display_option_str")]
+    pub payment_category: Option<String>,
+    /// Set when a database merge kept this row alongside a conflicting one
+    #[tabled(display_with = "display_option_str")]
+    pub tag: Option<String>,
+    /// Charged in a foreign currency, earning at the card's foreign rate
+    pub foreign: bool,
+    /// What was charged in the foreign currency, before conversion to the
+    /// home currency `amount` is in
+    #[tabled(display_with = "display_option_f64")]
+    pub original_amount: Option<f64>,
+    /// ISO code of that currency, e.g. "JPY"
+    #[tabled(display_with = "display_option_str")]
+    pub original_currency: Option<String>,
+    /// YYYY-MM-DD the spending was entered, when known
+    #[tabled(skip)]
+    pub recorded_on: Option<String>,
+    /// Cash back earned, at the card's cashback percent when recorded;
+    /// None on a card without one
+    #[tabled(skip)]
+    pub cashback: Option<f64>,
+}
+
+/// A transaction's amount in the foreign currency it was charged in
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginalAmount {
+    pub amount: f64,
+    /// ISO code, e.g. "JPY"
+    pub currency: String,
+}
+
+/// A stored conversion rate to the home currency, used for spending
+/// recorded in another currency
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct FxRate {
+    /// ISO code, e.g. "JPY"
+    pub currency: String,
+    /// Home currency per unit of `currency`
+    pub rate: f64,
+    /// YYYY-MM-DD the rate was last set
+    #[tabled(display_with = "display_date")]
+    pub updated_on: String,
+}
+
+/// A spending category, with how much uses it
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct Category {
+    pub name: String,
+    /// Cards earning in the category
+    pub cards: i64,
+    /// Transactions recorded under it
+    pub transactions: i64,
+    /// Dollars a month meant to be spent in it, set with `category budget`
+    #[tabled(display_with = "display_option_f64")]
+    pub monthly_budget: Option<f64>,
+}
+
+/// A category's posted spend in a month against its budget, from `budget`
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct CategoryBudget {
+    pub category: String,
+    pub budget: f64,
+    pub spent: f64,
+    /// Budget left, negative once it is overspent
+    pub remaining: f64,
+    /// Spend as a percentage of the budget, to one decimal place
+    pub used_percent: f64,
+}
+
+/// A transaction as `list-spending` shows it, with what it earned in its
+/// card's own unit
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct SpendingRow {
+    #[serde(flatten)]
+    #[tabled(inline)]
+    pub spending: Spending,
+    /// Miles or points earned, or the cash back of a cashback card
+    pub reward: f64,
+    /// "miles", "points" or "dollars"
+    pub reward_unit: &'static str,
+}
+
+/// Corrections to a recorded transaction; None leaves a field as it is
+#[derive(Debug, Clone, Default)]
+pub struct SpendingEdit {
+    pub card_id: Option<i64>,
+    pub amount: Option<f64>,
+    pub category: Option<String>,
+    /// YYYY-MM-DD
+    pub date: Option<String>,
+    pub foreign: Option<bool>,
+}
+
+/// Where a page of spending ends: the date and ID of its last row. Listings
+/// run newest first, so the next page holds the rows before this one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpendingCursor {
+    pub date: String,
+    pub id: i64,
+}
+
+/// One page of a spending listing
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendingPage {
+    pub items: Vec<Spending>,
+    /// Pass back to get the following page; None on the last page
+    pub next: Option<SpendingCursor>,
+}
+
+/// Which categorization rule a sample description matched
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct RuleMatch {
+    pub description: String,
+    /// Line of the matching rule in the rules file
+    #[tabled(display_with = "display_option_usize")]
+    pub rule_line: Option<usize>,
+    #[tabled(display_with = "display_option_str")]
+    pub pattern: Option<String>,
+    #[tabled(display_with = "display_option_str")]
+    pub category: Option<String>,
+    #[tabled(display_with = "display_option_str")]
+    pub payment_category: Option<String>,
+}
+
+/// Outcome of importing a statement
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Rows skipped because they matched spending already on the card
+    pub duplicates: usize,
+    /// Rows held back until their category is confirmed
+    pub queued_for_review: usize,
+}
+
+/// What a database merge did with one kind of record
+#[derive(Debug, Clone, Default, Serialize, Tabled)]
+pub struct MergeReport {
+    pub entity: String,
+    /// Records only in the other database, copied over
+    pub added: usize,
+    /// Records already present with the same contents
+    pub identical: usize,
+    /// Conflicts resolved in favour of this database
+    pub kept_local: usize,
+    /// Conflicts resolved by overwriting with the other database's record
+    pub replaced: usize,
+    /// Conflicts resolved by keeping both, the copy tagged
+    pub duplicated: usize,
+}
+
+/// Spending per category in an import preview
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CategoryTotal {
+    pub category: String,
+    pub count: usize,
+    pub amount: f64,
+}
+
+/// A statement row shown in an import preview
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct PreviewRow {
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub amount: f64,
+    #[tabled(display_with = "display_option_str")]
+    pub description: Option<String>,
+}
+
+/// What an import would do, shown before it is confirmed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportPreview {
+    pub card_id: i64,
+    /// Purchase rows read from the statement
+    pub rows: usize,
+    /// Rows that would be recorded as spending
+    pub to_import: usize,
+    /// Rows that would go to the review queue
+    pub to_review: usize,
+    /// Total of the rows that would be recorded
+    pub total_amount: f64,
+    pub categories: Vec<CategoryTotal>,
+    /// Rows matching spending already on the card
+    pub duplicates: Vec<PreviewRow>,
+    /// Descriptions of rows with no confident category
+    pub unmapped_merchants: Vec<String>,
+}
+
+/// An imported statement row waiting for its category to be confirmed
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ReviewItem {
+    pub id: i64,
+    pub card_id: i64,
+    pub amount: f64,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    #[tabled(display_with = "display_option_str")]
+    pub description: Option<String>,
+    /// Category most used for this merchant before, if it has history
+    #[tabled(display_with = "display_option_str")]
+    pub suggested_category: Option<String>,
+    /// Share of the merchant's past transactions that used the suggestion
+    #[tabled(display_with = "display_option_f64")]
+    pub confidence: Option<f64>,
+}
+
+/// Per-card miles balance split by whether the bank has posted them yet
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CardBalance {
+    pub card_id: i64,
+    pub card_name: String,
+    /// Miles from statements that have already posted
+    pub credited_miles: f64,
+    /// Miles earned but still waiting on the posting delay
+    pub pending_miles: f64,
+    pub total_miles: f64,
+    /// Projected miles from scheduled spending (not included in the total)
+    pub scheduled_miles: f64,
+}
+
+/// Miles balance of a loyalty program, pooled over the cards that earn into it
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ProgramBalance {
+    /// Program name, or "-" for a card that is in no program
+    pub program: String,
+    /// Names of the cards in the pool
+    pub cards: String,
+    pub credited_miles: f64,
+    pub pending_miles: f64,
+    pub total_miles: f64,
+    pub scheduled_miles: f64,
+}
+
+/// A card's rewards over a year against its annual fee, for `card-value`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CardValue {
+    #[tabled(skip)]
+    pub card_id: i64,
+    pub card_name: String,
+    pub miles_earned: f64,
+    /// The miles in dollars, at the mile value used
+    pub miles_value: f64,
+    pub cashback: f64,
+    /// Annual fees charged in the year, or the card's fee if none was recorded
+    pub annual_fee: f64,
+    /// Interest, late fees, FX fees and other charges in the year
+    pub other_charges: f64,
+    pub net_value: f64,
+    /// "keep" if the rewards covered the fee and charges, otherwise "review"
+    pub verdict: String,
+}
+
+/// How long one query took over a `bench` run, in milliseconds
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct BenchTiming {
+    pub query: String,
+    pub runs: usize,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Result of comparing the tracker's miles for one statement cycle with the
+/// miles the bank reported
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconciliation {
+    pub card_id: i64,
+    pub card_name: String,
+    pub cycle_start: String,
+    pub cycle_end: String,
+    pub tracked_miles: f64,
+    pub bank_miles: f64,
+    /// bank_miles - tracked_miles
+    pub difference: f64,
+    /// Miles if the block rounding were applied to the cycle's total spend
+    pub statement_level_miles: f64,
+    /// Transactions most likely to explain the difference, best match first
+    pub suspects: Vec<ReconcileSuspect>,
+}
+
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ReconcileSuspect {
+    pub spending_id: i64,
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub amount: f64,
+    pub category: String,
+    pub miles_earned: f64,
+    /// How far this transaction's miles are from the whole difference (0 = exact match)
+    pub gap: f64,
+}
+
+/// Frozen summary of a statement cycle that has been closed
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ClosedCycle {
+    pub id: i64,
+    pub card_id: i64,
+    #[tabled(display_with = "display_date")]
+    pub cycle_start: String,
+    #[tabled(display_with = "display_date")]
+    pub cycle_end: String,
+    pub total_spend: f64,
+    pub total_miles: f64,
+    pub transaction_count: i64,
+    /// Date the cycle was closed
+    #[tabled(display_with = "display_date")]
+    pub closed_on: String,
+}
+
+/// A repayment made towards a card's balance
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct Payment {
+    pub id: i64,
+    pub card_id: i64,
+    pub amount: f64,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+}
+
+/// Extra miles a card earns at merchants matching `merchant_pattern`, a
+/// regular expression matched case-insensitively against the merchant name
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct MerchantPartnership {
+    pub id: i64,
+    pub card_id: i64,
+    pub merchant_pattern: String,
+    /// Miles per block on top of the card's miles_per_dollar
+    pub bonus_miles_per_dollar: f64,
+}
+
+/// A card's promotional rate for one category between two dates
+/// (inclusive), in place of its usual miles per block there
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct Promotion {
+    pub id: i64,
+    pub card_id: i64,
+    pub category: String,
+    /// Miles per block while the promotion runs, like the card's miles_per_dollar
+    pub miles_per_dollar: f64,
+    #[tabled(display_with = "display_date")]
+    pub start_date: String,
+    #[tabled(display_with = "display_date")]
+    pub end_date: String,
+}
+
+/// A subscription or other charge recorded on a card every month on the
+/// same day, added with `recurring add`
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct Recurring {
+    pub id: i64,
+    pub card_id: i64,
+    pub amount: f64,
+    pub category: String,
+    /// Day of the month it is charged, the month's last day in shorter months
+    pub day_of_month: i32,
+    /// Merchant the spending is recorded with, if any
+    #[tabled(display_with = "display_option_str")]
+    pub merchant: Option<String>,
+    /// YYYY-MM-DD of the next charge not yet recorded
+    #[tabled(display_with = "display_date")]
+    pub next_date: String,
+}
+
+/// A charge `apply-recurring` recorded as spending
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct AppliedRecurring {
+    pub recurring_id: i64,
+    /// ID of the spending recorded
+    pub spending_id: i64,
+    pub card_id: i64,
+    pub amount: f64,
+    pub category: String,
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub miles_earned: f64,
+}
+
+/// A categorization rule added with `add-rule`: merchant descriptions
+/// matching `pattern` get `category`
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct MerchantRule {
+    pub id: i64,
+    pub pattern: String,
+    pub syntax: PatternSyntax,
+    pub category: String,
+}
+
+/// What a loyalty program's balance is counted in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgramKind {
+    /// An airline's frequent flyer miles
+    #[default]
+    Airline,
+    /// A bank's own points, transferred out to airlines
+    Bank,
+}
+
+impl ProgramKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProgramKind::Airline => "airline",
+            ProgramKind::Bank => "bank",
+        }
+    }
+
+    pub fn from_db(value: &str) -> ProgramKind {
+        if value == "bank" { ProgramKind::Bank } else { ProgramKind::Airline }
+    }
+
+    /// The unit the program's balance is counted in
+    pub fn unit(self) -> &'static str {
+        match self {
+            ProgramKind::Airline => "miles",
+            ProgramKind::Bank => "points",
+        }
+    }
+}
+
+impl fmt::Display for ProgramKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A loyalty program added with `programs add`. Cards join it by naming it
+/// as their `program`.
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct Program {
+    pub id: i64,
+    pub name: String,
+    pub kind: ProgramKind,
+    /// Months miles stay valid for after they are credited; None if they
+    /// never expire
+    #[tabled(display_with = "display_option_i32")]
+    pub expiry_months: Option<i32>,
+}
+
+/// A transfer partner of a program: each of its units converts to `ratio`
+/// units of the other (e.g. 0.4 KrisFlyer miles per bank point)
+#[derive(Debug, Clone, PartialEq, Serialize, Tabled)]
+pub struct ProgramTransfer {
+    pub id: i64,
+    #[tabled(skip)]
+    pub from_program_id: i64,
+    pub from_program: String,
+    #[tabled(skip)]
+    pub to_program_id: i64,
+    pub to_program: String,
+    pub ratio: f64,
+}
+
+/// The program a card's earnings land in, for `programs cards`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ProgramMembership {
+    pub card_id: i64,
+    pub card_name: String,
+    pub reward_type: RewardType,
+    /// "-" for a card without a program
+    pub program: String,
+    /// The program's kind, or "-" if it was never added with `programs add`
+    pub kind: String,
+}
+
+/// A non-earning charge (interest, fees) posted to a card
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct Charge {
+    pub id: i64,
+    pub card_id: i64,
+    /// One of CHARGE_KINDS
+    pub kind: String,
+    pub amount: f64,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+}
+
+/// A change to a miles balance besides the miles spending earns: a
+/// correction from `adjust-miles`, or miles spent on a redemption
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct MilesEntry {
+    pub id: i64,
+    /// The card whose miles changed; None for a program's pooled balance
+    #[tabled(display_with = "display_option_i64")]
+    pub card_id: Option<i64>,
+    /// The program whose pooled miles changed, when no card is given
+    #[tabled(display_with = "display_option_str")]
+    pub program: Option<String>,
+    /// "adjustment" or "redemption"
+    pub kind: String,
+    /// Miles added, or taken off when negative
+    pub miles: f64,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    #[tabled(display_with = "display_option_str")]
+    pub description: Option<String>,
+}
+
+/// Miles spent on a flight, upgrade or voucher, and the cash it would
+/// otherwise have cost
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct Redemption {
+    pub id: i64,
+    #[tabled(skip)]
+    pub card_id: Option<i64>,
+    /// Program whose pooled miles were redeemed, when no card is given
+    #[tabled(skip)]
+    pub program: Option<String>,
+    /// Card name, or the program for miles redeemed from its pool
+    pub account: String,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub miles: f64,
+    /// What the redemption would have cost in cash
+    pub cash_value: f64,
+    pub cents_per_mile: f64,
+    #[tabled(display_with = "display_option_str")]
+    pub description: Option<String>,
+    /// The miles ledger entry taking the miles off
+    #[tabled(skip)]
+    pub miles_entry_id: Option<i64>,
+}
+
+/// A card's or program's miles balance as the bank should show it
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct MilesAccount {
+    /// Card name, or program name for miles pooled in a program
+    pub account: String,
+    /// Miles credited from spending
+    pub earned: f64,
+    /// Miles spent on redemptions
+    pub redeemed: f64,
+    /// Net corrections from `adjust-miles`
+    pub adjusted: f64,
+    pub balance: f64,
+}
+
+/// Miles credited together that are still unspent and expire on one date
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct ExpiringMiles {
+    /// Card name, or program name for miles added to a program's pool
+    pub account: String,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub credited_on: String,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub expires_on: String,
+    pub days_left: i32,
+    /// Miles of the batch not yet redeemed or corrected away
+    pub miles: f64,
+}
+
+/// One line of an account's miles statement, with the balance after it
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct LedgerLine {
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    /// "earned", "adjustment" or "redemption"
+    pub kind: String,
+    pub description: String,
+    pub miles: f64,
+    pub balance: f64,
+}
+
+/// Money spent on, charged to and repaid to cards in one calendar month
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CashFlowMonth {
+    /// YYYY-MM
+    pub month: String,
+    pub spending: f64,
+    /// Interest and fees, which earn no miles
+    pub charges: f64,
+    pub payments: f64,
+    /// spending + charges - payments; positive when the balance grew
+    pub net: f64,
+}
+
+/// Posted spending totalled and averaged over one card, category or
+/// month, from `stats`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct SpendingStats {
+    /// Card name, category or month (YYYY-MM)
+    pub name: String,
+    pub transactions: i64,
+    pub total_spent: f64,
+    /// Per transaction
+    pub average_spent: f64,
+    pub total_miles: f64,
+    /// Per transaction
+    pub average_miles: f64,
+}
+
+/// The best eligible card for a purchase in a category, from
+/// `best-card-matrix`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CategoryBest {
+    pub category: String,
+    /// How the purchase is paid: whichever way earns most, unless the
+    /// matrix has a column per payment category
+    pub payment_category: String,
+    /// None when no card is eligible
+    #[tabled(display_with = "display_option_str")]
+    pub card_name: Option<String>,
+    pub miles_earned: f64,
+    pub effective_rate: f64,
+}
+
+/// A card's terms, and what it would earn on a purchase, from `compare-cards`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CardComparison {
+    pub card_id: i64,
+    pub card_name: String,
+    pub miles_per_dollar: f64,
+    #[tabled(display_with = "display_option_f64")]
+    pub miles_per_dollar_foreign: Option<f64>,
+    pub block_size: f64,
+    #[tabled(display_with = "display_option_f64")]
+    pub max_reward_limit: Option<f64>,
+    pub cap_unit: CapUnit,
+    #[tabled(display_with = "display_option_f64")]
+    pub min_spend: Option<f64>,
+    #[tabled(display_with = "display_option_f64")]
+    pub fx_fee_percent: Option<f64>,
+    #[tabled(display_with = "display_option_f64")]
+    pub annual_fee: Option<f64>,
+    /// How the purchase is paid: as asked, or whichever way earns most on
+    /// the card; None without a purchase to project
+    #[tabled(display_with = "display_option_str")]
+    pub payment_category: Option<String>,
+    /// Miles the purchase would earn, caps and minimum spend as they stand
+    #[tabled(display_with = "display_option_f64")]
+    pub projected_miles: Option<f64>,
+    #[tabled(display_with = "display_option_f64")]
+    pub effective_rate: Option<f64>,
+    /// Why the card would or would not earn on the purchase
+    #[tabled(display_with = "display_option_str")]
+    pub note: Option<String>,
+}
+
+/// What a card not in the database would have earned in a category, from
+/// `what-if`
+#[derive(Debug, Clone, Default, Serialize, Tabled)]
+pub struct WhatIfCategory {
+    pub category: String,
+    pub transactions: usize,
+    pub spent: f64,
+    pub miles: f64,
+    /// Cash back, for a cashback card
+    pub cashback: f64,
+    /// Miles the cards actually used earned on the same spending
+    pub actual_miles: f64,
+}
+
+/// Where a card's statement cycle around a date stands, from `cycle-summary`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CycleSummary {
+    pub card_id: i64,
+    pub card_name: String,
+    /// YYYY-MM-DD, after any weekend move or date the bank set
+    #[tabled(display_with = "display_date")]
+    pub cycle_start: String,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub cycle_end: String,
+    /// Days from the reference date to the cycle's last day
+    pub days_left: i32,
+    /// Posted spend in the cycle
+    pub spent: f64,
+    pub miles: f64,
+    /// What the reward cap still allows, in cap_unit; None without a cap
+    #[tabled(display_with = "display_option_f64")]
+    pub cap_left: Option<f64>,
+    /// "dollars" of spend or "miles" earned
+    #[tabled(display_with = "display_option_str")]
+    pub cap_unit: Option<String>,
+    /// Spend counting towards the minimum: all but its excluded categories
+    pub qualifying: f64,
+    #[tabled(display_with = "display_option_f64")]
+    pub min_spend: Option<f64>,
+    /// "met", or how much is still to go
+    #[tabled(display_with = "display_option_str")]
+    pub min_spend_status: Option<String>,
+}
+
+/// How fast money has lately gone onto a card, or into a category
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct SpendVelocity {
+    /// Card name, or the category with `--by category`
+    pub name: String,
+    pub last_7_days: f64,
+    pub last_30_days: f64,
+    /// Average spend per day over the last 7 days
+    pub per_day_7: f64,
+    /// Average spend per day over the last 30 days
+    pub per_day_30: f64,
+    /// ↑ when the last week ran faster than the last month, ↓ when slower
+    pub trend: String,
+    /// Spent so far in the card's current statement cycle
+    #[tabled(display_with = "display_option_f64")]
+    pub cycle_spent: Option<f64>,
+    /// Cycle spend by its last day if the 30-day pace holds
+    #[tabled(display_with = "display_option_f64")]
+    pub projected: Option<f64>,
+    /// The projection against the card's minimum spend and reward cap
+    #[tabled(display_with = "display_option_str")]
+    pub pace: Option<String>,
+}
+
+/// A statement's payment and what is still owed on it, from `due`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct PaymentDue {
+    pub card_name: String,
+    /// YYYY-MM-DD the statement closes (or closed): its cycle's last day
+    #[tabled(display_with = "display_date")]
+    pub statement_date: String,
+    /// YYYY-MM-DD payment is due, the card's payment_due_days later
+    #[tabled(display_with = "display_date")]
+    pub due_date: String,
+    /// Days from the as-of date to the due date, negative once past it
+    pub days_left: i32,
+    /// Balance on the statement; for a statement not yet closed, the
+    /// balance so far
+    pub statement_balance: f64,
+    /// Paid since the statement closed
+    pub paid: f64,
+    /// Still to pay to clear the statement
+    pub owed: f64,
+    /// "open" while the cycle runs, then "due", "paid" or "overdue"
+    pub status: String,
+}
+
+/// Where a card's statement cycle is heading by its last day, from `forecast`
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct CycleForecast {
+    pub card_name: String,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub cycle_end: String,
+    pub days_left: i32,
+    /// Posted spend in the cycle so far
+    pub spent: f64,
+    /// Expected spend per day: the card's daily average in each category,
+    /// added up
+    pub per_day: f64,
+    /// Cycle spend by its last day at that rate
+    pub projected: f64,
+    /// "met", "met around <date>", or how far short it will fall
+    #[tabled(display_with = "display_option_str")]
+    pub min_spend: Option<String>,
+    /// "within cap", or when the cap will run out
+    #[tabled(display_with = "display_option_str")]
+    pub cap: Option<String>,
+}
+
+/// One expense of a planned trip and the card it should go on
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct TripLeg {
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub expense: String,
+    pub category: String,
+    pub amount: f64,
+    /// None when no card earns on it
+    #[tabled(display_with = "display_option_str")]
+    pub card_name: Option<String>,
+    pub miles: f64,
+    /// The card's foreign transaction fee on the expense
+    pub fx_fee: f64,
+    /// The miles at the trip's mile value plus any cashback, less the FX fee
+    pub net_value: f64,
+    /// Why no card earns on the expense, when none does
+    #[tabled(display_with = "display_option_str")]
+    pub note: Option<String>,
+}
+
+/// One transaction of a reconstructed statement and the miles it earned
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct StatementLine {
+    pub id: i64,
+    /// YYYY-MM-DD
+    #[tabled(display_with = "display_date")]
+    pub date: String,
+    pub category: String,
+    #[tabled(display_with = "display_option_str")]
+    pub merchant: Option<String>,
+    pub amount: f64,
+    /// Miles at the card's rates, as recorded
+    pub miles: f64,
+    /// Miles left after the reward cap and minimum spend
+    pub earned: f64,
+    /// Why `earned` falls short of `miles`, or that the transaction does
+    /// not count towards the minimum spend
+    #[tabled(display_with = "display_option_str")]
+    pub note: Option<String>,
+}
+
+/// One planned purchase of a basket and the card it should go on
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct BasketAllocation {
+    pub item: String,
+    pub category: String,
+    pub amount: f64,
+    /// None when no card earns on it
+    #[tabled(display_with = "display_option_str")]
+    pub card_name: Option<String>,
+    #[tabled(display_with = "display_option_str")]
+    pub payment_category: Option<String>,
+    pub miles: f64,
+}
+
+/// What is currently owed on a card
+#[derive(Debug, Clone, Serialize, Tabled)]
+pub struct OutstandingBalance {
+    pub card_id: i64,
+    pub card_name: String,
+    pub starting_balance: f64,
+    pub spending: f64,
+    pub charges: f64,
+    pub payments: f64,
+    /// starting_balance + spending + charges - payments
+    pub outstanding: f64,
+    #[tabled(display_with = "display_option_f64")]
+    pub balance_alert: Option<f64>,
+    pub over_alert: bool,
 }
@@ -0,0 +1,276 @@
+//! The `today` overview: what matters right now, in one screen. The best
+//! card per common category, reward caps nearly used up, minimum spends
+//! still to meet, and what falls due in the coming week.
+
+
+use crate::db::{STATUS_POSTED, STATUS_SCHEDULED};
+use crate::digest::annual_fee_due;
+use crate::due;
+use crate::engine::{add_days, card_cycle, days_between};
+use crate::error::Result;
+use crate::models::{CapUnit, Card, CategoryBest, RecommendOptions, DEFAULT_CATEGORIES, DEFAULT_PAYMENT_CATEGORIES};
+use crate::store::Store;
+
+/// The purchase the best card per category is picked for
+pub const TYPICAL_PURCHASE: f64 = 50.0;
+
+/// Share of a reward cap used from which it counts as nearly used up
+const NEAR_CAP_SHARE: f64 = 0.8;
+
+/// Days ahead, today included, that "Due this week" covers
+const WEEK_DAYS: i32 = 7;
+
+/// The best eligible card for an `amount` purchase in each category on
+/// `date`, with caps and minimum spends as they stand then. With
+/// `per_payment` there is one per category and payment category; without
+/// it, one per category paid whichever way earns most.
+pub fn best_cards<S: Store + ?Sized>(
+    store: &S,
+    categories: &[String],
+    payment_categories: &[String],
+    amount: f64,
+    date: &str,
+    per_payment: bool,
+) -> Result<Vec<CategoryBest>> {
+    let mut cells = Vec::new();
+    for category in categories {
+        let mut best: Option<CategoryBest> = None;
+        for payment_category in payment_categories {
+            let top = store
+                .best_card_for_category(category, amount, payment_category, date, &RecommendOptions::default())?
+                .into_iter()
+                .find(|r| r.eligible);
+            let cell = CategoryBest {
+                category: category.clone(),
+                payment_category: payment_category.clone(),
+                card_name: top.as_ref().map(|top| top.card_name.clone()),
+                miles_earned: top.as_ref().map_or(0.0, |top| top.miles_earned),
+                effective_rate: top.as_ref().map_or(0.0, |top| top.effective_rate),
+            };
+            if per_payment {
+                cells.push(cell);
+            } else if best.as_ref().is_none_or(|b| {
+                cell.card_name.is_some() && (b.card_name.is_none() || cell.miles_earned > b.miles_earned)
+            }) {
+                best = Some(cell);
+            }
+        }
+        cells.extend(best);
+    }
+    Ok(cells)
+}
+
+/// Builds the overview for `date` as plain text.
+pub fn build<S: Store + ?Sized>(store: &S, date: &str) -> Result<String> {
+    let cards = store.list_cards()?;
+    let mut out = format!("Today, {}\n", date);
+    if cards.is_empty() {
+        out.push_str("\nNo cards yet; add one with add-card.\n");
+        return Ok(out);
+    }
+
+    out.push_str(&format!("\nBest card for a ${:.0} purchase\n", TYPICAL_PURCHASE));
+    let width = DEFAULT_CATEGORIES.iter().map(|c| c.len()).max().unwrap_or(0);
+    let categories: Vec<String> = DEFAULT_CATEGORIES.iter().map(|c| c.to_string()).collect();
+    let payment_categories: Vec<String> = DEFAULT_PAYMENT_CATEGORIES.iter().map(|c| c.to_string()).collect();
+    // However it is paid, whichever way earns most
+    for best in best_cards(store, &categories, &payment_categories, TYPICAL_PURCHASE, date, false)? {
+        match best.card_name {
+            Some(card_name) => out.push_str(&format!(
+                "  {:width$}  {} ({:.2} mi/$, {})\n",
+                best.category,
+                card_name,
+                best.effective_rate,
+                best.payment_category
+            )),
+            None => out.push_str(&format!("  {:width$}  no eligible card\n", best.category)),
+        }
+    }
+
+    // Posted spending in each card's current cycle
+    let earliest = cards
+        .iter()
+        .map(|c| card_cycle(c, date).0)
+        .min()
+        .unwrap_or_else(|| date.to_string());
+    let mut spending = Vec::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.date >= earliest {
+            spending.push(s);
+        }
+        Ok(())
+    })?;
+    // Spend, miles and spend counting towards the minimum spend
+    let cycle_totals = |card: &Card, start: &str, end: &str| -> (f64, f64, f64) {
+        let excluded = card.excluded_from_min_spend();
+        spending
+            .iter()
+            .filter(|s| s.card_id == card.id && s.status == STATUS_POSTED && s.date.as_str() >= start && s.date.as_str() <= end)
+            .fold((0.0, 0.0, 0.0), |(spent, miles, qualifying), s| {
+                let counts = !excluded.contains(&s.category.to_lowercase());
+                (spent + s.amount, miles + s.miles_earned, qualifying + if counts { s.amount } else { 0.0 })
+            })
+    };
+
+    let mut caps = Vec::new();
+    let mut minimums = Vec::new();
+    for card in cards.iter().filter(|c| !c.is_closed_on(date)) {
+        let (start, end) = card_cycle(card, date);
+        let (spent, miles, qualifying) = cycle_totals(card, &start, &end);
+        match (card.max_reward_limit, card.cap_unit) {
+            (Some(cap), CapUnit::Dollars) if spent >= cap * NEAR_CAP_SHARE => caps.push(format!(
+                "  {}: ${:.2} of its ${:.2} cap used, ${:.2} left until {}\n",
+                card.name,
+                spent,
+                cap,
+                (cap - spent).max(0.0),
+                end
+            )),
+            (Some(cap), CapUnit::Miles) if miles >= cap * NEAR_CAP_SHARE => caps.push(format!(
+                "  {}: {:.0} of its {:.0}-mile cap earned, {:.0} miles left until {}\n",
+                card.name,
+                miles,
+                cap,
+                (cap - miles).max(0.0),
+                end
+            )),
+            _ => {}
+        }
+        if let Some(minimum) = card.min_spend
+            && qualifying < minimum
+        {
+            let days_left = days_between(date, &end) + 1;
+            minimums.push(format!(
+                "  {}: ${:.2} more by {} ({} day{} left)\n",
+                card.name,
+                minimum - qualifying,
+                end,
+                days_left,
+                if days_left == 1 { "" } else { "s" }
+            ));
+        }
+    }
+    for (title, lines) in [("Caps nearly used", caps), ("Minimum spend to go", minimums)] {
+        out.push_str(&format!("\n{}\n", title));
+        if lines.is_empty() {
+            out.push_str("  Nothing.\n");
+        }
+        for line in lines {
+            out.push_str(&line);
+        }
+    }
+
+    // Payments for cards whose due dates are known; for the others,
+    // statements closing with the balance they will ask for. Then annual
+    // fees and scheduled spending
+    let week_end = add_days(date, WEEK_DAYS - 1);
+    let balances = store.outstanding_balances(date)?;
+    let mut due = Vec::new();
+    for payment in due::upcoming(store, None, date)? {
+        if payment.owed > 0.0 && payment.due_date <= week_end {
+            let what = if payment.status == "overdue" { "payment overdue" } else { "payment due" };
+            due.push((payment.due_date, format!("{} {}, ${:.2} owed", payment.card_name, what, payment.owed)));
+        }
+    }
+    for card in &cards {
+        let (_, closes) = card_cycle(card, date);
+        let owed = balances.iter().find(|b| b.card_id == card.id).map_or(0.0, |b| b.outstanding);
+        if card.payment_due_days.is_none() && closes <= week_end && owed > 0.0 {
+            due.push((closes.clone(), format!("{} statement closes, ${:.2} owed", card.name, owed)));
+        }
+        if let Some(fee_due) = annual_fee_due(store, card, date)?
+            && fee_due <= week_end
+        {
+            due.push((
+                fee_due,
+                format!("{} annual fee of ${:.2}", card.name, card.annual_fee.unwrap_or(0.0)),
+            ));
+        }
+    }
+    for s in spending
+        .iter()
+        .filter(|s| s.status == STATUS_SCHEDULED && s.date.as_str() >= date && s.date <= week_end)
+    {
+        let name = cards.iter().find(|c| c.id == s.card_id).map_or("?", |c| c.name.as_str());
+        due.push((s.date.clone(), format!("{} ${:.2} of {} scheduled", name, s.amount, s.category)));
+    }
+    due.sort();
+    out.push_str("\nDue this week\n");
+    if due.is_empty() {
+        out.push_str("  Nothing.\n");
+    }
+    for (day, what) in due {
+        out.push_str(&format!("  {}  {}\n", day, what));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    fn card(conn: &Connection, name: &str, categories: &[&str], miles_per_dollar: f64, max_reward_limit: Option<f64>, min_spend: Option<f64>) -> i64 {
+        db::add_card(
+            conn,
+            &NewCard {
+                name: name.to_string(),
+                categories: categories.iter().map(|c| c.to_string()).collect(),
+                payment_categories: DEFAULT_PAYMENT_CATEGORIES.iter().map(|c| c.to_string()).collect(),
+                miles_per_dollar,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit,
+                min_spend,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_overview_lists_what_matters_now() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let capped = card(&conn, "Capped", &["dining"], 4.0, Some(500.0), None);
+        let everyday = card(&conn, "Everyday", DEFAULT_CATEGORIES, 1.0, None, None);
+        let bonus = card(&conn, "Bonus", &["shopping"], 2.0, None, Some(800.0));
+        db::add_spending(&conn, capped, 460.0, "dining", "2026-04-02", None, false, None).unwrap();
+        db::add_spending(&conn, bonus, 100.0, "shopping", "2026-04-03", None, false, None).unwrap();
+        db::schedule_spending(&conn, everyday, 60.0, "transport", "2026-04-27", None, false, None).unwrap();
+
+        let overview = build(&conn, "2026-04-24").unwrap();
+        assert!(overview.contains("  dining         Everyday (1.00 mi/$, contactless)\n"), "{}", overview);
+        assert!(overview.contains("  travel         Everyday"), "{}", overview);
+        assert!(overview.contains("  Capped: $460.00 of its $500.00 cap used, $40.00 left until 2026-04-30\n"), "{}", overview);
+        assert!(overview.contains("  Bonus: $700.00 more by 2026-04-30 (7 days left)\n"), "{}", overview);
+        assert!(overview.contains("  2026-04-27  Everyday $60.00 of transport scheduled\n"), "{}", overview);
+        assert!(overview.contains("  2026-04-30  Capped statement closes, $460.00 owed\n"), "{}", overview);
+    }
+
+    #[test]
+    fn test_best_cards_reflect_caps_per_payment_category() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let capped = card(&conn, "Capped", &["dining"], 4.0, Some(500.0), None);
+        card(&conn, "Everyday", &["dining", "travel"], 1.0, None, None);
+        let categories = vec!["dining".to_string(), "groceries".to_string()];
+        let payment_categories = vec!["online".to_string(), "contactless".to_string()];
+
+        let best = best_cards(&conn, &categories, &payment_categories, 50.0, "2026-04-24", false).unwrap();
+        assert_eq!(best.len(), 2);
+        assert_eq!((best[0].card_name.as_deref(), best[0].miles_earned), (Some("Capped"), 200.0));
+        assert_eq!(best[1].card_name, None);
+
+        // Once the cap is nearly used, the next card takes over
+        db::add_spending(&conn, capped, 480.0, "dining", "2026-04-02", None, false, None).unwrap();
+        let grid = best_cards(&conn, &categories, &payment_categories, 50.0, "2026-04-24", true).unwrap();
+        let cells: Vec<_> = grid.iter().map(|c| (c.payment_category.as_str(), c.card_name.as_deref())).collect();
+        assert_eq!(
+            cells,
+            vec![("online", Some("Everyday")), ("contactless", Some("Everyday")), ("online", None), ("contactless", None)]
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Reporting periods: the `--period` flag shared by summary, the listings,
+//! cash-flow and export. Statement cycles differ from card to card, so a
+//! period resolves to a date range per card; calendar periods give every
+//! card the same range.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use crate::dates;
+use crate::engine::{add_days, cycle_bounds, days_to_ymd, format_date, parse_date, round_cents, ymd_to_days};
+use crate::error::Result;
+use crate::models::{Card, CycleOverride, OutstandingBalance};
+use crate::store::Store;
+
+/// A reporting period, as given to `--period`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Period {
+    ThisCycle,
+    LastCycle,
+    ThisMonth,
+    LastMonth,
+    /// January 1st up to and including today
+    Ytd,
+    Quarter { year: i32, quarter: i32 },
+    /// FROM..TO, both days included
+    Range { from: String, to: String },
+}
+
+/// The first and last day (both included) of a period, as YYYY-MM-DD
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateRange {
+    pub from: String,
+    pub to: String,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: &str) -> bool {
+        self.from.as_str() <= date && date <= self.to.as_str()
+    }
+}
+
+/// Checks a YYYY-MM-DD date, including that the day exists in its month.
+pub fn valid_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<i32>(), month.parse::<i32>(), day.parse::<i32>()) else {
+        return false;
+    };
+    (1..=12).contains(&month) && day >= 1 && days_to_ymd(ymd_to_days(year, month, day)) == (year, month, day)
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid period '{}'; use this-cycle, last-cycle, this-month, last-month, ytd, \
+                 a quarter such as 2026-Q1, or FROM..TO dates such as 2026-01-05..2026-02-04",
+                text
+            )
+        };
+        match text {
+            "this-cycle" => return Ok(Period::ThisCycle),
+            "last-cycle" => return Ok(Period::LastCycle),
+            "this-month" => return Ok(Period::ThisMonth),
+            "last-month" => return Ok(Period::LastMonth),
+            "ytd" => return Ok(Period::Ytd),
+            _ => {}
+        }
+        if let Some((from, to)) = text.split_once("..") {
+            let (Ok(from), Ok(to)) = (dates::parse_input(from), dates::parse_input(to)) else {
+                return Err(invalid());
+            };
+            if from > to {
+                return Err(format!("Period '{}' ends before it starts", text));
+            }
+            return Ok(Period::Range { from, to });
+        }
+        let (year, quarter) = text.split_once(['q', 'Q']).ok_or_else(invalid)?;
+        let year = year.strip_suffix('-').ok_or_else(invalid)?;
+        match (year.parse::<i32>(), quarter.parse::<i32>()) {
+            (Ok(year), Ok(quarter)) if year >= 1000 && (1..=4).contains(&quarter) => {
+                Ok(Period::Quarter { year, quarter })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The first and last day of `months` calendar months starting at (year, month).
+fn months_range(year: i32, month: i32, months: i32) -> DateRange {
+    let next = year * 12 + (month - 1) + months;
+    let (y, m, d) = days_to_ymd(ymd_to_days(next.div_euclid(12), next.rem_euclid(12) + 1, 1) - 1);
+    DateRange {
+        from: format_date(year, month, 1),
+        to: format_date(y, m, d),
+    }
+}
+
+impl Period {
+    /// Whether the period is a statement cycle, and so depends on the card
+    pub fn is_cycle(&self) -> bool {
+        matches!(self, Period::ThisCycle | Period::LastCycle)
+    }
+
+    /// The dates the period covers on `today`. Cycle periods use the
+    /// statement cycles of a card renewing on the given day, with its cycle
+    /// overrides, and are None without one.
+    pub fn range(&self, cycle: Option<(i32, &BTreeMap<String, CycleOverride>)>, today: &str) -> Option<DateRange> {
+        let (year, month, _) = parse_date(today);
+        let range = match self {
+            Period::ThisCycle | Period::LastCycle => {
+                let (renewal_day, overrides) = cycle?;
+                let mut date = today.to_string();
+                if *self == Period::LastCycle {
+                    date = add_days(&cycle_bounds(renewal_day, overrides, today).0, -1);
+                }
+                let (from, to) = cycle_bounds(renewal_day, overrides, &date);
+                DateRange { from, to }
+            }
+            Period::ThisMonth => months_range(year, month, 1),
+            Period::LastMonth => {
+                let (year, month) = if month == 1 { (year - 1, 12) } else { (year, month - 1) };
+                months_range(year, month, 1)
+            }
+            Period::Ytd => DateRange {
+                from: format_date(year, 1, 1),
+                to: today.to_string(),
+            },
+            Period::Quarter { year, quarter } => months_range(*year, quarter * 3 - 2, 3),
+            Period::Range { from, to } => DateRange {
+                from: from.clone(),
+                to: to.clone(),
+            },
+        };
+        Some(range)
+    }
+
+    /// The dates the period covers for a card on `today`
+    pub fn card_range(&self, card: &Card, today: &str) -> Option<DateRange> {
+        self.range(Some((card.statement_renewal_date, &card.cycle_overrides())), today)
+    }
+
+    /// The period's range for each card, keyed by card ID
+    pub fn card_ranges(&self, cards: &[Card], today: &str) -> CardRanges {
+        CardRanges(
+            cards
+                .iter()
+                .filter_map(|card| Some((card.id, self.card_range(card, today)?)))
+                .collect(),
+        )
+    }
+}
+
+/// A period resolved for every card
+pub struct CardRanges(HashMap<i64, DateRange>);
+
+impl CardRanges {
+    pub fn get(&self, card_id: i64) -> Option<&DateRange> {
+        self.0.get(&card_id)
+    }
+
+    /// Whether a record of the card dated `date` falls in the period
+    pub fn contains(&self, card_id: i64, date: &str) -> bool {
+        self.get(card_id).is_some_and(|range| range.contains(date))
+    }
+}
+
+/// Each card's balance over its range: `starting_balance` is what it owed
+/// the day before the range began, the spending, charges and payments are
+/// those dated in it, and `outstanding` is what it owed on its last day.
+pub fn outstanding_balances<S: Store + ?Sized>(store: &S, ranges: &CardRanges) -> Result<Vec<OutstandingBalance>> {
+    // Cards sharing a range share the balance queries
+    let mut by_date: HashMap<String, Vec<OutstandingBalance>> = HashMap::new();
+    let mut balance_on = |date: String, card_id: i64| -> Result<Option<OutstandingBalance>> {
+        if !by_date.contains_key(&date) {
+            let balances = store.outstanding_balances(&date)?;
+            by_date.insert(date.clone(), balances);
+        }
+        Ok(by_date[&date].iter().find(|b| b.card_id == card_id).cloned())
+    };
+
+    let mut balances = Vec::new();
+    for card in store.list_cards()? {
+        let Some(range) = ranges.get(card.id) else {
+            continue;
+        };
+        let (Some(opening), Some(closing)) = (
+            balance_on(add_days(&range.from, -1), card.id)?,
+            balance_on(range.to.clone(), card.id)?,
+        ) else {
+            continue;
+        };
+        balances.push(OutstandingBalance {
+            starting_balance: opening.outstanding,
+            spending: round_cents(closing.spending - opening.spending),
+            charges: round_cents(closing.charges - opening.charges),
+            payments: round_cents(closing.payments - opening.payments),
+            ..closing
+        });
+    }
+    Ok(balances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_periods_resolve_per_card() {
+        let parse = |text: &str| text.parse::<Period>();
+        let today = "2026-10-15";
+        let unmoved = BTreeMap::new();
+        let range = |period: Period, renewal_day: Option<i32>| {
+            let range = period.range(renewal_day.map(|day| (day, &unmoved)), today).unwrap();
+            (range.from, range.to)
+        };
+        let day = |s: &str| s.to_string();
+
+        assert_eq!(range(parse("this-month").unwrap(), None), (day("2026-10-01"), day("2026-10-31")));
+        assert_eq!(range(parse("last-month").unwrap(), None), (day("2026-09-01"), day("2026-09-30")));
+        assert_eq!(range(parse("ytd").unwrap(), None), (day("2026-01-01"), day("2026-10-15")));
+        assert_eq!(range(parse("2026-Q1").unwrap(), None), (day("2026-01-01"), day("2026-03-31")));
+        assert_eq!(range(parse("2025-q4").unwrap(), None), (day("2025-10-01"), day("2025-12-31")));
+        assert_eq!(
+            range(parse("2026-01-05..2026-02-04").unwrap(), None),
+            (day("2026-01-05"), day("2026-02-04"))
+        );
+        assert!(parse("2026-Q5").is_err());
+        assert!(parse("2026-02-30..2026-03-01").is_err());
+        assert!(parse("2026-03-01..2026-02-01").is_err());
+        assert!(parse("next-week").is_err());
+
+        // Cycles need a card: renewing on the 20th, today is in the cycle
+        // that began Sep 18 (the 20th was a Sunday)
+        assert_eq!(Period::ThisCycle.range(None, today), None);
+        assert_eq!(range(Period::ThisCycle, Some(20)), (day("2026-09-18"), day("2026-10-19")));
+        assert_eq!(range(Period::LastCycle, Some(20)), (day("2026-08-20"), day("2026-09-17")));
+    }
+}
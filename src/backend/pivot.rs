@@ -0,0 +1,154 @@
+//! Wide CSV export: one row per month and a spend and a miles column per
+//! category or card, ready for a spreadsheet pivot table or chart.
+
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+use crate::db::STATUS_POSTED;
+use crate::engine::{parse_date, round_cents};
+use crate::error::Result;
+use crate::period::CardRanges;
+use crate::store::Store;
+
+/// What the columns of the matrix are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PivotBy {
+    Category,
+    Card,
+}
+
+/// Posted spend and miles per month (rows) and category or card (columns)
+pub struct Pivot {
+    pub columns: Vec<String>,
+    /// Month (YYYY-MM) and its (spend, miles) per column, oldest first
+    pub rows: Vec<(String, Vec<(f64, f64)>)>,
+}
+
+/// The month after a YYYY-MM month.
+fn next_month(month: &str) -> String {
+    let (year, number, _) = parse_date(month);
+    let (year, number) = if number == 12 { (year + 1, 1) } else { (year, number + 1) };
+    format!("{:04}-{:02}", year, number)
+}
+
+/// Builds the matrix of posted spending, only within `ranges` when given.
+/// Months without spending between the first and last are kept as zeros,
+/// so a chart's time axis has no gaps.
+pub fn build<S: Store + ?Sized>(store: &S, by: PivotBy, ranges: Option<&CardRanges>) -> Result<Pivot> {
+    let cards = store.list_cards()?;
+    let mut cells: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+    let mut categories: Vec<String> = Vec::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status != STATUS_POSTED || ranges.is_some_and(|r| !r.contains(s.card_id, &s.date)) {
+            return Ok(());
+        }
+        let column = match by {
+            PivotBy::Category => {
+                if !categories.contains(&s.category) {
+                    categories.push(s.category.clone());
+                }
+                s.category
+            }
+            PivotBy::Card => s.card_id.to_string(),
+        };
+        let cell = cells.entry((s.date[..7].to_string(), column)).or_default();
+        cell.0 += s.amount;
+        cell.1 += s.miles_earned;
+        Ok(())
+    })?;
+
+    // Columns are keyed by category name, or by card ID and labelled with its name
+    let (keys, columns): (Vec<String>, Vec<String>) = match by {
+        PivotBy::Category => {
+            categories.sort();
+            (categories.clone(), categories)
+        }
+        PivotBy::Card => cards.iter().map(|c| (c.id.to_string(), c.name.clone())).unzip(),
+    };
+
+    let mut rows = Vec::new();
+    if let (Some(((first, _), _)), Some(((last, _), _))) = (cells.first_key_value(), cells.last_key_value()) {
+        let (mut month, last) = (first.clone(), last.clone());
+        while month <= last {
+            let values = keys
+                .iter()
+                .map(|key| cells.get(&(month.clone(), key.clone())).copied().unwrap_or_default())
+                .map(|(spend, miles)| (round_cents(spend), round_cents(miles)))
+                .collect();
+            let following = next_month(&month);
+            rows.push((month, values));
+            month = following;
+        }
+    }
+    Ok(Pivot { columns, rows })
+}
+
+impl Pivot {
+    /// The matrix as CSV: `month`, then `<column> spend` and `<column> miles`
+    /// for each column, then the month's totals.
+    pub fn to_csv(&self) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut header = vec!["month".to_string()];
+        for column in self.columns.iter().chain(std::iter::once(&"total".to_string())) {
+            header.push(format!("{} spend", column));
+            header.push(format!("{} miles", column));
+        }
+        writer.write_record(&header).expect("CSV writes to memory");
+        for (month, values) in &self.rows {
+            let spend: f64 = values.iter().map(|v| v.0).sum();
+            let miles: f64 = values.iter().map(|v| v.1).sum();
+            let mut record = vec![month.clone()];
+            for (spend, miles) in values.iter().chain(std::iter::once(&(round_cents(spend), round_cents(miles)))) {
+                record.push(spend.to_string());
+                record.push(miles.to_string());
+            }
+            writer.write_record(&record).expect("CSV writes to memory");
+        }
+        String::from_utf8(writer.into_inner().expect("CSV writes to memory")).expect("CSV is UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_pivot_by_category_and_card() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    miles_per_dollar: 2.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let (a, b) = (card("Card A"), card("Card B"));
+        db::add_spending(&conn, a, 40.0, "dining", "2026-01-10", None, false, None).unwrap();
+        db::add_spending(&conn, b, 10.0, "groceries", "2026-01-20", None, false, None).unwrap();
+        db::add_spending(&conn, a, 25.5, "dining", "2026-03-02", None, false, None).unwrap();
+        db::schedule_spending(&conn, b, 500.0, "dining", "2026-03-05", None, false, None).unwrap();
+
+        let csv = build(&conn, PivotBy::Category, None).unwrap().to_csv();
+        assert_eq!(
+            csv,
+            "month,dining spend,dining miles,groceries spend,groceries miles,total spend,total miles\n\
+             2026-01,40,80,10,20,50,100\n\
+             2026-02,0,0,0,0,0,0\n\
+             2026-03,25.5,50,0,0,25.5,50\n"
+        );
+
+        let pivot = build(&conn, PivotBy::Card, None).unwrap();
+        assert_eq!(pivot.columns, ["Card A", "Card B"]);
+        assert_eq!(pivot.rows[0].1, [(40.0, 80.0), (10.0, 20.0)]);
+    }
+}
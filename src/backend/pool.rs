@@ -0,0 +1,128 @@
+//! Points pooling: cards that earn into the same loyalty program share one
+//! balance there, so `balances --by program` adds their miles up, and
+//! `programs cards` shows which program each card earns into.
+
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+use crate::engine::round_cents;
+use crate::error::Result;
+use crate::models::{ProgramBalance, ProgramMembership};
+use crate::store::Store;
+
+/// What the balances have one row per
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BalancesBy {
+    Card,
+    Program,
+}
+
+/// Miles balances on `as_of` pooled by program, in program order. A card
+/// without a program pools with nothing and keeps a row of its own, after
+/// the programs.
+pub fn program_balances<S: Store + ?Sized>(store: &S, as_of: &str) -> Result<Vec<ProgramBalance>> {
+    let programs: BTreeMap<i64, String> =
+        store.list_cards()?.into_iter().filter_map(|card| Some((card.id, card.program?))).collect();
+    let mut pools: BTreeMap<String, ProgramBalance> = BTreeMap::new();
+    let mut unpooled = Vec::new();
+    for balance in store.card_balances(as_of)? {
+        let Some(program) = programs.get(&balance.card_id) else {
+            unpooled.push(ProgramBalance {
+                program: "-".to_string(),
+                cards: balance.card_name,
+                credited_miles: balance.credited_miles,
+                pending_miles: balance.pending_miles,
+                total_miles: balance.total_miles,
+                scheduled_miles: balance.scheduled_miles,
+            });
+            continue;
+        };
+        let pool = pools.entry(program.clone()).or_insert_with(|| ProgramBalance {
+            program: program.clone(),
+            cards: String::new(),
+            credited_miles: 0.0,
+            pending_miles: 0.0,
+            total_miles: 0.0,
+            scheduled_miles: 0.0,
+        });
+        if !pool.cards.is_empty() {
+            pool.cards.push_str(", ");
+        }
+        pool.cards.push_str(&balance.card_name);
+        pool.credited_miles = round_cents(pool.credited_miles + balance.credited_miles);
+        pool.pending_miles = round_cents(pool.pending_miles + balance.pending_miles);
+        pool.total_miles = round_cents(pool.total_miles + balance.total_miles);
+        pool.scheduled_miles = round_cents(pool.scheduled_miles + balance.scheduled_miles);
+    }
+    Ok(pools.into_values().chain(unpooled).collect())
+}
+
+/// The program each card's earnings land in, by card, with the program's
+/// kind when it was added with `programs add`.
+pub fn memberships<S: Store + ?Sized>(store: &S) -> Result<Vec<ProgramMembership>> {
+    let programs = store.list_programs()?;
+    Ok(store
+        .list_cards()?
+        .into_iter()
+        .map(|card| {
+            let kind = card.program.as_deref().and_then(|name| {
+                programs.iter().find(|program| program.name.eq_ignore_ascii_case(name)).map(|program| program.kind)
+            });
+            ProgramMembership {
+                card_id: card.id,
+                card_name: card.name,
+                reward_type: card.reward_type,
+                program: card.program.unwrap_or_else(|| "-".to_string()),
+                kind: kind.map_or("-".to_string(), |kind| kind.to_string()),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_cards_in_a_program_share_a_balance() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str, program: Option<&str>| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    miles_per_dollar: 2.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    program: program.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let a = card("Card A", Some("KrisFlyer"));
+        let b = card("Card B", None);
+        let c = card("Card C", Some("KrisFlyer"));
+        let d = card("Card D", Some("Asia Miles"));
+        db::add_spending(&conn, a, 40.0, "dining", "2026-01-10", None, false, None).unwrap();
+        db::add_spending(&conn, b, 10.0, "dining", "2026-01-10", None, false, None).unwrap();
+        db::add_spending(&conn, c, 25.0, "dining", "2026-01-10", None, false, None).unwrap();
+        db::add_spending(&conn, d, 5.0, "dining", "2026-01-10", None, false, None).unwrap();
+
+        let pools = program_balances(&conn, &db::today()).unwrap();
+        let rows: Vec<(&str, &str, f64)> =
+            pools.iter().map(|p| (p.program.as_str(), p.cards.as_str(), p.total_miles)).collect();
+        assert_eq!(
+            rows,
+            [
+                ("Asia Miles", "Card D", 10.0),
+                ("KrisFlyer", "Card A, Card C", 130.0),
+                ("-", "Card B", 20.0)
+            ]
+        );
+    }
+}
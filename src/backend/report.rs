@@ -0,0 +1,281 @@
+//! `report`: a month's spend and miles broken down per card and per
+//! category, with where each card's cap and minimum spend stood at the
+//! month's end, as plain tables or as markdown to paste into notes, and
+//! with `--chart` the same drawn as bars beside a year's spend trend.
+
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+use crate::chart;
+use crate::db::STATUS_POSTED;
+use crate::digest::{month_bounds, MONTH_NAMES};
+use crate::error::Result;
+use crate::models::{CycleSummary, StatsBy};
+use crate::period::DateRange;
+use crate::rollover;
+use crate::store::Store;
+
+/// How the report is laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Tables as the other commands print them
+    #[default]
+    Text,
+    /// Headings and markdown tables
+    Markdown,
+}
+
+/// Cells a chart's longest bar spans
+const CHART_WIDTH: usize = 30;
+/// Months the spend trend covers, ending with the report's
+const TREND_MONTHS: i32 = 12;
+
+#[derive(Tabled)]
+struct CardRow {
+    card: String,
+    transactions: usize,
+    spent: String,
+    miles: String,
+    cap: String,
+    min_spend: String,
+}
+
+#[derive(Tabled)]
+struct CategoryRow {
+    category: String,
+    transactions: usize,
+    spent: String,
+    share: String,
+    miles: String,
+}
+
+/// The share of a card's cap its cycle has used, in percent.
+fn cap_percent(summary: &CycleSummary, cap: Option<f64>) -> Option<f64> {
+    let (cap, left) = (cap?, summary.cap_left?);
+    Some(if cap > 0.0 { (cap - left) / cap * 100.0 } else { 100.0 })
+}
+
+/// How much of the card's cap its cycle has used, e.g. "$460.00 of
+/// $1000.00 (46%)", or "-" without one.
+fn cap_used(summary: &CycleSummary, cap: Option<f64>) -> String {
+    let (Some(cap), Some(left), Some(percent)) = (cap, summary.cap_left, cap_percent(summary, cap)) else {
+        return "-".to_string();
+    };
+    match summary.cap_unit.as_deref() {
+        Some("miles") => format!("{:.0} of {:.0} miles ({:.0}%)", cap - left, cap, percent),
+        _ => format!("${:.2} of ${:.2} ({:.0}%)", cap - left, cap, percent),
+    }
+}
+
+/// The monthly spend trend, miles by category and cap use drawn as charts.
+fn charts<S: Store + ?Sized>(
+    store: &S,
+    year: i32,
+    month: i32,
+    categories: &[(String, usize, f64, f64)],
+    caps: &[(String, f64)],
+) -> Result<String> {
+    let months: Vec<String> = (0..TREND_MONTHS)
+        .rev()
+        .map(|back| {
+            let index = year * 12 + month - 1 - back;
+            format!("{:04}-{:02}", index.div_euclid(12), index.rem_euclid(12) + 1)
+        })
+        .collect();
+    let period = DateRange { from: format!("{}-01", months[0]), to: month_bounds(year, month).1 };
+    let spent = store.spending_stats(StatsBy::Month, None, Some(&period))?;
+    let trend: Vec<(String, f64, String)> = months
+        .iter()
+        .map(|m| {
+            let total = spent.iter().find(|s| &s.name == m).map_or(0.0, |s| s.total_spent);
+            (m.clone(), total, format!("${:.2}", total))
+        })
+        .collect();
+    let values: Vec<f64> = trend.iter().map(|(_, value, _)| *value).collect();
+    let most = values.iter().copied().fold(0.0, f64::max);
+    let mut out = format!(
+        "Spend, {} to {}: {}
+{}",
+        months[0],
+        months[months.len() - 1],
+        chart::sparkline(&values),
+        chart::bars(&trend, most, CHART_WIDTH)
+    );
+
+    let miles: Vec<(String, f64, String)> =
+        categories.iter().map(|(category, _, _, miles)| (category.clone(), *miles, format!("{:.0}", miles))).collect();
+    let most = miles.iter().map(|(_, value, _)| *value).fold(0.0, f64::max);
+    out.push_str(&format!("
+Miles by category
+{}", chart::bars(&miles, most, CHART_WIDTH)));
+
+    let caps: Vec<(String, f64, String)> =
+        caps.iter().map(|(card, percent)| (card.clone(), *percent, format!("{:.0}%", percent))).collect();
+    if !caps.is_empty() {
+        out.push_str(&format!("
+Cap use
+{}", chart::bars(&caps, 100.0, CHART_WIDTH)));
+    }
+    Ok(out)
+}
+
+/// Builds the report for a month. Spend and miles count the month's posted
+/// transactions; the cap and minimum spend columns are for each card's
+/// statement cycle containing the month's last day. `chart` adds charts.
+pub fn build<S: Store + ?Sized>(store: &S, year: i32, month: i32, format: ReportFormat, chart: bool) -> Result<String> {
+    let (first, last) = month_bounds(year, month);
+    let cards: Vec<_> = store.list_cards()?.into_iter().filter(|c| !c.is_closed_on(&first)).collect();
+
+    let mut by_card: BTreeMap<i64, (usize, f64, f64)> = BTreeMap::new();
+    // Keyed in lower case, with the name first seen
+    let mut by_category: BTreeMap<String, (String, usize, f64, f64)> = BTreeMap::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status != STATUS_POSTED || s.date < first || s.date > last {
+            return Ok(());
+        }
+        let card = by_card.entry(s.card_id).or_default();
+        card.0 += 1;
+        card.1 += s.amount;
+        card.2 += s.miles_earned;
+        let category = by_category.entry(s.category.to_lowercase()).or_insert_with(|| (s.category.clone(), 0, 0.0, 0.0));
+        category.1 += 1;
+        category.2 += s.amount;
+        category.3 += s.miles_earned;
+        Ok(())
+    })?;
+    let total: f64 = by_card.values().map(|(_, spent, _)| spent).sum();
+    let miles: f64 = by_card.values().map(|(_, _, miles)| miles).sum();
+    let count: usize = by_card.values().map(|(count, _, _)| count).sum();
+
+    let mut card_rows = Vec::new();
+    let mut caps = Vec::new();
+    for card in &cards {
+        let (transactions, spent, miles) = by_card.get(&card.id).copied().unwrap_or_default();
+        let summary = rollover::summary(store, card, &last)?;
+        if let Some(percent) = cap_percent(&summary, card.max_reward_limit) {
+            caps.push((card.name.clone(), percent));
+        }
+        card_rows.push(CardRow {
+            card: card.name.clone(),
+            transactions,
+            spent: format!("${:.2}", spent),
+            miles: format!("{:.0}", miles),
+            cap: cap_used(&summary, card.max_reward_limit),
+            min_spend: summary.min_spend_status.unwrap_or_else(|| "-".to_string()),
+        });
+    }
+    let mut categories: Vec<_> = by_category.into_values().collect();
+    categories.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let drawn = if chart { Some(charts(store, year, month, &categories, &caps)?) } else { None };
+    let category_rows: Vec<CategoryRow> = categories
+        .into_iter()
+        .map(|(category, transactions, spent, miles)| CategoryRow {
+            category,
+            transactions,
+            spent: format!("${:.2}", spent),
+            share: format!("{:.0}%", spent / total.max(0.01) * 100.0),
+            miles: format!("{:.0}", miles),
+        })
+        .collect();
+
+    let title = format!("Spending report for {} {}", MONTH_NAMES[month as usize - 1], year);
+    let totals = format!("Spent ${:.2} over {} transactions and earned {:.0} miles.", total, count, miles);
+    let caps_note = format!("Caps and minimum spends are for each card's statement cycle containing {}.", last);
+    let (mut card_table, mut category_table) = (Table::new(card_rows), Table::new(category_rows));
+    let mut out = match format {
+        ReportFormat::Text => format!(
+            "{}\n{}\n\nBy card\n{}\n{}\n\nBy category\n{}\n",
+            title, totals, card_table, caps_note, category_table
+        ),
+        ReportFormat::Markdown => format!(
+            "# {}\n\n{}\n\n## By card\n\n{}\n\n{}\n\n## By category\n\n{}\n",
+            title,
+            totals,
+            card_table.with(Style::markdown()),
+            caps_note,
+            category_table.with(Style::markdown())
+        ),
+    };
+    match (drawn, format) {
+        (None, _) => {}
+        (Some(charts), ReportFormat::Text) => out.push_str(&format!("\nCharts\n{}", charts)),
+        // A code block keeps the bars lined up once rendered
+        (Some(charts), ReportFormat::Markdown) => out.push_str(&format!("\n## Charts\n\n```\n{}```\n", charts)),
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_markdown_report_breaks_down_cards_and_categories() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Altitude".to_string(),
+                categories: vec!["dining".to_string(), "travel".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 2.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit: Some(1000.0),
+                min_spend: Some(500.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::add_spending(&conn, card, 150.0, "travel", "2026-03-03", None, false, None).unwrap();
+        db::add_spending(&conn, card, 50.0, "Dining", "2026-03-10", None, false, None).unwrap();
+        db::add_spending(&conn, card, 50.0, "dining", "2026-03-12", None, false, None).unwrap();
+        db::add_spending(&conn, card, 99.0, "dining", "2026-02-20", None, false, None).unwrap();
+
+        let report = build(&conn, 2026, 3, ReportFormat::Markdown, false).unwrap();
+        assert!(report.starts_with(
+            "# Spending report for March 2026\n\nSpent $250.00 over 3 transactions and earned 500 miles.\n\n## By card\n"
+        ));
+        assert!(report.contains("| Altitude | 3            | $250.00 | 500   | $250.00 of $1000.00 (25%) | $250.00 to go by 2026-03-31 |"));
+        assert!(report.contains("| travel   | 1            | $150.00 | 60%   | 300   |"));
+        // Categories add up whatever their case
+        assert!(report.to_lowercase().contains("| dining   | 2            | $100.00 | 40%   | 200   |"));
+    }
+
+    #[test]
+    fn test_charts_show_trend_miles_and_cap_use() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Altitude".to_string(),
+                categories: vec!["dining".to_string(), "travel".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 2.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                max_reward_limit: Some(1000.0),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::add_spending(&conn, card, 500.0, "travel", "2026-03-03", None, false, None).unwrap();
+        db::add_spending(&conn, card, 250.0, "dining", "2026-02-20", None, false, None).unwrap();
+
+        let report = build(&conn, 2026, 3, ReportFormat::Text, true).unwrap();
+        assert!(report.contains("Spend, 2025-04 to 2026-03: ▁▁▁▁▁▁▁▁▁▁▅█\n"));
+        assert!(report.contains(&format!("2026-02  {:<30}  $250.00\n", "█".repeat(15))));
+        assert!(report.contains(&format!("travel  {}  1000\n", "█".repeat(30))));
+        assert!(report.contains(&format!("Cap use\nAltitude  {:<30}  50%\n", "█".repeat(15))));
+        assert!(!build(&conn, 2026, 3, ReportFormat::Text, false).unwrap().contains("Charts"));
+        let markdown = build(&conn, 2026, 3, ReportFormat::Markdown, true).unwrap();
+        assert!(markdown.contains("## Charts\n\n```\nSpend, 2025-04"));
+    }
+}
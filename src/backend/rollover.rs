@@ -0,0 +1,282 @@
+//! `preview-cycle`: when a card's statement cycle ends, what resets then
+//! and how the next cycle opens; and `cycle-summary`, the same cycle's
+//! standing as a row for tables and scripts.
+
+
+use crate::db::{STATUS_POSTED, STATUS_SCHEDULED};
+use crate::dates;
+use crate::engine::{add_days, card_cycle, days_between, miles_posting_date, renewal_moved_from, round_cents};
+use crate::error::Result;
+use crate::models::{CapUnit, Card, CycleSummary};
+use crate::store::Store;
+
+fn plural(n: i32, word: &str) -> String {
+    format!("{} {}{}", n, word, if n == 1 { "" } else { "s" })
+}
+
+/// Builds the preview of `card`'s cycle containing `date` as plain text.
+pub fn preview<S: Store + ?Sized>(store: &S, card: &Card, date: &str) -> Result<String> {
+    let (start, end) = card_cycle(card, date);
+    let next_start = add_days(&end, 1);
+    let (_, next_end) = card_cycle(card, &next_start);
+    let overrides = card.cycle_overrides();
+
+    let (mut posted, mut scheduled, mut miles, mut next_scheduled, mut next_miles) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    // The same, counting only spend towards the minimum spend
+    let (mut posted_qualifying, mut scheduled_qualifying, mut next_qualifying) = (0.0, 0.0, 0.0);
+    let excluded = card.excluded_from_min_spend();
+    store.for_each_spending(Some(card.id), None, &mut |s| {
+        let qualifying = if excluded.contains(&s.category.to_lowercase()) { 0.0 } else { s.amount };
+        if s.date >= start && s.date <= end {
+            if s.status == STATUS_POSTED {
+                posted += s.amount;
+                posted_qualifying += qualifying;
+                miles += s.miles_earned;
+            } else if s.status == STATUS_SCHEDULED {
+                scheduled += s.amount;
+                scheduled_qualifying += qualifying;
+            }
+        } else if s.date >= next_start && s.date <= next_end && s.status == STATUS_SCHEDULED {
+            next_scheduled += s.amount;
+            next_qualifying += qualifying;
+            next_miles += s.miles_earned;
+        }
+        Ok(())
+    })?;
+
+    let mut out = format!("{}: statement cycle {} to {}\n", card.name, start, end);
+    let days_left = days_between(date, &end);
+    out.push_str(&format!(
+        "  Ends {}, {}; the next cycle starts {}",
+        end,
+        if days_left == 0 { "today".to_string() } else { format!("in {}", plural(days_left, "day")) },
+        next_start
+    ));
+    match renewal_moved_from(card.statement_renewal_date, &next_start) {
+        _ if overrides.values().any(|o| o.start == next_start || o.end == end) => out.push_str(" (set by the bank)\n"),
+        Some(from) => out.push_str(&format!(" (moved from {}, a weekend)\n", from)),
+        None => out.push('\n'),
+    }
+
+    out.push_str("\nThis cycle so far\n");
+    out.push_str(&format!("  Spent: ${:.2} posted, ${:.2} scheduled\n", posted, scheduled));
+    out.push_str(&format!(
+        "  Miles: {:.0}, credited on {}\n",
+        miles,
+        miles_posting_date(card.statement_renewal_date, &overrides, card.posting_delay, date)
+    ));
+    match (card.max_reward_limit, card.cap_unit) {
+        (Some(cap), CapUnit::Dollars) => out.push_str(&format!(
+            "  Cap: ${:.2} of ${:.2} used, ${:.2} left\n",
+            posted.min(cap),
+            cap,
+            (cap - posted).max(0.0)
+        )),
+        (Some(cap), CapUnit::Miles) => out.push_str(&format!(
+            "  Cap: {:.0} of {:.0} miles earned, {:.0} left\n",
+            miles.min(cap),
+            cap,
+            (cap - miles).max(0.0)
+        )),
+        (None, _) => {}
+    }
+    if let Some(minimum) = card.min_spend {
+        if posted_qualifying >= minimum {
+            out.push_str(&format!("  Min spend: ${:.2} of ${:.2}, met\n", posted_qualifying, minimum));
+        } else {
+            out.push_str(&format!(
+                "  Min spend: ${:.2} of ${:.2}, ${:.2} to go by {}{}\n",
+                posted_qualifying,
+                minimum,
+                minimum - posted_qualifying,
+                end,
+                if posted_qualifying + scheduled_qualifying >= minimum {
+                    " (met once the scheduled spending posts)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if posted > posted_qualifying {
+            out.push_str(&format!(
+                "  Not counted towards it: ${:.2} in {}\n",
+                posted - posted_qualifying,
+                excluded.join(", ")
+            ));
+        }
+    }
+
+    out.push_str(&format!("\nResets on {}\n", next_start));
+    if card.max_reward_limit.is_none() && card.min_spend.is_none() {
+        out.push_str("  Nothing: the card has no cap or minimum spend\n");
+    }
+    match (card.max_reward_limit, card.cap_unit) {
+        (Some(cap), CapUnit::Dollars) => out.push_str(&format!("  Cap: back to ${:.2}\n", cap)),
+        (Some(cap), CapUnit::Miles) => out.push_str(&format!("  Cap: back to {:.0} miles\n", cap)),
+        (None, _) => {}
+    }
+    if let Some(minimum) = card.min_spend {
+        out.push_str(&format!("  Min spend: progress back to $0.00 of ${:.2}\n", minimum));
+    }
+
+    out.push_str(&format!("\nNext cycle, {} to {}\n", next_start, next_end));
+    if next_scheduled == 0.0 {
+        out.push_str("  Opens empty: nothing is scheduled in it yet\n");
+    } else {
+        out.push_str(&format!("  Opens with ${:.2} scheduled\n", next_scheduled));
+        match (card.max_reward_limit, card.cap_unit) {
+            (Some(cap), CapUnit::Dollars) => {
+                out.push_str(&format!("  Cap: ${:.2} of ${:.2} left\n", (cap - next_scheduled).max(0.0), cap))
+            }
+            (Some(cap), CapUnit::Miles) => {
+                out.push_str(&format!("  Cap: {:.0} of {:.0} miles left\n", (cap - next_miles).max(0.0), cap))
+            }
+            (None, _) => {}
+        }
+        if let Some(minimum) = card.min_spend {
+            out.push_str(&format!(
+                "  Min spend: ${:.2} to go of ${:.2}\n",
+                (minimum - next_qualifying).max(0.0),
+                minimum
+            ));
+        }
+    }
+    Ok(out)
+}
+
+/// Summarizes `card`'s cycle containing `date`: its bounds, the spend and
+/// miles posted in it, the cap's headroom and the minimum spend's status.
+/// Scheduled spending is left out; `preview` shows it.
+pub fn summary<S: Store + ?Sized>(store: &S, card: &Card, date: &str) -> Result<CycleSummary> {
+    let (start, end) = card_cycle(card, date);
+    let excluded = card.excluded_from_min_spend();
+    let (mut spent, mut qualifying, mut miles) = (0.0, 0.0, 0.0);
+    store.for_each_spending(Some(card.id), None, &mut |s| {
+        if s.status == STATUS_POSTED && s.date >= start && s.date <= end {
+            spent += s.amount;
+            miles += s.miles_earned;
+            if !excluded.contains(&s.category.to_lowercase()) {
+                qualifying += s.amount;
+            }
+        }
+        Ok(())
+    })?;
+    let cap_left = card.max_reward_limit.map(|cap| match card.cap_unit {
+        CapUnit::Dollars => round_cents((cap - spent).max(0.0)),
+        CapUnit::Miles => (cap - miles).max(0.0),
+    });
+    let min_spend_status = card.min_spend.map(|minimum| {
+        if qualifying >= minimum {
+            "met".to_string()
+        } else {
+            format!("${:.2} to go by {}", minimum - qualifying, dates::display(&end))
+        }
+    });
+    Ok(CycleSummary {
+        card_id: card.id,
+        card_name: card.name.clone(),
+        days_left: days_between(date, &end),
+        cycle_start: start,
+        cycle_end: end,
+        spent: round_cents(spent),
+        miles,
+        cap_left,
+        cap_unit: card.max_reward_limit.map(|_| card.cap_unit.as_str().to_string()),
+        qualifying: round_cents(qualifying),
+        min_spend: card.min_spend,
+        min_spend_status,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_preview_shows_weekend_rollover_and_next_cycle() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let id = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Altitude".to_string(),
+                categories: vec!["dining".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 3.0,
+                block_size: 1.0,
+                statement_renewal_date: 15,
+                max_reward_limit: Some(1000.0),
+                min_spend: Some(800.0),
+                posting_delay: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::add_spending(&conn, id, 460.0, "dining", "2026-10-16", None, false, None).unwrap();
+        db::schedule_spending(&conn, id, 400.0, "dining", "2026-11-10", None, false, None).unwrap();
+        db::schedule_spending(&conn, id, 60.0, "dining", "2026-11-20", None, false, None).unwrap();
+        let card = db::get_card(&conn, id).unwrap().unwrap();
+
+        // Sunday 15 November moves the renewal to Friday the 13th
+        let preview = preview(&conn, &card, "2026-10-20").unwrap();
+        assert_eq!(
+            preview,
+            "\
+Altitude: statement cycle 2026-10-15 to 2026-11-12
+  Ends 2026-11-12, in 23 days; the next cycle starts 2026-11-13 (moved from 2026-11-15, a weekend)
+
+This cycle so far
+  Spent: $460.00 posted, $400.00 scheduled
+  Miles: 1380, credited on 2026-12-15
+  Cap: $460.00 of $1000.00 used, $540.00 left
+  Min spend: $460.00 of $800.00, $340.00 to go by 2026-11-12 (met once the scheduled spending posts)
+
+Resets on 2026-11-13
+  Cap: back to $1000.00
+  Min spend: progress back to $0.00 of $800.00
+
+Next cycle, 2026-11-13 to 2026-12-14
+  Opens with $60.00 scheduled
+  Cap: $940.00 of $1000.00 left
+  Min spend: $740.00 to go of $800.00
+"
+        );
+    }
+
+    #[test]
+    fn test_summary_reports_cap_in_its_unit_and_min_spend_left() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let id = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Rewards".to_string(),
+                categories: vec!["dining".to_string(), "bills".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 4.0,
+                block_size: 1.0,
+                statement_renewal_date: 15,
+                max_reward_limit: Some(2000.0),
+                cap_unit: CapUnit::Miles,
+                min_spend: Some(500.0),
+                min_spend_exclusions: vec!["bills".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::add_spending(&conn, id, 200.0, "dining", "2026-10-16", None, false, None).unwrap();
+        db::add_spending(&conn, id, 100.0, "bills", "2026-10-18", None, false, None).unwrap();
+        db::schedule_spending(&conn, id, 400.0, "dining", "2026-11-10", None, false, None).unwrap();
+        let card = db::get_card(&conn, id).unwrap().unwrap();
+
+        let summary = summary(&conn, &card, "2026-10-20").unwrap();
+        assert_eq!((summary.cycle_start.as_str(), summary.cycle_end.as_str()), ("2026-10-15", "2026-11-12"));
+        assert_eq!((summary.days_left, summary.spent, summary.miles), (23, 300.0, 1200.0));
+        assert_eq!((summary.cap_left, summary.cap_unit.as_deref()), (Some(800.0), Some("miles")));
+        assert_eq!(summary.qualifying, 200.0);
+        assert_eq!(summary.min_spend_status.as_deref(), Some("$300.00 to go by 2026-11-12"));
+    }
+}
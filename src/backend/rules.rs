@@ -0,0 +1,465 @@
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+use std::path::Path;
+
+use crate::import::{learned_category, ImportedRow, MerchantHistory};
+use crate::models::{MerchantPartnership, MerchantRule, PatternSyntax};
+
+/// One line of the rules file: descriptions matching `pattern` get `category`
+/// and, optionally, `payment_category`.
+#[derive(Debug, Clone)]
+pub struct CategoryRule {
+    /// Line number in the rules file, for reporting which rule matched
+    pub line: usize,
+    pub pattern: Regex,
+    pub category: String,
+    pub payment_category: Option<String>,
+}
+
+/// A payment-category rule: purchases it matches are likely paid with
+/// `payment_category`.
+#[derive(Debug, Clone)]
+pub struct PaymentRule {
+    pub line: usize,
+    pub matches: PaymentMatch,
+    pub payment_category: String,
+}
+
+/// What a payment-category rule matches
+#[derive(Debug, Clone)]
+pub enum PaymentMatch {
+    /// Merchant descriptions matching the pattern
+    Merchant(Regex),
+    /// Purchases in this category
+    Category(String),
+}
+
+/// A rule added with `add-rule`, compiled: merchants matching `pattern`
+/// get `category`.
+#[derive(Debug, Clone)]
+pub struct StoredRule {
+    pub id: i64,
+    pub pattern: Regex,
+    pub category: String,
+}
+
+/// Everything in a rules file, plus the rules stored in the database once
+/// added with [`Rules::with_stored`]
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    pub categories: Vec<CategoryRule>,
+    pub payments: Vec<PaymentRule>,
+    pub stored: Vec<StoredRule>,
+}
+
+impl Rules {
+    /// Adds the rules stored in the database, which are tried after the
+    /// file's. A stored pattern that no longer compiles is skipped.
+    pub fn with_stored(mut self, rules: &[MerchantRule]) -> Rules {
+        self.stored = rules
+            .iter()
+            .filter_map(|rule| {
+                let pattern = stored_pattern(&rule.pattern, rule.syntax).ok()?;
+                Some(StoredRule { id: rule.id, pattern, category: rule.category.clone() })
+            })
+            .collect();
+        self
+    }
+
+    /// The category the rules give a description: the first matching rule
+    /// in the file, otherwise the oldest matching stored rule.
+    pub fn categorize(&self, description: &str) -> Option<MerchantCategory> {
+        if let Some(rule) = first_match(&self.categories, description) {
+            return Some(MerchantCategory::Rule { line: rule.line, category: rule.category.clone() });
+        }
+        let description = description.trim();
+        self.stored
+            .iter()
+            .find(|rule| rule.pattern.is_match(description))
+            .map(|rule| MerchantCategory::Stored { id: rule.id, category: rule.category.clone() })
+    }
+}
+
+/// Parses rules written as `pattern -> category [+ payment_category]`, one per
+/// line. `→` may be used instead of `->`; blank lines and lines starting with
+/// `#` are ignored. Patterns are regular expressions matched case-insensitively.
+///
+/// A rule without a category, `pattern -> + payment_category`, only says how
+/// matching merchants are paid, and `category:name -> + payment_category` how
+/// purchases in a category are.
+pub fn parse_rules(text: &str) -> Result<Rules, String> {
+    let mut rules = Rules::default();
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // Split on the last arrow so patterns may themselves contain "->"
+        let split = trimmed
+            .rfind("->")
+            .map(|i| (i, "->".len()))
+            .or_else(|| trimmed.rfind('→').map(|i| (i, '→'.len_utf8())))
+            .ok_or_else(|| format!("Line {}: expected 'pattern -> category'", line))?;
+        let pattern = trimmed[..split.0].trim();
+        let target = trimmed[split.0 + split.1..].trim();
+
+        let (category, payment_category) = match target.split_once('+') {
+            Some((category, payment)) => (category.trim(), Some(payment.trim().to_lowercase())),
+            None => (target, None),
+        };
+        if pattern.is_empty() || payment_category.as_deref() == Some("") {
+            return Err(format!("Line {}: expected 'pattern -> category [+ payment_category]'", line));
+        }
+        if category.is_empty() {
+            let Some(payment_category) = payment_category else {
+                return Err(format!("Line {}: expected 'pattern -> category [+ payment_category]'", line));
+            };
+            let matches = match pattern.strip_prefix("category:") {
+                Some(name) if name.trim().is_empty() => {
+                    return Err(format!("Line {}: expected 'category:name -> + payment_category'", line));
+                }
+                Some(name) => PaymentMatch::Category(name.trim().to_lowercase()),
+                None => PaymentMatch::Merchant(
+                    RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| format!("Line {}: invalid pattern: {}", line, e))?,
+                ),
+            };
+            rules.payments.push(PaymentRule { line, matches, payment_category });
+            continue;
+        }
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Line {}: invalid pattern: {}", line, e))?;
+
+        rules.categories.push(CategoryRule {
+            line,
+            pattern: regex,
+            category: category.to_lowercase(),
+            payment_category,
+        });
+    }
+    Ok(rules)
+}
+
+/// Loads the rules file, returning no rules if it does not exist.
+pub fn load(path: &Path) -> Result<Rules, String> {
+    if !path.exists() {
+        return Ok(Rules::default());
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_rules(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The first rule, in file order, whose pattern matches the description.
+pub fn first_match<'a>(rules: &'a [CategoryRule], description: &str) -> Option<&'a CategoryRule> {
+    rules.iter().find(|rule| rule.pattern.is_match(description))
+}
+
+/// Sets the category of every row whose description matches a rule, then
+/// the payment category any rule infers for it. Rules take precedence over
+/// the statement's own category.
+pub fn apply(rules: &Rules, rows: &mut [ImportedRow]) {
+    for row in rows {
+        if let Some(found) = row.description.as_deref().and_then(|d| rules.categorize(d)) {
+            row.category = Some(found.category().to_string());
+        }
+        if let Some(inferred) = infer_payment_category(rules, row.description.as_deref(), row.category.as_deref()) {
+            row.payment_category = Some(inferred.payment_category.to_string());
+        }
+    }
+}
+
+/// A payment category inferred from a rule
+#[derive(Debug, PartialEq)]
+pub struct InferredPayment<'a> {
+    pub line: usize,
+    pub payment_category: &'a str,
+}
+
+/// How a purchase is likely paid. Rules about the merchant come before rules
+/// about its category: first the categorization rule for the merchant, if it
+/// gives a payment category, then the first payment rule matching the
+/// merchant, then the first for the category.
+pub fn infer_payment_category<'a>(
+    rules: &'a Rules,
+    merchant: Option<&str>,
+    category: Option<&str>,
+) -> Option<InferredPayment<'a>> {
+    let by_merchant = merchant.and_then(|merchant| {
+        first_match(&rules.categories, merchant)
+            .and_then(|rule| {
+                rule.payment_category.as_deref().map(|payment_category| InferredPayment { line: rule.line, payment_category })
+            })
+            .or_else(|| {
+                rules.payments.iter().find_map(|rule| match &rule.matches {
+                    PaymentMatch::Merchant(pattern) if pattern.is_match(merchant) => {
+                        Some(InferredPayment { line: rule.line, payment_category: &rule.payment_category })
+                    }
+                    _ => None,
+                })
+            })
+    });
+    by_merchant.or_else(|| {
+        let category = category?.to_lowercase();
+        rules.payments.iter().find_map(|rule| match &rule.matches {
+            PaymentMatch::Category(name) if *name == category => {
+                Some(InferredPayment { line: rule.line, payment_category: &rule.payment_category })
+            }
+            _ => None,
+        })
+    })
+}
+
+/// Where a merchant's category was found, for `best-card --for` and
+/// `add-spending` without a category
+#[derive(Debug, PartialEq)]
+pub enum MerchantCategory {
+    /// A rule in the rules file matched
+    Rule { line: usize, category: String },
+    /// A rule added with `add-rule` matched
+    Stored { id: i64, category: String },
+    /// The category most past purchases there were recorded under, with
+    /// the share of them that were
+    History { category: String, share: f64 },
+}
+
+impl MerchantCategory {
+    pub fn category(&self) -> &str {
+        match self {
+            MerchantCategory::Rule { category, .. }
+            | MerchantCategory::Stored { category, .. }
+            | MerchantCategory::History { category, .. } => category,
+        }
+    }
+}
+
+impl fmt::Display for MerchantCategory {
+    /// Says where the category came from, e.g. "rule on line 3"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerchantCategory::Rule { line, .. } => write!(f, "rule on line {}", line),
+            MerchantCategory::Stored { id, .. } => write!(f, "rule {} from add-rule", id),
+            MerchantCategory::History { share, .. } => write!(f, "{:.0}% of past purchases there", share * 100.0),
+        }
+    }
+}
+
+/// Finds a merchant's category: the first matching rule, as for imported
+/// statements, otherwise the category used most often there before.
+pub fn categorize_merchant(rules: &Rules, history: &MerchantHistory, merchant: &str) -> Option<MerchantCategory> {
+    if let Some(found) = rules.categorize(merchant) {
+        return Some(found);
+    }
+    learned_category(history, merchant).map(|(category, share)| MerchantCategory::History { category, share })
+}
+
+/// Compiles a partnership's merchant pattern, matched case-insensitively
+/// like rule patterns.
+pub fn merchant_pattern(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("Invalid merchant pattern: {}", e))
+}
+
+/// Compiles a stored rule's pattern, matched case-insensitively like the
+/// rules file's. A glob must cover the whole description: `GRAB*` matches
+/// "GRAB*RIDE 1234" but not "PAY GRAB".
+pub fn stored_pattern(pattern: &str, syntax: PatternSyntax) -> Result<Regex, String> {
+    let source = match syntax {
+        PatternSyntax::Regex => pattern.to_string(),
+        PatternSyntax::Glob => {
+            let mut source = String::from("^");
+            for c in pattern.chars() {
+                match c {
+                    '*' => source.push_str(".*"),
+                    '?' => source.push('.'),
+                    c => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                }
+            }
+            source.push('$');
+            source
+        }
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("Invalid rule pattern: {}", e))
+}
+
+/// The partnership with the largest bonus whose pattern matches the
+/// merchant. Bonuses from several matching partnerships do not stack.
+pub fn partnership_for<'a>(
+    partnerships: &'a [MerchantPartnership],
+    merchant: &str,
+) -> Option<&'a MerchantPartnership> {
+    partnerships
+        .iter()
+        .filter(|p| merchant_pattern(&p.merchant_pattern).is_ok_and(|re| re.is_match(merchant)))
+        .max_by(|a, b| a.bonus_miles_per_dollar.total_cmp(&b.bonus_miles_per_dollar))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = "\
+# Rides first so GRAB FOOD is not caught by them
+^GRAB\\*? ?(RIDE|CAR) -> transport + mobile contactless
+GRAB ?FOOD|FOODPANDA → dining
+cold storage|fairprice -> Groceries
+
+netflix|spotify -> entertainment + online
+";
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules(RULES).unwrap().categories;
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[0].line, 2);
+        assert_eq!(rules[0].payment_category.as_deref(), Some("mobile contactless"));
+        assert_eq!(rules[1].category, "dining");
+        assert_eq!(rules[2].category, "groceries");
+        assert_eq!(rules[2].payment_category, None);
+    }
+
+    #[test]
+    fn test_parse_rules_reports_bad_lines() {
+        assert!(parse_rules("GRAB transport").unwrap_err().starts_with("Line 1"));
+        assert!(parse_rules("# ok\n(unclosed -> dining").unwrap_err().starts_with("Line 2: invalid pattern"));
+        assert!(parse_rules("NETFLIX -> entertainment +").is_err());
+        assert!(parse_rules("NETFLIX ->").is_err());
+        assert!(parse_rules("category: -> + online").unwrap_err().starts_with("Line 1"));
+    }
+
+    #[test]
+    fn test_first_match_uses_file_order() {
+        let rules = parse_rules(RULES).unwrap().categories;
+        assert_eq!(first_match(&rules, "GRAB*RIDE 1234").map(|r| r.line), Some(2));
+        assert_eq!(first_match(&rules, "Grab Food SG").map(|r| r.line), Some(3));
+        assert_eq!(first_match(&rules, "COLD STORAGE #12").map(|r| r.line), Some(4));
+        assert!(first_match(&rules, "SHELL PETROL").is_none());
+    }
+
+    #[test]
+    fn test_apply_overrides_statement_category() {
+        let rules = parse_rules(RULES).unwrap();
+        let row = |description: &str| ImportedRow {
+            date: "2026-02-05".to_string(),
+            amount: 10.0,
+            description: Some(description.to_string()),
+            category: Some("misc".to_string()),
+            payment_category: None,
+        };
+        let mut rows = vec![row("NETFLIX.COM"), row("SHELL PETROL")];
+        apply(&rules, &mut rows);
+        assert_eq!(rows[0].category.as_deref(), Some("entertainment"));
+        assert_eq!(rows[0].payment_category.as_deref(), Some("online"));
+        assert_eq!(rows[1].category.as_deref(), Some("misc"));
+    }
+
+    #[test]
+    fn test_infer_payment_category_prefers_merchant_rules() {
+        let rules = parse_rules(&format!(
+            "{}amazon|lazada -> + online\ncategory:Dining -> + contactless\nnetflix -> + mobile contactless\n",
+            RULES
+        ))
+        .unwrap();
+        assert_eq!(rules.categories.len(), 4);
+        assert_eq!(rules.payments.len(), 3);
+        let infer = |merchant: Option<&str>, category: Option<&str>| {
+            infer_payment_category(&rules, merchant, category).map(|p| (p.line, p.payment_category.to_string()))
+        };
+        // The categorization rule's own payment category comes first
+        assert_eq!(infer(Some("NETFLIX.COM"), Some("entertainment")), Some((6, "online".to_string())));
+        assert_eq!(infer(Some("AMAZON SG"), Some("dining")), Some((7, "online".to_string())));
+        assert_eq!(infer(Some("Grab Food SG"), Some("dining")), Some((8, "contactless".to_string())));
+        assert_eq!(infer(None, Some("dining")), Some((8, "contactless".to_string())));
+        assert_eq!(infer(Some("SHELL PETROL"), Some("transport")), None);
+        assert_eq!(infer(Some("SHELL PETROL"), None), None);
+
+        let mut rows = vec![ImportedRow {
+            date: "2026-02-05".to_string(),
+            amount: 10.0,
+            description: Some("FOODPANDA".to_string()),
+            category: None,
+            payment_category: None,
+        }];
+        apply(&rules, &mut rows);
+        assert_eq!(rows[0].category.as_deref(), Some("dining"));
+        assert_eq!(rows[0].payment_category.as_deref(), Some("contactless"));
+    }
+
+    #[test]
+    fn test_categorize_merchant_prefers_rules_to_history() {
+        let rules = parse_rules(RULES).unwrap();
+        let history = crate::import::merchant_history([
+            ("COLD STORAGE #12".to_string(), "dining".to_string()),
+            ("SHELL PETROL 0042".to_string(), "transport".to_string()),
+        ]);
+        assert_eq!(
+            categorize_merchant(&rules, &history, "Netflix"),
+            Some(MerchantCategory::Rule {
+                line: 6,
+                category: "entertainment".to_string(),
+            })
+        );
+        assert_eq!(categorize_merchant(&rules, &history, "Cold Storage").unwrap().category(), "groceries");
+        assert_eq!(
+            categorize_merchant(&rules, &history, "Shell Petrol"),
+            Some(MerchantCategory::History { category: "transport".to_string(), share: 1.0 })
+        );
+        assert_eq!(categorize_merchant(&rules, &history, "Esso"), None);
+    }
+
+    #[test]
+    fn test_stored_rules_come_after_the_file() {
+        let stored = |id: i64, pattern: &str, syntax: PatternSyntax, category: &str| MerchantRule {
+            id,
+            pattern: pattern.to_string(),
+            syntax,
+            category: category.to_string(),
+        };
+        let rules = parse_rules(RULES).unwrap().with_stored(&[
+            stored(1, "GRAB*", PatternSyntax::Glob, "taxi"),
+            stored(2, "shell ????", PatternSyntax::Glob, "petrol"),
+            stored(3, "esso|caltex", PatternSyntax::Regex, "petrol"),
+            stored(4, "(unclosed", PatternSyntax::Regex, "broken"),
+        ]);
+        assert_eq!(rules.stored.len(), 3);
+        // The file's rules win over the stored GRAB* glob
+        assert_eq!(rules.categorize("GRAB*RIDE 1234").unwrap().category(), "transport");
+        assert_eq!(rules.categorize("Grab Food SG").unwrap().category(), "dining");
+        assert_eq!(
+            rules.categorize("grab*mart 1234"),
+            Some(MerchantCategory::Stored { id: 1, category: "taxi".to_string() })
+        );
+        assert_eq!(rules.categorize("  SHELL 0042 ").unwrap().category(), "petrol");
+        assert!(rules.categorize("SHELL PETROL 0042").is_none());
+        assert!(rules.categorize("PAY GRAB").is_none());
+        assert_eq!(rules.categorize("CALTEX WOODLANDS").unwrap().category(), "petrol");
+        assert!(stored_pattern("a.b+", PatternSyntax::Glob).unwrap().is_match("A.B+"));
+        assert!(stored_pattern("(unclosed", PatternSyntax::Regex).is_err());
+    }
+
+    #[test]
+    fn test_partnership_for_picks_largest_matching_bonus() {
+        let partnership = |id: i64, pattern: &str, bonus: f64| MerchantPartnership {
+            id,
+            card_id: 1,
+            merchant_pattern: pattern.to_string(),
+            bonus_miles_per_dollar: bonus,
+        };
+        let partnerships = vec![partnership(1, "grab", 2.0), partnership(2, "^grab ?food", 3.0), partnership(3, "fairprice", 1.0)];
+        assert_eq!(partnership_for(&partnerships, "GRAB*RIDE 1234").map(|p| p.id), Some(1));
+        assert_eq!(partnership_for(&partnerships, "GrabFood SG").map(|p| p.id), Some(2));
+        assert!(partnership_for(&partnerships, "SHELL PETROL").is_none());
+        assert!(merchant_pattern("(unclosed").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! `statement`: one of a card's statement cycles rebuilt from the records,
+//! to check the bank's statement against. Every posted transaction is
+//! listed with the miles it earns once the reward cap has been applied in
+//! date order, and a cycle short of its minimum spend earns nothing.
+
+
+use crate::db::STATUS_POSTED;
+use crate::engine::{calculate_miles, miles_posting_date, round_cents};
+use crate::error::Result;
+use crate::models::{CapUnit, Card, Spending, StatementLine};
+use crate::store::Store;
+
+/// A reconstructed statement cycle and its totals
+pub struct Statement {
+    pub card_name: String,
+    pub cycle_start: String,
+    pub cycle_end: String,
+    pub lines: Vec<StatementLine>,
+    /// Posted spend in the cycle
+    pub spent: f64,
+    /// Spend counting towards the minimum: all but its excluded categories
+    pub qualifying: f64,
+    pub min_spend: Option<f64>,
+    /// The reward cap and its unit
+    pub cap: Option<(f64, CapUnit)>,
+    /// Miles at the card's rates, before the cap and minimum spend
+    pub miles: f64,
+    /// Miles the cap took off
+    pub capped: f64,
+    /// Miles the cycle earns
+    pub earned: f64,
+    /// The day the cycle's miles are credited
+    pub credited_on: String,
+}
+
+/// Rebuilds `card`'s statement cycle from `start` to `end` out of its
+/// posted spending.
+pub fn reconstruct<S: Store + ?Sized>(store: &S, card: &Card, start: &str, end: &str) -> Result<Statement> {
+    let mut spending = Vec::new();
+    store.for_each_spending(Some(card.id), None, &mut |s| {
+        if s.status == STATUS_POSTED && s.date.as_str() >= start && s.date.as_str() <= end {
+            spending.push(s);
+        }
+        Ok(())
+    })?;
+    Ok(from_spending(card, start, end, spending))
+}
+
+/// Builds `card`'s statement cycle from `start` to `end` out of the posted
+/// transactions dated in it, in any order.
+pub fn from_spending(card: &Card, start: &str, end: &str, mut spending: Vec<Spending>) -> Statement {
+    spending.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+
+    let excluded = card.excluded_from_min_spend();
+    // What the cap still allows, in its unit
+    let mut cap_left = card.max_reward_limit.unwrap_or(f64::INFINITY);
+    let (mut spent, mut qualifying) = (0.0, 0.0);
+    let mut lines = Vec::new();
+    for s in spending {
+        spent += s.amount;
+        let counts = !excluded.contains(&s.category.to_lowercase());
+        if counts {
+            qualifying += s.amount;
+        }
+        let earned = match card.cap_unit {
+            CapUnit::Dollars => {
+                // The blocks under the cap earn at the rate the whole transaction did
+                let eligible = s.amount.min(cap_left.max(0.0));
+                let blocks = (s.amount / card.block_size).floor();
+                let rate = if blocks > 0.0 { s.miles_earned / blocks } else { 0.0 };
+                cap_left -= s.amount;
+                if eligible < s.amount { calculate_miles(eligible, card.block_size, rate) } else { s.miles_earned }
+            }
+            CapUnit::Miles => {
+                let earned = s.miles_earned.min(cap_left.max(0.0));
+                cap_left -= s.miles_earned;
+                earned
+            }
+        };
+        let note = if earned < s.miles_earned {
+            Some(if earned > 0.0 { "partly over the cap" } else { "over the cap" }.to_string())
+        } else if !counts && card.min_spend.is_some() {
+            Some("not towards min spend".to_string())
+        } else {
+            None
+        };
+        lines.push(StatementLine {
+            id: s.id,
+            date: s.date,
+            category: s.category,
+            merchant: s.description,
+            amount: s.amount,
+            miles: s.miles_earned,
+            earned,
+            note,
+        });
+    }
+
+    let miles = lines.iter().fold(0.0, |sum, l| sum + l.miles);
+    let mut earned = lines.iter().fold(0.0, |sum, l| sum + l.earned);
+    let capped = miles - earned;
+    if card.min_spend.is_some_and(|minimum| qualifying < minimum) {
+        for line in &mut lines {
+            line.earned = 0.0;
+            line.note = Some("min spend not met".to_string());
+        }
+        earned = 0.0;
+    }
+    let overrides = card.cycle_overrides();
+    Statement {
+        card_name: card.name.clone(),
+        cycle_start: start.to_string(),
+        cycle_end: end.to_string(),
+        spent: round_cents(spent),
+        qualifying: round_cents(qualifying),
+        min_spend: card.min_spend,
+        cap: card.max_reward_limit.map(|cap| (cap, card.cap_unit)),
+        capped,
+        miles,
+        earned,
+        credited_on: miles_posting_date(card.statement_renewal_date, &overrides, card.posting_delay, start),
+        lines,
+    }
+}
+
+/// The statement's totals as plain text, for below its transactions.
+pub fn render_totals(statement: &Statement) -> String {
+    let mut out = format!(
+        "{}: statement cycle {} to {}\n  Spent: ${:.2} in {} transaction(s)\n  Miles: {:.0} at the card's rates\n",
+        statement.card_name,
+        statement.cycle_start,
+        statement.cycle_end,
+        statement.spent,
+        statement.lines.len(),
+        statement.miles
+    );
+    if let Some((cap, unit)) = statement.cap {
+        let cap = match unit {
+            CapUnit::Dollars => format!("${:.2}", cap),
+            CapUnit::Miles => format!("{:.0} miles", cap),
+        };
+        if statement.capped > 0.0 {
+            out.push_str(&format!("  Cap: {} reached, {:.0} miles over it not earned\n", cap, statement.capped));
+        } else {
+            out.push_str(&format!("  Cap: {} not reached\n", cap));
+        }
+    }
+    if let Some(minimum) = statement.min_spend {
+        if statement.qualifying >= minimum {
+            out.push_str(&format!("  Min spend: ${:.2} of ${:.2}, met\n", statement.qualifying, minimum));
+        } else {
+            out.push_str(&format!(
+                "  Min spend: ${:.2} of ${:.2}, not met, so the cycle earns nothing\n",
+                statement.qualifying, minimum
+            ));
+        }
+    }
+    out.push_str(&format!("  Earned: {:.0} miles, credited on {}\n", statement.earned, statement.credited_on));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::engine::statement_cycle;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_statement_applies_the_cap_in_date_order_and_gates_on_min_spend() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let id = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Altitude".to_string(),
+                categories: vec!["dining".to_string(), "bills".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 4.0,
+                block_size: 5.0,
+                statement_renewal_date: 15,
+                max_reward_limit: Some(1000.0),
+                min_spend: Some(500.0),
+                min_spend_exclusions: vec!["bills".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        // Entered out of order: the cap is used up by date
+        db::add_spending(&conn, id, 50.0, "dining", "2026-03-02", None, false, None).unwrap();
+        db::add_spending(&conn, id, 600.0, "dining", "2026-02-16", None, false, None).unwrap();
+        db::add_spending(&conn, id, 100.0, "bills", "2026-02-20", None, false, None).unwrap();
+        db::add_spending(&conn, id, 412.0, "dining", "2026-03-01", Some("Cafe"), false, None).unwrap();
+        db::schedule_spending(&conn, id, 80.0, "dining", "2026-03-05", None, false, None).unwrap();
+        let card = db::get_card(&conn, id).unwrap().unwrap();
+
+        // Sunday 15 February moves the renewal to Friday the 13th
+        let (start, end) = statement_cycle(&card, 2026, 2);
+        assert_eq!((start.as_str(), end.as_str()), ("2026-02-13", "2026-03-12"));
+        let statement = reconstruct(&conn, &card, &start, &end).unwrap();
+        let earned: Vec<(f64, f64, Option<&str>)> =
+            statement.lines.iter().map(|l| (l.miles, l.earned, l.note.as_deref())).collect();
+        assert_eq!(
+            earned,
+            vec![
+                (480.0, 480.0, None),
+                (80.0, 80.0, Some("not towards min spend")),
+                // $300 of it fits under the cap: 60 blocks of 4 miles
+                (328.0, 240.0, Some("partly over the cap")),
+                (40.0, 0.0, Some("over the cap")),
+            ]
+        );
+        assert_eq!((statement.spent, statement.qualifying), (1162.0, 1062.0));
+        assert_eq!((statement.miles, statement.capped, statement.earned), (928.0, 128.0, 800.0));
+        assert_eq!(
+            render_totals(&statement),
+            "\
+Altitude: statement cycle 2026-02-13 to 2026-03-12
+  Spent: $1162.00 in 4 transaction(s)
+  Miles: 928 at the card's rates
+  Cap: $1000.00 reached, 128 miles over it not earned
+  Min spend: $1062.00 of $500.00, met
+  Earned: 800 miles, credited on 2026-03-13
+"
+        );
+
+        db::add_spending(&conn, id, 300.0, "dining", "2026-03-20", None, false, None).unwrap();
+        let (start, end) = statement_cycle(&card, 2026, 3);
+        let statement = reconstruct(&conn, &card, &start, &end).unwrap();
+        assert_eq!((statement.miles, statement.earned), (240.0, 0.0));
+        assert_eq!(statement.lines[0].note.as_deref(), Some("min spend not met"));
+    }
+}
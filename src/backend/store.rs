@@ -0,0 +1,674 @@
+use rusqlite::{Connection, OpenFlags};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::db;
+use crate::error::Result;
+use crate::export::{self, Export, RestoreSummary};
+use crate::import::{ImportProfile, ImportedRow};
+use crate::merge::{self, MergeOptions};
+use crate::period::DateRange;
+use crate::models::{
+    AppliedRecurring, Card, CardBalance, CardRates, CardRecommendation, CashFlowMonth, Category, Charge, ClosedCycle, CycleOverride, FxRate,
+    ImportPreview, ImportSummary, MergeReport, MerchantPartnership, MerchantRule, MilesEntry, NewCard, OriginalAmount, OutstandingBalance,
+    PatternSyntax, Payment, Program, ProgramKind, ProgramTransfer, Promotion, RateChange, RecommendOptions, Reconciliation, Recurring, Redemption, ReviewItem, Spending, SpendingCursor, SpendingEdit, SpendingStats, StatsBy,
+    SpendingPage, SplitSuggestion,
+};
+
+/// Everything the command handlers need from storage. The SQLite
+/// implementation delegates to `db`; other backends implement the same
+/// operations with the same semantics (see the `db` function of each name).
+/// Failures are `CcTrackerError`s, so callers need not know the backend.
+pub trait Store {
+    // Cards
+    fn add_card(&self, card: &NewCard) -> Result<i64>;
+    fn list_cards(&self) -> Result<Vec<Card>>;
+    fn get_card(&self, id: i64) -> Result<Option<Card>>;
+    fn remove_card(&self, id: i64) -> Result<bool>;
+    fn update_card(&self, card_id: i64, card: &NewCard) -> Result<bool>;
+    fn update_card_details(&self, card_id: i64, card: &NewCard) -> Result<bool>;
+    fn change_card_rates(&self, card_id: i64, effective_from: &str, rates: &CardRates) -> Result<bool>;
+    fn last_rate_change(&self, card_id: i64) -> Result<Option<String>>;
+    fn list_rate_history(&self) -> Result<Vec<RateChange>>;
+    fn restore_rate_change(&self, change: &RateChange) -> Result<i64>;
+    fn set_balance_settings(&self, card_id: i64, starting_balance: Option<f64>, balance_alert: Option<f64>) -> Result<bool>;
+    fn close_card(&self, card_id: i64, closed_on: &str) -> Result<bool>;
+    fn set_cycle_overrides(&self, card_id: i64, overrides: &BTreeMap<String, CycleOverride>) -> Result<bool>;
+
+    // Merchant partnerships
+    fn add_partnership(&self, card_id: i64, merchant_pattern: &str, bonus_miles_per_dollar: f64) -> Result<i64>;
+    fn list_partnerships(&self, card_id: Option<i64>) -> Result<Vec<MerchantPartnership>>;
+    fn remove_partnership(&self, id: i64) -> Result<bool>;
+
+    // Promotions
+    fn add_promotion(&self, card_id: i64, category: &str, miles_per_dollar: f64, start_date: &str, end_date: &str) -> Result<i64>;
+    fn list_promotions(&self, card_id: Option<i64>) -> Result<Vec<Promotion>>;
+    fn remove_promotion(&self, id: i64) -> Result<bool>;
+
+    // Recurring spending
+    fn add_recurring(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        day_of_month: i32,
+        merchant: Option<&str>,
+        start_date: &str,
+    ) -> Result<i64>;
+    fn list_recurring(&self, card_id: Option<i64>) -> Result<Vec<Recurring>>;
+    fn remove_recurring(&self, id: i64) -> Result<bool>;
+    fn apply_recurring(&self, through: &str) -> Result<Vec<AppliedRecurring>>;
+
+    // Merchant rules
+    fn add_merchant_rule(&self, pattern: &str, syntax: PatternSyntax, category: &str) -> Result<i64>;
+    fn list_merchant_rules(&self) -> Result<Vec<MerchantRule>>;
+    fn remove_merchant_rule(&self, id: i64) -> Result<bool>;
+
+    // Loyalty programs
+    fn add_program(&self, name: &str, kind: ProgramKind, expiry_months: Option<i32>) -> Result<i64>;
+    fn list_programs(&self) -> Result<Vec<Program>>;
+    fn set_program_expiry(&self, id: i64, expiry_months: Option<i32>) -> Result<bool>;
+    fn remove_program(&self, id: i64) -> Result<bool>;
+    fn add_program_transfer(&self, from_program_id: i64, to_program_id: i64, ratio: f64) -> Result<i64>;
+    fn list_program_transfers(&self) -> Result<Vec<ProgramTransfer>>;
+    fn remove_program_transfer(&self, id: i64) -> Result<bool>;
+
+    // FX rates
+    fn set_fx_rate(&self, currency: &str, rate: f64) -> Result<()>;
+    fn restore_fx_rate(&self, rate: &FxRate) -> Result<()>;
+    fn list_fx_rates(&self) -> Result<Vec<FxRate>>;
+    fn remove_fx_rate(&self, currency: &str) -> Result<bool>;
+
+    // Categories
+    fn add_category(&self, name: &str) -> Result<bool>;
+    fn list_categories(&self) -> Result<Vec<Category>>;
+    fn rename_category(&self, from: &str, to: &str) -> Result<(usize, usize, usize)>;
+    fn set_category_budget(&self, name: &str, budget: Option<f64>) -> Result<()>;
+
+    // Recommendations
+    fn best_card_for_category(
+        &self,
+        category: &str,
+        amount: f64,
+        payment_category: &str,
+        date: &str,
+        options: &RecommendOptions,
+    ) -> Result<Vec<CardRecommendation>>;
+    fn suggest_split(
+        &self,
+        category: &str,
+        amount: f64,
+        payment_category: &str,
+        date: &str,
+        options: &RecommendOptions,
+    ) -> Result<Option<SplitSuggestion>>;
+
+    // Spending
+    #[allow(clippy::too_many_arguments)]
+    fn add_spending(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        date: &str,
+        merchant: Option<&str>,
+        foreign: bool,
+        original: Option<&OriginalAmount>,
+    ) -> Result<(i64, f64)>;
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_spending(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        date: &str,
+        merchant: Option<&str>,
+        foreign: bool,
+        original: Option<&OriginalAmount>,
+    ) -> Result<(i64, f64)>;
+    fn post_scheduled_spending(&self, id: i64) -> Result<bool>;
+    fn move_spending(&self, ids: &[i64], to_card: i64) -> Result<Vec<(i64, f64)>>;
+    fn edit_spending(&self, id: i64, edit: &SpendingEdit) -> Result<Option<f64>>;
+    fn list_spending(&self, card_id: Option<i64>, as_of: Option<&str>) -> Result<Vec<Spending>>;
+    fn for_each_spending(
+        &self,
+        card_id: Option<i64>,
+        as_of: Option<&str>,
+        f: &mut dyn FnMut(Spending) -> Result<()>,
+    ) -> Result<()>;
+    fn list_spending_page(
+        &self,
+        card_id: Option<i64>,
+        as_of: Option<&str>,
+        after: Option<&SpendingCursor>,
+        limit: usize,
+    ) -> Result<SpendingPage>;
+    fn restore_spending(&self, spending: &Spending) -> Result<i64>;
+    fn restore_spending_batch(&self, spending: &[Spending]) -> Result<()>;
+
+    // Statement import
+    fn preview_import(&self, card_id: i64, rows: &[ImportedRow], profile: &ImportProfile) -> Result<ImportPreview>;
+    fn import_spending(&self, card_id: i64, rows: &[ImportedRow], profile: &ImportProfile) -> Result<ImportSummary>;
+    fn list_review_queue(&self, card_id: Option<i64>) -> Result<Vec<ReviewItem>>;
+    fn restore_review_item(&self, item: &ReviewItem) -> Result<i64>;
+    fn resolve_review(&self, id: i64, category: Option<&str>) -> Result<Option<(i64, f64)>>;
+
+    // Payments and charges
+    fn add_payment(&self, card_id: i64, amount: f64, date: &str) -> Result<i64>;
+    fn list_payments(&self, card_id: Option<i64>) -> Result<Vec<Payment>>;
+    fn add_charge(&self, card_id: i64, kind: &str, amount: f64, date: &str) -> Result<i64>;
+    fn list_charges(&self, card_id: Option<i64>) -> Result<Vec<Charge>>;
+    fn add_miles_entry(
+        &self,
+        card_id: Option<i64>,
+        program: Option<&str>,
+        kind: &str,
+        miles: f64,
+        date: &str,
+        description: Option<&str>,
+    ) -> Result<i64>;
+    fn list_miles_entries(&self) -> Result<Vec<MilesEntry>>;
+    fn add_redemption(
+        &self,
+        card_id: Option<i64>,
+        program: Option<&str>,
+        miles: f64,
+        cash_value: f64,
+        date: &str,
+        description: Option<&str>,
+    ) -> Result<i64>;
+    fn list_redemptions(&self) -> Result<Vec<Redemption>>;
+    fn restore_redemption(&self, redemption: &Redemption) -> Result<i64>;
+    fn cash_flow(&self, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<CashFlowMonth>>;
+    fn spending_stats(&self, by: StatsBy, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<SpendingStats>>;
+    fn outstanding_balances(&self, as_of: &str) -> Result<Vec<OutstandingBalance>>;
+
+    // Miles balances and statements
+    fn card_balances(&self, as_of: &str) -> Result<Vec<CardBalance>>;
+    fn reconcile_cycle(&self, card_id: i64, reference_date: &str, bank_miles: f64) -> Result<Option<Reconciliation>>;
+    fn close_cycle(&self, card_id: i64, through: &str) -> Result<Option<ClosedCycle>>;
+    fn closed_cycle_containing(&self, card_id: i64, date: &str) -> Result<Option<ClosedCycle>>;
+    fn reopen_cycle(&self, card_id: i64, date: &str) -> Result<bool>;
+    fn list_closed_cycles(&self, card_id: Option<i64>) -> Result<Vec<ClosedCycle>>;
+    fn restore_closed_cycle(&self, cycle: &ClosedCycle) -> Result<i64>;
+
+    /// Loads an export with `export::restore_records`, all of it or, on any
+    /// error, none of it.
+    fn restore_export(&self, export: &Export) -> Result<RestoreSummary>;
+
+    /// Merges records from another SQLite database file into this store.
+    fn merge_database(&self, other: &Path, options: &MergeOptions) -> Result<Vec<MergeReport>>;
+}
+
+impl Store for Connection {
+    fn add_card(&self, card: &NewCard) -> Result<i64> {
+        Ok(db::add_card(self, card)?)
+    }
+
+    fn list_cards(&self) -> Result<Vec<Card>> {
+        Ok(db::list_cards(self)?)
+    }
+
+    fn get_card(&self, id: i64) -> Result<Option<Card>> {
+        Ok(db::get_card(self, id)?)
+    }
+
+    fn remove_card(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_card(self, id)?)
+    }
+
+    fn update_card(&self, card_id: i64, card: &NewCard) -> Result<bool> {
+        Ok(db::update_card(self, card_id, card)?)
+    }
+
+    fn update_card_details(&self, card_id: i64, card: &NewCard) -> Result<bool> {
+        Ok(db::update_card_details(self, card_id, card)?)
+    }
+
+    fn change_card_rates(&self, card_id: i64, effective_from: &str, rates: &CardRates) -> Result<bool> {
+        Ok(db::change_card_rates(self, card_id, effective_from, rates)?)
+    }
+
+    fn last_rate_change(&self, card_id: i64) -> Result<Option<String>> {
+        Ok(db::last_rate_change(self, card_id)?)
+    }
+
+    fn list_rate_history(&self) -> Result<Vec<RateChange>> {
+        Ok(db::list_rate_history(self)?)
+    }
+
+    fn restore_rate_change(&self, change: &RateChange) -> Result<i64> {
+        Ok(db::restore_rate_change(self, change)?)
+    }
+
+    fn set_balance_settings(&self, card_id: i64, starting_balance: Option<f64>, balance_alert: Option<f64>) -> Result<bool> {
+        Ok(db::set_balance_settings(self, card_id, starting_balance, balance_alert)?)
+    }
+
+    fn close_card(&self, card_id: i64, closed_on: &str) -> Result<bool> {
+        Ok(db::close_card(self, card_id, closed_on)?)
+    }
+
+    fn set_cycle_overrides(&self, card_id: i64, overrides: &BTreeMap<String, CycleOverride>) -> Result<bool> {
+        Ok(db::set_cycle_overrides(self, card_id, overrides)?)
+    }
+
+    fn add_partnership(&self, card_id: i64, merchant_pattern: &str, bonus_miles_per_dollar: f64) -> Result<i64> {
+        Ok(db::add_partnership(self, card_id, merchant_pattern, bonus_miles_per_dollar)?)
+    }
+
+    fn list_partnerships(&self, card_id: Option<i64>) -> Result<Vec<MerchantPartnership>> {
+        Ok(db::list_partnerships(self, card_id)?)
+    }
+
+    fn remove_partnership(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_partnership(self, id)?)
+    }
+
+    fn add_promotion(&self, card_id: i64, category: &str, miles_per_dollar: f64, start_date: &str, end_date: &str) -> Result<i64> {
+        Ok(db::add_promotion(self, card_id, category, miles_per_dollar, start_date, end_date)?)
+    }
+
+    fn list_promotions(&self, card_id: Option<i64>) -> Result<Vec<Promotion>> {
+        Ok(db::list_promotions(self, card_id)?)
+    }
+
+    fn remove_promotion(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_promotion(self, id)?)
+    }
+
+    fn add_recurring(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        day_of_month: i32,
+        merchant: Option<&str>,
+        start_date: &str,
+    ) -> Result<i64> {
+        Ok(db::add_recurring(self, card_id, amount, category, day_of_month, merchant, start_date)?)
+    }
+
+    fn list_recurring(&self, card_id: Option<i64>) -> Result<Vec<Recurring>> {
+        Ok(db::list_recurring(self, card_id)?)
+    }
+
+    fn remove_recurring(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_recurring(self, id)?)
+    }
+
+    fn apply_recurring(&self, through: &str) -> Result<Vec<AppliedRecurring>> {
+        Ok(db::apply_recurring(self, through)?)
+    }
+
+    fn add_merchant_rule(&self, pattern: &str, syntax: PatternSyntax, category: &str) -> Result<i64> {
+        Ok(db::add_merchant_rule(self, pattern, syntax, category)?)
+    }
+
+    fn list_merchant_rules(&self) -> Result<Vec<MerchantRule>> {
+        Ok(db::list_merchant_rules(self)?)
+    }
+
+    fn remove_merchant_rule(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_merchant_rule(self, id)?)
+    }
+
+    fn add_program(&self, name: &str, kind: ProgramKind, expiry_months: Option<i32>) -> Result<i64> {
+        Ok(db::add_program(self, name, kind, expiry_months)?)
+    }
+
+    fn list_programs(&self) -> Result<Vec<Program>> {
+        Ok(db::list_programs(self)?)
+    }
+
+    fn set_program_expiry(&self, id: i64, expiry_months: Option<i32>) -> Result<bool> {
+        Ok(db::set_program_expiry(self, id, expiry_months)?)
+    }
+
+    fn remove_program(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_program(self, id)?)
+    }
+
+    fn add_program_transfer(&self, from_program_id: i64, to_program_id: i64, ratio: f64) -> Result<i64> {
+        Ok(db::add_program_transfer(self, from_program_id, to_program_id, ratio)?)
+    }
+
+    fn list_program_transfers(&self) -> Result<Vec<ProgramTransfer>> {
+        Ok(db::list_program_transfers(self)?)
+    }
+
+    fn remove_program_transfer(&self, id: i64) -> Result<bool> {
+        Ok(db::remove_program_transfer(self, id)?)
+    }
+
+    fn set_fx_rate(&self, currency: &str, rate: f64) -> Result<()> {
+        Ok(db::set_fx_rate(self, currency, rate)?)
+    }
+
+    fn restore_fx_rate(&self, rate: &FxRate) -> Result<()> {
+        Ok(db::restore_fx_rate(self, rate)?)
+    }
+
+    fn list_fx_rates(&self) -> Result<Vec<FxRate>> {
+        Ok(db::list_fx_rates(self)?)
+    }
+
+    fn remove_fx_rate(&self, currency: &str) -> Result<bool> {
+        Ok(db::remove_fx_rate(self, currency)?)
+    }
+
+    fn add_category(&self, name: &str) -> Result<bool> {
+        Ok(db::add_category(self, name)?)
+    }
+
+    fn list_categories(&self) -> Result<Vec<Category>> {
+        Ok(db::list_categories(self)?)
+    }
+
+    fn rename_category(&self, from: &str, to: &str) -> Result<(usize, usize, usize)> {
+        Ok(db::rename_category(self, from, to)?)
+    }
+
+    fn set_category_budget(&self, name: &str, budget: Option<f64>) -> Result<()> {
+        Ok(db::set_category_budget(self, name, budget)?)
+    }
+
+    fn best_card_for_category(
+        &self,
+        category: &str,
+        amount: f64,
+        payment_category: &str,
+        date: &str,
+        options: &RecommendOptions,
+    ) -> Result<Vec<CardRecommendation>> {
+        Ok(db::best_card_for_category(self, category, amount, payment_category, date, options)?)
+    }
+
+    fn suggest_split(
+        &self,
+        category: &str,
+        amount: f64,
+        payment_category: &str,
+        date: &str,
+        options: &RecommendOptions,
+    ) -> Result<Option<SplitSuggestion>> {
+        Ok(db::suggest_split(self, category, amount, payment_category, date, options)?)
+    }
+
+    fn add_spending(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        date: &str,
+        merchant: Option<&str>,
+        foreign: bool,
+        original: Option<&OriginalAmount>,
+    ) -> Result<(i64, f64)> {
+        Ok(db::add_spending(self, card_id, amount, category, date, merchant, foreign, original)?)
+    }
+
+    fn schedule_spending(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        date: &str,
+        merchant: Option<&str>,
+        foreign: bool,
+        original: Option<&OriginalAmount>,
+    ) -> Result<(i64, f64)> {
+        Ok(db::schedule_spending(self, card_id, amount, category, date, merchant, foreign, original)?)
+    }
+
+    fn post_scheduled_spending(&self, id: i64) -> Result<bool> {
+        Ok(db::post_scheduled_spending(self, id)?)
+    }
+
+    fn move_spending(&self, ids: &[i64], to_card: i64) -> Result<Vec<(i64, f64)>> {
+        Ok(db::move_spending(self, ids, to_card)?)
+    }
+
+    fn edit_spending(&self, id: i64, edit: &SpendingEdit) -> Result<Option<f64>> {
+        Ok(db::edit_spending(self, id, edit)?)
+    }
+
+    fn list_spending(&self, card_id: Option<i64>, as_of: Option<&str>) -> Result<Vec<Spending>> {
+        Ok(db::list_spending(self, card_id, as_of)?)
+    }
+
+    fn for_each_spending(
+        &self,
+        card_id: Option<i64>,
+        as_of: Option<&str>,
+        f: &mut dyn FnMut(Spending) -> Result<()>,
+    ) -> Result<()> {
+        db::for_each_spending(self, card_id, as_of, f)
+    }
+
+    fn list_spending_page(
+        &self,
+        card_id: Option<i64>,
+        as_of: Option<&str>,
+        after: Option<&SpendingCursor>,
+        limit: usize,
+    ) -> Result<SpendingPage> {
+        Ok(db::list_spending_page(self, card_id, as_of, after, limit)?)
+    }
+
+    fn restore_spending(&self, spending: &Spending) -> Result<i64> {
+        Ok(db::restore_spending(self, spending)?)
+    }
+
+    fn restore_spending_batch(&self, spending: &[Spending]) -> Result<()> {
+        Ok(db::restore_spending_batch(self, spending)?)
+    }
+
+    fn preview_import(&self, card_id: i64, rows: &[ImportedRow], profile: &ImportProfile) -> Result<ImportPreview> {
+        Ok(db::preview_import(self, card_id, rows, profile)?)
+    }
+
+    fn import_spending(&self, card_id: i64, rows: &[ImportedRow], profile: &ImportProfile) -> Result<ImportSummary> {
+        Ok(db::import_spending(self, card_id, rows, profile)?)
+    }
+
+    fn list_review_queue(&self, card_id: Option<i64>) -> Result<Vec<ReviewItem>> {
+        Ok(db::list_review_queue(self, card_id)?)
+    }
+
+    fn restore_review_item(&self, item: &ReviewItem) -> Result<i64> {
+        Ok(db::restore_review_item(self, item)?)
+    }
+
+    fn resolve_review(&self, id: i64, category: Option<&str>) -> Result<Option<(i64, f64)>> {
+        Ok(db::resolve_review(self, id, category)?)
+    }
+
+    fn add_payment(&self, card_id: i64, amount: f64, date: &str) -> Result<i64> {
+        Ok(db::add_payment(self, card_id, amount, date)?)
+    }
+
+    fn list_payments(&self, card_id: Option<i64>) -> Result<Vec<Payment>> {
+        Ok(db::list_payments(self, card_id)?)
+    }
+
+    fn add_charge(&self, card_id: i64, kind: &str, amount: f64, date: &str) -> Result<i64> {
+        Ok(db::add_charge(self, card_id, kind, amount, date)?)
+    }
+
+    fn list_charges(&self, card_id: Option<i64>) -> Result<Vec<Charge>> {
+        Ok(db::list_charges(self, card_id)?)
+    }
+
+    fn add_miles_entry(
+        &self,
+        card_id: Option<i64>,
+        program: Option<&str>,
+        kind: &str,
+        miles: f64,
+        date: &str,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        Ok(db::add_miles_entry(self, card_id, program, kind, miles, date, description)?)
+    }
+
+    fn list_miles_entries(&self) -> Result<Vec<MilesEntry>> {
+        Ok(db::list_miles_entries(self)?)
+    }
+
+    fn add_redemption(
+        &self,
+        card_id: Option<i64>,
+        program: Option<&str>,
+        miles: f64,
+        cash_value: f64,
+        date: &str,
+        description: Option<&str>,
+    ) -> Result<i64> {
+        Ok(db::add_redemption(self, card_id, program, miles, cash_value, date, description)?)
+    }
+
+    fn list_redemptions(&self) -> Result<Vec<Redemption>> {
+        Ok(db::list_redemptions(self)?)
+    }
+
+    fn restore_redemption(&self, redemption: &Redemption) -> Result<i64> {
+        Ok(db::restore_redemption(self, redemption)?)
+    }
+
+    fn cash_flow(&self, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<CashFlowMonth>> {
+        Ok(db::cash_flow(self, card_id, period)?)
+    }
+
+    fn spending_stats(&self, by: StatsBy, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<SpendingStats>> {
+        Ok(db::spending_stats(self, by, card_id, period)?)
+    }
+
+    fn outstanding_balances(&self, as_of: &str) -> Result<Vec<OutstandingBalance>> {
+        Ok(db::outstanding_balances(self, as_of)?)
+    }
+
+    fn card_balances(&self, as_of: &str) -> Result<Vec<CardBalance>> {
+        Ok(db::card_balances(self, as_of)?)
+    }
+
+    fn reconcile_cycle(&self, card_id: i64, reference_date: &str, bank_miles: f64) -> Result<Option<Reconciliation>> {
+        Ok(db::reconcile_cycle(self, card_id, reference_date, bank_miles)?)
+    }
+
+    fn close_cycle(&self, card_id: i64, through: &str) -> Result<Option<ClosedCycle>> {
+        Ok(db::close_cycle(self, card_id, through)?)
+    }
+
+    fn closed_cycle_containing(&self, card_id: i64, date: &str) -> Result<Option<ClosedCycle>> {
+        Ok(db::closed_cycle_containing(self, card_id, date)?)
+    }
+
+    fn reopen_cycle(&self, card_id: i64, date: &str) -> Result<bool> {
+        Ok(db::reopen_cycle(self, card_id, date)?)
+    }
+
+    fn list_closed_cycles(&self, card_id: Option<i64>) -> Result<Vec<ClosedCycle>> {
+        Ok(db::list_closed_cycles(self, card_id)?)
+    }
+
+    fn restore_closed_cycle(&self, cycle: &ClosedCycle) -> Result<i64> {
+        Ok(db::restore_closed_cycle(self, cycle)?)
+    }
+
+    fn restore_export(&self, export: &Export) -> Result<RestoreSummary> {
+        let tx = self.unchecked_transaction()?;
+        let summary = export::restore_records(&*tx, export)?;
+        tx.commit()?;
+        Ok(summary)
+    }
+
+    fn merge_database(&self, other: &Path, options: &MergeOptions) -> Result<Vec<MergeReport>> {
+        let other = Connection::open_with_flags(other, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        Ok(merge::merge_database(self, &other, options)?)
+    }
+}
+
+/// A store that can be handed to another thread
+pub type SharedStore = Box<dyn Store + Send>;
+
+/// Async access to a store for the server. Store calls block (on SQLite I/O,
+/// or on the Postgres client's own runtime), so each one runs on tokio's
+/// blocking thread pool instead of an async worker thread; calls from
+/// concurrent tasks take turns on the one store.
+#[derive(Clone)]
+pub struct AsyncStore {
+    inner: Arc<Mutex<SharedStore>>,
+}
+
+impl AsyncStore {
+    pub fn new(store: SharedStore) -> AsyncStore {
+        AsyncStore {
+            inner: Arc::new(Mutex::new(store)),
+        }
+    }
+
+    /// Runs `f` against the store on the blocking pool, e.g.
+    /// `store.call(|s| s.list_cards()).await`. A call that panics passes its
+    /// panic on to the caller but leaves the store usable for the next one.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&dyn Store) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        match tokio::task::spawn_blocking(move || f(inner.lock().unwrap_or_else(PoisonError::into_inner).as_ref())).await {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_store_runs_concurrent_calls() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = NewCard {
+            name: "Card A".to_string(),
+            miles_per_dollar: 4.0,
+            block_size: 5.0,
+            statement_renewal_date: 1,
+            ..NewCard::default()
+        };
+        let card_id = conn.add_card(&card).unwrap();
+        let store = AsyncStore::new(Box::new(conn));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store.call(move |s| s.add_spending(card_id, 10.0 + i as f64, "dining", "2025-03-05", None, false, None)).await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let spending = store.call(move |s| s.list_spending(Some(card_id), None)).await.unwrap();
+        assert_eq!(spending.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_async_store_outlives_a_panicking_call() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let store = AsyncStore::new(Box::new(conn));
+
+        let panicking = store.clone();
+        let task = tokio::spawn(async move { panicking.call(|_| -> Result<()> { panic!("handler bug") }).await });
+        assert!(task.await.unwrap_err().is_panic());
+
+        assert!(store.call(|s| s.list_cards()).await.unwrap().is_empty());
+    }
+}
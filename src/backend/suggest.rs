@@ -0,0 +1,55 @@
+//! "Did you mean" suggestions for mistyped names: the known category or
+//! card name closest to what was typed, by edit distance.
+
+/// The Levenshtein distance between two names, ignoring case: how many
+/// characters must be inserted, deleted or replaced to turn one into the other.
+pub fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != cb);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `name`, if one is close enough to be a typo of
+/// it: at most one edit away for every three characters typed (and at least
+/// one). The first of equally close candidates wins.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let allowed = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("dinning", "dining"), 1);
+        assert_eq!(distance("grocceries", "Groceries"), 1);
+        assert_eq!(distance("travle", "travel"), 2);
+        assert_eq!(distance("", "fuel"), 4);
+        assert_eq!(distance("fuel", "fuel"), 0);
+    }
+
+    #[test]
+    fn test_closest_suggests_only_likely_typos() {
+        let categories = ["dining", "groceries", "travel", "transport"];
+        assert_eq!(closest("dinning", categories), Some("dining"));
+        assert_eq!(closest("grocceries", categories), Some("groceries"));
+        assert_eq!(closest("travle", categories), Some("travel"));
+        assert_eq!(closest("bills", categories), None);
+    }
+}
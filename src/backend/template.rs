@@ -0,0 +1,158 @@
+//! Output templates for listings: one line per row with `{field}`
+//! placeholders, e.g. `{card_name}: {miles_earned} mi ({reason})`.
+
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    /// A row field, with the decimal places to show for numbers
+    Field(String, Option<usize>),
+}
+
+/// A parsed template. Fields are the row's JSON field names; `{amount:.2}`
+/// fixes the decimal places of a number, and `{{` / `}}` are literal braces.
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(format!("Unclosed '{{' in template: {}", text)),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_field(&field)?);
+                }
+                '}' => return Err(format!("Unmatched '}}' in template (write '}}}}' for a brace): {}", text)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Fills the template from one row. Missing values print as "-"; a field
+    /// the row does not have is an error listing the ones it does.
+    pub fn render<T: Serialize>(&self, row: &T) -> Result<String, String> {
+        let value = serde_json::to_value(row).map_err(|e| e.to_string())?;
+        let Value::Object(fields) = value else {
+            return Err("Rows of this listing cannot be templated".to_string());
+        };
+
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field(name, precision) => {
+                    let value = fields.get(name).ok_or_else(|| {
+                        let available: Vec<&str> = fields.keys().map(|k| k.as_str()).collect();
+                        format!("Unknown template field '{}'; available: {}", name, available.join(", "))
+                    })?;
+                    line.push_str(&format_value(value, *precision));
+                }
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Parses `name` or `name:.N`.
+fn parse_field(field: &str) -> Result<Part, String> {
+    let (name, spec) = match field.split_once(':') {
+        Some((name, spec)) => (name.trim(), Some(spec)),
+        None => (field.trim(), None),
+    };
+    if name.is_empty() {
+        return Err("Empty '{}' in template".to_string());
+    }
+    let precision = match spec {
+        None => None,
+        Some(spec) => Some(
+            spec.strip_prefix('.')
+                .and_then(|digits| digits.parse().ok())
+                .ok_or_else(|| format!("Invalid format '{}' for field '{}'; use e.g. {{{}:.2}}", spec, name, name))?,
+        ),
+    };
+    Ok(Part::Field(name.to_string(), precision))
+}
+
+fn format_value(value: &Value, precision: Option<usize>) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match (precision, n.as_f64()) {
+            (Some(places), Some(f)) => format!("{:.*}", places, f),
+            // Whole-number floats print without a trailing ".0", as in tables
+            (None, Some(f)) if n.is_f64() => f.to_string(),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        card_name: String,
+        miles_earned: f64,
+        remaining_limit: Option<f64>,
+        eligible: bool,
+    }
+
+    fn row() -> Row {
+        Row {
+            card_name: "Card A".to_string(),
+            miles_earned: 16.0,
+            remaining_limit: None,
+            eligible: true,
+        }
+    }
+
+    #[test]
+    fn test_render_fills_fields() {
+        let template = Template::parse("{card_name}: {miles_earned} mi, cap {remaining_limit} {{{eligible}}}").unwrap();
+        assert_eq!(template.render(&row()).unwrap(), "Card A: 16 mi, cap - {true}");
+
+        let template = Template::parse("{miles_earned:.2}").unwrap();
+        assert_eq!(template.render(&row()).unwrap(), "16.00");
+    }
+
+    #[test]
+    fn test_template_errors_are_explained() {
+        assert!(Template::parse("{card_name").unwrap_err().contains("Unclosed"));
+        assert!(Template::parse("a } b").unwrap_err().contains("Unmatched"));
+        assert!(Template::parse("{miles:2}").unwrap_err().contains("Invalid format"));
+
+        let err = Template::parse("{miles}").unwrap().render(&row()).unwrap_err();
+        assert_eq!(err, "Unknown template field 'miles'; available: card_name, eligible, miles_earned, remaining_limit");
+    }
+}
@@ -0,0 +1,161 @@
+//! The [`CcTracker`] facade: the operations a program embedding the tracker
+//! needs most, over any store. Everything else is on [`CcTracker::store`].
+
+use rusqlite::Connection;
+use std::path::Path;
+
+use crate::dates;
+use crate::db::{self, OpenMode};
+use crate::error::{CcTrackerError, Result};
+use crate::models::{
+    Card, CardBalance, CardRecommendation, CashFlowMonth, NewCard, OutstandingBalance, RecommendOptions, Spending,
+};
+use crate::overview;
+use crate::period::DateRange;
+use crate::store::{SharedStore, Store};
+
+/// A miles tracker over one database. Dates are YYYY-MM-DD and amounts are
+/// in the home currency, as in the CLI and the REST API.
+pub struct CcTracker {
+    store: SharedStore,
+}
+
+impl CcTracker {
+    /// Opens (or creates) the SQLite database file at `path`, creating its
+    /// directory and upgrading its tables as needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<CcTracker> {
+        Ok(CcTracker::from_store(Box::new(db::init_db(path.as_ref(), OpenMode::ReadWrite)?)))
+    }
+
+    /// Opens an empty database that lives in memory until the tracker is
+    /// dropped.
+    pub fn open_in_memory() -> Result<CcTracker> {
+        let conn = Connection::open_in_memory()?;
+        db::init_tables(&conn)?;
+        Ok(CcTracker::from_store(Box::new(conn)))
+    }
+
+    /// Wraps a store that is already open, such as a Postgres one.
+    pub fn from_store(store: SharedStore) -> CcTracker {
+        CcTracker { store }
+    }
+
+    /// The underlying store, for operations the facade does not cover.
+    pub fn store(&self) -> &(dyn Store + Send) {
+        self.store.as_ref()
+    }
+
+    /// Adds a card and returns its ID.
+    pub fn add_card(&self, card: &NewCard) -> Result<i64> {
+        self.store.add_card(card)
+    }
+
+    /// Lists every card, closed ones included.
+    pub fn cards(&self) -> Result<Vec<Card>> {
+        self.store.list_cards()
+    }
+
+    /// Records a purchase and returns its ID and the miles it earned.
+    /// `merchant` is its description, which partnership bonuses match on.
+    /// Fails with `NotFound` for an unknown card.
+    pub fn record_spending(
+        &self,
+        card_id: i64,
+        amount: f64,
+        category: &str,
+        date: &str,
+        merchant: Option<&str>,
+    ) -> Result<(i64, f64)> {
+        check_date(date)?;
+        if self.store.get_card(card_id)?.is_none() {
+            return Err(CcTrackerError::card_not_found(card_id));
+        }
+        self.store.add_spending(card_id, amount, category, date, merchant, false, None)
+    }
+
+    /// Lists recorded spending, newest first, on one card or all of them.
+    pub fn spending(&self, card_id: Option<i64>) -> Result<Vec<Spending>> {
+        self.store.list_spending(card_id, None)
+    }
+
+    /// Ranks the cards for a purchase, best first, as `best-card` does.
+    pub fn recommend(
+        &self,
+        category: &str,
+        amount: f64,
+        payment_category: &str,
+        date: &str,
+        options: &RecommendOptions,
+    ) -> Result<Vec<CardRecommendation>> {
+        check_date(date)?;
+        self.store.best_card_for_category(category, amount, payment_category, date, options)
+    }
+
+    /// Each card's credited and pending miles as of a date.
+    pub fn miles_balances(&self, as_of: &str) -> Result<Vec<CardBalance>> {
+        check_date(as_of)?;
+        self.store.card_balances(as_of)
+    }
+
+    /// What each card owes as of a date, as `summary` shows.
+    pub fn outstanding_balances(&self, as_of: &str) -> Result<Vec<OutstandingBalance>> {
+        check_date(as_of)?;
+        self.store.outstanding_balances(as_of)
+    }
+
+    /// Spending, charges and payments by month, on one card or all of them.
+    pub fn cash_flow(&self, card_id: Option<i64>, period: Option<&DateRange>) -> Result<Vec<CashFlowMonth>> {
+        self.store.cash_flow(card_id, period)
+    }
+
+    /// The `today` overview for a date, as plain text.
+    pub fn overview(&self, date: &str) -> Result<String> {
+        check_date(date)?;
+        overview::build(self.store(), date)
+    }
+}
+
+/// Refuses a date that is not a real day in YYYY-MM-DD, which every store
+/// operation relies on.
+fn check_date(date: &str) -> Result<()> {
+    dates::parse_with(date, None).map(|_| ()).map_err(CcTrackerError::InvalidDate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_recommend() {
+        let tracker = CcTracker::open_in_memory().unwrap();
+        let card = NewCard {
+            name: "Dining card".to_string(),
+            categories: vec!["dining".to_string()],
+            payment_categories: vec!["contactless".to_string()],
+            miles_per_dollar: 4.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            ..NewCard::default()
+        };
+        let card_id = tracker.add_card(&card).unwrap();
+
+        let (_, miles) = tracker.record_spending(card_id, 42.0, "dining", "2026-03-05", None).unwrap();
+        assert_eq!(miles, 168.0);
+        assert_eq!(tracker.spending(Some(card_id)).unwrap().len(), 1);
+
+        let ranked = tracker
+            .recommend("dining", 50.0, "contactless", "2026-03-06", &RecommendOptions::default())
+            .unwrap();
+        assert_eq!(ranked[0].card_id, card_id);
+        assert_eq!(tracker.outstanding_balances("2026-03-31").unwrap()[0].outstanding, 42.0);
+    }
+
+    #[test]
+    fn test_refused_input_is_a_structured_error() {
+        let tracker = CcTracker::open_in_memory().unwrap();
+        let unknown = tracker.record_spending(7, 10.0, "dining", "2026-03-05", None);
+        assert!(matches!(unknown, Err(CcTrackerError::NotFound { what: "card", id: 7 })));
+        let bad_date = tracker.record_spending(7, 10.0, "dining", "2026-02-30", None);
+        assert!(matches!(bad_date, Err(CcTrackerError::InvalidDate(message)) if message.contains("2026-02-30")));
+    }
+}
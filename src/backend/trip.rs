@@ -0,0 +1,264 @@
+//! Trip plans: a list of upcoming foreign-currency expenses, each matched to
+//! the card that earns most on it once its FX fee is paid, with earlier
+//! expenses counting towards caps and minimum spends as the plan goes.
+//!
+//! ```yaml
+//! mile_value: 0.02        # dollars a mile is worth to you
+//! fx_fees:                # foreign transaction fee per card, in percent
+//!   DBS Altitude: 3.25
+//!   Trust: 0
+//! expenses:
+//!   - expense: Flights to Tokyo
+//!     date: 2026-11-02
+//!     category: travel
+//!     payment_category: online
+//!     amount: 1200
+//!   - expense: Ryokan
+//!     date: 2026-11-05
+//!     category: travel
+//!     payment_category: contactless
+//!     amount: 640
+//!     foreign_amount: 68000   # in yen, for cards earning per foreign block
+//!     merchant: Hoshinoya
+//! ```
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::engine::round_cents;
+use crate::error;
+use crate::import::parse_date_with_format;
+use crate::models::{PlannedSpend, RecommendOptions, TripLeg};
+use crate::store::Store;
+
+/// What a mile is worth, in dollars, when the trip file does not say
+pub const DEFAULT_MILE_VALUE: f64 = 0.02;
+
+fn default_mile_value() -> f64 {
+    DEFAULT_MILE_VALUE
+}
+
+/// A trip file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trip {
+    #[serde(default = "default_mile_value")]
+    pub mile_value: f64,
+    /// Foreign transaction fee per card name, in percent; cards left out
+    /// charge none
+    #[serde(default)]
+    pub fx_fees: BTreeMap<String, f64>,
+    pub expenses: Vec<TripExpense>,
+}
+
+/// One upcoming expense, in the home currency
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TripExpense {
+    pub expense: String,
+    pub date: String,
+    pub category: String,
+    pub payment_category: String,
+    pub amount: f64,
+    /// The amount in the currency it is charged in, for cards that count
+    /// blocks in that currency
+    #[serde(default)]
+    pub foreign_amount: Option<f64>,
+    #[serde(default)]
+    pub merchant: Option<String>,
+}
+
+/// Reads a trip file, picking JSON or YAML by its extension.
+pub fn read(path: &Path) -> Result<Trip, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("json") => parse(&text, false),
+        Some("yaml" | "yml") => parse(&text, true),
+        _ => Err(format!("Trip files must be .json, .yaml or .yml: {}", path.display())),
+    }
+}
+
+/// Parses and validates a trip file, listing every problem found, e.g.
+/// `expenses[1].amount: must be positive`.
+pub fn parse(text: &str, yaml: bool) -> Result<Trip, String> {
+    let trip: Trip = if yaml {
+        serde_yaml::from_str(text).map_err(|e| format!("Invalid trip file: {}", e))?
+    } else {
+        serde_json::from_str(text).map_err(|e| format!("Invalid trip file: {}", e))?
+    };
+    let is_date = |value: &str| parse_date_with_format(value, "YYYY-MM-DD").as_deref() == Some(value);
+    let mut problems = Vec::new();
+    if trip.mile_value < 0.0 {
+        problems.push("mile_value: must not be negative".to_string());
+    }
+    for (card, fee) in &trip.fx_fees {
+        if !(0.0..=100.0).contains(fee) {
+            problems.push(format!("fx_fees.{}: must be a percentage from 0 to 100", card));
+        }
+    }
+    if trip.expenses.is_empty() {
+        problems.push("expenses: must list at least one expense".to_string());
+    }
+    for (i, expense) in trip.expenses.iter().enumerate() {
+        if !is_date(&expense.date) {
+            problems.push(format!("expenses[{}].date: \"{}\" is not a YYYY-MM-DD date", i, expense.date));
+        }
+        if expense.amount <= 0.0 {
+            problems.push(format!("expenses[{}].amount: must be positive", i));
+        }
+        if expense.foreign_amount.is_some_and(|amount| amount <= 0.0) {
+            problems.push(format!("expenses[{}].foreign_amount: must be positive", i));
+        }
+    }
+    if problems.is_empty() {
+        Ok(trip)
+    } else {
+        Err(format!("Invalid trip file:\n  {}", problems.join("\n  ")))
+    }
+}
+
+/// Picks a card for each expense in date order. Each card is worth its
+/// miles at the trip's mile value, plus any cashback, less its FX fee, and every expense
+/// planned so far counts towards the cycle totals of the next, as does
+/// spending already scheduled.
+pub fn plan<S: Store + ?Sized>(store: &S, trip: &Trip) -> error::Result<Vec<TripLeg>> {
+    let mut expenses: Vec<&TripExpense> = trip.expenses.iter().collect();
+    expenses.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut planned = Vec::new();
+    let mut legs = Vec::new();
+    for expense in expenses {
+        let options = RecommendOptions {
+            include_scheduled: true,
+            merchant: expense.merchant.clone(),
+            mile_value: Some(trip.mile_value),
+            foreign_amount: expense.foreign_amount,
+            planned: planned.clone(),
+            ..RecommendOptions::default()
+        };
+        let recommendations = store.best_card_for_category(
+            &expense.category,
+            expense.amount,
+            &expense.payment_category,
+            &expense.date,
+            &options,
+        )?;
+        let fee = |card_name: &str| expense.amount * trip.fx_fees.get(card_name).copied().unwrap_or(0.0) / 100.0;
+        let best = recommendations
+            .iter()
+            .filter(|r| r.eligible)
+            .map(|r| (r, r.value.unwrap_or(0.0) - fee(&r.card_name)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut leg = TripLeg {
+            date: expense.date.clone(),
+            expense: expense.expense.clone(),
+            category: expense.category.clone(),
+            amount: expense.amount,
+            card_name: None,
+            miles: 0.0,
+            fx_fee: 0.0,
+            net_value: 0.0,
+            note: None,
+        };
+        match best {
+            Some((card, net_value)) => {
+                planned.push(PlannedSpend {
+                    card_id: card.card_id,
+                    date: expense.date.clone(),
+                    amount: expense.amount,
+                    category: expense.category.clone(),
+                    miles: card.miles_earned,
+                });
+                leg.card_name = Some(card.card_name.clone());
+                leg.miles = card.miles_earned;
+                leg.fx_fee = round_cents(fee(&card.card_name));
+                leg.net_value = round_cents(net_value);
+            }
+            None => {
+                leg.note = Some(match recommendations.first() {
+                    Some(top) => format!("{}: {}", top.card_name, top.reason),
+                    None => format!("No cards match '{}' with '{}'", expense.category, expense.payment_category),
+                });
+            }
+        }
+        legs.push(leg);
+    }
+    Ok(legs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    const TRIP: &str = "\
+fx_fees:
+  Miles: 3.25
+expenses:
+  - expense: Hotel
+    date: 2026-05-20
+    category: travel
+    payment_category: online
+    amount: 300
+  - expense: Flights
+    date: 2026-05-04
+    category: travel
+    payment_category: online
+    amount: 900
+  - expense: Museum
+    date: 2026-05-21
+    category: entertainment
+    payment_category: online
+    amount: 20
+";
+
+    #[test]
+    fn test_plan_weighs_fx_fees_and_fills_caps() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        for (name, miles_per_dollar, max_reward_limit) in [("Miles", 4.0, Some(1000.0)), ("NoFee", 1.5, None)] {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    categories: vec!["travel".to_string()],
+                    payment_categories: vec!["online".to_string()],
+                    miles_per_dollar,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    max_reward_limit,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        let legs = plan(&conn, &parse(TRIP, true).unwrap()).unwrap();
+        let expenses: Vec<&str> = legs.iter().map(|l| l.expense.as_str()).collect();
+        assert_eq!(expenses, ["Flights", "Hotel", "Museum"]);
+        // 3600 miles are worth $72, well over the $29.25 fee
+        assert_eq!(legs[0].card_name.as_deref(), Some("Miles"));
+        assert_eq!((legs[0].miles, legs[0].fx_fee, legs[0].net_value), (3600.0, 29.25, 42.75));
+        // The flights leave $100 of Miles' cap for the hotel
+        assert_eq!(legs[1].card_name.as_deref(), Some("NoFee"));
+        assert_eq!((legs[1].miles, legs[1].fx_fee), (450.0, 0.0));
+        assert_eq!(legs[2].card_name, None);
+        assert_eq!(legs[2].note.as_deref(), Some("No cards match 'entertainment' with 'online'"));
+    }
+
+    #[test]
+    fn test_parse_reports_every_problem() {
+        let text = r#"{"fx_fees": {"Miles": 120}, "expenses": [
+            {"expense": "Hotel", "date": "2026-5-20", "category": "travel", "payment_category": "online", "amount": 0}
+        ]}"#;
+        assert_eq!(
+            parse(text, false).unwrap_err(),
+            "Invalid trip file:\n  fx_fees.Miles: must be a percentage from 0 to 100\n  \
+             expenses[0].date: \"2026-5-20\" is not a YYYY-MM-DD date\n  expenses[0].amount: must be positive"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Spend velocity: rolling 7- and 30-day spend per card or per category,
+//! with a trend arrow and, for cards, where the current statement cycle is
+//! heading against the minimum spend and reward cap.
+
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+use crate::db::STATUS_POSTED;
+use crate::engine::{add_days, card_cycle, days_between, round_cents};
+use crate::error::Result;
+use crate::models::{CapUnit, Card, SpendVelocity};
+use crate::store::Store;
+
+/// How far the 7-day pace may stray from the 30-day pace, as a share of
+/// it, before the trend shows an arrow up or down
+const TREND_BAND: f64 = 0.1;
+
+/// What the report has one row per
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VelocityBy {
+    Card,
+    Category,
+}
+
+fn trend(per_day_7: f64, per_day_30: f64) -> &'static str {
+    if per_day_7 > per_day_30 * (1.0 + TREND_BAND) {
+        "↑"
+    } else if per_day_7 < per_day_30 * (1.0 - TREND_BAND) {
+        "↓"
+    } else {
+        "→"
+    }
+}
+
+/// Posted spending in the 7 and 30 days up to and including `date`
+#[derive(Default)]
+struct Window {
+    last_7_days: f64,
+    last_30_days: f64,
+}
+
+impl Window {
+    fn row(&self, name: String) -> SpendVelocity {
+        let per_day_7 = self.last_7_days / 7.0;
+        let per_day_30 = self.last_30_days / 30.0;
+        SpendVelocity {
+            name,
+            last_7_days: round_cents(self.last_7_days),
+            last_30_days: round_cents(self.last_30_days),
+            per_day_7: round_cents(per_day_7),
+            per_day_30: round_cents(per_day_30),
+            trend: trend(per_day_7, per_day_30).to_string(),
+            cycle_spent: None,
+            projected: None,
+            pace: None,
+        }
+    }
+}
+
+/// Describes a projected cycle spend against the card's minimum spend and
+/// cap, or None when the card has neither. `excluded` is the spend so far
+/// that does not count towards the minimum.
+fn pace(card: &Card, spent: f64, excluded: f64, projected: f64) -> Option<String> {
+    let mut notes = Vec::new();
+    if let Some(minimum) = card.min_spend {
+        notes.push(if spent - excluded >= minimum {
+            "min spend met".to_string()
+        } else if projected - excluded >= minimum {
+            "on pace for min spend".to_string()
+        } else {
+            format!("${:.2} short of min spend", minimum - (projected - excluded))
+        });
+    }
+    // A cap in miles is reached at the spend that earns them at the card's rate
+    let cap = match card.cap_unit {
+        CapUnit::Dollars => card.max_reward_limit,
+        CapUnit::Miles if card.miles_per_dollar > 0.0 => {
+            card.max_reward_limit.map(|miles| round_cents(miles / card.miles_per_dollar * card.block_size))
+        }
+        CapUnit::Miles => None,
+    };
+    if let Some(cap) = cap {
+        notes.push(if projected > cap {
+            format!("overshoots cap by ${:.2}", projected - cap)
+        } else {
+            "within cap".to_string()
+        });
+    }
+    (!notes.is_empty()).then(|| notes.join("; "))
+}
+
+/// Builds the report for `date`, one row per card or per category with
+/// spending in the last 30 days (every card is listed with `VelocityBy::Card`).
+pub fn report<S: Store + ?Sized>(store: &S, date: &str, by: VelocityBy) -> Result<Vec<SpendVelocity>> {
+    let cards = store.list_cards()?;
+    let week_start = add_days(date, -6);
+    let month_start = add_days(date, -29);
+    let cycle_starts: Vec<String> = cards.iter().map(|c| card_cycle(c, date).0).collect();
+    let earliest = cycle_starts.iter().min().map_or(month_start.clone(), |start| start.clone().min(month_start.clone()));
+
+    let mut by_card: BTreeMap<i64, Window> = BTreeMap::new();
+    let mut by_category: BTreeMap<String, Window> = BTreeMap::new();
+    let mut cycle_spent: BTreeMap<i64, f64> = BTreeMap::new();
+    let mut cycle_excluded: BTreeMap<i64, f64> = BTreeMap::new();
+    let exclusions: Vec<Vec<String>> = cards.iter().map(|c| c.excluded_from_min_spend()).collect();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status != STATUS_POSTED || s.date < earliest || s.date.as_str() > date {
+            return Ok(());
+        }
+        if let Some(i) = cards.iter().position(|c| c.id == s.card_id)
+            && s.date >= cycle_starts[i]
+        {
+            *cycle_spent.entry(s.card_id).or_default() += s.amount;
+            if exclusions[i].contains(&s.category.to_lowercase()) {
+                *cycle_excluded.entry(s.card_id).or_default() += s.amount;
+            }
+        }
+        if s.date < month_start {
+            return Ok(());
+        }
+        for window in [by_card.entry(s.card_id).or_default(), by_category.entry(s.category.clone()).or_default()] {
+            window.last_30_days += s.amount;
+            if s.date >= week_start {
+                window.last_7_days += s.amount;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(match by {
+        VelocityBy::Card => cards
+            .iter()
+            .map(|card| {
+                let window = by_card.remove(&card.id).unwrap_or_default();
+                let spent = cycle_spent.get(&card.id).copied().unwrap_or(0.0);
+                let days_left = days_between(date, &card_cycle(card, date).1);
+                let projected = spent + window.last_30_days / 30.0 * days_left as f64;
+                SpendVelocity {
+                    cycle_spent: Some(round_cents(spent)),
+                    projected: Some(round_cents(projected)),
+                    pace: pace(card, spent, cycle_excluded.get(&card.id).copied().unwrap_or(0.0), projected),
+                    ..window.row(card.name.clone())
+                }
+            })
+            .collect(),
+        VelocityBy::Category => by_category.into_iter().map(|(category, window)| window.row(category)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::models::NewCard;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_velocity_projects_cycle_against_min_spend_and_cap() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let card = |name: &str, max_reward_limit, min_spend| {
+            db::add_card(
+                &conn,
+                &NewCard {
+                    name: name.to_string(),
+                    categories: vec!["dining".to_string(), "groceries".to_string()],
+                    payment_categories: vec!["contactless".to_string()],
+                    miles_per_dollar: 1.0,
+                    block_size: 1.0,
+                    statement_renewal_date: 1,
+                    max_reward_limit,
+                    min_spend,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+        };
+        let fast = card("Fast", Some(1000.0), None);
+        let slow = card("Slow", None, Some(1500.0));
+        // A quiet month, then a busy last week
+        db::add_spending(&conn, fast, 150.0, "dining", "2026-04-01", None, false, None).unwrap();
+        db::add_spending(&conn, fast, 600.0, "dining", "2026-04-12", None, false, None).unwrap();
+        db::add_spending(&conn, slow, 300.0, "groceries", "2026-03-20", None, false, None).unwrap();
+        db::add_spending(&conn, slow, 150.0, "groceries", "2026-04-02", None, false, None).unwrap();
+        db::schedule_spending(&conn, slow, 900.0, "groceries", "2026-04-14", None, false, None).unwrap();
+
+        let rows = report(&conn, "2026-04-15", VelocityBy::Card).unwrap();
+        assert_eq!(rows[0].name, "Fast");
+        assert_eq!((rows[0].last_7_days, rows[0].last_30_days, rows[0].trend.as_str()), (600.0, 750.0, "↑"));
+        // $750 spent, $25 a day for the 15 days left
+        assert_eq!(rows[0].projected, Some(1125.0));
+        assert_eq!(rows[0].pace.as_deref(), Some("overshoots cap by $125.00"));
+        assert_eq!((rows[1].last_7_days, rows[1].trend.as_str()), (0.0, "↓"));
+        assert_eq!(rows[1].cycle_spent, Some(150.0));
+        assert_eq!(rows[1].pace.as_deref(), Some("$1125.00 short of min spend"));
+
+        let rows = report(&conn, "2026-04-15", VelocityBy::Category).unwrap();
+        let names: Vec<&str> = rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["dining", "groceries"]);
+        assert_eq!(rows[1].last_30_days, 450.0);
+    }
+}
@@ -0,0 +1,204 @@
+//! `what-if`: replays past spending against a card that is not in the
+//! database, to see what it would have earned before applying for it.
+//! Nothing is written; the card's rules are applied in memory.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::db::STATUS_POSTED;
+use crate::engine::{self, round_cents, CandidateCard, CycleState, Purchase};
+use crate::error::Result;
+use crate::models::{NewCard, WhatIfCategory};
+use crate::period::DateRange;
+use crate::store::Store;
+
+/// What a hypothetical card would have earned on past spending
+#[derive(Debug, Clone, Default)]
+pub struct WhatIf {
+    /// One row per category the card covers, most spent first
+    pub categories: Vec<WhatIfCategory>,
+    /// Spend in categories or paid in ways the card does not cover
+    pub uncovered: f64,
+    /// Statement cycles whose spend fell short of the minimum, earning nothing
+    pub missed_min_spend: usize,
+}
+
+impl WhatIf {
+    pub fn miles(&self) -> f64 {
+        self.categories.iter().map(|c| c.miles).sum()
+    }
+
+    pub fn actual_miles(&self) -> f64 {
+        self.categories.iter().map(|c| c.actual_miles).sum()
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.categories.iter().map(|c| c.spent).sum()
+    }
+}
+
+/// The card as the recommendation engine sees it, before any purchase.
+fn candidate(card: &NewCard) -> CandidateCard {
+    CandidateCard {
+        id: 0,
+        name: card.name.clone(),
+        miles_per_dollar: card.miles_per_dollar,
+        block_size: card.block_size,
+        max_reward_limit: card.max_reward_limit,
+        cap_unit: card.cap_unit,
+        // Applied per cycle once the cycle is over; see `replay`
+        min_spend: None,
+        min_spend_exclusions: card.min_spend_exclusions.iter().map(|c| c.to_lowercase()).collect(),
+        statement_renewal_date: card.statement_renewal_date,
+        annual_fee: card.annual_fee,
+        reward_type: card.reward_type,
+        cashback_percent: card.cashback_percent,
+        foreign_blocks: card.foreign_blocks,
+        miles_per_dollar_foreign: card.miles_per_dollar_foreign,
+        foreign_rate: false,
+        fx_fee_percent: card.fx_fee_percent,
+        excluded_category: false,
+        historical_rates: false,
+        partnership: None,
+        promotion: None,
+    }
+}
+
+/// Replays the posted spending in `range` (all of it without one), in date
+/// order, as if `card` had been used for every purchase it covers: those in
+/// its categories, paid a way it takes when the way is known. Caps fill up
+/// cycle by cycle as they would have. A minimum spend is judged on the whole
+/// cycle, so a cycle that fell short of it earns nothing.
+pub fn replay<S: Store + ?Sized>(store: &S, card: &NewCard, range: Option<&DateRange>) -> Result<WhatIf> {
+    let mut spending = Vec::new();
+    store.for_each_spending(None, None, &mut |s| {
+        if s.status == STATUS_POSTED && range.is_none_or(|r| r.from <= s.date && s.date <= r.to) {
+            spending.push(s);
+        }
+        Ok(())
+    })?;
+    spending.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+
+    let covers = |names: &[String], name: &str| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+    let base = candidate(card);
+    let mut whatif = WhatIf::default();
+    let mut cycles: HashMap<String, CycleState> = HashMap::new();
+    // Each covered purchase's category, cycle, spend, miles, cashback and actual miles
+    let mut earned = Vec::new();
+    for s in &spending {
+        let paid_ok = s.payment_category.as_deref().is_none_or(|p| covers(&card.payment_categories, p));
+        if !covers(&card.categories, &s.category) || !paid_ok {
+            whatif.uncovered += s.amount;
+            continue;
+        }
+        let (start, _) = engine::cycle_bounds(card.statement_renewal_date, &BTreeMap::new(), &s.date);
+        let cycle = cycles.entry(start.clone()).or_default();
+        let mut candidate = base.clone();
+        candidate.excluded_category = covers(&card.excluded_categories, &s.category);
+        if s.foreign {
+            candidate.use_foreign_rate();
+        }
+        let purchase = Purchase {
+            category: &s.category,
+            payment_category: s.payment_category.as_deref().unwrap_or(""),
+            amount: s.amount,
+            merchant: None,
+            foreign_amount: s.original_amount.filter(|_| s.foreign),
+            foreign: s.foreign,
+        };
+        let recommendation = engine::recommend(&candidate, &purchase, cycle, None, None);
+        let (miles, cashback) = if recommendation.eligible {
+            (recommendation.miles_earned, recommendation.cashback.unwrap_or(0.0))
+        } else {
+            (0.0, 0.0)
+        };
+        cycle.spent += s.amount;
+        cycle.miles += miles;
+        if candidate.min_spend_exclusions.contains(&s.category.to_lowercase()) {
+            cycle.excluded += s.amount;
+        }
+        earned.push((s, start, miles, cashback));
+    }
+
+    let missed: HashSet<&String> = cycles
+        .iter()
+        .filter(|(_, cycle)| card.min_spend.is_some_and(|minimum| cycle.spent - cycle.excluded < minimum))
+        .map(|(start, _)| start)
+        .collect();
+    whatif.missed_min_spend = missed.len();
+    // Keyed in lower case, with the name first seen
+    let mut by_category: BTreeMap<String, WhatIfCategory> = BTreeMap::new();
+    for (s, start, miles, cashback) in earned {
+        let row = by_category.entry(s.category.to_lowercase()).or_insert_with(|| WhatIfCategory {
+            category: s.category.clone(),
+            ..WhatIfCategory::default()
+        });
+        let met = !missed.contains(&start);
+        row.transactions += 1;
+        row.spent += s.amount;
+        row.miles += if met { miles } else { 0.0 };
+        row.cashback += if met { cashback } else { 0.0 };
+        row.actual_miles += s.miles_earned;
+    }
+    whatif.categories = by_category
+        .into_values()
+        .map(|row| WhatIfCategory { spent: round_cents(row.spent), cashback: round_cents(row.cashback), ..row })
+        .collect();
+    whatif.categories.sort_by(|a, b| b.spent.total_cmp(&a.spent));
+    Ok(whatif)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_replay_fills_caps_and_judges_min_spend_per_cycle() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_tables(&conn).unwrap();
+        let existing = db::add_card(
+            &conn,
+            &NewCard {
+                name: "Everyday".to_string(),
+                categories: vec!["dining".to_string(), "travel".to_string(), "bills".to_string()],
+                payment_categories: vec!["online".to_string()],
+                miles_per_dollar: 1.0,
+                block_size: 1.0,
+                statement_renewal_date: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        db::add_spending(&conn, existing, 300.0, "dining", "2026-09-03", None, false, None).unwrap();
+        db::add_spending(&conn, existing, 800.0, "travel", "2026-09-10", None, false, None).unwrap();
+        db::add_spending(&conn, existing, 200.0, "Dining", "2026-09-12", None, false, None).unwrap();
+        db::add_spending(&conn, existing, 90.0, "bills", "2026-09-14", None, false, None).unwrap();
+        db::add_spending(&conn, existing, 45.0, "dining", "2026-10-05", None, false, None).unwrap();
+        let hypothetical = NewCard {
+            name: "Hypothetical".to_string(),
+            categories: vec!["dining".to_string(), "travel".to_string()],
+            payment_categories: vec!["online".to_string()],
+            miles_per_dollar: 3.0,
+            block_size: 1.0,
+            statement_renewal_date: 1,
+            max_reward_limit: Some(1000.0),
+            min_spend: Some(500.0),
+            ..Default::default()
+        };
+
+        let whatif = replay(&conn, &hypothetical, None).unwrap();
+        // The travel purchase goes past the cap, as does all spending after
+        // it that cycle, earning nothing; October falls short of the minimum
+        let rows: Vec<_> = whatif.categories.iter().map(|c| (c.category.as_str(), c.transactions, c.miles)).collect();
+        assert_eq!(rows, vec![("travel", 1, 0.0), ("dining", 3, 900.0)]);
+        assert_eq!((whatif.uncovered, whatif.missed_min_spend), (90.0, 1));
+        assert_eq!((whatif.spent(), whatif.actual_miles()), (1345.0, 1345.0));
+
+        let october = DateRange { from: "2026-10-01".to_string(), to: "2026-10-31".to_string() };
+        let whatif = replay(&conn, &NewCard { min_spend: None, ..hypothetical }, Some(&october)).unwrap();
+        assert_eq!(whatif.miles(), 135.0);
+        // Nothing was written
+        assert_eq!(db::list_cards(&conn).unwrap().len(), 1);
+    }
+}